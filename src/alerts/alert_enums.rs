@@ -20,16 +20,19 @@ use std::fmt::{self, Display};
 
 use chrono::{DateTime, Utc};
 use derive_more::derive::FromStr;
+use tokio::sync::oneshot;
 use ulid::Ulid;
 
 use crate::alerts::{
-    alert_structs::{AnomalyConfig, ForecastConfig, RollingWindow},
+    alert_structs::{AnomalyConfig, ForecastConfig, RollingWindow, ScheduledAlertTask},
     alert_traits::AlertTrait,
 };
 
 pub enum AlertTask {
     Create(Box<dyn AlertTrait>),
     Delete(Ulid),
+    /// Lists the scheduled tasks, sent back over the channel
+    List(oneshot::Sender<Vec<ScheduledAlertTask>>),
 }
 
 #[derive(Default, Debug, serde::Serialize, serde::Deserialize, Clone)]
@@ -146,6 +149,38 @@ impl Display for AlertOperator {
     }
 }
 
+/// How the aggregates of a threshold alert query are turned into the value compared against the threshold
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum AggregateMode {
+    /// The query has a single aggregate which is compared as is
+    #[default]
+    Single,
+    /// The query has two aggregates, the first one is divided by the second one
+    Ratio,
+}
+
+/// Outcome of a ratio alert whose denominator is zero
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum ZeroDenominator {
+    #[default]
+    NoTrigger,
+    Trigger,
+}
+
+/// Evaluation of `is null` and `is not null` checks on columns missing from the schema
+/// of the queried streams, as with columns a dynamic-schema stream hasn't received yet
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum MissingColumns {
+    /// the query fails, as with any reference to an unknown column
+    #[default]
+    Error,
+    /// the column is all-null, `is null` matches every row and `is not null` none
+    TreatAsNull,
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, FromStr, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub enum WhereConfigOperator {
@@ -179,6 +214,18 @@ pub enum WhereConfigOperator {
     DoesNotBeginWith,
     #[serde(rename = "does not end with")]
     DoesNotEndWith,
+    /// Matches a regular expression. Unlike the other operators,
+    /// it can't be used to prune parquet files with their column statistics,
+    /// so every file in the time range is scanned
+    #[serde(rename = "matches regex")]
+    Regex,
+    #[serde(rename = "does not match regex")]
+    NotRegex,
+    /// Matches any value of a list, given as comma separated values or a JSON array
+    #[serde(rename = "in")]
+    In,
+    #[serde(rename = "not in")]
+    NotIn,
 }
 
 impl WhereConfigOperator {
@@ -200,6 +247,10 @@ impl WhereConfigOperator {
             Self::DoesNotContain => "does not contain",
             Self::DoesNotBeginWith => "does not begin with",
             Self::DoesNotEndWith => "does not end with",
+            Self::Regex => "matches regex",
+            Self::NotRegex => "does not match regex",
+            Self::In => "in",
+            Self::NotIn => "not in",
         }
     }
 }
@@ -261,6 +312,16 @@ pub enum AlertState {
     #[serde(rename = "not-triggered")]
     NotTriggered,
     Disabled,
+    /// the stream queried by the alert was deleted
+    #[serde(rename = "stream-missing")]
+    StreamMissing,
+}
+
+impl AlertState {
+    /// Alerts in a paused state have no scheduled evaluation task
+    pub fn is_paused(&self) -> bool {
+        matches!(self, AlertState::Disabled | AlertState::StreamMissing)
+    }
 }
 
 impl Display for AlertState {
@@ -269,6 +330,7 @@ impl Display for AlertState {
             AlertState::Triggered => write!(f, "triggered"),
             AlertState::Disabled => write!(f, "disabled"),
             AlertState::NotTriggered => write!(f, "not-triggered"),
+            AlertState::StreamMissing => write!(f, "stream-missing"),
         }
     }
 }
@@ -0,0 +1,121 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Results of the scheduled evaluations of the alerts recording them, ingested as events of the
+//! `--alert-results-stream` internal stream, to be queried, charted or alerted on.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::{
+    event::format::{LogSource, LogSourceEntry},
+    handlers::{TelemetryType, http::ingest::ingest_internal_stream},
+    parseable::PARSEABLE,
+    storage::StreamType,
+    utils::time::TimeRange,
+};
+
+use super::{AlertError, alert_structs::AlertQueryResult, alert_traits::AlertTrait};
+
+/// Groups recorded per evaluation, the results of a query grouping by a high cardinality
+/// field would otherwise turn every evaluation into a large batch of events
+pub const MAX_RECORDED_GROUPS: usize = 100;
+
+/// An evaluation of an alert, one per group for queries with a `GROUP BY`
+#[derive(Debug, Serialize)]
+struct AlertResultEvent<'a> {
+    alert_id: String,
+    title: &'a str,
+    /// `k1=v1,k2=v2` values of the group, unset for queries without a `GROUP BY`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    /// null for a ratio with a zero denominator
+    value: f64,
+    state: &'static str,
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+    timestamp: DateTime<Utc>,
+}
+
+/// Ingests the result of an evaluation of the alert over `window`
+pub async fn record_alert_results(
+    alert: &dyn AlertTrait,
+    result: &AlertQueryResult,
+    window: &TimeRange,
+) -> Result<(), AlertError> {
+    let stream_name = &PARSEABLE.options.alert_results_stream;
+    if result.groups.len() > MAX_RECORDED_GROUPS {
+        warn!(
+            "alert {} returned {} groups, only the first {MAX_RECORDED_GROUPS} are recorded",
+            alert.get_id(),
+            result.groups.len()
+        );
+    }
+
+    let timestamp = Utc::now();
+    let threshold_config = alert.get_threshold_config();
+    let events = result
+        .groups
+        .iter()
+        .take(MAX_RECORDED_GROUPS)
+        .map(|group| AlertResultEvent {
+            alert_id: alert.get_id().to_string(),
+            title: alert.get_title(),
+            group: (!group.group_values.is_empty()).then(|| {
+                group
+                    .group_values
+                    .iter()
+                    .sorted()
+                    .map(|(key, value)| format!("{key}={value}"))
+                    .join(",")
+            }),
+            value: group.aggregate_value,
+            state: match threshold_config.has_enough_samples(group)
+                && threshold_config.is_breached(group.aggregate_value)
+            {
+                true => "triggered",
+                false => "not-triggered",
+            },
+            window_start: window.start,
+            window_end: window.end,
+            timestamp,
+        })
+        .collect::<Vec<_>>();
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    PARSEABLE
+        .create_stream_if_not_exists(
+            stream_name,
+            StreamType::Internal,
+            None,
+            vec![LogSourceEntry::new(LogSource::Json, HashSet::new())],
+            TelemetryType::Logs,
+        )
+        .await
+        .map_err(|err| AlertError::CustomError(err.to_string()))?;
+    let body = serde_json::to_vec(&events)?;
+    ingest_internal_stream(stream_name.clone(), body.into())
+        .await
+        .map_err(|err| AlertError::CustomError(err.to_string()))
+}
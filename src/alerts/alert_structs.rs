@@ -28,11 +28,13 @@ use crate::{
     alerts::{
         AlertError, CURRENT_ALERTS_VERSION,
         alert_enums::{
-            AlertOperator, AlertState, AlertTask, AlertType, AlertVersion, EvalConfig,
-            LogicalOperator, NotificationState, Severity, WhereConfigOperator,
+            AggregateMode, AlertOperator, AlertState, AlertTask, AlertType, AlertVersion,
+            EvalConfig, LogicalOperator, MissingColumns, NotificationState, Severity,
+            WhereConfigOperator, ZeroDenominator,
         },
         alert_traits::AlertTrait,
-        target::{NotificationConfig, TARGETS},
+        alerts_utils::{evaluate_condition, parse_value_list},
+        target::{NotificationConfig, TARGETS, TargetCheck},
     },
     metastore::metastore_traits::MetastoreObject,
     query::resolve_stream_names,
@@ -66,6 +68,9 @@ const RESERVED_FIELDS: &[&str] = &[
     "tags",
     "lastTriggeredAt",
     "last_triggered_at",
+    "warnings",
+    "targetChecks",
+    "target_checks",
 ];
 
 /// Helper struct for basic alert fields during migration
@@ -79,6 +84,8 @@ pub struct BasicAlertFields {
 pub struct Alerts {
     pub alerts: RwLock<HashMap<Ulid, Box<dyn AlertTrait>>>,
     pub sender: mpsc::Sender<AlertTask>,
+    /// alerts that could not be scheduled, with the reason
+    pub failed_tasks: RwLock<HashMap<Ulid, String>>,
 }
 
 #[derive(Debug, Clone)]
@@ -189,46 +196,38 @@ pub struct Conditions {
     pub condition_config: Vec<ConditionConfig>,
 }
 
+impl ConditionConfig {
+    fn message(&self) -> String {
+        match (
+            &self.operator,
+            self.value.as_ref().filter(|v| !v.is_empty()),
+        ) {
+            (WhereConfigOperator::In | WhereConfigOperator::NotIn, Some(value)) => {
+                match parse_value_list(value) {
+                    Ok(values) => {
+                        format!("{} {} ({})", self.column, self.operator, values.join(", "))
+                    }
+                    Err(_) => format!("{} {} {value}", self.column, self.operator),
+                }
+            }
+            (_, Some(value)) => format!("{} {} {value}", self.column, self.operator),
+            (_, None) => format!("{} {}", self.column, self.operator),
+        }
+    }
+}
+
 impl Conditions {
     pub fn generate_filter_message(&self) -> String {
         match &self.operator {
             Some(op) => match op {
                 LogicalOperator::And | LogicalOperator::Or => {
-                    let expr1 = &self.condition_config[0];
-                    let expr2 = &self.condition_config[1];
-                    let expr1_msg = if expr1.value.as_ref().is_some_and(|v| !v.is_empty()) {
-                        format!(
-                            "{} {} {}",
-                            expr1.column,
-                            expr1.operator,
-                            expr1.value.as_ref().unwrap()
-                        )
-                    } else {
-                        format!("{} {}", expr1.column, expr1.operator)
-                    };
-
-                    let expr2_msg = if expr2.value.as_ref().is_some_and(|v| !v.is_empty()) {
-                        format!(
-                            "{} {} {}",
-                            expr2.column,
-                            expr2.operator,
-                            expr2.value.as_ref().unwrap()
-                        )
-                    } else {
-                        format!("{} {}", expr2.column, expr2.operator)
-                    };
+                    let expr1_msg = self.condition_config[0].message();
+                    let expr2_msg = self.condition_config[1].message();
 
                     format!("[{expr1_msg} {op} {expr2_msg}]")
                 }
             },
-            None => {
-                let expr = &self.condition_config[0];
-                if let Some(val) = &expr.value {
-                    format!("{} {} {}", expr.column, expr.operator, val)
-                } else {
-                    format!("{} {}", expr.column, expr.operator)
-                }
-            }
+            None => self.condition_config[0].message(),
         }
     }
 }
@@ -244,6 +243,35 @@ pub struct GroupBy {
 pub struct ThresholdConfig {
     pub operator: AlertOperator,
     pub value: f64,
+    #[serde(default)]
+    pub aggregate_mode: AggregateMode,
+    /// only used in ratio mode
+    #[serde(default)]
+    pub zero_denominator: ZeroDenominator,
+    #[serde(default)]
+    pub missing_columns: MissingColumns,
+    /// rows a group needs to aggregate for its value to be compared to the threshold,
+    /// groups with fewer rows don't trigger the alert
+    #[serde(default)]
+    pub min_sample_count: i64,
+}
+
+impl ThresholdConfig {
+    /// Whether `value` breaches the threshold, a NaN value stands for a ratio with a zero denominator
+    pub fn is_breached(&self, value: f64) -> bool {
+        if value.is_nan() {
+            return self.zero_denominator == ZeroDenominator::Trigger;
+        }
+        evaluate_condition(&self.operator, value, self.value)
+    }
+
+    /// Whether the group aggregates enough rows to be evaluated
+    pub fn has_enough_samples(&self, group: &GroupResult) -> bool {
+        self.min_sample_count <= 0
+            || group
+                .sample_count
+                .is_some_and(|count| count >= self.min_sample_count as u64)
+    }
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
@@ -255,6 +283,9 @@ pub struct RollingWindow {
     pub eval_end: String,
     // x minutes (5m)
     pub eval_frequency: u64,
+    /// ingest the result of every scheduled evaluation into `--alert-results-stream`
+    #[serde(default)]
+    pub record_results: bool,
 }
 
 impl Default for RollingWindow {
@@ -263,6 +294,7 @@ impl Default for RollingWindow {
             eval_start: "10m".into(),
             eval_end: "now".into(),
             eval_frequency: 10,
+            record_results: false,
         }
     }
 }
@@ -375,6 +407,134 @@ impl AlertRequest {
     }
 }
 
+/// Portable form of an alert, without its id and runtime state, used by the alert export
+impl From<AlertConfig> for AlertRequest {
+    fn from(config: AlertConfig) -> Self {
+        let (alert_type, anomaly_config, forecast_config) = match config.alert_type {
+            AlertType::Threshold => ("threshold", None, None),
+            AlertType::Anomaly(conf) => ("anomaly", Some(conf), None),
+            AlertType::Forecast(conf) => ("forecast", None, Some(conf)),
+        };
+
+        Self {
+            severity: config.severity,
+            title: config.title,
+            query: config.query,
+            alert_type: alert_type.to_string(),
+            anomaly_config,
+            forecast_config,
+            threshold_config: config.threshold_config,
+            notification_config: config.notification_config,
+            eval_config: config.eval_config,
+            targets: config.targets,
+            tags: config.tags,
+            other_fields: config.other_fields,
+        }
+    }
+}
+
+/// Bundle of alert definitions, as returned by the export and accepted by the import
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertsBundle {
+    pub alerts: Vec<AlertRequest>,
+    /// Stream names referenced by the imported alert queries to replace, old name -> new name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub stream_mapping: HashMap<String, String>,
+}
+
+#[derive(Debug, serde::Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AlertImportStatus {
+    Created,
+    /// an alert with the same title already exists on the dataset
+    Conflict,
+    Failed,
+}
+
+/// Outcome of importing a single alert of a bundle
+#[derive(Debug, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertImportResult {
+    pub title: String,
+    pub status: AlertImportStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Ulid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Alerts to evaluate with `POST /alerts/evaluate-batch`,
+/// all the alerts the user has access to are evaluated when no ids are given
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluateBatchRequest {
+    #[serde(default)]
+    pub alert_ids: Option<Vec<Ulid>>,
+}
+
+/// Outcome of evaluating a single alert of a batch
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertEvaluationResult {
+    pub id: Ulid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<AlertState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AlertEvaluationResult {
+    pub fn failed(id: Ulid, error: impl ToString) -> Self {
+        Self {
+            id,
+            value: None,
+            state: None,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+/// Evaluation task of an alert, as scheduled on the alert runtime
+#[derive(Debug, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledAlertTask {
+    pub id: Ulid,
+    /// minutes between two evaluations
+    pub eval_frequency: u64,
+    /// `None` once the task has stopped
+    pub next_run: Option<DateTime<Utc>>,
+    /// false when the task gave up after repeated evaluation failures
+    pub alive: bool,
+    /// evaluations in a row aborted for running past the evaluation timeout
+    pub consecutive_timeouts: u32,
+    /// evaluations run since the task was scheduled, including the failed ones
+    pub evaluations: u64,
+    pub failed_evaluations: u64,
+    pub last_eval_duration_ms: Option<u64>,
+    pub avg_eval_duration_ms: Option<u64>,
+}
+
+/// Alert that could not be scheduled for evaluation
+#[derive(Debug, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedAlertTask {
+    pub id: Ulid,
+    pub error: String,
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledAlerts {
+    /// number of scheduled tasks, to compare against the total number of alerts
+    pub count: usize,
+    pub total_alerts: usize,
+    pub tasks: Vec<ScheduledAlertTask>,
+    pub failed: Vec<FailedAlertTask>,
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AlertConfig {
@@ -425,6 +585,12 @@ pub struct AlertConfigResponse {
     pub created: DateTime<Utc>,
     pub tags: Option<Vec<String>>,
     pub last_triggered_at: Option<DateTime<Utc>>,
+    /// issues with the configuration which don't prevent the alert from being evaluated
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// reachability of the targets, only checked when `P_CHECK_ALERT_TARGETS` is set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub target_checks: Vec<TargetCheck>,
     #[serde(flatten)]
     pub other_fields: Option<serde_json::Map<String, Value>>,
 }
@@ -485,6 +651,8 @@ impl AlertConfig {
             created: self.created,
             tags: self.tags,
             last_triggered_at: self.last_triggered_at,
+            warnings: vec![],
+            target_checks: vec![],
             other_fields: self.other_fields,
         }
     }
@@ -587,9 +755,20 @@ pub struct GroupResult {
     pub group_values: HashMap<String, String>,
     /// The aggregate function value for this group
     pub aggregate_value: f64,
+    /// Rows aggregated by this group, only counted for alerts with a minimum sample count
+    #[serde(default)]
+    pub sample_count: Option<u64>,
 }
 
 impl AlertQueryResult {
+    /// Result of a query returning no rows
+    pub fn no_data() -> Self {
+        Self {
+            groups: vec![],
+            is_simple_query: true,
+        }
+    }
+
     /// Get the single aggregate value for simple queries (backward compatibility)
     pub fn get_single_value(&self) -> f64 {
         if self.is_simple_query && !self.groups.is_empty() {
@@ -850,7 +1029,7 @@ impl AlertStateEntry {
                         trigger_time = None; // Reset for next cycle
                     }
                 }
-                AlertState::Disabled => {
+                AlertState::Disabled | AlertState::StreamMissing => {
                     // Ignore disabled state - it doesn't affect MTTR calculation
                     // until it's explicitly resolved (moves to not-triggered)
                 }
@@ -20,7 +20,9 @@ use crate::{
     alerts::{
         AlertConfig, AlertError, AlertState, AlertType, EvalConfig, Severity,
         alert_enums::NotificationState,
-        alert_structs::{Context, ThresholdConfig},
+        alert_structs::{
+            AlertQueryResult, Context, FailedAlertTask, ScheduledAlertTask, ThresholdConfig,
+        },
     },
     metastore::metastore_traits::MetastoreObject,
     rbac::map::SessionKey,
@@ -49,7 +51,13 @@ pub trait MessageCreation {
 
 #[async_trait]
 pub trait AlertTrait: Debug + Send + Sync + MetastoreObject {
-    async fn eval_alert(&self) -> Result<Option<String>, AlertError>;
+    /// Evaluates the alert over its window ending at `window_end`
+    async fn eval_alert(&self, window_end: DateTime<Utc>) -> Result<Option<String>, AlertError>;
+    /// Same as `eval_alert`, but also returns the query result the alert was evaluated against
+    async fn eval_alert_with_result(
+        &self,
+        window_end: DateTime<Utc>,
+    ) -> Result<(AlertQueryResult, Option<String>), AlertError>;
     async fn validate(&self, session_key: &SessionKey) -> Result<(), AlertError>;
     async fn update_notification_state(
         &mut self,
@@ -103,11 +111,14 @@ pub trait AlertManagerTrait: Send + Sync {
     async fn get_state(&self, alert_id: Ulid) -> Result<AlertState, AlertError>;
     async fn start_task(&self, alert: Box<dyn AlertTrait>) -> Result<(), AlertError>;
     async fn delete_task(&self, alert_id: Ulid) -> Result<(), AlertError>;
+    async fn scheduled_tasks(&self) -> Result<Vec<ScheduledAlertTask>, AlertError>;
+    async fn failed_tasks(&self) -> Vec<FailedAlertTask>;
     async fn list_tags(&self) -> Vec<String>;
     async fn get_all_alerts(&self) -> HashMap<Ulid, Box<dyn AlertTrait>>;
 }
 
 #[async_trait]
 pub trait CallableTarget {
-    async fn call(&self, payload: &Context);
+    /// Sends the notification, returning the status code of the target's response
+    async fn call(&self, payload: &Context) -> Result<reqwest::StatusCode, reqwest::Error>;
 }
@@ -28,17 +28,21 @@ use crate::{
     alerts::{
         AlertConfig, AlertError, AlertState, AlertType, AlertVersion, EvalConfig, Severity,
         ThresholdConfig,
+        alert_enums::AggregateMode,
         alert_enums::NotificationState,
-        alert_structs::{AlertStateEntry, GroupResult},
+        alert_structs::{AlertQueryResult, AlertStateEntry, GroupResult},
         alert_traits::{AlertTrait, MessageCreation},
-        alerts_utils::{evaluate_condition, execute_alert_query, extract_time_range},
+        alerts_utils::{execute_alert_query, extract_time_range},
         get_number_of_agg_exprs,
+        missing_columns::apply_missing_columns,
+        sample_count::with_sample_count,
         target::{self, NotificationConfig},
+        validate_regex_patterns,
     },
     handlers::http::query::create_streams_for_distributed,
     metastore::metastore_traits::MetastoreObject,
     parseable::PARSEABLE,
-    query::resolve_stream_names,
+    query::{ensure_streams_exist, resolve_stream_names},
     rbac::map::SessionKey,
     storage::object_storage::alert_json_path,
     utils::user_auth_for_query,
@@ -82,35 +86,48 @@ impl MetastoreObject for ThresholdAlert {
 
 #[async_trait]
 impl AlertTrait for ThresholdAlert {
-    async fn eval_alert(&self) -> Result<Option<String>, AlertError> {
-        let time_range = extract_time_range(&self.eval_config)?;
-        let query_result = execute_alert_query(self.get_query(), &time_range).await?;
+    async fn eval_alert(&self, window_end: DateTime<Utc>) -> Result<Option<String>, AlertError> {
+        let (_, message) = self.eval_alert_with_result(window_end).await?;
+        Ok(message)
+    }
+
+    async fn eval_alert_with_result(
+        &self,
+        window_end: DateTime<Utc>,
+    ) -> Result<(AlertQueryResult, Option<String>), AlertError> {
+        let time_range = extract_time_range(&self.eval_config, window_end)?;
+        let query_result = execute_alert_query(
+            self.get_query(),
+            &time_range,
+            self.threshold_config.aggregate_mode,
+            self.threshold_config.missing_columns,
+            self.threshold_config.min_sample_count > 0,
+        )
+        .await?;
 
         if query_result.is_simple_query {
             // Handle simple queries
             let final_value = query_result.get_single_value();
-            let result = evaluate_condition(
-                &self.threshold_config.operator,
-                final_value,
-                self.threshold_config.value,
-            );
+            let has_enough_samples = self.threshold_config.min_sample_count <= 0
+                || query_result
+                    .groups
+                    .first()
+                    .is_some_and(|group| self.threshold_config.has_enough_samples(group));
+            let result = has_enough_samples && self.threshold_config.is_breached(final_value);
 
             let message = if result {
                 Some(self.create_threshold_message(final_value)?)
             } else {
                 None
             };
-            Ok(message)
+            Ok((query_result, message))
         } else {
             // Handle GROUP BY queries - evaluate each group
             let mut breached_groups = Vec::new();
 
             for group in &query_result.groups {
-                let result = evaluate_condition(
-                    &self.threshold_config.operator,
-                    group.aggregate_value,
-                    self.threshold_config.value,
-                );
+                let result = self.threshold_config.has_enough_samples(group)
+                    && self.threshold_config.is_breached(group.aggregate_value);
 
                 if result {
                     breached_groups.push(group.clone());
@@ -122,7 +139,7 @@ impl AlertTrait for ThresholdAlert {
             } else {
                 None
             };
-            Ok(message)
+            Ok((query_result, message))
         }
     }
 
@@ -164,20 +181,37 @@ impl AlertTrait for ThresholdAlert {
                 "No tables found in query".into(),
             ));
         }
-        create_streams_for_distributed(tables)
+        create_streams_for_distributed(tables.clone())
             .await
             .map_err(|_| AlertError::InvalidAlertQuery("Invalid tables".into()))?;
+        ensure_streams_exist(&tables).await?;
 
         // validate that the user has access to the tables mentioned in the query
         user_auth_for_query(session_key, &self.query).await?;
 
         // validate that the alert query is valid and can be evaluated
-        let num_aggrs = get_number_of_agg_exprs(&self.query).await?;
-        if num_aggrs != 1 {
+        let query =
+            apply_missing_columns(&self.query, self.threshold_config.missing_columns).await?;
+        let num_aggrs = get_number_of_agg_exprs(&query).await?;
+        let expected_aggrs = match self.threshold_config.aggregate_mode {
+            AggregateMode::Single => 1,
+            AggregateMode::Ratio => 2,
+        };
+        if num_aggrs != expected_aggrs {
             return Err(AlertError::InvalidAlertQuery(format!(
-                "Found {num_aggrs} aggregate expressions, only 1 allowed"
+                "Found {num_aggrs} aggregate expressions, {expected_aggrs} required for {:?} aggregate mode",
+                self.threshold_config.aggregate_mode
             )));
         }
+        if self.threshold_config.min_sample_count < 0 {
+            return Err(AlertError::Metadata(
+                "minSampleCount should be a non-negative number of rows",
+            ));
+        }
+        if self.threshold_config.min_sample_count > 0 {
+            with_sample_count(&query)?;
+        }
+        validate_regex_patterns(&query).await?;
         Ok(())
     }
 
@@ -201,10 +235,10 @@ impl AlertTrait for ThresholdAlert {
         new_state: AlertState,
         trigger_notif: Option<String>,
     ) -> Result<(), AlertError> {
-        if self.state.eq(&AlertState::Disabled) {
+        if self.state.is_paused() {
             warn!(
-                "Alert- {} is currently Disabled. Updating state to {new_state}.",
-                self.id
+                "Alert- {} is currently {}. Updating state to {new_state}.",
+                self.id, self.state
             );
             // update state in memory
             self.state = new_state;
@@ -16,29 +16,38 @@
  *
  */
 
-use std::{collections::HashMap, fmt::Display};
+use std::{collections::HashMap, fmt::Display, time::Duration};
 
 use actix_web::Either;
 use arrow_array::{Array, Float64Array, Int64Array, RecordBatch};
+use chrono::{DateTime, TimeDelta, Utc};
 use datafusion::{
     logical_expr::{Literal, LogicalPlan},
     prelude::{Expr, lit},
 };
-use tracing::trace;
+use tracing::{info, trace, warn};
 
 use crate::{
     alerts::{
-        AlertTrait, LogicalOperator, WhereConfigOperator,
+        AggregateMode, AlertTrait, LogicalOperator, MissingColumns, WhereConfigOperator,
+        alert_results::record_alert_results,
         alert_structs::{AlertQueryResult, Conditions, GroupResult},
         extract_aggregate_aliases,
+        missing_columns::apply_missing_columns,
+        sample_count::{SAMPLE_COUNT_COLUMN, with_sample_count},
     },
+    enterprise::utils::fetch_parquet_file_paths,
     handlers::http::{
         cluster::send_query_request,
         query::{Query, create_streams_for_distributed},
     },
     option::Mode,
     parseable::PARSEABLE,
-    query::{QUERY_SESSION, execute, resolve_stream_names},
+    query::{
+        QUERY_SESSION, column_aliases::resolve_column_aliases, ensure_streams_exist, execute,
+        resolve_stream_names, streams_without_data,
+    },
+    response::NullMode,
     utils::time::TimeRange,
 };
 
@@ -55,43 +64,192 @@ use super::{ALERTS, AlertError, AlertOperator, AlertState};
 /// collect the results in the end
 ///
 /// check whether notification needs to be triggered or not
-pub async fn evaluate_alert(alert: &dyn AlertTrait) -> Result<(), AlertError> {
+///
+/// The window of the evaluation ends at `scheduled_at`, when the evaluation was due,
+/// so that an evaluation waiting for a slot doesn't shift its window
+pub async fn evaluate_alert(
+    alert: &dyn AlertTrait,
+    scheduled_at: DateTime<Utc>,
+) -> Result<(), AlertError> {
     trace!("RUNNING EVAL TASK FOR- {alert:?}");
 
-    let message = alert.eval_alert().await?;
+    let (query_result, message) = alert.eval_alert_with_result(scheduled_at).await?;
+
+    let super::EvalConfig::RollingWindow(rolling_window) = alert.get_eval_config();
+    if rolling_window.record_results {
+        let recorded = match extract_time_range(alert.get_eval_config(), scheduled_at) {
+            Ok(window) => record_alert_results(alert, &query_result, &window).await,
+            Err(err) => Err(err),
+        };
+        // the state of the alert is updated regardless
+        if let Err(err) = recorded {
+            warn!(
+                "failed to record the evaluation of alert {}: {err}",
+                alert.get_id()
+            );
+        }
+    }
+
+    let warmup = i64::try_from(PARSEABLE.options.alert_warmup_minutes)
+        .ok()
+        .and_then(TimeDelta::try_minutes)
+        .unwrap_or_default();
+    if in_warmup(&alert.get_created(), warmup, Utc::now()) {
+        trace!(
+            "Alert {} is warming up, skipping state update",
+            alert.get_id()
+        );
+        return Ok(());
+    }
 
     update_alert_state(alert, message).await
 }
 
-/// Extract time range from alert evaluation configuration
-pub fn extract_time_range(eval_config: &super::EvalConfig) -> Result<TimeRange, AlertError> {
-    let (start_time, end_time) = match eval_config {
-        super::EvalConfig::RollingWindow(rolling_window) => (&rolling_window.eval_start, "now"),
+/// Whether an alert created at `created` (RFC3339) is still within its `warmup` period
+pub fn in_warmup(created: &str, warmup: TimeDelta, now: DateTime<Utc>) -> bool {
+    if warmup <= TimeDelta::zero() {
+        return false;
+    }
+
+    DateTime::parse_from_rfc3339(created).is_ok_and(|created| {
+        created
+            .with_timezone(&Utc)
+            .checked_add_signed(warmup)
+            .is_some_and(|end| end > now)
+    })
+}
+
+/// Runs a single evaluation of the alert right away, updating its state and
+/// notifications the same way the scheduled task does.
+///
+/// Returns the query result the alert was evaluated against
+pub async fn evaluate_alert_now(alert: &dyn AlertTrait) -> Result<AlertQueryResult, AlertError> {
+    trace!("RUNNING ON-DEMAND EVAL FOR- {alert:?}");
+
+    let (query_result, message) = alert.eval_alert_with_result(Utc::now()).await?;
+
+    update_alert_state(alert, message).await?;
+    Ok(query_result)
+}
+
+/// Evaluates the alert once and corrects its state if it no longer matches the data,
+/// e.g. an alert stored as triggered whose condition resolved while the server was down.
+///
+/// No notifications are sent, they resume with the next scheduled evaluation
+pub async fn reconcile_alert_state(alert: &mut dyn AlertTrait) -> Result<(), AlertError> {
+    if alert.get_state().is_paused() {
+        return Ok(());
+    }
+
+    let actual_state = match alert.eval_alert(Utc::now()).await? {
+        Some(_) => AlertState::Triggered,
+        None => AlertState::NotTriggered,
     };
+    if alert.get_state().eq(&actual_state) {
+        return Ok(());
+    }
+
+    info!(
+        "Reconciling state of alert {} from {} to {actual_state}",
+        alert.get_id(),
+        alert.get_state()
+    );
+    alert.update_state(actual_state, None).await
+}
 
-    TimeRange::parse_human_time(start_time, end_time)
-        .map_err(|err| AlertError::CustomError(err.to_string()))
+/// Extract time range from alert evaluation configuration, the window ending at `window_end`
+pub fn extract_time_range(
+    eval_config: &super::EvalConfig,
+    window_end: DateTime<Utc>,
+) -> Result<TimeRange, AlertError> {
+    let window = match eval_config {
+        super::EvalConfig::RollingWindow(rolling_window) => &rolling_window.eval_start,
+    };
+    let window = humantime::parse_duration(window)
+        .map_err(|err| AlertError::CustomError(err.to_string()))?;
+    let window =
+        TimeDelta::from_std(window).map_err(|err| AlertError::CustomError(err.to_string()))?;
+
+    TimeRange::parse_human_time(
+        &(window_end - window).to_rfc3339(),
+        &window_end.to_rfc3339(),
+    )
+    .map_err(|err| AlertError::CustomError(err.to_string()))
 }
 
-/// Execute the alert query based on the current mode and return structured group results
+/// Execute the alert query based on the current mode and return structured group results,
+/// aborted when it exceeds the evaluation timeout or would scan more than the configured limit.
+///
+/// With `count_samples`, the rows aggregated by each group are counted along with its value
 pub async fn execute_alert_query(
     query: &str,
     time_range: &TimeRange,
+    aggregate_mode: AggregateMode,
+    missing_columns: MissingColumns,
+    count_samples: bool,
 ) -> Result<AlertQueryResult, AlertError> {
-    match PARSEABLE.options.mode {
-        Mode::All | Mode::Query => execute_local_query(query, time_range).await,
-        Mode::Prism => execute_remote_query(query, time_range).await,
-        _ => Err(AlertError::CustomError(format!(
-            "Unsupported mode '{:?}' for alert evaluation",
-            PARSEABLE.options.mode
-        ))),
+    let timeout = PARSEABLE.options.alert_eval_timeout;
+    let evaluation = async {
+        if let Some(limit) = PARSEABLE.options.alert_eval_max_scan_size {
+            check_scan_size(query, time_range, limit).await?;
+        }
+        // deleted streams are reported as such rather than failing to plan the query
+        let tables = resolve_stream_names(query)?;
+        ensure_streams_exist(&tables).await?;
+        // streams that haven't received any event yet can't be planned against
+        if streams_without_data(&tables).await {
+            return Ok(AlertQueryResult::no_data());
+        }
+        let mut query = apply_missing_columns(query, missing_columns).await?;
+        if count_samples {
+            query = with_sample_count(&query)?;
+        }
+        let query = &query;
+
+        match PARSEABLE.options.mode {
+            Mode::All | Mode::Query => execute_local_query(query, time_range, aggregate_mode).await,
+            Mode::Prism => execute_remote_query(query, time_range, aggregate_mode).await,
+            _ => Err(AlertError::CustomError(format!(
+                "Unsupported mode '{:?}' for alert evaluation",
+                PARSEABLE.options.mode
+            ))),
+        }
+    };
+
+    tokio::time::timeout(Duration::from_secs(timeout), evaluation)
+        .await
+        .map_err(|_| AlertError::EvalTimeout(timeout))?
+}
+
+/// Rejects queries whose datasets hold more than `limit` MiB of parquet files in the time range
+async fn check_scan_size(
+    query: &str,
+    time_range: &TimeRange,
+    limit: u64,
+) -> Result<(), AlertError> {
+    let mut scan_size = 0;
+    for table in resolve_stream_names(query)? {
+        let files = fetch_parquet_file_paths(&table, time_range).await?;
+        scan_size += files
+            .values()
+            .flatten()
+            .map(|file| file.file_size)
+            .sum::<u64>();
+    }
+
+    let scan_size = scan_size / (1024 * 1024);
+    if scan_size > limit {
+        return Err(AlertError::ScanLimitExceeded { scan_size, limit });
     }
+
+    Ok(())
 }
 
 /// Execute alert query locally (Query/All mode)
 async fn execute_local_query(
     query: &str,
     time_range: &TimeRange,
+    aggregate_mode: AggregateMode,
 ) -> Result<AlertQueryResult, AlertError> {
     let session_state = QUERY_SESSION.state();
 
@@ -100,11 +258,14 @@ async fn execute_local_query(
         .await
         .map_err(|err| AlertError::CustomError(format!("Failed to create streams: {err}")))?;
 
-    let raw_logical_plan = session_state.create_logical_plan(query).await?;
+    let sql = resolve_column_aliases(query)?;
+    let raw_logical_plan = session_state.create_logical_plan(&sql).await?;
     let query = crate::query::Query {
         raw_logical_plan: raw_logical_plan.clone(),
         time_range: time_range.clone(),
         filter_tag: None,
+        as_of: None,
+        sample: None,
     };
 
     let (records, _) = execute(query, false)
@@ -120,16 +281,23 @@ async fn execute_local_query(
         }
     };
 
-    Ok(extract_group_results(records, raw_logical_plan))
+    Ok(extract_group_results(
+        records,
+        raw_logical_plan,
+        aggregate_mode,
+    ))
 }
 
 /// Execute alert query remotely (Prism mode)
 async fn execute_remote_query(
     query: &str,
     time_range: &TimeRange,
+    aggregate_mode: AggregateMode,
 ) -> Result<AlertQueryResult, AlertError> {
     let session_state = QUERY_SESSION.state();
-    let raw_logical_plan = session_state.create_logical_plan(query).await?;
+    let raw_logical_plan = session_state
+        .create_logical_plan(&resolve_column_aliases(query)?)
+        .await?;
 
     let query_request = Query {
         query: query.to_string(),
@@ -139,13 +307,46 @@ async fn execute_remote_query(
         send_null: false,
         fields: false,
         filter_tags: None,
+        // alerts are evaluated against the latest data
+        no_cache: true,
+        as_of: None,
+        null_mode: NullMode::Omit,
+        sample: None,
     };
 
     let (result_value, _) = send_query_request(&query_request)
         .await
         .map_err(|err| AlertError::CustomError(format!("Failed to send query request: {err}")))?;
 
-    convert_result_to_group_results(result_value, raw_logical_plan)
+    convert_result_to_group_results(result_value, raw_logical_plan, aggregate_mode)
+}
+
+/// Names of the aggregate columns used by the alert, the numerator and denominator in ratio mode
+fn aggregate_columns(plan: &LogicalPlan, aggregate_mode: AggregateMode) -> Vec<String> {
+    let count = match aggregate_mode {
+        AggregateMode::Single => 1,
+        AggregateMode::Ratio => 2,
+    };
+    let aggregate_aliases = extract_aggregate_aliases(plan);
+    if aggregate_aliases.len() < count {
+        return vec![];
+    }
+
+    aggregate_aliases
+        .into_iter()
+        .take(count)
+        .map(|(agg_condition, alias)| alias.unwrap_or(agg_condition))
+        .collect()
+}
+
+/// Value compared against the threshold, NaN when the denominator of a ratio is zero
+fn aggregate_value(values: &[f64]) -> f64 {
+    match values {
+        [_, denominator] if *denominator == 0.0 => f64::NAN,
+        [numerator, denominator] => numerator / denominator,
+        [value] => *value,
+        _ => 0.0,
+    }
 }
 
 /// Convert JSON result value to AlertQueryResult
@@ -153,49 +354,45 @@ async fn execute_remote_query(
 fn convert_result_to_group_results(
     result_value: serde_json::Value,
     plan: LogicalPlan,
+    aggregate_mode: AggregateMode,
 ) -> Result<AlertQueryResult, AlertError> {
     let array_val = result_value
         .as_array()
         .ok_or_else(|| AlertError::CustomError("Expected array in query result".to_string()))?;
 
-    let aggregate_aliases = extract_aggregate_aliases(&plan);
+    let aggregate_keys = aggregate_columns(&plan, aggregate_mode);
 
-    if array_val.is_empty() || aggregate_aliases.is_empty() {
-        return Ok(AlertQueryResult {
-            groups: vec![],
-            is_simple_query: true,
-        });
+    if array_val.is_empty() || aggregate_keys.is_empty() {
+        return Ok(AlertQueryResult::no_data());
     }
 
-    // take the first entry and extract the column name / alias
-    let (agg_condition, alias) = &aggregate_aliases[0];
-
-    let aggregate_key = if let Some(alias) = alias {
-        alias
-    } else {
-        agg_condition
-    };
-
     // Find the aggregate column from the first row
     let first_row = array_val[0]
         .as_object()
         .ok_or_else(|| AlertError::CustomError("Expected object in query result".to_string()))?;
 
-    let is_simple_query = first_row.len() == 1;
+    let is_simple_query = first_row
+        .keys()
+        .filter(|key| *key != SAMPLE_COUNT_COLUMN)
+        .count()
+        == aggregate_keys.len();
     let mut groups = Vec::new();
 
     // Process each row as a separate group
     for row in array_val {
         if let Some(object) = row.as_object() {
             let mut group_values = HashMap::new();
-            let mut aggregate_value = 0.0;
+            let mut values = vec![0.0; aggregate_keys.len()];
+            let mut sample_count = None;
 
             for (key, value) in object {
-                if key == aggregate_key {
-                    aggregate_value = value.as_f64().ok_or_else(|| {
+                if key == SAMPLE_COUNT_COLUMN {
+                    sample_count = value.as_u64();
+                } else if let Some(index) = aggregate_keys.iter().position(|k| k == key) {
+                    values[index] = value.as_f64().ok_or_else(|| {
                         AlertError::CustomError(format!(
                             "Non-numeric value found in aggregate column '{}'",
-                            aggregate_key
+                            key
                         ))
                     })?;
                 } else {
@@ -207,7 +404,8 @@ fn convert_result_to_group_results(
 
             groups.push(GroupResult {
                 group_values,
-                aggregate_value,
+                aggregate_value: aggregate_value(&values),
+                sample_count,
             });
         }
     }
@@ -298,46 +496,46 @@ async fn update_alert_state(
 }
 
 /// Extract group results from record batches, supporting both simple and GROUP BY queries
-fn extract_group_results(records: Vec<RecordBatch>, plan: LogicalPlan) -> AlertQueryResult {
+fn extract_group_results(
+    records: Vec<RecordBatch>,
+    plan: LogicalPlan,
+    aggregate_mode: AggregateMode,
+) -> AlertQueryResult {
     trace!("records-\n{records:?}");
 
-    let aggregate_aliases = extract_aggregate_aliases(&plan);
+    let aggregate_columns = aggregate_columns(&plan, aggregate_mode);
 
-    // since there is going to be only one aggregate, we'll check if it is empty
-    if aggregate_aliases.is_empty() || records.is_empty() {
-        return AlertQueryResult {
-            groups: vec![],
-            is_simple_query: true,
-        };
+    if aggregate_columns.is_empty() || records.is_empty() {
+        return AlertQueryResult::no_data();
     }
 
-    // take the first entry and extract the column name / alias
-    let (agg_condition, alias) = &aggregate_aliases[0];
-
-    let alias = if let Some(alias) = alias {
-        alias
-    } else {
-        agg_condition
-    };
-
     let first_batch = &records[0];
     let schema = first_batch.schema();
 
     // Determine if this is a simple query (no GROUP BY) or a grouped query
-    let is_simple_query = schema.fields().len() == 1;
+    let is_simple_query = schema
+        .fields()
+        .iter()
+        .filter(|field| field.name() != SAMPLE_COUNT_COLUMN)
+        .count()
+        == aggregate_columns.len();
 
     let mut groups = Vec::new();
 
     for batch in &records {
         for row_index in 0..batch.num_rows() {
             let mut group_values = HashMap::new();
-            let mut aggregate_value = 0.0;
+            let mut values = vec![0.0; aggregate_columns.len()];
+            let mut sample_count = None;
 
             // Extract values for each column
             for (col_index, field) in schema.fields().iter().enumerate() {
                 let column = batch.column(col_index);
-                if field.name().eq(alias) {
-                    aggregate_value = extract_numeric_value(column, row_index)
+                if field.name() == SAMPLE_COUNT_COLUMN {
+                    sample_count = Some(extract_numeric_value(column, row_index) as u64);
+                } else if let Some(index) = aggregate_columns.iter().position(|c| c == field.name())
+                {
+                    values[index] = extract_numeric_value(column, row_index)
                 } else {
                     // This is a GROUP BY column
                     let value = extract_string_value(column, row_index);
@@ -347,7 +545,8 @@ fn extract_group_results(records: Vec<RecordBatch>, plan: LogicalPlan) -> AlertQ
 
             groups.push(GroupResult {
                 group_values,
-                aggregate_value,
+                aggregate_value: aggregate_value(&values),
+                sample_count,
             });
         }
     }
@@ -425,6 +624,19 @@ pub fn get_filter_string(where_clause: &Conditions) -> Result<String, String> {
                                     .replace('_', "\\_");
                                 format!("NOT LIKE '%{escaped_value}' ESCAPE '\\'")
                             }
+                            WhereConfigOperator::Regex => {
+                                format!("~ {}", regex_literal(value)?)
+                            }
+                            WhereConfigOperator::NotRegex => {
+                                format!("!~ {}", regex_literal(value)?)
+                            }
+                            WhereConfigOperator::In | WhereConfigOperator::NotIn => {
+                                format!(
+                                    "{} ({})",
+                                    condition.operator.as_str().to_uppercase(),
+                                    list_literal(value)?
+                                )
+                            }
                             _ => {
                                 let value = match ValueType::from_string(value.to_owned()) {
                                     ValueType::Number(val) => format!("{val}"),
@@ -452,6 +664,69 @@ pub fn get_filter_string(where_clause: &Conditions) -> Result<String, String> {
     }
 }
 
+/// Quotes a regex pattern for SQL, after checking that it compiles
+pub fn regex_literal(pattern: &str) -> Result<String, String> {
+    regex::Regex::new(pattern)
+        .map_err(|err| format!("invalid regex pattern '{pattern}': {err}"))?;
+    Ok(format!("'{}'", pattern.replace('\'', "''")))
+}
+
+/// Splits the value of an `in` condition into its values,
+/// given either as a JSON array or as comma separated values
+pub fn parse_value_list(value: &str) -> Result<Vec<String>, String> {
+    let value = value.trim();
+    let values = if value.starts_with('[') {
+        serde_json::from_str::<Vec<serde_json::Value>>(value)
+            .map_err(|err| format!("invalid list of values '{value}': {err}"))?
+            .into_iter()
+            .map(|value| match value {
+                serde_json::Value::String(s) => Ok(s),
+                serde_json::Value::Number(_) | serde_json::Value::Bool(_) => Ok(value.to_string()),
+                _ => Err(format!(
+                    "invalid value {value} in list, only strings, numbers and booleans are allowed"
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        value
+            .split(',')
+            .map(|value| value.trim().to_owned())
+            .filter(|value| !value.is_empty())
+            .collect()
+    };
+
+    if values.is_empty() {
+        return Err(format!("'{value}' is not a list of values"));
+    }
+    Ok(values)
+}
+
+/// Quotes the values of an `in` condition for SQL, the values must all be of the same type
+/// for the list to match a single column
+fn list_literal(value: &str) -> Result<String, String> {
+    let values = parse_value_list(value)?
+        .into_iter()
+        .map(ValueType::from_string)
+        .collect::<Vec<_>>();
+    if values
+        .iter()
+        .any(|value| std::mem::discriminant(value) != std::mem::discriminant(&values[0]))
+    {
+        return Err(format!(
+            "values of list '{value}' must either all be numbers, all be booleans or all be strings"
+        ));
+    }
+
+    Ok(values
+        .iter()
+        .map(|value| match value {
+            ValueType::String(s) => format!("'{}'", s.replace('\'', "''")),
+            _ => value.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", "))
+}
+
 enum ValueType {
     Number(f64),
     String(String),
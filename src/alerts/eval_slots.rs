@@ -0,0 +1,67 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::sync::Arc;
+
+use once_cell::sync::Lazy;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{
+    metrics::{ALERT_EVALS_DELAYED, ALERT_EVALS_QUEUED},
+    parseable::PARSEABLE,
+};
+
+/// Bounds the scheduled alert evaluations running at once, unbounded when
+/// `--max-concurrent-alert-evals` is not set
+static ALERT_EVAL_SLOTS: Lazy<Option<Arc<Semaphore>>> = Lazy::new(|| {
+    PARSEABLE
+        .options
+        .max_concurrent_alert_evals
+        .map(|limit| Arc::new(Semaphore::new(limit as usize)))
+});
+
+/// A scheduled alert evaluation running, released when dropped
+pub struct AlertEvalSlot {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+/// Waits for another evaluation to complete when `--max-concurrent-alert-evals` are already running
+pub async fn acquire_alert_eval_slot() -> AlertEvalSlot {
+    let Some(slots) = ALERT_EVAL_SLOTS.as_ref() else {
+        return AlertEvalSlot { _permit: None };
+    };
+
+    let permit = match slots.clone().try_acquire_owned() {
+        Ok(permit) => permit,
+        Err(_) => {
+            ALERT_EVALS_DELAYED.inc();
+            ALERT_EVALS_QUEUED.inc();
+            let permit = slots
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("alert evaluation semaphore is never closed");
+            ALERT_EVALS_QUEUED.dec();
+            permit
+        }
+    };
+
+    AlertEvalSlot {
+        _permit: Some(permit),
+    }
+}
@@ -0,0 +1,251 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Dotted paths to the fields of nested events. Events are flattened on ingestion, the field
+//! `status` of `{"response": {"status": 500}}` is stored in the column `response_status`,
+//! alert queries may still refer to it as `response.status`.
+
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+
+use datafusion::sql::sqlparser::ast::{
+    Expr, Ident, Statement, TableFactor, Visit, Visitor, visit_expressions, visit_expressions_mut,
+};
+use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
+use datafusion::sql::sqlparser::parser::{Parser, ParserError};
+
+use crate::query::resolve_stream_names;
+
+use super::{
+    AlertError,
+    alert_enums::MissingColumns,
+    missing_columns::{column_name, known_columns},
+};
+
+/// A query whose dotted paths were replaced with the columns they resolve to
+#[derive(Debug, PartialEq)]
+pub struct FieldPathRewrite {
+    pub sql: String,
+    /// paths for which neither the dotted nor the flattened column exist,
+    /// they are replaced with the flattened column
+    pub unresolved: Vec<String>,
+}
+
+/// Resolves the dotted paths of an alert query to the columns of the queried streams. A path
+/// which resolves to no column is an error, unless missing columns are treated as null.
+pub async fn resolve_field_paths(
+    query: &str,
+    policy: MissingColumns,
+) -> Result<String, AlertError> {
+    // the schemas are only fetched for queries which may refer to a nested field
+    if !mentions_field_paths(query) {
+        return Ok(query.to_owned());
+    }
+
+    let streams = resolve_stream_names(query)?;
+    let columns = known_columns(&streams).await?;
+    let rewrite = rewrite_field_paths(query, &streams, |column| columns.contains(column))?;
+    if policy == MissingColumns::Error
+        && let Some(path) = rewrite.unresolved.first()
+    {
+        return Err(AlertError::InvalidAlertQuery(format!(
+            "column `{path}` doesn't exist in the queried datasets, neither does its flattened form `{}`",
+            path.replace('.', "_")
+        )));
+    }
+
+    Ok(rewrite.sql)
+}
+
+/// Whether the query has a dotted identifier, either a qualified column or a path
+fn mentions_field_paths(sql: &str) -> bool {
+    let Ok(statements) = parse(sql) else {
+        return false;
+    };
+
+    statements.iter().any(|statement| {
+        visit_expressions(statement, |expr| match path_segments(expr) {
+            Some(_) => ControlFlow::Break(()),
+            None => ControlFlow::Continue(()),
+        })
+        .is_break()
+    })
+}
+
+/// Replaces the dotted paths of `sql` with the column they refer to, the column named after
+/// the dotted path when `is_known`, the flattened column otherwise. Identifiers qualified with
+/// one of the `streams` or a table alias of the query are paths relative to the table.
+pub fn rewrite_field_paths(
+    sql: &str,
+    streams: &[String],
+    is_known: impl Fn(&str) -> bool,
+) -> Result<FieldPathRewrite, ParserError> {
+    let mut statements = parse(sql)?;
+    let mut qualifiers = TableQualifiers(streams.iter().cloned().collect());
+    for statement in &statements {
+        let _ = statement.visit(&mut qualifiers);
+    }
+
+    let mut rewritten = false;
+    let mut unresolved = vec![];
+    for statement in statements.iter_mut() {
+        let _ = visit_expressions_mut(statement, |expr| {
+            let Some(segments) = path_segments(expr) else {
+                return ControlFlow::<()>::Continue(());
+            };
+            let qualifier = match &*expr {
+                Expr::CompoundIdentifier(idents) if qualifiers.contains(&idents[0]) => {
+                    Some(idents[0].clone())
+                }
+                _ => None,
+            };
+            let path = match qualifier {
+                Some(_) => &segments[1..],
+                None => &segments[..],
+            };
+            // a column qualified with its table
+            if path.len() < 2 {
+                return ControlFlow::Continue(());
+            }
+
+            let dotted = path.join(".");
+            let column = if is_known(&dotted) {
+                dotted
+            } else {
+                let flattened = path.join("_");
+                if !is_known(&flattened) {
+                    unresolved.push(dotted);
+                }
+                flattened
+            };
+            if matches!(expr, Expr::Identifier(ident) if ident.value == column) {
+                return ControlFlow::Continue(());
+            }
+
+            let column = Ident::with_quote('"', column);
+            *expr = match qualifier {
+                Some(qualifier) => Expr::CompoundIdentifier(vec![qualifier, column]),
+                None => Expr::Identifier(column),
+            };
+            rewritten = true;
+            ControlFlow::Continue(())
+        });
+    }
+
+    if !rewritten {
+        return Ok(FieldPathRewrite {
+            sql: sql.to_owned(),
+            unresolved,
+        });
+    }
+
+    Ok(FieldPathRewrite {
+        sql: statements
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; "),
+        unresolved,
+    })
+}
+
+fn parse(sql: &str) -> Result<Vec<Statement>, ParserError> {
+    Parser::parse_sql(&PostgreSqlDialect {}, &sql.replace('`', "\""))
+}
+
+/// The segments of a dotted identifier, `"response.status"` included
+fn path_segments(expr: &Expr) -> Option<Vec<String>> {
+    match expr {
+        Expr::CompoundIdentifier(idents) => Some(idents.iter().map(column_name).collect()),
+        Expr::Identifier(ident) if ident.quote_style.is_some() && ident.value.contains('.') => {
+            Some(ident.value.split('.').map(ToOwned::to_owned).collect())
+        }
+        _ => None,
+    }
+}
+
+/// The names tables can be referred to with in a query, the queried streams and their aliases
+struct TableQualifiers(HashSet<String>);
+
+impl TableQualifiers {
+    fn contains(&self, ident: &Ident) -> bool {
+        self.0.contains(&ident.value) || self.0.contains(&column_name(ident))
+    }
+}
+
+impl Visitor for TableQualifiers {
+    type Break = ();
+
+    fn pre_visit_table_factor(&mut self, table_factor: &TableFactor) -> ControlFlow<Self::Break> {
+        if let TableFactor::Table {
+            alias: Some(alias), ..
+        }
+        | TableFactor::Derived {
+            alias: Some(alias), ..
+        } = table_factor
+        {
+            self.0.insert(column_name(&alias.name));
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the schema of a stream of nested events, `response` was flattened on ingestion
+    fn is_known(column: &str) -> bool {
+        ["host", "response_status", "response_time"].contains(&column)
+    }
+
+    #[test]
+    fn dotted_paths_resolve_to_flattened_columns() {
+        let rewrite = rewrite_field_paths(
+            r#"SELECT avg(response.time) FROM app a WHERE a.response.status >= 500 AND "response.status" < 600 AND a.host = 'x'"#,
+            &["app".to_owned()],
+            is_known,
+        )
+        .unwrap();
+
+        assert_eq!(
+            rewrite,
+            FieldPathRewrite {
+                sql: r#"SELECT avg("response_time") FROM app AS a WHERE a."response_status" >= 500 AND "response_status" < 600 AND a.host = 'x'"#
+                    .to_owned(),
+                unresolved: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_paths_are_reported() {
+        let rewrite = rewrite_field_paths(
+            "SELECT count(*) FROM app WHERE request.method = 'GET'",
+            &["app".to_owned()],
+            is_known,
+        )
+        .unwrap();
+
+        assert_eq!(rewrite.unresolved, vec!["request.method".to_owned()]);
+        assert_eq!(
+            rewrite.sql,
+            r#"SELECT count(*) FROM app WHERE "request_method" = 'GET'"#
+        );
+    }
+}
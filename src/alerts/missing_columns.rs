@@ -0,0 +1,230 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Null checks on columns missing from the schema of the queried streams. The columns of a
+//! dynamic-schema stream only exist once an event carried them, alerts can treat such a column
+//! as all-null instead of failing to plan their query.
+
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+
+use arrow_schema::Schema;
+use datafusion::sql::sqlparser::ast::{Expr, Ident, Value, visit_expressions_mut};
+use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
+use datafusion::sql::sqlparser::parser::{Parser, ParserError};
+
+use crate::{
+    handlers::http::fetch_schema,
+    option::Mode,
+    parseable::{PARSEABLE, StreamNotFound},
+    query::resolve_stream_names,
+};
+
+use super::{AlertError, alert_enums::MissingColumns, field_paths::resolve_field_paths};
+
+/// A query whose null checks on missing columns were replaced with their outcome
+#[derive(Debug, PartialEq)]
+pub struct NullCheckRewrite {
+    pub sql: String,
+    /// missing columns checked with `is not null`, these checks never match
+    pub never_matching: Vec<String>,
+}
+
+/// Applies the missing column policy of an alert to its query, once its dotted paths
+/// to nested fields are resolved to their flattened columns
+pub async fn apply_missing_columns(
+    query: &str,
+    policy: MissingColumns,
+) -> Result<String, AlertError> {
+    let query = resolve_field_paths(query, policy).await?;
+    match policy {
+        MissingColumns::Error => Ok(query),
+        MissingColumns::TreatAsNull => Ok(resolve_missing_column_null_checks(&query).await?.sql),
+    }
+}
+
+/// Warnings for the `is not null` checks of an alert query which never match,
+/// as their column is missing from the queried streams and treated as all-null
+pub async fn missing_column_warnings(
+    query: &str,
+    policy: MissingColumns,
+) -> Result<Vec<String>, AlertError> {
+    if policy == MissingColumns::Error {
+        return Ok(vec![]);
+    }
+
+    let query = resolve_field_paths(query, policy).await?;
+    Ok(resolve_missing_column_null_checks(&query)
+        .await?
+        .never_matching
+        .into_iter()
+        .map(|column| {
+            format!(
+                "`{column} is not null` never matches, column {column} is missing from the queried datasets and is treated as all-null"
+            )
+        })
+        .collect())
+}
+
+/// Rewrites the null checks of `query` on columns missing from all the queried streams
+pub async fn resolve_missing_column_null_checks(
+    query: &str,
+) -> Result<NullCheckRewrite, AlertError> {
+    let columns = known_columns(&resolve_stream_names(query)?).await?;
+
+    Ok(rewrite_missing_column_null_checks(query, |column| {
+        columns.contains(column)
+    })?)
+}
+
+/// The columns of the streams, along with their aliases
+pub(super) async fn known_columns(streams: &[String]) -> Result<HashSet<String>, AlertError> {
+    let mut columns = HashSet::new();
+    for stream in streams {
+        columns.extend(
+            stream_schema(stream)
+                .await?
+                .fields()
+                .iter()
+                .map(|field| field.name().clone()),
+        );
+        // aliases are only resolved to their column when the query is planned
+        columns.extend(
+            PARSEABLE
+                .get_stream(stream)?
+                .get_column_aliases()
+                .into_keys(),
+        );
+    }
+
+    Ok(columns)
+}
+
+async fn stream_schema(stream: &str) -> Result<Schema, AlertError> {
+    if !PARSEABLE.check_or_load_stream(stream).await {
+        return Err(StreamNotFound(stream.to_owned()).into());
+    }
+
+    match PARSEABLE.options.mode {
+        // queriers only learn the fields of a stream from the schemas uploaded by the ingestors
+        Mode::Query | Mode::Prism => Ok(fetch_schema(stream).await?),
+        _ => Ok(PARSEABLE.get_stream(stream)?.get_schema().as_ref().clone()),
+    }
+}
+
+/// Replaces `is null` checks on columns for which `is_known` is false with `true`,
+/// and `is not null` checks with `false`. The SQL is returned untouched when no check is replaced.
+pub fn rewrite_missing_column_null_checks(
+    sql: &str,
+    is_known: impl Fn(&str) -> bool,
+) -> Result<NullCheckRewrite, ParserError> {
+    let normalized_sql = sql.replace('`', "\"");
+    let mut statements = Parser::parse_sql(&PostgreSqlDialect {}, &normalized_sql)?;
+
+    let mut rewritten = false;
+    let mut never_matching = vec![];
+    for statement in statements.iter_mut() {
+        let _ = visit_expressions_mut(statement, |expr| {
+            let outcome = match expr {
+                Expr::IsNull(column) => missing_column(column, &is_known).map(|c| (c, true)),
+                Expr::IsNotNull(column) => missing_column(column, &is_known).map(|c| (c, false)),
+                _ => None,
+            };
+            if let Some((column, matches)) = outcome {
+                if !matches {
+                    never_matching.push(column);
+                }
+                *expr = Expr::value(Value::Boolean(matches));
+                rewritten = true;
+            }
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    if !rewritten {
+        return Ok(NullCheckRewrite {
+            sql: sql.to_owned(),
+            never_matching,
+        });
+    }
+
+    Ok(NullCheckRewrite {
+        sql: statements
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; "),
+        never_matching,
+    })
+}
+
+fn missing_column(expr: &Expr, is_known: impl Fn(&str) -> bool) -> Option<String> {
+    let ident = match expr {
+        Expr::Identifier(ident) => ident,
+        Expr::CompoundIdentifier(idents) => idents.last()?,
+        _ => return None,
+    };
+    let column = column_name(ident);
+
+    (!is_known(&column)).then_some(column)
+}
+
+/// The column an identifier refers to, unquoted identifiers are lowercased when the query is planned
+pub(super) fn column_name(ident: &Ident) -> String {
+    match ident.quote_style {
+        Some(_) => ident.value.clone(),
+        None => ident.value.to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the schema of a dynamic stream which hasn't received `user_id` or `trace_id` yet
+    fn is_known(column: &str) -> bool {
+        ["host", "status"].contains(&column)
+    }
+
+    #[test]
+    fn missing_columns_are_all_null() {
+        let rewrite = rewrite_missing_column_null_checks(
+            r#"SELECT count(*) FROM app WHERE app.user_id IS NOT NULL AND "trace_id" IS NULL AND host IS NOT NULL"#,
+            is_known,
+        )
+        .unwrap();
+
+        assert_eq!(
+            rewrite,
+            NullCheckRewrite {
+                sql: "SELECT count(*) FROM app WHERE false AND true AND host IS NOT NULL"
+                    .to_owned(),
+                never_matching: vec!["user_id".to_owned()],
+            }
+        );
+    }
+
+    #[test]
+    fn known_columns_are_left_untouched() {
+        let sql = "SELECT count(*) FROM app WHERE status IS NULL OR Host IS NOT NULL";
+        let rewrite = rewrite_missing_column_null_checks(sql, is_known).unwrap();
+
+        assert_eq!(rewrite.sql, sql);
+        assert!(rewrite.never_matching.is_empty());
+    }
+}
@@ -20,10 +20,13 @@ use actix_web::http::header::ContentType;
 use arrow_schema::{ArrowError, DataType, Schema};
 use async_trait::async_trait;
 use chrono::Utc;
-use datafusion::logical_expr::{LogicalPlan, Projection};
+use datafusion::common::ScalarValue;
+use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
+use datafusion::logical_expr::{BinaryExpr, LogicalPlan, Operator, Projection};
 use datafusion::prelude::Expr;
 use datafusion::sql::sqlparser::parser::ParserError;
 use derive_more::FromStrError;
+use futures::StreamExt;
 use http::StatusCode;
 use serde_json::{Error as SerdeError, Value as JsonValue};
 use std::collections::HashMap;
@@ -32,37 +35,48 @@ use std::sync::Arc;
 use std::thread;
 // use std::time::Duration;
 use tokio::sync::oneshot::{Receiver, Sender};
-use tokio::sync::{RwLock, mpsc};
+use tokio::sync::{RwLock, mpsc, oneshot};
 use tokio::task::JoinHandle;
 use tracing::{error, trace, warn};
 use ulid::Ulid;
 
 pub mod alert_enums;
+pub mod alert_results;
 pub mod alert_structs;
 pub mod alert_traits;
 pub mod alert_types;
 pub mod alerts_utils;
+pub mod eval_slots;
+pub mod field_paths;
+pub mod missing_columns;
+pub mod sample_count;
 pub mod target;
 
 pub use crate::alerts::alert_enums::{
-    AggregateFunction, AlertOperator, AlertState, AlertTask, AlertType, AlertVersion, EvalConfig,
-    LogicalOperator, NotificationState, Severity, WhereConfigOperator,
+    AggregateFunction, AggregateMode, AlertOperator, AlertState, AlertTask, AlertType,
+    AlertVersion, EvalConfig, LogicalOperator, MissingColumns, NotificationState, Severity,
+    WhereConfigOperator, ZeroDenominator,
 };
 pub use crate::alerts::alert_structs::{
     AlertConfig, AlertInfo, AlertRequest, AlertStateEntry, Alerts, AlertsInfo, AlertsInfoByState,
-    AlertsSummary, BasicAlertFields, Context, DeploymentInfo, RollingWindow, StateTransition,
-    ThresholdConfig,
+    AlertsSummary, BasicAlertFields, Context, DeploymentInfo, FailedAlertTask, RollingWindow,
+    ScheduledAlertTask, ScheduledAlerts, StateTransition, ThresholdConfig,
 };
 use crate::alerts::alert_traits::{AlertManagerTrait, AlertTrait};
 use crate::alerts::alert_types::ThresholdAlert;
+use crate::alerts::alerts_utils::{reconcile_alert_state, regex_literal};
 use crate::alerts::target::{NotificationConfig, TARGETS};
 use crate::handlers::http::fetch_schema;
 use crate::metastore::MetastoreError;
+use crate::metastore::metastore_traits::MetastoreObject;
+use crate::option::DeletedStreamAlertPolicy;
 // use crate::handlers::http::query::create_streams_for_distributed;
 // use crate::option::Mode;
 use crate::parseable::{PARSEABLE, StreamNotFound};
 use crate::query::{QUERY_SESSION, resolve_stream_names};
 use crate::rbac::map::SessionKey;
+use crate::rbac::role::Action;
+use crate::rbac::{Response, Users};
 use crate::storage;
 use crate::storage::ObjectStorageError;
 use crate::sync::alert_runtime;
@@ -73,6 +87,9 @@ pub type ScheduledTaskHandlers = (JoinHandle<()>, Receiver<()>, Sender<()>);
 
 pub const CURRENT_ALERTS_VERSION: &str = "v2";
 
+// number of alerts evaluated concurrently while reconciling alert states on load
+const MAX_CONCURRENT_ALERT_RECONCILIATIONS: usize = 10;
+
 pub static ALERTS: RwLock<Option<Arc<dyn AlertManagerTrait>>> = RwLock::const_new(None);
 
 pub async fn get_alert_manager() -> Arc<dyn AlertManagerTrait> {
@@ -98,6 +115,7 @@ pub fn create_default_alerts_manager() -> Alerts {
     let alerts = Alerts {
         alerts: RwLock::new(HashMap::new()),
         sender: tx,
+        failed_tasks: RwLock::new(HashMap::new()),
     };
     thread::spawn(|| alert_runtime(rx));
     alerts
@@ -332,6 +350,10 @@ impl AlertConfig {
             "does not contain" => WhereConfigOperator::DoesNotContain,
             "does not begin with" => WhereConfigOperator::DoesNotBeginWith,
             "does not end with" => WhereConfigOperator::DoesNotEndWith,
+            "matches regex" => WhereConfigOperator::Regex,
+            "does not match regex" => WhereConfigOperator::NotRegex,
+            "in" => WhereConfigOperator::In,
+            "not in" => WhereConfigOperator::NotIn,
             _ => WhereConfigOperator::Equal, // default fallback
         }
     }
@@ -376,6 +398,32 @@ impl AlertConfig {
                 "\"{column}\" ILIKE '{}'",
                 value.replace('\'', "''")
             )),
+            WhereConfigOperator::Regex | WhereConfigOperator::NotRegex => {
+                let pattern = regex_literal(value).map_err(|err| {
+                    AlertError::CustomError(format!("{err} during migration of {alert_info}"))
+                })?;
+                let operator = match operator {
+                    WhereConfigOperator::Regex => "~",
+                    _ => "!~",
+                };
+                Ok(format!("\"{column}\" {operator} {pattern}"))
+            }
+            WhereConfigOperator::In | WhereConfigOperator::NotIn => {
+                let values = alerts_utils::parse_value_list(value)
+                    .map_err(|err| {
+                        AlertError::CustomError(format!("{err} during migration of {alert_info}"))
+                    })?
+                    .iter()
+                    .map(|value| {
+                        Self::convert_value_by_data_type(column, value, schema, alert_info)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(format!(
+                    "\"{column}\" {} ({})",
+                    operator.as_str().to_uppercase(),
+                    values.join(", ")
+                ))
+            }
             _ => {
                 // Standard operators: =, !=, <, >, <=, >=
                 let formatted_value =
@@ -495,6 +543,10 @@ impl AlertConfig {
         Ok(ThresholdConfig {
             operator,
             value: threshold_value,
+            aggregate_mode: AggregateMode::Single,
+            zero_denominator: ZeroDenominator::NoTrigger,
+            missing_columns: MissingColumns::Error,
+            min_sample_count: 0,
         })
     }
 
@@ -529,6 +581,7 @@ impl AlertConfig {
             eval_start,
             eval_end,
             eval_frequency,
+            record_results: false,
         }))
     }
 
@@ -607,10 +660,27 @@ impl AlertConfig {
     pub async fn trigger_notifications(&self, message: String) -> Result<(), AlertError> {
         let mut context = self.get_context();
         context.message = message;
-        for target_id in &self.targets {
-            let target = TARGETS.get_target_by_id(target_id).await?;
-            trace!("Target (trigger_notifications)-\n{target:?}");
-            target.call(context.clone());
+        if !self.targets.is_empty() {
+            for target_id in &self.targets {
+                let target = TARGETS.get_target_by_id(target_id).await?;
+                trace!("Target (trigger_notifications)-\n{target:?}");
+                target.call(context.clone());
+            }
+            return Ok(());
+        }
+
+        // alerts without targets of their own are routed to the default targets of their severity
+        for target_id in default_targets(&self.severity) {
+            match TARGETS.get_target_by_id(target_id).await {
+                Ok(target) => {
+                    trace!("Default target (trigger_notifications)-\n{target:?}");
+                    target.call(context.clone());
+                }
+                Err(err) => warn!(
+                    "Default target {target_id} for severity {} is unavailable: {err}",
+                    self.severity
+                ),
+            }
         }
         Ok(())
     }
@@ -707,6 +777,65 @@ pub async fn get_number_of_agg_exprs(query: &str) -> Result<usize, AlertError> {
     _get_number_of_agg_exprs(&logical_plan)
 }
 
+/// Checks that the literal patterns of regex matches in a query compile,
+/// as invalid patterns are otherwise only rejected when the query is executed
+pub async fn validate_regex_patterns(query: &str) -> Result<(), AlertError> {
+    let logical_plan = QUERY_SESSION
+        .state()
+        .create_logical_plan(query)
+        .await
+        .map_err(|err| AlertError::CustomError(format!("Failed to parse query: {err}")))?;
+
+    let mut invalid = None;
+    logical_plan.apply_with_subqueries(|plan| {
+        plan.apply_expressions(|expr| {
+            expr.apply(|expr| {
+                if let Some(pattern) = regex_pattern(expr)
+                    && let Err(err) = regex_literal(pattern)
+                {
+                    invalid = Some(err);
+                    return Ok(TreeNodeRecursion::Stop);
+                }
+                Ok(TreeNodeRecursion::Continue)
+            })
+        })
+    })?;
+
+    match invalid {
+        Some(err) => Err(AlertError::InvalidAlertQuery(err)),
+        None => Ok(()),
+    }
+}
+
+/// The literal pattern of `~`, `~*`, `!~`, `!~*` and of the `regexp_*` functions
+fn regex_pattern(expr: &Expr) -> Option<&str> {
+    let pattern = match expr {
+        Expr::BinaryExpr(BinaryExpr {
+            op:
+                Operator::RegexMatch
+                | Operator::RegexIMatch
+                | Operator::RegexNotMatch
+                | Operator::RegexNotIMatch,
+            right,
+            ..
+        }) => right.as_ref(),
+        Expr::ScalarFunction(function) if function.name().starts_with("regexp_") => {
+            function.args.get(1)?
+        }
+        _ => return None,
+    };
+
+    match pattern {
+        Expr::Literal(
+            ScalarValue::Utf8(Some(pattern))
+            | ScalarValue::LargeUtf8(Some(pattern))
+            | ScalarValue::Utf8View(Some(pattern)),
+            _,
+        ) => Some(pattern),
+        _ => None,
+    }
+}
+
 /// Extract the projection which deals with aggregation
 pub async fn get_aggregate_projection(query: &str) -> Result<String, AlertError> {
     let session_state = QUERY_SESSION.state();
@@ -958,6 +1087,12 @@ pub enum AlertError {
     ValidationFailure(String),
     #[error(transparent)]
     MetastoreError(#[from] MetastoreError),
+    #[error("Alert limit exceeded: {0}")]
+    LimitExceeded(String),
+    #[error("Alert evaluation timed out after {0} seconds")]
+    EvalTimeout(u64),
+    #[error("Alert query would scan {scan_size} MiB, above the limit of {limit} MiB")]
+    ScanLimitExceeded { scan_size: u64, limit: u64 },
 }
 
 impl actix_web::ResponseError for AlertError {
@@ -986,10 +1121,21 @@ impl actix_web::ResponseError for AlertError {
             Self::Unimplemented(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::NotPresentInOSS(_) => StatusCode::BAD_REQUEST,
             Self::MetastoreError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::LimitExceeded(_) => StatusCode::FORBIDDEN,
+            Self::EvalTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Self::ScanLimitExceeded { .. } => StatusCode::BAD_REQUEST,
         }
     }
 
     fn error_response(&self) -> actix_web::HttpResponse<actix_web::body::BoxBody> {
+        // clients tell a stream removed since the alert was created from a malformed query
+        if let Self::StreamNotFound(StreamNotFound(stream)) = self {
+            return actix_web::HttpResponse::build(self.status_code()).json(serde_json::json!({
+                "error": "streamNotFound",
+                "stream": stream,
+                "message": format!("stream {stream} doesn't exist, it may have been deleted"),
+            }));
+        }
         actix_web::HttpResponse::build(self.status_code())
             .insert_header(ContentType::plaintext())
             .body(self.to_string())
@@ -1003,7 +1149,9 @@ impl AlertManagerTrait for Alerts {
         // Get alerts path and read raw bytes for migration handling
         let raw_objects = PARSEABLE.metastore.get_alerts().await?;
 
-        let mut map = self.alerts.write().await;
+        let mut alerts = Vec::with_capacity(raw_objects.len());
+        // a single alert failing to schedule must not keep the others from running
+        let mut failed_tasks = HashMap::new();
 
         for raw_bytes in raw_objects {
             // First, try to parse as JSON Value to check version
@@ -1056,40 +1204,64 @@ impl AlertManagerTrait for Alerts {
                     Box::new(ThresholdAlert::from(alert)) as Box<dyn AlertTrait>
                 }
                 AlertType::Anomaly(_) => {
-                    return Err(anyhow::Error::msg(
-                        AlertError::NotPresentInOSS("anomaly").to_string(),
-                    ));
+                    failed_tasks
+                        .insert(alert.id, AlertError::NotPresentInOSS("anomaly").to_string());
+                    continue;
                 }
                 AlertType::Forecast(_) => {
-                    return Err(anyhow::Error::msg(
+                    failed_tasks.insert(
+                        alert.id,
                         AlertError::NotPresentInOSS("forecast").to_string(),
-                    ));
+                    );
+                    continue;
                 }
             };
+            alerts.push(alert);
+        }
 
-            // Create alert task iff alert's state is not paused
-            if alert.get_state().eq(&AlertState::Disabled) {
-                map.insert(*alert.get_id(), alert);
-                continue;
-            }
+        // correct states that drifted while the server was down, before any task is scheduled.
+        // The alerts aren't locked meanwhile, each evaluation may take up to the evaluation timeout
+        if PARSEABLE.options.alert_reconcile_on_load {
+            alerts = futures::stream::iter(alerts)
+                .map(|mut alert| async move {
+                    if let Err(e) = reconcile_alert_state(alert.as_mut()).await {
+                        warn!("Failed to reconcile state of alert {}: {e}", alert.get_id());
+                    }
+                    alert
+                })
+                .buffer_unordered(MAX_CONCURRENT_ALERT_RECONCILIATIONS)
+                .collect()
+                .await;
+        }
 
+        // Create alert task iff alert's state is not paused
+        let scheduled: Vec<Box<dyn AlertTrait>> = alerts
+            .iter()
+            .filter(|alert| !alert.get_state().is_paused())
+            .map(|alert| alert.clone_box())
+            .collect();
+        self.alerts
+            .write()
+            .await
+            .extend(alerts.into_iter().map(|alert| (*alert.get_id(), alert)));
+
+        for alert in scheduled {
             match self.sender.send(AlertTask::Create(alert.clone_box())).await {
                 Ok(_) => {}
                 Err(e) => {
                     warn!("Failed to create alert task: {e}\nRetrying...");
                     // Retry sending the task
-                    match self.sender.send(AlertTask::Create(alert.clone_box())).await {
-                        Ok(_) => {}
-                        Err(e) => {
-                            error!("Failed to create alert task: {e}");
-                            continue;
-                        }
+                    if let Err(e) = self.sender.send(AlertTask::Create(alert.clone_box())).await {
+                        failed_tasks.insert(*alert.get_id(), e.to_string());
                     }
                 }
             };
+        }
 
-            map.insert(*alert.get_id(), alert);
+        for (id, err) in &failed_tasks {
+            error!("Failed to schedule alert {id}: {err}");
         }
+        *self.failed_tasks.write().await = failed_tasks;
 
         Ok(())
     }
@@ -1208,10 +1380,9 @@ impl AlertManagerTrait for Alerts {
             };
 
             let current_state = *alert.get_state();
-            let should_delete_task =
-                new_state.eq(&AlertState::Disabled) && !current_state.eq(&AlertState::Disabled);
+            let should_delete_task = new_state.is_paused() && !current_state.is_paused();
             let should_create_task =
-                current_state.eq(&AlertState::Disabled) && new_state.eq(&AlertState::NotTriggered);
+                current_state.is_paused() && new_state.eq(&AlertState::NotTriggered);
 
             if new_state.eq(&AlertState::Disabled) && current_state.eq(&AlertState::Disabled) {
                 return Err(AlertError::InvalidStateChange(
@@ -1283,6 +1454,7 @@ impl AlertManagerTrait for Alerts {
 
     /// Remove alert and scheduled task from disk and memory
     async fn delete(&self, alert_id: Ulid) -> Result<(), AlertError> {
+        self.failed_tasks.write().await.remove(&alert_id);
         if self.alerts.write().await.remove(&alert_id).is_some() {
             trace!("removed alert from memory");
         } else {
@@ -1305,10 +1477,12 @@ impl AlertManagerTrait for Alerts {
 
     /// Start a scheduled alert task
     async fn start_task(&self, alert: Box<dyn AlertTrait>) -> Result<(), AlertError> {
+        let id = *alert.get_id();
         self.sender
             .send(AlertTask::Create(alert))
             .await
             .map_err(|e| AlertError::CustomError(e.to_string()))?;
+        self.failed_tasks.write().await.remove(&id);
         Ok(())
     }
 
@@ -1322,6 +1496,30 @@ impl AlertManagerTrait for Alerts {
         Ok(())
     }
 
+    /// List the evaluation tasks scheduled on the alert runtime
+    async fn scheduled_tasks(&self) -> Result<Vec<ScheduledAlertTask>, AlertError> {
+        let (tx, rx) = oneshot::channel();
+        self.sender
+            .send(AlertTask::List(tx))
+            .await
+            .map_err(|e| AlertError::CustomError(e.to_string()))?;
+
+        rx.await.map_err(|e| AlertError::CustomError(e.to_string()))
+    }
+
+    /// List the alerts that could not be scheduled since the last load
+    async fn failed_tasks(&self) -> Vec<FailedAlertTask> {
+        self.failed_tasks
+            .read()
+            .await
+            .iter()
+            .map(|(id, error)| FailedAlertTask {
+                id: *id,
+                error: error.clone(),
+            })
+            .collect()
+    }
+
     /// List tags from all alerts
     /// This function returns a list of unique tags from all alerts
     async fn list_tags(&self) -> Vec<String> {
@@ -1342,11 +1540,208 @@ impl AlertManagerTrait for Alerts {
     }
 }
 
-// TODO: add RBAC
+/// Targets configured for `severity` at the deployment level
+fn default_targets(severity: &Severity) -> &'static [Ulid] {
+    PARSEABLE
+        .options
+        .alert_severity_targets
+        .as_ref()
+        .and_then(|routing| routing.get(&severity.to_string().to_lowercase()))
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+/// Alert titles have to be unique (case-insensitive) amongst the alerts on a dataset,
+/// the same title can be reused for alerts on different datasets
+pub fn validate_unique_title<'a>(
+    alert: &dyn AlertTrait,
+    existing_alerts: impl IntoIterator<Item = &'a dyn AlertTrait>,
+) -> Result<(), AlertError> {
+    let title = alert.get_title().trim();
+    let duplicate = existing_alerts.into_iter().find(|existing| {
+        existing.get_id() != alert.get_id()
+            && existing.get_title().trim().eq_ignore_ascii_case(title)
+            && existing
+                .get_datasets()
+                .iter()
+                .any(|dataset| alert.get_datasets().contains(dataset))
+    });
+
+    if let Some(duplicate) = duplicate {
+        return Err(AlertError::CustomError(format!(
+            "An alert with the title \"{}\" already exists for the dataset(s) {} (ID- {})",
+            duplicate.get_title(),
+            duplicate.get_datasets().join(", "),
+            duplicate.get_id()
+        )));
+    }
+
+    Ok(())
+}
+
+/// A new alert must not take the deployment, or any of its datasets, over the configured number of alerts
+pub fn validate_alert_limits<'a>(
+    alert: &dyn AlertTrait,
+    existing_alerts: impl IntoIterator<Item = &'a dyn AlertTrait>,
+    max_total: Option<usize>,
+    max_per_stream: Option<usize>,
+) -> Result<(), AlertError> {
+    let existing_alerts = existing_alerts
+        .into_iter()
+        .filter(|existing| existing.get_id() != alert.get_id())
+        .collect::<Vec<_>>();
+
+    if let Some(max_total) = max_total
+        && existing_alerts.len() >= max_total
+    {
+        return Err(AlertError::LimitExceeded(format!(
+            "the deployment already has the maximum of {max_total} alerts"
+        )));
+    }
+
+    if let Some(max_per_stream) = max_per_stream {
+        for dataset in alert.get_datasets() {
+            let count = existing_alerts
+                .iter()
+                .filter(|existing| existing.get_datasets().contains(dataset))
+                .count();
+            if count >= max_per_stream {
+                return Err(AlertError::LimitExceeded(format!(
+                    "dataset {dataset} already has the maximum of {max_per_stream} alerts"
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// An alert must not notify more than the configured number of targets
+pub fn validate_target_count(
+    alert: &dyn AlertTrait,
+    max_targets: Option<usize>,
+) -> Result<(), AlertError> {
+    if let Some(max_targets) = max_targets
+        && alert.get_targets().len() > max_targets
+    {
+        return Err(AlertError::LimitExceeded(format!(
+            "an alert can have at most {max_targets} targets, found {}",
+            alert.get_targets().len()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Stops the alerts querying a deleted stream, marking them as stream-missing or deleting them
+/// according to the deleted stream alert policy. Returns the ids of the affected alerts.
+pub async fn handle_deleted_stream(stream_name: &str) -> Result<Vec<Ulid>, AlertError> {
+    let guard = ALERTS.read().await;
+    let Some(alerts) = guard.as_ref() else {
+        return Ok(Vec::new());
+    };
+
+    let mut affected = Vec::new();
+    for (id, alert) in alerts.get_all_alerts().await {
+        if !alert
+            .get_datasets()
+            .iter()
+            .any(|dataset| dataset == stream_name)
+        {
+            continue;
+        }
+
+        match PARSEABLE.options.deleted_stream_alert_policy {
+            DeletedStreamAlertPolicy::Mark => {
+                if alert.get_state().eq(&AlertState::StreamMissing) {
+                    continue;
+                }
+                alerts
+                    .update_state(id, AlertState::StreamMissing, None)
+                    .await?;
+            }
+            DeletedStreamAlertPolicy::Delete => {
+                PARSEABLE.metastore.delete_alert(&*alert).await?;
+                // state doesn't matter for deletion
+                let state_to_delete = AlertStateEntry::new(id, AlertState::NotTriggered);
+                PARSEABLE
+                    .metastore
+                    .delete_alert_state(&state_to_delete as &dyn MetastoreObject)
+                    .await?;
+                alerts.delete(id).await?;
+                alerts.delete_task(id).await?;
+                target::forget_alert(id);
+            }
+        }
+        affected.push(id);
+    }
+
+    Ok(affected)
+}
+
+/// An alert whose query reads a stream
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertDependent {
+    pub id: Ulid,
+    pub title: String,
+    pub state: AlertState,
+}
+
+/// The alerts whose query reads the stream, in any of its tables or joins. The tables are
+/// resolved from the parsed query so that aliased streams are matched too, the datasets saved
+/// with the alert are used for a query which can't be parsed. Only the alerts whose query the
+/// user is allowed to run are listed.
+pub async fn alerts_querying(stream_name: &str, session: &SessionKey) -> Vec<AlertDependent> {
+    let dependents = {
+        let guard = ALERTS.read().await;
+        let Some(alerts) = guard.as_ref() else {
+            return Vec::new();
+        };
+
+        alerts
+            .get_all_alerts()
+            .await
+            .into_values()
+            .filter(|alert| {
+                resolve_stream_names(alert.get_query())
+                    .unwrap_or_else(|_| alert.get_datasets().to_vec())
+                    .iter()
+                    .any(|table| table == stream_name)
+            })
+            .map(|alert| {
+                (
+                    alert.get_query().to_owned(),
+                    AlertDependent {
+                        id: *alert.get_id(),
+                        title: alert.get_title().to_owned(),
+                        state: *alert.get_state(),
+                    },
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut authorized = Vec::with_capacity(dependents.len());
+    for (query, dependent) in dependents {
+        if user_auth_for_query(session, &query).await.is_ok() {
+            authorized.push(dependent);
+        }
+    }
+
+    authorized
+}
+
+/// Summary of the alerts on the streams the user can query,
+/// empty for users who aren't allowed to get alerts
 pub async fn get_alerts_summary(key: &SessionKey) -> Result<AlertsSummary, AlertError> {
     let guard = ALERTS.read().await;
     let alerts = if let Some(alerts) = guard.as_ref() {
-        alerts.list_alerts_for_user(key.clone(), vec![]).await?
+        if Users.authorize(key.clone(), Action::GetAlert, None, None) == Response::Authorized {
+            alerts.list_alerts_for_user(key.clone(), vec![]).await?
+        } else {
+            vec![]
+        }
     } else {
         return Err(AlertError::CustomError("No AlertManager registered".into()));
     };
@@ -1372,7 +1767,7 @@ pub async fn get_alerts_summary(key: &SessionKey) -> Result<AlertsSummary, Alert
                     severity: alert.severity.clone(),
                 });
             }
-            AlertState::Disabled => {
+            AlertState::Disabled | AlertState::StreamMissing => {
                 disabled += 1;
                 disabled_alerts.push(AlertsInfo {
                     title: alert.title.clone(),
@@ -1427,3 +1822,229 @@ fn get_severity_priority(severity: &Severity) -> u8 {
         Severity::Low => 3,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeDelta};
+
+    use super::*;
+    use crate::alerts::alert_structs::{ConditionConfig, Conditions, GroupResult};
+
+    fn threshold_alert(title: &str, datasets: &[&str]) -> ThresholdAlert {
+        ThresholdAlert::from(AlertConfig {
+            version: AlertVersion::V2,
+            id: Ulid::new(),
+            severity: Severity::High,
+            title: title.to_owned(),
+            query: "SELECT COUNT(*) FROM test".to_owned(),
+            datasets: datasets.iter().map(|d| d.to_string()).collect(),
+            alert_type: AlertType::Threshold,
+            threshold_config: ThresholdConfig {
+                operator: AlertOperator::GreaterThan,
+                value: 100.0,
+                aggregate_mode: AggregateMode::Single,
+                zero_denominator: ZeroDenominator::NoTrigger,
+                missing_columns: MissingColumns::Error,
+                min_sample_count: 0,
+            },
+            eval_config: EvalConfig::RollingWindow(RollingWindow::default()),
+            targets: vec![],
+            state: AlertState::NotTriggered,
+            notification_state: NotificationState::Notify,
+            notification_config: NotificationConfig::default(),
+            created: Utc::now(),
+            tags: None,
+            last_triggered_at: None,
+            other_fields: None,
+        })
+    }
+
+    #[test]
+    fn duplicate_title_on_same_stream_is_rejected() {
+        let first = threshold_alert("High CPU", &["app_logs"]);
+        let second = threshold_alert("high cpu", &["app_logs"]);
+
+        assert!(validate_unique_title(&first, Vec::<&dyn AlertTrait>::new()).is_ok());
+        assert!(matches!(
+            validate_unique_title(&second, [&first as &dyn AlertTrait]),
+            Err(AlertError::CustomError(_))
+        ));
+    }
+
+    #[test]
+    fn same_title_on_different_streams_is_allowed() {
+        let first = threshold_alert("High CPU", &["app_logs"]);
+        let second = threshold_alert("High CPU", &["infra_logs"]);
+
+        assert!(validate_unique_title(&second, [&first as &dyn AlertTrait]).is_ok());
+    }
+
+    #[test]
+    fn updating_alert_keeps_its_own_title() {
+        let alert = threshold_alert("High CPU", &["app_logs"]);
+
+        assert!(validate_unique_title(&alert, [&alert as &dyn AlertTrait]).is_ok());
+    }
+
+    #[test]
+    fn ratio_with_zero_denominator_follows_policy() {
+        let mut config = ThresholdConfig {
+            operator: AlertOperator::LessThan,
+            value: 0.05,
+            aggregate_mode: AggregateMode::Ratio,
+            zero_denominator: ZeroDenominator::NoTrigger,
+            missing_columns: MissingColumns::Error,
+            min_sample_count: 0,
+        };
+        assert!(config.is_breached(0.01));
+        assert!(!config.is_breached(f64::NAN));
+
+        config.zero_denominator = ZeroDenominator::Trigger;
+        assert!(config.is_breached(f64::NAN));
+    }
+
+    #[test]
+    fn groups_below_min_sample_count_are_not_evaluated() {
+        let mut config = ThresholdConfig {
+            operator: AlertOperator::GreaterThan,
+            value: 0.5,
+            aggregate_mode: AggregateMode::Single,
+            zero_denominator: ZeroDenominator::NoTrigger,
+            missing_columns: MissingColumns::Error,
+            min_sample_count: 0,
+        };
+        let group = |sample_count| GroupResult {
+            group_values: HashMap::new(),
+            aggregate_value: 1.0,
+            sample_count,
+        };
+        assert!(config.has_enough_samples(&group(None)));
+
+        config.min_sample_count = 50;
+        assert!(!config.has_enough_samples(&group(Some(3))));
+        assert!(!config.has_enough_samples(&group(None)));
+        assert!(config.has_enough_samples(&group(Some(50))));
+    }
+
+    #[test]
+    fn alert_limits_are_enforced() {
+        let existing = [
+            threshold_alert("High CPU", &["app_logs"]),
+            threshold_alert("High memory", &["app_logs"]),
+            threshold_alert("Errors", &["nginx"]),
+        ];
+        let existing = || existing.iter().map(|a| a as &dyn AlertTrait);
+        let alert = threshold_alert("Slow requests", &["app_logs"]);
+
+        assert!(validate_alert_limits(&alert, existing(), None, None).is_ok());
+        assert!(validate_alert_limits(&alert, existing(), Some(4), Some(3)).is_ok());
+        assert!(matches!(
+            validate_alert_limits(&alert, existing(), Some(3), None),
+            Err(AlertError::LimitExceeded(_))
+        ));
+        assert!(matches!(
+            validate_alert_limits(&alert, existing(), None, Some(2)),
+            Err(AlertError::LimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn target_count_is_enforced() {
+        let mut alert = threshold_alert("Slow requests", &["app_logs"]);
+        alert.targets = vec![Ulid::new(), Ulid::new()];
+
+        assert!(validate_target_count(&alert, None).is_ok());
+        assert!(validate_target_count(&alert, Some(2)).is_ok());
+        assert!(matches!(
+            validate_target_count(&alert, Some(1)),
+            Err(AlertError::LimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn warmup_suppresses_recently_created_alerts() {
+        let now: DateTime<Utc> = "2024-01-01T01:00:00Z".parse().unwrap();
+        let warmup = TimeDelta::minutes(30);
+
+        assert!(alerts_utils::in_warmup("2024-01-01T00:45:00Z", warmup, now));
+        assert!(!alerts_utils::in_warmup(
+            "2024-01-01T00:15:00Z",
+            warmup,
+            now
+        ));
+        assert!(!alerts_utils::in_warmup(
+            "2024-01-01T00:45:00Z",
+            TimeDelta::zero(),
+            now
+        ));
+    }
+
+    #[test]
+    fn regex_conditions_are_validated() {
+        let conditions = |operator, value: &str| Conditions {
+            operator: Some(LogicalOperator::And),
+            condition_config: vec![ConditionConfig {
+                column: "message".to_owned(),
+                operator,
+                value: Some(value.to_owned()),
+            }],
+        };
+
+        assert_eq!(
+            alerts_utils::get_filter_string(&conditions(
+                WhereConfigOperator::Regex,
+                "^timeout after \\d+'s"
+            ))
+            .unwrap(),
+            "\"message\" ~ '^timeout after \\d+''s'"
+        );
+        assert_eq!(
+            alerts_utils::get_filter_string(&conditions(WhereConfigOperator::NotRegex, "^GET"))
+                .unwrap(),
+            "\"message\" !~ '^GET'"
+        );
+        assert!(
+            alerts_utils::get_filter_string(&conditions(WhereConfigOperator::Regex, "(unclosed"))
+                .unwrap_err()
+                .contains("invalid regex pattern")
+        );
+    }
+
+    #[test]
+    fn in_conditions_match_lists() {
+        let conditions = |operator, value: &str| Conditions {
+            operator: Some(LogicalOperator::And),
+            condition_config: vec![ConditionConfig {
+                column: "status".to_owned(),
+                operator,
+                value: Some(value.to_owned()),
+            }],
+        };
+
+        assert_eq!(
+            alerts_utils::get_filter_string(&conditions(WhereConfigOperator::In, "500, 502,503"))
+                .unwrap(),
+            "\"status\" IN (500, 502, 503)"
+        );
+        assert_eq!(
+            alerts_utils::get_filter_string(&conditions(
+                WhereConfigOperator::NotIn,
+                r#"["ok", "can't"]"#
+            ))
+            .unwrap(),
+            "\"status\" NOT IN ('ok', 'can''t')"
+        );
+        assert!(
+            alerts_utils::get_filter_string(&conditions(WhereConfigOperator::In, "500, error"))
+                .is_err()
+        );
+        assert!(
+            alerts_utils::get_filter_string(&conditions(WhereConfigOperator::In, " , ")).is_err()
+        );
+        let single = Conditions {
+            operator: None,
+            ..conditions(WhereConfigOperator::In, "[500, 502]")
+        };
+        assert_eq!(single.generate_filter_message(), "status in (500, 502)");
+    }
+}
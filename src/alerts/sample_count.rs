@@ -0,0 +1,70 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Number of rows an alert is evaluated against. Alerts with a `minSampleCount` only compare
+//! groups which aggregate at least that many rows to their threshold, their query is extended
+//! with a `count(*)` computed alongside the aggregate.
+
+use datafusion::sql::sqlparser::ast::{Ident, SelectItem, SetExpr, Statement};
+use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
+use datafusion::sql::sqlparser::parser::Parser;
+
+use super::AlertError;
+
+/// Column of the rows aggregated by each group of the alert query
+pub const SAMPLE_COUNT_COLUMN: &str = "p_sample_count";
+
+/// Adds the count of the rows aggregated by each group to the projection of the alert query
+pub fn with_sample_count(sql: &str) -> Result<String, AlertError> {
+    let dialect = PostgreSqlDialect {};
+    let mut statements = Parser::parse_sql(&dialect, &sql.replace('`', "\""))?;
+    let [Statement::Query(query)] = statements.as_mut_slice() else {
+        return Err(AlertError::InvalidAlertQuery(
+            "minSampleCount requires a single SELECT query".into(),
+        ));
+    };
+    let SetExpr::Select(select) = query.body.as_mut() else {
+        return Err(AlertError::InvalidAlertQuery(
+            "minSampleCount requires a single SELECT query".into(),
+        ));
+    };
+
+    select.projection.push(SelectItem::ExprWithAlias {
+        expr: Parser::new(&dialect)
+            .try_with_sql("count(*)")?
+            .parse_expr()?,
+        alias: Ident::with_quote('"', SAMPLE_COUNT_COLUMN),
+    });
+
+    Ok(statements[0].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_count_is_computed_alongside_the_aggregate() {
+        assert_eq!(
+            with_sample_count("SELECT host, avg(latency) AS latency FROM app GROUP BY host")
+                .unwrap(),
+            r#"SELECT host, avg(latency) AS latency, count(*) AS "p_sample_count" FROM app GROUP BY host"#
+        );
+        assert!(with_sample_count("SELECT 1 UNION SELECT 2").is_err());
+    }
+}
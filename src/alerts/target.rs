@@ -17,25 +17,26 @@
  */
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     sync::{Arc, Mutex},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use async_trait::async_trait;
 use base64::Engine;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use http::{HeaderMap, HeaderValue, header::AUTHORIZATION};
 use itertools::Itertools;
 use once_cell::sync::Lazy;
 use reqwest::ClientBuilder;
 use serde_json::{Value, json};
 use tokio::sync::RwLock;
-use tracing::{error, trace, warn};
+use tracing::{error, info, trace, warn};
 use ulid::Ulid;
 use url::Url;
 
 use crate::{
+    OutboundTls,
     alerts::{AlertError, AlertState, Context, alert_traits::CallableTarget},
     metastore::metastore_traits::MetastoreObject,
     parseable::PARSEABLE,
@@ -48,6 +49,85 @@ pub static TARGETS: Lazy<TargetConfigs> = Lazy::new(|| TargetConfigs {
     target_configs: RwLock::new(HashMap::new()),
 });
 
+/// When each target was last notified of each triggered alert, keyed by (alert id, target id)
+static LAST_NOTIFIED: Lazy<Mutex<HashMap<(Ulid, Ulid), Instant>>> = Lazy::new(Mutex::default);
+
+/// Upper bound of the cooldown of a target, in minutes
+const MAX_COOLDOWN_MINUTES: u64 = 7 * 24 * 60;
+
+pub static DELIVERIES: Lazy<DeliveryReceipts> = Lazy::new(DeliveryReceipts::default);
+
+/// Number of delivery receipts kept per alert, the oldest ones are dropped first
+const MAX_DELIVERIES_PER_ALERT: usize = 100;
+
+/// Outcome of notifying a target of an alert
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeliveryReceipt {
+    pub target_id: Ulid,
+    pub target_name: String,
+    /// state of the alert the target was notified of
+    pub alert_state: AlertState,
+    pub success: bool,
+    /// unset when the target couldn't be reached
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub attempted_at: DateTime<Utc>,
+}
+
+/// Outcome of checking that the endpoint of a target accepts requests
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetCheck {
+    pub target_id: Ulid,
+    pub target_name: String,
+    pub reachable: bool,
+    pub error: Option<String>,
+}
+
+/// How long the reachability check of a target waits for its endpoint to answer
+const TARGET_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Latest delivery receipts of each alert, held in memory on the node evaluating the alerts
+#[derive(Debug, Default)]
+pub struct DeliveryReceipts {
+    receipts: Mutex<HashMap<Ulid, VecDeque<DeliveryReceipt>>>,
+}
+
+impl DeliveryReceipts {
+    fn record(&self, alert_id: Ulid, receipt: DeliveryReceipt) {
+        let mut receipts = self.receipts.lock().unwrap();
+        let receipts = receipts.entry(alert_id).or_default();
+        if receipts.len() >= MAX_DELIVERIES_PER_ALERT {
+            receipts.pop_front();
+        }
+        receipts.push_back(receipt);
+    }
+
+    /// Receipts of an alert, latest first
+    pub fn list(&self, alert_id: Ulid) -> Vec<DeliveryReceipt> {
+        self.receipts
+            .lock()
+            .unwrap()
+            .get(&alert_id)
+            .map(|receipts| receipts.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn remove(&self, alert_id: Ulid) {
+        self.receipts.lock().unwrap().remove(&alert_id);
+    }
+}
+
+/// Drops the notification history of a deleted alert
+pub fn forget_alert(alert_id: Ulid) {
+    DELIVERIES.remove(alert_id);
+    LAST_NOTIFIED
+        .lock()
+        .unwrap()
+        .retain(|(id, _), _| *id != alert_id);
+}
+
 #[derive(Debug)]
 pub struct TargetConfigs {
     pub target_configs: RwLock<HashMap<Ulid, Target>>,
@@ -116,6 +196,10 @@ impl TargetConfigs {
             .remove(target_id)
             .ok_or(AlertError::InvalidTargetID(target_id.to_string()))?;
         PARSEABLE.metastore.delete_target(&target).await?;
+        LAST_NOTIFIED
+            .lock()
+            .unwrap()
+            .retain(|(_, id), _| id != target_id);
         Ok(target)
     }
 }
@@ -141,11 +225,41 @@ pub struct Target {
     pub name: String,
     #[serde(flatten)]
     pub target: TargetType,
+    /// Minimum number of minutes between two triggered notifications of the same alert,
+    /// applies across separate firings and on top of the retries of the notification config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cooldown: Option<u64>,
     #[serde(default = "Ulid::new")]
     pub id: Ulid,
 }
 
 impl Target {
+    /// Sends a `HEAD` request to the endpoint of the target, any response counts as reachable
+    /// since webhooks aren't expected to handle anything but their notifications
+    pub async fn check_reachability(&self) -> TargetCheck {
+        let (endpoint, skip_tls_check) = match &self.target {
+            TargetType::Slack(target) => (&target.endpoint, false),
+            TargetType::Other(target) => (&target.endpoint, target.skip_tls_check),
+            TargetType::AlertManager(target) => (&target.endpoint, target.skip_tls_check),
+        };
+
+        let mut builder = default_client_builder().timeout(TARGET_CHECK_TIMEOUT);
+        if skip_tls_check {
+            builder = builder.danger_accept_invalid_certs(true)
+        }
+        let result = match builder.build() {
+            Ok(client) => client.head(endpoint.clone()).send().await.map(|_| ()),
+            Err(err) => Err(err),
+        };
+
+        TargetCheck {
+            target_id: self.id,
+            target_name: self.name.clone(),
+            reachable: result.is_ok(),
+            error: result.err().map(|err| err.to_string()),
+        }
+    }
+
     pub fn mask(self) -> Value {
         match self.target {
             TargetType::Slack(slack_web_hook) => {
@@ -157,6 +271,7 @@ impl Target {
                 };
                 json!({
                    "name":self.name,
+                   "cooldown":self.cooldown,
                    "type":"slack",
                    "endpoint":masked_endpoint,
                    "id":self.id
@@ -171,6 +286,7 @@ impl Target {
                 };
                 json!({
                     "name":self.name,
+                    "cooldown":self.cooldown,
                     "type":"webhook",
                     "endpoint":masked_endpoint,
                     "headers":other_web_hook.headers,
@@ -189,6 +305,7 @@ impl Target {
                     let password = "********";
                     json!({
                         "name":self.name,
+                        "cooldown":self.cooldown,
                         "type":"webhook",
                         "endpoint":masked_endpoint,
                         "username":auth.username,
@@ -199,6 +316,7 @@ impl Target {
                 } else {
                     json!({
                         "name":self.name,
+                        "cooldown":self.cooldown,
                         "type":"webhook",
                         "endpoint":masked_endpoint,
                         "username":Value::Null,
@@ -224,7 +342,7 @@ impl Target {
                 if !state.timed_out {
                     // call once and then start sleeping
                     // reduce repeats by 1
-                    call_target(self.target.clone(), context.clone());
+                    self.notify_triggered(context.clone());
                     // set state
                     state.timed_out = true;
                     state.awaiting_resolve = true;
@@ -244,20 +362,48 @@ impl Target {
                     }
                 }
 
-                call_target(self.target.clone(), context);
+                call_target(self, context);
             }
             // do not send out any notifs
             // (an eval should not have run!)
-            AlertState::Disabled => {}
+            AlertState::Disabled | AlertState::StreamMissing => {}
         }
     }
 
+    /// Notifies the target of a triggered alert, unless it was already notified of the alert within its cooldown
+    fn notify_triggered(&self, context: Context) {
+        let alert_id = context.alert_info.alert_id;
+        if let Some(cooldown) = self.cooldown {
+            let period = cooldown
+                .checked_mul(60)
+                .map_or(Duration::MAX, Duration::from_secs);
+            let mut last_notified = LAST_NOTIFIED.lock().unwrap();
+            let now = Instant::now();
+            match last_notified.get(&(alert_id, self.id)) {
+                Some(at) if now.duration_since(*at) < period => {
+                    info!(
+                        "Suppressed notification of alert {alert_id} to target {} ({}), last notified {}s ago with a cooldown of {cooldown}m",
+                        self.name,
+                        self.id,
+                        now.duration_since(*at).as_secs()
+                    );
+                    return;
+                }
+                _ => {
+                    last_notified.insert((alert_id, self.id), now);
+                }
+            }
+        }
+
+        call_target(self, context);
+    }
+
     fn spawn_timeout_task(&self, target_timeout: &NotificationConfig, alert_context: Context) {
         trace!("repeat-\n{target_timeout:?}");
         let state = Arc::clone(&target_timeout.state);
         let retry = target_timeout.times;
         let timeout = target_timeout.interval;
-        let target = self.target.clone();
+        let target = self.clone();
         let alert_id = alert_context.alert_info.alert_id;
 
         let sleep_and_check_if_call =
@@ -304,7 +450,7 @@ impl Target {
                     let should_call =
                         sleep_and_check_if_call(Arc::clone(&state), current_state).await;
                     if should_call {
-                        call_target(target.clone(), alert_context.clone())
+                        target.notify_triggered(alert_context.clone())
                     }
                 },
                 Retry::Finite(times) => {
@@ -322,7 +468,7 @@ impl Target {
                         let should_call =
                             sleep_and_check_if_call(Arc::clone(&state), current_state).await;
                         if should_call {
-                            call_target(target.clone(), alert_context.clone())
+                            target.notify_triggered(alert_context.clone())
                         }
                     }
                 }
@@ -342,9 +488,43 @@ impl MetastoreObject for Target {
     }
 }
 
-fn call_target(target: TargetType, context: Context) {
+/// Notifies the target in the background and records the outcome in [`DELIVERIES`]
+fn call_target(target: &Target, context: Context) {
     trace!("Calling target with context- {context:?}");
-    tokio::spawn(async move { target.call(&context).await });
+    let target = target.clone();
+    tokio::spawn(async move {
+        let alert_id = context.alert_info.alert_id;
+        let (status_code, error) = match target.target.call(&context).await {
+            Ok(status) if status.is_success() => (Some(status.as_u16()), None),
+            Ok(status) => (
+                Some(status.as_u16()),
+                Some(format!("target responded with {status}")),
+            ),
+            Err(err) => (
+                err.status().map(|status| status.as_u16()),
+                Some(err.to_string()),
+            ),
+        };
+        if let Some(error) = &error {
+            error!(
+                "Couldn't deliver notification of alert {alert_id} to target {} ({}): {error}",
+                target.name, target.id
+            );
+        }
+
+        DELIVERIES.record(
+            alert_id,
+            DeliveryReceipt {
+                target_id: target.id,
+                target_name: target.name,
+                alert_state: context.alert_info.alert_state,
+                success: error.is_none(),
+                status_code,
+                error,
+                attempted_at: Utc::now(),
+            },
+        );
+    });
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -361,6 +541,8 @@ pub struct TargetVerifier {
     pub target: TargetType,
     #[serde(default)]
     pub notification_config: Option<NotificationConfigVerifier>,
+    #[serde(default)]
+    pub cooldown: Option<u64>,
     #[serde(default = "Ulid::new")]
     pub id: Ulid,
 }
@@ -388,9 +570,18 @@ impl TryFrom<TargetVerifier> for Target {
             }
         }
 
+        if let Some(cooldown) = value.cooldown
+            && cooldown > MAX_COOLDOWN_MINUTES
+        {
+            return Err(format!(
+                "cooldown of {cooldown} minutes exceeds the maximum of {MAX_COOLDOWN_MINUTES} minutes"
+            ));
+        }
+
         Ok(Target {
             name: value.name,
             target: value.target,
+            cooldown: value.cooldown,
             id: value.id,
         })
     }
@@ -410,7 +601,7 @@ pub enum TargetType {
 }
 
 impl TargetType {
-    pub async fn call(&self, payload: &Context) {
+    pub async fn call(&self, payload: &Context) -> Result<reqwest::StatusCode, reqwest::Error> {
         match self {
             TargetType::Slack(target) => target.call(payload).await,
             TargetType::Other(target) => target.call(payload).await,
@@ -421,6 +612,8 @@ impl TargetType {
 
 fn default_client_builder() -> ClientBuilder {
     ClientBuilder::new()
+        .danger_accept_invalid_certs(PARSEABLE.options.outbound_tls_skip_verify)
+        .with_outbound_ca_bundle()
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -430,7 +623,7 @@ pub struct SlackWebHook {
 
 #[async_trait]
 impl CallableTarget for SlackWebHook {
-    async fn call(&self, payload: &Context) {
+    async fn call(&self, payload: &Context) -> Result<reqwest::StatusCode, reqwest::Error> {
         let client = default_client_builder()
             .build()
             .expect("Client can be constructed on this system");
@@ -442,14 +635,17 @@ impl CallableTarget for SlackWebHook {
             AlertState::NotTriggered => {
                 serde_json::json!({ "text": payload.default_resolved_string() })
             }
-            AlertState::Disabled => {
+            AlertState::Disabled | AlertState::StreamMissing => {
                 serde_json::json!({ "text": payload.default_disabled_string() })
             }
         };
 
-        if let Err(e) = client.post(self.endpoint.clone()).json(&alert).send().await {
-            error!("Couldn't make call to webhook, error: {}", e)
-        }
+        client
+            .post(self.endpoint.clone())
+            .json(&alert)
+            .send()
+            .await
+            .map(|response| response.status())
     }
 }
 
@@ -465,7 +661,7 @@ pub struct OtherWebHook {
 
 #[async_trait]
 impl CallableTarget for OtherWebHook {
-    async fn call(&self, payload: &Context) {
+    async fn call(&self, payload: &Context) -> Result<reqwest::StatusCode, reqwest::Error> {
         let mut builder = default_client_builder();
         if self.skip_tls_check {
             builder = builder.danger_accept_invalid_certs(true)
@@ -478,16 +674,18 @@ impl CallableTarget for OtherWebHook {
         let alert = match payload.alert_info.alert_state {
             AlertState::Triggered => payload.message.clone(),
             AlertState::NotTriggered => payload.default_resolved_string(),
-            AlertState::Disabled => payload.default_disabled_string(),
+            AlertState::Disabled | AlertState::StreamMissing => payload.default_disabled_string(),
         };
 
         let request = client
             .post(self.endpoint.clone())
             .headers((&self.headers).try_into().expect("valid_headers"));
 
-        if let Err(e) = request.body(alert).send().await {
-            error!("Couldn't make call to webhook, error: {}", e)
-        }
+        request
+            .body(alert)
+            .send()
+            .await
+            .map(|response| response.status())
     }
 }
 
@@ -503,7 +701,7 @@ pub struct AlertManager {
 
 #[async_trait]
 impl CallableTarget for AlertManager {
-    async fn call(&self, payload: &Context) {
+    async fn call(&self, payload: &Context) -> Result<reqwest::StatusCode, reqwest::Error> {
         let mut builder = default_client_builder();
 
         if self.skip_tls_check {
@@ -552,16 +750,15 @@ impl CallableTarget for AlertManager {
                     .into();
             }
             AlertState::Disabled => alert["labels"]["status"] = "disabled".into(),
+            AlertState::StreamMissing => alert["labels"]["status"] = "stream-missing".into(),
         };
 
-        if let Err(e) = client
+        client
             .post(self.endpoint.clone())
             .json(&alerts)
             .send()
             .await
-        {
-            error!("Couldn't make call to alertmanager, error: {}", e)
-        }
+            .map(|response| response.status())
     }
 }
 
@@ -22,7 +22,7 @@ use clokwerk::{AsyncScheduler, Interval};
 use http::header;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::collections::HashMap;
 use std::sync::Mutex;
 use std::time::Duration;
@@ -171,16 +171,48 @@ impl Report {
         })
     }
 
+    /// The report as sent to the analytics server, without the excluded fields and with the redacted ones hashed
+    pub fn payload(&self) -> anyhow::Result<Value> {
+        let mut payload = serde_json::to_value(self)?;
+        if let Value::Object(fields) = &mut payload {
+            anonymize(
+                fields,
+                &PARSEABLE.options.analytics_exclude,
+                &PARSEABLE.options.analytics_redact,
+            );
+        }
+        Ok(payload)
+    }
+
     pub async fn send(&self) {
+        let payload = match self.payload() {
+            Ok(payload) => payload,
+            Err(err) => {
+                error!("Error while building analytics payload: {err}");
+                return;
+            }
+        };
         let _ = HTTP_CLIENT
             .post(ANALYTICS_SERVER_URL)
             .header(STREAM_NAME_HEADER_KEY, "serverusageevent")
-            .json(&self)
+            .json(&payload)
             .send()
             .await;
     }
 }
 
+fn anonymize(fields: &mut Map<String, Value>, exclude: &[String], redact: &[String]) {
+    for field in exclude {
+        fields.remove(field);
+    }
+    for field in redact {
+        if let Some(value) = fields.get_mut(field) {
+            let hash = xxhash_rust::xxh3::xxh3_64(value.to_string().as_bytes());
+            *value = Value::String(format!("{hash:016x}"));
+        }
+    }
+}
+
 /// build the node metrics for the node ingestor endpoint
 pub async fn get_analytics(_: HttpRequest) -> impl Responder {
     let json = NodeMetrics::build();
@@ -431,3 +463,32 @@ impl NodeMetrics {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn anonymize_excludes_and_redacts_fields() {
+        let Value::Object(mut fields) = json!({
+            "deployment_id": "01HZX8Q4J6Y6M5V2K0ZP3W9N7A",
+            "platform": "Kubernetes",
+            "stream_count": 3,
+        }) else {
+            unreachable!()
+        };
+        anonymize(
+            &mut fields,
+            &["platform".to_owned()],
+            &["deployment_id".to_owned(), "unknown".to_owned()],
+        );
+
+        assert!(!fields.contains_key("platform"));
+        assert_eq!(fields["stream_count"], json!(3));
+        let redacted = fields["deployment_id"].as_str().unwrap();
+        assert_eq!(redacted.len(), 16);
+        assert_ne!(redacted, "01HZX8Q4J6Y6M5V2K0ZP3W9N7A");
+    }
+}
@@ -0,0 +1,334 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Consolidation of the snapshots of a stream. In distributed mode each ingestor lists the
+//! manifests it uploaded in its own stream json, which queriers otherwise merge on every query.
+//! The consolidated snapshot is written alongside them and never modifies them, ingestors keep
+//! updating their own stream jsons while it is built.
+//!
+//! Manifests listed after consolidation for data it claims to be complete for, e.g. uploads
+//! retried well after their data was ingested, drop the consolidated snapshot until it is rebuilt.
+
+use std::ops::Bound;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use relative_path::RelativePathBuf;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    metastore::metastore_traits::MetastoreObject,
+    parseable::PARSEABLE,
+    query::PartialTimeFilter,
+    storage::{
+        CONSOLIDATED_SNAPSHOT_FILE_NAME, ObjectStorageError, ObjectStoreFormat,
+        STREAM_ROOT_DIRECTORY,
+    },
+};
+
+use super::snapshot::{ManifestItem, Snapshot};
+
+/// Time for the nodes to list the manifests of what they ingested, the same on every node
+/// whatever their sync intervals. Manifests listed later invalidate the consolidated snapshot.
+const SETTLE_TIME: TimeDelta = TimeDelta::minutes(10);
+
+/// The snapshots of all the stream jsons of a stream, merged at `consolidated_at`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsolidatedSnapshot {
+    #[serde(default)]
+    pub stream_name: String,
+    pub consolidated_at: DateTime<Utc>,
+    /// all the manifests of data up to this time are listed, later data may still be uploaded
+    pub complete_until: DateTime<Utc>,
+    /// number of stream jsons merged
+    pub sources: usize,
+    pub snapshot: Snapshot,
+}
+
+impl MetastoreObject for ConsolidatedSnapshot {
+    fn get_object_path(&self) -> String {
+        RelativePathBuf::from_iter([
+            &self.stream_name,
+            STREAM_ROOT_DIRECTORY,
+            CONSOLIDATED_SNAPSHOT_FILE_NAME,
+        ])
+        .to_string()
+    }
+
+    fn get_object_id(&self) -> String {
+        self.stream_name.clone()
+    }
+}
+
+impl ConsolidatedSnapshot {
+    fn new(
+        stream_name: &str,
+        snapshot: Snapshot,
+        sources: usize,
+        consolidated_at: DateTime<Utc>,
+        settle_time: TimeDelta,
+    ) -> Self {
+        Self {
+            stream_name: stream_name.to_owned(),
+            consolidated_at,
+            complete_until: consolidated_at - settle_time,
+            sources,
+            snapshot,
+        }
+    }
+
+    /// Whether the manifests of a query with the time predicates are all listed,
+    /// i.e. whether the query ends before `complete_until`
+    pub fn covers(&self, time_predicates: &[PartialTimeFilter]) -> bool {
+        time_predicates.iter().any(|predicate| match predicate {
+            PartialTimeFilter::High(Bound::Included(time) | Bound::Excluded(time))
+            | PartialTimeFilter::Eq(time) => time.and_utc() < self.complete_until,
+            _ => false,
+        })
+    }
+
+    /// Whether any of the manifests listed after consolidation has data before `complete_until`,
+    /// queries covered by the consolidated snapshot would miss it
+    pub fn misses(&self, manifests: &[ManifestItem]) -> bool {
+        manifests
+            .iter()
+            .any(|manifest| manifest.time_lower_bound < self.complete_until)
+    }
+}
+
+/// Merges the snapshots of the stream jsons, as queries do
+pub fn merge_snapshots(stream_jsons: impl IntoIterator<Item = ObjectStoreFormat>) -> Snapshot {
+    let mut merged = Snapshot::default();
+    for stream_json in stream_jsons {
+        merged
+            .manifest_list
+            .extend(stream_json.snapshot.manifest_list);
+        merged.removals.extend(stream_json.snapshot.removals);
+    }
+
+    merged
+}
+
+async fn stream_jsons(stream_name: &str) -> Result<Vec<ObjectStoreFormat>, ObjectStorageError> {
+    PARSEABLE
+        .metastore
+        .get_all_stream_jsons(stream_name, None)
+        .await
+        .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))
+        .map(|stream_jsons| {
+            // as when merging at query time, a stream json which can't be parsed is skipped
+            stream_jsons
+                .iter()
+                .filter_map(|stream_json| serde_json::from_slice(stream_json).ok())
+                .collect()
+        })
+}
+
+/// Merges the stream jsons of the stream into its consolidated snapshot. Streams with a custom
+/// time partition accept data for any time, there is no time up to which their manifests are complete.
+pub async fn consolidate_snapshot(
+    stream_name: &str,
+) -> Result<ConsolidatedSnapshot, ObjectStorageError> {
+    let stream = PARSEABLE.get_stream(stream_name)?;
+    if stream.get_time_partition().is_some() {
+        return Err(ObjectStorageError::Custom(format!(
+            "stream {stream_name} has a time partition, its snapshots can't be consolidated"
+        )));
+    }
+
+    // taken before reading the stream jsons, later uploads may be missed
+    let consolidated_at = Utc::now();
+    let stream_jsons = stream_jsons(stream_name).await?;
+    let consolidated = ConsolidatedSnapshot::new(
+        stream_name,
+        merge_snapshots(stream_jsons.iter().cloned()),
+        stream_jsons.len(),
+        consolidated_at,
+        SETTLE_TIME,
+    );
+    PARSEABLE
+        .metastore
+        .put_consolidated_snapshot(&consolidated, stream_name)
+        .await
+        .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?;
+
+    Ok(consolidated)
+}
+
+/// The snapshot of the stream for a query with the time predicates, read from the consolidated
+/// snapshot when it lists all the manifests of the query, merged from the stream jsons otherwise
+pub async fn merged_snapshot(
+    stream_name: &str,
+    time_partition: Option<&String>,
+    time_predicates: &[PartialTimeFilter],
+) -> Snapshot {
+    if time_partition.is_none() {
+        match PARSEABLE
+            .metastore
+            .get_consolidated_snapshot(stream_name)
+            .await
+        {
+            Ok(Some(bytes)) => match serde_json::from_slice::<ConsolidatedSnapshot>(&bytes) {
+                Ok(consolidated) if consolidated.covers(time_predicates) => {
+                    return consolidated.snapshot;
+                }
+                Ok(_) => {}
+                Err(err) => warn!("invalid consolidated snapshot of stream {stream_name}: {err}"),
+            },
+            Ok(None) => {}
+            Err(err) => {
+                warn!("couldn't get the consolidated snapshot of stream {stream_name}: {err}")
+            }
+        }
+    }
+
+    stream_jsons(stream_name)
+        .await
+        .map(merge_snapshots)
+        .unwrap_or_default()
+}
+
+/// Drops the consolidated snapshot of the stream, for manifests removed from the stream jsons
+/// not to be queried until it is consolidated again
+pub async fn invalidate_consolidated_snapshot(stream_name: &str) {
+    if let Err(err) = PARSEABLE
+        .metastore
+        .delete_consolidated_snapshot(stream_name)
+        .await
+    {
+        warn!("couldn't delete the consolidated snapshot of stream {stream_name}: {err}");
+    }
+}
+
+/// Drops the consolidated snapshot of the stream when the manifests just listed in a stream json
+/// are for data it claims to list all the manifests of
+pub async fn invalidate_for_new_manifests(stream_name: &str, manifests: &[ManifestItem]) {
+    if manifests.is_empty() {
+        return;
+    }
+    let consolidated = match PARSEABLE
+        .metastore
+        .get_consolidated_snapshot(stream_name)
+        .await
+    {
+        Ok(Some(bytes)) => serde_json::from_slice::<ConsolidatedSnapshot>(&bytes).ok(),
+        Ok(None) => return,
+        Err(err) => {
+            warn!("couldn't get the consolidated snapshot of stream {stream_name}: {err}");
+            None
+        }
+    };
+    // when in doubt, queries merge the stream jsons until the next consolidation
+    if consolidated.is_none_or(|consolidated| consolidated.misses(manifests)) {
+        info!("new manifests of stream {stream_name} invalidate its consolidated snapshot");
+        invalidate_consolidated_snapshot(stream_name).await;
+    }
+}
+
+/// Consolidates the snapshots of all the streams every `P_SNAPSHOT_CONSOLIDATION_INTERVAL` minutes
+pub fn init_snapshot_consolidation_scheduler() {
+    let Some(minutes) = PARSEABLE.options.snapshot_consolidation_interval else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(minutes * 60));
+        loop {
+            interval.tick().await;
+            let mut consolidated = 0;
+            for stream_name in PARSEABLE.streams.list() {
+                let Ok(stream) = PARSEABLE.get_stream(&stream_name) else {
+                    continue;
+                };
+                if stream.get_time_partition().is_some() {
+                    continue;
+                }
+                match consolidate_snapshot(&stream_name).await {
+                    Ok(_) => consolidated += 1,
+                    Err(err) => {
+                        warn!("couldn't consolidate the snapshots of stream {stream_name}: {err}")
+                    }
+                }
+            }
+            info!("consolidated the snapshots of {consolidated} streams");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+
+    #[test]
+    fn covers_queries_ending_before_complete_until() {
+        let consolidated_at: DateTime<Utc> = "2024-05-01T12:00:00Z".parse().unwrap();
+        let consolidated = ConsolidatedSnapshot::new(
+            "app",
+            Snapshot::default(),
+            3,
+            consolidated_at,
+            TimeDelta::minutes(2),
+        );
+        let time = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S").unwrap();
+
+        let settled = [
+            PartialTimeFilter::Low(Bound::Included(time("2024-05-01T10:00:00"))),
+            PartialTimeFilter::High(Bound::Excluded(time("2024-05-01T11:00:00"))),
+        ];
+        assert!(consolidated.covers(&settled));
+
+        let recent = [PartialTimeFilter::High(Bound::Excluded(time(
+            "2024-05-01T11:59:00",
+        )))];
+        assert!(!consolidated.covers(&recent));
+
+        let unbounded = [PartialTimeFilter::Low(Bound::Included(time(
+            "2024-05-01T10:00:00",
+        )))];
+        assert!(!consolidated.covers(&unbounded));
+    }
+
+    #[test]
+    fn late_manifests_are_missed() {
+        let consolidated = ConsolidatedSnapshot::new(
+            "app",
+            Snapshot::default(),
+            1,
+            "2024-05-01T12:00:00Z".parse().unwrap(),
+            TimeDelta::minutes(2),
+        );
+        let manifest = |day: &str| ManifestItem {
+            manifest_path: format!("app/date={day}/manifest.json"),
+            time_lower_bound: format!("{day}T00:00:00Z").parse().unwrap(),
+            time_upper_bound: format!("{day}T23:59:59Z").parse().unwrap(),
+            events_ingested: 0,
+            ingestion_size: 0,
+            storage_size: 0,
+        };
+
+        assert!(consolidated.misses(&[manifest("2024-05-01")]));
+        assert!(!consolidated.misses(&[manifest("2024-05-02")]));
+        assert_eq!(
+            consolidated.get_object_path(),
+            "app/.stream/.consolidated_snapshot.json"
+        );
+    }
+}
@@ -18,9 +18,10 @@
 
 use std::collections::HashMap;
 
+use chrono::{DateTime, Utc};
 use itertools::Itertools;
 use parquet::file::{
-    metadata::{RowGroupMetaData, SortingColumn},
+    metadata::{ParquetMetaData, RowGroupMetaData, SortingColumn},
     reader::FileReader,
 };
 
@@ -61,6 +62,9 @@ pub struct File {
     pub ingestion_size: u64,
     pub columns: Vec<Column>,
     pub sort_order_id: Vec<SortInfo>,
+    /// when the file was uploaded, unset for files uploaded before this was tracked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uploaded_at: Option<DateTime<Utc>>,
 }
 
 /// A manifest file composed of multiple file entries.
@@ -107,17 +111,31 @@ pub fn create_from_parquet_file(
     object_store_path: String,
     fs_file_path: &std::path::Path,
 ) -> anyhow::Result<File> {
+    let file = std::fs::File::open(fs_file_path)?;
+    let file_size = file.metadata()?.len();
+
+    let file = parquet::file::serialized_reader::SerializedFileReader::new(file)?;
+    Ok(from_parquet_metadata(
+        object_store_path,
+        file_size,
+        file.metadata(),
+    ))
+}
+
+/// Builds the manifest entry of a parquet file from its footer
+pub fn from_parquet_metadata(
+    object_store_path: String,
+    file_size: u64,
+    metadata: &ParquetMetaData,
+) -> File {
     let mut manifest_file = File {
         file_path: object_store_path,
+        file_size,
         ..File::default()
     };
 
-    let file = std::fs::File::open(fs_file_path)?;
-    manifest_file.file_size = file.metadata()?.len();
-
-    let file = parquet::file::serialized_reader::SerializedFileReader::new(file)?;
-    let file_meta = file.metadata().file_metadata();
-    let row_groups = file.metadata().row_groups();
+    let file_meta = metadata.file_metadata();
+    let row_groups = metadata.row_groups();
 
     manifest_file.num_rows = file_meta.num_rows() as u64;
     manifest_file.ingestion_size = row_groups
@@ -135,7 +153,7 @@ pub fn create_from_parquet_file(
         manifest_file.sort_order_id = last_sort_order;
     }
 
-    Ok(manifest_file)
+    manifest_file
 }
 
 fn sort_order(
@@ -16,36 +16,49 @@
  *
  */
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
-use chrono::{DateTime, Local, NaiveTime, Utc};
-use column::Column;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, Utc};
+use column::{Column, TypedStatistics};
 use manifest::Manifest;
+use object_store::ObjectMeta;
+use parquet::arrow::async_reader::AsyncFileReader;
 use rayon::prelude::*;
-use relative_path::RelativePathBuf;
+use relative_path::{RelativePath, RelativePathBuf};
 use snapshot::ManifestItem;
 use std::io::Error as IOError;
-use tracing::error;
+use tracing::{error, warn};
 
 use crate::{
     event::DEFAULT_TIMESTAMP_KEY,
     handlers::{
         self,
-        http::{base_path_without_preceding_slash, cluster::for_each_live_ingestor},
+        http::{
+            base_path_without_preceding_slash, cluster::for_each_live_ingestor,
+            modal::ingest_server::INGESTOR_META,
+        },
     },
     metrics::{EVENTS_INGESTED_DATE, EVENTS_INGESTED_SIZE_DATE, EVENTS_STORAGE_SIZE_DATE},
     option::Mode,
     parseable::PARSEABLE,
     query::PartialTimeFilter,
-    stats::{event_labels_date, get_current_stats, storage_size_labels_date, update_deleted_stats},
+    stats::{
+        Stats, event_labels_date, get_current_stats, storage_size_labels_date, update_deleted_stats,
+    },
     storage::{
         ObjectStorage, ObjectStorageError, ObjectStoreFormat, object_storage::manifest_path,
     },
+    utils::time::TimeRange,
 };
 pub use manifest::create_from_parquet_file;
 
 pub mod column;
+pub mod consolidation;
 pub mod manifest;
+pub mod partition_stats;
 pub mod snapshot;
 pub trait Snapshot {
     fn manifests(&self, time_predicates: &[PartialTimeFilter]) -> Vec<ManifestItem>;
@@ -54,9 +67,7 @@ pub trait Snapshot {
 pub trait ManifestFile {
     #[allow(unused)]
     fn file_name(&self) -> &str;
-    #[allow(unused)]
     fn ingestion_size(&self) -> u64;
-    #[allow(unused)]
     fn file_size(&self) -> u64;
     fn num_rows(&self) -> u64;
     fn columns(&self) -> &[Column];
@@ -368,7 +379,9 @@ async fn finalize_snapshot_update(
     stream_name: &str,
 ) -> Result<(), ObjectStorageError> {
     // Add all new manifest entries to the snapshot
-    meta.snapshot.manifest_list.extend(new_manifest_entries);
+    meta.snapshot
+        .manifest_list
+        .extend(new_manifest_entries.iter().cloned());
 
     let stats = get_current_stats(stream_name, "json");
     if let Some(stats) = stats {
@@ -379,6 +392,8 @@ async fn finalize_snapshot_update(
         .put_stream_json(&meta, stream_name)
         .await
         .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?;
+    // checked once listed, for a consolidation reading the stream json earlier to be caught
+    consolidation::invalidate_for_new_manifests(stream_name, &new_manifest_entries).await;
     Ok(())
 }
 
@@ -473,6 +488,267 @@ async fn create_manifest(
     }
 }
 
+/// Lists the manifests of the stream along with its time partition,
+/// merged across all ingestors in distributed mode.
+async fn stream_manifests(
+    stream_name: &str,
+) -> Result<(Option<String>, Vec<ManifestItem>), ObjectStorageError> {
+    let stream_jsons = if matches!(PARSEABLE.options.mode, Mode::Query | Mode::Prism) {
+        PARSEABLE
+            .metastore
+            .get_all_stream_jsons(stream_name, None)
+            .await
+    } else {
+        PARSEABLE
+            .metastore
+            .get_stream_json(stream_name, false)
+            .await
+            .map(|stream_json| vec![stream_json])
+    }
+    .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?;
+
+    let mut time_partition = None;
+    let mut manifests = Vec::new();
+    for stream_json in stream_jsons {
+        let meta: ObjectStoreFormat = serde_json::from_slice(&stream_json)?;
+        time_partition = time_partition.or(meta.time_partition);
+        manifests.extend(meta.snapshot.manifest_list);
+    }
+
+    Ok((time_partition, manifests))
+}
+
+/// Scans the manifests of the stream for the timestamp of its earliest event,
+/// reading the manifests of one day at a time, starting from the earliest.
+pub async fn get_first_event(stream_name: &str) -> Result<Option<String>, ObjectStorageError> {
+    let (time_partition, manifests) = stream_manifests(stream_name).await?;
+    let partition_column = time_partition.unwrap_or_else(|| DEFAULT_TIMESTAMP_KEY.to_owned());
+
+    let mut days = manifests
+        .iter()
+        .map(|item| item.time_lower_bound)
+        .collect::<Vec<_>>();
+    days.sort();
+    days.dedup();
+
+    for day in days {
+        let mut first_event: Option<DateTime<Utc>> = None;
+        for item in manifests.iter().filter(|item| item.time_lower_bound == day) {
+            let Some(manifest) = PARSEABLE
+                .metastore
+                .get_manifest(
+                    stream_name,
+                    item.time_lower_bound,
+                    item.time_upper_bound,
+                    Some(item.manifest_path.clone()),
+                )
+                .await
+                .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?
+            else {
+                continue;
+            };
+
+            let lower_bounds = manifest.files.iter().filter_map(|file| {
+                let column = file
+                    .columns()
+                    .iter()
+                    .find(|col| col.name == partition_column)?;
+                match column.stats.as_ref()? {
+                    column::TypedStatistics::Int(stats) => {
+                        DateTime::from_timestamp_millis(stats.min)
+                    }
+                    _ => None,
+                }
+            });
+            first_event = first_event.into_iter().chain(lower_bounds).min();
+        }
+
+        if let Some(first_event) = first_event {
+            return Ok(Some(first_event.with_timezone(&Local).to_rfc3339()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Start of a time range holding at least the latest `rows` events of the stream in storage,
+/// found from the statistics of the newest manifest entries without reading any parquet file.
+/// Only the manifests needed to account for `rows` events are fetched, newest first.
+///
+/// Returns `None` when the stream doesn't hold that many events, i.e. all of it has to be read.
+pub async fn get_latest_events_start(
+    stream_name: &str,
+    time_column: &str,
+    rows: u64,
+) -> Result<Option<DateTime<Utc>>, ObjectStorageError> {
+    let (_, mut manifests) = stream_manifests(stream_name).await?;
+    manifests.sort_by(|a, b| b.time_upper_bound.cmp(&a.time_upper_bound));
+
+    // (earliest, latest) event time in millis and number of rows of the files seen so far
+    let mut files: Vec<(i64, i64, u64)> = Vec::new();
+    for item in manifests {
+        let Some(manifest) = PARSEABLE
+            .metastore
+            .get_manifest(
+                stream_name,
+                item.time_lower_bound,
+                item.time_upper_bound,
+                Some(item.manifest_path.clone()),
+            )
+            .await
+            .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?
+        else {
+            continue;
+        };
+
+        for file in &manifest.files {
+            let bounds = file
+                .columns()
+                .iter()
+                .find(|col| col.name == time_column)
+                .and_then(|col| match &col.stats {
+                    Some(TypedStatistics::Int(stats)) => Some((stats.min, stats.max)),
+                    _ => None,
+                })
+                // files without statistics are assumed to span the whole manifest
+                .unwrap_or((
+                    item.time_lower_bound.timestamp_millis(),
+                    item.time_upper_bound.timestamp_millis(),
+                ));
+            files.push((bounds.0, bounds.1, file.num_rows));
+        }
+
+        // the newest files holding enough rows bound the time of the latest events
+        files.sort_by(|a, b| b.1.cmp(&a.1));
+        let mut seen = 0;
+        let mut start = i64::MAX;
+        for (min, _, num_rows) in &files {
+            seen += num_rows;
+            start = start.min(*min);
+            if seen >= rows {
+                return Ok(DateTime::from_timestamp_millis(start));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Merges the statistics of `column` over the files whose events overlap the time range.
+///
+/// Files are only partially covered at the edges of the range, so the bounds are approximate.
+pub async fn get_column_bounds(
+    stream_name: &str,
+    column: &str,
+    time_range: &TimeRange,
+) -> Result<Option<TypedStatistics>, ObjectStorageError> {
+    let (time_partition, manifests) = stream_manifests(stream_name).await?;
+    let partition_column = time_partition.unwrap_or_else(|| DEFAULT_TIMESTAMP_KEY.to_owned());
+
+    let mut bounds: Option<TypedStatistics> = None;
+    for item in manifests.iter().filter(|item| {
+        item.time_lower_bound <= time_range.end && item.time_upper_bound >= time_range.start
+    }) {
+        let Some(manifest) = PARSEABLE
+            .metastore
+            .get_manifest(
+                stream_name,
+                item.time_lower_bound,
+                item.time_upper_bound,
+                Some(item.manifest_path.clone()),
+            )
+            .await
+            .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?
+        else {
+            continue;
+        };
+
+        for file in &manifest.files {
+            let column_stats = |name: &str| {
+                file.columns()
+                    .iter()
+                    .find(|col| col.name == name)
+                    .and_then(|col| col.stats.clone())
+            };
+            let in_range = match column_stats(&partition_column) {
+                Some(TypedStatistics::Int(stats)) => {
+                    stats.min <= time_range.end.timestamp_millis()
+                        && stats.max >= time_range.start.timestamp_millis()
+                }
+                _ => false,
+            };
+            if !in_range {
+                continue;
+            }
+
+            bounds = match (bounds, column_stats(column)) {
+                (None, stats) => stats,
+                (Some(bounds), None) => Some(bounds),
+                (Some(bounds), Some(stats)) => {
+                    // the type of a column may have changed across files
+                    if std::mem::discriminant(&bounds) != std::mem::discriminant(&stats) {
+                        return Ok(None);
+                    }
+                    Some(bounds.update(stats))
+                }
+            };
+        }
+    }
+
+    Ok(bounds)
+}
+
+/// Sums the stats of the stream's files whose first event falls within `time_range`,
+/// used when the range doesn't align with the UTC dates the stats metrics are kept for
+pub async fn get_stats_in_range(
+    stream_name: &str,
+    time_range: &TimeRange,
+) -> Result<Stats, ObjectStorageError> {
+    let (time_partition, manifests) = stream_manifests(stream_name).await?;
+    let partition_column = time_partition.unwrap_or_else(|| DEFAULT_TIMESTAMP_KEY.to_owned());
+    let (start, end) = (
+        time_range.start.timestamp_millis(),
+        time_range.end.timestamp_millis(),
+    );
+
+    let mut stats = Stats::default();
+    for item in manifests.iter().filter(|item| {
+        item.time_lower_bound < time_range.end && item.time_upper_bound >= time_range.start
+    }) {
+        let Some(manifest) = PARSEABLE
+            .metastore
+            .get_manifest(
+                stream_name,
+                item.time_lower_bound,
+                item.time_upper_bound,
+                Some(item.manifest_path.clone()),
+            )
+            .await
+            .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?
+        else {
+            continue;
+        };
+
+        for file in &manifest.files {
+            let first_event = file
+                .columns()
+                .iter()
+                .find(|col| col.name == partition_column)
+                .and_then(|col| match col.stats.as_ref()? {
+                    TypedStatistics::Int(stats) => Some(stats.min),
+                    _ => None,
+                });
+            if first_event.is_some_and(|min| min >= start && min < end) {
+                stats.events += file.num_rows();
+                stats.ingestion += file.ingestion_size();
+                stats.storage += file.file_size();
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
 pub async fn remove_manifest_from_snapshot(
     storage: Arc<dyn ObjectStorage>,
     stream_name: &str,
@@ -490,15 +766,27 @@ pub async fn remove_manifest_from_snapshot(
 
         let meta_for_stats = meta.clone();
         update_deleted_stats(storage.clone(), stream_name, meta_for_stats, dates.clone()).await?;
-        let manifests = &mut meta.snapshot.manifest_list;
+        let is_removed =
+            |item: &ManifestItem| dates.iter().any(|date| item.manifest_path.contains(date));
+        if let Some(history_days) = PARSEABLE.options.snapshot_history_days {
+            let removed = meta
+                .snapshot
+                .manifest_list
+                .iter()
+                .filter(|item| is_removed(item))
+                .cloned()
+                .collect::<Vec<_>>();
+            meta.snapshot.record_removal(&removed, history_days);
+        }
         // Filter out items whose manifest_path contains any of the dates_to_delete
-        manifests.retain(|item| !dates.iter().any(|date| item.manifest_path.contains(date)));
+        meta.snapshot.manifest_list.retain(|item| !is_removed(item));
         PARSEABLE.get_stream(stream_name)?.reset_first_event_at();
         meta.first_event_at = None;
         storage.put_snapshot(stream_name, meta.snapshot).await?;
     }
 
     if !dates.is_empty() && matches!(PARSEABLE.options.mode, Mode::Query | Mode::Prism) {
+        consolidation::invalidate_consolidated_snapshot(stream_name).await;
         let stream_name_clone = stream_name.to_string();
         let dates_clone = dates.clone();
 
@@ -523,6 +811,196 @@ pub async fn remove_manifest_from_snapshot(
     Ok(())
 }
 
+/// Outcome of rebuilding the manifest of a day from the parquet files present in storage
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ManifestRebuild {
+    pub manifest_path: String,
+    pub dry_run: bool,
+    pub files: usize,
+    pub num_rows: u64,
+    /// files in storage that were missing from the manifest
+    pub added: Vec<String>,
+    /// files in the manifest that are not in storage anymore
+    pub removed: Vec<String>,
+}
+
+/// Rewrites the manifest of this node for `date`, and its entry in the snapshot,
+/// from the footers of the parquet files this node uploaded for that day.
+/// Nothing is written on a dry run, only the differences are reported.
+pub async fn rebuild_manifest(
+    stream_name: &str,
+    date: NaiveDate,
+    dry_run: bool,
+) -> Result<ManifestRebuild, ObjectStorageError> {
+    let storage = PARSEABLE.storage.get_object_store();
+    let (lower_bound, upper_bound) =
+        create_partition_bounds(date.and_time(NaiveTime::MIN).and_utc());
+    // in distributed mode, every ingestor keeps its own manifest of the files it uploaded
+    let node_id = match PARSEABLE.options.mode {
+        Mode::Ingest => INGESTOR_META.get().map(|meta| meta.get_node_id()),
+        _ => None,
+    };
+
+    let mut files = Vec::new();
+    let prefix = partition_path(stream_name, lower_bound, upper_bound);
+    for object in list_parquet_objects(&storage, &prefix).await? {
+        if let Some(node_id) = &node_id
+            && !uploaded_by(&object, node_id)
+        {
+            continue;
+        }
+        let path = RelativePathBuf::from(object.location.as_ref());
+        let mut reader = storage.get_buffered_reader(&path).await?;
+        let metadata = reader.get_metadata(None).await.map_err(|err| {
+            ObjectStorageError::Custom(format!("failed to read the footer of {path}: {err}"))
+        })?;
+        let mut file = manifest::from_parquet_metadata(
+            storage.absolute_url(&path).to_string(),
+            object.size,
+            &metadata,
+        );
+        file.uploaded_at = Some(object.last_modified);
+        files.push(file);
+    }
+    files.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+
+    let mut meta: ObjectStoreFormat = serde_json::from_slice(
+        &PARSEABLE
+            .metastore
+            .get_stream_json(stream_name, false)
+            .await
+            .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?,
+    )?;
+    let pos = meta.snapshot.manifest_list.iter().position(|item| {
+        item.time_lower_bound <= lower_bound && lower_bound < item.time_upper_bound
+    });
+    let recorded = match pos {
+        Some(pos) => {
+            let item = &meta.snapshot.manifest_list[pos];
+            PARSEABLE
+                .metastore
+                .get_manifest(
+                    stream_name,
+                    item.time_lower_bound,
+                    item.time_upper_bound,
+                    Some(item.manifest_path.clone()),
+                )
+                .await
+                .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?
+                .map(|manifest| manifest.files)
+                .unwrap_or_default()
+        }
+        None => Vec::new(),
+    };
+
+    let recorded_paths = recorded
+        .iter()
+        .map(|file| file.file_path.as_str())
+        .collect::<HashSet<_>>();
+    let stored_paths = files
+        .iter()
+        .map(|file| file.file_path.as_str())
+        .collect::<HashSet<_>>();
+    let manifest_path = PARSEABLE
+        .metastore
+        .get_manifest_path(stream_name, lower_bound, upper_bound)
+        .await
+        .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?;
+    let rebuild = ManifestRebuild {
+        manifest_path: manifest_path.clone(),
+        dry_run,
+        files: files.len(),
+        num_rows: files.iter().map(|file| file.num_rows).sum(),
+        added: files
+            .iter()
+            .filter(|file| !recorded_paths.contains(file.file_path.as_str()))
+            .map(|file| file.file_path.clone())
+            .collect(),
+        removed: recorded
+            .iter()
+            .filter(|file| !stored_paths.contains(file.file_path.as_str()))
+            .map(|file| file.file_path.clone())
+            .collect(),
+    };
+    if dry_run {
+        return Ok(rebuild);
+    }
+
+    if files.is_empty() {
+        if let Some(pos) = pos {
+            meta.snapshot.manifest_list.remove(pos);
+        }
+        if let Err(err) = PARSEABLE
+            .metastore
+            .delete_manifest(stream_name, lower_bound, upper_bound)
+            .await
+        {
+            warn!("failed to delete the manifest of stream {stream_name} for {date}: {err}");
+        }
+    } else {
+        let item = ManifestItem {
+            manifest_path,
+            time_lower_bound: lower_bound,
+            time_upper_bound: upper_bound,
+            events_ingested: files.iter().map(|file| file.num_rows).sum(),
+            ingestion_size: files.iter().map(|file| file.ingestion_size).sum(),
+            storage_size: files.iter().map(|file| file.file_size).sum(),
+        };
+        let manifest = Manifest {
+            files,
+            ..Manifest::default()
+        };
+        PARSEABLE
+            .metastore
+            .put_manifest(&manifest, stream_name, lower_bound, upper_bound)
+            .await
+            .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?;
+        match pos {
+            Some(pos) => meta.snapshot.manifest_list[pos] = item,
+            None => meta.snapshot.manifest_list.push(item),
+        }
+    }
+
+    PARSEABLE
+        .metastore
+        .put_stream_json(&meta, stream_name)
+        .await
+        .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?;
+
+    Ok(rebuild)
+}
+
+/// Lists the parquet objects under `prefix`, including the ones in hour, minute and custom partition directories
+async fn list_parquet_objects(
+    storage: &Arc<dyn ObjectStorage>,
+    prefix: &RelativePath,
+) -> Result<Vec<ObjectMeta>, ObjectStorageError> {
+    let mut prefixes = vec![storage.absolute_url(prefix)];
+    let mut objects = Vec::new();
+    while let Some(prefix) = prefixes.pop() {
+        let listing = storage.list_with_delimiter(Some(prefix)).await?;
+        prefixes.extend(listing.common_prefixes);
+        objects.extend(
+            listing
+                .objects
+                .into_iter()
+                .filter(|object| object.location.extension() == Some("parquet")),
+        );
+    }
+
+    Ok(objects)
+}
+
+/// Whether a parquet file was uploaded by the given ingestor,
+/// i.e. its name is `{hostname}{node_id}.data.{random}.parquet`
+fn uploaded_by(object: &ObjectMeta, node_id: &str) -> bool {
+    object
+        .location
+        .filename()
+        .and_then(|name| name.split_once(".data."))
+        .is_some_and(|(node, _)| node.ends_with(node_id))
+}
+
 /// Partition the path to which this manifest belongs.
 /// Useful when uploading the manifest file.
 pub fn partition_path(
@@ -0,0 +1,158 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Sizes of the partitions of a stream, aggregated from the files listed in its manifests.
+//! The partition of a file is read from its path, `date=.../hour=.../minute=.../key=value/...`,
+//! the custom partitions of the stream if it has any, its hours otherwise.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use super::manifest::File;
+
+/// Files and sizes of a partition of the stream
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartitionStats {
+    pub partition: String,
+    pub files: u64,
+    pub bytes: u64,
+    pub rows: u64,
+    /// more than the skew factor times the median partition size
+    pub skewed: bool,
+}
+
+/// Sizes of partitions, keyed by partition
+#[derive(Debug, Default)]
+pub struct PartitionSizes {
+    by_custom_partition: bool,
+    partitions: BTreeMap<String, PartitionStats>,
+}
+
+impl PartitionSizes {
+    pub fn new(by_custom_partition: bool) -> Self {
+        Self {
+            by_custom_partition,
+            partitions: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, file: &File) {
+        let partition = partition_of(&file.file_path, self.by_custom_partition);
+        let stats = self
+            .partitions
+            .entry(partition.clone())
+            .or_insert_with(|| PartitionStats {
+                partition,
+                ..Default::default()
+            });
+        stats.files += 1;
+        stats.bytes += file.file_size;
+        stats.rows += file.num_rows;
+    }
+
+    /// The partitions from the largest, flagged when larger than `skew_factor` times the median
+    /// partition size, along with the median
+    pub fn finish(self, skew_factor: f64) -> (Vec<PartitionStats>, u64) {
+        let mut partitions = self.partitions.into_values().collect::<Vec<_>>();
+        partitions.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+
+        let median = match partitions.len() {
+            0 => 0,
+            len if len % 2 == 1 => partitions[len / 2].bytes,
+            len => (partitions[len / 2 - 1].bytes + partitions[len / 2].bytes) / 2,
+        };
+        // a single partition can't be skewed against the others
+        if partitions.len() > 1 {
+            for stats in &mut partitions {
+                stats.skewed = stats.bytes as f64 > skew_factor * median as f64;
+            }
+        }
+
+        (partitions, median)
+    }
+}
+
+/// The partition of the file at `file_path`, the `key=value` segments of its custom partitions,
+/// or of its date and hour
+fn partition_of(file_path: &str, by_custom_partition: bool) -> String {
+    let segments = file_path
+        .split('/')
+        .filter(|segment| segment.contains('=') && !segment.ends_with(".parquet"));
+    let is_time = |segment: &&str| {
+        ["date=", "hour=", "minute="]
+            .iter()
+            .any(|prefix| segment.starts_with(prefix))
+    };
+
+    let partition = if by_custom_partition {
+        segments
+            .filter(|segment| !is_time(segment))
+            .collect::<Vec<_>>()
+    } else {
+        segments
+            .filter(|segment| segment.starts_with("date=") || segment.starts_with("hour="))
+            .collect::<Vec<_>>()
+    };
+
+    partition.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(file_path: &str, file_size: u64) -> File {
+        File {
+            file_path: file_path.to_owned(),
+            num_rows: file_size / 10,
+            file_size,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn partitions_larger_than_the_median_are_skewed() {
+        let mut sizes = PartitionSizes::new(true);
+        for (region, size) in [("us", 100), ("eu", 120), ("ap", 90), ("us", 5000)] {
+            sizes.add(&file(
+                &format!("app/date=2024-05-01/hour=00/minute=00/region={region}/host.data.parquet"),
+                size,
+            ));
+        }
+
+        let (partitions, median) = sizes.finish(10.0);
+        assert_eq!(median, 120);
+        assert_eq!(partitions[0].partition, "region=us");
+        assert_eq!((partitions[0].files, partitions[0].bytes), (2, 5100));
+        assert!(partitions[0].skewed);
+        assert!(partitions[1..].iter().all(|stats| !stats.skewed));
+    }
+
+    #[test]
+    fn streams_without_custom_partitions_are_partitioned_by_hour() {
+        assert_eq!(
+            partition_of(
+                "app/date=2024-05-01/hour=07/minute=15/host.data.parquet",
+                false
+            ),
+            "date=2024-05-01/hour=07"
+        );
+    }
+}
@@ -18,7 +18,7 @@
 
 use std::ops::Bound;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 
 use crate::query::PartialTimeFilter;
 
@@ -27,6 +27,9 @@ pub const CURRENT_SNAPSHOT_VERSION: &str = "v2";
 pub struct Snapshot {
     pub version: String,
     pub manifest_list: Vec<ManifestItem>,
+    /// data removed from storage, tracked while snapshot history is enabled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removals: Vec<SnapshotRemoval>,
 }
 
 impl Default for Snapshot {
@@ -34,43 +37,87 @@ impl Default for Snapshot {
         Self {
             version: CURRENT_SNAPSHOT_VERSION.to_string(),
             manifest_list: Vec::default(),
+            removals: Vec::default(),
         }
     }
 }
 
-impl super::Snapshot for Snapshot {
-    fn manifests(&self, time_predicates: &[PartialTimeFilter]) -> Vec<ManifestItem> {
-        let mut manifests = self.manifest_list.clone();
-        for predicate in time_predicates {
-            match predicate {
-                PartialTimeFilter::Low(Bound::Included(time)) => manifests.retain(|item| {
-                    let time = time.and_utc();
-                    item.time_upper_bound >= time
-                }),
-                PartialTimeFilter::Low(Bound::Excluded(time)) => manifests.retain(|item| {
-                    let time = time.and_utc();
-                    item.time_upper_bound > time
-                }),
-                PartialTimeFilter::High(Bound::Included(time)) => manifests.retain(|item| {
-                    let time = time.and_utc();
-                    item.time_lower_bound <= time
-                }),
-                PartialTimeFilter::High(Bound::Excluded(time)) => manifests.retain(|item| {
-                    let time = time.and_utc();
-                    item.time_lower_bound < time
-                }),
-                PartialTimeFilter::Eq(time) => manifests.retain(|item| {
-                    let time = time.and_utc();
-                    item.time_lower_bound <= time && time <= item.time_upper_bound
-                }),
-                _ => (),
-            }
+impl Snapshot {
+    /// Records the removal of the data of `items` from storage,
+    /// removals older than `history_days` are dropped as queries can't go that far back
+    pub fn record_removal(&mut self, items: &[ManifestItem], history_days: u32) {
+        let now = Utc::now();
+        let oldest = now - TimeDelta::days(history_days as i64);
+        self.removals.retain(|removal| removal.removed_at >= oldest);
+        self.removals
+            .extend(items.iter().map(|item| SnapshotRemoval {
+                removed_at: now,
+                time_lower_bound: item.time_lower_bound,
+                time_upper_bound: item.time_upper_bound,
+            }));
+    }
+
+    /// The first removal after `as_of` of data in the time range of the predicates,
+    /// the data as it was at `as_of` can't be queried anymore if there is one
+    pub fn removed_after(
+        &self,
+        as_of: DateTime<Utc>,
+        time_predicates: &[PartialTimeFilter],
+    ) -> Option<&SnapshotRemoval> {
+        self.removals.iter().find(|removal| {
+            removal.removed_at > as_of
+                && time_predicates.iter().all(|predicate| {
+                    overlaps(
+                        predicate,
+                        removal.time_lower_bound,
+                        removal.time_upper_bound,
+                    )
+                })
+        })
+    }
+}
+
+// whether data between the bounds can match the time predicate
+fn overlaps(
+    predicate: &PartialTimeFilter,
+    lower_bound: DateTime<Utc>,
+    upper_bound: DateTime<Utc>,
+) -> bool {
+    match predicate {
+        PartialTimeFilter::Low(Bound::Included(time)) => upper_bound >= time.and_utc(),
+        PartialTimeFilter::Low(Bound::Excluded(time)) => upper_bound > time.and_utc(),
+        PartialTimeFilter::High(Bound::Included(time)) => lower_bound <= time.and_utc(),
+        PartialTimeFilter::High(Bound::Excluded(time)) => lower_bound < time.and_utc(),
+        PartialTimeFilter::Eq(time) => {
+            let time = time.and_utc();
+            lower_bound <= time && time <= upper_bound
         }
+        _ => true,
+    }
+}
 
-        manifests
+impl super::Snapshot for Snapshot {
+    fn manifests(&self, time_predicates: &[PartialTimeFilter]) -> Vec<ManifestItem> {
+        self.manifest_list
+            .iter()
+            .filter(|item| {
+                time_predicates.iter().all(|predicate| {
+                    overlaps(predicate, item.time_lower_bound, item.time_upper_bound)
+                })
+            })
+            .cloned()
+            .collect()
     }
 }
 
+/// Time range of data removed from storage, e.g. by retention
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SnapshotRemoval {
+    pub removed_at: DateTime<Utc>,
+    pub time_lower_bound: DateTime<Utc>,
+    pub time_upper_bound: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct ManifestItem {
     pub manifest_path: String,
@@ -80,3 +127,45 @@ pub struct ManifestItem {
     pub ingestion_size: u64,
     pub storage_size: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn removals_after_as_of_overlapping_the_query() {
+        let day = |d| {
+            NaiveDate::from_ymd_opt(2024, 1, d)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        };
+        let snapshot = Snapshot {
+            removals: vec![SnapshotRemoval {
+                removed_at: day(10).and_utc(),
+                time_lower_bound: day(1).and_utc(),
+                time_upper_bound: day(2).and_utc(),
+            }],
+            ..Snapshot::default()
+        };
+        let since = |d| [PartialTimeFilter::Low(Bound::Included(day(d)))];
+
+        assert!(
+            snapshot
+                .removed_after(day(5).and_utc(), &since(1))
+                .is_some()
+        );
+        assert!(
+            snapshot
+                .removed_after(day(5).and_utc(), &since(3))
+                .is_none()
+        );
+        assert!(
+            snapshot
+                .removed_after(day(11).and_utc(), &since(1))
+                .is_none()
+        );
+    }
+}
@@ -17,7 +17,7 @@
  */
 
 use clap::Parser;
-use std::{env, fs, path::PathBuf};
+use std::{collections::HashMap, env, fs, num::NonZeroU32, path::PathBuf};
 
 use url::Url;
 
@@ -26,7 +26,10 @@ use crate::connectors::kafka::config::KafkaConfig;
 
 use crate::{
     oidc::{self, OpenidConfig},
-    option::{Compression, Mode, validation},
+    option::{
+        AccessLogFormat, Compression, DeletedStreamAlertPolicy, IngestorSyncPolicy, Mode,
+        validation,
+    },
     storage::{AzureBlobConfig, FSConfig, GcsConfig, S3Config},
 };
 
@@ -36,6 +39,8 @@ pub const DEFAULT_USERNAME: &str = "admin";
 pub const DEFAULT_PASSWORD: &str = "admin";
 
 pub const DATASET_FIELD_COUNT_LIMIT: usize = 1000;
+/// 30 days, a longer warmup is as good as disabling the alert
+pub const MAX_ALERT_WARMUP_MINUTES: u64 = 30 * 24 * 60;
 #[derive(Parser)]
 #[command(
     name = "parseable",
@@ -173,6 +178,42 @@ pub struct Options {
     )]
     pub cors: bool,
 
+    /// Origins allowed to call the API from browsers, `*` allows any origin.
+    /// When unset, CORS behaves as configured by `P_CORS`, which doesn't restrict
+    /// the calling origin in debug builds or when disabled.
+    /// SECURITY NOTE: set this to your frontend's origin in production.
+    #[arg(
+        long,
+        env = "P_CORS_ALLOWED_ORIGINS",
+        value_delimiter = ',',
+        value_parser = validation::cors_origin,
+        value_name = "origins"
+    )]
+    pub cors_allowed_origins: Vec<String>,
+
+    /// Encodings responses can be compressed with, the encoding of a response is negotiated
+    /// among them with the `Accept-Encoding` of the client
+    #[arg(
+        long,
+        env = "P_COMPRESSION_ENCODINGS",
+        value_delimiter = ',',
+        default_value = "gzip,br,zstd",
+        value_parser = validation::response_encoding,
+        value_name = "encodings"
+    )]
+    pub compression_encodings: Vec<String>,
+
+    /// Content types of responses sent uncompressed, those already compressed
+    /// and live tails whose events would be held back by the encoder
+    #[arg(
+        long,
+        env = "P_COMPRESSION_EXCLUDED_CONTENT_TYPES",
+        value_delimiter = ',',
+        default_value = "application/vnd.apache.parquet,application/vnd.apache.arrow.stream,application/vnd.apache.arrow.file,text/event-stream",
+        value_name = "content-types"
+    )]
+    pub compression_excluded_content_types: Vec<String>,
+
     #[arg(
         long,
         env = "P_CHECK_UPDATE",
@@ -189,6 +230,26 @@ pub struct Options {
     )]
     pub send_analytics: bool,
 
+    // top level fields of the analytics report, e.g. deployment_id or metrics
+    #[arg(
+        long,
+        env = "P_ANALYTICS_EXCLUDE",
+        value_delimiter = ',',
+        value_name = "fields",
+        help = "Comma separated fields left out of the anonymous usage data"
+    )]
+    pub analytics_exclude: Vec<String>,
+
+    // redacted values are replaced by their hash, so that they can still be told apart
+    #[arg(
+        long,
+        env = "P_ANALYTICS_REDACT",
+        value_delimiter = ',',
+        value_name = "fields",
+        help = "Comma separated fields of the anonymous usage data sent as a hash of their value"
+    )]
+    pub analytics_redact: Vec<String>,
+
     #[arg(
         long,
         env = "P_MASK_PII",
@@ -205,6 +266,61 @@ pub struct Options {
     )]
     pub metrics_endpoint_auth: bool,
 
+    #[arg(
+        long,
+        env = "P_INTERNAL_STREAM_NAME",
+        default_value = "pmeta",
+        value_parser = validation::internal_stream_name,
+        help = "Name of the internal stream holding cluster metadata"
+    )]
+    pub internal_stream_name: String,
+
+    #[arg(
+        long,
+        env = "P_ALERT_RESULTS_STREAM",
+        default_value = "palerts",
+        value_parser = validation::internal_stream_name,
+        help = "Name of the internal stream the results of alerts recording their evaluations are ingested in"
+    )]
+    pub alert_results_stream: String,
+
+    // applied to the internal stream on startup, the internal stream is kept forever when not set
+    #[arg(
+        long,
+        env = "P_INTERNAL_STREAM_RETENTION_DAYS",
+        help = "Number of days of data retained in the internal stream"
+    )]
+    pub internal_stream_retention_days: Option<NonZeroU32>,
+
+    #[arg(
+        long,
+        env = "P_ACCESS_LOG_FORMAT",
+        default_value = "text",
+        value_parser = validation::access_log_format,
+        help = "Format of the access log, text or json. JSON lines are written to stdout for every request, regardless of RUST_LOG"
+    )]
+    pub access_log_format: AccessLogFormat,
+
+    // bodies of the endpoints updating metadata (alerts, retention, hot tier, roles)
+    // are rejected with 413 beyond this size, before being buffered entirely
+    #[arg(
+        long,
+        env = "P_MAX_METADATA_PAYLOAD_SIZE",
+        default_value = "1024",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Maximum size in KiB of the request body of endpoints updating metadata"
+    )]
+    pub max_metadata_payload_size: u64,
+
+    #[arg(
+        long,
+        env = "P_INGESTOR_SYNC_POLICY",
+        default_value = "fail_fast",
+        value_parser = validation::ingestor_sync_policy,
+        help = "Behavior when stream changes can't be forwarded to an ingestor, fail_fast or best_effort. Changes are retried on the ingestors that are down once they return, with best_effort also on those they fail on"
+    )]
+    pub ingestor_sync_policy: IngestorSyncPolicy,
+
     // TLS/Security
     #[arg(
         long,
@@ -242,6 +358,77 @@ pub struct Options {
     )]
     pub tls_skip_verify: bool,
 
+    /// Requires clients to authenticate with a certificate signed by one of the CAs in
+    /// `P_TRUSTED_CA_CERTS_DIR`. Requests without a verified client certificate are rejected.
+    /// Intra-cluster clients must present a certificate as well when this is enabled.
+    #[arg(
+        long,
+        env = "P_TLS_REQUIRE_CLIENT_CERT",
+        value_name = "bool",
+        default_value = "false",
+        requires = "trusted_ca_certs_path"
+    )]
+    pub tls_require_client_cert: bool,
+
+    #[arg(
+        long,
+        env = "P_TLS_CLIENT_CERT_USER_MAP",
+        value_parser = validation::client_cert_user_map,
+        help = "Comma separated <common name>:<username> pairs mapping client certificates to users. Certificates without a mapping don't authenticate anyone, unless P_TLS_CLIENT_CERT_CN_PASSTHROUGH is set"
+    )]
+    pub tls_client_cert_user_map: Option<HashMap<String, String>>,
+
+    /// Lets client certificates without an entry in `P_TLS_CLIENT_CERT_USER_MAP` authenticate as
+    /// the user named after their subject common name.
+    /// SECURITY NOTE: Any certificate issued by a trusted CA then authenticates as the user of its
+    /// common name, including the certificates of the nodes of the cluster.
+    #[arg(
+        long,
+        env = "P_TLS_CLIENT_CERT_CN_PASSTHROUGH",
+        value_name = "bool",
+        default_value = "false"
+    )]
+    pub tls_client_cert_cn_passthrough: bool,
+
+    /// PEM bundle of CAs trusted on top of the system roots for outbound connections,
+    /// i.e. intra-cluster requests and alert target webhooks. Needed when the other nodes
+    /// or the targets have certificates issued by an internal or self-signed CA.
+    #[arg(
+        long,
+        env = "P_OUTBOUND_CA_BUNDLE",
+        value_parser = validation::ca_bundle,
+        help = "Local path on this device where the PEM bundle of CAs trusted for outbound connections is located"
+    )]
+    pub outbound_ca_bundle: Option<PathBuf>,
+
+    /// Allows invalid TLS certificates for the alert target webhooks.
+    /// SECURITY NOTE: Prefer `P_OUTBOUND_CA_BUNDLE`, this disables all verification of the targets.
+    #[arg(
+        long,
+        env = "P_OUTBOUND_TLS_SKIP_VERIFY",
+        value_name = "bool",
+        default_value = "false"
+    )]
+    pub outbound_tls_skip_verify: bool,
+
+    #[arg(
+        long,
+        env = "P_INTRA_CLUSTER_CLIENT_CERT_PATH",
+        value_parser = validation::pem_certs,
+        requires = "intra_cluster_client_key_path",
+        help = "Local path on this device where the certificate presented to other nodes of the cluster is located. Required for mutual TLS between nodes"
+    )]
+    pub intra_cluster_client_cert_path: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "P_INTRA_CLUSTER_CLIENT_KEY_PATH",
+        value_parser = validation::pem_private_key,
+        requires = "intra_cluster_client_cert_path",
+        help = "Local path on this device where the private key of the certificate presented to other nodes of the cluster is located"
+    )]
+    pub intra_cluster_client_key_path: Option<PathBuf>,
+
     // Storage configuration
     #[arg(
         long,
@@ -305,13 +492,130 @@ pub struct Options {
     )]
     pub livetail_channel_capacity: usize,
 
+    #[arg(
+        long,
+        env = "P_MAX_TAIL_CONNECTIONS",
+        default_value = "100",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Maximum number of live tails open at once over server-sent events"
+    )]
+    pub max_tail_connections: u32,
+
     #[arg(
         long,
         long = "query-mempool-size",
+        visible_alias = "query-memory-limit",
         env = "P_QUERY_MEMORY_LIMIT",
         help = "Set a fixed memory limit for query in GiB"
     )]
     pub query_memory_pool_size: Option<usize>,
+
+    // directory used by datafusion to spill intermediate results of large
+    // sorts/aggregations once the query memory pool is exhausted
+    #[arg(
+        long = "query-spill-path",
+        env = "P_QUERY_SPILL_DIR",
+        value_parser = validation::writable_dir,
+        help = "Local path on this device used to spill query data to disk when the memory limit is reached"
+    )]
+    pub query_spill_path: Option<PathBuf>,
+
+    // maximum number of queries executed concurrently on this node,
+    // requests beyond the limit wait for `query_queue_timeout` seconds before being rejected
+    #[arg(
+        long = "query-max-concurrent",
+        env = "P_QUERY_MAX_CONCURRENT",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Maximum number of queries executed concurrently on this node"
+    )]
+    pub query_max_concurrent: Option<u32>,
+
+    // applied to query requests without `startTime`/`endTime`,
+    // scanning the whole history of a stream requires an explicit range
+    #[arg(
+        long = "default-query-range",
+        env = "P_DEFAULT_QUERY_RANGE",
+        default_value = "15m",
+        value_parser = validation::query_range,
+        help = "Time range queried when a query request doesn't specify one, e.g. 15m or 1h"
+    )]
+    pub default_query_range: String,
+
+    #[arg(
+        long = "query-queue-timeout",
+        env = "P_QUERY_QUEUE_TIMEOUT",
+        default_value = "30",
+        value_parser = validation::validate_seconds,
+        help = "Time in seconds a query waits for an execution slot before getting rejected with 429"
+    )]
+    pub query_queue_timeout: u64,
+
+    // asynchronous query jobs are kept in memory, submissions are rejected with 429 beyond this count
+    #[arg(
+        long = "query-jobs-max",
+        env = "P_QUERY_JOBS_MAX",
+        default_value = "100",
+        help = "Maximum number of asynchronous query jobs held on this node"
+    )]
+    pub query_jobs_max: usize,
+
+    #[arg(
+        long = "query-jobs-ttl",
+        env = "P_QUERY_JOBS_TTL",
+        default_value = "3600",
+        value_parser = validation::validate_seconds,
+        help = "Time in seconds after which the results of a completed or failed query job are dropped"
+    )]
+    pub query_jobs_ttl: u64,
+
+    // object store reads of a query are aborted once this budget is exhausted,
+    // so that a degraded store can't keep queries hanging past any client timeout
+    #[arg(
+        long = "query-timeout",
+        env = "P_QUERY_TIMEOUT",
+        value_parser = validation::validate_seconds,
+        help = "Time in seconds after which a query and its pending object store requests are aborted"
+    )]
+    pub query_timeout: Option<u64>,
+
+    // results of repeated non-streaming queries are served from memory when set,
+    // entries are dropped after the ttl or once data for their time range is ingested on this node.
+    // queriers in a distributed setup don't see ingestion, so only the ttl bounds staleness there
+    #[arg(
+        long = "query-cache-ttl",
+        env = "P_QUERY_CACHE_TTL",
+        value_parser = validation::validate_seconds,
+        help = "Time in seconds query results are cached for. Query result caching is disabled when not set"
+    )]
+    pub query_cache_ttl: Option<u64>,
+
+    #[arg(
+        long = "query-cache-size",
+        env = "P_QUERY_CACHE_SIZE",
+        default_value = "256",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Maximum memory in MiB used to cache query results"
+    )]
+    pub query_cache_size: u64,
+
+    // data removed from streams (e.g. by retention) is tracked in the snapshots for this many days,
+    // so that queries `as_of` a past time can tell whether the data they'd have read is still there
+    #[arg(
+        long = "snapshot-history-days",
+        env = "P_SNAPSHOT_HISTORY_DAYS",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Number of days a stream can be queried as of a past time. Querying past snapshots is disabled when not set"
+    )]
+    pub snapshot_history_days: Option<u32>,
+
+    #[arg(
+        long,
+        env = "P_SNAPSHOT_CONSOLIDATION_INTERVAL",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Interval in minutes at which the querier merges the snapshots of the ingestors of each stream, for queries to read a single snapshot. Snapshots are only consolidated on demand when not set"
+    )]
+    pub snapshot_consolidation_interval: Option<u64>,
+
     // reduced the max row group size from 1048576
     // smaller row groups help in faster query performance in multi threaded query
     #[arg(
@@ -414,6 +718,25 @@ pub struct Options {
     )]
     pub dataset_fields_allowed_limit: usize,
 
+    /// Naming convention of the streams created by users, e.g. `[a-z]+-(dev|prod)-[a-z0-9-]+`.
+    /// The whole name has to match, on top of the built-in rules on stream names.
+    #[arg(
+        long,
+        env = "P_STREAM_NAME_PATTERN",
+        value_parser = validation::stream_name_pattern,
+        value_name = "regex"
+    )]
+    pub stream_name_pattern: Option<regex::Regex>,
+
+    #[arg(
+        long,
+        env = "P_STREAM_NAME_RESERVED",
+        value_delimiter = ',',
+        value_name = "names",
+        help = "Comma separated names that can't be used for streams created by users, regardless of case"
+    )]
+    pub stream_name_reserved: Vec<String>,
+
     // maximum level of flattening allowed for events
     // this is to prevent nested list type fields from getting created
     #[arg(
@@ -458,6 +781,54 @@ pub struct Options {
         help = "Object store sync threshold in seconds"
     )]
     pub object_store_sync_threshold: u64,
+
+    #[arg(
+        long,
+        env = "P_OBJECT_STORE_SYNC_INTERVAL",
+        default_value = "30",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Interval in seconds at which staged parquet files are uploaded to the object store"
+    )]
+    pub object_store_sync_interval: u64,
+
+    // the staging directory grows when uploads can't keep up with ingestion
+    #[arg(
+        long,
+        env = "P_STAGING_BACKLOG_THRESHOLD",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Size in MiB of the staged parquet files waiting for upload past which the object store sync warns it is falling behind"
+    )]
+    pub staging_backlog_threshold: Option<u64>,
+
+    // staged parquet files failing to upload are retried with an exponential backoff,
+    // after this many failed attempts they are moved to the failed/ directory of the stream's staging
+    #[arg(
+        long,
+        env = "P_UPLOAD_MAX_RETRIES",
+        default_value = "10",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Number of failed uploads after which a staged parquet file is quarantined"
+    )]
+    pub upload_max_retries: u32,
+
+    // a backfill can otherwise start a multipart upload per staged file at once,
+    // each one buffering its file in memory and holding its own connections
+    #[arg(
+        long,
+        env = "P_MAX_MULTIPART_UPLOADS",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Maximum number of multipart uploads in flight across the process"
+    )]
+    pub max_multipart_uploads: Option<u32>,
+
+    // a custom partition on a high cardinality field would otherwise create a prefix per value
+    #[arg(
+        long,
+        env = "P_MAX_CUSTOM_PARTITIONS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Maximum number of distinct custom partition values per stream, events of new values past it are stored in an overflow partition"
+    )]
+    pub max_custom_partitions: Option<u64>,
     // the oidc scope
     #[arg(
         long = "oidc-scope",
@@ -479,6 +850,99 @@ pub struct Options {
         help = "Max allowed age gap (in hours) between events within the same node, relative to the reference event"
     )]
     pub event_max_chunk_age: u64,
+
+    // re-evaluate alerts once on startup so their state reflects the current data
+    #[arg(
+        long,
+        env = "P_ALERT_RECONCILE_ON_LOAD",
+        default_value = "false",
+        help = "Enable/Disable evaluating every alert once on startup to correct stale alert states"
+    )]
+    pub alert_reconcile_on_load: bool,
+
+    // alerts of freshly provisioned streams would otherwise fire on little or no data
+    #[arg(
+        long,
+        env = "P_ALERT_WARMUP_MINUTES",
+        default_value = "0",
+        value_parser = clap::value_parser!(u64).range(0..=MAX_ALERT_WARMUP_MINUTES),
+        help = "Minutes after an alert is created during which its evaluations don't change its state or send notifications"
+    )]
+    pub alert_warmup_minutes: u64,
+
+    // a badly scoped alert would otherwise run an expensive scan on every evaluation
+    #[arg(
+        long,
+        env = "P_ALERT_EVAL_TIMEOUT",
+        default_value = "300",
+        value_parser = validation::validate_seconds,
+        help = "Time in seconds after which an alert evaluation is aborted, leaving the alert state unchanged"
+    )]
+    pub alert_eval_timeout: u64,
+
+    #[arg(
+        long,
+        env = "P_ALERT_EVAL_MAX_SCAN_SIZE",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Maximum size in MiB of the data an alert evaluation may scan, evaluations above it are aborted"
+    )]
+    pub alert_eval_max_scan_size: Option<u64>,
+
+    // alerts on the same frequency would otherwise all query at once
+    #[arg(
+        long,
+        env = "P_MAX_CONCURRENT_ALERT_EVALS",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Maximum number of scheduled alert evaluations running at once, others wait for their turn"
+    )]
+    pub max_concurrent_alert_evals: Option<u32>,
+
+    #[arg(
+        long,
+        env = "P_ALERT_SEVERITY_TARGETS",
+        value_parser = validation::severity_targets,
+        help = "Comma separated <severity>:<target id>[|<target id>...] pairs, the targets notified for alerts of that severity which don't have targets of their own"
+    )]
+    pub alert_severity_targets: Option<HashMap<String, Vec<ulid::Ulid>>>,
+
+    #[arg(
+        long,
+        env = "P_MAX_ALERTS_TOTAL",
+        help = "Maximum number of alerts that can be created on the deployment"
+    )]
+    pub max_alerts_total: Option<usize>,
+
+    #[arg(
+        long,
+        env = "P_MAX_ALERTS_PER_STREAM",
+        help = "Maximum number of alerts that can be created on a single dataset"
+    )]
+    pub max_alerts_per_stream: Option<usize>,
+
+    #[arg(
+        long,
+        env = "P_MAX_TARGETS_PER_ALERT",
+        help = "Maximum number of targets a single alert can notify"
+    )]
+    pub max_targets_per_alert: Option<usize>,
+
+    #[arg(
+        long,
+        env = "P_CHECK_ALERT_TARGETS",
+        value_name = "bool",
+        default_value = "false",
+        help = "Check that the endpoints of the targets of an alert are reachable when the alert is saved. Unreachable endpoints are reported as warnings"
+    )]
+    pub check_alert_targets: bool,
+
+    #[arg(
+        long,
+        env = "P_DELETED_STREAM_ALERT_POLICY",
+        default_value = "mark",
+        value_parser = validation::deleted_stream_alert_policy,
+        help = "What happens to the alerts of a deleted stream, mark or delete. Marked alerts stop being evaluated and are set to the stream-missing state"
+    )]
+    pub deleted_stream_alert_policy: DeletedStreamAlertPolicy,
 }
 
 #[derive(Parser, Debug)]
@@ -525,6 +989,19 @@ impl Options {
         }
     }
 
+    /// Resolves the user a verified client certificate authenticates as, `None` when the
+    /// certificate isn't mapped to a user
+    pub fn client_cert_username(&self, common_name: &str) -> Option<String> {
+        self.tls_client_cert_user_map
+            .as_ref()
+            .and_then(|map| map.get(common_name))
+            .cloned()
+            .or_else(|| {
+                self.tls_client_cert_cn_passthrough
+                    .then(|| common_name.to_owned())
+            })
+    }
+
     pub fn openid(&self) -> Option<OpenidConfig> {
         let OidcConfig {
             secret,
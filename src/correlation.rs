@@ -16,10 +16,13 @@
  *
  */
 
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
 
-use actix_web::{Error, http::header::ContentType};
-use chrono::Utc;
+use actix_web::{Error, http::header::ContentType, web::Bytes};
+use chrono::{DateTime, Utc};
 use datafusion::error::DataFusionError;
 use http::StatusCode;
 use itertools::Itertools;
@@ -32,15 +35,16 @@ use tracing::error;
 
 use crate::{
     handlers::http::{
+        query::QueryError,
         rbac::RBACError,
         users::{CORRELATION_DIR, USERS_ROOT_DIR},
     },
     metastore::{MetastoreError, metastore_traits::MetastoreObject},
     parseable::PARSEABLE,
-    query::QUERY_SESSION,
+    query::{QUERY_SESSION, resolve_stream_names},
     rbac::{Users, map::SessionKey},
     storage::ObjectStorageError,
-    users::filters::FilterQuery,
+    users::filters::{FilterQuery, FilterType},
     utils::{get_hash, user_auth_for_datasets},
 };
 
@@ -59,14 +63,25 @@ impl Correlations {
         let mut guard = self.write().await;
 
         for correlations_bytes in all_correlations {
-            let correlation = match serde_json::from_slice::<CorrelationConfig>(&correlations_bytes)
-            {
-                Ok(c) => c,
-                Err(e) => {
-                    error!("Unable to load correlation file : {e}");
-                    continue;
+            let mut correlation =
+                match serde_json::from_slice::<CorrelationConfig>(&correlations_bytes) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        error!("Unable to load correlation file : {e}");
+                        continue;
+                    }
+                };
+
+            // v1 correlations only lack the optional description, bump the version and persist
+            if matches!(correlation.version, CorrelationVersion::V1) {
+                correlation.version = CorrelationVersion::V2;
+                if let Err(e) = PARSEABLE.metastore.put_correlation(&correlation).await {
+                    error!(
+                        "Unable to migrate correlation with ID- {} to v2: {e}",
+                        correlation.id
+                    );
                 }
-            };
+            }
 
             guard.insert(correlation.id.to_owned(), correlation);
         }
@@ -74,14 +89,23 @@ impl Correlations {
         Ok(())
     }
 
+    /// List the correlations owned by the user along with the shared ones
+    /// which the user is authorized to query
     pub async fn list_correlations(
         &self,
         session_key: &SessionKey,
     ) -> Result<Vec<CorrelationConfig>, CorrelationError> {
         let mut user_correlations = vec![];
         let permissions = Users.get_permissions(session_key);
+        let user_id = Users
+            .get_userid_from_session(session_key)
+            .map(|user| get_hash(&user))
+            .unwrap_or_default();
 
         for correlation in self.read().await.values() {
+            if !correlation.is_visible_to(&user_id) {
+                continue;
+            }
             let tables = &correlation
                 .table_configs
                 .iter()
@@ -95,6 +119,42 @@ impl Correlations {
         Ok(user_correlations)
     }
 
+    /// The correlations whose query reads the stream, from the tables of their SQL filter query
+    /// or of their join, among the correlations the user can list
+    pub async fn querying(
+        &self,
+        stream_name: &str,
+        session_key: &SessionKey,
+    ) -> Result<Vec<CorrelationDependent>, CorrelationError> {
+        let dependents = self
+            .list_correlations(session_key)
+            .await?
+            .into_iter()
+            .filter(|correlation| {
+                correlation
+                    .generate_sql()
+                    .ok()
+                    .and_then(|sql| resolve_stream_names(&sql).ok())
+                    .unwrap_or_else(|| {
+                        correlation
+                            .table_configs
+                            .iter()
+                            .map(|t| t.table_name.clone())
+                            .collect()
+                    })
+                    .iter()
+                    .any(|table| table == stream_name)
+            })
+            .map(|correlation| CorrelationDependent {
+                id: correlation.id,
+                title: correlation.title,
+                owner: correlation.owner,
+            })
+            .collect();
+
+        Ok(dependents)
+    }
+
     pub async fn get_correlation(
         &self,
         correlation_id: &str,
@@ -117,7 +177,9 @@ impl Correlations {
         session_key: &SessionKey,
     ) -> Result<CorrelationConfig, CorrelationError> {
         correlation.id = get_hash(Utc::now().timestamp_micros().to_string().as_str());
+        correlation.version = CorrelationVersion::V2;
         correlation.validate(session_key).await?;
+        self.validate_title(&correlation).await?;
 
         // Update in metastore
         PARSEABLE.metastore.put_correlation(&correlation).await?;
@@ -133,11 +195,11 @@ impl Correlations {
     /// Update existing correlation for the user and with the same ID
     pub async fn update(
         &self,
-        mut updated_correlation: CorrelationConfig,
+        updated_correlation: CorrelationConfig,
         session_key: &SessionKey,
     ) -> Result<CorrelationConfig, CorrelationError> {
         // validate whether user has access to this correlation object or not
-        let correlation = self.get_correlation(&updated_correlation.id).await?;
+        let mut correlation = self.get_correlation(&updated_correlation.id).await?;
         if correlation.user_id != updated_correlation.user_id {
             return Err(CorrelationError::AnyhowError(anyhow::Error::msg(format!(
                 r#"User "{}" isn't authorized to update correlation with ID - {}"#,
@@ -145,22 +207,45 @@ impl Correlations {
             ))));
         }
 
+        correlation.update(updated_correlation);
+        correlation.version = CorrelationVersion::V2;
         correlation.validate(session_key).await?;
-        updated_correlation.update(correlation);
+        self.validate_title(&correlation).await?;
 
         // Update in metastore
-        PARSEABLE
-            .metastore
-            .put_correlation(&updated_correlation)
-            .await?;
+        PARSEABLE.metastore.put_correlation(&correlation).await?;
 
         // Update in memory
-        self.write().await.insert(
-            updated_correlation.id.to_owned(),
-            updated_correlation.clone(),
-        );
+        self.write()
+            .await
+            .insert(correlation.id.to_owned(), correlation.clone());
+
+        // the cached result may not match the updated definition
+        CORRELATION_RESULT_CACHE.invalidate(&correlation.id);
+
+        Ok(correlation)
+    }
 
-        Ok(updated_correlation)
+    /// Title should be non-empty and unique amongst the correlations of the same user
+    async fn validate_title(
+        &self,
+        correlation: &CorrelationConfig,
+    ) -> Result<(), CorrelationError> {
+        let title = correlation.title.trim();
+        if title.is_empty() {
+            return Err(CorrelationError::Metadata("Title cannot be empty"));
+        }
+
+        let is_duplicate = self.read().await.values().any(|c| {
+            c.id != correlation.id
+                && c.user_id == correlation.user_id
+                && c.title.trim().eq_ignore_ascii_case(title)
+        });
+        if is_duplicate {
+            return Err(CorrelationError::DuplicateTitle(title.to_owned()));
+        }
+
+        Ok(())
     }
 
     /// Delete correlation from memory and storage
@@ -181,21 +266,82 @@ impl Correlations {
 
         // Delete from memory
         self.write().await.remove(&correlation.id);
+        CORRELATION_RESULT_CACHE.invalidate(&correlation.id);
 
         Ok(())
     }
 }
 
+/// Last result of each correlation run, for the correlations with a cache ttl
+pub static CORRELATION_RESULT_CACHE: Lazy<CorrelationResultCache> =
+    Lazy::new(CorrelationResultCache::default);
+
+struct CachedRun {
+    // identifies the user and the parameters the correlation was run with
+    key: String,
+    body: Bytes,
+    cached_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+pub struct CorrelationResultCache(Mutex<HashMap<CorrelationId, CachedRun>>);
+
+impl CorrelationResultCache {
+    /// Returns the cached response body and its age in seconds, if it was cached for the same key within `ttl` seconds
+    pub fn get(&self, correlation_id: &str, key: &str, ttl: u64) -> Option<(Bytes, i64)> {
+        let cache = self.0.lock().expect("lock poisoned");
+        let cached = cache.get(correlation_id)?;
+        let age = (Utc::now() - cached.cached_at).num_seconds();
+        (cached.key == key && age < ttl as i64).then(|| (cached.body.clone(), age))
+    }
+
+    /// Replaces the cached result of the correlation
+    pub fn insert(&self, correlation_id: &str, key: String, body: Bytes) {
+        self.0.lock().expect("lock poisoned").insert(
+            correlation_id.to_owned(),
+            CachedRun {
+                key,
+                body,
+                cached_at: Utc::now(),
+            },
+        );
+    }
+
+    pub fn invalidate(&self, correlation_id: &str) {
+        self.0.lock().expect("lock poisoned").remove(correlation_id);
+    }
+}
+
+/// Private correlations are only visible to their owner,
+/// shared ones to every user who is authorized to query the underlying datasets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CorrelationVisibility {
+    Private,
+    #[default]
+    Shared,
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum CorrelationVersion {
     #[default]
     V1,
+    V2,
 }
 
 type CorrelationId = String;
 type UserId = String;
 
+/// A correlation whose query reads a stream
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorrelationDependent {
+    pub id: CorrelationId,
+    pub title: String,
+    pub owner: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CorrelationConfig {
@@ -203,14 +349,25 @@ pub struct CorrelationConfig {
     pub version: CorrelationVersion,
     pub title: String,
     #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
     pub id: CorrelationId,
     #[serde(default)]
     pub user_id: UserId,
+    /// username of the user who created the correlation
+    #[serde(default)]
+    pub owner: String,
+    #[serde(default)]
+    pub visibility: CorrelationVisibility,
     pub table_configs: Vec<TableConfig>,
     pub join_config: JoinConfig,
     pub filter: Option<FilterQuery>,
     pub start_time: Option<String>,
     pub end_time: Option<String>,
+    /// Seconds for which the last result of the correlation is served by the run endpoint,
+    /// results are not cached if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_ttl: Option<u64>,
 }
 
 impl MetastoreObject for CorrelationConfig {
@@ -235,11 +392,64 @@ impl CorrelationConfig {
 
     pub fn update(&mut self, update: Self) {
         self.title = update.title;
+        self.description = update.description;
+        self.visibility = update.visibility;
         self.table_configs = update.table_configs;
         self.join_config = update.join_config;
         self.filter = update.filter;
         self.start_time = update.start_time;
         self.end_time = update.end_time;
+        self.cache_ttl = update.cache_ttl;
+    }
+
+    /// SQL to run for this correlation, the SQL filter query if one was saved
+    /// otherwise a join of the selected fields of both tables on the join conditions
+    /// The query of the correlation, fails when the stored correlation doesn't join two tables
+    pub fn generate_sql(&self) -> Result<String, CorrelationError> {
+        if let Some(filter) = &self.filter
+            && filter.filter_type == FilterType::SQL
+            && let Some(query) = &filter.filter_query
+        {
+            return Ok(query.clone());
+        }
+
+        let selected_fields = self
+            .table_configs
+            .iter()
+            .flat_map(|t| {
+                t.selected_fields
+                    .iter()
+                    .map(move |f| format!("\"{}\".\"{f}\"", t.table_name))
+            })
+            .join(", ");
+        let join_fields = self
+            .join_config
+            .join_conditions
+            .iter()
+            .map(|j| format!("\"{}\".\"{}\"", j.table_name, j.field))
+            .collect_vec();
+        let tables = self
+            .table_configs
+            .iter()
+            .map(|t| format!("\"{}\"", t.table_name))
+            .collect_vec();
+
+        let ([left, right, ..], [left_field, right_field, ..]) =
+            (tables.as_slice(), join_fields.as_slice())
+        else {
+            return Err(CorrelationError::Metadata(
+                "Correlation must join two tables on a field of each",
+            ));
+        };
+
+        Ok(format!(
+            "SELECT {selected_fields} FROM {left} JOIN {right} ON {left_field} = {right_field}"
+        ))
+    }
+
+    /// Only the owner can see a private correlation
+    pub fn is_visible_to(&self, user_id: &str) -> bool {
+        self.visibility == CorrelationVisibility::Shared || self.user_id == user_id
     }
 
     /// This function will validate the TableConfigs, JoinConfig, and user auth
@@ -332,6 +542,12 @@ pub enum CorrelationError {
     ActixError(#[from] Error),
     #[error(transparent)]
     MetastoreError(#[from] MetastoreError),
+    #[error("Invalid query parameter: {0}")]
+    InvalidQueryParameter(String),
+    #[error("{0}")]
+    Query(#[from] QueryError),
+    #[error("A correlation with the title \"{0}\" already exists")]
+    DuplicateTitle(String),
 }
 
 impl actix_web::ResponseError for CorrelationError {
@@ -346,6 +562,9 @@ impl actix_web::ResponseError for CorrelationError {
             Self::DataFusion(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::ActixError(_) => StatusCode::BAD_REQUEST,
             Self::MetastoreError(e) => e.status_code(),
+            Self::InvalidQueryParameter(_) => StatusCode::BAD_REQUEST,
+            Self::DuplicateTitle(_) => StatusCode::CONFLICT,
+            Self::Query(e) => actix_web::ResponseError::status_code(e),
         }
     }
 
@@ -7,7 +7,7 @@ use relative_path::RelativePathBuf;
 
 use crate::query::stream_schema_provider::extract_primary_filter;
 use crate::{
-    catalog::{Snapshot, manifest::File, snapshot},
+    catalog::{Snapshot, consolidation, manifest::File},
     event,
     parseable::PARSEABLE,
     query::{PartialTimeFilter, stream_schema_provider::ManifestExt},
@@ -76,19 +76,8 @@ pub async fn fetch_parquet_file_paths(
 
     let time_filters = extract_primary_filter(&time_filter_expr, &time_partition);
 
-    let mut merged_snapshot: snapshot::Snapshot = snapshot::Snapshot::default();
-
-    let obs = PARSEABLE.metastore.get_all_stream_jsons(stream, None).await;
-    if let Ok(obs) = obs {
-        for ob in obs {
-            if let Ok(object_store_format) = serde_json::from_slice::<ObjectStoreFormat>(&ob) {
-                let snapshot = object_store_format.snapshot;
-                for manifest in snapshot.manifest_list {
-                    merged_snapshot.manifest_list.push(manifest);
-                }
-            }
-        }
-    }
+    let merged_snapshot =
+        consolidation::merged_snapshot(stream, time_partition.as_ref(), &time_filters).await;
 
     let mut manifest_files = Vec::new();
 
@@ -21,6 +21,7 @@ pub mod format;
 
 use arrow_array::RecordBatch;
 use arrow_schema::{Field, Fields, Schema};
+use arrow_select::concat::concat_batches;
 use itertools::Itertools;
 use std::sync::Arc;
 
@@ -30,7 +31,8 @@ use crate::{
     metadata::update_stats,
     metrics::{increment_events_ingested_by_date, increment_events_ingested_size_by_date},
     parseable::{PARSEABLE, StagingError},
-    storage::StreamType,
+    query::result_cache::QUERY_RESULT_CACHE,
+    storage::{StreamType, type_widening::merge_schemas},
 };
 use chrono::NaiveDateTime;
 use std::collections::HashMap;
@@ -56,7 +58,10 @@ pub struct Event {
 
 // Events holds the schema related to a each event for a single log stream
 impl Event {
-    pub fn process(self) -> Result<(), EventError> {
+    pub fn process(mut self) -> Result<(), EventError> {
+        let stream = PARSEABLE.get_or_create_stream(&self.stream_name);
+        stream.route_custom_partition(&mut self.custom_partition_values);
+
         let mut key = get_schema_key(&self.rb.schema().fields);
         if self.time_partition.is_some() {
             let parsed_timestamp_to_min = self.parsed_timestamp.format("%Y%m%dT%H%M").to_string();
@@ -73,7 +78,7 @@ impl Event {
             commit_schema(&self.stream_name, self.rb.schema())?;
         }
 
-        PARSEABLE.get_or_create_stream(&self.stream_name).push(
+        stream.push(
             &key,
             &self.rb,
             self.parsed_timestamp,
@@ -95,6 +100,7 @@ impl Event {
         increment_events_ingested_size_by_date(self.origin_size, &date_string);
 
         crate::livetail::LIVETAIL.process(&self.stream_name, &self.rb);
+        QUERY_RESULT_CACHE.invalidate(&self.stream_name, self.parsed_timestamp.and_utc());
 
         Ok(())
     }
@@ -114,6 +120,63 @@ impl Event {
     }
 }
 
+/// Merges the events bound to the same staging file, i.e. of the same stream, schema, minute and
+/// custom partition, into one record batch each so that partitioned payloads are pushed per
+/// partition rather than per event
+pub fn coalesce(events: Vec<Event>) -> Vec<Event> {
+    // fields inferred in another order or type are pushed apart, so the events of a partition
+    // are grouped by schema as well
+    let mut groups: Vec<Vec<Event>> = Vec::with_capacity(events.len());
+    let mut partitions: HashMap<String, Vec<usize>> = HashMap::new();
+    for event in events {
+        let key = format!(
+            "{}/{}/{}/{}",
+            event.stream_name,
+            get_schema_key(&event.rb.schema().fields),
+            event.parsed_timestamp.format("%Y%m%dT%H%M"),
+            event
+                .custom_partition_values
+                .iter()
+                .sorted()
+                .map(|(k, v)| format!("{k}={v}"))
+                .join("&")
+        );
+        let indices = partitions.entry(key).or_default();
+        match indices
+            .iter()
+            .copied()
+            .find(|&index| groups[index][0].rb.schema() == event.rb.schema())
+        {
+            Some(index) => groups[index].push(event),
+            None => {
+                indices.push(groups.len());
+                groups.push(vec![event]);
+            }
+        }
+    }
+
+    groups.into_iter().flat_map(merge_events).collect()
+}
+
+/// Concatenates the record batches of events sharing a schema in a single pass
+fn merge_events(mut events: Vec<Event>) -> Vec<Event> {
+    if events.len() == 1 {
+        return events;
+    }
+    let schema = events[0].rb.schema();
+    let Ok(rb) = concat_batches(&schema, events.iter().map(|event| &event.rb)) else {
+        return events;
+    };
+    let origin_size = events.iter().map(|event| event.origin_size).sum();
+    let is_first_event = events.iter().any(|event| event.is_first_event);
+
+    let mut merged = events.swap_remove(0);
+    merged.rb = rb;
+    merged.origin_size = origin_size;
+    merged.is_first_event = is_first_event;
+    vec![merged]
+}
+
 pub fn get_schema_key(fields: &[Arc<Field>]) -> String {
     // Fields must be sorted
     let mut hasher = xxhash_rust::xxh3::Xxh3::new();
@@ -135,7 +198,7 @@ pub fn commit_schema(stream_name: &str, schema: Arc<Schema>) -> Result<(), Stagi
         .expect(LOCK_EXPECT)
         .schema;
     let current_schema = Schema::new(map.values().cloned().collect::<Fields>());
-    let schema = Schema::try_merge(vec![current_schema, schema.as_ref().clone()])?;
+    let schema = merge_schemas(vec![current_schema, schema.as_ref().clone()])?;
     map.clear();
     map.extend(schema.fields.iter().map(|f| (f.name().clone(), f.clone())));
     Ok(())
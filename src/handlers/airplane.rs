@@ -38,6 +38,7 @@ use crate::handlers::livetail::cross_origin_config;
 use crate::metrics::QUERY_EXECUTE_TIME;
 use crate::parseable::PARSEABLE;
 use crate::query::{QUERY_SESSION, execute, resolve_stream_names};
+use crate::storage::masking::{mask_logical_plan, masks_for_session};
 use crate::utils::arrow::flight::{
     append_temporary_events, get_query_from_ticket, into_flight_data, run_do_get_rpc,
     send_to_ingester,
@@ -148,7 +149,7 @@ impl FlightService for AirServiceImpl {
             .to_owned();
 
         // map payload to query
-        let query = into_query(&ticket, &session_state, time_range)
+        let mut query = into_query(&ticket, &session_state, time_range)
             .await
             .map_err(|_| Status::internal("Failed to parse query"))?;
 
@@ -203,6 +204,10 @@ impl FlightService for AirServiceImpl {
             .map_err(|_| {
                 Status::permission_denied("User Does not have permission to access this")
             })?;
+        // columns masked for the roles of the requesting user
+        let masks = masks_for_session(&key, &streams);
+        query.raw_logical_plan = mask_logical_plan(query.raw_logical_plan, &masks)
+            .map_err(|err| Status::internal(err.to_string()))?;
         let time = Instant::now();
 
         let (records, _) = execute(query, false)
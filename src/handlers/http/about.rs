@@ -21,8 +21,9 @@ use serde_json::{Value, json};
 
 use crate::{
     about::{self, get_latest_release},
+    analytics::Report,
     parseable::PARSEABLE,
-    storage::StorageMetadata,
+    storage::{REDACTED, StorageMetadata, redact_secret, redact_url},
 };
 use std::path::PathBuf;
 
@@ -107,3 +108,121 @@ pub async fn about() -> Json<Value> {
         },
     }))
 }
+
+/// The configuration the server is running with, as resolved from the arguments, the environment
+/// and the defaults. Credentials are redacted, only whether they are set is shown.
+pub async fn config() -> Json<Value> {
+    let options = &PARSEABLE.options;
+    let path = |path: &Option<PathBuf>| path.as_ref().map(|path| path.display().to_string());
+
+    Json(json!({
+        "mode": PARSEABLE.get_server_mode_string(),
+        "server": {
+            "address": options.address,
+            "domainAddress": options.domain_address.as_ref().map(|url| redact_url(url.as_str())),
+            "grpcPort": options.grpc_port,
+            "flightPort": options.flight_port,
+            "username": options.username,
+            "password": REDACTED,
+            "cors": options.cors,
+            "corsAllowedOrigins": options.cors_allowed_origins,
+            "accessLogFormat": format!("{:?}", options.access_log_format),
+            "metricsEndpointAuth": options.metrics_endpoint_auth,
+            "ingestorSyncPolicy": format!("{:?}", options.ingestor_sync_policy),
+        },
+        "storage": {
+            "type": PARSEABLE.get_storage_mode_string(),
+            "provider": PARSEABLE.storage.name(),
+            "config": PARSEABLE.storage.redacted_config(),
+            "parquetCompression": format!("{:?}", options.parquet_compression),
+            "rowGroupSize": options.row_group_size,
+            "uploadMaxRetries": options.upload_max_retries,
+            "maxMultipartUploads": options.max_multipart_uploads,
+            "snapshotHistoryDays": options.snapshot_history_days,
+            "snapshotConsolidationInterval": options.snapshot_consolidation_interval,
+        },
+        "paths": {
+            "staging": options.staging_dir().display().to_string(),
+            "hotTier": path(PARSEABLE.hot_tier_dir()),
+            "index": path(&options.index_storage_path),
+            "querySpill": path(&options.query_spill_path),
+            "maxDiskUsage": options.max_disk_usage,
+        },
+        "tls": {
+            "certPath": path(&options.tls_cert_path),
+            "keyPath": path(&options.tls_key_path),
+            "trustedCaCertsPath": path(&options.trusted_ca_certs_path),
+            "skipVerify": options.tls_skip_verify,
+            "requireClientCert": options.tls_require_client_cert,
+            "clientCertCnPassthrough": options.tls_client_cert_cn_passthrough,
+            "outboundCaBundle": path(&options.outbound_ca_bundle),
+            "outboundSkipVerify": options.outbound_tls_skip_verify,
+            "intraClusterClientCertPath": path(&options.intra_cluster_client_cert_path),
+        },
+        "query": {
+            "memoryPoolSize": options.query_memory_pool_size,
+            "maxConcurrent": options.query_max_concurrent,
+            "queueTimeout": options.query_queue_timeout,
+            "timeout": options.query_timeout,
+            "defaultRange": options.default_query_range,
+            "jobsMax": options.query_jobs_max,
+            "jobsTtl": options.query_jobs_ttl,
+            "cacheTtl": options.query_cache_ttl,
+            "cacheSize": options.query_cache_size,
+            "executionBatchSize": options.execution_batch_size,
+        },
+        "limits": {
+            "maxMetadataPayloadSize": options.max_metadata_payload_size,
+            "datasetFieldsAllowedLimit": options.dataset_fields_allowed_limit,
+            "eventFlattenLevel": options.event_flatten_level,
+            "maxFieldStatistics": options.max_field_statistics,
+            "maxCustomPartitions": options.max_custom_partitions,
+            "localSyncThreshold": options.local_sync_threshold,
+            "objectStoreSyncThreshold": options.object_store_sync_threshold,
+            "objectStoreSyncInterval": options.object_store_sync_interval,
+            "stagingBacklogThreshold": options.staging_backlog_threshold,
+            "eventMaxChunkAge": options.event_max_chunk_age,
+            "livetailChannelCapacity": options.livetail_channel_capacity,
+            "cpuUtilizationThreshold": options.cpu_utilization_threshold,
+            "memoryUtilizationThreshold": options.memory_utilization_threshold,
+            "resourceCheckInterval": options.resource_check_interval,
+        },
+        "alerts": {
+            "reconcileOnLoad": options.alert_reconcile_on_load,
+            "warmupMinutes": options.alert_warmup_minutes,
+            "evalTimeout": options.alert_eval_timeout,
+            "evalMaxScanSize": options.alert_eval_max_scan_size,
+            "maxConcurrentEvals": options.max_concurrent_alert_evals,
+            "resultsStream": options.alert_results_stream,
+            "maxAlertsTotal": options.max_alerts_total,
+            "maxAlertsPerStream": options.max_alerts_per_stream,
+            "maxTargetsPerAlert": options.max_targets_per_alert,
+            "checkTargets": options.check_alert_targets,
+            "deletedStreamPolicy": format!("{:?}", options.deleted_stream_alert_policy),
+        },
+        "features": {
+            "checkUpdate": options.check_update,
+            "sendAnalytics": options.send_analytics,
+            "maskPii": options.mask_pii,
+            "collectDatasetStats": options.collect_dataset_stats,
+            "internalStream": options.internal_stream_name,
+            "internalStreamRetentionDays": options.internal_stream_retention_days,
+            "openAiKey": redact_secret(&options.open_ai_key),
+            "oidc": options.oidc.as_ref().map(|oidc| json!({
+                "issuer": redact_url(oidc.issuer.as_str()),
+                "clientId": oidc.client_id,
+                "secret": REDACTED,
+            })),
+        },
+    }))
+}
+
+/// The anonymous usage data exactly as it would be sent, after the configured exclusions and redactions
+pub async fn analytics_preview() -> Result<Json<Value>, actix_web::Error> {
+    let payload = Report::new()
+        .await
+        .and_then(|report| report.payload())
+        .map_err(|err| actix_web::error::ErrorInternalServerError(err.to_string()))?;
+
+    Ok(Json(payload))
+}
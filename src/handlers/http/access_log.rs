@@ -0,0 +1,156 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::{
+    fmt::Debug,
+    io::{Write, stdout},
+    time::Instant,
+};
+
+use actix_web::{
+    Error,
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::{Logger, Next},
+};
+use chrono::Utc;
+use serde_json::json;
+use tracing::{
+    Event, Level, Subscriber,
+    field::{Field, Visit},
+    info,
+};
+use tracing_subscriber::{
+    Layer,
+    filter::{Targets, filter_fn},
+    layer::{Context, Filter},
+};
+use ulid::Ulid;
+
+use crate::{
+    handlers::STREAM_NAME_HEADER_KEY, option::AccessLogFormat, parseable::PARSEABLE,
+    utils::get_user_from_request,
+};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Tracing target of the json access log lines
+pub const ACCESS_LOG_TARGET: &str = "access_log";
+
+// incoming request ids longer than this are replaced with a generated one
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// actix's default access log format, with the request id appended
+const TEXT_LOG_FORMAT: &str = r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T %{x-request-id}o"#;
+
+/// Text access logger, used unless the access log format is set to json
+pub fn text_logger() -> Logger {
+    Logger::new(TEXT_LOG_FORMAT)
+}
+
+/// Writes the json access log lines to stdout as they are, without the timestamp, level and
+/// target prefixes of the other logs
+struct JsonLogLayer;
+
+impl<S: Subscriber> Layer<S> for JsonLogLayer {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        let mut line = MessageVisitor(None);
+        event.record(&mut line);
+        if let Some(line) = line.0 {
+            _ = writeln!(stdout().lock(), "{line}");
+        }
+    }
+}
+
+struct MessageVisitor(Option<String>);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
+
+/// Layer of the json access log lines, enabled regardless of `RUST_LOG`
+pub fn json_log_layer<S: Subscriber>() -> impl Layer<S> {
+    JsonLogLayer.with_filter(Targets::new().with_target(ACCESS_LOG_TARGET, Level::INFO))
+}
+
+/// Keeps the json access log lines out of the other layers
+pub fn exclude_access_log<S>() -> impl Filter<S> {
+    filter_fn(|metadata| metadata.target() != ACCESS_LOG_TARGET)
+}
+
+/// Tags every request with a request id, reusing the one sent by the client in `X-Request-Id`
+/// if any, and returns it in the response headers.
+/// Logs one JSON access log line per request when the access log format is json.
+pub async fn access_log_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_owned)
+        .unwrap_or_else(|| Ulid::new().to_string());
+    let http_req = req.request().clone();
+    let start = Instant::now();
+
+    // errors are turned into responses here so that they are logged and tagged as well
+    let mut res = match next.call(req).await {
+        Ok(res) => res.map_into_boxed_body(),
+        Err(err) => ServiceResponse::from_err(err, http_req),
+    };
+    let latency = start.elapsed();
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut()
+            .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+
+    if PARSEABLE.options.access_log_format == AccessLogFormat::Json {
+        let req = res.request();
+        let stream = req.match_info().get("logstream").or_else(|| {
+            req.headers()
+                .get(STREAM_NAME_HEADER_KEY)
+                .and_then(|stream| stream.to_str().ok())
+        });
+        let line = json!({
+            "timestamp": Utc::now().to_rfc3339(),
+            "request_id": request_id,
+            "method": req.method().as_str(),
+            "path": req.path(),
+            "status": res.status().as_u16(),
+            "latency_ms": latency.as_secs_f64() * 1000.0,
+            "user": get_user_from_request(req).ok(),
+            "stream": stream,
+            "remote_addr": req.connection_info().realip_remote_addr(),
+            "user_agent": req
+                .headers()
+                .get(actix_web::http::header::USER_AGENT)
+                .and_then(|agent| agent.to_str().ok()),
+        });
+        info!(target: ACCESS_LOG_TARGET, "{line}");
+    }
+
+    Ok(res)
+}
@@ -16,38 +16,56 @@
  *
  */
 
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 
 use crate::{
     alerts::{
         ALERTS, AlertError, AlertState, Severity,
         alert_enums::{AlertType, NotificationState},
-        alert_structs::{AlertConfig, AlertRequest, AlertStateEntry, NotificationStateRequest},
+        alert_structs::{
+            AlertConfig, AlertConfigResponse, AlertEvaluationResult, AlertImportResult,
+            AlertImportStatus, AlertRequest, AlertStateEntry, AlertsBundle, EvaluateBatchRequest,
+            NotificationStateRequest, ScheduledAlerts,
+        },
         alert_traits::AlertTrait,
         alert_types::ThresholdAlert,
-        target::Retry,
+        alerts_utils::evaluate_alert_now,
+        missing_columns::missing_column_warnings,
+        target::{self, DELIVERIES, Retry, TARGETS, TargetCheck},
+        validate_alert_limits, validate_target_count, validate_unique_title,
     },
+    handlers::http::pagination::Paginated,
     metastore::metastore_traits::MetastoreObject,
     parseable::PARSEABLE,
-    utils::{actix::extract_session_key_from_req, user_auth_for_query},
+    rbac::map::SessionKey,
+    utils::{actix::extract_session_key_from_req, is_admin, user_auth_for_query},
 };
 use actix_web::{
-    HttpRequest, Responder,
+    HttpRequest, HttpResponse, Responder,
     web::{self, Json, Path},
 };
 use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use regex::Regex;
 use ulid::Ulid;
 
 // Reserved query parameter names that are not treated as other_fields filters
-const RESERVED_PARAMS: [&str; 3] = ["tags", "offset", "limit"];
+const RESERVED_PARAMS: [&str; 4] = ["tags", "offset", "limit", "paginated"];
 const MAX_LIMIT: usize = 1000;
 const DEFAULT_LIMIT: usize = 100;
+// Alerts are evaluated one after the other within the request, bound the time it can take
+const MAX_BATCH_EVALUATIONS: usize = 50;
 
 /// Query parameters for listing alerts
 struct ListQueryParams {
     tags_list: Vec<String>,
     offset: usize,
     limit: usize,
+    /// respond with a page along with the total count instead of a bare array
+    paginated: bool,
     other_fields_filters: HashMap<String, String>,
 }
 
@@ -65,6 +83,7 @@ fn parse_list_query_params(
             tags_list,
             offset,
             limit,
+            paginated: false,
             other_fields_filters,
         });
     }
@@ -115,6 +134,7 @@ fn parse_list_query_params(
         tags_list,
         offset,
         limit,
+        paginated: query_map.get("paginated").is_some_and(|p| p == "true"),
         other_fields_filters,
     })
 }
@@ -237,10 +257,18 @@ pub async fn list(req: HttpRequest) -> Result<impl Responder, AlertError> {
     // Sort alerts
     sort_alerts(&mut alerts_summary);
 
+    if params.paginated {
+        return Ok(HttpResponse::Ok().json(Paginated::new(
+            alerts_summary,
+            params.offset,
+            params.limit,
+        )));
+    }
+
     // Paginate results
     let paginated_alerts = paginate_alerts(alerts_summary, params.offset, params.limit);
 
-    Ok(web::Json(paginated_alerts))
+    Ok(HttpResponse::Ok().json(paginated_alerts))
 }
 
 // POST /alerts
@@ -248,6 +276,60 @@ pub async fn post(
     req: HttpRequest,
     Json(alert): Json<AlertRequest>,
 ) -> Result<impl Responder, AlertError> {
+    let session_key = extract_session_key_from_req(&req)?;
+    let alert = prepare_alert(alert).await?;
+    create_alert(&session_key, &alert).await?;
+
+    Ok(web::Json(alert_response(&alert).await))
+}
+
+/// The alert along with the warnings on its configuration, e.g. null checks that never match
+/// or unreachable targets
+async fn alert_response(alert: &dyn AlertTrait) -> AlertConfigResponse {
+    let mut response = alert.to_alert_config().to_response();
+    response.warnings = missing_column_warnings(
+        alert.get_query(),
+        alert.get_threshold_config().missing_columns,
+    )
+    .await
+    .unwrap_or_default();
+
+    if PARSEABLE.options.check_alert_targets {
+        response.target_checks = check_targets(alert.get_targets()).await;
+        response.warnings.extend(
+            response
+                .target_checks
+                .iter()
+                .filter(|check| !check.reachable)
+                .map(|check| {
+                    format!(
+                        "target {} ({}) is unreachable: {}",
+                        check.target_name,
+                        check.target_id,
+                        check.error.as_deref().unwrap_or_default()
+                    )
+                }),
+        );
+    }
+
+    response
+}
+
+/// Checks the reachability of the endpoints of the targets concurrently
+async fn check_targets(target_ids: &[Ulid]) -> Vec<TargetCheck> {
+    let mut targets = Vec::with_capacity(target_ids.len());
+    for id in target_ids {
+        // the targets were validated to exist along with the alert
+        if let Ok(target) = TARGETS.get_target_by_id(id).await {
+            targets.push(target);
+        }
+    }
+
+    join_all(targets.iter().map(|target| target.check_reachability())).await
+}
+
+/// Builds a new alert out of a request, validating its configuration
+async fn prepare_alert(alert: AlertRequest) -> Result<ThresholdAlert, AlertError> {
     let mut alert: AlertConfig = alert.into().await?;
 
     if alert.notification_config.interval > alert.get_eval_frequency() {
@@ -278,20 +360,15 @@ pub async fn post(
 
     alert.notification_config.times = Retry::Finite(times);
 
-    let threshold_alert;
-    let alert: &dyn AlertTrait = match &alert.alert_type {
-        AlertType::Threshold => {
-            threshold_alert = ThresholdAlert::from(alert);
-            &threshold_alert
-        }
-        AlertType::Anomaly(_) => {
-            return Err(AlertError::NotPresentInOSS("anomaly"));
-        }
-        AlertType::Forecast(_) => {
-            return Err(AlertError::NotPresentInOSS("forecast"));
-        }
-    };
+    match &alert.alert_type {
+        AlertType::Threshold => Ok(ThresholdAlert::from(alert)),
+        AlertType::Anomaly(_) => Err(AlertError::NotPresentInOSS("anomaly")),
+        AlertType::Forecast(_) => Err(AlertError::NotPresentInOSS("forecast")),
+    }
+}
 
+/// Validates the alert against the session, then persists and schedules it
+async fn create_alert(session_key: &SessionKey, alert: &dyn AlertTrait) -> Result<(), AlertError> {
     let guard = ALERTS.write().await;
     let alerts = if let Some(alerts) = guard.as_ref() {
         alerts
@@ -301,9 +378,17 @@ pub async fn post(
 
     // validate the incoming alert query
     // does the user have access to these tables or not?
-    let session_key = extract_session_key_from_req(&req)?;
-
-    alert.validate(&session_key).await?;
+    alert.validate(session_key).await?;
+
+    let existing_alerts = alerts.get_all_alerts().await;
+    validate_unique_title(alert, existing_alerts.values().map(|a| a.as_ref()))?;
+    validate_alert_limits(
+        alert,
+        existing_alerts.values().map(|a| a.as_ref()),
+        PARSEABLE.options.max_alerts_total,
+        PARSEABLE.options.max_alerts_per_stream,
+    )?;
+    validate_target_count(alert, PARSEABLE.options.max_targets_per_alert)?;
 
     // update persistent storage first
     PARSEABLE
@@ -324,7 +409,170 @@ pub async fn post(
     // start the task
     alerts.start_task(alert.clone_box()).await?;
 
-    Ok(web::Json(alert.to_alert_config().to_response()))
+    Ok(())
+}
+
+// GET /alerts/scheduled
+/// Lists the evaluation tasks scheduled for the alerts the user has access to,
+/// along with the alerts that failed to schedule
+pub async fn list_scheduled(req: HttpRequest) -> Result<impl Responder, AlertError> {
+    let session_key = extract_session_key_from_req(&req)?;
+
+    let guard = ALERTS.read().await;
+    let alerts = if let Some(alerts) = guard.as_ref() {
+        alerts
+    } else {
+        return Err(AlertError::CustomError("No AlertManager set".into()));
+    };
+
+    let visible = alerts
+        .list_alerts_for_user(session_key, vec![])
+        .await?
+        .into_iter()
+        .map(|alert| alert.id)
+        .collect::<HashSet<_>>();
+    let mut tasks = alerts
+        .scheduled_tasks()
+        .await?
+        .into_iter()
+        .filter(|task| visible.contains(&task.id))
+        .collect::<Vec<_>>();
+    tasks.sort_by_key(|task| task.id);
+
+    // alerts that failed to load can't be checked for access, they are listed for admins only
+    let is_admin = is_admin(&req)?;
+    let loaded = alerts.get_all_alerts().await;
+    let mut failed = alerts
+        .failed_tasks()
+        .await
+        .into_iter()
+        .filter(|task| visible.contains(&task.id) || (is_admin && !loaded.contains_key(&task.id)))
+        .collect::<Vec<_>>();
+    failed.sort_by_key(|task| task.id);
+
+    Ok(web::Json(ScheduledAlerts {
+        count: tasks.len(),
+        total_alerts: visible.len(),
+        tasks,
+        failed,
+    }))
+}
+
+// GET /alerts/export
+/// Exports the alerts the user has access to, without their ids and state
+pub async fn export(req: HttpRequest) -> Result<impl Responder, AlertError> {
+    let session_key = extract_session_key_from_req(&req)?;
+
+    let guard = ALERTS.read().await;
+    let alerts = if let Some(alerts) = guard.as_ref() {
+        alerts
+    } else {
+        return Err(AlertError::CustomError("No AlertManager set".into()));
+    };
+
+    let alerts = alerts.list_alerts_for_user(session_key, vec![]).await?;
+    let bundle = AlertsBundle {
+        alerts: alerts.into_iter().map(AlertRequest::from).collect(),
+        stream_mapping: HashMap::new(),
+    };
+
+    Ok(web::Json(bundle))
+}
+
+// POST /alerts/import
+/// Creates every alert of the bundle, alerts conflicting with an existing title are skipped
+pub async fn import(
+    req: HttpRequest,
+    Json(bundle): Json<AlertsBundle>,
+) -> Result<impl Responder, AlertError> {
+    let session_key = extract_session_key_from_req(&req)?;
+
+    let mut results = Vec::with_capacity(bundle.alerts.len());
+    for mut alert in bundle.alerts {
+        alert.query = remap_streams(&alert.query, &bundle.stream_mapping);
+        let title = alert.title.clone();
+
+        let alert = match prepare_alert(alert).await {
+            Ok(alert) => alert,
+            Err(err) => {
+                results.push(AlertImportResult {
+                    title,
+                    status: AlertImportStatus::Failed,
+                    id: None,
+                    error: Some(err.to_string()),
+                });
+                continue;
+            }
+        };
+
+        let existing_alerts = match ALERTS.read().await.as_ref() {
+            Some(alerts) => alerts.get_all_alerts().await,
+            None => return Err(AlertError::CustomError("No AlertManager set".into())),
+        };
+        if let Err(err) =
+            validate_unique_title(&alert, existing_alerts.values().map(|a| a.as_ref()))
+        {
+            results.push(AlertImportResult {
+                title,
+                status: AlertImportStatus::Conflict,
+                id: None,
+                error: Some(err.to_string()),
+            });
+            continue;
+        }
+
+        let result = match create_alert(&session_key, &alert).await {
+            Ok(()) => AlertImportResult {
+                title,
+                status: AlertImportStatus::Created,
+                id: Some(*alert.get_id()),
+                error: None,
+            },
+            Err(err) => AlertImportResult {
+                title,
+                status: AlertImportStatus::Failed,
+                id: None,
+                error: Some(err.to_string()),
+            },
+        };
+        results.push(result);
+    }
+
+    Ok(web::Json(results))
+}
+
+/// Replaces the stream names read by the query (after FROM / JOIN) as per the mapping.
+/// All names are replaced in a single pass, so a mapping can swap or chain names
+fn remap_streams(query: &str, stream_mapping: &HashMap<String, String>) -> String {
+    if stream_mapping.is_empty() {
+        return query.to_owned();
+    }
+    // longest first, so that a name isn't cut short by another name it starts with
+    let mut names: Vec<&String> = stream_mapping.keys().collect();
+    names.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    let names = names
+        .into_iter()
+        .map(|name| regex::escape(name))
+        .collect::<Vec<_>>()
+        .join("|");
+    let re = Regex::new(&format!(
+        r#"\b((?i:from|join))(\s+)(?:"({names})"|({names})\b)"#
+    ))
+    .expect("stream names are escaped");
+
+    re.replace_all(query, |caps: &regex::Captures| {
+        let from = caps
+            .get(3)
+            .or_else(|| caps.get(4))
+            .expect("one name matches");
+        format!(
+            "{}{}\"{}\"",
+            &caps[1],
+            &caps[2],
+            stream_mapping[from.as_str()]
+        )
+    })
+    .into_owned()
 }
 
 // GET /alerts/{alert_id}
@@ -378,10 +626,34 @@ pub async fn delete(req: HttpRequest, alert_id: Path<Ulid>) -> Result<impl Respo
 
     // delete the scheduled task
     alerts.delete_task(alert_id).await?;
+    target::forget_alert(alert_id);
 
     Ok(format!("Deleted alert with ID- {alert_id}"))
 }
 
+// GET /alerts/{alert_id}/deliveries
+/// Outcome of the latest notifications of the alert to its targets, latest first
+pub async fn deliveries(
+    req: HttpRequest,
+    alert_id: Path<Ulid>,
+) -> Result<impl Responder, AlertError> {
+    let session_key = extract_session_key_from_req(&req)?;
+    let alert_id = alert_id.into_inner();
+
+    let guard = ALERTS.read().await;
+    let alerts = if let Some(alerts) = guard.as_ref() {
+        alerts
+    } else {
+        return Err(AlertError::CustomError("No AlertManager set".into()));
+    };
+
+    let alert = alerts.get_alert_by_id(alert_id).await?;
+    // validate that the user has access to the tables mentioned in the query
+    user_auth_for_query(&session_key, alert.get_query()).await?;
+
+    Ok(web::Json(DELIVERIES.list(alert_id)))
+}
+
 // PATCH /alerts/{alert_id}/update_notification_state
 /// first save on disk, then in memory
 /// then modify scheduled task
@@ -490,8 +762,8 @@ pub async fn enable_alert(
     // check if alert id exists in map
     let alert = alerts.get_alert_by_id(alert_id).await?;
 
-    // only run if alert is disabled
-    if alert.get_state().ne(&AlertState::Disabled) {
+    // only run if alert is disabled, or paused since its stream was deleted
+    if !alert.get_state().is_paused() {
         return Err(AlertError::InvalidStateChange(
             "Can't enable an alert which is not currently disabled".into(),
         ));
@@ -574,13 +846,17 @@ pub async fn modify_alert(
 
     new_alert.validate(&session_key).await?;
 
+    let existing_alerts = alerts.get_all_alerts().await;
+    validate_unique_title(&*new_alert, existing_alerts.values().map(|a| a.as_ref()))?;
+    validate_target_count(&*new_alert, PARSEABLE.options.max_targets_per_alert)?;
+
     // Perform I/O operations
     PARSEABLE
         .metastore
         .put_alert(&new_alert.to_alert_config())
         .await?;
 
-    let is_disabled = new_alert.get_state().eq(&AlertState::Disabled);
+    let is_disabled = new_alert.get_state().is_paused();
     // Now perform the atomic operations
     alerts.delete_task(alert_id).await?;
     alerts.delete(alert_id).await?;
@@ -591,8 +867,7 @@ pub async fn modify_alert(
         alerts.start_task(new_alert.clone_box()).await?;
     }
 
-    let config = new_alert.to_alert_config().to_response();
-    Ok(web::Json(config))
+    Ok(web::Json(alert_response(&*new_alert).await))
 }
 
 // PUT /alerts/{alert_id}/evaluate_alert
@@ -625,6 +900,125 @@ pub async fn evaluate_alert(
     Ok(Json(config))
 }
 
+// POST /alerts/{alert_id}/evaluate
+pub async fn evaluate_now(
+    req: HttpRequest,
+    alert_id: Path<Ulid>,
+) -> Result<impl Responder, AlertError> {
+    let session_key = extract_session_key_from_req(&req)?;
+    let alert_id = alert_id.into_inner();
+
+    // the evaluation updates the alert state through ALERTS, so don't hold the lock across it
+    let alerts = {
+        let guard = ALERTS.read().await;
+        if let Some(alerts) = guard.as_ref() {
+            alerts.clone()
+        } else {
+            return Err(AlertError::CustomError("No AlertManager set".into()));
+        }
+    };
+
+    let alert = alerts.get_alert_by_id(alert_id).await?;
+
+    user_auth_for_query(&session_key, alert.get_query()).await?;
+
+    if alert.get_state().is_paused() {
+        return Err(AlertError::InvalidStateChange(
+            "Cannot evaluate a disabled alert, enable it first".into(),
+        ));
+    }
+
+    let query_result = evaluate_alert_now(&*alert).await?;
+    let state = alerts.get_state(alert_id).await?;
+
+    Ok(web::Json(serde_json::json!({
+        "value": query_result.get_single_value(),
+        "groups": query_result.groups,
+        "state": state,
+    })))
+}
+
+// POST /alerts/evaluate-batch
+/// Evaluates the requested alerts, or all the alerts the user has access to, one after the other.
+/// Each alert is authorized separately and its state and notifications are updated
+/// as with a scheduled evaluation, failures are reported per alert.
+pub async fn evaluate_batch(
+    req: HttpRequest,
+    Json(batch): Json<EvaluateBatchRequest>,
+) -> Result<impl Responder, AlertError> {
+    let session_key = extract_session_key_from_req(&req)?;
+
+    // the evaluations update the alert states through ALERTS, so don't hold the lock across them
+    let alerts = {
+        let guard = ALERTS.read().await;
+        if let Some(alerts) = guard.as_ref() {
+            alerts.clone()
+        } else {
+            return Err(AlertError::CustomError("No AlertManager set".into()));
+        }
+    };
+
+    let alert_ids = match batch.alert_ids {
+        Some(mut alert_ids) => {
+            let mut seen = HashSet::new();
+            alert_ids.retain(|id| seen.insert(*id));
+            alert_ids
+        }
+        // paused alerts are only reported when requested explicitly
+        None => alerts
+            .list_alerts_for_user(session_key.clone(), vec![])
+            .await?
+            .into_iter()
+            .filter(|alert| !alert.state.is_paused())
+            .map(|alert| alert.id)
+            .collect(),
+    };
+    if alert_ids.len() > MAX_BATCH_EVALUATIONS {
+        return Err(AlertError::ValidationFailure(format!(
+            "Can't evaluate {} alerts at once, the limit is {MAX_BATCH_EVALUATIONS}",
+            alert_ids.len()
+        )));
+    }
+
+    let mut results = Vec::with_capacity(alert_ids.len());
+    for alert_id in alert_ids {
+        let alert = match alerts.get_alert_by_id(alert_id).await {
+            Ok(alert) => alert,
+            Err(err) => {
+                results.push(AlertEvaluationResult::failed(alert_id, err));
+                continue;
+            }
+        };
+        if let Err(err) = user_auth_for_query(&session_key, alert.get_query()).await {
+            results.push(AlertEvaluationResult::failed(alert_id, err));
+            continue;
+        }
+        if alert.get_state().is_paused() {
+            results.push(AlertEvaluationResult::failed(
+                alert_id,
+                "Cannot evaluate a disabled alert, enable it first",
+            ));
+            continue;
+        }
+
+        let result = match evaluate_alert_now(&*alert).await {
+            Ok(query_result) => match alerts.get_state(alert_id).await {
+                Ok(state) => AlertEvaluationResult {
+                    id: alert_id,
+                    value: Some(query_result.get_single_value()),
+                    state: Some(state),
+                    error: None,
+                },
+                Err(err) => AlertEvaluationResult::failed(alert_id, err),
+            },
+            Err(err) => AlertEvaluationResult::failed(alert_id, err),
+        };
+        results.push(result);
+    }
+
+    Ok(web::Json(results))
+}
+
 pub async fn list_tags() -> Result<impl Responder, AlertError> {
     let guard = ALERTS.read().await;
     let alerts = if let Some(alerts) = guard.as_ref() {
@@ -635,3 +1029,69 @@ pub async fn list_tags() -> Result<impl Responder, AlertError> {
     let tags = alerts.list_tags().await;
     Ok(web::Json(tags))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remaps_streams_read_by_the_query() {
+        let mapping = HashMap::from([("app".to_owned(), "app-prod".to_owned())]);
+
+        assert_eq!(
+            remap_streams("SELECT COUNT(*) FROM app WHERE app_id = 1", &mapping),
+            r#"SELECT COUNT(*) FROM "app-prod" WHERE app_id = 1"#
+        );
+        assert_eq!(
+            remap_streams(r#"select count(*) from "app" join apps on true"#, &mapping),
+            r#"select count(*) from "app-prod" join apps on true"#
+        );
+    }
+
+    #[test]
+    fn remaps_swapped_streams_in_a_single_pass() {
+        let mapping = HashMap::from([
+            ("a".to_owned(), "b".to_owned()),
+            ("b".to_owned(), "a".to_owned()),
+        ]);
+
+        assert_eq!(
+            remap_streams("SELECT * FROM a JOIN b ON a.id = b.id", &mapping),
+            r#"SELECT * FROM "b" JOIN "a" ON a.id = b.id"#
+        );
+    }
+
+    #[test]
+    fn paginated_total_counts_filtered_alerts() {
+        let alerts = (0..30)
+            .map(|i| {
+                serde_json::json!({
+                    "title": format!("alert-{i:02}"),
+                    "team": if i % 3 == 0 { "infra" } else { "app" },
+                })
+                .as_object()
+                .unwrap()
+                .clone()
+            })
+            .collect::<Vec<_>>();
+        let filters = HashMap::from([("team".to_owned(), "infra".to_owned())]);
+
+        let page = Paginated::new(filter_by_other_fields(alerts, &filters), 8, 5);
+        assert_eq!(page.total, 10);
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.items[0]["title"], "alert-24");
+    }
+
+    #[test]
+    fn paginated_flag_is_reserved() {
+        let query_map = HashMap::from([
+            ("paginated".to_owned(), "true".to_owned()),
+            ("limit".to_owned(), "10".to_owned()),
+        ]);
+        let params = parse_list_query_params(&query_map).unwrap();
+
+        assert!(params.paginated);
+        assert_eq!(params.limit, 10);
+        assert!(params.other_fields_filters.is_empty());
+    }
+}
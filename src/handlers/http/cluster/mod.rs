@@ -16,6 +16,7 @@
  *
  */
 
+pub mod reconcile;
 pub mod utils;
 use futures::{StreamExt, future, stream};
 use lazy_static::lazy_static;
@@ -25,13 +26,14 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::{RwLock, Semaphore};
 
-use actix_web::Responder;
 use actix_web::http::header::{self, HeaderMap};
 use actix_web::web::Path;
+use actix_web::{HttpRequest, Responder};
 use bytes::Bytes;
-use chrono::Utc;
+use chrono::{NaiveDate, Utc};
 use http::{StatusCode, header as http_header};
 use itertools::Itertools;
+use reconcile::{PENDING_SYNCS, StreamOperation};
 use serde::de::{DeserializeOwned, Error};
 use serde_json::error::Error as SerdeError;
 use serde_json::{Value as JsonValue, to_vec};
@@ -40,14 +42,19 @@ use url::Url;
 use utils::{IngestionStats, QueriedStats, StorageStats, check_liveness, to_url_string};
 
 use crate::INTRA_CLUSTER_CLIENT;
+use crate::alerts::{alert_structs::AlertsSummary, get_alerts_summary};
+use crate::catalog::ManifestRebuild;
 use crate::handlers::http::query::{Query, QueryError, TIME_ELAPSED_HEADER};
 use crate::metrics::prom_utils::Metrics;
-use crate::option::Mode;
+use crate::option::{IngestorSyncPolicy, Mode};
 use crate::parseable::PARSEABLE;
+use crate::rbac::Users;
+use crate::rbac::role::Action;
 use crate::rbac::role::model::DefaultPrivilege;
 use crate::rbac::user::User;
 use crate::stats::Stats;
-use crate::storage::{ObjectStorageError, ObjectStoreFormat};
+use crate::storage::{ObjectStorageError, ObjectStoreFormat, StreamType};
+use crate::utils::actix::extract_session_key_from_req;
 
 use super::base_path_without_preceding_slash;
 use super::ingest::PostError;
@@ -324,47 +331,250 @@ pub async fn sync_streams_with_ingestors(
         reqwest_headers.insert(key.clone(), value.clone());
     }
 
-    let body_clone = body.clone();
-    let stream_name = stream_name.to_string();
-    let reqwest_headers_clone = reqwest_headers.clone();
-
-    for_each_live_ingestor(
-        move |ingestor| {
-            let url = format!(
-                "{}{}/logstream/{}/sync",
-                ingestor.domain_name,
-                base_path_without_preceding_slash(),
-                stream_name
+    let operation = StreamOperation::Upsert {
+        headers: reqwest_headers,
+        body,
+    };
+    forward_stream_operation(stream_name, &operation).await
+}
+
+// forward a setting of a stream to all ingestors, as they apply the settings of a stream on ingestion,
+// when writing its parquet files and when querying their staging. `setting` is the path of the setting
+// under the stream, the body is the one the querier received
+pub async fn sync_stream_setting_with_ingestors(
+    stream_name: &str,
+    setting: &'static str,
+    body: Bytes,
+) -> Result<(), StreamError> {
+    let operation = StreamOperation::Setting {
+        path: setting,
+        body,
+    };
+    forward_stream_operation(stream_name, &operation).await
+}
+
+/// Forwards a stream operation to all ingestors. The operation is queued for the ingestors that
+/// aren't live and applied once they return, with the best effort policy also for those it fails on.
+async fn forward_stream_operation(
+    stream_name: &str,
+    operation: &StreamOperation,
+) -> Result<(), StreamError> {
+    let ingestors: Vec<NodeMetadata> = get_node_info(NodeType::Ingestor).await?;
+
+    if PARSEABLE.options.ingestor_sync_policy == IngestorSyncPolicy::BestEffort {
+        future::join_all(
+            ingestors
+                .iter()
+                .map(|ingestor| apply_or_queue(ingestor, stream_name, operation)),
+        )
+        .await;
+        return Ok(());
+    }
+
+    future::join_all(ingestors.iter().map(|ingestor| async move {
+        if !utils::check_liveness(&ingestor.domain_name).await {
+            queue_for_down_ingestor(ingestor, stream_name, operation);
+            return Ok(());
+        }
+        send_stream_operation(ingestor, stream_name, operation).await
+    }))
+    .await
+    .into_iter()
+    .collect()
+}
+
+fn queue_for_down_ingestor(
+    ingestor: &NodeMetadata,
+    stream_name: &str,
+    operation: &StreamOperation,
+) {
+    let err = format!("ingestor {} is not live", ingestor.domain_name);
+    warn!("Queueing stream {stream_name} sync: {err}");
+    PENDING_SYNCS.queue(ingestor, stream_name, operation.clone(), err);
+}
+
+/// Applies a stream operation on an ingestor, queueing it for the background reconciliation
+/// when the ingestor can't be reached
+async fn apply_or_queue(ingestor: &NodeMetadata, stream_name: &str, operation: &StreamOperation) {
+    let result = if utils::check_liveness(&ingestor.domain_name).await {
+        send_stream_operation(ingestor, stream_name, operation)
+            .await
+            .map_err(|err| err.to_string())
+    } else {
+        Err(format!("ingestor {} is not live", ingestor.domain_name))
+    };
+
+    if let Err(err) = result {
+        warn!(
+            "Queueing stream {stream_name} sync for ingestor {}: {err}",
+            ingestor.domain_name
+        );
+        PENDING_SYNCS.queue(ingestor, stream_name, operation.clone(), err);
+    }
+}
+
+/// Forwards a create/update or delete of a stream to an ingestor
+pub async fn send_stream_operation(
+    ingestor: &NodeMetadata,
+    stream_name: &str,
+    operation: &StreamOperation,
+) -> Result<(), StreamError> {
+    let url = format!(
+        "{}{}/logstream/{}",
+        ingestor.domain_name,
+        base_path_without_preceding_slash(),
+        stream_name
+    );
+
+    let (request, action) = match operation {
+        StreamOperation::Upsert { headers, body } => (
+            INTRA_CLUSTER_CLIENT
+                .put(format!("{url}/sync"))
+                .headers(headers.clone())
+                .body(body.clone()),
+            "upsert stream request",
+        ),
+        StreamOperation::Delete => (
+            INTRA_CLUSTER_CLIENT
+                .delete(format!("{url}/sync"))
+                .header(header::CONTENT_TYPE, "application/json"),
+            "delete stream request",
+        ),
+        StreamOperation::Setting { path, body } => (
+            INTRA_CLUSTER_CLIENT
+                .put(format!("{url}/{path}"))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body.clone()),
+            *path,
+        ),
+    };
+
+    let res = request
+        .header(header::AUTHORIZATION, &ingestor.token)
+        .send()
+        .await
+        .map_err(|err| {
+            error!(
+                "Fatal: failed to forward {action} to ingestor: {}\n Error: {:?}",
+                ingestor.domain_name, err
             );
-            let headers = reqwest_headers_clone.clone();
-            let body = body_clone.clone();
-            async move {
-                let res = INTRA_CLUSTER_CLIENT
-                    .put(url)
-                    .headers(headers)
-                    .header(header::AUTHORIZATION, &ingestor.token)
-                    .body(body)
-                    .send()
-                    .await
-                    .map_err(|err| {
-                        error!(
-                            "Fatal: failed to forward upsert stream request to ingestor: {}\n Error: {:?}",
-                            ingestor.domain_name, err
-                        );
-                        StreamError::Network(err)
-                    })?;
-
-                if !res.status().is_success() {
+            StreamError::Network(err)
+        })?;
+
+    if !res.status().is_success() {
+        error!(
+            "failed to forward {action} to ingestor: {}\nResponse Returned: {:?}",
+            ingestor.domain_name,
+            res.text().await
+        );
+    }
+    Ok(())
+}
+
+/// Rebuilds the manifests of the given date on all live ingestors, each one from the files it uploaded
+pub async fn rebuild_manifest_on_ingestors(
+    stream_name: &str,
+    date: NaiveDate,
+    dry_run: bool,
+) -> Result<Vec<ManifestRebuild>, StreamError> {
+    let stream_name = stream_name.to_string();
+    let rebuilds = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+    let collected = rebuilds.clone();
+    for_each_live_ingestor(move |ingestor| {
+        let url = format!(
+            "{}{}/logstream/{}/rebuild-manifest?date={date}&dry_run={dry_run}",
+            ingestor.domain_name,
+            base_path_without_preceding_slash(),
+            stream_name
+        );
+        let collected = collected.clone();
+        async move {
+            let res = INTRA_CLUSTER_CLIENT
+                .post(url)
+                .header(header::AUTHORIZATION, &ingestor.token)
+                .send()
+                .await
+                .map_err(|err| {
                     error!(
-                        "failed to forward upsert stream request to ingestor: {}\nResponse Returned: {:?}",
-                        ingestor.domain_name,
-                        res.text().await
+                        "Fatal: failed to forward manifest rebuild to ingestor: {}\n Error: {:?}",
+                        ingestor.domain_name, err
                     );
-                }
-                Ok(())
+                    StreamError::Network(err)
+                })?;
+
+            if !res.status().is_success() {
+                let err = res.text().await.unwrap_or_default();
+                error!(
+                    "failed to rebuild manifest on ingestor: {}\nResponse Returned: {err}",
+                    ingestor.domain_name,
+                );
+                return Err(StreamError::Anyhow(anyhow::anyhow!(
+                    "failed to rebuild manifest on ingestor {}: {err}",
+                    ingestor.domain_name
+                )));
+            }
+
+            let rebuilt: Vec<ManifestRebuild> = res.json().await.map_err(StreamError::Network)?;
+            collected.lock().expect("lock poisoned").extend(rebuilt);
+            Ok(())
+        }
+    })
+    .await?;
+
+    Ok(std::mem::take(
+        &mut *rebuilds.lock().expect("lock poisoned"),
+    ))
+}
+
+/// Sends the partial query of a scatter-gather query to all live ingestors
+/// and collects the partial aggregates over their staging
+pub async fn send_staging_query_to_ingestors(
+    query: &Query,
+) -> Result<Vec<serde_json::Map<String, JsonValue>>, QueryError> {
+    let body = Bytes::from(to_vec(query)?);
+    let partials = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+    let collected = partials.clone();
+    for_each_live_ingestor(move |ingestor| {
+        let url = format!(
+            "{}{}/query/staging",
+            ingestor.domain_name,
+            base_path_without_preceding_slash(),
+        );
+        let body = body.clone();
+        let collected = collected.clone();
+        async move {
+            let res = INTRA_CLUSTER_CLIENT
+                .post(url)
+                .header(header::AUTHORIZATION, &ingestor.token)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body)
+                .send()
+                .await?;
+
+            if !res.status().is_success() {
+                let err = res.text().await?;
+                error!(
+                    "failed to query staging of ingestor: {}\nResponse Returned: {err}",
+                    ingestor.domain_name,
+                );
+                return Err(QueryError::CustomError(format!(
+                    "failed to query staging of ingestor {}: {err}",
+                    ingestor.domain_name
+                )));
             }
+
+            let rows: Vec<serde_json::Map<String, JsonValue>> = res.json().await?;
+            collected.lock().expect("lock poisoned").extend(rows);
+            Ok(())
         }
-    ).await
+    })
+    .await?;
+
+    Ok(std::mem::take(
+        &mut *partials.lock().expect("lock poisoned"),
+    ))
 }
 
 // forward the demo data request to one of the live ingestor
@@ -624,6 +834,43 @@ pub async fn sync_password_reset_with_ingestors(username: &str) -> Result<(), RB
     .await
 }
 
+// forward the session rotation to all ingestors to revoke the sessions they hold
+pub async fn sync_session_rotation_with_ingestors() -> Result<(), RBACError> {
+    for_each_live_ingestor(move |ingestor| {
+        let url = format!(
+            "{}{}/user/sessions/rotate/sync",
+            ingestor.domain_name,
+            base_path_without_preceding_slash(),
+        );
+
+        async move {
+            let res = INTRA_CLUSTER_CLIENT
+                .post(url)
+                .header(header::AUTHORIZATION, &ingestor.token)
+                .send()
+                .await
+                .map_err(|err| {
+                    error!(
+                        "Fatal: failed to forward request to ingestor: {}\n Error: {:?}",
+                        ingestor.domain_name, err
+                    );
+                    RBACError::Network(err)
+                })?;
+
+            if !res.status().is_success() {
+                error!(
+                    "failed to forward request to ingestor: {}\nResponse Returned: {:?}",
+                    ingestor.domain_name,
+                    res.text().await
+                );
+            }
+
+            Ok(())
+        }
+    })
+    .await
+}
+
 // forward the put role request to all ingestors to keep them in sync
 pub async fn sync_role_update_with_ingestors(
     name: String,
@@ -752,40 +999,26 @@ pub async fn fetch_stats_from_ingestors(
     Ok(vec![qs])
 }
 
-/// send a delete stream request to all ingestors
+/// send a delete stream request to an ingestor
 pub async fn send_stream_delete_request(
-    url: &str,
+    stream_name: &str,
     ingestor: IngestorMetadata,
 ) -> Result<(), StreamError> {
-    if !utils::check_liveness(&ingestor.domain_name).await {
+    if PARSEABLE.options.ingestor_sync_policy == IngestorSyncPolicy::BestEffort {
+        apply_or_queue(&ingestor, stream_name, &StreamOperation::Delete).await;
         return Ok(());
     }
-    let resp = INTRA_CLUSTER_CLIENT
-        .delete(url)
-        .header(header::CONTENT_TYPE, "application/json")
-        .header(header::AUTHORIZATION, ingestor.token)
-        .send()
-        .await
-        .map_err(|err| {
-            // log the error and return a custom error
-            error!(
-                "Fatal: failed to delete stream: {}\n Error: {:?}",
-                ingestor.domain_name, err
-            );
-            StreamError::Network(err)
-        })?;
 
-    // if the response is not successful, log the error and return a custom error
-    // this could be a bit too much, but we need to be sure it covers all cases
-    if !resp.status().is_success() {
-        error!(
-            "failed to delete stream: {}\nResponse Returned: {:?}",
-            ingestor.domain_name,
-            resp.text().await
-        );
+    if !utils::check_liveness(&ingestor.domain_name).await {
+        queue_for_down_ingestor(&ingestor, stream_name, &StreamOperation::Delete);
+        return Ok(());
     }
+    send_stream_operation(&ingestor, stream_name, &StreamOperation::Delete).await
+}
 
-    Ok(())
+/// Stream operations waiting for unreachable ingestors to return
+pub async fn get_pending_syncs() -> Result<impl Responder, StreamError> {
+    Ok(actix_web::web::Json(PENDING_SYNCS.list()))
 }
 
 /// send a retention cleanup request to all ingestors
@@ -829,6 +1062,10 @@ pub async fn send_retention_cleanup_request(
 
 /// Fetches cluster information for all nodes (ingestor, indexer, querier and prism)
 pub async fn get_cluster_info() -> Result<impl Responder, StreamError> {
+    Ok(actix_web::HttpResponse::Ok().json(fetch_cluster_info().await?))
+}
+
+async fn fetch_cluster_info() -> Result<Vec<utils::ClusterInfo>, StreamError> {
     // Get querier, ingestor and indexer metadata concurrently
     let (prism_result, querier_result, ingestor_result, indexer_result) = future::join4(
         get_node_info(NodeType::Prism),
@@ -885,7 +1122,109 @@ pub async fn get_cluster_info() -> Result<impl Responder, StreamError> {
     infos.extend(querier_infos?);
     infos.extend(ingestor_infos?);
     infos.extend(indexer_infos?);
-    Ok(actix_web::HttpResponse::Ok().json(infos))
+    Ok(infos)
+}
+
+/// Streams whose stats are fetched at once for the cluster overview
+const OVERVIEW_STATS_CONCURRENCY: usize = 16;
+
+/// Totals of the streams the user can list, alerts and node health of the cluster
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterOverview {
+    pub streams: usize,
+    /// summed over the querier and the ingestors
+    pub events: u64,
+    pub ingestion_size: u64,
+    pub storage_size: u64,
+    pub alerts: AlertsSummary,
+    pub reachable_nodes: usize,
+    pub unreachable_nodes: usize,
+    pub nodes: Vec<utils::ClusterInfo>,
+}
+
+// GET /cluster/overview
+pub async fn get_cluster_overview(req: HttpRequest) -> Result<impl Responder, StreamError> {
+    let key = extract_session_key_from_req(&req)
+        .map_err(|err| StreamError::Anyhow(anyhow::Error::msg(err.to_string())))?;
+
+    let streams = PARSEABLE
+        .metastore
+        .list_streams()
+        .await?
+        .into_iter()
+        .filter(|stream| {
+            Users.authorize(key.clone(), Action::ListStream, Some(stream), None)
+                == crate::rbac::Response::Authorized
+        })
+        .collect_vec();
+
+    let (stats, alerts, nodes) = future::join3(
+        stream::iter(&streams)
+            .map(|stream| stream_stats(stream))
+            .buffer_unordered(OVERVIEW_STATS_CONCURRENCY)
+            .collect::<Vec<_>>(),
+        get_alerts_summary(&key),
+        fetch_cluster_info(),
+    )
+    .await;
+
+    let alerts = alerts.map_err(|err| StreamError::Anyhow(anyhow::Error::msg(err.to_string())))?;
+    let nodes = nodes?;
+    let mut overview = ClusterOverview {
+        streams: streams.len(),
+        events: 0,
+        ingestion_size: 0,
+        storage_size: 0,
+        alerts,
+        reachable_nodes: nodes.iter().filter(|node| node.is_reachable()).count(),
+        unreachable_nodes: nodes.iter().filter(|node| !node.is_reachable()).count(),
+        nodes,
+    };
+    for stats in stats {
+        for stats in stats? {
+            overview.events += stats.ingestion.count;
+            overview.ingestion_size += stats.ingestion.size;
+            overview.storage_size += stats.storage.size;
+        }
+    }
+
+    Ok(actix_web::web::Json(overview))
+}
+
+/// Stats of a stream on this node and on each ingestor
+async fn stream_stats(stream_name: &str) -> Result<Vec<QueriedStats>, StreamError> {
+    if !PARSEABLE.check_or_load_stream(stream_name).await {
+        return Ok(vec![]);
+    }
+
+    let mut stats = match PARSEABLE.get_stream(stream_name)?.get_stream_type() {
+        StreamType::UserDefined => fetch_stats_from_ingestors(stream_name).await?,
+        StreamType::Internal => vec![],
+    };
+    if let Some(local) = crate::stats::get_current_stats(stream_name, "json") {
+        stats.push(QueriedStats::new(
+            stream_name,
+            Utc::now(),
+            IngestionStats::new(
+                local.current_stats.events,
+                local.current_stats.ingestion,
+                local.lifetime_stats.events,
+                local.lifetime_stats.ingestion,
+                local.deleted_stats.events,
+                local.deleted_stats.ingestion,
+                "json",
+            ),
+            StorageStats::new(
+                local.current_stats.storage,
+                local.lifetime_stats.storage,
+                local.deleted_stats.storage,
+                "parquet",
+            ),
+        ));
+    }
+
+    Ok(stats)
 }
 
 /// Fetches info for a single node
@@ -1554,6 +1893,15 @@ struct QuerierStatus {
     metadata: QuerierMetadata,
     available: bool,
     last_used: Option<Instant>,
+    // set when the querier rejected a query with 429, it is skipped by round-robin until then
+    at_capacity_until: Option<Instant>,
+}
+
+impl QuerierStatus {
+    fn is_at_capacity(&self) -> bool {
+        self.at_capacity_until
+            .is_some_and(|until| until > Instant::now())
+    }
 }
 
 pub async fn get_available_querier() -> Result<QuerierMetadata, QueryError> {
@@ -1608,6 +1956,7 @@ pub async fn get_available_querier() -> Result<QuerierMetadata, QueryError> {
                         metadata,
                         available: true,
                         last_used: None,
+                        at_capacity_until: None,
                     },
                 );
             }
@@ -1649,7 +1998,7 @@ async fn select_next_querier(map: &mut HashMap<String, QuerierStatus>) -> Option
     let available_queriers: Vec<String> = map
         .iter()
         .filter_map(|(domain, status)| {
-            if status.available {
+            if status.available && !status.is_at_capacity() {
                 Some(domain.clone())
             } else {
                 None
@@ -1742,6 +2091,14 @@ pub async fn mark_querier_available(domain_name: &str) {
     }
 }
 
+// Mark a querier as at capacity, so that new queries are routed to other queriers for a while
+async fn mark_querier_at_capacity(domain_name: &str, retry_after: Duration) {
+    let mut map = QUERIER_MAP.write().await;
+    if let Some(status) = map.get_mut(domain_name) {
+        status.at_capacity_until = Some(Instant::now() + retry_after);
+    }
+}
+
 pub async fn send_query_request(query_request: &Query) -> Result<(JsonValue, String), QueryError> {
     let querier = get_available_querier().await?;
     let domain_name = querier.domain_name.clone();
@@ -1750,10 +2107,15 @@ pub async fn send_query_request(query_request: &Query) -> Result<(JsonValue, Str
     let fields = query_request.fields;
     let streaming = query_request.streaming;
     let send_null = query_request.send_null;
-    let uri = format!(
-        "{}api/v1/query?fields={fields}&streaming={streaming}&send_null={send_null}",
+    let no_cache = query_request.no_cache;
+    let null_mode = query_request.null_mode;
+    let mut uri = format!(
+        "{}api/v1/query?fields={fields}&streaming={streaming}&send_null={send_null}&no_cache={no_cache}&null_mode={null_mode}",
         &querier.domain_name,
     );
+    if let Some(sample) = query_request.sample {
+        uri.push_str(&format!("&sample={sample}"));
+    }
 
     let body = match serde_json::to_string(&query_request) {
         Ok(body) => body,
@@ -1791,6 +2153,16 @@ pub async fn send_query_request(query_request: &Query) -> Result<(JsonValue, Str
         None => String::default(),
     };
 
+    if res.status() == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = headers
+            .get(header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1);
+        mark_querier_at_capacity(&domain_name, Duration::from_secs(retry_after)).await;
+        return Err(QueryError::TooManyQueries(retry_after));
+    }
+
     if res.status().is_success() {
         match res.text().await {
             Ok(text) => {
@@ -0,0 +1,245 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Stream operations that could not be applied on unreachable ingestors,
+//! retried in the background once the ingestors are back.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use http::header::HeaderMap;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::handlers::http::modal::{NodeMetadata, NodeType};
+use crate::metrics::PENDING_INGESTOR_SYNCS;
+
+use super::{get_node_info, send_stream_operation, utils::check_liveness};
+
+pub static PENDING_SYNCS: Lazy<PendingSyncs> = Lazy::new(PendingSyncs::default);
+
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Change of a stream forwarded to the ingestors
+#[derive(Debug, Clone)]
+pub enum StreamOperation {
+    Upsert {
+        headers: HeaderMap,
+        body: Bytes,
+    },
+    Delete,
+    /// Update of a setting of the stream, `path` is the path of the setting under the stream
+    Setting {
+        path: &'static str,
+        body: Bytes,
+    },
+}
+
+impl StreamOperation {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Upsert { .. } => "upsert",
+            Self::Delete => "delete",
+            Self::Setting { path, .. } => path,
+        }
+    }
+
+    /// What the operation changes, operations on the same target supersede each other
+    fn target(&self) -> &'static str {
+        match self {
+            Self::Upsert { .. } | Self::Delete => "stream",
+            Self::Setting { path, .. } => path,
+        }
+    }
+}
+
+/// Ingestor id, stream and target of a pending operation
+type PendingKey = (String, String, &'static str);
+
+#[derive(Debug, Clone)]
+struct PendingSync {
+    domain_name: String,
+    operation: StreamOperation,
+    queued_at: DateTime<Utc>,
+    attempts: u32,
+    last_error: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingSyncInfo {
+    pub node_id: String,
+    pub domain_name: String,
+    pub stream: String,
+    pub operation: &'static str,
+    pub queued_at: DateTime<Utc>,
+    pub attempts: u32,
+    pub last_error: String,
+}
+
+/// Pending operations by ingestor id, stream and target, only the latest operation on a target
+/// is kept as it supersedes the earlier ones. The deletion of a stream supersedes all of them.
+#[derive(Default)]
+pub struct PendingSyncs {
+    pending: Mutex<HashMap<PendingKey, PendingSync>>,
+}
+
+impl PendingSyncs {
+    pub fn queue(
+        &self,
+        ingestor: &NodeMetadata,
+        stream_name: &str,
+        operation: StreamOperation,
+        error: String,
+    ) {
+        let mut pending = self.pending.lock().expect("lock poisoned");
+        if matches!(operation, StreamOperation::Delete) {
+            pending.retain(|(node_id, stream, _), _| {
+                node_id != &ingestor.node_id || stream != stream_name
+            });
+        }
+        pending.insert(
+            (
+                ingestor.node_id.clone(),
+                stream_name.to_owned(),
+                operation.target(),
+            ),
+            PendingSync {
+                domain_name: ingestor.domain_name.clone(),
+                operation,
+                queued_at: Utc::now(),
+                attempts: 0,
+                last_error: error,
+            },
+        );
+        PENDING_INGESTOR_SYNCS.set(pending.len() as i64);
+    }
+
+    pub fn list(&self) -> Vec<PendingSyncInfo> {
+        self.pending
+            .lock()
+            .expect("lock poisoned")
+            .iter()
+            .map(|((node_id, stream, _), sync)| PendingSyncInfo {
+                node_id: node_id.clone(),
+                domain_name: sync.domain_name.clone(),
+                stream: stream.clone(),
+                operation: sync.operation.name(),
+                queued_at: sync.queued_at,
+                attempts: sync.attempts,
+                last_error: sync.last_error.clone(),
+            })
+            .collect()
+    }
+
+    fn snapshot(&self) -> Vec<(PendingKey, PendingSync)> {
+        self.pending
+            .lock()
+            .expect("lock poisoned")
+            .iter()
+            .map(|(key, sync)| (key.clone(), sync.clone()))
+            .collect()
+    }
+
+    /// Records the outcome of a retry, unless the operation was superseded in the meantime
+    fn complete(&self, key: &PendingKey, queued_at: DateTime<Utc>, error: Option<String>) {
+        let mut pending = self.pending.lock().expect("lock poisoned");
+        let Some(sync) = pending
+            .get_mut(key)
+            .filter(|sync| sync.queued_at == queued_at)
+        else {
+            return;
+        };
+        match error {
+            Some(error) => {
+                sync.attempts += 1;
+                sync.last_error = error;
+            }
+            None => {
+                pending.remove(key);
+            }
+        }
+        PENDING_INGESTOR_SYNCS.set(pending.len() as i64);
+    }
+
+    /// Retries the pending operations of the ingestors that are live again,
+    /// dropping those of ingestors removed from the cluster
+    async fn reconcile(&self) {
+        let mut pending = self.snapshot();
+        if pending.is_empty() {
+            return;
+        }
+        // the settings of a stream apply to the stream as created or updated on the ingestor
+        pending.sort_by_key(|((_, _, target), _)| *target != "stream");
+
+        let ingestors: Vec<NodeMetadata> = match get_node_info(NodeType::Ingestor).await {
+            Ok(ingestors) => ingestors,
+            Err(err) => {
+                warn!("Failed to get ingestor info for stream sync reconciliation: {err}");
+                return;
+            }
+        };
+
+        for (key, sync) in pending {
+            let (node_id, stream_name, _) = &key;
+            let Some(ingestor) = ingestors
+                .iter()
+                .find(|ingestor| &ingestor.node_id == node_id)
+            else {
+                info!(
+                    "Dropping pending {} of stream {stream_name}, ingestor {} left the cluster",
+                    sync.operation.name(),
+                    sync.domain_name
+                );
+                self.complete(&key, sync.queued_at, None);
+                continue;
+            };
+            if !check_liveness(&ingestor.domain_name).await {
+                continue;
+            }
+
+            let error = send_stream_operation(ingestor, stream_name, &sync.operation)
+                .await
+                .err()
+                .map(|err| err.to_string());
+            if error.is_none() {
+                info!(
+                    "Applied pending {} of stream {stream_name} on ingestor {}",
+                    sync.operation.name(),
+                    ingestor.domain_name
+                );
+            }
+            self.complete(&key, sync.queued_at, error);
+        }
+    }
+}
+
+/// Periodically retries the stream operations queued for unreachable ingestors
+pub fn init_sync_reconciler() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(RECONCILE_INTERVAL);
+        loop {
+            interval.tick().await;
+            PENDING_SYNCS.reconcile().await;
+        }
+    });
+}
@@ -82,6 +82,10 @@ impl ClusterInfo {
             node_type: node_type.clone(),
         }
     }
+
+    pub fn is_reachable(&self) -> bool {
+        self.reachable
+    }
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
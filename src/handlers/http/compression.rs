@@ -0,0 +1,139 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Negotiation of the compression of responses. The compression middleware only picks among
+//! the encodings allowed with `P_COMPRESSION_ENCODINGS`, and responses whose content type is
+//! already compressed, such as parquet or arrow exports, are sent as is.
+
+use actix_web::{
+    Error,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, HeaderValue},
+    middleware::Next,
+};
+
+use crate::parseable::PARSEABLE;
+
+/// Encodings the compression middleware can compress responses with
+pub const RESPONSE_ENCODINGS: [&str; 3] = ["gzip", "br", "zstd"];
+
+/// Drops the encodings which aren't allowed from the `Accept-Encoding` of the request,
+/// before the compression middleware negotiates the encoding of the response
+pub async fn allowed_encodings_middleware(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let accepted = req
+        .headers()
+        .get(ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(|accepted| allowed_encodings(accepted, &PARSEABLE.options.compression_encodings));
+    if let Some(accepted) = accepted
+        && let Ok(value) = HeaderValue::from_str(&accepted)
+    {
+        req.headers_mut().insert(ACCEPT_ENCODING, value);
+    }
+
+    next.call(req).await
+}
+
+/// Sends the responses of the excluded content types uncompressed, the compression
+/// middleware leaves responses which already have a `Content-Encoding` as they are
+pub async fn excluded_content_types_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let mut res = next.call(req).await?;
+
+    let excluded = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| {
+            is_excluded(
+                content_type,
+                &PARSEABLE.options.compression_excluded_content_types,
+            )
+        });
+    if excluded && !res.headers().contains_key(CONTENT_ENCODING) {
+        res.headers_mut()
+            .insert(CONTENT_ENCODING, HeaderValue::from_static("identity"));
+    }
+
+    Ok(res)
+}
+
+/// The `Accept-Encoding` of a client restricted to the `allowed` encodings, `identity` when
+/// none of them is accepted. A wildcard is replaced with the allowed encodings.
+fn allowed_encodings(accepted: &str, allowed: &[String]) -> String {
+    let mut encodings = vec![];
+    for encoding in accepted.split(',').map(str::trim) {
+        let name = encoding.split(';').next().unwrap_or_default().trim();
+        if name == "*" {
+            encodings.extend(allowed.iter().cloned());
+        } else if name.eq_ignore_ascii_case("identity")
+            || allowed
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(name))
+        {
+            encodings.push(encoding.to_owned());
+        }
+    }
+
+    if encodings.is_empty() {
+        return "identity".to_owned();
+    }
+    encodings.join(", ")
+}
+
+/// Whether the content type, parameters aside, is one of the `excluded` ones
+fn is_excluded(content_type: &str, excluded: &[String]) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    excluded
+        .iter()
+        .any(|excluded| excluded.eq_ignore_ascii_case(essence))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_allowed_encodings_are_negotiated() {
+        let allowed = ["gzip".to_owned(), "zstd".to_owned()];
+
+        assert_eq!(
+            allowed_encodings("br;q=1.0, gzip;q=0.8, deflate", &allowed),
+            "gzip;q=0.8"
+        );
+        assert_eq!(allowed_encodings("br", &allowed), "identity");
+        assert_eq!(allowed_encodings("*", &allowed), "gzip, zstd");
+    }
+
+    #[test]
+    fn excluded_content_types_ignore_parameters() {
+        let excluded = ["application/vnd.apache.arrow.stream".to_owned()];
+
+        assert!(is_excluded(
+            "application/vnd.apache.arrow.stream; charset=binary",
+            &excluded
+        ));
+        assert!(!is_excluded("application/json", &excluded));
+    }
+}
@@ -16,24 +16,142 @@
  *
  */
 
+use std::collections::HashMap;
+
+use actix_web::body::{BoxBody, to_bytes};
+use actix_web::http::header::{ContentType, HeaderName, HeaderValue};
 use actix_web::web::{Json, Path};
 use actix_web::{HttpRequest, HttpResponse, Responder, web};
 use anyhow::Error;
 use itertools::Itertools;
+use serde::Deserialize;
+use serde_json::json;
 
+use crate::handlers::http::pagination::Paginated;
+use crate::handlers::http::query::{self, Query};
 use crate::rbac::Users;
+use crate::response::NullMode;
 use crate::utils::actix::extract_session_key_from_req;
-use crate::utils::{get_hash, get_user_from_request, user_auth_for_datasets};
+use crate::utils::{get_hash, get_user_from_request, user_auth_for_datasets, user_auth_for_query};
+
+use crate::correlation::{
+    CORRELATION_RESULT_CACHE, CORRELATIONS, CorrelationConfig, CorrelationError,
+};
+
+/// Age in seconds of the cached correlation result served by the run endpoint
+pub const CACHE_AGE_HEADER: &str = "p-cache-age";
+const MAX_LIMIT: usize = 1000;
+const DEFAULT_LIMIT: usize = 100;
+
+/// Query parameters for listing correlations
+struct ListQueryParams {
+    offset: usize,
+    limit: usize,
+    search: Option<String>,
+    /// respond with the shared paginated shape instead of `{correlations, total}`
+    paginated: bool,
+}
+
+/// Parse and validate query parameters for listing correlations,
+/// returns None if no pagination/search parameter was provided
+fn parse_list_query_params(
+    query_map: &HashMap<String, String>,
+) -> Result<Option<ListQueryParams>, CorrelationError> {
+    if !["offset", "limit", "search", "paginated"]
+        .iter()
+        .any(|param| query_map.contains_key(*param))
+    {
+        return Ok(None);
+    }
+
+    let offset = match query_map.get("offset") {
+        Some(offset) => offset.parse().map_err(|_| {
+            CorrelationError::InvalidQueryParameter("offset is not a valid number".to_string())
+        })?,
+        None => 0,
+    };
 
-use crate::correlation::{CORRELATIONS, CorrelationConfig, CorrelationError};
+    let limit = match query_map.get("limit") {
+        Some(limit) => limit.parse().map_err(|_| {
+            CorrelationError::InvalidQueryParameter("limit is not a valid number".to_string())
+        })?,
+        None => DEFAULT_LIMIT,
+    };
+    if limit == 0 || limit > MAX_LIMIT {
+        return Err(CorrelationError::InvalidQueryParameter(format!(
+            "limit should be between 1 and {MAX_LIMIT}"
+        )));
+    }
 
-pub async fn list(req: HttpRequest) -> Result<impl Responder, CorrelationError> {
+    let search = query_map
+        .get("search")
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty());
+
+    Ok(Some(ListQueryParams {
+        offset,
+        limit,
+        search,
+        paginated: query_map.get("paginated").is_some_and(|p| p == "true"),
+    }))
+}
+
+/// Check whether the search term is contained in the title or the query text of the correlation
+fn matches_search(correlation: &CorrelationConfig, search: &str) -> bool {
+    correlation.title.to_lowercase().contains(search)
+        || correlation
+            .filter
+            .as_ref()
+            .and_then(|f| f.filter_query.as_ref())
+            .is_some_and(|q| q.to_lowercase().contains(search))
+}
+
+// GET /correlation
+/// Returns all correlations the user is authorized to query,
+/// `?limit=&offset=&search=` returns a page of the matching correlations along with the total count,
+/// `?paginated=true` returns the page in the shape shared by all list endpoints
+pub async fn list(req: HttpRequest) -> Result<HttpResponse, CorrelationError> {
     let session_key = extract_session_key_from_req(&req)
         .map_err(|err| CorrelationError::AnyhowError(Error::msg(err.to_string())))?;
+    let query_map =
+        web::Query::<HashMap<String, String>>::from_query(req.query_string()).map_err(|_| {
+            CorrelationError::InvalidQueryParameter("malformed query parameters".to_string())
+        })?;
+
+    let params = parse_list_query_params(&query_map)?;
+
+    let mut correlations = CORRELATIONS.list_correlations(&session_key).await?;
+
+    let Some(params) = params else {
+        return Ok(HttpResponse::Ok().json(correlations));
+    };
 
-    let correlations = CORRELATIONS.list_correlations(&session_key).await?;
+    if let Some(search) = &params.search {
+        correlations.retain(|c| matches_search(c, search));
+    }
 
-    Ok(web::Json(correlations))
+    // sort for a stable order across pages
+    correlations.sort_by(|a, b| a.title.cmp(&b.title).then_with(|| a.id.cmp(&b.id)));
+
+    if params.paginated {
+        return Ok(HttpResponse::Ok().json(Paginated::new(
+            correlations,
+            params.offset,
+            params.limit,
+        )));
+    }
+
+    let total = correlations.len();
+    let correlations = correlations
+        .into_iter()
+        .skip(params.offset)
+        .take(params.limit)
+        .collect_vec();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "correlations": correlations,
+        "total": total,
+    })))
 }
 
 pub async fn get(
@@ -44,7 +162,14 @@ pub async fn get(
     let session_key = extract_session_key_from_req(&req)
         .map_err(|err| CorrelationError::AnyhowError(Error::msg(err.to_string())))?;
 
+    let user_id = get_user_from_request(&req)
+        .map(|s| get_hash(&s.to_string()))
+        .map_err(|err| CorrelationError::AnyhowError(Error::msg(err.to_string())))?;
+
     let correlation = CORRELATIONS.get_correlation(&correlation_id).await?;
+    if !correlation.is_visible_to(&user_id) {
+        return Err(CorrelationError::Unauthorized);
+    }
 
     let permissions = Users.get_permissions(&session_key);
 
@@ -65,10 +190,10 @@ pub async fn post(
 ) -> Result<impl Responder, CorrelationError> {
     let session_key = extract_session_key_from_req(&req)
         .map_err(|err| CorrelationError::AnyhowError(anyhow::Error::msg(err.to_string())))?;
-    let user_id = get_user_from_request(&req)
-        .map(|s| get_hash(&s.to_string()))
+    let username = get_user_from_request(&req)
         .map_err(|err| CorrelationError::AnyhowError(Error::msg(err.to_string())))?;
-    correlation.user_id = user_id;
+    correlation.user_id = get_hash(&username);
+    correlation.owner = username;
 
     let correlation = CORRELATIONS.create(correlation, &session_key).await?;
 
@@ -106,3 +231,120 @@ pub async fn delete(
 
     Ok(HttpResponse::Ok().finish())
 }
+
+/// Time range to run a correlation for
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorrelationRunRequest {
+    pub start_time: String,
+    pub end_time: String,
+}
+
+// POST /correlation/{correlation_id}/run
+/// Runs the query of the correlation for the given time range,
+/// accepts the same `fields`, `sendNull` and `streaming` query params as the query API
+pub async fn run(
+    req: HttpRequest,
+    correlation_id: Path<String>,
+    Json(run_request): Json<CorrelationRunRequest>,
+) -> Result<HttpResponse, CorrelationError> {
+    let correlation_id = correlation_id.into_inner();
+    let session_key = extract_session_key_from_req(&req)
+        .map_err(|err| CorrelationError::AnyhowError(Error::msg(err.to_string())))?;
+    let user_id = get_user_from_request(&req)
+        .map(|s| get_hash(&s.to_string()))
+        .map_err(|err| CorrelationError::AnyhowError(Error::msg(err.to_string())))?;
+
+    let correlation = CORRELATIONS.get_correlation(&correlation_id).await?;
+    if !correlation.is_visible_to(&user_id) {
+        return Err(CorrelationError::Unauthorized);
+    }
+
+    let sql = correlation.generate_sql()?;
+    user_auth_for_query(&session_key, &sql).await?;
+
+    let raw_params = web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .map(|x| x.into_inner())
+        .unwrap_or_default();
+    let null_mode = raw_params
+        .get("null_mode")
+        .map(|null_mode| null_mode.parse::<NullMode>())
+        .transpose()
+        .map_err(|err| CorrelationError::AnyhowError(Error::msg(err)))?
+        .unwrap_or_default();
+    let params = raw_params
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value.parse::<bool>().ok()?)))
+        .collect::<HashMap<_, _>>();
+    let send_null = params.get("sendNull").cloned().unwrap_or(false);
+    let fields = params.get("fields").cloned().unwrap_or(false);
+    let streaming = params.get("streaming").cloned().unwrap_or(false);
+    let no_cache = params.get("no_cache").cloned().unwrap_or(false);
+
+    // results are masked per user, so cached results are only served to the same user
+    let cache_ttl = correlation.cache_ttl.filter(|_| !streaming && !no_cache);
+    let cache_key = format!(
+        "{user_id}/{}/{}/{fields}/{send_null}/{null_mode}",
+        run_request.start_time, run_request.end_time
+    );
+    if let Some(ttl) = cache_ttl
+        && let Some((body, age)) = CORRELATION_RESULT_CACHE.get(&correlation_id, &cache_key, ttl)
+    {
+        return Ok(HttpResponse::Ok()
+            .insert_header((CACHE_AGE_HEADER, age.to_string()))
+            .content_type(ContentType::json())
+            .body(body));
+    }
+
+    let query_request = Query {
+        query: sql,
+        start_time: run_request.start_time,
+        end_time: run_request.end_time,
+        send_null,
+        fields,
+        streaming,
+        filter_tags: None,
+        no_cache,
+        as_of: None,
+        null_mode,
+        sample: None,
+    };
+
+    let response = query::query(req, query_request).await?;
+    if cache_ttl.is_none() || !response.status().is_success() {
+        return Ok(response);
+    }
+
+    let (mut response, body) = response.into_parts();
+    let body = to_bytes(body)
+        .await
+        .map_err(|err| CorrelationError::AnyhowError(Error::msg(err.to_string())))?;
+    CORRELATION_RESULT_CACHE.insert(&correlation_id, cache_key, body.clone());
+    response.headers_mut().insert(
+        HeaderName::from_static(CACHE_AGE_HEADER),
+        HeaderValue::from_static("0"),
+    );
+
+    Ok(response.set_body(BoxBody::new(body)))
+}
+
+// DELETE /correlation/{correlation_id}/cache
+/// Drops the cached result of the correlation, so that the next run recomputes it
+pub async fn clear_cache(
+    req: HttpRequest,
+    correlation_id: Path<String>,
+) -> Result<HttpResponse, CorrelationError> {
+    let correlation_id = correlation_id.into_inner();
+    let user_id = get_user_from_request(&req)
+        .map(|s| get_hash(&s.to_string()))
+        .map_err(|err| CorrelationError::AnyhowError(Error::msg(err.to_string())))?;
+
+    let correlation = CORRELATIONS.get_correlation(&correlation_id).await?;
+    if !correlation.is_visible_to(&user_id) {
+        return Err(CorrelationError::Unauthorized);
+    }
+
+    CORRELATION_RESULT_CACHE.invalidate(&correlation_id);
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -31,7 +31,7 @@ use crate::event::format::{self, EventFormat, LogSource, LogSourceEntry};
 use crate::event::{self, FORMAT_KEY, USER_AGENT_KEY};
 use crate::handlers::http::modal::utils::ingest_utils::validate_stream_for_ingestion;
 use crate::handlers::{
-    CONTENT_TYPE_JSON, CONTENT_TYPE_PROTOBUF, EXTRACT_LOG_KEY, LOG_SOURCE_KEY,
+    CONTENT_TYPE_JSON, CONTENT_TYPE_PROTOBUF, EXTRACT_LOG_KEY, INGEST_MODE_KEY, LOG_SOURCE_KEY,
     STREAM_NAME_HEADER_KEY, TELEMETRY_TYPE_KEY, TelemetryType,
 };
 use crate::metadata::SchemaVersion;
@@ -46,7 +46,9 @@ use crate::utils::header_parsing::ParseHeaderError;
 use crate::utils::json::{flatten::JsonFlattenError, strict::StrictValue};
 
 use super::logstream::error::{CreateStreamError, StreamError};
-use super::modal::utils::ingest_utils::{flatten_and_push_logs, get_custom_fields_from_header};
+use super::modal::utils::ingest_utils::{
+    flatten_and_push_logs, flatten_and_push_logs_partially, get_custom_fields_from_header,
+};
 use super::users::dashboards::DashboardError;
 use super::users::filters::FiltersError;
 
@@ -125,9 +127,40 @@ pub async fn ingest(
         .add_update_log_source(&stream_name, log_source_entry)
         .await?;
 
-    flatten_and_push_logs(json, &stream_name, &log_source, &p_custom_fields, None).await?;
+    push_events(&req, json, &stream_name, &log_source, &p_custom_fields).await
+}
 
-    Ok(HttpResponse::Ok().finish())
+/// Ingests a batch of events, each event of an array is accepted or rejected independently
+/// unless the client asks for all-or-nothing ingestion with `X-P-Ingest-Mode: strict`.
+/// Partially ingested batches are answered with 207 and the rejected indices.
+async fn push_events(
+    req: &HttpRequest,
+    json: serde_json::Value,
+    stream_name: &str,
+    log_source: &LogSource,
+    p_custom_fields: &HashMap<String, String>,
+) -> Result<HttpResponse, PostError> {
+    let strict = req
+        .headers()
+        .get(INGEST_MODE_KEY)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|mode| mode.eq_ignore_ascii_case("strict"));
+    if strict {
+        flatten_and_push_logs(json, stream_name, log_source, p_custom_fields, None).await?;
+        return Ok(HttpResponse::Ok().finish());
+    }
+
+    let result =
+        flatten_and_push_logs_partially(json, stream_name, log_source, p_custom_fields).await?;
+    let response = if result.rejected.is_empty() {
+        HttpResponse::Ok().finish()
+    } else if result.accepted == 0 {
+        HttpResponse::BadRequest().json(result)
+    } else {
+        HttpResponse::MultiStatus().json(result)
+    };
+
+    Ok(response)
 }
 
 pub async fn ingest_internal_stream(stream_name: String, body: Bytes) -> Result<(), PostError> {
@@ -396,9 +429,7 @@ pub async fn post_event(
     //return error if the stream log source is otel traces or otel metrics
     validate_stream_for_ingestion(&stream_name)?;
 
-    flatten_and_push_logs(json, &stream_name, &log_source, &p_custom_fields, None).await?;
-
-    Ok(HttpResponse::Ok().finish())
+    push_events(&req, json, &stream_name, &log_source, &p_custom_fields).await
 }
 
 pub async fn push_logs_unchecked(
@@ -460,6 +491,10 @@ pub enum PostError {
     IncorrectLogSource(LogSource),
     #[error("Ingestion is not allowed in Query mode")]
     IngestionNotAllowed,
+    #[error("Log stream {0} is read-only, it doesn't accept new events")]
+    ReadOnlyStream(String),
+    #[error("Not authorized to ingest into stream {0}")]
+    NotAuthorized(String),
     #[error("Missing field for time partition in json: {0}")]
     MissingTimePartition(String),
     #[error("{0}")]
@@ -511,6 +546,8 @@ impl actix_web::ResponseError for PostError {
 
             StreamNotFound(_) => StatusCode::NOT_FOUND,
 
+            ReadOnlyStream(_) | NotAuthorized(_) => StatusCode::FORBIDDEN,
+
             MetastoreError(e) => e.status_code(),
         }
     }
@@ -18,34 +18,62 @@
 
 use self::error::StreamError;
 use super::cluster::utils::{IngestionStats, QueriedStats, StorageStats};
+use super::cluster::{rebuild_manifest_on_ingestors, sync_stream_setting_with_ingestors};
+use super::pagination::{PageParams, Paginated};
 use super::query::update_schema_when_distributed;
+use crate::alerts;
+use crate::catalog::{
+    self,
+    column::TypedStatistics,
+    partition_stats::{PartitionSizes, PartitionStats},
+};
+use crate::correlation::{CORRELATIONS, CorrelationDependent};
+use crate::event::DEFAULT_TIMESTAMP_KEY;
 use crate::event::format::override_data_type;
 use crate::hottier::{CURRENT_HOT_TIER_VERSION, HotTierManager, StreamHotTier};
 use crate::metadata::SchemaVersion;
 use crate::metrics::{EVENTS_INGESTED_DATE, EVENTS_INGESTED_SIZE_DATE, EVENTS_STORAGE_SIZE_DATE};
-use crate::parseable::{PARSEABLE, StreamNotFound};
+use crate::option::Mode;
+use crate::parseable::{PARSEABLE, Stream, StreamNotFound};
 use crate::rbac::Users;
 use crate::rbac::role::Action;
 use crate::stats::{Stats, event_labels_date, storage_size_labels_date};
+use crate::storage::late_events::LateEventWindow;
+use crate::storage::masking::MaskingConfig;
+use crate::storage::partition_expression::PartitionExpression;
+use crate::storage::reserved_fields::ReservedFieldPolicy;
 use crate::storage::retention::Retention;
+use crate::storage::sampling::SamplingRate;
+use crate::storage::timestamp_policy::TimestampPolicy;
+use crate::storage::transforms::TransformRule;
+use crate::storage::type_coercion::TypeCoercionPolicy;
+use crate::storage::type_widening::TypeWideningPolicy;
 use crate::storage::{ObjectStoreFormat, StreamInfo, StreamType};
 use crate::utils::actix::extract_session_key_from_req;
+use crate::utils::arrow::{schema_to_ipc, schema_to_sql_ddl};
 use crate::utils::json::flatten::{
     self, convert_to_array, generic_flattening, has_more_than_max_allowed_levels,
 };
+use crate::utils::time::{TimeRange, parse_timezone};
 use crate::{LOCK_EXPECT, stats, validator};
 
 use actix_web::http::StatusCode;
+use actix_web::http::header::{self, ContentType};
 use actix_web::web::{Json, Path};
-use actix_web::{HttpRequest, Responder, web};
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
 use arrow_json::reader::infer_json_schema_from_iterator;
+use arrow_schema::{DataType, Schema};
 use bytes::Bytes;
-use chrono::Utc;
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use chrono_tz::Tz;
 use itertools::Itertools;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use std::collections::HashMap;
 use std::fs;
 use std::sync::Arc;
-use tracing::warn;
+use tracing::{error, info, warn};
 
 pub async fn delete(stream_name: Path<String>) -> Result<impl Responder, StreamError> {
     let stream_name = stream_name.into_inner();
@@ -68,132 +96,1429 @@ pub async fn delete(stream_name: Path<String>) -> Result<impl Responder, StreamE
         )
     }
 
-    if let Some(hot_tier_manager) = HotTierManager::global()
-        && hot_tier_manager.check_stream_hot_tier_exists(&stream_name)
-    {
-        hot_tier_manager.delete_hot_tier(&stream_name).await?;
+    if let Some(hot_tier_manager) = HotTierManager::global()
+        && hot_tier_manager.check_stream_hot_tier_exists(&stream_name)
+    {
+        hot_tier_manager.delete_hot_tier(&stream_name).await?;
+    }
+
+    // Delete from memory
+    PARSEABLE.streams.delete(&stream_name);
+    stats::delete_stats(&stream_name, "json")
+        .unwrap_or_else(|e| warn!("failed to delete stats for stream {}: {:?}", stream_name, e));
+
+    // alerts of the deleted stream would otherwise keep failing on every evaluation
+    let affected_alerts = alerts::handle_deleted_stream(&stream_name)
+        .await
+        .unwrap_or_else(|err| {
+            warn!("failed to stop the alerts of deleted stream {stream_name}: {err}");
+            Vec::new()
+        });
+
+    Ok((
+        web::Json(json!({
+            "message": format!("log stream {stream_name} deleted"),
+            "affectedAlerts": affected_alerts,
+        })),
+        StatusCode::OK,
+    ))
+}
+
+/// Stream info along with the name of the stream, returned by `GET /logstream?detail=true`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamDetails {
+    pub name: String,
+    #[serde(flatten)]
+    pub info: StreamInfo,
+    pub hot_tier_enabled: bool,
+}
+
+pub async fn list(req: HttpRequest) -> Result<impl Responder, StreamError> {
+    let key = extract_session_key_from_req(&req)
+        .map_err(|err| StreamError::Anyhow(anyhow::Error::msg(err.to_string())))?;
+    let detail = web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .ok()
+        .and_then(|params| params.get("detail").map(|detail| detail == "true"))
+        .unwrap_or(false);
+    let page = PageParams::from_query_string(req.query_string())
+        .map_err(StreamError::InvalidQueryParameter)?;
+
+    // list all streams from storage
+    let mut names = PARSEABLE
+        .metastore
+        .list_streams()
+        .await?
+        .into_iter()
+        .filter(|logstream| {
+            Users.authorize(key.clone(), Action::ListStream, Some(logstream), None)
+                == crate::rbac::Response::Authorized
+        })
+        .collect_vec();
+
+    // only the streams of the requested page are detailed
+    let total = names.len();
+    if let Some(page) = page {
+        names.sort();
+        names = page.paginate(names).items;
+    }
+    let respond = |res: Vec<Value>| match page {
+        Some(page) => HttpResponse::Ok().json(Paginated {
+            items: res,
+            total,
+            limit: page.limit,
+            offset: page.offset,
+        }),
+        None => HttpResponse::Ok().json(res),
+    };
+
+    if !detail {
+        let res = names
+            .into_iter()
+            .map(|name| json!({"name": name}))
+            .collect_vec();
+        return Ok(respond(res));
+    }
+
+    let mut res = Vec::with_capacity(names.len());
+    for name in names {
+        // streams are loaded in memory only once, details are then served from memory
+        if !PARSEABLE.check_or_load_stream(&name).await {
+            continue;
+        }
+        let Ok(stream) = PARSEABLE.get_stream(&name) else {
+            continue;
+        };
+        let details = {
+            let meta = stream.metadata.read().expect(LOCK_EXPECT);
+            StreamDetails {
+                info: StreamInfo {
+                    stream_type: meta.stream_type,
+                    created_at: meta.created_at.clone(),
+                    first_event_at: meta.first_event_at.clone(),
+                    latest_event_at: None,
+                    time_partition: meta.time_partition.clone(),
+                    time_partition_limit: meta.time_partition_limit.map(|limit| limit.to_string()),
+                    time_partition_format: meta.time_partition_format.clone(),
+                    custom_partition: meta.custom_partition.clone(),
+                    static_schema_flag: meta.static_schema_flag,
+                    log_source: meta.log_source.clone(),
+                    telemetry_type: meta.telemetry_type,
+                    sampling_rate: meta.sampling_rate,
+                    indexed_columns: meta.indexed_columns.clone(),
+                    reserved_field_policy: meta.reserved_field_policy,
+                    type_coercion_policy: meta.type_coercion_policy,
+                    type_widening_policy: meta.type_widening_policy,
+                    timestamp_policy: meta.timestamp_policy.clone(),
+                    row_group_size: meta.row_group_size,
+                    schema_frozen: meta.schema_frozen,
+                    numeric_downcast: meta.numeric_downcast,
+                    late_event_window: meta.late_event_window.clone(),
+                    read_only: meta.read_only,
+                    timestamp_column: meta.timestamp_column.clone(),
+                    column_aliases: meta.column_aliases.clone(),
+                    partition_expressions: meta.partition_expressions.clone(),
+                    transforms: meta.transforms.clone(),
+                },
+                hot_tier_enabled: meta.hot_tier_enabled,
+                name,
+            }
+        };
+        res.push(serde_json::to_value(details)?);
+    }
+
+    Ok(respond(res))
+}
+
+pub async fn detect_schema(Json(json): Json<Value>) -> Result<impl Responder, StreamError> {
+    // flatten before infer
+    if !has_more_than_max_allowed_levels(&json, 1) {
+        //perform generic flattening, return error if failed to flatten
+        let mut flattened_json = match generic_flattening(&json) {
+            Ok(flattened) => match convert_to_array(flattened) {
+                Ok(array) => array,
+                Err(e) => {
+                    return Err(StreamError::Custom {
+                        msg: format!("Failed to convert to array: {e}"),
+                        status: StatusCode::BAD_REQUEST,
+                    });
+                }
+            },
+            Err(e) => {
+                return Err(StreamError::Custom {
+                    msg: e.to_string(),
+                    status: StatusCode::BAD_REQUEST,
+                });
+            }
+        };
+        if let Err(err) = flatten::flatten(&mut flattened_json, "_", None, None, None, false) {
+            return Err(StreamError::Custom {
+                msg: err.to_string(),
+                status: StatusCode::BAD_REQUEST,
+            });
+        }
+        let flattened_json_arr = match flattened_json {
+            Value::Array(arr) => arr,
+            value @ Value::Object(_) => vec![value],
+            _ => unreachable!("flatten would have failed beforehand"),
+        };
+        let mut schema = match infer_json_schema_from_iterator(flattened_json_arr.iter().map(Ok)) {
+            Ok(schema) => Arc::new(schema),
+            Err(e) => {
+                return Err(StreamError::Custom {
+                    msg: format!("Failed to infer schema: {e}"),
+                    status: StatusCode::BAD_REQUEST,
+                });
+            }
+        };
+        for flattened_json in flattened_json_arr {
+            schema = override_data_type(schema, flattened_json, SchemaVersion::V1);
+        }
+        Ok((web::Json(schema), StatusCode::OK))
+    } else {
+        // error out if the JSON is heavily nested
+        Err(StreamError::Custom {
+            msg: format!(
+                "JSON is too deeply nested (exceeds level {}), cannot flatten",
+                PARSEABLE.options.event_flatten_level
+            ),
+            status: StatusCode::BAD_REQUEST,
+        })
+    }
+}
+
+/// Format the schema of a stream is returned in
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaFormat {
+    #[default]
+    Json,
+    /// Arrow IPC stream holding only the schema message
+    ArrowIpc,
+    /// `CREATE TABLE` statement derived from the arrow fields
+    Sql,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SchemaQuery {
+    #[serde(default)]
+    pub format: SchemaFormat,
+}
+
+pub async fn get_schema(
+    stream_name: Path<String>,
+    web::Query(SchemaQuery { format }): web::Query<SchemaQuery>,
+) -> Result<HttpResponse, StreamError> {
+    let stream_name = stream_name.into_inner();
+
+    // Ensure parseable is aware of stream in distributed mode
+    if !PARSEABLE.check_or_load_stream(&stream_name).await {
+        return Err(StreamNotFound(stream_name.clone()).into());
+    }
+
+    let stream = PARSEABLE.get_stream(&stream_name)?;
+    match update_schema_when_distributed(&vec![stream_name.clone()]).await {
+        Ok(_) => {
+            let schema = stream.get_schema();
+            let response = match format {
+                SchemaFormat::Json => HttpResponse::Ok().json(schema),
+                SchemaFormat::ArrowIpc => {
+                    let bytes = schema_to_ipc(&schema).map_err(|err| StreamError::Custom {
+                        msg: format!("Failed to serialize schema: {err}"),
+                        status: StatusCode::INTERNAL_SERVER_ERROR,
+                    })?;
+                    HttpResponse::Ok()
+                        .content_type("application/vnd.apache.arrow.stream")
+                        .insert_header((
+                            header::CONTENT_DISPOSITION,
+                            format!("attachment; filename=\"{stream_name}.arrow\""),
+                        ))
+                        .body(bytes)
+                }
+                SchemaFormat::Sql => HttpResponse::Ok()
+                    .content_type(ContentType::plaintext())
+                    .body(schema_to_sql_ddl(&stream_name, &schema)),
+            };
+            Ok(response)
+        }
+        Err(err) => Err(StreamError::Custom {
+            msg: err.to_string(),
+            status: StatusCode::EXPECTATION_FAILED,
+        }),
+    }
+}
+
+pub async fn put_stream(
+    req: HttpRequest,
+    stream_name: Path<String>,
+    body: Bytes,
+) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+    PARSEABLE
+        .create_update_stream(req.headers(), &body, &stream_name)
+        .await?;
+
+    Ok(("Log stream created", StatusCode::OK))
+}
+
+pub async fn get_retention(stream_name: Path<String>) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+    // For query mode, if the stream not found in memory map,
+    //check if it exists in the storage
+    //create stream and schema from storage
+    if !PARSEABLE.check_or_load_stream(&stream_name).await {
+        return Err(StreamNotFound(stream_name.clone()).into());
+    }
+
+    let retention = PARSEABLE
+        .get_stream(&stream_name)?
+        .get_retention()
+        .unwrap_or_default();
+    Ok((web::Json(retention), StatusCode::OK))
+}
+
+pub async fn put_retention(
+    stream_name: Path<String>,
+    Json(retention): Json<Retention>,
+) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+
+    // For query mode, if the stream not found in memory map,
+    //check if it exists in the storage
+    //create stream and schema from storage
+    if !PARSEABLE.check_or_load_stream(&stream_name).await {
+        return Err(StreamNotFound(stream_name).into());
+    }
+
+    PARSEABLE
+        .storage
+        .get_object_store()
+        .put_retention(&stream_name, &retention)
+        .await?;
+
+    PARSEABLE.get_stream(&stream_name)?.set_retention(retention);
+
+    Ok((
+        format!("set retention configuration for log stream {stream_name}"),
+        StatusCode::OK,
+    ))
+}
+
+pub async fn get_masking(stream_name: Path<String>) -> Result<impl Responder, StreamError> {
+    let masking = load_stream(&stream_name.into_inner())
+        .await?
+        .get_masking()
+        .unwrap_or_default();
+    Ok((web::Json(masking), StatusCode::OK))
+}
+
+pub async fn put_masking(
+    stream_name: Path<String>,
+    Json(masking): Json<MaskingConfig>,
+) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+    let stream = load_stream(&stream_name).await?;
+    masking
+        .validate(&stream.get_schema())
+        .map_err(StreamError::InvalidMaskingConfig)?;
+
+    PARSEABLE
+        .storage
+        .get_object_store()
+        .update_stream_json(
+            &stream_name,
+            Box::new(|format: &mut ObjectStoreFormat| format.masking = Some(masking.clone())),
+        )
+        .await?;
+
+    stream.set_masking(masking);
+
+    Ok((
+        format!("set masking configuration for log stream {stream_name}"),
+        StatusCode::OK,
+    ))
+}
+
+/// Loads a stream from storage when it isn't in memory, as on a querier for the streams
+/// created through other nodes
+async fn load_stream(stream_name: &str) -> Result<Arc<Stream>, StreamError> {
+    // For query mode, if the stream not found in memory map,
+    //check if it exists in the storage
+    //create stream and schema from storage
+    if !PARSEABLE.check_or_load_stream(stream_name).await {
+        return Err(StreamNotFound(stream_name.to_owned()).into());
+    }
+
+    Ok(PARSEABLE.get_stream(stream_name)?)
+}
+
+/// A setting of a stream read and updated on its own at `/logstream/{logstream}/{PATH}`.
+///
+/// Updates are written to the stream json, applied to the stream in memory and, on the querier,
+/// forwarded to the live ingestors, as they apply the settings when ingesting events, writing
+/// parquet files and querying their staging.
+pub trait StreamSetting: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Path of the setting under the stream
+    const PATH: &'static str;
+    const GET_ACTION: Action;
+    const PUT_ACTION: Action;
+
+    fn get(stream: &Stream) -> Self;
+
+    /// Rejects the settings the stream can't have, with the reason
+    fn validate(&mut self, _stream: &Stream) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn store(&self, format: &mut ObjectStoreFormat);
+
+    fn apply(self, stream: &Stream);
+
+    /// Response to a successful update
+    fn updated(&self, stream_name: &str) -> String;
+}
+
+pub async fn get_setting<T: StreamSetting>(
+    stream_name: Path<String>,
+) -> Result<impl Responder, StreamError> {
+    let stream = load_stream(&stream_name.into_inner()).await?;
+
+    Ok((web::Json(T::get(&stream)), StatusCode::OK))
+}
+
+pub async fn put_setting<T: StreamSetting>(
+    stream_name: Path<String>,
+    Json(mut setting): Json<T>,
+) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+    let stream = load_stream(&stream_name).await?;
+
+    setting
+        .validate(&stream)
+        .map_err(|msg| StreamError::Custom {
+            msg,
+            status: StatusCode::BAD_REQUEST,
+        })?;
+
+    PARSEABLE
+        .storage
+        .get_object_store()
+        .update_stream_json(
+            &stream_name,
+            Box::new(|format: &mut ObjectStoreFormat| setting.store(format)),
+        )
+        .await?;
+
+    let body = serde_json::to_vec(&setting)?;
+    let msg = setting.updated(&stream_name);
+    setting.apply(&stream);
+
+    if matches!(PARSEABLE.options.mode, Mode::Query | Mode::Prism) {
+        sync_stream_setting_with_ingestors(&stream_name, T::PATH, body.into()).await?;
+    }
+
+    Ok((msg, StatusCode::OK))
+}
+
+/// Sampling configuration of a stream, `None` ingests every event
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SamplingConfig {
+    #[serde(default)]
+    pub sampling_rate: Option<SamplingRate>,
+}
+
+impl StreamSetting for SamplingConfig {
+    const PATH: &'static str = "sampling";
+    const GET_ACTION: Action = Action::GetSampling;
+    const PUT_ACTION: Action = Action::PutSampling;
+
+    fn get(stream: &Stream) -> Self {
+        Self {
+            sampling_rate: stream.get_sampling_rate(),
+        }
+    }
+
+    fn validate(&mut self, stream: &Stream) -> Result<(), String> {
+        if stream.get_stream_type() == StreamType::Internal {
+            return Err("Sampling can not be configured for internal stream".to_string());
+        }
+        Ok(())
+    }
+
+    fn store(&self, format: &mut ObjectStoreFormat) {
+        format.sampling_rate = self.sampling_rate;
+    }
+
+    fn apply(self, stream: &Stream) {
+        stream.set_sampling_rate(self.sampling_rate);
+    }
+
+    fn updated(&self, stream_name: &str) -> String {
+        format!("set sampling configuration for log stream {stream_name}")
+    }
+}
+
+/// Columns of a stream written with page level statistics and bloom filters
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedColumnsConfig {
+    #[serde(default)]
+    pub indexed_columns: Vec<String>,
+}
+
+impl StreamSetting for IndexedColumnsConfig {
+    const PATH: &'static str = "indexed-columns";
+    const GET_ACTION: Action = Action::GetIndexedColumns;
+    const PUT_ACTION: Action = Action::PutIndexedColumns;
+
+    fn get(stream: &Stream) -> Self {
+        Self {
+            indexed_columns: stream.get_indexed_columns(),
+        }
+    }
+
+    fn validate(&mut self, stream: &Stream) -> Result<(), String> {
+        self.indexed_columns.sort();
+        self.indexed_columns.dedup();
+
+        // ingestors may not have seen every column yet, the columns are validated where the request originates
+        if PARSEABLE.options.mode != Mode::Ingest {
+            let schema = stream.get_schema();
+            if let Some(column) = self
+                .indexed_columns
+                .iter()
+                .find(|column| schema.field_with_name(column).is_err())
+            {
+                return Err(format!(
+                    "Column {column} is not part of the schema of log stream {}",
+                    stream.stream_name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn store(&self, format: &mut ObjectStoreFormat) {
+        format.indexed_columns.clone_from(&self.indexed_columns);
+    }
+
+    fn apply(self, stream: &Stream) {
+        stream.set_indexed_columns(self.indexed_columns);
+    }
+
+    fn updated(&self, stream_name: &str) -> String {
+        format!("set indexed columns for log stream {stream_name}")
+    }
+}
+
+/// Handling of event fields colliding with the reserved fields of a stream
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReservedFieldPolicyConfig {
+    pub reserved_field_policy: ReservedFieldPolicy,
+}
+
+impl StreamSetting for ReservedFieldPolicyConfig {
+    const PATH: &'static str = "reserved-field-policy";
+    const GET_ACTION: Action = Action::GetReservedFieldPolicy;
+    const PUT_ACTION: Action = Action::PutReservedFieldPolicy;
+
+    fn get(stream: &Stream) -> Self {
+        Self {
+            reserved_field_policy: stream.get_reserved_field_policy(),
+        }
+    }
+
+    fn store(&self, format: &mut ObjectStoreFormat) {
+        format.reserved_field_policy = self.reserved_field_policy;
+    }
+
+    fn apply(self, stream: &Stream) {
+        stream.set_reserved_field_policy(self.reserved_field_policy);
+    }
+
+    fn updated(&self, stream_name: &str) -> String {
+        format!("set reserved field policy for log stream {stream_name}")
+    }
+}
+
+/// Handling of event values conflicting with the type of their column
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeCoercionPolicyConfig {
+    pub type_coercion_policy: TypeCoercionPolicy,
+}
+
+impl StreamSetting for TypeCoercionPolicyConfig {
+    const PATH: &'static str = "type-coercion-policy";
+    const GET_ACTION: Action = Action::GetTypeCoercionPolicy;
+    const PUT_ACTION: Action = Action::PutTypeCoercionPolicy;
+
+    fn get(stream: &Stream) -> Self {
+        Self {
+            type_coercion_policy: stream.get_type_coercion_policy(),
+        }
+    }
+
+    fn store(&self, format: &mut ObjectStoreFormat) {
+        format.type_coercion_policy = self.type_coercion_policy;
+    }
+
+    fn apply(self, stream: &Stream) {
+        stream.set_type_coercion_policy(self.type_coercion_policy);
+    }
+
+    fn updated(&self, stream_name: &str) -> String {
+        format!("set type coercion policy for log stream {stream_name}")
+    }
+}
+
+/// Handling of fractional numbers sent for the integer columns of a stream
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeWideningPolicyConfig {
+    pub type_widening_policy: TypeWideningPolicy,
+}
+
+impl StreamSetting for TypeWideningPolicyConfig {
+    const PATH: &'static str = "type-widening-policy";
+    const GET_ACTION: Action = Action::GetTypeWideningPolicy;
+    const PUT_ACTION: Action = Action::PutTypeWideningPolicy;
+
+    fn get(stream: &Stream) -> Self {
+        Self {
+            type_widening_policy: stream.get_type_widening_policy(),
+        }
+    }
+
+    fn validate(&mut self, stream: &Stream) -> Result<(), String> {
+        if self.type_widening_policy == TypeWideningPolicy::Widen && stream.get_static_schema_flag()
+        {
+            return Err(format!(
+                "Log stream {} has a static schema, its columns can't be widened",
+                stream.stream_name
+            ));
+        }
+        Ok(())
+    }
+
+    fn store(&self, format: &mut ObjectStoreFormat) {
+        format.type_widening_policy = self.type_widening_policy;
+    }
+
+    fn apply(self, stream: &Stream) {
+        stream.set_type_widening_policy(self.type_widening_policy);
+    }
+
+    fn updated(&self, stream_name: &str) -> String {
+        format!("set type widening policy for log stream {stream_name}")
+    }
+}
+
+/// How the events of a stream get their p_timestamp
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimestampPolicyConfig {
+    pub timestamp_policy: TimestampPolicy,
+}
+
+impl StreamSetting for TimestampPolicyConfig {
+    const PATH: &'static str = "timestamp-policy";
+    const GET_ACTION: Action = Action::GetTimestampPolicy;
+    const PUT_ACTION: Action = Action::PutTimestampPolicy;
+
+    fn get(stream: &Stream) -> Self {
+        Self {
+            timestamp_policy: stream.get_timestamp_policy(),
+        }
+    }
+
+    fn validate(&mut self, stream: &Stream) -> Result<(), String> {
+        // ingestors may not have seen every event yet, the field is validated where the request originates
+        if let Some(field) = self.timestamp_policy.field()
+            && PARSEABLE.options.mode != Mode::Ingest
+        {
+            let schema = stream.get_schema();
+            let Ok(field) = schema.field_with_name(field) else {
+                return Err(format!(
+                    "Field {field} not found in log stream {}",
+                    stream.stream_name
+                ));
+            };
+            if !TimestampPolicy::accepts_type(field.data_type()) {
+                return Err(format!(
+                    "Field {} is of type {}, a timestamp, string or integer field is required",
+                    field.name(),
+                    field.data_type()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn store(&self, format: &mut ObjectStoreFormat) {
+        format.timestamp_policy = self.timestamp_policy.clone();
+    }
+
+    fn apply(self, stream: &Stream) {
+        stream.set_timestamp_policy(self.timestamp_policy);
+    }
+
+    fn updated(&self, stream_name: &str) -> String {
+        format!("set timestamp policy for log stream {stream_name}")
+    }
+}
+
+/// Partition keys derived from the fields of events on ingestion, key -> expression
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartitionExpressionsConfig {
+    pub partition_expressions: HashMap<String, PartitionExpression>,
+}
+
+impl StreamSetting for PartitionExpressionsConfig {
+    const PATH: &'static str = "partition-expressions";
+    const GET_ACTION: Action = Action::GetPartitionExpressions;
+    const PUT_ACTION: Action = Action::PutPartitionExpressions;
+
+    fn get(stream: &Stream) -> Self {
+        Self {
+            partition_expressions: stream.get_partition_expressions(),
+        }
+    }
+
+    fn validate(&mut self, stream: &Stream) -> Result<(), String> {
+        // ingestors get the expressions once validated by the querier
+        if PARSEABLE.options.mode != Mode::Ingest {
+            validate_partition_expressions(stream, &self.partition_expressions)?;
+        }
+        Ok(())
+    }
+
+    fn store(&self, format: &mut ObjectStoreFormat) {
+        format
+            .partition_expressions
+            .clone_from(&self.partition_expressions);
+    }
+
+    fn apply(self, stream: &Stream) {
+        stream.set_partition_expressions(self.partition_expressions);
+    }
+
+    fn updated(&self, stream_name: &str) -> String {
+        format!("set partition expressions for log stream {stream_name}")
+    }
+}
+
+/// Derived keys can't overwrite a field of the events, and are computed from existing fields only
+fn validate_partition_expressions(
+    stream: &Stream,
+    partition_expressions: &HashMap<String, PartitionExpression>,
+) -> Result<(), String> {
+    let schema = stream.get_schema_raw();
+    let derived_keys = stream.get_partition_expressions();
+    let time_partition = stream.get_time_partition();
+
+    for (key, expression) in partition_expressions {
+        if key.trim().is_empty() || key.contains(',') {
+            return Err(format!("{key:?} is not a valid partition key"));
+        }
+        if time_partition.as_ref() == Some(key) {
+            return Err(format!(
+                "time partition {key} cannot be derived from an expression"
+            ));
+        }
+        // keys derived before are part of the schema once events are ingested
+        if schema.contains_key(key) && !derived_keys.contains_key(key) {
+            return Err(format!(
+                "partition key {key} is a field of the events of stream {}",
+                stream.stream_name
+            ));
+        }
+        for field in expression.fields() {
+            if partition_expressions.contains_key(field) || derived_keys.contains_key(field) {
+                return Err(format!(
+                    "partition key {key} cannot be computed from the derived key {field}"
+                ));
+            }
+            if !schema.contains_key(field) {
+                return Err(format!(
+                    "field {field} referenced by partition key {key} does not exist in the schema of stream {}",
+                    stream.stream_name
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Transforms applied in order to the events of a stream on ingestion
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransformsConfig {
+    pub transforms: Vec<TransformRule>,
+}
+
+impl StreamSetting for TransformsConfig {
+    const PATH: &'static str = "transforms";
+    const GET_ACTION: Action = Action::GetTransforms;
+    const PUT_ACTION: Action = Action::PutTransforms;
+
+    fn get(stream: &Stream) -> Self {
+        Self {
+            transforms: stream.get_transforms(),
+        }
+    }
+
+    fn validate(&mut self, stream: &Stream) -> Result<(), String> {
+        // ingestors get the transforms once validated by the querier
+        if PARSEABLE.options.mode != Mode::Ingest {
+            validate_transforms(stream, &self.transforms)?;
+        }
+        Ok(())
+    }
+
+    fn store(&self, format: &mut ObjectStoreFormat) {
+        format.transforms.clone_from(&self.transforms);
+    }
+
+    fn apply(self, stream: &Stream) {
+        stream.set_transforms(self.transforms);
+    }
+
+    fn updated(&self, stream_name: &str) -> String {
+        format!("set transforms for log stream {stream_name}")
+    }
+}
+
+/// Transforms can't touch the timestamp or the partitions of the events, those are computed
+/// from the fields the events are ingested with
+fn validate_transforms(stream: &Stream, transforms: &[TransformRule]) -> Result<(), String> {
+    let time_partition = stream.get_time_partition();
+    let custom_partitions = stream.get_custom_partition();
+    let custom_partitions: Vec<&str> = custom_partitions
+        .iter()
+        .flat_map(|partitions| partitions.split(','))
+        .map(str::trim)
+        .collect();
+
+    for (position, rule) in transforms.iter().enumerate() {
+        if let TransformRule::Rename { from, to } = rule
+            && from == to
+        {
+            return Err(format!(
+                "transform {position} renames field {from} to itself"
+            ));
+        }
+        for field in rule.output_field().into_iter().chain(rule.removed_field()) {
+            if field.trim().is_empty() {
+                return Err(format!("transform {position} has an empty field name"));
+            }
+            if field == DEFAULT_TIMESTAMP_KEY {
+                return Err(format!(
+                    "transform {position} cannot modify the {DEFAULT_TIMESTAMP_KEY} field"
+                ));
+            }
+            if time_partition.as_deref() == Some(field) {
+                return Err(format!(
+                    "transform {position} cannot modify the time partition {field}"
+                ));
+            }
+            if custom_partitions.contains(&field) {
+                return Err(format!(
+                    "transform {position} cannot modify the custom partition {field}"
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Bounds of the parquet row group size configurable for a stream
+const MIN_ROW_GROUP_SIZE: usize = 1024;
+const MAX_ROW_GROUP_SIZE: usize = 8 * 1024 * 1024;
+
+/// Number of rows per parquet row group of a stream, `None` uses the server wide default
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RowGroupSizeConfig {
+    #[serde(default)]
+    pub row_group_size: Option<usize>,
+}
+
+impl StreamSetting for RowGroupSizeConfig {
+    const PATH: &'static str = "row-group-size";
+    const GET_ACTION: Action = Action::GetRowGroupSize;
+    const PUT_ACTION: Action = Action::PutRowGroupSize;
+
+    fn get(stream: &Stream) -> Self {
+        Self {
+            row_group_size: stream.get_row_group_size(),
+        }
+    }
+
+    fn validate(&mut self, _stream: &Stream) -> Result<(), String> {
+        if let Some(row_group_size) = self.row_group_size
+            && !(MIN_ROW_GROUP_SIZE..=MAX_ROW_GROUP_SIZE).contains(&row_group_size)
+        {
+            return Err(format!(
+                "Row group size must be between {MIN_ROW_GROUP_SIZE} and {MAX_ROW_GROUP_SIZE} rows"
+            ));
+        }
+        Ok(())
+    }
+
+    fn store(&self, format: &mut ObjectStoreFormat) {
+        format.row_group_size = self.row_group_size;
+    }
+
+    fn apply(self, stream: &Stream) {
+        stream.set_row_group_size(self.row_group_size);
+    }
+
+    fn updated(&self, stream_name: &str) -> String {
+        format!("set row group size for log stream {stream_name}")
+    }
+}
+
+/// Whether the wide numeric columns of a stream are downcast to the narrowest type fitting
+/// their values when written to parquet
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NumericDowncastConfig {
+    pub numeric_downcast: bool,
+}
+
+impl StreamSetting for NumericDowncastConfig {
+    const PATH: &'static str = "numeric-downcast";
+    const GET_ACTION: Action = Action::GetNumericDowncast;
+    const PUT_ACTION: Action = Action::PutNumericDowncast;
+
+    fn get(stream: &Stream) -> Self {
+        Self {
+            numeric_downcast: stream.get_numeric_downcast(),
+        }
+    }
+
+    fn store(&self, format: &mut ObjectStoreFormat) {
+        format.numeric_downcast = self.numeric_downcast;
+    }
+
+    fn apply(self, stream: &Stream) {
+        stream.set_numeric_downcast(self.numeric_downcast);
+    }
+
+    fn updated(&self, stream_name: &str) -> String {
+        let action = if self.numeric_downcast {
+            "enabled"
+        } else {
+            "disabled"
+        };
+        format!("{action} numeric downcasting for log stream {stream_name}")
+    }
+}
+
+/// How far behind the partition being written the events of a stream are still written to the
+/// partition of their event time, `None` writes all events to their own partition
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LateEventWindowConfig {
+    #[serde(default)]
+    pub late_event_window: Option<LateEventWindow>,
+}
+
+impl StreamSetting for LateEventWindowConfig {
+    const PATH: &'static str = "late-event-window";
+    const GET_ACTION: Action = Action::GetLateEventWindow;
+    const PUT_ACTION: Action = Action::PutLateEventWindow;
+
+    fn get(stream: &Stream) -> Self {
+        Self {
+            late_event_window: stream.get_late_event_window(),
+        }
+    }
+
+    fn store(&self, format: &mut ObjectStoreFormat) {
+        format.late_event_window = self.late_event_window.clone();
+    }
+
+    fn apply(self, stream: &Stream) {
+        stream.set_late_event_window(self.late_event_window);
+    }
+
+    fn updated(&self, stream_name: &str) -> String {
+        format!("set late event window for log stream {stream_name}")
+    }
+}
+
+/// Whether the inferred schema of a stream is frozen, events with unknown fields are then rejected
+/// instead of extending the schema
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaFreezeConfig {
+    pub schema_frozen: bool,
+}
+
+impl StreamSetting for SchemaFreezeConfig {
+    const PATH: &'static str = "schema-freeze";
+    const GET_ACTION: Action = Action::GetSchemaFreeze;
+    const PUT_ACTION: Action = Action::PutSchemaFreeze;
+
+    fn get(stream: &Stream) -> Self {
+        Self {
+            schema_frozen: stream.get_schema_frozen(),
+        }
+    }
+
+    fn validate(&mut self, stream: &Stream) -> Result<(), String> {
+        // ingestors may not have seen every event yet, the schema is validated where the request originates
+        if self.schema_frozen && PARSEABLE.options.mode != Mode::Ingest {
+            if stream.get_static_schema_flag() {
+                return Err(format!(
+                    "Log stream {} already has a static schema",
+                    stream.stream_name
+                ));
+            }
+            if stream.get_schema().fields().is_empty() {
+                return Err(format!(
+                    "Log stream {} has no schema to freeze yet, ingest some events first",
+                    stream.stream_name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn store(&self, format: &mut ObjectStoreFormat) {
+        format.schema_frozen = self.schema_frozen;
+    }
+
+    fn apply(self, stream: &Stream) {
+        stream.set_schema_frozen(self.schema_frozen);
+    }
+
+    fn updated(&self, stream_name: &str) -> String {
+        let action = if self.schema_frozen {
+            "froze"
+        } else {
+            "unfroze"
+        };
+        format!("{action} schema of log stream {stream_name}")
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SchemaDiffQuery {
+    pub a: String,
+    pub b: String,
+}
+
+/// A field of one of the compared streams
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffField {
+    pub name: String,
+    pub data_type: DataType,
+}
+
+/// A field of both streams, with a different type in each of them
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeMismatch {
+    pub name: String,
+    pub type_in_a: DataType,
+    pub type_in_b: DataType,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaDiff {
+    pub only_in_a: Vec<DiffField>,
+    pub only_in_b: Vec<DiffField>,
+    pub type_mismatches: Vec<TypeMismatch>,
+}
+
+/// Compares the fields of two schemas by name, fields are listed in the order of their schema
+pub fn diff_schemas(a: &Schema, b: &Schema) -> SchemaDiff {
+    let only_in = |schema: &Schema, other: &Schema| {
+        schema
+            .fields()
+            .iter()
+            .filter(|field| other.field_with_name(field.name()).is_err())
+            .map(|field| DiffField {
+                name: field.name().clone(),
+                data_type: field.data_type().clone(),
+            })
+            .collect::<Vec<_>>()
+    };
+    let type_mismatches = a
+        .fields()
+        .iter()
+        .filter_map(|field| {
+            let other = b.field_with_name(field.name()).ok()?;
+            (field.data_type() != other.data_type()).then(|| TypeMismatch {
+                name: field.name().clone(),
+                type_in_a: field.data_type().clone(),
+                type_in_b: other.data_type().clone(),
+            })
+        })
+        .collect();
+
+    SchemaDiff {
+        only_in_a: only_in(a, b),
+        only_in_b: only_in(b, a),
+        type_mismatches,
+    }
+}
+
+pub async fn diff_stream_schemas(
+    req: HttpRequest,
+    web::Query(SchemaDiffQuery { a, b }): web::Query<SchemaDiffQuery>,
+) -> Result<impl Responder, StreamError> {
+    let key = extract_session_key_from_req(&req)
+        .map_err(|err| StreamError::Anyhow(anyhow::Error::msg(err.to_string())))?;
+    for stream_name in [&a, &b] {
+        if Users.authorize(key.clone(), Action::GetSchema, Some(stream_name), None)
+            != crate::rbac::Response::Authorized
+        {
+            return Err(StreamError::Custom {
+                msg: format!("Not authorized to access the schema of stream {stream_name}"),
+                status: StatusCode::FORBIDDEN,
+            });
+        }
+        // Ensure parseable is aware of stream in distributed mode
+        if !PARSEABLE.check_or_load_stream(stream_name).await {
+            return Err(StreamNotFound(stream_name.clone()).into());
+        }
+    }
+
+    update_schema_when_distributed(&vec![a.clone(), b.clone()])
+        .await
+        .map_err(|err| StreamError::Custom {
+            msg: err.to_string(),
+            status: StatusCode::EXPECTATION_FAILED,
+        })?;
+    let diff = diff_schemas(
+        &PARSEABLE.get_stream(&a)?.get_schema(),
+        &PARSEABLE.get_stream(&b)?.get_schema(),
+    );
+
+    Ok((web::Json(diff), StatusCode::OK))
+}
+
+/// Whether a stream is read-only, ingestion into it is then rejected
+/// while it can still be queried, have its retention applied and be deleted
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadOnlyConfig {
+    pub read_only: bool,
+}
+
+impl StreamSetting for ReadOnlyConfig {
+    const PATH: &'static str = "read-only";
+    const GET_ACTION: Action = Action::GetReadOnly;
+    const PUT_ACTION: Action = Action::PutReadOnly;
+
+    fn get(stream: &Stream) -> Self {
+        Self {
+            read_only: stream.get_read_only(),
+        }
+    }
+
+    fn validate(&mut self, stream: &Stream) -> Result<(), String> {
+        if stream.get_stream_type() == StreamType::Internal {
+            return Err(format!(
+                "Internal stream {} can't be made read-only",
+                stream.stream_name
+            ));
+        }
+        Ok(())
+    }
+
+    fn store(&self, format: &mut ObjectStoreFormat) {
+        format.read_only = self.read_only;
+    }
+
+    fn apply(self, stream: &Stream) {
+        stream.set_read_only(self.read_only);
+    }
+
+    fn updated(&self, stream_name: &str) -> String {
+        let state = if self.read_only {
+            "read-only"
+        } else {
+            "writable"
+        };
+        format!("log stream {stream_name} is now {state}")
+    }
+}
+
+/// Column used instead of p_timestamp as the event time of the stream, for ordering
+/// and default time range filters. The time partition, if any, takes precedence.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimestampColumnConfig {
+    pub timestamp_column: Option<String>,
+}
+
+impl StreamSetting for TimestampColumnConfig {
+    const PATH: &'static str = "timestamp-column";
+    const GET_ACTION: Action = Action::GetTimestampColumn;
+    const PUT_ACTION: Action = Action::PutTimestampColumn;
+
+    fn get(stream: &Stream) -> Self {
+        Self {
+            timestamp_column: stream.get_timestamp_column(),
+        }
+    }
+
+    fn validate(&mut self, stream: &Stream) -> Result<(), String> {
+        // ingestors may not have seen every event yet, the column is validated where the request originates
+        if let Some(column) = &self.timestamp_column
+            && PARSEABLE.options.mode != Mode::Ingest
+        {
+            let schema = stream.get_schema();
+            let Ok(field) = schema.field_with_name(column) else {
+                return Err(format!(
+                    "Column {column} not found in log stream {}",
+                    stream.stream_name
+                ));
+            };
+            if !matches!(field.data_type(), DataType::Timestamp(_, _)) {
+                return Err(format!(
+                    "Column {column} is of type {}, a timestamp column is required",
+                    field.data_type()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn store(&self, format: &mut ObjectStoreFormat) {
+        format.timestamp_column.clone_from(&self.timestamp_column);
+    }
+
+    fn apply(self, stream: &Stream) {
+        stream.set_timestamp_column(self.timestamp_column);
+    }
+
+    fn updated(&self, stream_name: &str) -> String {
+        let column = self
+            .timestamp_column
+            .as_deref()
+            .unwrap_or(DEFAULT_TIMESTAMP_KEY);
+        format!("set timestamp column of log stream {stream_name} to {column}")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ColumnRename {
+    pub from: String,
+    pub to: String,
+}
+
+/// Renames a column of a stream by adding an alias to it, stored data is left untouched.
+/// Queries can reference the column by either name.
+pub async fn rename_column(
+    stream_name: Path<String>,
+    Json(rename): Json<ColumnRename>,
+) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+    let stream = load_stream(&stream_name).await?;
+    let ColumnRename { from, to } = &rename;
+    let bad_request = |msg: String| StreamError::Custom {
+        msg,
+        status: StatusCode::BAD_REQUEST,
+    };
+    if to.is_empty() {
+        return Err(bad_request("New column name can't be empty".to_owned()));
+    }
+
+    // ingestors may not have seen every event yet, the rename is validated where the request originates
+    if PARSEABLE.options.mode != Mode::Ingest {
+        let schema = stream.get_schema();
+        if schema.field_with_name(from).is_err() {
+            return Err(bad_request(format!(
+                "Column {from} not found in log stream {stream_name}"
+            )));
+        }
+        let is_partition = from == DEFAULT_TIMESTAMP_KEY
+            || stream.get_time_partition().as_ref() == Some(from)
+            || stream
+                .get_custom_partition()
+                .is_some_and(|partitions| partitions.split(',').any(|p| p.trim() == from));
+        if is_partition {
+            return Err(bad_request(format!(
+                "Column {from} is a partition column of log stream {stream_name} and can't be renamed"
+            )));
+        }
+        if schema.field_with_name(to).is_ok() || stream.get_column_aliases().contains_key(to) {
+            return Err(bad_request(format!(
+                "Column {to} already exists in log stream {stream_name}"
+            )));
+        }
     }
 
-    // Delete from memory
-    PARSEABLE.streams.delete(&stream_name);
-    stats::delete_stats(&stream_name, "json")
-        .unwrap_or_else(|e| warn!("failed to delete stats for stream {}: {:?}", stream_name, e));
+    PARSEABLE
+        .storage
+        .get_object_store()
+        .update_stream_json(
+            &stream_name,
+            Box::new(|format: &mut ObjectStoreFormat| {
+                format.column_aliases.insert(to.clone(), from.clone());
+            }),
+        )
+        .await?;
 
-    Ok((format!("log stream {stream_name} deleted"), StatusCode::OK))
-}
+    stream.add_column_alias(to.clone(), from.clone());
 
-pub async fn list(req: HttpRequest) -> Result<impl Responder, StreamError> {
-    let key = extract_session_key_from_req(&req)
-        .map_err(|err| StreamError::Anyhow(anyhow::Error::msg(err.to_string())))?;
+    if matches!(PARSEABLE.options.mode, Mode::Query | Mode::Prism) {
+        sync_stream_setting_with_ingestors(
+            &stream_name,
+            "schema/rename",
+            serde_json::to_vec(&rename)?.into(),
+        )
+        .await?;
+    }
 
-    // list all streams from storage
-    let res = PARSEABLE
-        .metastore
-        .list_streams()
-        .await?
-        .into_iter()
-        .filter(|logstream| {
-            Users.authorize(key.clone(), Action::ListStream, Some(logstream), None)
-                == crate::rbac::Response::Authorized
-        })
-        .map(|name| json!({"name": name}))
-        .collect_vec();
+    Ok((
+        format!("renamed column {from} to {to} in log stream {stream_name}"),
+        StatusCode::OK,
+    ))
+}
 
-    Ok(web::Json(res))
+#[derive(Debug, Deserialize)]
+pub struct RebuildManifestQuery {
+    pub date: NaiveDate,
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
-pub async fn detect_schema(Json(json): Json<Value>) -> Result<impl Responder, StreamError> {
-    // flatten before infer
-    if !has_more_than_max_allowed_levels(&json, 1) {
-        //perform generic flattening, return error if failed to flatten
-        let mut flattened_json = match generic_flattening(&json) {
-            Ok(flattened) => match convert_to_array(flattened) {
-                Ok(array) => array,
-                Err(e) => {
-                    return Err(StreamError::Custom {
-                        msg: format!("Failed to convert to array: {e}"),
-                        status: StatusCode::BAD_REQUEST,
-                    });
-                }
-            },
-            Err(e) => {
-                return Err(StreamError::Custom {
-                    msg: e.to_string(),
-                    status: StatusCode::BAD_REQUEST,
-                });
-            }
-        };
-        if let Err(err) = flatten::flatten(&mut flattened_json, "_", None, None, None, false) {
-            return Err(StreamError::Custom {
-                msg: err.to_string(),
-                status: StatusCode::BAD_REQUEST,
-            });
-        }
-        let flattened_json_arr = match flattened_json {
-            Value::Array(arr) => arr,
-            value @ Value::Object(_) => vec![value],
-            _ => unreachable!("flatten would have failed beforehand"),
-        };
-        let mut schema = match infer_json_schema_from_iterator(flattened_json_arr.iter().map(Ok)) {
-            Ok(schema) => Arc::new(schema),
-            Err(e) => {
-                return Err(StreamError::Custom {
-                    msg: format!("Failed to infer schema: {e}"),
-                    status: StatusCode::BAD_REQUEST,
-                });
-            }
-        };
-        for flattened_json in flattened_json_arr {
-            schema = override_data_type(schema, flattened_json, SchemaVersion::V1);
-        }
-        Ok((web::Json(schema), StatusCode::OK))
+/// Rebuilds the manifests of the given date from the footers of the parquet files in storage,
+/// to recover from manifests out of sync with the stored files, e.g. after a crash mid-write.
+/// In distributed mode every ingestor rebuilds the manifest of the files it uploaded.
+pub async fn rebuild_manifest(
+    stream_name: Path<String>,
+    web::Query(RebuildManifestQuery { date, dry_run }): web::Query<RebuildManifestQuery>,
+) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+    load_stream(&stream_name).await?;
+
+    let rebuilds = if matches!(PARSEABLE.options.mode, Mode::Query | Mode::Prism) {
+        rebuild_manifest_on_ingestors(&stream_name, date, dry_run).await?
     } else {
-        // error out if the JSON is heavily nested
-        Err(StreamError::Custom {
-            msg: format!(
-                "JSON is too deeply nested (exceeds level {}), cannot flatten",
-                PARSEABLE.options.event_flatten_level
-            ),
-            status: StatusCode::BAD_REQUEST,
-        })
+        vec![catalog::rebuild_manifest(&stream_name, date, dry_run).await?]
+    };
+    for rebuild in rebuilds.iter().filter(|rebuild| !rebuild.dry_run) {
+        info!(
+            "rebuilt manifest {} of stream {stream_name} with {} files, {} added and {} removed",
+            rebuild.manifest_path,
+            rebuild.files,
+            rebuild.added.len(),
+            rebuild.removed.len()
+        );
     }
+
+    Ok((web::Json(rebuilds), StatusCode::OK))
 }
 
-pub async fn get_schema(stream_name: Path<String>) -> Result<impl Responder, StreamError> {
+/// Merges the snapshots of the ingestors of the stream into its consolidated snapshot,
+/// which queries over settled time ranges read instead of the stream json of every ingestor.
+/// Snapshots are also consolidated every `P_SNAPSHOT_CONSOLIDATION_INTERVAL` minutes when set.
+pub async fn consolidate_snapshot(
+    stream_name: Path<String>,
+) -> Result<impl Responder, StreamError> {
     let stream_name = stream_name.into_inner();
 
-    // Ensure parseable is aware of stream in distributed mode
-    if !PARSEABLE.check_or_load_stream(&stream_name).await {
-        return Err(StreamNotFound(stream_name.clone()).into());
+    if load_stream(&stream_name)
+        .await?
+        .get_time_partition()
+        .is_some()
+    {
+        return Err(StreamError::Custom {
+            msg: format!(
+                "log stream {stream_name} has a time partition, its snapshots can't be consolidated"
+            ),
+            status: StatusCode::BAD_REQUEST,
+        });
     }
 
-    let stream = PARSEABLE.get_stream(&stream_name)?;
-    match update_schema_when_distributed(&vec![stream_name.clone()]).await {
-        Ok(_) => {
-            let schema = stream.get_schema();
-            Ok((web::Json(schema), StatusCode::OK))
-        }
-        Err(err) => Err(StreamError::Custom {
-            msg: err.to_string(),
-            status: StatusCode::EXPECTATION_FAILED,
-        }),
-    }
+    let consolidated = catalog::consolidation::consolidate_snapshot(&stream_name).await?;
+    info!(
+        "consolidated {} snapshots of stream {stream_name} with {} manifests",
+        consolidated.sources,
+        consolidated.snapshot.manifest_list.len()
+    );
+
+    Ok((
+        web::Json(json!({
+            "consolidatedAt": consolidated.consolidated_at,
+            "completeUntil": consolidated.complete_until,
+            "sources": consolidated.sources,
+            "manifests": consolidated.snapshot.manifest_list.len(),
+        })),
+        StatusCode::OK,
+    ))
 }
 
-pub async fn put_stream(
-    req: HttpRequest,
+/// Recomputes the first event timestamp of the stream from its manifests, in the background
+/// as it may have to read the manifests of many days for large streams
+pub async fn recompute_first_event(
     stream_name: Path<String>,
-    body: Bytes,
 ) -> Result<impl Responder, StreamError> {
     let stream_name = stream_name.into_inner();
-    PARSEABLE
-        .create_update_stream(req.headers(), &body, &stream_name)
-        .await?;
+    load_stream(&stream_name).await?;
+
+    let msg = format!("recomputing first event timestamp of log stream {stream_name}");
+    tokio::spawn(async move {
+        match catalog::get_first_event(&stream_name).await {
+            Ok(Some(first_event_at)) => {
+                PARSEABLE
+                    .update_first_event_at(&stream_name, &first_event_at)
+                    .await;
+                info!("first event timestamp of stream {stream_name} set to {first_event_at}");
+            }
+            Ok(None) => info!("no events found to recompute first event of stream {stream_name}"),
+            Err(err) => {
+                error!("failed to recompute first event timestamp of stream {stream_name}: {err}")
+            }
+        }
+    });
 
-    Ok(("Log stream created", StatusCode::OK))
+    Ok((msg, StatusCode::ACCEPTED))
 }
 
-pub async fn get_retention(stream_name: Path<String>) -> Result<impl Responder, StreamError> {
+#[derive(Debug, Deserialize)]
+pub struct ManifestQuery {
+    pub date: NaiveDate,
+}
+
+/// A parquet file listed in a manifest, along with the time bounds recorded in its statistics
+#[derive(Debug, Serialize)]
+pub struct ManifestFileInfo {
+    pub file_path: String,
+    pub num_rows: u64,
+    pub file_size: u64,
+    pub ingestion_size: u64,
+    pub time_lower_bound: Option<DateTime<Utc>>,
+    pub time_upper_bound: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ManifestInfo {
+    pub manifest_path: String,
+    pub time_lower_bound: DateTime<Utc>,
+    pub time_upper_bound: DateTime<Utc>,
+    pub files: Vec<ManifestFileInfo>,
+}
+
+/// Returns the manifests (one per node in distributed mode) recorded for the given date
+pub async fn get_manifest(
+    stream_name: Path<String>,
+    web::Query(ManifestQuery { date }): web::Query<ManifestQuery>,
+) -> Result<impl Responder, StreamError> {
     let stream_name = stream_name.into_inner();
     // For query mode, if the stream not found in memory map,
     //check if it exists in the storage
@@ -202,40 +1527,237 @@ pub async fn get_retention(stream_name: Path<String>) -> Result<impl Responder,
         return Err(StreamNotFound(stream_name.clone()).into());
     }
 
-    let retention = PARSEABLE
+    let time_column = PARSEABLE
         .get_stream(&stream_name)?
-        .get_retention()
-        .unwrap_or_default();
-    Ok((web::Json(retention), StatusCode::OK))
+        .get_time_partition()
+        .unwrap_or_else(|| DEFAULT_TIMESTAMP_KEY.to_owned());
+
+    let mut manifests = Vec::new();
+    for stream_json in PARSEABLE
+        .metastore
+        .get_all_stream_jsons(&stream_name, None)
+        .await?
+    {
+        let Ok(object_store_format) = serde_json::from_slice::<ObjectStoreFormat>(&stream_json)
+        else {
+            continue;
+        };
+        for item in object_store_format
+            .snapshot
+            .manifest_list
+            .into_iter()
+            .filter(|item| item.time_lower_bound.date_naive() == date)
+        {
+            let Some(manifest) = PARSEABLE
+                .metastore
+                .get_manifest(
+                    &stream_name,
+                    item.time_lower_bound,
+                    item.time_upper_bound,
+                    Some(item.manifest_path.clone()),
+                )
+                .await?
+            else {
+                continue;
+            };
+
+            let files = manifest
+                .files
+                .into_iter()
+                .map(|file| {
+                    let (time_lower_bound, time_upper_bound) = file
+                        .columns
+                        .iter()
+                        .find(|column| column.name == time_column)
+                        .and_then(|column| match &column.stats {
+                            Some(TypedStatistics::Int(stats)) => Some((
+                                DateTime::from_timestamp_millis(stats.min),
+                                DateTime::from_timestamp_millis(stats.max),
+                            )),
+                            _ => None,
+                        })
+                        .unwrap_or_default();
+                    ManifestFileInfo {
+                        file_path: file.file_path,
+                        num_rows: file.num_rows,
+                        file_size: file.file_size,
+                        ingestion_size: file.ingestion_size,
+                        time_lower_bound,
+                        time_upper_bound,
+                    }
+                })
+                .collect();
+
+            manifests.push(ManifestInfo {
+                manifest_path: item.manifest_path,
+                time_lower_bound: item.time_lower_bound,
+                time_upper_bound: item.time_upper_bound,
+                files,
+            });
+        }
+    }
+
+    if manifests.is_empty() {
+        return Err(StreamError::Custom {
+            msg: format!("no manifest found for log stream {stream_name} on {date}"),
+            status: StatusCode::NOT_FOUND,
+        });
+    }
+
+    Ok((web::Json(manifests), StatusCode::OK))
 }
 
-pub async fn put_retention(
+/// Days of manifests the partition stats of a stream can be computed over
+const MAX_PARTITION_STATS_DAYS: i64 = 90;
+const DEFAULT_SKEW_FACTOR: f64 = 10.0;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartitionStatsQuery {
+    /// defaults to a week before `end`
+    pub start: Option<NaiveDate>,
+    /// defaults to today
+    pub end: Option<NaiveDate>,
+    /// partitions larger than this many times the median partition are reported as skewed
+    pub skew_factor: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartitionStatsReport {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    /// the custom partitions of the stream, or its date and hour
+    pub partitioned_by: Vec<String>,
+    pub skew_factor: f64,
+    pub median_bytes: u64,
+    /// from the largest partition
+    pub partitions: Vec<PartitionStats>,
+}
+
+/// Reports the files and sizes of the partitions of the stream between two dates, computed
+/// from its manifests, to help finding a partition key which splits the data unevenly
+pub async fn get_partition_stats(
     stream_name: Path<String>,
-    Json(retention): Json<Retention>,
+    web::Query(query): web::Query<PartitionStatsQuery>,
 ) -> Result<impl Responder, StreamError> {
     let stream_name = stream_name.into_inner();
+    let stream = load_stream(&stream_name).await?;
 
-    // For query mode, if the stream not found in memory map,
-    //check if it exists in the storage
-    //create stream and schema from storage
-    if !PARSEABLE.check_or_load_stream(&stream_name).await {
-        return Err(StreamNotFound(stream_name).into());
+    let bad_request = |msg: String| StreamError::Custom {
+        msg,
+        status: StatusCode::BAD_REQUEST,
+    };
+    let end = query.end.unwrap_or_else(|| Utc::now().date_naive());
+    let start = query.start.unwrap_or(end - TimeDelta::days(6));
+    if start > end {
+        return Err(bad_request(format!("start {start} is after end {end}")));
+    }
+    if (end - start).num_days() >= MAX_PARTITION_STATS_DAYS {
+        return Err(bad_request(format!(
+            "partition stats can be computed over at most {MAX_PARTITION_STATS_DAYS} days"
+        )));
+    }
+    let skew_factor = query.skew_factor.unwrap_or(DEFAULT_SKEW_FACTOR);
+    if !(skew_factor.is_finite() && skew_factor > 1.0) {
+        return Err(bad_request(format!(
+            "skewFactor should be greater than 1, got {skew_factor}"
+        )));
     }
 
-    PARSEABLE
-        .storage
-        .get_object_store()
-        .put_retention(&stream_name, &retention)
-        .await?;
+    let custom_partition = stream.get_custom_partition();
+    let partitioned_by = match &custom_partition {
+        Some(custom_partition) => custom_partition
+            .split(',')
+            .map(|key| key.trim().to_owned())
+            .collect(),
+        None => vec!["date".to_owned(), "hour".to_owned()],
+    };
 
-    PARSEABLE.get_stream(&stream_name)?.set_retention(retention);
+    let mut sizes = PartitionSizes::new(custom_partition.is_some());
+    for stream_json in PARSEABLE
+        .metastore
+        .get_all_stream_jsons(&stream_name, None)
+        .await?
+    {
+        let Ok(object_store_format) = serde_json::from_slice::<ObjectStoreFormat>(&stream_json)
+        else {
+            continue;
+        };
+        for item in object_store_format
+            .snapshot
+            .manifest_list
+            .into_iter()
+            .filter(|item| {
+                item.time_lower_bound.date_naive() <= end
+                    && item.time_upper_bound.date_naive() >= start
+            })
+        {
+            let Some(manifest) = PARSEABLE
+                .metastore
+                .get_manifest(
+                    &stream_name,
+                    item.time_lower_bound,
+                    item.time_upper_bound,
+                    Some(item.manifest_path),
+                )
+                .await?
+            else {
+                continue;
+            };
+            for file in &manifest.files {
+                sizes.add(file);
+            }
+        }
+    }
 
+    let (partitions, median_bytes) = sizes.finish(skew_factor);
     Ok((
-        format!("set retention configuration for log stream {stream_name}"),
+        web::Json(PartitionStatsReport {
+            start,
+            end,
+            partitioned_by,
+            skew_factor,
+            median_bytes,
+            partitions,
+        }),
         StatusCode::OK,
     ))
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamDependents {
+    pub stream: String,
+    pub alerts: Vec<alerts::AlertDependent>,
+    pub correlations: Vec<CorrelationDependent>,
+}
+
+/// Lists the alerts and correlations whose query reads the stream, the definitions which
+/// would break if the stream were deleted, among those the user can see
+pub async fn get_dependents(
+    req: HttpRequest,
+    stream_name: Path<String>,
+) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+    if !PARSEABLE.check_or_load_stream(&stream_name).await {
+        return Err(StreamNotFound(stream_name.clone()).into());
+    }
+    let key = extract_session_key_from_req(&req)
+        .map_err(|err| StreamError::Anyhow(anyhow::Error::msg(err.to_string())))?;
+
+    let dependents = StreamDependents {
+        alerts: alerts::alerts_querying(&stream_name, &key).await,
+        correlations: CORRELATIONS
+            .querying(&stream_name, &key)
+            .await
+            .map_err(|err| StreamError::Anyhow(err.into()))?,
+        stream: stream_name,
+    };
+
+    Ok((web::Json(dependents), StatusCode::OK))
+}
+
 pub async fn get_stats_date(stream_name: &str, date: &str) -> Result<Stats, StreamError> {
     let event_labels = event_labels_date(stream_name, "json", date);
     let storage_size_labels = storage_size_labels_date(stream_name, date);
@@ -260,6 +1782,31 @@ pub async fn get_stats_date(stream_name: &str, date: &str) -> Result<Stats, Stre
     Ok(stats)
 }
 
+/// Time zone of the `tz` query parameter, `None` if absent or UTC
+pub fn stats_timezone(query_map: &HashMap<String, String>) -> Result<Option<Tz>, StreamError> {
+    let Some(tz) = query_map.get("tz") else {
+        return Ok(None);
+    };
+    let tz = parse_timezone(tz).map_err(StreamError::InvalidQueryParameter)?;
+    Ok((tz != Tz::UTC).then_some(tz))
+}
+
+/// Stats of `date` in the time zone `tz`, summed from the manifests
+/// since the per date stats metrics are kept for UTC dates
+pub async fn get_stats_date_in_timezone(
+    stream_name: &str,
+    date: &str,
+    tz: Tz,
+) -> Result<Stats, StreamError> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+        StreamError::InvalidQueryParameter(format!(
+            "Invalid date \"{date}\", it should be given as YYYY-MM-DD"
+        ))
+    })?;
+
+    Ok(catalog::get_stats_in_range(stream_name, &TimeRange::local_day(date, tz)).await?)
+}
+
 pub async fn get_stats(
     req: HttpRequest,
     stream_name: Path<String>,
@@ -273,20 +1820,21 @@ pub async fn get_stats(
         return Err(StreamNotFound(stream_name.clone()).into());
     }
 
-    let query_string = req.query_string();
-    if !query_string.is_empty() {
-        let tokens = query_string.split('=').collect::<Vec<&str>>();
-        let date_key = tokens[0];
-        let date_value = tokens[1];
-        if date_key != "date" {
+    let query_map = web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .map_err(|_| StreamError::InvalidQueryParameter("date".to_string()))?;
+    if !query_map.is_empty() {
+        let Some(date_value) = query_map.get("date") else {
             return Err(StreamError::Custom {
                 msg: "Invalid query parameter".to_string(),
                 status: StatusCode::BAD_REQUEST,
             });
-        }
+        };
 
         if !date_value.is_empty() {
-            let stats = get_stats_date(&stream_name, date_value).await?;
+            let stats = match stats_timezone(&query_map)? {
+                Some(tz) => get_stats_date_in_timezone(&stream_name, date_value, tz).await?,
+                None => get_stats_date(&stream_name, date_value).await?,
+            };
             let stats = serde_json::to_value(stats)?;
             return Ok((web::Json(stats), StatusCode::OK));
         }
@@ -365,10 +1913,26 @@ pub async fn get_stream_info(stream_name: Path<String>) -> Result<impl Responder
         time_partition_limit: stream_meta
             .time_partition_limit
             .map(|limit| limit.to_string()),
+        time_partition_format: stream_meta.time_partition_format.clone(),
         custom_partition: stream_meta.custom_partition.clone(),
         static_schema_flag: stream_meta.static_schema_flag,
         log_source: stream_meta.log_source.clone(),
         telemetry_type: stream_meta.telemetry_type,
+        sampling_rate: stream_meta.sampling_rate,
+        indexed_columns: stream_meta.indexed_columns.clone(),
+        reserved_field_policy: stream_meta.reserved_field_policy,
+        type_coercion_policy: stream_meta.type_coercion_policy,
+        type_widening_policy: stream_meta.type_widening_policy,
+        timestamp_policy: stream_meta.timestamp_policy.clone(),
+        row_group_size: stream_meta.row_group_size,
+        schema_frozen: stream_meta.schema_frozen,
+        numeric_downcast: stream_meta.numeric_downcast,
+        late_event_window: stream_meta.late_event_window.clone(),
+        read_only: stream_meta.read_only,
+        timestamp_column: stream_meta.timestamp_column.clone(),
+        column_aliases: stream_meta.column_aliases.clone(),
+        partition_expressions: stream_meta.partition_expressions.clone(),
+        transforms: stream_meta.transforms.clone(),
     };
 
     Ok((web::Json(stream_info), StatusCode::OK))
@@ -449,6 +2013,48 @@ pub async fn get_stream_hot_tier(stream_name: Path<String>) -> Result<impl Respo
     Ok((web::Json(meta), StatusCode::OK))
 }
 
+pub async fn sync_stream_hot_tier(
+    stream_name: Path<String>,
+) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+
+    if !PARSEABLE.check_or_load_stream(&stream_name).await {
+        return Err(StreamNotFound(stream_name).into());
+    }
+
+    let Some(hot_tier_manager) = HotTierManager::global() else {
+        return Err(StreamError::HotTierNotEnabled(stream_name));
+    };
+    if !hot_tier_manager.trigger_sync(&stream_name).await? {
+        return Err(StreamError::Custom {
+            msg: format!("hot tier sync already in progress for stream {stream_name}"),
+            status: StatusCode::CONFLICT,
+        });
+    }
+
+    Ok((
+        format!("hot tier sync started for stream {stream_name}"),
+        StatusCode::ACCEPTED,
+    ))
+}
+
+pub async fn get_stream_hot_tier_status(
+    stream_name: Path<String>,
+) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+
+    if !PARSEABLE.check_or_load_stream(&stream_name).await {
+        return Err(StreamNotFound(stream_name).into());
+    }
+
+    let Some(hot_tier_manager) = HotTierManager::global() else {
+        return Err(StreamError::HotTierNotEnabled(stream_name));
+    };
+    let status = hot_tier_manager.get_sync_status(&stream_name).await?;
+
+    Ok((web::Json(status), StatusCode::OK))
+}
+
 pub async fn delete_stream_hot_tier(
     stream_name: Path<String>,
 ) -> Result<impl Responder, StreamError> {
@@ -566,6 +2172,8 @@ pub mod error {
         InvalidAlertMessage(String, String),
         #[error("failed to set retention configuration due to err: {0}")]
         InvalidRetentionConfig(serde_json::Error),
+        #[error("failed to set masking configuration due to err: {0}")]
+        InvalidMaskingConfig(String),
         #[error("{msg}")]
         Custom { msg: String, status: StatusCode },
         #[error("Error: {0}")]
@@ -613,6 +2221,7 @@ pub mod error {
                 StreamError::InvalidAlert(_) => StatusCode::BAD_REQUEST,
                 StreamError::InvalidAlertMessage(_, _) => StatusCode::BAD_REQUEST,
                 StreamError::InvalidRetentionConfig(_) => StatusCode::BAD_REQUEST,
+                StreamError::InvalidMaskingConfig(_) => StatusCode::BAD_REQUEST,
                 StreamError::SerdeError(_) => StatusCode::BAD_REQUEST,
                 StreamError::Anyhow(_) => StatusCode::INTERNAL_SERVER_ERROR,
                 StreamError::Network(err) => {
@@ -646,6 +2255,9 @@ mod tests {
     use crate::{
         event::format::LogSource, handlers::http::modal::utils::logstream_utils::PutStreamHeaders,
     };
+    use arrow_schema::{DataType, Field, Schema};
+
+    use super::{DiffField, TypeMismatch, diff_schemas};
     use actix_web::test::TestRequest;
 
     // TODO: Fix this test with routes
@@ -705,4 +2317,42 @@ mod tests {
             LogSource::Custom(src) if src == "teststream"
         );
     }
+
+    #[test]
+    fn diff_schemas_by_field_name() {
+        let a = Schema::new(vec![
+            Field::new("host", DataType::Utf8, true),
+            Field::new("status", DataType::Int64, true),
+            Field::new("trace_id", DataType::Utf8, true),
+        ]);
+        let b = Schema::new(vec![
+            Field::new("status", DataType::Utf8, true),
+            Field::new("host", DataType::Utf8, false),
+            Field::new("span_id", DataType::Utf8, true),
+        ]);
+
+        let diff = diff_schemas(&a, &b);
+        assert_eq!(
+            diff.only_in_a,
+            vec![DiffField {
+                name: "trace_id".to_owned(),
+                data_type: DataType::Utf8
+            }]
+        );
+        assert_eq!(
+            diff.only_in_b,
+            vec![DiffField {
+                name: "span_id".to_owned(),
+                data_type: DataType::Utf8
+            }]
+        );
+        assert_eq!(
+            diff.type_mismatches,
+            vec![TypeMismatch {
+                name: "status".to_owned(),
+                type_in_a: DataType::Int64,
+                type_in_b: DataType::Utf8
+            }]
+        );
+    }
 }
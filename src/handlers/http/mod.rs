@@ -17,20 +17,22 @@
  */
 
 use actix_cors::Cors;
-use actix_web::Responder;
+use actix_web::{Responder, web};
 use arrow_schema::Schema;
 use cluster::get_node_info;
 use http::StatusCode;
 use modal::{NodeMetadata, NodeType};
 use serde_json::Value;
 
-use crate::{INTRA_CLUSTER_CLIENT, parseable::PARSEABLE};
+use crate::{INTRA_CLUSTER_CLIENT, parseable::PARSEABLE, storage::type_widening::merge_schemas};
 
 use self::query::Query;
 
 pub mod about;
+pub mod access_log;
 pub mod alerts;
 pub mod cluster;
+pub mod compression;
 pub mod correlation;
 pub mod demo_data;
 pub mod health_check;
@@ -41,12 +43,15 @@ pub mod logstream;
 pub mod middleware;
 pub mod modal;
 pub mod oidc;
+pub mod pagination;
 pub mod prism_home;
 pub mod prism_logstream;
 pub mod query;
 pub mod rbac;
+pub mod replay;
 pub mod resource_check;
 pub mod role;
+pub mod tail;
 pub mod targets;
 pub mod users;
 pub const MAX_EVENT_PAYLOAD_SIZE: usize = 10485760;
@@ -54,6 +59,11 @@ pub const API_BASE_PATH: &str = "api";
 pub const API_VERSION: &str = "v1";
 pub const PRISM_BASE_PATH: &str = "prism";
 
+/// Json config of the endpoints updating metadata, bodies above the configured size are rejected with 413
+pub fn metadata_json_config() -> web::JsonConfig {
+    web::JsonConfig::default().limit(PARSEABLE.options.max_metadata_payload_size as usize * 1024)
+}
+
 pub fn base_path() -> String {
     format!("/{API_BASE_PATH}/{API_VERSION}")
 }
@@ -67,7 +77,19 @@ pub fn metrics_path() -> String {
 }
 
 pub(crate) fn cross_origin_config() -> Cors {
-    if !PARSEABLE.options.cors || cfg!(debug_assertions) {
+    let allowed_origins = &PARSEABLE.options.cors_allowed_origins;
+    if allowed_origins.iter().any(|origin| origin == "*") {
+        Cors::permissive()
+    } else if !allowed_origins.is_empty() {
+        allowed_origins
+            .iter()
+            .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+            .allow_any_method()
+            .allow_any_header()
+            .expose_any_header()
+            .supports_credentials()
+            .max_age(3600)
+    } else if !PARSEABLE.options.cors || cfg!(debug_assertions) {
         Cors::permissive().block_on_origin_mismatch(false)
     } else {
         Cors::default().block_on_origin_mismatch(false)
@@ -90,7 +112,7 @@ pub fn base_path_without_preceding_slash() -> String {
 pub async fn fetch_schema(stream_name: &str) -> anyhow::Result<arrow_schema::Schema> {
     let res: Vec<Schema> = PARSEABLE.metastore.get_all_schemas(stream_name).await?;
 
-    let new_schema = Schema::try_merge(res)?;
+    let new_schema = merge_schemas(res)?;
     Ok(new_schema)
 }
 
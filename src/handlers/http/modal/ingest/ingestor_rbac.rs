@@ -28,7 +28,7 @@ use crate::{
     },
     rbac::{
         Users,
-        map::roles,
+        map::{mut_sessions, roles},
         user::{self, User as ParseableUser},
     },
     storage,
@@ -198,3 +198,10 @@ pub async fn post_gen_password(username: web::Path<String>) -> Result<HttpRespon
     Users.change_password_hash(&username, &new_hash);
     Ok(HttpResponse::Ok().status(StatusCode::OK).finish())
 }
+
+// Handler for POST /api/v1/user/sessions/rotate/sync
+// Revokes the sessions of all the users, the querier records the rotation
+pub async fn rotate_sessions() -> Result<HttpResponse, RBACError> {
+    mut_sessions().remove_all();
+    Ok(HttpResponse::Ok().status(StatusCode::OK).finish())
+}
@@ -37,9 +37,9 @@ use crate::{
     handlers::{
         airplane,
         http::{
-            base_path, ingest, logstream,
+            MAX_EVENT_PAYLOAD_SIZE, base_path, ingest, logstream, metadata_json_config,
             middleware::{DisAllowRootUser, RouteExt},
-            resource_check, role,
+            replay, resource_check, role, tail,
         },
     },
     migration,
@@ -71,7 +71,9 @@ impl ParseableServer for IngestServer {
                         resource_check::check_resource_utilization_middleware,
                     )))
                     .service(Self::logstream_api())
+                    .service(Server::get_staging_query_factory())
                     .service(Server::get_about_factory())
+                    .service(Server::get_config_factory())
                     .service(Self::analytics_factory())
                     .service(Server::get_liveness_factory())
                     .service(Self::get_user_webscope())
@@ -173,6 +175,7 @@ impl IngestServer {
             )
             .service(
                 web::resource("/{name}/sync")
+                    .app_data(metadata_json_config())
                     .route(web::put().to(ingestor_role::put).authorize(Action::PutRole)),
             )
     }
@@ -195,6 +198,14 @@ impl IngestServer {
                     )
                     .wrap(DisAllowRootUser),
             )
+            .service(
+                // POST /user/sessions/rotate/sync => Sync the revocation of all the sessions
+                web::resource("/sessions/rotate/sync").route(
+                    web::post()
+                        .to(ingestor_rbac::rotate_sessions)
+                        .authorize(Action::RotateSessions),
+                ),
+            )
             .service(
                 web::resource("/{userid}/role/sync/add")
                     // PATCH /user/{userid}/role/sync/add => Add roles to a user
@@ -241,6 +252,27 @@ impl IngestServer {
                             resource_check::check_resource_utilization_middleware,
                         )),
                 )
+                .service(
+                    // GET "/logstream/{logstream}/tail" ==> Stream the events ingested into given logstream
+                    web::resource("/tail").route(
+                        web::get()
+                            .to(tail::tail)
+                            .authorize_for_resource(Action::Query),
+                    ),
+                )
+                .service(
+                    // POST "/logstream/{logstream}/replay" ==> Re-ingest an export of given logstream
+                    web::resource("/replay")
+                        .route(
+                            web::post()
+                                .to(replay::replay)
+                                .authorize_for_resource(Action::Ingest),
+                        )
+                        .wrap(from_fn(
+                            resource_check::check_resource_utilization_middleware,
+                        ))
+                        .app_data(web::PayloadConfig::default().limit(MAX_EVENT_PAYLOAD_SIZE)),
+                )
                 .service(
                     web::resource("/sync")
                         // DELETE "/logstream/{logstream}/sync" ==> Sync deletion of a log stream
@@ -272,6 +304,15 @@ impl IngestServer {
                             .authorize_for_resource(Action::GetStats),
                     ),
                 )
+                .configure(|config| Server::configure_stream_settings(config, false))
+                .service(
+                    // POST "/logstream/{logstream}/rebuild-manifest" ==> Rebuild manifest of the files uploaded by this ingestor
+                    web::resource("/rebuild-manifest").route(
+                        web::post()
+                            .to(logstream::rebuild_manifest)
+                            .authorize_for_resource(Action::RebuildManifest),
+                    ),
+                )
                 .service(
                     web::scope("/retention").service(
                         web::resource("/cleanup").route(
@@ -20,7 +20,7 @@ use std::{fmt, path::Path, sync::Arc};
 
 use actix_web::{
     App, HttpServer,
-    middleware::from_fn,
+    middleware::{Condition, from_fn},
     web::{self, ServiceConfig},
 };
 use actix_web_prometheus::PrometheusMetrics;
@@ -33,7 +33,7 @@ use openid::Discovered;
 use relative_path::RelativePathBuf;
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
-use ssl_acceptor::get_ssl_acceptor;
+use ssl_acceptor::{extract_client_cert, get_ssl_acceptor};
 use tokio::sync::oneshot;
 use tracing::{error, info, warn};
 
@@ -44,14 +44,17 @@ use crate::{
     hottier::{HotTierManager, StreamHotTier},
     metastore::metastore_traits::MetastoreObject,
     oidc::Claims,
-    option::Mode,
+    option::{AccessLogFormat, Mode},
     parseable::PARSEABLE,
     storage::{ObjectStorageProvider, PARSEABLE_ROOT_DIRECTORY},
     users::{dashboards::DASHBOARDS, filters::FILTERS},
     utils::get_node_id,
 };
 
-use super::{API_BASE_PATH, API_VERSION, cross_origin_config, health_check, resource_check};
+use super::{
+    API_BASE_PATH, API_VERSION, access_log, compression, cross_origin_config, health_check,
+    resource_check,
+};
 
 pub mod ingest;
 pub mod ingest_server;
@@ -111,6 +114,7 @@ pub trait ParseableServer {
             &PARSEABLE.options.tls_cert_path,
             &PARSEABLE.options.tls_key_path,
             &PARSEABLE.options.trusted_ca_certs_path,
+            PARSEABLE.options.tls_require_client_cert,
         )?;
 
         // Start resource monitor
@@ -124,13 +128,20 @@ pub trait ParseableServer {
                 .wrap(prometheus.clone())
                 .configure(|config| Self::configure_routes(config))
                 .wrap(from_fn(health_check::check_shutdown_middleware))
-                .wrap(actix_web::middleware::Logger::default())
+                .wrap(from_fn(access_log::access_log_middleware))
+                .wrap(Condition::new(
+                    PARSEABLE.options.access_log_format == AccessLogFormat::Text,
+                    access_log::text_logger(),
+                ))
+                .wrap(from_fn(compression::excluded_content_types_middleware))
                 .wrap(actix_web::middleware::Compress::default())
+                .wrap(from_fn(compression::allowed_encodings_middleware))
                 .wrap(cross_origin_config())
         };
 
         // Create the HTTP server
         let http_server = HttpServer::new(create_app_fn)
+            .on_connect(extract_client_cert)
             .workers(num_cpus::get())
             .shutdown_timeout(60);
 
@@ -26,21 +26,22 @@ use actix_web::{
 use bytes::Bytes;
 use chrono::Utc;
 use http::StatusCode;
+use serde_json::json;
 use tokio::sync::Mutex;
 use tracing::{error, warn};
 
 pub static CREATE_STREAM_LOCK: Mutex<()> = Mutex::const_new(());
 
 use crate::{
+    alerts,
     handlers::{
         UPDATE_STREAM_KEY,
         http::{
-            base_path_without_preceding_slash,
             cluster::{
                 self, fetch_daily_stats, fetch_stats_from_ingestors, sync_streams_with_ingestors,
                 utils::{IngestionStats, QueriedStats, StorageStats, merge_queried_stats},
             },
-            logstream::error::StreamError,
+            logstream::{error::StreamError, get_stats_date_in_timezone, stats_timezone},
             modal::{NodeMetadata, NodeType},
         },
     },
@@ -92,15 +93,8 @@ pub async fn delete(stream_name: Path<String>) -> Result<impl Responder, StreamE
         })?;
 
     for ingestor in ingestor_metadata {
-        let url = format!(
-            "{}{}/logstream/{}/sync",
-            ingestor.domain_name,
-            base_path_without_preceding_slash(),
-            stream_name
-        );
-
         // delete the stream
-        cluster::send_stream_delete_request(&url, ingestor.clone()).await?;
+        cluster::send_stream_delete_request(&stream_name, ingestor).await?;
     }
 
     // Delete from memory
@@ -108,7 +102,21 @@ pub async fn delete(stream_name: Path<String>) -> Result<impl Responder, StreamE
     stats::delete_stats(&stream_name, "json")
         .unwrap_or_else(|e| warn!("failed to delete stats for stream {}: {:?}", stream_name, e));
 
-    Ok((format!("log stream {stream_name} deleted"), StatusCode::OK))
+    // alerts of the deleted stream would otherwise keep failing on every evaluation
+    let affected_alerts = alerts::handle_deleted_stream(&stream_name)
+        .await
+        .unwrap_or_else(|err| {
+            warn!("failed to stop the alerts of deleted stream {stream_name}: {err}");
+            Vec::new()
+        });
+
+    Ok((
+        web::Json(json!({
+            "message": format!("log stream {stream_name} deleted"),
+            "affectedAlerts": affected_alerts,
+        })),
+        StatusCode::OK,
+    ))
 }
 
 pub async fn put_stream(
@@ -163,6 +171,11 @@ pub async fn get_stats(
         })?;
 
         if !date_value.is_empty() {
+            if let Some(tz) = stats_timezone(&query_map)? {
+                let stats = get_stats_date_in_timezone(&stream_name, date_value, tz).await?;
+                return Ok(web::Json(serde_json::to_value(stats)?));
+            }
+
             let obs = PARSEABLE
                 .metastore
                 .get_all_stream_jsons(&stream_name, None)
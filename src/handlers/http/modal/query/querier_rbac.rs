@@ -23,15 +23,19 @@ use actix_web::{HttpResponse, Responder, web};
 use crate::{
     handlers::http::{
         cluster::{
-            sync_password_reset_with_ingestors, sync_user_creation_with_ingestors,
-            sync_user_deletion_with_ingestors, sync_users_with_roles_with_ingestors,
+            sync_password_reset_with_ingestors, sync_session_rotation_with_ingestors,
+            sync_user_creation_with_ingestors, sync_user_deletion_with_ingestors,
+            sync_users_with_roles_with_ingestors,
         },
         modal::utils::rbac_utils::{get_metadata, put_metadata},
-        rbac::{RBACError, UPDATE_LOCK},
+        rbac::{
+            RBACError, RoleAssignmentRequest, UPDATE_LOCK, assign_role_to_users,
+            record_sessions_rotation,
+        },
     },
     rbac::{
         Users,
-        map::{roles, users, write_user_groups},
+        map::{mut_sessions, roles, users, write_user_groups},
         user::{self, UserType},
     },
     validator,
@@ -207,6 +211,24 @@ pub async fn add_roles_to_user(
     Ok(format!("Roles updated successfully for {username}"))
 }
 
+// Handler POST /role/{name}/assign => Assign a role to several users at once
+pub async fn assign_role(
+    name: web::Path<String>,
+    web::Json(request): web::Json<RoleAssignmentRequest>,
+) -> Result<impl Responder, RBACError> {
+    let name = name.into_inner();
+
+    let _guard = UPDATE_LOCK.lock().await;
+    let (results, assigned) = assign_role_to_users(&name, request.usernames).await?;
+
+    let role = HashSet::from([name]);
+    for userid in &assigned {
+        sync_users_with_roles_with_ingestors(userid, &role, "add").await?;
+    }
+
+    Ok(web::Json(results))
+}
+
 // Handler PATCH /user/{userid}/role/remove => Remove roles from a user
 pub async fn remove_roles_from_user(
     userid: web::Path<String>,
@@ -307,3 +329,17 @@ pub async fn post_gen_password(username: web::Path<String>) -> Result<impl Respo
 
     Ok(new_password)
 }
+
+// Handler for POST /api/v1/user/sessions/rotate
+// Revokes the sessions of all the users on the querier and the ingestors
+pub async fn rotate_sessions() -> Result<impl Responder, RBACError> {
+    let rotated_at = record_sessions_rotation().await?;
+    let revoked = mut_sessions().remove_all();
+
+    sync_session_rotation_with_ingestors().await?;
+
+    Ok(web::Json(serde_json::json!({
+        "rotatedAt": rotated_at,
+        "revokedSessions": revoked,
+    })))
+}
@@ -19,14 +19,16 @@
 use std::sync::Arc;
 use std::thread;
 
+use crate::catalog::consolidation::init_snapshot_consolidation_scheduler;
 use crate::handlers::airplane;
 use crate::handlers::http::cluster;
 use crate::handlers::http::middleware::{DisAllowRootUser, RouteExt};
 use crate::handlers::http::modal::initialize_hot_tier_metadata_on_startup;
-use crate::handlers::http::{MAX_EVENT_PAYLOAD_SIZE, logstream};
+use crate::handlers::http::{MAX_EVENT_PAYLOAD_SIZE, logstream, metadata_json_config, query};
 use crate::handlers::http::{base_path, prism_base_path, resource_check};
 use crate::handlers::http::{rbac, role};
 use crate::hottier::HotTierManager;
+use crate::query::jobs::init_query_jobs_sweeper;
 use crate::rbac::role::Action;
 use crate::sync::sync_start;
 use crate::{analytics, migration, storage, sync};
@@ -57,9 +59,17 @@ impl ParseableServer for QueryServer {
                     .service(Server::get_query_factory().wrap(from_fn(
                         resource_check::check_resource_utilization_middleware,
                     )))
+                    .service(Server::get_scatter_gather_query_factory().wrap(from_fn(
+                        resource_check::check_resource_utilization_middleware,
+                    )))
+                    .service(Server::get_query_jobs_webscope().wrap(from_fn(
+                        resource_check::check_resource_utilization_middleware,
+                    )))
                     .service(Server::get_liveness_factory())
                     .service(Server::get_readiness_factory())
                     .service(Server::get_about_factory())
+                    .service(Server::get_analytics_preview_factory())
+                    .service(Server::get_config_factory())
                     .service(Self::get_logstream_webscope())
                     .service(Self::get_user_webscope())
                     .service(Server::get_users_webscope())
@@ -128,6 +138,9 @@ impl ParseableServer for QueryServer {
         if PARSEABLE.options.send_analytics {
             analytics::init_analytics_scheduler()?;
         }
+        init_query_jobs_sweeper();
+        init_snapshot_consolidation_scheduler();
+        cluster::reconcile::init_sync_reconciler();
 
         // local sync on init
         let startup_sync_handle = tokio::spawn(async {
@@ -177,10 +190,19 @@ impl QueryServer {
             .service(
                 // PUT, GET, DELETE Roles
                 resource("/{name}")
+                    .app_data(metadata_json_config())
                     .route(web::put().to(querier_role::put).authorize(Action::PutRole))
                     .route(web::delete().to(role::delete).authorize(Action::DeleteRole))
                     .route(web::get().to(role::get).authorize(Action::GetRole)),
             )
+            .service(
+                // POST /role/{name}/assign => Assign a role to several users
+                resource("/{name}/assign").route(
+                    web::post()
+                        .to(querier_rbac::assign_role)
+                        .authorize(Action::PutUserRoles),
+                ),
+            )
     }
 
     // get the user webscope
@@ -207,6 +229,14 @@ impl QueryServer {
                     )
                     .wrap(DisAllowRootUser),
             )
+            .service(
+                // POST /user/sessions/rotate => Revoke the sessions of all the users
+                web::resource("/sessions/rotate").route(
+                    web::post()
+                        .to(querier_rbac::rotate_sessions)
+                        .authorize(Action::RotateSessions),
+                ),
+            )
             .service(
                 web::resource("/{userid}/role").route(
                     web::get()
@@ -265,6 +295,14 @@ impl QueryServer {
                         ),
                 ),
             )
+            .service(
+                // GET "/logstream/diff?a={logstream}&b={logstream}" ==> Compare the schemas of two log streams
+                web::resource("/diff").route(
+                    web::get()
+                        .to(logstream::diff_stream_schemas)
+                        .authorize(Action::GetSchema),
+                ),
+            )
             .service(
                 web::scope("/{logstream}")
                     .service(
@@ -313,8 +351,33 @@ impl QueryServer {
                                 .authorize_for_resource(Action::GetStats),
                         ),
                     )
+                    .service(
+                        // GET "/logstream/{logstream}/manifest?date=YYYY-MM-DD" ==> Get manifests of given logstream for a date
+                        web::resource("/manifest").route(
+                            web::get()
+                                .to(logstream::get_manifest)
+                                .authorize_for_resource(Action::GetStats),
+                        ),
+                    )
+                    .service(
+                        // GET "/logstream/{logstream}/partition-stats?start=YYYY-MM-DD&end=YYYY-MM-DD" ==> Get file counts and sizes of the partitions of given logstream
+                        web::resource("/partition-stats").route(
+                            web::get()
+                                .to(logstream::get_partition_stats)
+                                .authorize_for_resource(Action::GetStats),
+                        ),
+                    )
+                    .service(
+                        // GET "/logstream/{logstream}/dependents" ==> Get the alerts and correlations querying given logstream
+                        web::resource("/dependents").route(
+                            web::get()
+                                .to(logstream::get_dependents)
+                                .authorize_for_resource(Action::GetStreamInfo),
+                        ),
+                    )
                     .service(
                         web::resource("/retention")
+                            .app_data(metadata_json_config())
                             // PUT "/logstream/{logstream}/retention" ==> Set retention for given logstream
                             .route(
                                 web::put()
@@ -328,8 +391,65 @@ impl QueryServer {
                                     .authorize_for_resource(Action::GetRetention),
                             ),
                     )
+                    .service(
+                        web::resource("/masking")
+                            // PUT "/logstream/{logstream}/masking" ==> Set masking for given logstream
+                            .route(
+                                web::put()
+                                    .to(logstream::put_masking)
+                                    .authorize_for_resource(Action::PutMasking),
+                            )
+                            // GET "/logstream/{logstream}/masking" ==> Get masking for given logstream
+                            .route(
+                                web::get()
+                                    .to(logstream::get_masking)
+                                    .authorize_for_resource(Action::GetMasking),
+                            ),
+                    )
+                    .configure(|config| Server::configure_stream_settings(config, true))
+                    .service(
+                        // GET "/logstream/{logstream}/preview" ==> Get the latest events of a log stream
+                        web::resource("/preview").route(
+                            web::get()
+                                .to(query::get_preview)
+                                .authorize_for_resource(Action::Query),
+                        ),
+                    )
+                    .service(
+                        // GET "/logstream/{logstream}/column/{column}/values" ==> Get distinct values and bounds of a column
+                        web::resource("/column/{column}/values").route(
+                            web::get()
+                                .to(query::get_column_values)
+                                .authorize_for_resource(Action::Query),
+                        ),
+                    )
+                    .service(
+                        // POST "/logstream/{logstream}/recompute-first-event" ==> Recompute first event timestamp of given logstream
+                        web::resource("/recompute-first-event").route(
+                            web::post()
+                                .to(logstream::recompute_first_event)
+                                .authorize_for_resource(Action::RecomputeFirstEvent),
+                        ),
+                    )
+                    .service(
+                        // POST "/logstream/{logstream}/rebuild-manifest?date=YYYY-MM-DD&dry_run=true" ==> Rebuild manifests of given logstream for a date from the stored parquet files
+                        web::resource("/rebuild-manifest").route(
+                            web::post()
+                                .to(logstream::rebuild_manifest)
+                                .authorize_for_resource(Action::RebuildManifest),
+                        ),
+                    )
+                    .service(
+                        // POST "/logstream/{logstream}/consolidate-snapshot" ==> Merge the snapshots of the ingestors of given logstream
+                        web::resource("/consolidate-snapshot").route(
+                            web::post()
+                                .to(logstream::consolidate_snapshot)
+                                .authorize_for_resource(Action::ConsolidateSnapshot),
+                        ),
+                    )
                     .service(
                         web::resource("/hottier")
+                            .app_data(metadata_json_config())
                             // PUT "/logstream/{logstream}/hottier" ==> Set hottier for given logstream
                             .route(
                                 web::put()
@@ -346,6 +466,22 @@ impl QueryServer {
                                     .to(logstream::delete_stream_hot_tier)
                                     .authorize_for_resource(Action::DeleteHotTierEnabled),
                             ),
+                    )
+                    .service(
+                        // POST "/logstream/{logstream}/hottier/sync" ==> Sync the hottier of given logstream now
+                        web::resource("/hottier/sync").route(
+                            web::post()
+                                .to(logstream::sync_stream_hot_tier)
+                                .authorize_for_resource(Action::PutHotTierEnabled),
+                        ),
+                    )
+                    .service(
+                        // GET "/logstream/{logstream}/hottier/status" ==> Get the hottier usage and sync progress of given logstream
+                        web::resource("/hottier/status").route(
+                            web::get()
+                                .to(logstream::get_stream_hot_tier_status)
+                                .authorize_for_resource(Action::GetHotTierEnabled),
+                        ),
                     ),
             )
     }
@@ -360,6 +496,22 @@ impl QueryServer {
                         .authorize(Action::ListCluster),
                 ),
             )
+            // GET "/cluster/overview" ==> Get the totals of the streams, the alerts and the node health of the cluster
+            .service(
+                web::resource("/overview").route(
+                    web::get()
+                        .to(cluster::get_cluster_overview)
+                        .authorize(Action::ListCluster),
+                ),
+            )
+            // GET "/cluster/pending-syncs" ==> Get the stream changes waiting for unreachable ingestors
+            .service(
+                web::resource("/pending-syncs").route(
+                    web::get()
+                        .to(cluster::get_pending_syncs)
+                        .authorize(Action::ListCluster),
+                ),
+            )
             // GET "/cluster/metrics" ==> Get metrics of the cluster
             .service(
                 web::resource("/metrics").route(
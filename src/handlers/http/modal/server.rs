@@ -52,11 +52,20 @@ use tokio::sync::oneshot;
 
 use crate::{
     handlers::http::{
-        self, MAX_EVENT_PAYLOAD_SIZE, ingest, llm, logstream,
+        self, MAX_EVENT_PAYLOAD_SIZE, ingest, llm,
+        logstream::{
+            self, IndexedColumnsConfig, LateEventWindowConfig, NumericDowncastConfig,
+            PartitionExpressionsConfig, ReadOnlyConfig, ReservedFieldPolicyConfig,
+            RowGroupSizeConfig, SamplingConfig, SchemaFreezeConfig, StreamSetting,
+            TimestampColumnConfig, TimestampPolicyConfig, TransformsConfig,
+            TypeCoercionPolicyConfig, TypeWideningPolicyConfig,
+        },
+        metadata_json_config,
         middleware::{DisAllowRootUser, RouteExt},
         oidc, role,
     },
     parseable::PARSEABLE,
+    query::jobs::init_query_jobs_sweeper,
     rbac::role::Action,
 };
 
@@ -78,12 +87,17 @@ impl ParseableServer for Server {
                     .service(Self::get_query_factory().wrap(from_fn(
                         resource_check::check_resource_utilization_middleware,
                     )))
+                    .service(Self::get_query_jobs_webscope().wrap(from_fn(
+                        resource_check::check_resource_utilization_middleware,
+                    )))
                     .service(Self::get_ingest_factory().wrap(from_fn(
                         resource_check::check_resource_utilization_middleware,
                     )))
                     .service(Self::get_liveness_factory())
                     .service(Self::get_readiness_factory())
                     .service(Self::get_about_factory())
+                    .service(Self::get_analytics_preview_factory())
+                    .service(Self::get_config_factory())
                     .service(Self::get_logstream_webscope())
                     .service(Self::get_user_webscope())
                     .service(Self::get_users_webscope())
@@ -158,6 +172,7 @@ impl ParseableServer for Server {
         if PARSEABLE.options.send_analytics {
             analytics::init_analytics_scheduler()?;
         }
+        init_query_jobs_sweeper();
 
         tokio::spawn(handlers::livetail::server());
         tokio::spawn(handlers::airplane::server());
@@ -249,6 +264,20 @@ impl Server {
                             .authorize(Action::DeleteCorrelation),
                     ),
             )
+            .service(
+                web::resource("/{correlation_id}/run").route(
+                    web::post()
+                        .to(http::correlation::run)
+                        .authorize(Action::GetCorrelation),
+                ),
+            )
+            .service(
+                web::resource("/{correlation_id}/cache").route(
+                    web::delete()
+                        .to(http::correlation::clear_cache)
+                        .authorize(Action::GetCorrelation),
+                ),
+            )
     }
 
     pub fn get_alerts_webscope() -> Scope {
@@ -256,7 +285,7 @@ impl Server {
             .service(
                 web::resource("")
                     .route(web::get().to(alerts::list).authorize(Action::GetAlert))
-                    .route(web::post().to(alerts::post).authorize(Action::PutAlert)),
+                    .route(web::post().to(alerts::post).authorize(Action::CreateAlert)),
             )
             .service(
                 web::resource("/list_tags").route(
@@ -265,8 +294,34 @@ impl Server {
                         .authorize(Action::ListDashboard),
                 ),
             )
+            .service(
+                web::resource("/scheduled").route(
+                    web::get()
+                        .to(alerts::list_scheduled)
+                        .authorize(Action::GetAlert),
+                ),
+            )
+            .service(
+                web::resource("/export")
+                    .route(web::get().to(alerts::export).authorize(Action::GetAlert)),
+            )
+            .service(
+                web::resource("/import").route(
+                    web::post()
+                        .to(alerts::import)
+                        .authorize(Action::CreateAlert),
+                ),
+            )
+            .service(
+                web::resource("/evaluate-batch").route(
+                    web::post()
+                        .to(alerts::evaluate_batch)
+                        .authorize(Action::PutAlertState),
+                ),
+            )
             .service(
                 web::resource("/{alert_id}")
+                    .app_data(metadata_json_config())
                     .route(web::get().to(alerts::get).authorize(Action::GetAlert))
                     .route(
                         web::put()
@@ -279,32 +334,46 @@ impl Server {
                             .authorize(Action::DeleteAlert),
                     ),
             )
+            .service(
+                web::resource("/{alert_id}/deliveries").route(
+                    web::get()
+                        .to(alerts::deliveries)
+                        .authorize(Action::GetAlert),
+                ),
+            )
             .service(
                 web::resource("/{alert_id}/disable").route(
                     web::patch()
                         .to(alerts::disable_alert)
-                        .authorize(Action::PutAlert),
+                        .authorize(Action::PutAlertState),
                 ),
             )
             .service(
                 web::resource("/{alert_id}/enable").route(
                     web::patch()
                         .to(alerts::enable_alert)
-                        .authorize(Action::PutAlert),
+                        .authorize(Action::PutAlertState),
                 ),
             )
             .service(
                 web::resource("/{alert_id}/update_notification_state").route(
                     web::patch()
                         .to(alerts::update_notification_state)
-                        .authorize(Action::PutAlert),
+                        .authorize(Action::PutAlertState),
                 ),
             )
             .service(
                 web::resource("/{alert_id}/evaluate_alert").route(
                     web::put()
                         .to(alerts::evaluate_alert)
-                        .authorize(Action::PutAlert),
+                        .authorize(Action::PutAlertState),
+                ),
+            )
+            .service(
+                web::resource("/{alert_id}/evaluate").route(
+                    web::post()
+                        .to(alerts::evaluate_now)
+                        .authorize(Action::PutAlertState),
                 ),
             )
     }
@@ -393,6 +462,14 @@ impl Server {
                     )
                     .route(web::get().to(filters::list).authorize(Action::ListFilter)),
             )
+            // GET "/filters/usage" ==> Get the age and last use of the filters
+            .service(
+                web::resource("/usage").route(
+                    web::get()
+                        .to(filters::list_usage)
+                        .authorize(Action::ListFilter),
+                ),
+            )
             .service(
                 web::resource("/{filter_id}")
                     .route(web::get().to(filters::get).authorize(Action::GetFilter))
@@ -418,6 +495,41 @@ impl Server {
         web::resource("/query").route(web::post().to(query::query).authorize(Action::Query))
     }
 
+    // POST "/query/async" ==> Submit a query job
+    // GET "/query/async/{id}" ==> Get the status and results of a query job
+    pub fn get_query_jobs_webscope() -> Scope {
+        web::scope("/query/async")
+            .service(
+                web::resource("").route(
+                    web::post()
+                        .to(query::submit_query_job)
+                        .authorize(Action::Query),
+                ),
+            )
+            .service(
+                web::resource("/{id}")
+                    .route(web::get().to(query::get_query_job).authorize(Action::Query)),
+            )
+    }
+
+    // POST "/query/scatter-gather" ==> Aggregate over the object store and the staging of every ingestor
+    pub fn get_scatter_gather_query_factory() -> Resource {
+        web::resource("/query/scatter-gather").route(
+            web::post()
+                .to(query::query_scatter_gather)
+                .authorize(Action::Query),
+        )
+    }
+
+    // POST "/query/staging" ==> Partial aggregates over the staging of an ingestor
+    pub fn get_staging_query_factory() -> Resource {
+        web::resource("/query/staging").route(
+            web::post()
+                .to(query::query_staging)
+                .authorize(Action::Query),
+        )
+    }
+
     // get the logstream web scope
     pub fn get_logstream_webscope() -> Scope {
         web::scope("/logstream")
@@ -437,6 +549,14 @@ impl Server {
                         ),
                 ),
             )
+            .service(
+                // GET "/logstream/diff?a={logstream}&b={logstream}" ==> Compare the schemas of two log streams
+                web::resource("/diff").route(
+                    web::get()
+                        .to(logstream::diff_stream_schemas)
+                        .authorize(Action::GetSchema),
+                ),
+            )
             .service(
                 web::scope("/{logstream}")
                     .service(
@@ -464,6 +584,27 @@ impl Server {
                             )
                             .app_data(web::JsonConfig::default().limit(MAX_EVENT_PAYLOAD_SIZE)),
                     )
+                    .service(
+                        // GET "/logstream/{logstream}/tail" ==> Stream the events ingested into given logstream
+                        web::resource("/tail").route(
+                            web::get()
+                                .to(http::tail::tail)
+                                .authorize_for_resource(Action::Query),
+                        ),
+                    )
+                    .service(
+                        // POST "/logstream/{logstream}/replay" ==> Re-ingest an export of given logstream
+                        web::resource("/replay")
+                            .route(
+                                web::post()
+                                    .to(http::replay::replay)
+                                    .authorize_for_resource(Action::Ingest)
+                                    .wrap(from_fn(
+                                        resource_check::check_resource_utilization_middleware,
+                                    )),
+                            )
+                            .app_data(web::PayloadConfig::default().limit(MAX_EVENT_PAYLOAD_SIZE)),
+                    )
                     .service(
                         // GET "/logstream/{logstream}/info" ==> Get info for given log stream
                         web::resource("/info").route(
@@ -488,8 +629,33 @@ impl Server {
                                 .authorize_for_resource(Action::GetStats),
                         ),
                     )
+                    .service(
+                        // GET "/logstream/{logstream}/manifest?date=YYYY-MM-DD" ==> Get manifests of given logstream for a date
+                        web::resource("/manifest").route(
+                            web::get()
+                                .to(logstream::get_manifest)
+                                .authorize_for_resource(Action::GetStats),
+                        ),
+                    )
+                    .service(
+                        // GET "/logstream/{logstream}/partition-stats?start=YYYY-MM-DD&end=YYYY-MM-DD" ==> Get file counts and sizes of the partitions of given logstream
+                        web::resource("/partition-stats").route(
+                            web::get()
+                                .to(logstream::get_partition_stats)
+                                .authorize_for_resource(Action::GetStats),
+                        ),
+                    )
+                    .service(
+                        // GET "/logstream/{logstream}/dependents" ==> Get the alerts and correlations querying given logstream
+                        web::resource("/dependents").route(
+                            web::get()
+                                .to(logstream::get_dependents)
+                                .authorize_for_resource(Action::GetStreamInfo),
+                        ),
+                    )
                     .service(
                         web::resource("/retention")
+                            .app_data(metadata_json_config())
                             // PUT "/logstream/{logstream}/retention" ==> Set retention for given logstream
                             .route(
                                 web::put()
@@ -503,8 +669,57 @@ impl Server {
                                     .authorize_for_resource(Action::GetRetention),
                             ),
                     )
+                    .service(
+                        web::resource("/masking")
+                            // PUT "/logstream/{logstream}/masking" ==> Set masking for given logstream
+                            .route(
+                                web::put()
+                                    .to(logstream::put_masking)
+                                    .authorize_for_resource(Action::PutMasking),
+                            )
+                            // GET "/logstream/{logstream}/masking" ==> Get masking for given logstream
+                            .route(
+                                web::get()
+                                    .to(logstream::get_masking)
+                                    .authorize_for_resource(Action::GetMasking),
+                            ),
+                    )
+                    .configure(|config| Self::configure_stream_settings(config, true))
+                    .service(
+                        // GET "/logstream/{logstream}/preview" ==> Get the latest events of a log stream
+                        web::resource("/preview").route(
+                            web::get()
+                                .to(query::get_preview)
+                                .authorize_for_resource(Action::Query),
+                        ),
+                    )
+                    .service(
+                        // GET "/logstream/{logstream}/column/{column}/values" ==> Get distinct values and bounds of a column
+                        web::resource("/column/{column}/values").route(
+                            web::get()
+                                .to(query::get_column_values)
+                                .authorize_for_resource(Action::Query),
+                        ),
+                    )
+                    .service(
+                        // POST "/logstream/{logstream}/recompute-first-event" ==> Recompute first event timestamp of given logstream
+                        web::resource("/recompute-first-event").route(
+                            web::post()
+                                .to(logstream::recompute_first_event)
+                                .authorize_for_resource(Action::RecomputeFirstEvent),
+                        ),
+                    )
+                    .service(
+                        // POST "/logstream/{logstream}/rebuild-manifest?date=YYYY-MM-DD&dry_run=true" ==> Rebuild manifests of given logstream for a date from the stored parquet files
+                        web::resource("/rebuild-manifest").route(
+                            web::post()
+                                .to(logstream::rebuild_manifest)
+                                .authorize_for_resource(Action::RebuildManifest),
+                        ),
+                    )
                     .service(
                         web::resource("/hottier")
+                            .app_data(metadata_json_config())
                             // PUT "/logstream/{logstream}/hottier" ==> Set hottier for given logstream
                             .route(
                                 web::put()
@@ -521,10 +736,72 @@ impl Server {
                                     .to(logstream::delete_stream_hot_tier)
                                     .authorize_for_resource(Action::DeleteHotTierEnabled),
                             ),
+                    )
+                    .service(
+                        // POST "/logstream/{logstream}/hottier/sync" ==> Sync the hottier of given logstream now
+                        web::resource("/hottier/sync").route(
+                            web::post()
+                                .to(logstream::sync_stream_hot_tier)
+                                .authorize_for_resource(Action::PutHotTierEnabled),
+                        ),
+                    )
+                    .service(
+                        // GET "/logstream/{logstream}/hottier/status" ==> Get the hottier usage and sync progress of given logstream
+                        web::resource("/hottier/status").route(
+                            web::get()
+                                .to(logstream::get_stream_hot_tier_status)
+                                .authorize_for_resource(Action::GetHotTierEnabled),
+                        ),
                     ),
             )
     }
 
+    /// Routes of the settings of a stream read and updated on their own, under
+    /// `/logstream/{logstream}`. Ingestors only receive the updates forwarded by the querier.
+    pub fn configure_stream_settings(config: &mut web::ServiceConfig, readable: bool) {
+        // PUT "/logstream/{logstream}/{setting}" ==> Set the setting of given logstream
+        // GET "/logstream/{logstream}/{setting}" ==> Get the setting of given logstream
+        fn setting<T: StreamSetting>(readable: bool) -> Resource {
+            let resource = web::resource(format!("/{}", T::PATH)).route(
+                web::put()
+                    .to(logstream::put_setting::<T>)
+                    .authorize_for_resource(T::PUT_ACTION),
+            );
+            if !readable {
+                return resource;
+            }
+            resource.route(
+                web::get()
+                    .to(logstream::get_setting::<T>)
+                    .authorize_for_resource(T::GET_ACTION),
+            )
+        }
+
+        config
+            .service(setting::<SamplingConfig>(readable))
+            .service(setting::<IndexedColumnsConfig>(readable))
+            .service(setting::<ReservedFieldPolicyConfig>(readable))
+            .service(setting::<TypeCoercionPolicyConfig>(readable))
+            .service(setting::<TypeWideningPolicyConfig>(readable))
+            .service(setting::<TimestampPolicyConfig>(readable))
+            .service(setting::<PartitionExpressionsConfig>(readable))
+            .service(setting::<TransformsConfig>(readable))
+            .service(setting::<RowGroupSizeConfig>(readable))
+            .service(setting::<NumericDowncastConfig>(readable))
+            .service(setting::<LateEventWindowConfig>(readable))
+            .service(setting::<SchemaFreezeConfig>(readable))
+            .service(setting::<ReadOnlyConfig>(readable))
+            .service(setting::<TimestampColumnConfig>(readable))
+            .service(
+                // PUT "/logstream/{logstream}/schema/rename" ==> Rename a column of given logstream by adding an alias
+                web::resource("/schema/rename").route(
+                    web::put()
+                        .to(logstream::rename_column)
+                        .authorize_for_resource(Action::RenameColumn),
+                ),
+            );
+    }
+
     // get the factory for the ingest route
     pub fn get_ingest_factory() -> Resource {
         web::resource("/ingest")
@@ -597,10 +874,19 @@ impl Server {
             .service(
                 // PUT, GET, DELETE Roles
                 resource("/{name}")
+                    .app_data(metadata_json_config())
                     .route(web::put().to(role::put).authorize(Action::PutRole))
                     .route(web::delete().to(role::delete).authorize(Action::DeleteRole))
                     .route(web::get().to(role::get).authorize(Action::GetRole)),
             )
+            .service(
+                // POST /role/{name}/assign => Assign a role to several users
+                resource("/{name}/assign").route(
+                    web::post()
+                        .to(http::rbac::assign_role)
+                        .authorize(Action::PutUserRoles),
+                ),
+            )
     }
 
     // get the users webscope (for Prism only)
@@ -652,6 +938,14 @@ impl Server {
                     )
                     .wrap(DisAllowRootUser),
             )
+            .service(
+                // POST /user/sessions/rotate => Revoke the sessions of all the users
+                web::resource("/sessions/rotate").route(
+                    web::post()
+                        .to(http::rbac::rotate_sessions)
+                        .authorize(Action::RotateSessions),
+                ),
+            )
             .service(
                 web::resource("/{username}/role").route(
                     web::get()
@@ -725,6 +1019,21 @@ impl Server {
         web::resource("/about").route(web::get().to(about::about).authorize(Action::GetAbout))
     }
 
+    // GET "/about/config" ==> Get the configuration the server is running with, secrets redacted
+    pub fn get_config_factory() -> Resource {
+        web::resource("/about/config")
+            .route(web::get().to(about::config).authorize(Action::GetConfig))
+    }
+
+    // GET "/about/analytics-preview" ==> Get the anonymous usage data as it would be sent
+    pub fn get_analytics_preview_factory() -> Resource {
+        web::resource("/about/analytics-preview").route(
+            web::get()
+                .to(about::analytics_preview)
+                .authorize(Action::GetAbout),
+        )
+    }
+
     // GET "/" ==> Serve the static frontend directory
     pub fn get_generated() -> ResourceFiles {
         ResourceFiles::new("/", generate()).resolve_not_found_to_root()
@@ -17,21 +17,45 @@
  */
 
 use std::{
+    any::Any,
     fs::{self, File},
     io::BufReader,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
-use rustls::ServerConfig;
+use actix_tls::accept::rustls_0_22::TlsStream;
+use actix_web::{dev::Extensions, rt::net::TcpStream};
+use rustls::{RootCertStore, ServerConfig, server::WebPkiClientVerifier};
+use x509_parser::parse_x509_certificate;
+
+/// Identity of the client behind a TLS connection, as proven by its verified certificate
+#[derive(Debug, Clone)]
+pub struct ClientCertIdentity {
+    pub common_name: String,
+}
 
 pub fn get_ssl_acceptor(
     tls_cert: &Option<PathBuf>,
     tls_key: &Option<PathBuf>,
     other_certs: &Option<PathBuf>,
+    require_client_cert: bool,
 ) -> anyhow::Result<Option<ServerConfig>> {
     match (tls_cert, tls_key) {
         (Some(cert), Some(key)) => {
-            let server_config = ServerConfig::builder().with_no_client_auth();
+            let server_config = if require_client_cert {
+                let ca_dir = other_certs.as_ref().ok_or(anyhow::anyhow!(
+                    "Trusted CA certificates are required to verify client certificates."
+                ))?;
+                // Unauthenticated clients are let through the handshake so that
+                // they are answered with a 401 instead of a TLS alert
+                let verifier = WebPkiClientVerifier::builder(Arc::new(trusted_roots(ca_dir)?))
+                    .allow_unauthenticated()
+                    .build()?;
+                ServerConfig::builder().with_client_cert_verifier(verifier)
+            } else {
+                ServerConfig::builder().with_no_client_auth()
+            };
 
             let cert_file = &mut BufReader::new(File::open(cert)?);
             let key_file = &mut BufReader::new(File::open(key)?);
@@ -60,3 +84,102 @@ pub fn get_ssl_acceptor(
         (_, _) => Ok(None),
     }
 }
+
+/// Loads all certificates in the trusted CA directory as roots for client certificate verification
+fn trusted_roots(ca_dir: &Path) -> anyhow::Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for entry in fs::read_dir(ca_dir)? {
+        let path = entry?.path();
+
+        if path.is_file() {
+            let ca_file = &mut BufReader::new(File::open(&path)?);
+            for cert in rustls_pemfile::certs(ca_file) {
+                roots.add(cert?)?;
+            }
+        }
+    }
+
+    if roots.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No trusted CA certificates found in {}",
+            ca_dir.display()
+        ));
+    }
+
+    Ok(roots)
+}
+
+/// Connection hook that records the identity of clients which presented a verified certificate,
+/// the certificate chain has already been checked against the trusted CAs during the handshake
+pub fn extract_client_cert(conn: &dyn Any, ext: &mut Extensions) {
+    let Some(tls) = conn.downcast_ref::<TlsStream<TcpStream>>() else {
+        return;
+    };
+    let (_, session) = tls.get_ref();
+    if let Some(common_name) = session
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .and_then(|cert| subject_common_name(cert))
+    {
+        ext.insert(ClientCertIdentity { common_name });
+    }
+}
+
+/// Extracts the subject's common name from a DER encoded X.509 certificate
+fn subject_common_name(cert: &[u8]) -> Option<String> {
+    let (_, cert) = parse_x509_certificate(cert).ok()?;
+    let common_name = cert.subject().iter_common_name().next()?;
+
+    common_name.as_str().ok().map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Issued by `CN=parseable-ca` to `O=parseable, CN=ingestor`
+    const INGESTOR_CERT: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBgjCCASigAwIBAgIUOVT2xmDtUlPETbSvJ3DnrrrMUR8wCgYIKoZIzj0EAwIw
+FzEVMBMGA1UEAwwMcGFyc2VhYmxlLWNhMB4XDTI2MTAxNjE0MzI1MVoXDTM2MTAx
+MzE0MzI1MVowJzESMBAGA1UECgwJcGFyc2VhYmxlMREwDwYDVQQDDAhpbmdlc3Rv
+cjBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABJ4y7cwA2GgM7t6Y/+wpjESfJnFf
+EQN7BaCrjztMCXEi8Xup6mZT4CG63gz1FCczvZDKbSJYqkxuK+UlGL2tY5yjQjBA
+MB0GA1UdDgQWBBTqUjbC+Bc0YEyxx4fJ9j23u3/zTDAfBgNVHSMEGDAWgBTZxvgZ
+OLAbMkyQS6O73DKM4Y9JpTAKBggqhkjOPQQDAgNIADBFAiEAhm5WNSKBXkTyfhWq
+N2RTrqUSRpG8P/Hhl+JMFRSloAkCIHd4DZZt7zLK6PRmMn/17JdJgKTne+HM6FKT
+/AYuUL0Z
+-----END CERTIFICATE-----
+";
+
+    /// Issued by `CN=parseable-ca` to `O=parseable`
+    const CERT_WITHOUT_COMMON_NAME: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBbzCCARWgAwIBAgIUOVT2xmDtUlPETbSvJ3DnrrrMUSAwCgYIKoZIzj0EAwIw
+FzEVMBMGA1UEAwwMcGFyc2VhYmxlLWNhMB4XDTI2MTAxNjE0MzI1MVoXDTM2MTAx
+MzE0MzI1MVowFDESMBAGA1UECgwJcGFyc2VhYmxlMFkwEwYHKoZIzj0CAQYIKoZI
+zj0DAQcDQgAEnjLtzADYaAzu3pj/7CmMRJ8mcV8RA3sFoKuPO0wJcSLxe6nqZlPg
+IbreDPUUJzO9kMptIliqTG4r5SUYva1jnKNCMEAwHQYDVR0OBBYEFOpSNsL4FzRg
+TLHHh8n2Pbe7f/NMMB8GA1UdIwQYMBaAFNnG+Bk4sBsyTJBLo7vcMozhj0mlMAoG
+CCqGSM49BAMCA0gAMEUCIQCSLSYJm6HoiqeYplzFgO/o21hzku9LxERun6c7qU1E
++AIgD0+BYEY3fUpQ37HtxHzznc1jaQU4Li7N3MaFk0ZujqU=
+-----END CERTIFICATE-----
+";
+
+    fn der(pem: &str) -> Vec<u8> {
+        rustls_pemfile::certs(&mut pem.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap()
+            .to_vec()
+    }
+
+    #[test]
+    fn reads_subject_common_name() {
+        let cert = der(INGESTOR_CERT);
+
+        assert_eq!(subject_common_name(&cert).as_deref(), Some("ingestor"));
+        assert_eq!(subject_common_name(&cert[..cert.len() / 2]), None);
+        assert_eq!(subject_common_name(&der(CERT_WITHOUT_COMMON_NAME)), None);
+    }
+}
@@ -17,34 +17,50 @@
  */
 
 use actix_web::HttpRequest;
+use arrow_schema::{DataType, Field};
 use chrono::Utc;
 use http::header::USER_AGENT;
 use opentelemetry_proto::tonic::{
     logs::v1::LogsData, metrics::v1::MetricsData, trace::v1::TracesData,
 };
+use serde::Serialize;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::sync::Arc;
 use tracing::warn;
 
 use crate::{
     event::{
-        FORMAT_KEY, SOURCE_IP_KEY, USER_AGENT_KEY,
+        self, DEFAULT_TIMESTAMP_KEY, Event, FORMAT_KEY, SOURCE_IP_KEY, USER_AGENT_KEY,
         format::{EventFormat, LogSource, json},
     },
     handlers::{
-        EXTRACT_LOG_KEY, LOG_SOURCE_KEY, STREAM_NAME_HEADER_KEY,
+        EXTRACT_LOG_KEY, INGEST_MODE_KEY, LOG_SOURCE_KEY, STREAM_NAME_HEADER_KEY,
         http::{
             ingest::PostError,
             kinesis::{Message, flatten_kinesis_logs},
         },
     },
+    metrics::{LATE_EVENTS, TIMESTAMP_FALLBACKS, TRANSFORM_ERRORS, VALUES_COERCED},
     otel::{logs::flatten_otel_logs, metrics::flatten_otel_metrics, traces::flatten_otel_traces},
     parseable::PARSEABLE,
-    storage::StreamType,
-    utils::json::{convert_array_to_object, flatten::convert_to_array},
+    storage::{
+        StreamType, late_events::LateEvent, partition_expression::derive_partition_values,
+        sampling::SAMPLING_RATE_KEY, timestamp_policy::TimestampPolicy,
+        transforms::apply_transforms,
+    },
+    utils::json::{
+        convert_array_to_object,
+        flatten::{convert_to_array, normalize_time_partition},
+    },
 };
 
-const IGNORE_HEADERS: [&str; 3] = [STREAM_NAME_HEADER_KEY, LOG_SOURCE_KEY, EXTRACT_LOG_KEY];
+const IGNORE_HEADERS: [&str; 4] = [
+    STREAM_NAME_HEADER_KEY,
+    LOG_SOURCE_KEY,
+    EXTRACT_LOG_KEY,
+    INGEST_MODE_KEY,
+];
 const MAX_CUSTOM_FIELDS: usize = 10;
 const MAX_FIELD_VALUE_LENGTH: usize = 100;
 
@@ -55,6 +71,7 @@ pub async fn flatten_and_push_logs(
     p_custom_fields: &HashMap<String, String>,
     time_partition: Option<String>,
 ) -> Result<(), PostError> {
+    verify_stream_writable(stream_name)?;
     // Verify the dataset fields count
     verify_dataset_fields_count(stream_name)?;
 
@@ -130,6 +147,105 @@ pub async fn flatten_and_push_logs(
     Ok(())
 }
 
+/// Outcome of ingesting an array of events where each event is accepted or rejected on its own
+#[derive(Debug, Default, Serialize)]
+pub struct PartialIngestion {
+    pub accepted: usize,
+    pub rejected: Vec<RejectedEvent>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RejectedEvent {
+    pub index: usize,
+    pub error: String,
+}
+
+/// Ingests each event of an array independently, so that malformed events
+/// don't fail the rest of the batch. Other payloads are ingested as a whole.
+pub async fn flatten_and_push_logs_partially(
+    json: Value,
+    stream_name: &str,
+    log_source: &LogSource,
+    p_custom_fields: &HashMap<String, String>,
+) -> Result<PartialIngestion, PostError> {
+    let events = match json {
+        Value::Array(events)
+            if !matches!(
+                log_source,
+                LogSource::Kinesis
+                    | LogSource::OtelLogs
+                    | LogSource::OtelMetrics
+                    | LogSource::OtelTraces
+            ) =>
+        {
+            events
+        }
+        json => {
+            flatten_and_push_logs(json, stream_name, log_source, p_custom_fields, None).await?;
+            return Ok(PartialIngestion {
+                accepted: 1,
+                rejected: vec![],
+            });
+        }
+    };
+    verify_stream_writable(stream_name)?;
+    verify_dataset_fields_count(stream_name)?;
+
+    // each event is validated and built on its own, the accepted ones are then processed
+    // together, so that nothing is pushed for the rejected ones nor pushed twice
+    let mut result = PartialIngestion::default();
+    let mut accepted = PreparedEvents::default();
+    for (index, event) in events.into_iter().enumerate() {
+        match prepare_events(stream_name, event, log_source, p_custom_fields, None, false) {
+            Ok(events) => {
+                accepted.extend(events);
+                result.accepted += 1;
+            }
+            Err(err) => result.rejected.push(RejectedEvent {
+                index,
+                error: err.to_string(),
+            }),
+        }
+    }
+    accepted.process(stream_name)?;
+
+    Ok(result)
+}
+
+/// Ingests events exported from a stream one by one, each keeping the `p_timestamp` it was
+/// exported with. Events which couldn't be decoded are rejected with their error.
+pub async fn push_replayed_logs(
+    events: Vec<Result<Value, String>>,
+    stream_name: &str,
+    p_custom_fields: &HashMap<String, String>,
+) -> Result<PartialIngestion, PostError> {
+    verify_stream_writable(stream_name)?;
+    verify_dataset_fields_count(stream_name)?;
+
+    let mut result = PartialIngestion::default();
+    for (index, event) in events.into_iter().enumerate() {
+        let pushed = match event {
+            Ok(event) => ingest_logs(
+                stream_name,
+                event,
+                &LogSource::Json,
+                p_custom_fields,
+                None,
+                true,
+            )
+            .await
+            .map_err(|err| err.to_string()),
+            Err(err) => Err(err),
+        };
+        match pushed {
+            Ok(()) => result.accepted += 1,
+            Err(error) => result.rejected.push(RejectedEvent { index, error }),
+        }
+    }
+
+    Ok(result)
+}
+
 pub async fn push_logs(
     stream_name: &str,
     json: Value,
@@ -137,6 +253,82 @@ pub async fn push_logs(
     p_custom_fields: &HashMap<String, String>,
     time_partition: Option<String>,
 ) -> Result<(), PostError> {
+    ingest_logs(
+        stream_name,
+        json,
+        log_source,
+        p_custom_fields,
+        time_partition,
+        false,
+    )
+    .await
+}
+
+/// Ingests the events, replayed events keep their `p_timestamp` and have the columns added on
+/// ingestion dropped, they are added anew
+async fn ingest_logs(
+    stream_name: &str,
+    json: Value,
+    log_source: &LogSource,
+    p_custom_fields: &HashMap<String, String>,
+    time_partition: Option<String>,
+    replayed: bool,
+) -> Result<(), PostError> {
+    // the events are only processed once all of them are accepted, for a rejected event
+    // to fail the request without any of the others being ingested
+    let events = prepare_events(
+        stream_name,
+        json,
+        log_source,
+        p_custom_fields,
+        time_partition,
+        replayed,
+    )?;
+    events.process(stream_name)
+}
+
+/// Events validated and built, processed once all of them are accepted
+#[derive(Default)]
+struct PreparedEvents {
+    events: Vec<Event>,
+    /// integer columns of the stream the events were built with as Float64
+    widened: Vec<String>,
+}
+
+impl PreparedEvents {
+    fn extend(&mut self, other: Self) {
+        self.events.extend(other.events);
+        for column in other.widened {
+            if !self.widened.contains(&column) {
+                self.widened.push(column);
+            }
+        }
+    }
+
+    fn process(self, stream_name: &str) -> Result<(), PostError> {
+        // widening finishes the staged files, so it is left until the whole request is accepted
+        if !self.widened.is_empty() {
+            PARSEABLE
+                .get_stream(stream_name)?
+                .widen_columns(&self.widened);
+        }
+        for event in event::coalesce(self.events) {
+            event.process()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Validates the events and builds them, without pushing any of them
+fn prepare_events(
+    stream_name: &str,
+    json: Value,
+    log_source: &LogSource,
+    p_custom_fields: &HashMap<String, String>,
+    time_partition: Option<String>,
+    replayed: bool,
+) -> Result<PreparedEvents, PostError> {
     let stream = PARSEABLE.get_stream(stream_name)?;
     let time_partition_limit = PARSEABLE
         .get_stream(stream_name)?
@@ -146,33 +338,187 @@ pub async fn push_logs(
     let schema_version = stream.get_schema_version();
     let p_timestamp = Utc::now();
 
-    let data = convert_array_to_object(
+    // drop events for sampled streams, the ones kept are tagged with the rate they were sampled at
+    let mut sampled_custom_fields;
+    let (mut json, p_custom_fields) = match stream.get_sampling_rate() {
+        Some(rate) => {
+            let Some(json) = rate.sample(json) else {
+                return Ok(PreparedEvents::default());
+            };
+            sampled_custom_fields = p_custom_fields.clone();
+            sampled_custom_fields.insert(SAMPLING_RATE_KEY.to_owned(), rate.to_string());
+            (json, &sampled_custom_fields)
+        }
+        None => (json, p_custom_fields),
+    };
+
+    // timestamps in a custom format are rewritten as RFC3339 before they are validated,
+    // events whose time partition is missing or doesn't match the format are rejected
+    let time_partition = match stream.get_time_partition_format() {
+        Some(time_partition_format) => {
+            let time_partition = time_partition.or_else(|| stream.get_time_partition());
+            if let Some(time_partition) = &time_partition {
+                normalize_time_partition(&mut json, time_partition, &time_partition_format)
+                    .map_err(|err| PostError::Invalid(err.into()))?;
+            }
+            time_partition
+        }
+        None => time_partition,
+    };
+
+    // derived partition keys are only added to the events once flattened, so they are validated then
+    let partition_expressions = stream.get_partition_expressions();
+    let raw_custom_partition = custom_partition
+        .as_ref()
+        .map(|custom_partition| {
+            custom_partition
+                .split(',')
+                .filter(|key| !partition_expressions.contains_key(key.trim()))
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .filter(|custom_partition| !custom_partition.is_empty());
+
+    let mut data = convert_array_to_object(
         json,
         time_partition.as_ref(),
         time_partition_limit,
-        custom_partition.as_ref(),
+        raw_custom_partition.as_ref(),
         schema_version,
         log_source,
     )?;
 
-    for json in data {
+    // the events are transformed once flattened, so their schema is inferred from the transformed fields
+    let transforms = stream.get_transforms();
+    if !transforms.is_empty() {
+        let errors: u64 = data
+            .iter_mut()
+            .map(|json| apply_transforms(json, &transforms))
+            .sum();
+        if errors > 0 {
+            TRANSFORM_ERRORS
+                .with_label_values(&[stream_name])
+                .inc_by(errors);
+        }
+    }
+
+    // fields named like the columns added on ingestion are kept, rejected or renamed, as per the stream
+    let reserved_field_policy = stream.get_reserved_field_policy();
+    let schema_frozen = stream.get_schema_frozen();
+    // replayed events are always late, they keep the partition of their event time
+    let late_event_window = stream.get_late_event_window().filter(|_| !replayed);
+    let type_coercion_policy = stream.get_type_coercion_policy();
+    let type_widening_policy = stream.get_type_widening_policy();
+    let reserved_fields = std::iter::once(DEFAULT_TIMESTAMP_KEY)
+        .chain(p_custom_fields.keys().map(String::as_str))
+        .collect::<Vec<_>>();
+
+    // events get their p_timestamp from a field of theirs, as per the stream
+    let timestamp_policy = if replayed {
+        TimestampPolicy::Reject {
+            field: DEFAULT_TIMESTAMP_KEY.to_owned(),
+        }
+    } else {
+        stream.get_timestamp_policy()
+    };
+    let (data, fallbacks) = timestamp_policy
+        .assign(data, p_timestamp)
+        .map_err(|err| PostError::Invalid(anyhow::anyhow!(err)))?;
+    if fallbacks > 0 {
+        TIMESTAMP_FALLBACKS
+            .with_label_values(&[stream_name])
+            .inc_by(fallbacks);
+    }
+
+    let ingested_at = p_timestamp;
+    let mut events = Vec::with_capacity(data.len());
+    let mut schema = stream.get_schema_raw();
+    let mut widened = vec![];
+    for (mut json, p_timestamp) in data {
+        if replayed && let Some(event) = json.as_object_mut() {
+            for field in &reserved_fields {
+                event.remove(*field);
+            }
+        }
+        reserved_field_policy
+            .apply(&mut json, &reserved_fields)
+            .map_err(|err| PostError::Invalid(anyhow::anyhow!(err)))?;
         let origin_size = serde_json::to_vec(&json).unwrap().len() as u64; // string length need not be the same as byte length
-        let schema = PARSEABLE.get_stream(stream_name)?.get_schema_raw();
-        json::Event { json, p_timestamp }
-            .into_event(
-                stream_name.to_owned(),
-                origin_size,
-                &schema,
-                static_schema_flag,
-                custom_partition.as_ref(),
-                time_partition.as_ref(),
-                schema_version,
-                StreamType::UserDefined,
-                p_custom_fields,
-            )?
-            .process()?;
+        // values conflicting with the type of their column are converted, as per the stream
+        let mut coerced = type_coercion_policy.apply(&mut json, &schema);
+        // fractional numbers sent for integer columns widen them, or are rejected or truncated.
+        // The columns are widened for the events of the request, the stream's once they are accepted
+        let conflicts = type_widening_policy
+            .check(&json, &schema)
+            .map_err(|err| PostError::Invalid(anyhow::anyhow!(err)))?;
+        for column in conflicts {
+            if let Some(field) = schema.get_mut(&column) {
+                *field = Arc::new(field.as_ref().clone().with_data_type(DataType::Float64));
+            }
+            widened.push(column);
+        }
+        coerced += type_widening_policy.apply(&mut json, &schema);
+        if coerced > 0 {
+            VALUES_COERCED
+                .with_label_values(&[stream_name])
+                .inc_by(coerced);
+        }
+        // events of frozen streams only carry known fields, so the schema is never inferred for them
+        if schema_frozen && let Some(field) = unknown_field(&json, &schema) {
+            return Err(PostError::Invalid(anyhow::anyhow!(
+                "field {field} is not part of the frozen schema of stream {stream_name}"
+            )));
+        }
+        derive_partition_values(&mut json, &partition_expressions)
+            .map_err(|err| PostError::Invalid(err.into()))?;
+        let mut event = json::Event { json, p_timestamp }.into_event(
+            stream_name.to_owned(),
+            origin_size,
+            &schema,
+            static_schema_flag,
+            custom_partition.as_ref(),
+            time_partition.as_ref(),
+            schema_version,
+            StreamType::UserDefined,
+            p_custom_fields,
+        )?;
+        // events behind the partition being written go to the partition of their event time,
+        // or past the window, to the partition being written
+        if let Some(window) = &late_event_window {
+            let late_event = window.route(event.parsed_timestamp, ingested_at);
+            if late_event != LateEvent::OnTime {
+                LATE_EVENTS
+                    .with_label_values(&[stream_name, late_event.label()])
+                    .inc();
+            }
+            match late_event {
+                LateEvent::OnTime | LateEvent::Routed => {}
+                LateEvent::Bucketed => event.parsed_timestamp = ingested_at.naive_utc(),
+                LateEvent::Rejected => {
+                    return Err(PostError::Invalid(anyhow::anyhow!(
+                        "event time {} is more than {} behind, events further behind are rejected by stream {stream_name}",
+                        event.parsed_timestamp,
+                        humantime::format_duration(window.window)
+                    )));
+                }
+            }
+        }
+        events.push(event);
+    }
+
+    Ok(PreparedEvents { events, widened })
+}
+
+/// Returns the first field of the event(s) missing from `schema`
+fn unknown_field<'a>(json: &'a Value, schema: &HashMap<String, Arc<Field>>) -> Option<&'a str> {
+    match json {
+        Value::Array(events) => events.iter().find_map(|event| unknown_field(event, schema)),
+        Value::Object(event) => event
+            .keys()
+            .find(|field| !schema.contains_key(field.as_str()))
+            .map(String::as_str),
+        _ => None,
     }
-    Ok(())
 }
 
 pub fn get_custom_fields_from_header(req: &HttpRequest) -> HashMap<String, String> {
@@ -236,6 +582,14 @@ pub fn get_custom_fields_from_header(req: &HttpRequest) -> HashMap<String, Strin
     p_custom_fields
 }
 
+/// Rejects the ingestion into read-only streams
+fn verify_stream_writable(stream_name: &str) -> Result<(), PostError> {
+    if PARSEABLE.get_stream(stream_name)?.get_read_only() {
+        return Err(PostError::ReadOnlyStream(stream_name.to_owned()));
+    }
+    Ok(())
+}
+
 fn verify_dataset_fields_count(stream_name: &str) -> Result<(), PostError> {
     let fields_count = PARSEABLE
         .get_stream(stream_name)?
@@ -347,4 +701,24 @@ mod tests {
         assert_eq!(custom_fields.get(USER_AGENT_KEY).unwrap(), "");
         assert_eq!(custom_fields.get(SOURCE_IP_KEY).unwrap(), "");
     }
+
+    #[test]
+    fn test_unknown_field_of_frozen_schema() {
+        let schema = HashMap::from([(
+            "level".to_owned(),
+            Arc::new(Field::new("level", arrow_schema::DataType::Utf8, true)),
+        )]);
+
+        assert_eq!(
+            unknown_field(&serde_json::json!({"level": "info"}), &schema),
+            None
+        );
+        assert_eq!(
+            unknown_field(
+                &serde_json::json!([{"level": "info"}, {"level": "warn", "host": "a"}]),
+                &schema
+            ),
+            Some("host")
+        );
+    }
 }
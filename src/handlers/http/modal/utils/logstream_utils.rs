@@ -21,9 +21,9 @@ use actix_web::http::header::HeaderMap;
 use crate::{
     event::format::LogSource,
     handlers::{
-        CUSTOM_PARTITION_KEY, LOG_SOURCE_KEY, STATIC_SCHEMA_FLAG, STREAM_TYPE_KEY,
-        TELEMETRY_TYPE_KEY, TIME_PARTITION_KEY, TIME_PARTITION_LIMIT_KEY, TelemetryType,
-        UPDATE_STREAM_KEY,
+        CUSTOM_PARTITION_KEY, IF_NOT_EXISTS_KEY, LOG_SOURCE_KEY, STATIC_SCHEMA_FLAG,
+        STREAM_TYPE_KEY, TELEMETRY_TYPE_KEY, TIME_PARTITION_FORMAT_KEY, TIME_PARTITION_KEY,
+        TIME_PARTITION_LIMIT_KEY, TelemetryType, UPDATE_STREAM_KEY,
     },
     storage::StreamType,
 };
@@ -32,9 +32,12 @@ use crate::{
 pub struct PutStreamHeaders {
     pub time_partition: String,
     pub time_partition_limit: String,
+    pub time_partition_format: Option<String>,
     pub custom_partition: Option<String>,
     pub static_schema_flag: bool,
     pub update_stream_flag: bool,
+    /// creating a stream which exists with the same settings succeeds without changes
+    pub if_not_exists_flag: bool,
     pub stream_type: StreamType,
     pub log_source: LogSource,
     pub telemetry_type: TelemetryType,
@@ -51,6 +54,9 @@ impl From<&HeaderMap> for PutStreamHeaders {
                 .get(TIME_PARTITION_LIMIT_KEY)
                 .map_or("", |v| v.to_str().unwrap())
                 .to_string(),
+            time_partition_format: headers
+                .get(TIME_PARTITION_FORMAT_KEY)
+                .map(|v| v.to_str().unwrap().to_string()),
             custom_partition: headers
                 .get(CUSTOM_PARTITION_KEY)
                 .map(|v| v.to_str().unwrap().to_string()),
@@ -60,6 +66,9 @@ impl From<&HeaderMap> for PutStreamHeaders {
             update_stream_flag: headers
                 .get(UPDATE_STREAM_KEY)
                 .is_some_and(|v| v.to_str().unwrap() == "true"),
+            if_not_exists_flag: headers
+                .get(IF_NOT_EXISTS_KEY)
+                .is_some_and(|v| v.to_str().unwrap() == "true"),
             stream_type: headers
                 .get(STREAM_TYPE_KEY)
                 .map(|v| StreamType::from(v.to_str().unwrap()))
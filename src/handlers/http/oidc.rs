@@ -122,6 +122,8 @@ pub async fn login(
             }
             _ => Err(OIDCError::BadRequest("Bad Request".to_string())),
         },
+        // client certificates are presented on every request, nothing to exchange
+        SessionKey::ClientCert { .. } => Ok(redirect_to_client(query.redirect.as_str(), None)),
         // if it's a valid active session, just redirect back
         key @ SessionKey::SessionId(_) => {
             let resp = if Users.session_exists(&key) {
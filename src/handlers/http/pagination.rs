@@ -0,0 +1,119 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::collections::HashMap;
+
+use actix_web::web;
+use serde::Serialize;
+
+pub const MAX_LIMIT: usize = 1000;
+pub const DEFAULT_LIMIT: usize = 100;
+
+/// A page of a list, along with the number of items in the whole list
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+impl<T> Paginated<T> {
+    /// Pages `items`, which must already be filtered so that `total` counts the matching items only
+    pub fn new(items: Vec<T>, offset: usize, limit: usize) -> Self {
+        let total = items.len();
+        Self {
+            items: items.into_iter().skip(offset).take(limit).collect(),
+            total,
+            limit,
+            offset,
+        }
+    }
+}
+
+/// Paging requested with `?paginated=true&offset=&limit=`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageParams {
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl PageParams {
+    /// Returns `None` unless `paginated=true`, list endpoints then keep returning bare arrays
+    pub fn from_query_map(query_map: &HashMap<String, String>) -> Result<Option<Self>, String> {
+        if query_map.get("paginated").map(String::as_str) != Some("true") {
+            return Ok(None);
+        }
+
+        let offset = match query_map.get("offset") {
+            Some(offset) => offset
+                .parse()
+                .map_err(|_| "offset is not a valid number".to_string())?,
+            None => 0,
+        };
+        let limit = match query_map.get("limit") {
+            Some(limit) => limit
+                .parse()
+                .map_err(|_| "limit is not a valid number".to_string())?,
+            None => DEFAULT_LIMIT,
+        };
+        if limit == 0 || limit > MAX_LIMIT {
+            return Err(format!("limit should be between 1 and {MAX_LIMIT}"));
+        }
+
+        Ok(Some(Self { offset, limit }))
+    }
+
+    pub fn from_query_string(query: &str) -> Result<Option<Self>, String> {
+        let query_map = web::Query::<HashMap<String, String>>::from_query(query)
+            .map_err(|_| "malformed query parameters".to_string())?;
+        Self::from_query_map(&query_map)
+    }
+
+    pub fn paginate<T>(&self, items: Vec<T>) -> Paginated<T> {
+        Paginated::new(items, self.offset, self.limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn total_counts_filtered_items_before_paging() {
+        let matching = (0..25).filter(|i| i % 2 == 0).collect::<Vec<_>>();
+        let page = Paginated::new(matching, 10, 5);
+
+        assert_eq!(page.total, 13);
+        assert_eq!(page.items, vec![20, 22, 24]);
+        assert_eq!((page.offset, page.limit), (10, 5));
+    }
+
+    #[test]
+    fn paging_is_opt_in() {
+        assert_eq!(PageParams::from_query_string("limit=5").unwrap(), None);
+        assert_eq!(
+            PageParams::from_query_string("paginated=true&offset=5").unwrap(),
+            Some(PageParams {
+                offset: 5,
+                limit: DEFAULT_LIMIT
+            })
+        );
+        assert!(PageParams::from_query_string("paginated=true&limit=0").is_err());
+    }
+}
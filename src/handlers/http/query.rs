@@ -17,18 +17,22 @@
  */
 
 use crate::event::error::EventError;
+use crate::handlers::http::cluster::send_staging_query_to_ingestors;
 use crate::handlers::http::fetch_schema;
 use crate::metastore::MetastoreError;
 use crate::option::Mode;
 use crate::rbac::map::SessionKey;
-use crate::utils::arrow::record_batches_to_json;
+use crate::utils::arrow::{quote_identifier, record_batches_to_json};
 use actix_web::http::header::ContentType;
 use actix_web::web::{self, Json};
-use actix_web::{Either, FromRequest, HttpRequest, HttpResponse, Responder};
+use actix_web::{Either, FromRequest, HttpRequest, HttpResponse, HttpResponseBuilder, Responder};
 use arrow_array::RecordBatch;
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{ArrowError, DataType};
 use bytes::Bytes;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use datafusion::error::DataFusionError;
+use datafusion::execution::SendableRecordBatchStream;
 use datafusion::execution::context::SessionState;
 use datafusion::sql::sqlparser::parser::ParserError;
 use futures::stream::once;
@@ -43,28 +47,75 @@ use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Instant;
 use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
 use tracing::{error, warn};
+use ulid::Ulid;
 
+use crate::catalog::{self, column::TypedStatistics};
 use crate::event::{DEFAULT_TIMESTAMP_KEY, commit_schema};
-use crate::metrics::{QUERY_EXECUTE_TIME, increment_query_calls_by_date};
+use crate::metrics::{
+    QUERY_EXECUTE_TIME, QUERY_RESULT_CACHE_HITS, QUERY_RESULT_CACHE_MISSES,
+    increment_query_calls_by_date,
+};
 use crate::parseable::{PARSEABLE, StreamNotFound};
+use crate::query::column_aliases::resolve_column_aliases;
 use crate::query::error::ExecuteError;
-use crate::query::{CountsRequest, Query as LogicalQuery, execute};
-use crate::query::{QUERY_SESSION, resolve_stream_names};
+use crate::query::jobs::{JobsFull, QUERY_JOBS};
+use crate::query::result_cache::{CacheKey, QUERY_RESULT_CACHE};
+use crate::query::sampling::validate_sample_fraction;
+use crate::query::{
+    CountsRequest, Query as LogicalQuery, QuerySlot, acquire_query_slot, execute,
+    execute_with_cancellation,
+};
+use crate::query::{
+    NO_DATA_REASON, QUERY_SESSION, STAGING_QUERY_SESSION, ensure_streams_exist,
+    resolve_stream_names, scatter_gather, streams_without_data,
+};
 use crate::rbac::Users;
-use crate::response::QueryResponse;
+use crate::response::{NullMode, QueryResponse};
 use crate::storage::ObjectStorageError;
+use crate::storage::masking::{mask_logical_plan, masks_for_session};
 use crate::utils::actix::extract_session_key_from_req;
 use crate::utils::time::{TimeParseError, TimeRange};
-use crate::utils::user_auth_for_datasets;
+use crate::utils::{get_user_from_request, user_auth_for_datasets};
 
 pub const TIME_ELAPSED_HEADER: &str = "p-time-elapsed";
+pub const SAMPLE_FRACTION_HEADER: &str = "p-sample-fraction";
+/// Media type of the Arrow IPC streaming format
+const ARROW_STREAM_CONTENT_TYPE: &str = "application/vnd.apache.arrow.stream";
+
+/// Output format of streaming queries, selected with the `format` query parameter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum StreamingFormat {
+    /// one JSON array of records per line
+    #[default]
+    Json,
+    /// Arrow IPC stream of the record batches
+    Arrow,
+}
+
+impl StreamingFormat {
+    fn from_request(req: &HttpRequest) -> Result<Self, QueryError> {
+        let params = web::Query::<HashMap<String, String>>::from_query(req.query_string())
+            .map(|x| x.into_inner())
+            .unwrap_or_default();
+        match params.get("format").map(String::as_str) {
+            None | Some("json") => Ok(Self::Json),
+            Some("arrow") => Ok(Self::Arrow),
+            Some(_) => Err(QueryError::MalformedQuery(
+                "format should be either json or arrow",
+            )),
+        }
+    }
+}
 /// Query Request through http endpoint.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Query {
     pub query: String,
+    #[serde(default = "default_start_time")]
     pub start_time: String,
+    #[serde(default = "default_end_time")]
     pub end_time: String,
     #[serde(default)]
     pub send_null: bool,
@@ -74,6 +125,27 @@ pub struct Query {
     pub streaming: bool,
     #[serde(skip)]
     pub filter_tags: Option<Vec<String>>,
+    #[serde(skip)]
+    pub no_cache: bool,
+    /// read the streams as they were at this time, set with `?as_of=`
+    #[serde(skip)]
+    pub as_of: Option<DateTime<Utc>>,
+    /// how null values are rendered in the results, set with `?null_mode=`
+    #[serde(skip)]
+    pub null_mode: NullMode,
+    /// read this fraction of the data only for approximate results, set with `?sample=`
+    #[serde(skip)]
+    pub sample: Option<f64>,
+}
+
+/// Queries without a time range cover the last `--default-query-range`,
+/// so that a request can't scan the whole history of a stream by accident
+fn default_start_time() -> String {
+    PARSEABLE.options.default_query_range.clone()
+}
+
+fn default_end_time() -> String {
+    "now".to_owned()
 }
 
 /// A function to execute the query and fetch QueryResponse
@@ -89,13 +161,22 @@ pub async fn get_records_and_fields(
     let tables = resolve_stream_names(&query_request.query)?;
     //check or load streams in memory
     create_streams_for_distributed(tables.clone()).await?;
-
-    let query: LogicalQuery = into_query(query_request, &session_state, time_range).await?;
+    // deleted streams are reported as such rather than failing to plan the query
+    ensure_streams_exist(&tables).await?;
 
     let permissions = Users.get_permissions(creds);
 
     user_auth_for_datasets(&permissions, &tables).await?;
 
+    if streams_without_data(&tables).await {
+        return Ok((Some(vec![]), Some(vec![])));
+    }
+
+    let mut query: LogicalQuery = into_query(query_request, &session_state, time_range).await?;
+    // columns masked for the roles of the requesting user
+    let masks = masks_for_session(creds, &tables);
+    query.raw_logical_plan = mask_logical_plan(query.raw_logical_plan, &masks)?;
+
     let (records, fields) = execute(query, false).await?;
 
     let records = match records {
@@ -115,12 +196,28 @@ pub async fn query(req: HttpRequest, query_request: Query) -> Result<HttpRespons
     let tables = resolve_stream_names(&query_request.query)?;
     //check or load streams in memory
     create_streams_for_distributed(tables.clone()).await?;
+    // deleted streams are reported as such rather than failing to plan the query
+    ensure_streams_exist(&tables).await?;
 
-    let query: LogicalQuery = into_query(&query_request, &session_state, time_range).await?;
     let creds = extract_session_key_from_req(&req)?;
     let permissions = Users.get_permissions(&creds);
 
     user_auth_for_datasets(&permissions, &tables).await?;
+
+    // streams without any event have no columns to plan the query against
+    if streams_without_data(&tables).await {
+        return Ok(HttpResponse::Ok().json(json!({
+            "fields": [],
+            "records": [],
+            "rows": 0,
+            "reason": NO_DATA_REASON,
+        })));
+    }
+
+    let mut query: LogicalQuery = into_query(&query_request, &session_state, time_range).await?;
+    // columns masked for the roles of the requesting user, masked plans are cached apart
+    let masks = masks_for_session(&creds, &tables);
+    query.raw_logical_plan = mask_logical_plan(query.raw_logical_plan, &masks)?;
     let time = Instant::now();
 
     // Track billing metrics for query calls
@@ -130,22 +227,128 @@ pub async fn query(req: HttpRequest, query_request: Query) -> Result<HttpRespons
     // if the query is `select count(*) from <dataset>`
     // we use the `get_bin_density` method to get the count of records in the dataset
     // instead of executing the query using datafusion
-    if let Some(column_name) = query.is_logical_plan_count_without_filters() {
+    // counts come from the current snapshot, so past snapshots go through datafusion
+    if let Some(column_name) = query
+        .is_logical_plan_count_without_filters()
+        .filter(|_| query.as_of.is_none())
+    {
         let table = tables
             .first()
             .ok_or_else(|| QueryError::MalformedQuery("No table name found in query"))?;
         return handle_count_query(&query_request, table, column_name, time).await;
     }
 
+    // repeated non-streaming queries are served from the result cache,
+    // without waiting for an execution slot
+    let cache_key = if query_request.streaming || query_request.no_cache {
+        None
+    } else {
+        QUERY_RESULT_CACHE.key(&query, &tables)
+    };
+    if let Some(key) = &cache_key {
+        if let Some(cached) = QUERY_RESULT_CACHE.get(key) {
+            QUERY_RESULT_CACHE_HITS
+                .with_label_values(&[&tables[0]])
+                .inc();
+            return records_response(
+                cached.records.clone(),
+                cached.fields.clone(),
+                &tables[0],
+                &query_request,
+                time,
+            );
+        }
+        QUERY_RESULT_CACHE_MISSES
+            .with_label_values(&[&tables[0]])
+            .inc();
+    }
+
+    let format = StreamingFormat::from_request(&req)?;
+    if format == StreamingFormat::Arrow && !query_request.streaming {
+        return Err(QueryError::MalformedQuery(
+            "arrow format is only supported for streaming queries",
+        ));
+    }
+
+    // wait for an execution slot, rejects with 429 if the node stays at capacity
+    let slot = acquire_query_slot().await?;
+
     // if the query request has streaming = false (default)
     // we use datafusion's `execute` method to get the records
     if !query_request.streaming {
-        return handle_non_streaming_query(query, tables, &query_request, time).await;
+        let response =
+            handle_non_streaming_query(query, tables, &query_request, cache_key, time).await;
+        drop(slot);
+        return response;
     }
 
     // if the query request has streaming = true
     // we use datafusion's `execute_stream` method to get the records
-    handle_streaming_query(query, tables, &query_request, time).await
+    handle_streaming_query(query, tables, &query_request, format, slot, time).await
+}
+
+/// Submits a query for asynchronous execution and returns the id of its job.
+/// Jobs are held in memory on the node they were submitted to, until they expire after completing.
+pub async fn submit_query_job(
+    req: HttpRequest,
+    query_request: Query,
+) -> Result<impl Responder, QueryError> {
+    if query_request.streaming {
+        return Err(QueryError::MalformedQuery(
+            "streaming is not supported for query jobs",
+        ));
+    }
+    TimeRange::parse_human_time(&query_request.start_time, &query_request.end_time)?;
+    let creds = extract_session_key_from_req(&req)?;
+    let owner = get_user_from_request(&req).map_err(|_| QueryError::Unauthorized)?;
+
+    // reject malformed queries and unauthorized datasets before accepting the job
+    let tables = resolve_stream_names(&query_request.query)?;
+    create_streams_for_distributed(tables.clone()).await?;
+    // deleted streams are reported as such rather than failing to plan the query
+    ensure_streams_exist(&tables).await?;
+    let permissions = Users.get_permissions(&creds);
+    user_auth_for_datasets(&permissions, &tables).await?;
+
+    let id = QUERY_JOBS.submit(&owner)?;
+    tokio::spawn(async move {
+        let result = run_query_job(&query_request, &creds)
+            .await
+            .map_err(|err| err.to_string());
+        if let Err(err) = &result {
+            warn!("query job {id} failed: {err}");
+        }
+        QUERY_JOBS.finish(id, result);
+    });
+
+    Ok((web::Json(json!({ "id": id })), StatusCode::ACCEPTED))
+}
+
+async fn run_query_job(query_request: &Query, creds: &SessionKey) -> Result<Value, QueryError> {
+    let _slot = acquire_query_slot().await?;
+    let (records, fields) = get_records_and_fields(query_request, creds).await?;
+
+    QueryResponse {
+        records: records.unwrap_or_default(),
+        fields: fields.unwrap_or_default(),
+        null_mode: query_request.null_mode,
+        with_fields: query_request.fields,
+    }
+    .to_json()
+}
+
+/// Status of a query job, along with its results once completed
+pub async fn get_query_job(
+    req: HttpRequest,
+    id: web::Path<Ulid>,
+) -> Result<impl Responder, QueryError> {
+    let id = id.into_inner();
+    let owner = get_user_from_request(&req).map_err(|_| QueryError::Unauthorized)?;
+    let job = QUERY_JOBS
+        .get(id, &owner)
+        .ok_or(QueryError::JobNotFound(id))?;
+
+    Ok(web::Json(job))
 }
 
 /// Handles count queries (e.g., `SELECT COUNT(*) FROM <dataset-name>`)
@@ -208,6 +411,7 @@ async fn handle_count_query(
 /// - `query`: The logical query to execute.
 /// - `table_name`: The name of the table/dataset being queried.
 /// - `query_request`: The original query request from the client.
+/// - `cache_key`: The key to cache the results under, if caching applies.
 /// - `time`: The timer for measuring query execution time.
 ///
 /// # Returns
@@ -216,10 +420,20 @@ async fn handle_non_streaming_query(
     query: LogicalQuery,
     table_name: Vec<String>,
     query_request: &Query,
+    cache_key: Option<CacheKey>,
     time: Instant,
 ) -> Result<HttpResponse, QueryError> {
     let first_table_name = table_name[0].clone();
-    let (records, fields) = execute(query, query_request.streaming).await?;
+    // cancels the query if actix drops this future on client disconnect
+    let cancel_token = CancellationToken::new();
+    let _disconnect_guard = cancel_token.clone().drop_guard();
+    let (records, fields) = execute_with_cancellation(
+        query,
+        query_request.streaming,
+        first_table_name.clone(),
+        cancel_token,
+    )
+    .await?;
     let records = match records {
         Either::Left(rbs) => rbs,
         Either::Right(_) => {
@@ -228,28 +442,42 @@ async fn handle_non_streaming_query(
             ));
         }
     };
+    if let Some(key) = cache_key {
+        QUERY_RESULT_CACHE.insert(key, records.clone(), fields.clone());
+    }
+
+    records_response(records, fields, &first_table_name, query_request, time)
+}
+
+/// Serializes the records of a non-streaming query into the JSON response
+fn records_response(
+    records: Vec<RecordBatch>,
+    fields: Vec<String>,
+    table_name: &str,
+    query_request: &Query,
+    time: Instant,
+) -> Result<HttpResponse, QueryError> {
     let total_time = format!("{:?}", time.elapsed());
     let time = time.elapsed().as_secs_f64();
 
     QUERY_EXECUTE_TIME
-        .with_label_values(&[&first_table_name])
+        .with_label_values(&[table_name])
         .observe(time);
     let response = QueryResponse {
         records,
         fields,
-        fill_null: query_request.send_null,
+        null_mode: query_request.null_mode,
         with_fields: query_request.fields,
     }
     .to_json()?;
-    Ok(HttpResponse::Ok()
-        .insert_header((TIME_ELAPSED_HEADER, total_time.as_str()))
-        .json(response))
+    Ok(ok_response(query_request, &total_time).json(response))
 }
 
-/// Handles streaming queries, returning results as newline-delimited JSON (NDJSON).
+/// Handles streaming queries, returning results as newline-delimited JSON (NDJSON)
+/// or as an Arrow IPC stream.
 ///
 /// Executes the logical query using DataFusion's streaming execution. If the `fields`
-/// flag is set, the first chunk of the response contains the field names as a JSON object.
+/// flag is set, the first chunk of the JSON response contains the field names as a JSON object.
 /// Each subsequent chunk contains a record batch as a JSON object, separated by newlines.
 /// This allows clients to start processing results before the entire query completes.
 ///
@@ -257,18 +485,33 @@ async fn handle_non_streaming_query(
 /// - `query`: The logical query to execute.
 /// - `table_name`: The name of the table/dataset being queried.
 /// - `query_request`: The original query request from the client.
+/// - `format`: The format the record batches are serialized in.
+/// - `slot`: The execution slot, held until the response stream is dropped.
 /// - `time`: The timer for measuring query execution time.
 ///
 /// # Returns
-/// - `HttpResponse` streaming the query results as NDJSON, optionally prefixed with the fields array.
+/// - `HttpResponse` streaming the query results as NDJSON, optionally prefixed with the fields array,
+///   or as Arrow IPC messages.
 async fn handle_streaming_query(
     query: LogicalQuery,
     table_name: Vec<String>,
     query_request: &Query,
+    format: StreamingFormat,
+    slot: QuerySlot,
     time: Instant,
 ) -> Result<HttpResponse, QueryError> {
     let first_table_name = table_name[0].clone();
-    let (records_stream, fields) = execute(query, query_request.streaming).await?;
+    // cancels the query if actix drops this future on client disconnect,
+    // once the stream is returned dropping it already stops the execution
+    let cancel_token = CancellationToken::new();
+    let _disconnect_guard = cancel_token.clone().drop_guard();
+    let (records_stream, fields) = execute_with_cancellation(
+        query,
+        query_request.streaming,
+        first_table_name.clone(),
+        cancel_token,
+    )
+    .await?;
     let records_stream = match records_stream {
         Either::Left(_) => {
             return Err(QueryError::MalformedQuery(
@@ -283,9 +526,21 @@ async fn handle_streaming_query(
         .with_label_values(&[&first_table_name])
         .observe(time);
 
-    let send_null = query_request.send_null;
+    let null_mode = query_request.null_mode;
     let with_fields = query_request.fields;
 
+    if format == StreamingFormat::Arrow {
+        let stream = arrow_ipc_stream(records_stream)?;
+        // the execution slot is released only once the response stream is fully consumed or dropped
+        let stream = stream.map(move |chunk| {
+            let _ = &slot;
+            chunk
+        });
+        return Ok(ok_response(query_request, &total_time)
+            .content_type(ARROW_STREAM_CONTENT_TYPE)
+            .streaming(stream));
+    }
+
     let stream = if with_fields {
         // send the fields json as an initial chunk
         let fields_json = serde_json::json!({
@@ -294,7 +549,7 @@ async fn handle_streaming_query(
         .to_string();
 
         // stream the records without fields
-        let mut batch_processor = create_batch_processor(send_null);
+        let mut batch_processor = create_batch_processor(null_mode);
         let records_stream = records_stream.map(move |batch_result| {
             let batch_result = batch_result.map_err(QueryError::from);
             batch_processor(batch_result)
@@ -307,27 +562,78 @@ async fn handle_streaming_query(
         Box::pin(fields_chunk.chain(records_stream))
             as Pin<Box<dyn Stream<Item = Result<Bytes, actix_web::Error>>>>
     } else {
-        let mut batch_processor = create_batch_processor(send_null);
+        let mut batch_processor = create_batch_processor(null_mode);
         let stream = records_stream
             .map(move |batch_result| batch_processor(batch_result.map_err(QueryError::from)));
         Box::pin(stream) as Pin<Box<dyn Stream<Item = Result<Bytes, actix_web::Error>>>>
     };
 
-    Ok(HttpResponse::Ok()
+    // the execution slot is released only once the response stream is fully consumed or dropped
+    let stream = stream.map(move |chunk| {
+        let _ = &slot;
+        chunk
+    });
+
+    Ok(ok_response(query_request, &total_time)
         .content_type("application/x-ndjson")
-        .insert_header((TIME_ELAPSED_HEADER, total_time.as_str()))
         .streaming(stream))
 }
 
+/// Responses of sampled queries carry the sample fraction, their results are approximate
+fn ok_response(query_request: &Query, total_time: &str) -> HttpResponseBuilder {
+    let mut response = HttpResponse::Ok();
+    response.insert_header((TIME_ELAPSED_HEADER, total_time));
+    if let Some(fraction) = query_request.sample {
+        response.insert_header((SAMPLE_FRACTION_HEADER, fraction.to_string()));
+    }
+    response
+}
+
+/// Serializes the record batches into Arrow IPC messages as they are produced,
+/// the schema message comes first and the end of stream marker last
+fn arrow_ipc_stream(
+    records_stream: SendableRecordBatchStream,
+) -> Result<impl Stream<Item = Result<Bytes, actix_web::Error>>, QueryError> {
+    let mut writer = StreamWriter::try_new(Vec::new(), &records_stream.schema())?;
+    let schema_chunk = Bytes::from(std::mem::take(writer.get_mut()));
+
+    let batches = futures::stream::unfold(Some((records_stream, writer)), |state| async move {
+        let (mut records_stream, mut writer) = state?;
+        let Some(batch) = records_stream.next().await else {
+            let end = writer
+                .finish()
+                .map(|_| Bytes::from(std::mem::take(writer.get_mut())))
+                .map_err(actix_web::error::ErrorInternalServerError);
+            return Some((end, None));
+        };
+
+        let written = batch
+            .map_err(QueryError::from)
+            .and_then(|batch| writer.write(&batch).map_err(QueryError::from));
+        match written {
+            Ok(()) => {
+                let chunk = Bytes::from(std::mem::take(writer.get_mut()));
+                Some((Ok(chunk), Some((records_stream, writer))))
+            }
+            Err(e) => {
+                error!("Failed to write record batch as arrow IPC: {}", e);
+                Some((Err(actix_web::error::ErrorInternalServerError(e)), None))
+            }
+        }
+    });
+
+    Ok(once(future::ok(schema_chunk)).chain(batches))
+}
+
 fn create_batch_processor(
-    send_null: bool,
+    null_mode: NullMode,
 ) -> impl FnMut(Result<RecordBatch, QueryError>) -> Result<Bytes, actix_web::Error> {
     move |batch_result| match batch_result {
         Ok(batch) => {
             let response = QueryResponse {
                 records: vec![batch],
                 fields: Vec::new(),
-                fill_null: send_null,
+                null_mode,
                 with_fields: false,
             }
             .to_json()
@@ -360,7 +666,7 @@ pub async fn get_counts(
     if body.conditions.is_some() {
         let time_partition = PARSEABLE
             .get_stream(&body.stream)?
-            .get_time_partition()
+            .get_time_column()
             .unwrap_or_else(|| DEFAULT_TIMESTAMP_KEY.into());
 
         let sql = body.get_df_sql(time_partition).await?;
@@ -373,6 +679,10 @@ pub async fn get_counts(
             fields: true,
             streaming: false,
             filter_tags: None,
+            no_cache: false,
+            as_of: None,
+            null_mode: NullMode::default(),
+            sample: None,
         };
 
         let creds = extract_session_key_from_req(&req)?;
@@ -404,6 +714,200 @@ pub async fn get_counts(
     Ok(web::Json(res))
 }
 
+const DEFAULT_COLUMN_VALUES_LIMIT: usize = 100;
+const MAX_COLUMN_VALUES_LIMIT: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct ColumnValuesParams {
+    from: String,
+    to: String,
+    limit: Option<usize>,
+}
+
+/// Distinct values of a column over a time range, capped at `limit`,
+/// along with its min and max from the parquet statistics for numeric and time columns
+pub async fn get_column_values(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    params: web::Query<ColumnValuesParams>,
+) -> Result<impl Responder, QueryError> {
+    let (stream_name, column) = path.into_inner();
+    let ColumnValuesParams { from, to, limit } = params.into_inner();
+    let limit = limit
+        .unwrap_or(DEFAULT_COLUMN_VALUES_LIMIT)
+        .clamp(1, MAX_COLUMN_VALUES_LIMIT);
+
+    let creds = extract_session_key_from_req(&req)?;
+    let permissions = Users.get_permissions(&creds);
+    user_auth_for_datasets(&permissions, std::slice::from_ref(&stream_name)).await?;
+    create_streams_for_distributed(vec![stream_name.clone()]).await?;
+
+    let schema = PARSEABLE.get_stream(&stream_name)?.get_schema();
+    let Ok(field) = schema.field_with_name(&column) else {
+        return Err(QueryError::CustomError(format!(
+            "Column {column} is not part of the schema of log stream {stream_name}"
+        )));
+    };
+    let masks = masks_for_session(&creds, std::slice::from_ref(&stream_name));
+
+    // one more value than the limit is fetched to tell whether the values were truncated
+    let query_request = Query {
+        query: format!(
+            "SELECT DISTINCT {column} FROM {stream} WHERE {column} IS NOT NULL LIMIT {}",
+            limit + 1,
+            column = quote_identifier(&column),
+            stream = quote_identifier(&stream_name),
+        ),
+        start_time: from,
+        end_time: to,
+        send_null: false,
+        fields: false,
+        streaming: false,
+        filter_tags: None,
+        no_cache: true,
+        as_of: None,
+        null_mode: NullMode::default(),
+        sample: None,
+    };
+    let time_range =
+        TimeRange::parse_human_time(&query_request.start_time, &query_request.end_time)?;
+
+    let slot = acquire_query_slot().await?;
+    let (records, _) = get_records_and_fields(&query_request, &creds).await?;
+    drop(slot);
+
+    let records = records.unwrap_or_default();
+    let mut values = record_batches_to_json(&records)?
+        .into_iter()
+        .filter_map(|mut row| row.remove(&column))
+        .collect_vec();
+    let truncated = values.len() > limit;
+    values.truncate(limit);
+
+    // bounds of masked columns would leak the original values
+    let (min, max) = if masks.contains_key(&column) {
+        (Value::Null, Value::Null)
+    } else {
+        catalog::get_column_bounds(&stream_name, &column, &time_range)
+            .await?
+            .map_or((Value::Null, Value::Null), |bounds| {
+                column_bounds_to_json(bounds, field.data_type())
+            })
+    };
+
+    Ok(web::Json(json!({
+        "column": column,
+        "values": values,
+        "truncated": truncated,
+        "min": min,
+        "max": max,
+    })))
+}
+
+const DEFAULT_PREVIEW_LIMIT: usize = 100;
+const MAX_PREVIEW_LIMIT: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct PreviewParams {
+    limit: Option<usize>,
+    order: Option<String>,
+}
+
+/// The latest `limit` events of a stream, by the time column of the stream.
+///
+/// Only the files of the newest manifest entries holding that many events are read,
+/// the time range of the query is derived from their statistics.
+pub async fn get_preview(
+    req: HttpRequest,
+    stream_name: web::Path<String>,
+    params: web::Query<PreviewParams>,
+) -> Result<impl Responder, QueryError> {
+    let stream_name = stream_name.into_inner();
+    let PreviewParams { limit, order } = params.into_inner();
+    let limit = limit
+        .unwrap_or(DEFAULT_PREVIEW_LIMIT)
+        .clamp(1, MAX_PREVIEW_LIMIT);
+    let ascending = match order.as_deref() {
+        None | Some("desc") => false,
+        Some("asc") => true,
+        Some(_) => {
+            return Err(QueryError::MalformedQuery(
+                "order should be either asc or desc",
+            ));
+        }
+    };
+
+    let creds = extract_session_key_from_req(&req)?;
+    let permissions = Users.get_permissions(&creds);
+    user_auth_for_datasets(&permissions, std::slice::from_ref(&stream_name)).await?;
+    create_streams_for_distributed(vec![stream_name.clone()]).await?;
+
+    let time_column = PARSEABLE
+        .get_stream(&stream_name)?
+        .get_time_column()
+        .unwrap_or_else(|| DEFAULT_TIMESTAMP_KEY.to_owned());
+    let start = catalog::get_latest_events_start(&stream_name, &time_column, limit as u64)
+        .await?
+        .unwrap_or(DateTime::<Utc>::UNIX_EPOCH);
+
+    let query_request = Query {
+        query: format!(
+            "SELECT * FROM {stream} ORDER BY {column} DESC LIMIT {limit}",
+            stream = quote_identifier(&stream_name),
+            column = quote_identifier(&time_column),
+        ),
+        start_time: start.to_rfc3339(),
+        end_time: "now".to_owned(),
+        send_null: false,
+        fields: true,
+        streaming: false,
+        filter_tags: None,
+        no_cache: true,
+        as_of: None,
+        null_mode: NullMode::default(),
+        sample: None,
+    };
+
+    let slot = acquire_query_slot().await?;
+    let (records, fields) = get_records_and_fields(&query_request, &creds).await?;
+    drop(slot);
+
+    let mut records = records.unwrap_or_default();
+    if ascending {
+        records.reverse();
+        records = records.iter().map(crate::utils::arrow::reverse).collect();
+    }
+
+    QueryResponse {
+        records,
+        fields: fields.unwrap_or_default(),
+        null_mode: NullMode::Omit,
+        with_fields: true,
+    }
+    .to_json()
+    .map(web::Json)
+}
+
+// only numeric and time columns have meaningful bounds
+fn column_bounds_to_json(bounds: TypedStatistics, data_type: &DataType) -> (Value, Value) {
+    match (bounds, data_type) {
+        (TypedStatistics::Int(stats), DataType::Timestamp(_, _)) => {
+            let to_json = |millis| {
+                DateTime::from_timestamp_millis(millis)
+                    .map_or(Value::Null, |t| Value::String(t.to_rfc3339()))
+            };
+            (to_json(stats.min), to_json(stats.max))
+        }
+        (TypedStatistics::Int(stats), data_type) if data_type.is_integer() => {
+            (json!(stats.min), json!(stats.max))
+        }
+        (TypedStatistics::Float(stats), data_type) if data_type.is_floating() => {
+            (json!(stats.min), json!(stats.max))
+        }
+        _ => (Value::Null, Value::Null),
+    }
+}
+
 pub async fn update_schema_when_distributed(tables: &Vec<String>) -> Result<(), EventError> {
     // if the mode is query or prism, we need to update the schema in memory
     // no need to commit schema to storage
@@ -455,13 +959,45 @@ impl FromRequest for Query {
 
     fn from_request(req: &HttpRequest, payload: &mut actix_web::dev::Payload) -> Self::Future {
         let query = Json::<Query>::from_request(req, payload);
-        let params = web::Query::<HashMap<String, bool>>::from_request(req, payload)
-            .into_inner()
-            .map(|x| x.0)
+        let raw_params = web::Query::<HashMap<String, String>>::from_query(req.query_string())
+            .map(|x| x.into_inner())
             .unwrap_or_default();
+        let as_of = raw_params
+            .get("as_of")
+            .map(|as_of| {
+                DateTime::parse_from_rfc3339(as_of)
+                    .map(|as_of| as_of.with_timezone(&Utc))
+                    .map_err(|_| {
+                        actix_web::error::ErrorBadRequest("as_of should be an RFC 3339 timestamp")
+                    })
+            })
+            .transpose();
+        let null_mode = raw_params
+            .get("null_mode")
+            .map(|null_mode| null_mode.parse::<NullMode>())
+            .transpose()
+            .map_err(actix_web::error::ErrorBadRequest);
+        let sample = raw_params
+            .get("sample")
+            .map(|sample| {
+                sample
+                    .parse::<f64>()
+                    .map_err(|_| format!("sample should be a fraction, got {sample}"))
+                    .and_then(validate_sample_fraction)
+                    .map_err(actix_web::error::ErrorBadRequest)
+            })
+            .transpose();
+        // other non boolean params, like `format`, are parsed by the query handler
+        let params = raw_params
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, value.parse::<bool>().ok()?)))
+            .collect::<HashMap<_, _>>();
 
         let fut = async move {
             let mut query = query.await?.into_inner();
+            query.as_of = as_of?;
+            query.null_mode = null_mode?.unwrap_or_default();
+            query.sample = sample?;
             // format output json to include field names
             query.fields = params.get("fields").cloned().unwrap_or(false);
 
@@ -470,9 +1006,15 @@ impl FromRequest for Query {
             }
 
             if !query.streaming {
-                query.streaming = params.get("streaming").cloned().unwrap_or(false);
+                query.streaming = params
+                    .get("streaming")
+                    .or_else(|| params.get("stream"))
+                    .cloned()
+                    .unwrap_or(false);
             }
 
+            query.no_cache = params.get("no_cache").cloned().unwrap_or(false);
+
             Ok(query)
         };
 
@@ -480,6 +1022,102 @@ impl FromRequest for Query {
     }
 }
 
+/// Runs an aggregation over the object store here and over the staging of every live ingestor,
+/// then merges the partial aggregates, so that events not yet uploaded are accounted for
+pub async fn query_scatter_gather(
+    req: HttpRequest,
+    Json(mut query_request): Json<Query>,
+) -> Result<HttpResponse, QueryError> {
+    let time = Instant::now();
+    // the staging of the ingestors can't be sampled
+    if query_request.sample.is_some() {
+        return Err(QueryError::MalformedQuery(
+            "sampling is not supported for scatter-gather queries",
+        ));
+    }
+    let time_range =
+        TimeRange::parse_human_time(&query_request.start_time, &query_request.end_time)?;
+    let tables = resolve_stream_names(&query_request.query)?;
+    create_streams_for_distributed(tables.clone()).await?;
+    // deleted streams are reported as such rather than failing to plan the query
+    ensure_streams_exist(&tables).await?;
+
+    let creds = extract_session_key_from_req(&req)?;
+    let permissions = Users.get_permissions(&creds);
+    user_auth_for_datasets(&permissions, &tables).await?;
+    // masked values could leak through the group keys or min/max of the merged results
+    if !masks_for_session(&creds, &tables).is_empty() {
+        return Err(QueryError::Unauthorized);
+    }
+
+    let mut query = into_query(&query_request, &QUERY_SESSION.state(), time_range).await?;
+    let (partial, merge_plan) =
+        scatter_gather::partial_plan(&query.raw_logical_plan).map_err(QueryError::CustomError)?;
+    query.raw_logical_plan = partial;
+
+    // every node aggregates over the same absolute time range
+    query_request.start_time = query.time_range.start.to_rfc3339();
+    query_request.end_time = query.time_range.end.to_rfc3339();
+
+    let _slot = acquire_query_slot().await?;
+    let (local, remote) = future::join(
+        execute(query, false),
+        send_staging_query_to_ingestors(&query_request),
+    )
+    .await;
+    let mut partials = match local?.0 {
+        Either::Left(records) => record_batches_to_json(&records)?,
+        Either::Right(_) => unreachable!("non-streaming query returns record batches"),
+    };
+    partials.extend(remote?);
+
+    let records = scatter_gather::merge(&merge_plan, partials).map_err(QueryError::CustomError)?;
+    let time = time.elapsed().as_secs_f64();
+    QUERY_EXECUTE_TIME
+        .with_label_values(&[&tables[0]])
+        .observe(time);
+
+    Ok(HttpResponse::Ok()
+        .insert_header((TIME_ELAPSED_HEADER, time.to_string()))
+        .json(json!({
+            "fields": merge_plan.fields(),
+            "records": records,
+        })))
+}
+
+/// Computes the partial aggregates of a scatter-gather query over the staging of this ingestor
+pub async fn query_staging(
+    req: HttpRequest,
+    Json(query_request): Json<Query>,
+) -> Result<impl Responder, QueryError> {
+    let tables = resolve_stream_names(&query_request.query)?;
+    // the same checks as on the querier, the endpoint can be called directly
+    let creds = extract_session_key_from_req(&req)?;
+    let permissions = Users.get_permissions(&creds);
+    user_auth_for_datasets(&permissions, &tables).await?;
+    if !masks_for_session(&creds, &tables).is_empty() {
+        return Err(QueryError::Unauthorized);
+    }
+
+    // streams this ingestor hasn't received events for have nothing in staging
+    if tables
+        .iter()
+        .any(|table| !PARSEABLE.streams.contains(table))
+    {
+        return Ok(web::Json(vec![]));
+    }
+
+    let time_range =
+        TimeRange::parse_human_time(&query_request.start_time, &query_request.end_time)?;
+    let mut query = into_query(&query_request, &STAGING_QUERY_SESSION.state(), time_range).await?;
+    let (partial, _) =
+        scatter_gather::partial_plan(&query.raw_logical_plan).map_err(QueryError::CustomError)?;
+    query.raw_logical_plan = partial;
+
+    let records = query.execute_on_staging().await?;
+    Ok(web::Json(record_batches_to_json(&records)?))
+}
+
 pub async fn into_query(
     query: &Query,
     session_state: &SessionState,
@@ -496,15 +1134,42 @@ pub async fn into_query(
     if query.end_time.is_empty() {
         return Err(QueryError::EmptyEndTime);
     }
-    let raw_logical_plan = session_state.create_logical_plan(&query.query).await?;
+    if let Some(as_of) = query.as_of {
+        validate_as_of(as_of)?;
+    }
+    let sql = resolve_column_aliases(&query.query)?;
+    let raw_logical_plan = session_state.create_logical_plan(&sql).await?;
 
     Ok(crate::query::Query {
         raw_logical_plan,
         time_range,
         filter_tag: query.filter_tags.clone(),
+        as_of: query.as_of,
+        sample: query.sample,
     })
 }
 
+// streams can only be read as of a time within the tracked snapshot history
+fn validate_as_of(as_of: DateTime<Utc>) -> Result<(), QueryError> {
+    let Some(history_days) = PARSEABLE.options.snapshot_history_days else {
+        return Err(QueryError::AsOf(
+            "querying past snapshots is disabled, set P_SNAPSHOT_HISTORY_DAYS to enable it"
+                .to_owned(),
+        ));
+    };
+    let now = Utc::now();
+    if as_of > now {
+        return Err(QueryError::AsOf("as_of can't be in the future".to_owned()));
+    }
+    if as_of < now - TimeDelta::days(history_days as i64) {
+        return Err(QueryError::AsOf(format!(
+            "as_of is older than the {history_days} days of snapshot history"
+        )));
+    }
+
+    Ok(())
+}
+
 /// unused for now, might need it in the future
 #[allow(unused)]
 fn transform_query_for_ingestor(query: &Query) -> Option<Query> {
@@ -538,6 +1203,10 @@ fn transform_query_for_ingestor(query: &Query) -> Option<Query> {
         start_time: start_time.to_rfc3339(),
         end_time: end_time.to_rfc3339(),
         streaming: query.streaming,
+        no_cache: query.no_cache,
+        as_of: query.as_of,
+        null_mode: query.null_mode,
+        sample: query.sample,
     };
 
     Some(q)
@@ -587,19 +1256,48 @@ Description: {0}"#
     ParserError(#[from] ParserError),
     #[error(transparent)]
     MetastoreError(#[from] MetastoreError),
+    #[error("Arrow Error: {0}")]
+    Arrow(#[from] ArrowError),
+    #[error("Too many concurrent queries on this node, retry after {0} seconds")]
+    TooManyQueries(u64),
+    #[error("{0}")]
+    JobsFull(#[from] JobsFull),
+    #[error("Query job {0} not found")]
+    JobNotFound(Ulid),
+    #[error("{0}")]
+    AsOf(String),
 }
 
 impl actix_web::ResponseError for QueryError {
     fn status_code(&self) -> http::StatusCode {
         match self {
-            QueryError::Execute(_) | QueryError::JsonParse(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            QueryError::Execute(ExecuteError::DeadlineExceeded(_)) => StatusCode::GATEWAY_TIMEOUT,
+            QueryError::Execute(_) | QueryError::JsonParse(_) | QueryError::Arrow(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
             QueryError::MetastoreError(e) => e.status_code(),
+            QueryError::TooManyQueries(_) | QueryError::JobsFull(_) => {
+                StatusCode::TOO_MANY_REQUESTS
+            }
+            QueryError::JobNotFound(_) | QueryError::StreamNotFound(_) => StatusCode::NOT_FOUND,
             _ => StatusCode::BAD_REQUEST,
         }
     }
 
     fn error_response(&self) -> actix_web::HttpResponse<actix_web::body::BoxBody> {
-        actix_web::HttpResponse::build(self.status_code())
+        let mut response = actix_web::HttpResponse::build(self.status_code());
+        if let QueryError::TooManyQueries(retry_after) = self {
+            response.insert_header((http::header::RETRY_AFTER, retry_after.to_string()));
+        }
+        // clients tell a stream removed since the query was saved from a malformed query
+        if let QueryError::StreamNotFound(StreamNotFound(stream)) = self {
+            return response.json(json!({
+                "error": "streamNotFound",
+                "stream": stream,
+                "message": format!("stream {stream} doesn't exist, it may have been deleted"),
+            }));
+        }
+        response
             .insert_header(ContentType::plaintext())
             .body(self.to_string())
     }
@@ -19,9 +19,10 @@
 use std::collections::{HashMap, HashSet};
 
 use crate::{
+    parseable::PARSEABLE,
     rbac::{
         self, Users,
-        map::{read_user_groups, roles, users},
+        map::{mut_sessions, read_user_groups, roles, users},
         role::model::DefaultPrivilege,
         user::{self, UserType},
         utils::to_prism_user,
@@ -30,17 +31,19 @@ use crate::{
     validator::{self, error::UsernameValidationError},
 };
 use actix_web::{
-    HttpResponse, Responder,
+    HttpRequest, HttpResponse, Responder,
     http::header::ContentType,
     web::{self, Path},
 };
+use chrono::{DateTime, Utc};
 use http::StatusCode;
 use itertools::Itertools;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tokio::sync::Mutex;
 
 use super::modal::utils::rbac_utils::{get_metadata, put_metadata};
+use super::pagination::PageParams;
 
 // async aware lock for updating storage metadata and user map atomically
 pub(crate) static UPDATE_LOCK: Mutex<()> = Mutex::const_new(());
@@ -66,18 +69,42 @@ impl From<&user::User> for User {
 }
 
 // Handler for GET /api/v1/user
-// returns list of all registered users
-pub async fn list_users() -> impl Responder {
-    web::Json(Users.collect_user::<User>())
+// returns list of all registered users, a page of them with `?paginated=true`
+pub async fn list_users(req: HttpRequest) -> Result<HttpResponse, RBACError> {
+    let page = PageParams::from_query_string(req.query_string())
+        .map_err(RBACError::InvalidQueryParameter)?;
+    let mut users = Users.collect_user::<User>();
+
+    match page {
+        Some(page) => {
+            // sort for a stable order across pages
+            users.sort_by(|a, b| a.id.cmp(&b.id));
+            Ok(HttpResponse::Ok().json(page.paginate(users)))
+        }
+        None => Ok(HttpResponse::Ok().json(users)),
+    }
 }
 
 /// Handler for GET /api/v1/users
-/// returns list of all registered users along with their roles and other info
-pub async fn list_users_prism() -> impl Responder {
+/// returns list of all registered users along with their roles and other info,
+/// a page of them with `?paginated=true`
+pub async fn list_users_prism(req: HttpRequest) -> Result<HttpResponse, RBACError> {
+    let page = PageParams::from_query_string(req.query_string())
+        .map_err(RBACError::InvalidQueryParameter)?;
     // get all users
-    let prism_users = rbac::map::users().values().map(to_prism_user).collect_vec();
+    let users = rbac::map::users();
 
-    web::Json(prism_users)
+    match page {
+        Some(page) => {
+            let prism_users = users
+                .values()
+                .sorted_by(|a, b| a.userid().cmp(b.userid()))
+                .map(to_prism_user)
+                .collect_vec();
+            Ok(HttpResponse::Ok().json(page.paginate(prism_users)))
+        }
+        None => Ok(HttpResponse::Ok().json(users.values().map(to_prism_user).collect_vec())),
+    }
 }
 
 /// Function for GET /users/{username}
@@ -178,6 +205,31 @@ pub async fn post_gen_password(username: web::Path<String>) -> Result<impl Respo
     Ok(new_password)
 }
 
+// Handler for POST /api/v1/user/sessions/rotate
+// Revokes the sessions of all the users, users logged in through the console or OIDC log in again.
+// Sessions are random keys held in memory, no key signs them, so passwords and basic auth
+// credentials stay valid.
+pub async fn rotate_sessions() -> Result<impl Responder, RBACError> {
+    let rotated_at = record_sessions_rotation().await?;
+    let revoked = mut_sessions().remove_all();
+
+    Ok(web::Json(json!({
+        "rotatedAt": rotated_at,
+        "revokedSessions": revoked,
+    })))
+}
+
+// records the time of the rotation in the storage metadata
+pub(crate) async fn record_sessions_rotation() -> Result<DateTime<Utc>, RBACError> {
+    let _guard = UPDATE_LOCK.lock().await;
+    let mut metadata = get_metadata().await?;
+    let rotated_at = Utc::now();
+    metadata.sessions_rotated_at = Some(rotated_at);
+    put_metadata(&metadata).await?;
+
+    Ok(rotated_at)
+}
+
 // Handler for GET /api/v1/user/{userid}/role
 // returns role for a user if that user exists
 pub async fn get_role(userid: web::Path<String>) -> Result<impl Responder, RBACError> {
@@ -366,6 +418,97 @@ pub async fn remove_roles_from_user(
     Ok(HttpResponse::Ok().json(format!("Roles updated successfully for {username}")))
 }
 
+/// Users a role is assigned to at once
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleAssignmentRequest {
+    pub usernames: Vec<String>,
+}
+
+/// Outcome of the assignment of a role to one of the users
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RoleAssignment {
+    Assigned,
+    AlreadyAssigned,
+    UserDoesNotExist,
+    RootUser,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleAssignmentResult {
+    pub username: String,
+    pub result: RoleAssignment,
+}
+
+/// Assigns the role to all the users which exist and don't have it yet, with a single update of
+/// the storage metadata followed by the in-memory users. The caller holds `UPDATE_LOCK`.
+/// Returns the result for every user, along with the users the role was assigned to.
+pub(crate) async fn assign_role_to_users(
+    role: &str,
+    usernames: Vec<String>,
+) -> Result<(Vec<RoleAssignmentResult>, Vec<String>), RBACError> {
+    if !roles().contains_key(role) {
+        return Err(RBACError::RolesDoNotExist(vec![role.to_owned()]));
+    }
+
+    let results = usernames
+        .into_iter()
+        .unique()
+        .map(|username| {
+            let result = if username == PARSEABLE.options.username {
+                RoleAssignment::RootUser
+            } else if !Users.contains(&username) {
+                RoleAssignment::UserDoesNotExist
+            } else if Users.get_role(&username).iter().any(|r| r == role) {
+                RoleAssignment::AlreadyAssigned
+            } else {
+                RoleAssignment::Assigned
+            };
+            RoleAssignmentResult { username, result }
+        })
+        .collect::<Vec<_>>();
+    let assigned = results
+        .iter()
+        .filter(|user| user.result == RoleAssignment::Assigned)
+        .map(|user| user.username.clone())
+        .collect::<Vec<_>>();
+    if assigned.is_empty() {
+        return Ok((results, assigned));
+    }
+
+    // update parseable.json first
+    let mut metadata = get_metadata().await?;
+    for user in metadata
+        .users
+        .iter_mut()
+        .filter(|user| assigned.iter().any(|userid| userid == user.userid()))
+    {
+        user.roles.insert(role.to_owned());
+    }
+    put_metadata(&metadata).await?;
+    // update in mem table
+    for userid in &assigned {
+        Users.add_roles(userid, HashSet::from([role.to_owned()]));
+    }
+
+    Ok((results, assigned))
+}
+
+// Handler POST /role/{name}/assign => Assign a role to several users at once
+pub async fn assign_role(
+    name: web::Path<String>,
+    web::Json(request): web::Json<RoleAssignmentRequest>,
+) -> Result<impl Responder, RBACError> {
+    let name = name.into_inner();
+
+    let _guard = UPDATE_LOCK.lock().await;
+    let (results, _) = assign_role_to_users(&name, request.usernames).await?;
+
+    Ok(web::Json(results))
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct InvalidUserGroupError {
@@ -413,6 +556,8 @@ pub enum RBACError {
     ResourceInUse(String),
     #[error("{0}")]
     InvalidDeletionRequest(String),
+    #[error("Invalid query parameter: {0}")]
+    InvalidQueryParameter(String),
 }
 
 impl actix_web::ResponseError for RBACError {
@@ -435,6 +580,7 @@ impl actix_web::ResponseError for RBACError {
             Self::UserGroupNotEmpty(_) => StatusCode::BAD_REQUEST,
             Self::ResourceInUse(_) => StatusCode::BAD_REQUEST,
             Self::InvalidDeletionRequest(_) => StatusCode::BAD_REQUEST,
+            Self::InvalidQueryParameter(_) => StatusCode::BAD_REQUEST,
         }
     }
 
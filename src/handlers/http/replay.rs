@@ -0,0 +1,300 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Replay of events exported from a stream, e.g. to migrate a dataset or to recover a deleted
+//! stream from an export. The events are ingested as any other event, only they keep the
+//! `p_timestamp` they were exported with.
+//!
+//! Exports can also be read from the object store, from the `replay` prefix of the stream
+//! they were exported from, i.e. `<stream>/replay/<file>`.
+
+use std::collections::HashSet;
+
+use actix_web::{
+    HttpRequest, HttpResponse,
+    http::header::CONTENT_TYPE,
+    web::{self, Path},
+};
+use arrow_schema::Schema;
+use bytes::Bytes;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use relative_path::RelativePathBuf;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    event::{
+        DEFAULT_TIMESTAMP_KEY,
+        format::{LogSource, LogSourceEntry},
+    },
+    handlers::TelemetryType,
+    parseable::PARSEABLE,
+    rbac::{Response, Users, role::Action},
+    storage::StreamType,
+    utils::{actix::extract_session_key_from_req, arrow::record_batches_to_json},
+};
+
+use super::{
+    MAX_EVENT_PAYLOAD_SIZE,
+    ingest::PostError,
+    modal::utils::ingest_utils::{
+        get_custom_fields_from_header, push_replayed_logs, validate_stream_for_ingestion,
+    },
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayQuery {
+    /// stream the events are ingested into, the replayed stream by default
+    pub target: Option<String>,
+    /// path of the export in the object store, read instead of the request body,
+    /// it should be under the `replay` prefix of a stream
+    pub source: Option<String>,
+}
+
+/// Prefix, under the directory of a stream, of the exports which can be replayed
+const REPLAY_DIR: &str = "replay";
+
+/// Formats of the exports which can be replayed
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReplayFormat {
+    /// an array of events
+    Json,
+    /// one event per line
+    Ndjson,
+    Parquet,
+}
+
+impl ReplayFormat {
+    fn from_content_type(content_type: Option<&str>) -> Self {
+        match content_type.and_then(|content_type| content_type.split(';').next()) {
+            Some("application/x-ndjson") => Self::Ndjson,
+            Some("application/vnd.apache.parquet") => Self::Parquet,
+            _ => Self::Json,
+        }
+    }
+
+    fn from_path(path: &str) -> Self {
+        if path.ends_with(".parquet") {
+            Self::Parquet
+        } else if path.ends_with(".ndjson") || path.ends_with(".jsonl") {
+            Self::Ndjson
+        } else {
+            Self::Json
+        }
+    }
+}
+
+// Handler for POST /api/v1/logstream/{logstream}/replay
+// re-ingests the events of an export of the stream, into the stream or the `target` one,
+// which is created if it doesn't exist
+pub async fn replay(
+    req: HttpRequest,
+    stream_name: Path<String>,
+    web::Query(query): web::Query<ReplayQuery>,
+    body: Bytes,
+) -> Result<HttpResponse, PostError> {
+    let target = query.target.unwrap_or_else(|| stream_name.into_inner());
+    if PARSEABLE.streams.list_internal_streams().contains(&target) {
+        return Err(PostError::InternalStream(target));
+    }
+    // the route only authorizes ingestion into the replayed stream
+    let key = extract_session_key_from_req(&req)
+        .map_err(|err| PostError::Invalid(anyhow::anyhow!(err.to_string())))?;
+    if Users.authorize(key.clone(), Action::Ingest, Some(&target), None) != Response::Authorized {
+        return Err(PostError::NotAuthorized(target));
+    }
+
+    let (payload, format) = match &query.source {
+        Some(path) => {
+            // the export is read on behalf of the user, who should be able to query its stream
+            let source_stream =
+                source_stream(path).map_err(|err| PostError::Invalid(anyhow::anyhow!(err)))?;
+            if Users.authorize(key, Action::Query, Some(source_stream), None)
+                != Response::Authorized
+            {
+                return Err(PostError::NotAuthorized(source_stream.to_owned()));
+            }
+            let payload = PARSEABLE
+                .storage
+                .get_object_store()
+                .get_object(&RelativePathBuf::from(path))
+                .await?;
+            (payload, ReplayFormat::from_path(path))
+        }
+        None => {
+            let content_type = req
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok());
+            (body, ReplayFormat::from_content_type(content_type))
+        }
+    };
+    // exports in the object store are bound by the same limit as request bodies
+    if payload.len() > MAX_EVENT_PAYLOAD_SIZE {
+        return Err(PostError::Invalid(anyhow::anyhow!(
+            "the export is {} bytes, at most {MAX_EVENT_PAYLOAD_SIZE} bytes can be replayed at once",
+            payload.len()
+        )));
+    }
+    let (events, schema) = decode_export(payload, format)?;
+
+    PARSEABLE
+        .create_stream_if_not_exists(
+            &target,
+            StreamType::UserDefined,
+            None,
+            vec![LogSourceEntry::new(LogSource::Json, HashSet::new())],
+            TelemetryType::Logs,
+        )
+        .await?;
+    validate_stream_for_ingestion(&target)?;
+    if let Some(schema) = schema {
+        check_schema_compatibility(&target, &schema)?;
+    }
+
+    let p_custom_fields = get_custom_fields_from_header(&req);
+    let result = push_replayed_logs(events, &target, &p_custom_fields).await?;
+    let response = if result.rejected.is_empty() {
+        HttpResponse::Ok().json(result)
+    } else if result.accepted == 0 {
+        HttpResponse::BadRequest().json(result)
+    } else {
+        HttpResponse::MultiStatus().json(result)
+    };
+
+    Ok(response)
+}
+
+/// The stream an export in the object store belongs to, exports are only read from
+/// `<stream>/replay/`, absolute paths and relative segments are rejected
+fn source_stream(path: &str) -> Result<&str, String> {
+    let segments = path.split('/').collect::<Vec<_>>();
+    let is_relative_segment =
+        |segment: &&str| segment.is_empty() || *segment == "." || *segment == "..";
+    match segments.as_slice() {
+        [stream, REPLAY_DIR, file @ ..]
+            if !file.is_empty()
+                && !path.contains('\\')
+                && !segments.iter().any(is_relative_segment) =>
+        {
+            Ok(stream)
+        }
+        _ => Err(format!(
+            "source {path} is not an export, exports are read from <stream>/{REPLAY_DIR}/"
+        )),
+    }
+}
+
+/// The events of an export, an event which can't be decoded is an error of its own so that
+/// it is rejected alone. Parquet exports also come with their schema.
+fn decode_export(
+    payload: Bytes,
+    format: ReplayFormat,
+) -> Result<(Vec<Result<Value, String>>, Option<Schema>), PostError> {
+    let events = match format {
+        ReplayFormat::Json => match serde_json::from_slice(&payload)? {
+            Value::Array(events) => events.into_iter().map(Ok).collect(),
+            event => vec![Ok(event)],
+        },
+        ReplayFormat::Ndjson => payload
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.trim_ascii().is_empty())
+            .map(|line| serde_json::from_slice(line).map_err(|err| err.to_string()))
+            .collect(),
+        ReplayFormat::Parquet => {
+            let reader = ParquetRecordBatchReaderBuilder::try_new(payload)
+                .and_then(|builder| builder.build())
+                .map_err(|err| PostError::Invalid(err.into()))?;
+            let schema = reader.schema().as_ref().clone();
+            let batches = reader
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| PostError::Invalid(err.into()))?;
+            let events = record_batches_to_json(&batches)?
+                .into_iter()
+                .map(|event| Ok(Value::Object(event)))
+                .collect();
+            return Ok((events, Some(schema)));
+        }
+    };
+
+    Ok((events, None))
+}
+
+/// Fails when a column of the export has another type in the target stream,
+/// the columns added on ingestion aside
+fn check_schema_compatibility(stream_name: &str, schema: &Schema) -> Result<(), PostError> {
+    let stream_schema = PARSEABLE.get_stream(stream_name)?.get_schema_raw();
+    for field in schema.fields() {
+        if field.name() == DEFAULT_TIMESTAMP_KEY {
+            continue;
+        }
+        if let Some(existing) = stream_schema.get(field.name())
+            && existing.data_type() != field.data_type()
+        {
+            return Err(PostError::Invalid(anyhow::anyhow!(
+                "column {} of the export is of type {}, it is of type {} in stream {stream_name}",
+                field.name(),
+                field.data_type(),
+                existing.data_type()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_ndjson_lines_are_rejected_alone() {
+        let payload = Bytes::from_static(
+            b"{\"p_timestamp\":\"2024-05-01T10:00:00.000\",\"level\":\"info\"}\n{oops\n\n{\"level\":\"warn\"}\n",
+        );
+
+        let (events, schema) = decode_export(payload, ReplayFormat::Ndjson).unwrap();
+        assert!(schema.is_none());
+        assert_eq!(events.len(), 3);
+        assert!(events[0].is_ok() && events[1].is_err() && events[2].is_ok());
+    }
+
+    #[test]
+    fn exports_are_only_read_from_the_replay_prefix_of_a_stream() {
+        assert_eq!(source_stream("app/replay/2024-05-01.ndjson"), Ok("app"));
+        assert!(source_stream("app/.stream/.parseable.json").is_err());
+        assert!(source_stream("/app/replay/export.json").is_err());
+        assert!(source_stream("app/replay/../../.parseable.json").is_err());
+        assert!(source_stream("app/replay/").is_err());
+        assert!(source_stream("app/replay").is_err());
+    }
+
+    #[test]
+    fn export_format_follows_content_type_or_extension() {
+        assert_eq!(
+            ReplayFormat::from_content_type(Some("application/x-ndjson; charset=utf-8")),
+            ReplayFormat::Ndjson
+        );
+        assert_eq!(
+            ReplayFormat::from_path("exports/app/2024-05-01.parquet"),
+            ReplayFormat::Parquet
+        );
+        assert_eq!(ReplayFormat::from_content_type(None), ReplayFormat::Json);
+    }
+}
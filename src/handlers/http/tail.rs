@@ -0,0 +1,205 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Live tail of a stream over server-sent events, the events ingested by this node are pushed
+//! to the client as they are processed. Each event is sent as a `data:` line holding its JSON,
+//! heartbeats are sent in between for proxies not to close an idle connection.
+//! The masks of the user apply to the tailed events as they do to query results.
+
+use std::{sync::Arc, time::Duration};
+
+use actix_web::{
+    HttpRequest, HttpResponse,
+    http::StatusCode,
+    web::{self, Path},
+};
+use bytes::Bytes;
+use futures::{StreamExt, stream};
+use once_cell::sync::Lazy;
+use rand::distributions::{Alphanumeric, DistString};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tokio::sync::Semaphore;
+use tokio_stream::wrappers::IntervalStream;
+use tracing::warn;
+
+use crate::{
+    livetail::{LIVETAIL, Message},
+    parseable::{PARSEABLE, StreamNotFound},
+    storage::masking::{mask_record_batch, masks_for_session},
+    utils::{actix::extract_session_key_from_req, arrow::record_batches_to_json},
+};
+
+use super::logstream::error::StreamError;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Bounds the live tails open at once to `--max-tail-connections`
+static TAIL_CONNECTIONS: Lazy<Arc<Semaphore>> = Lazy::new(|| {
+    Arc::new(Semaphore::new(
+        PARSEABLE.options.max_tail_connections as usize,
+    ))
+});
+
+#[derive(Debug, Deserialize)]
+pub struct TailQuery {
+    /// comma separated `column=value` conditions the events should all match
+    pub filter: Option<String>,
+}
+
+/// Equality conditions on the columns of the tailed events
+#[derive(Debug, Default, PartialEq)]
+struct TailFilter(Vec<(String, String)>);
+
+impl TailFilter {
+    fn parse(filter: &str) -> Result<Self, String> {
+        filter
+            .split(',')
+            .map(|condition| match condition.split_once('=') {
+                Some((column, value)) if !column.trim().is_empty() => {
+                    Ok((column.trim().to_owned(), value.trim().to_owned()))
+                }
+                _ => Err(format!(
+                    "invalid filter condition \"{condition}\", it should be given as column=value"
+                )),
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    /// Columns the conditions are on
+    fn columns(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|(column, _)| column.as_str())
+    }
+
+    fn matches(&self, event: &Map<String, Value>) -> bool {
+        self.0
+            .iter()
+            .all(|(column, expected)| match event.get(column) {
+                Some(Value::String(value)) => value == expected,
+                Some(value) => value.to_string() == *expected,
+                None => false,
+            })
+    }
+}
+
+// Handler for GET /api/v1/logstream/{logstream}/tail
+// pushes the events ingested into the stream as server-sent events until the client disconnects
+pub async fn tail(
+    req: HttpRequest,
+    stream_name: Path<String>,
+    web::Query(TailQuery { filter }): web::Query<TailQuery>,
+) -> Result<HttpResponse, StreamError> {
+    let stream_name = stream_name.into_inner();
+    if !PARSEABLE.streams.contains(&stream_name) {
+        return Err(StreamNotFound(stream_name).into());
+    }
+    let filter = match filter {
+        Some(filter) => TailFilter::parse(&filter).map_err(|msg| StreamError::Custom {
+            msg,
+            status: StatusCode::BAD_REQUEST,
+        })?,
+        None => TailFilter::default(),
+    };
+    let key = extract_session_key_from_req(&req).map_err(|err| StreamError::Custom {
+        msg: err.to_string(),
+        status: StatusCode::UNAUTHORIZED,
+    })?;
+    let masks = masks_for_session(&key, std::slice::from_ref(&stream_name));
+    // filtering on a masked column would reveal its values
+    if let Some(column) = filter.columns().find(|column| masks.contains_key(*column)) {
+        return Err(StreamError::Custom {
+            msg: format!("column {column} is masked, the events can't be filtered on it"),
+            status: StatusCode::FORBIDDEN,
+        });
+    }
+    let permit = TAIL_CONNECTIONS
+        .clone()
+        .try_acquire_owned()
+        .map_err(|_| StreamError::Custom {
+            msg: format!(
+                "{} live tails are already open, try again later",
+                PARSEABLE.options.max_tail_connections
+            ),
+            status: StatusCode::TOO_MANY_REQUESTS,
+        })?;
+
+    let pipe = LIVETAIL.new_pipe(
+        Alphanumeric.sample_string(&mut rand::thread_rng(), 32),
+        stream_name,
+    );
+    let events = pipe.map(move |message| match message {
+        Message::Record(rb) => {
+            let events = match mask_record_batch(rb, &masks) {
+                Ok(rb) => record_batches_to_json(&[rb]).unwrap_or_default(),
+                Err(err) => {
+                    warn!("live tail couldn't mask events, skipping them: {err}");
+                    vec![]
+                }
+            };
+            Bytes::from(
+                events
+                    .iter()
+                    .filter(|event| filter.matches(event))
+                    .map(|event| format!("data: {}\n\n", Value::Object(event.clone())))
+                    .collect::<String>(),
+            )
+        }
+        Message::Skipped(count) => {
+            warn!("live tail is too slow, skipped {count} events");
+            Bytes::from(format!("event: skipped\ndata: {count}\n\n"))
+        }
+    });
+    let heartbeats = IntervalStream::new(tokio::time::interval(HEARTBEAT_INTERVAL))
+        .map(|_| Bytes::from_static(b"event: heartbeat\ndata: {}\n\n"));
+
+    // the connection slot is released once the client disconnects and the stream is dropped
+    let stream = stream::select(events, heartbeats)
+        .filter(|chunk| futures::future::ready(!chunk.is_empty()))
+        .map(move |chunk| {
+            let _ = &permit;
+            Ok::<_, actix_web::Error>(chunk)
+        });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        // nginx would otherwise buffer the events
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn events_match_all_the_filter_conditions() {
+        let filter = TailFilter::parse("level=error, status=500").unwrap();
+        let event = |value: Value| value.as_object().unwrap().clone();
+
+        assert!(filter.matches(&event(
+            json!({"level": "error", "status": 500, "host": "a"})
+        )));
+        assert!(!filter.matches(&event(json!({"level": "info", "status": 500}))));
+        assert!(!filter.matches(&event(json!({"level": "error"}))));
+        assert!(TailFilter::parse("level").is_err());
+    }
+}
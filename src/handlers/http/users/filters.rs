@@ -40,6 +40,13 @@ pub async fn list(req: HttpRequest) -> Result<impl Responder, FiltersError> {
     Ok((web::Json(filters), StatusCode::OK))
 }
 
+pub async fn list_usage(req: HttpRequest) -> Result<impl Responder, FiltersError> {
+    let key =
+        extract_session_key_from_req(&req).map_err(|e| FiltersError::Custom(e.to_string()))?;
+    let usage = FILTERS.list_usage(&key).await;
+    Ok((web::Json(usage), StatusCode::OK))
+}
+
 pub async fn get(
     req: HttpRequest,
     filter_id: Path<String>,
@@ -47,16 +54,22 @@ pub async fn get(
     let user_id = get_user_from_request(&req)?;
     let filter_id = filter_id.into_inner();
     let is_admin = is_admin(&req).map_err(|e| FiltersError::Custom(e.to_string()))?;
-    if let Some(filter) = FILTERS
+    let Some(mut filter) = FILTERS
         .get_filter(&filter_id, &get_hash(&user_id), is_admin)
         .await
-    {
-        return Ok((web::Json(filter), StatusCode::OK));
+    else {
+        return Err(FiltersError::Metadata(
+            "Filter does not exist or user is not authorized",
+        ));
+    };
+
+    // fetching a filter is how it gets applied
+    if let Some(used) = FILTERS.mark_used(&filter_id).await {
+        PARSEABLE.metastore.put_filter(&used).await?;
+        filter = used;
     }
 
-    Err(FiltersError::Metadata(
-        "Filter does not exist or user is not authorized",
-    ))
+    Ok((web::Json(filter), StatusCode::OK))
 }
 
 pub async fn post(
@@ -69,6 +82,7 @@ pub async fn post(
     filter.filter_id = Some(filter_id.clone());
     filter.user_id = Some(user_id.clone());
     filter.version = Some(CURRENT_FILTER_VERSION.to_string());
+    filter.last_used = None;
 
     PARSEABLE.metastore.put_filter(&filter).await?;
     FILTERS.update(&filter).await;
@@ -86,15 +100,12 @@ pub async fn update(
     let filter_id = filter_id.into_inner();
     let is_admin = is_admin(&req).map_err(|e| FiltersError::Custom(e.to_string()))?;
 
-    if FILTERS
-        .get_filter(&filter_id, &user_id, is_admin)
-        .await
-        .is_none()
-    {
+    let Some(existing) = FILTERS.get_filter(&filter_id, &user_id, is_admin).await else {
         return Err(FiltersError::Metadata(
             "Filter does not exist or user is not authorized",
         ));
-    }
+    };
+    filter.last_used = existing.last_used;
     filter.filter_id = Some(filter_id.clone());
     filter.user_id = Some(user_id.clone());
     filter.version = Some(CURRENT_FILTER_VERSION.to_string());
@@ -21,6 +21,7 @@ use std::net::SocketAddr;
 use arrow_array::RecordBatch;
 use arrow_flight::PollInfo;
 use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
 use cookie::Cookie;
 use futures::stream::BoxStream;
 use futures_util::{Future, StreamExt, TryFutureExt, TryStreamExt};
@@ -127,7 +128,9 @@ impl FlightService for FlightServiceImpl {
 
         let adapter_schema = schema.clone();
         let rx = rx.map(move |x| match x {
-            Message::Record(t) => Ok(utils::arrow::adapt_batch(&adapter_schema, &t)),
+            Message::Record(t) => {
+                utils::arrow::adapt_batch(&adapter_schema, &t).map_err(FlightError::from)
+            }
             Message::Skipped(_) => {
                 warn!("livetail channel capacity is full.");
                 Ok(RecordBatch::new_empty(adapter_schema.clone()))
@@ -29,12 +29,15 @@ pub const LOG_SOURCE_KEY: &str = "x-p-log-source";
 pub const EXTRACT_LOG_KEY: &str = "x-p-extract-log";
 pub const TIME_PARTITION_KEY: &str = "x-p-time-partition";
 pub const TIME_PARTITION_LIMIT_KEY: &str = "x-p-time-partition-limit";
+pub const TIME_PARTITION_FORMAT_KEY: &str = "x-p-time-partition-format";
 pub const CUSTOM_PARTITION_KEY: &str = "x-p-custom-partition";
 pub const STATIC_SCHEMA_FLAG: &str = "x-p-static-schema-flag";
 pub const AUTHORIZATION_KEY: &str = "authorization";
 pub const UPDATE_STREAM_KEY: &str = "x-p-update-stream";
+pub const IF_NOT_EXISTS_KEY: &str = "x-p-if-not-exists";
 pub const STREAM_TYPE_KEY: &str = "x-p-stream-type";
 pub const TELEMETRY_TYPE_KEY: &str = "x-p-telemetry-type";
+pub const INGEST_MODE_KEY: &str = "x-p-ingest-mode";
 const COOKIE_AGE_DAYS: usize = 7;
 const SESSION_COOKIE_NAME: &str = "session";
 const USER_COOKIE_NAME: &str = "username";
@@ -17,20 +17,20 @@
  */
 
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap},
     io,
     path::{Path, PathBuf},
+    sync::Mutex,
 };
 
 use crate::{
     catalog::manifest::{File, Manifest},
-    handlers::http::cluster::PMETA_STREAM_NAME,
     parseable::PARSEABLE,
     storage::{ObjectStorageError, field_stats::DATASET_STATS_STREAM_NAME},
     utils::{extract_datetime, human_size::bytes_to_human_size},
     validator::error::HotTierValidationError,
 };
-use chrono::NaiveDate;
+use chrono::{DateTime, NaiveDate, Utc};
 use clokwerk::{AsyncScheduler, Interval, Job};
 use futures::{StreamExt, TryStreamExt, stream::FuturesUnordered};
 use futures_util::TryFutureExt;
@@ -64,9 +64,39 @@ pub struct StreamHotTier {
     pub oldest_date_time_entry: Option<String>,
 }
 
+/// Progress of the latest hot tier sync of a stream
+#[derive(Debug, Clone, Default)]
+struct SyncProgress {
+    in_progress: bool,
+    bytes_downloaded: u64,
+    files_pending: usize,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+    error: Option<String>,
+}
+
+/// Hot tier usage of a stream along with the progress of its latest sync
+#[derive(Debug, serde::Serialize)]
+pub struct HotTierSyncStatus {
+    #[serde(with = "crate::utils::human_size")]
+    pub size: u64,
+    #[serde(with = "crate::utils::human_size")]
+    pub used_size: u64,
+    #[serde(with = "crate::utils::human_size")]
+    pub available_size: u64,
+    pub in_progress: bool,
+    pub bytes_downloaded: u64,
+    pub files_pending: usize,
+    pub last_sync_started_at: Option<DateTime<Utc>>,
+    pub last_sync_finished_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_sync_error: Option<String>,
+}
+
 pub struct HotTierManager {
     filesystem: LocalFileSystem,
     hot_tier_path: &'static Path,
+    sync_progress: Mutex<HashMap<String, SyncProgress>>,
 }
 
 impl HotTierManager {
@@ -75,6 +105,7 @@ impl HotTierManager {
         HotTierManager {
             filesystem: LocalFileSystem::new(),
             hot_tier_path,
+            sync_progress: Mutex::new(HashMap::new()),
         }
     }
 
@@ -272,23 +303,109 @@ impl HotTierManager {
         Ok(())
     }
 
-    /// process the hot tier files for the stream
-    /// delete the files from the hot tier directory if the available date range is outside the hot tier range
+    /// trigger an immediate sync of the hot tier of the stream, in the background
+    /// return false if a sync of the stream is already in progress
+    pub async fn trigger_sync(&'static self, stream: &str) -> Result<bool, HotTierError> {
+        // fails if the stream has no hot tier
+        self.get_hot_tier(stream).await?;
+        if !self.start_sync(stream) {
+            return Ok(false);
+        }
+
+        let stream = stream.to_owned();
+        tokio::spawn(async move {
+            if let Err(err) = self.run_sync(&stream).await {
+                error!("Failed to sync hot tier of stream {stream}: {err:?}");
+            }
+        });
+        Ok(true)
+    }
+
+    ///get the hot tier usage of the stream and the progress of its latest sync
+    pub async fn get_sync_status(&self, stream: &str) -> Result<HotTierSyncStatus, HotTierError> {
+        let hot_tier = self.get_hot_tier(stream).await?;
+        let progress = self
+            .sync_progress
+            .lock()
+            .expect("lock poisoned")
+            .get(stream)
+            .cloned()
+            .unwrap_or_default();
+
+        Ok(HotTierSyncStatus {
+            size: hot_tier.size,
+            used_size: hot_tier.used_size,
+            available_size: hot_tier.available_size,
+            in_progress: progress.in_progress,
+            bytes_downloaded: progress.bytes_downloaded,
+            files_pending: progress.files_pending,
+            last_sync_started_at: progress.started_at,
+            last_sync_finished_at: progress.finished_at,
+            last_sync_error: progress.error,
+        })
+    }
+
+    /// mark a sync of the stream as started, unless one is already in progress
+    fn start_sync(&self, stream: &str) -> bool {
+        let mut sync_progress = self.sync_progress.lock().expect("lock poisoned");
+        let progress = sync_progress.entry(stream.to_owned()).or_default();
+        if progress.in_progress {
+            return false;
+        }
+        *progress = SyncProgress {
+            in_progress: true,
+            started_at: Some(Utc::now()),
+            ..Default::default()
+        };
+        true
+    }
+
+    fn update_sync_progress(&self, stream: &str, update: impl FnOnce(&mut SyncProgress)) {
+        if let Some(progress) = self
+            .sync_progress
+            .lock()
+            .expect("lock poisoned")
+            .get_mut(stream)
+        {
+            update(progress);
+        }
+    }
+
+    async fn run_sync(&self, stream: &str) -> Result<(), HotTierError> {
+        let res = self.download_stream(stream).await;
+        self.update_sync_progress(stream, |progress| {
+            progress.in_progress = false;
+            progress.finished_at = Some(Utc::now());
+            progress.error = res.as_ref().err().map(|err| err.to_string());
+        });
+        res
+    }
+
+    /// process the hot tier files for the stream, skipped if a sync of the stream is already in progress
     async fn process_stream(&self, stream: String) -> Result<(), HotTierError> {
-        let stream_hot_tier = self.get_hot_tier(&stream).await?;
+        if !self.start_sync(&stream) {
+            return Ok(());
+        }
+        self.run_sync(&stream).await
+    }
+
+    /// download the hot tier files for the stream
+    /// delete the files from the hot tier directory if the available date range is outside the hot tier range
+    async fn download_stream(&self, stream: &str) -> Result<(), HotTierError> {
+        let stream_hot_tier = self.get_hot_tier(stream).await?;
         let mut parquet_file_size = stream_hot_tier.used_size;
 
         let mut s3_manifest_file_list = PARSEABLE
             .metastore
-            .get_all_manifest_files(&stream)
+            .get_all_manifest_files(stream)
             .await
             .map_err(|e| {
-            HotTierError::ObjectStorageError(ObjectStorageError::MetastoreError(Box::new(
-                e.to_detail(),
-            )))
-        })?;
+                HotTierError::ObjectStorageError(ObjectStorageError::MetastoreError(Box::new(
+                    e.to_detail(),
+                )))
+            })?;
 
-        self.process_manifest(&stream, &mut s3_manifest_file_list, &mut parquet_file_size)
+        self.process_manifest(stream, &mut s3_manifest_file_list, &mut parquet_file_size)
             .await?;
 
         Ok(())
@@ -307,6 +424,14 @@ impl HotTierManager {
         if manifest_files_to_download.is_empty() {
             return Ok(());
         }
+        let files_pending = manifest_files_to_download
+            .values()
+            .flatten()
+            .flat_map(|manifest| &manifest.files)
+            .filter(|file| !self.hot_tier_path.join(&file.file_path).exists())
+            .count();
+        self.update_sync_progress(stream, |progress| progress.files_pending = files_pending);
+
         for (str_date, manifest_files) in manifest_files_to_download.iter().rev() {
             let mut storage_combined_manifest = Manifest::default();
 
@@ -391,6 +516,10 @@ impl HotTierManager {
             .get_object(&parquet_file_path)
             .await?;
         file.write_all(&parquet_data).await?;
+        self.update_sync_progress(stream, |progress| {
+            progress.bytes_downloaded += parquet_file.file_size;
+            progress.files_pending = progress.files_pending.saturating_sub(1);
+        });
         *parquet_file_size += parquet_file.file_size;
         stream_hot_tier.used_size = *parquet_file_size;
 
@@ -697,7 +826,8 @@ impl HotTierManager {
     }
 
     pub async fn put_internal_stream_hot_tier(&self) -> Result<(), HotTierError> {
-        if !self.check_stream_hot_tier_exists(PMETA_STREAM_NAME) {
+        let internal_stream = &PARSEABLE.options.internal_stream_name;
+        if !self.check_stream_hot_tier_exists(internal_stream) {
             let mut stream_hot_tier = StreamHotTier {
                 version: Some(CURRENT_HOT_TIER_VERSION.to_string()),
                 size: INTERNAL_STREAM_HOT_TIER_SIZE_BYTES,
@@ -705,7 +835,7 @@ impl HotTierManager {
                 available_size: INTERNAL_STREAM_HOT_TIER_SIZE_BYTES,
                 oldest_date_time_entry: None,
             };
-            self.put_hot_tier(PMETA_STREAM_NAME, &mut stream_hot_tier)
+            self.put_hot_tier(internal_stream, &mut stream_hot_tier)
                 .await?;
         }
         Ok(())
@@ -60,7 +60,7 @@ pub use handlers::http::modal::{
 use once_cell::sync::Lazy;
 pub use opentelemetry_proto;
 use parseable::PARSEABLE;
-use reqwest::{Client, ClientBuilder};
+use reqwest::{Certificate, Client, ClientBuilder, Identity};
 
 // It is very unlikely that panic will occur when dealing with locks.
 pub const LOCK_EXPECT: &str = "Thread shouldn't panic while holding a lock";
@@ -72,9 +72,6 @@ pub const LOCAL_SYNC_INTERVAL: Duration = Duration::from_secs(60);
 /// Duration used to configure prefix generation.
 pub const OBJECT_STORE_DATA_GRANULARITY: u32 = LOCAL_SYNC_INTERVAL.as_secs() as u32 / 60;
 
-/// Describes the duration at the end of which parquets are pushed into objectstore.
-pub const STORAGE_UPLOAD_INTERVAL: Duration = Duration::from_secs(30);
-
 // A single HTTP client for all outgoing HTTP requests from the parseable server
 pub static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
     ClientBuilder::new()
@@ -105,6 +102,45 @@ pub static INTRA_CLUSTER_CLIENT: Lazy<Client> = Lazy::new(|| {
         .use_rustls_tls() // use only the rustls backend
         .http1_only() // use only http/1.1
         .danger_accept_invalid_certs(PARSEABLE.options.tls_skip_verify)
+        .with_outbound_ca_bundle()
+        .with_intra_cluster_identity()
         .build()
         .expect("Construction of client shouldn't fail")
 });
+
+/// TLS configuration of the clients connecting to the other nodes of the cluster and to alert targets
+pub trait OutboundTls {
+    /// Trusts the CAs of `P_OUTBOUND_CA_BUNDLE` on top of the system roots
+    fn with_outbound_ca_bundle(self) -> Self;
+    /// Presents the certificate of `P_INTRA_CLUSTER_CLIENT_CERT_PATH` for mutual TLS
+    fn with_intra_cluster_identity(self) -> Self;
+}
+
+impl OutboundTls for ClientBuilder {
+    fn with_outbound_ca_bundle(self) -> Self {
+        let Some(path) = &PARSEABLE.options.outbound_ca_bundle else {
+            return self;
+        };
+        // the bundle is validated when the options are parsed
+        let pem = std::fs::read(path).expect("CA bundle is readable");
+        Certificate::from_pem_bundle(&pem)
+            .expect("CA bundle is valid")
+            .into_iter()
+            .fold(self, |builder, cert| builder.add_root_certificate(cert))
+    }
+
+    fn with_intra_cluster_identity(self) -> Self {
+        let (Some(cert_path), Some(key_path)) = (
+            &PARSEABLE.options.intra_cluster_client_cert_path,
+            &PARSEABLE.options.intra_cluster_client_key_path,
+        ) else {
+            return self;
+        };
+        // rustls expects the key and the certificate chain in a single PEM
+        let mut pem = std::fs::read(key_path).expect("client key is readable");
+        pem.push(b'\n');
+        pem.extend(std::fs::read(cert_path).expect("client certificate is readable"));
+
+        self.identity(Identity::from_pem(&pem).expect("client certificate and key are valid"))
+    }
+}
@@ -20,13 +20,19 @@ use std::process::exit;
 #[cfg(feature = "kafka")]
 use parseable::connectors;
 use parseable::{
-    IngestServer, ParseableServer, QueryServer, Server, banner, metrics, option::Mode,
-    parseable::PARSEABLE, rbac, storage,
+    IngestServer, ParseableServer, QueryServer, Server, banner,
+    handlers::http::access_log::{exclude_access_log, json_log_layer},
+    metrics,
+    option::Mode,
+    parseable::PARSEABLE,
+    rbac, storage,
 };
 use tokio::signal::ctrl_c;
 use tokio::sync::oneshot;
 use tracing::Level;
 use tracing::{info, warn};
+use tracing_subscriber::Layer;
+use tracing_subscriber::filter::FilterExt;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::{EnvFilter, Registry, fmt};
@@ -108,11 +114,13 @@ pub fn init_logger() {
         .with_line_number(true)
         .with_timer(tracing_subscriber::fmt::time::UtcTime::rfc_3339())
         .with_target(true)
-        .compact();
+        .compact()
+        .with_filter(filter_layer.and(exclude_access_log()));
 
+    // access logs are enabled by their format, not the log level
     Registry::default()
-        .with(filter_layer)
         .with(fmt_layer)
+        .with(json_log_layer())
         .init();
 }
 
@@ -31,8 +31,17 @@ use crate::metrics::{
     EVENTS_INGESTED, EVENTS_INGESTED_DATE, EVENTS_INGESTED_SIZE, EVENTS_INGESTED_SIZE_DATE,
     EVENTS_STORAGE_SIZE_DATE, LIFETIME_EVENTS_INGESTED, LIFETIME_EVENTS_INGESTED_SIZE,
 };
-use crate::storage::StreamType;
+use crate::storage::late_events::LateEventWindow;
+use crate::storage::masking::MaskingConfig;
+use crate::storage::partition_expression::PartitionExpression;
+use crate::storage::reserved_fields::ReservedFieldPolicy;
 use crate::storage::retention::Retention;
+use crate::storage::sampling::SamplingRate;
+use crate::storage::timestamp_policy::TimestampPolicy;
+use crate::storage::transforms::TransformRule;
+use crate::storage::type_coercion::TypeCoercionPolicy;
+use crate::storage::type_widening::TypeWideningPolicy;
+use crate::storage::{ObjectStoreFormat, StreamType};
 
 pub fn update_stats(
     stream_name: &str,
@@ -85,6 +94,7 @@ pub struct LogStreamMetadata {
     pub first_event_at: Option<String>,
     pub time_partition: Option<String>,
     pub time_partition_limit: Option<NonZeroU32>,
+    pub time_partition_format: Option<String>,
     pub custom_partition: Option<String>,
     pub static_schema_flag: bool,
     pub hot_tier_enabled: bool,
@@ -92,6 +102,22 @@ pub struct LogStreamMetadata {
     pub stream_type: StreamType,
     pub log_source: Vec<LogSourceEntry>,
     pub telemetry_type: TelemetryType,
+    pub masking: Option<MaskingConfig>,
+    pub sampling_rate: Option<SamplingRate>,
+    pub indexed_columns: Vec<String>,
+    pub reserved_field_policy: ReservedFieldPolicy,
+    pub type_coercion_policy: TypeCoercionPolicy,
+    pub type_widening_policy: TypeWideningPolicy,
+    pub timestamp_policy: TimestampPolicy,
+    pub row_group_size: Option<usize>,
+    pub schema_frozen: bool,
+    pub numeric_downcast: bool,
+    pub late_event_window: Option<LateEventWindow>,
+    pub read_only: bool,
+    pub timestamp_column: Option<String>,
+    pub column_aliases: HashMap<String, String>,
+    pub partition_expressions: HashMap<String, PartitionExpression>,
+    pub transforms: Vec<TransformRule>,
 }
 
 impl LogStreamMetadata {
@@ -134,6 +160,27 @@ impl LogStreamMetadata {
             ..Default::default()
         }
     }
+
+    /// Sets the settings of the stream updated on their own from its stream json
+    pub fn set_stream_settings(&mut self, format: &ObjectStoreFormat) {
+        self.masking.clone_from(&format.masking);
+        self.sampling_rate = format.sampling_rate;
+        self.indexed_columns.clone_from(&format.indexed_columns);
+        self.reserved_field_policy = format.reserved_field_policy;
+        self.type_coercion_policy = format.type_coercion_policy;
+        self.type_widening_policy = format.type_widening_policy;
+        self.timestamp_policy.clone_from(&format.timestamp_policy);
+        self.row_group_size = format.row_group_size;
+        self.schema_frozen = format.schema_frozen;
+        self.numeric_downcast = format.numeric_downcast;
+        self.late_event_window.clone_from(&format.late_event_window);
+        self.read_only = format.read_only;
+        self.timestamp_column.clone_from(&format.timestamp_column);
+        self.column_aliases.clone_from(&format.column_aliases);
+        self.partition_expressions
+            .clone_from(&format.partition_expressions);
+        self.transforms.clone_from(&format.transforms);
+    }
 }
 
 ///this function updates the data type of time partition field
@@ -140,6 +140,18 @@ pub trait Metastore: std::fmt::Debug + Send + Sync {
         mode: Option<Mode>,
     ) -> Result<Vec<Bytes>, MetastoreError>;
 
+    /// consolidated snapshot, the snapshots of all the stream jsons of a stream merged into one
+    async fn get_consolidated_snapshot(
+        &self,
+        stream_name: &str,
+    ) -> Result<Option<Bytes>, MetastoreError>;
+    async fn put_consolidated_snapshot(
+        &self,
+        obj: &dyn MetastoreObject,
+        stream_name: &str,
+    ) -> Result<(), MetastoreError>;
+    async fn delete_consolidated_snapshot(&self, stream_name: &str) -> Result<(), MetastoreError>;
+
     /// manifest
     async fn get_all_manifest_files(
         &self,
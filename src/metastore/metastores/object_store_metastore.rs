@@ -48,9 +48,9 @@ use crate::{
     option::Mode,
     parseable::PARSEABLE,
     storage::{
-        ALERTS_ROOT_DIRECTORY, ObjectStorage, ObjectStorageError, PARSEABLE_ROOT_DIRECTORY,
-        SETTINGS_ROOT_DIRECTORY, STREAM_METADATA_FILE_NAME, STREAM_ROOT_DIRECTORY,
-        TARGETS_ROOT_DIRECTORY,
+        ALERTS_ROOT_DIRECTORY, CONSOLIDATED_SNAPSHOT_FILE_NAME, ObjectStorage, ObjectStorageError,
+        PARSEABLE_ROOT_DIRECTORY, SETTINGS_ROOT_DIRECTORY, STREAM_METADATA_FILE_NAME,
+        STREAM_ROOT_DIRECTORY, TARGETS_ROOT_DIRECTORY,
         object_storage::{
             alert_json_path, alert_state_json_path, filter_path, manifest_path, mttr_json_path,
             parseable_json_path, schema_path, stream_json_path, to_bytes,
@@ -643,6 +643,50 @@ impl Metastore for ObjectStoreMetastore {
         }
     }
 
+    /// Fetch the consolidated snapshot of a stream, if it was ever consolidated
+    async fn get_consolidated_snapshot(
+        &self,
+        stream_name: &str,
+    ) -> Result<Option<Bytes>, MetastoreError> {
+        let path = RelativePathBuf::from_iter([
+            stream_name,
+            STREAM_ROOT_DIRECTORY,
+            CONSOLIDATED_SNAPSHOT_FILE_NAME,
+        ]);
+        match self.storage.get_object(&path).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(ObjectStorageError::NoSuchKey(_)) => Ok(None),
+            Err(err) => Err(MetastoreError::ObjectStorageError(err)),
+        }
+    }
+
+    /// Save the consolidated snapshot of a stream
+    async fn put_consolidated_snapshot(
+        &self,
+        obj: &dyn MetastoreObject,
+        stream_name: &str,
+    ) -> Result<(), MetastoreError> {
+        let path = RelativePathBuf::from_iter([
+            stream_name,
+            STREAM_ROOT_DIRECTORY,
+            CONSOLIDATED_SNAPSHOT_FILE_NAME,
+        ]);
+        Ok(self.storage.put_object(&path, to_bytes(obj)).await?)
+    }
+
+    /// Delete the consolidated snapshot of a stream, queries merge the stream jsons until it is rebuilt
+    async fn delete_consolidated_snapshot(&self, stream_name: &str) -> Result<(), MetastoreError> {
+        let path = RelativePathBuf::from_iter([
+            stream_name,
+            STREAM_ROOT_DIRECTORY,
+            CONSOLIDATED_SNAPSHOT_FILE_NAME,
+        ]);
+        match self.storage.delete_object(&path).await {
+            Ok(()) | Err(ObjectStorageError::NoSuchKey(_)) => Ok(()),
+            Err(err) => Err(MetastoreError::ObjectStorageError(err)),
+        }
+    }
+
     /// Save an `ObjectStoreFormat` file
     async fn put_stream_json(
         &self,
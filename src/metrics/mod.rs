@@ -22,7 +22,9 @@ use actix_web::Responder;
 use actix_web_prometheus::{PrometheusMetrics, PrometheusMetricsBuilder};
 use error::MetricsError;
 use once_cell::sync::Lazy;
-use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry};
+use prometheus::{
+    HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+};
 
 pub const METRICS_NAMESPACE: &str = env!("CARGO_PKG_NAME");
 
@@ -172,6 +174,62 @@ pub static STAGING_FILES: Lazy<IntGaugeVec> = Lazy::new(|| {
     .expect("metric can be created")
 });
 
+pub static PENDING_INGESTOR_SYNCS: Lazy<IntGauge> = Lazy::new(|| {
+    IntGauge::with_opts(
+        Opts::new(
+            "pending_ingestor_syncs",
+            "Stream changes waiting for unreachable ingestors to return",
+        )
+        .namespace(METRICS_NAMESPACE),
+    )
+    .expect("metric can be created")
+});
+
+pub static STAGING_FILES_QUARANTINED: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "staging_files_quarantined",
+            "Staged parquet files quarantined after repeated upload failures",
+        )
+        .namespace(METRICS_NAMESPACE),
+        &["stream"],
+    )
+    .expect("metric can be created")
+});
+
+pub static MULTIPART_UPLOADS_IN_FLIGHT: Lazy<IntGauge> = Lazy::new(|| {
+    IntGauge::with_opts(
+        Opts::new(
+            "multipart_uploads_in_flight",
+            "Multipart uploads to the object store currently in flight",
+        )
+        .namespace(METRICS_NAMESPACE),
+    )
+    .expect("metric can be created")
+});
+
+pub static STAGING_BACKLOG_BYTES: Lazy<IntGauge> = Lazy::new(|| {
+    IntGauge::with_opts(
+        Opts::new(
+            "staging_backlog_bytes",
+            "Size of the staged parquet files waiting to be uploaded to the object store",
+        )
+        .namespace(METRICS_NAMESPACE),
+    )
+    .expect("metric can be created")
+});
+
+pub static OBJECT_STORE_SYNCS_SKIPPED: Lazy<IntCounter> = Lazy::new(|| {
+    IntCounter::with_opts(
+        Opts::new(
+            "object_store_syncs_skipped_total",
+            "Object store syncs skipped as the previous sync was still running",
+        )
+        .namespace(METRICS_NAMESPACE),
+    )
+    .expect("metric can be created")
+});
+
 pub static QUERY_EXECUTE_TIME: Lazy<HistogramVec> = Lazy::new(|| {
     HistogramVec::new(
         HistogramOpts::new("query_execute_time", "Query execute time").namespace(METRICS_NAMESPACE),
@@ -180,6 +238,64 @@ pub static QUERY_EXECUTE_TIME: Lazy<HistogramVec> = Lazy::new(|| {
     .expect("metric can be created")
 });
 
+pub static QUERY_JOB_COUNT: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "query_jobs",
+            "Asynchronous query jobs held on this node by status",
+        )
+        .namespace(METRICS_NAMESPACE),
+        &["status"],
+    )
+    .expect("metric can be created")
+});
+
+pub static QUERIES_RUNNING: Lazy<IntGauge> = Lazy::new(|| {
+    IntGauge::with_opts(
+        Opts::new(
+            "queries_running",
+            "Queries currently executing on this node",
+        )
+        .namespace(METRICS_NAMESPACE),
+    )
+    .expect("metric can be created")
+});
+
+pub static QUERIES_QUEUED: Lazy<IntGauge> = Lazy::new(|| {
+    IntGauge::with_opts(
+        Opts::new(
+            "queries_queued",
+            "Queries waiting for an execution slot on this node",
+        )
+        .namespace(METRICS_NAMESPACE),
+    )
+    .expect("metric can be created")
+});
+
+pub static QUERY_CANCELLED_ON_DISCONNECT: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "query_cancelled_on_disconnect",
+            "Queries cancelled because the client disconnected",
+        )
+        .namespace(METRICS_NAMESPACE),
+        &["stream"],
+    )
+    .expect("metric can be created")
+});
+
+pub static QUERY_DEADLINE_EXCEEDED: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "query_deadline_exceeded",
+            "Queries aborted because they ran past the query timeout",
+        )
+        .namespace(METRICS_NAMESPACE),
+        &["stream"],
+    )
+    .expect("metric can be created")
+});
+
 pub static QUERY_CACHE_HIT: Lazy<IntCounterVec> = Lazy::new(|| {
     IntCounterVec::new(
         Opts::new("QUERY_CACHE_HIT", "Full Cache hit").namespace(METRICS_NAMESPACE),
@@ -188,6 +304,126 @@ pub static QUERY_CACHE_HIT: Lazy<IntCounterVec> = Lazy::new(|| {
     .expect("metric can be created")
 });
 
+pub static CUSTOM_PARTITIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "custom_partitions",
+            "Distinct custom partition values ingested by stream since the server started",
+        )
+        .namespace(METRICS_NAMESPACE),
+        &["stream"],
+    )
+    .expect("metric can be created")
+});
+
+pub static CUSTOM_PARTITION_OVERFLOW: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "custom_partition_overflow",
+            "Events routed to the overflow partition as their stream reached the maximum number of custom partitions",
+        )
+        .namespace(METRICS_NAMESPACE),
+        &["stream"],
+    )
+    .expect("metric can be created")
+});
+
+pub static NUMERIC_COLUMNS_WIDENED: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "numeric_columns_widened",
+            "Downcast numeric columns written back with their wide type as a value didn't fit the narrow one",
+        )
+        .namespace(METRICS_NAMESPACE),
+        &["stream"],
+    )
+    .expect("metric can be created")
+});
+
+pub static SCHEMA_COLUMNS_WIDENED: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "schema_columns_widened",
+            "Integer columns of stream schemas widened to Float64 on receiving fractional numbers",
+        )
+        .namespace(METRICS_NAMESPACE),
+        &["stream"],
+    )
+    .expect("metric can be created")
+});
+
+pub static LATE_EVENTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "late_events",
+            "Events ingested after the partition of their event time, by whether they were routed to it, bucketed or rejected",
+        )
+        .namespace(METRICS_NAMESPACE),
+        &["stream", "outcome"],
+    )
+    .expect("metric can be created")
+});
+
+pub static TIMESTAMP_FALLBACKS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "timestamp_fallbacks",
+            "Events without a valid timestamp field that got the server time, as per the timestamp policy of the stream",
+        )
+        .namespace(METRICS_NAMESPACE),
+        &["stream"],
+    )
+    .expect("metric can be created")
+});
+
+pub static TRANSFORM_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "transform_errors",
+            "Derived fields that couldn't be computed and were left out of their event, as per the transforms of the stream",
+        )
+        .namespace(METRICS_NAMESPACE),
+        &["stream"],
+    )
+    .expect("metric can be created")
+});
+
+pub static VALUES_COERCED: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "values_coerced",
+            "Event values converted to the type of their column on ingestion, as per the type coercion policy of the stream",
+        )
+        .namespace(METRICS_NAMESPACE),
+        &["stream"],
+    )
+    .expect("metric can be created")
+});
+
+pub static QUERY_RESULT_CACHE_HITS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "query_result_cache_hits",
+            "Queries served from the query result cache",
+        )
+        .namespace(METRICS_NAMESPACE),
+        &["stream"],
+    )
+    .expect("metric can be created")
+});
+
+pub static QUERY_RESULT_CACHE_MISSES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "query_result_cache_misses",
+            "Cacheable queries not found in the query result cache",
+        )
+        .namespace(METRICS_NAMESPACE),
+        &["stream"],
+    )
+    .expect("metric can be created")
+});
+
 pub static ALERTS_STATES: Lazy<IntCounterVec> = Lazy::new(|| {
     IntCounterVec::new(
         Opts::new("alerts_states", "Alerts States").namespace(METRICS_NAMESPACE),
@@ -196,6 +432,53 @@ pub static ALERTS_STATES: Lazy<IntCounterVec> = Lazy::new(|| {
     .expect("metric can be created")
 });
 
+// labelled by alert id only, the series of an alert are removed along with its evaluation task
+pub static ALERT_EVAL_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    HistogramVec::new(
+        HistogramOpts::new(
+            "alert_eval_duration_seconds",
+            "Duration of the scheduled evaluations of alerts",
+        )
+        .namespace(METRICS_NAMESPACE),
+        &["alert_id"],
+    )
+    .expect("metric can be created")
+});
+
+pub static ALERT_EVAL_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "alert_eval_errors_total",
+            "Scheduled evaluations of alerts that failed",
+        )
+        .namespace(METRICS_NAMESPACE),
+        &["alert_id"],
+    )
+    .expect("metric can be created")
+});
+
+pub static ALERT_EVALS_QUEUED: Lazy<IntGauge> = Lazy::new(|| {
+    IntGauge::with_opts(
+        Opts::new(
+            "alert_evals_queued",
+            "Scheduled evaluations of alerts waiting for a slot, as per --max-concurrent-alert-evals",
+        )
+        .namespace(METRICS_NAMESPACE),
+    )
+    .expect("metric can be created")
+});
+
+pub static ALERT_EVALS_DELAYED: Lazy<IntCounter> = Lazy::new(|| {
+    IntCounter::with_opts(
+        Opts::new(
+            "alert_evals_delayed_total",
+            "Scheduled evaluations of alerts that waited for a slot, as per --max-concurrent-alert-evals",
+        )
+        .namespace(METRICS_NAMESPACE),
+    )
+    .expect("metric can be created")
+});
+
 // Billing Metrics - Counter type metrics for billing/usage tracking
 pub static TOTAL_EVENTS_INGESTED_BY_DATE: Lazy<IntCounterVec> = Lazy::new(|| {
     IntCounterVec::new(
@@ -368,7 +651,7 @@ pub static STORAGE_REQUEST_RESPONSE_TIME: Lazy<HistogramVec> = Lazy::new(|| {
     HistogramVec::new(
         HistogramOpts::new("storage_request_response_time", "Storage Request Latency")
             .namespace(METRICS_NAMESPACE),
-        &["provider", "method", "status"],
+        &["provider", "method", "status", "purpose"],
     )
     .expect("metric can be created")
 });
@@ -413,15 +696,87 @@ fn custom_metrics(registry: &Registry) {
     registry
         .register(Box::new(STAGING_FILES.clone()))
         .expect("metric can be registered");
+    registry
+        .register(Box::new(STAGING_FILES_QUARANTINED.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(PENDING_INGESTOR_SYNCS.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(MULTIPART_UPLOADS_IN_FLIGHT.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(STAGING_BACKLOG_BYTES.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(OBJECT_STORE_SYNCS_SKIPPED.clone()))
+        .expect("metric can be registered");
     registry
         .register(Box::new(QUERY_EXECUTE_TIME.clone()))
         .expect("metric can be registered");
+    registry
+        .register(Box::new(QUERY_CANCELLED_ON_DISCONNECT.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(QUERY_DEADLINE_EXCEEDED.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(QUERIES_RUNNING.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(QUERY_JOB_COUNT.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(QUERIES_QUEUED.clone()))
+        .expect("metric can be registered");
     registry
         .register(Box::new(QUERY_CACHE_HIT.clone()))
         .expect("metric can be registered");
+    registry
+        .register(Box::new(VALUES_COERCED.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(TIMESTAMP_FALLBACKS.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(TRANSFORM_ERRORS.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(CUSTOM_PARTITIONS.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(CUSTOM_PARTITION_OVERFLOW.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(NUMERIC_COLUMNS_WIDENED.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(SCHEMA_COLUMNS_WIDENED.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(LATE_EVENTS.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(QUERY_RESULT_CACHE_HITS.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(QUERY_RESULT_CACHE_MISSES.clone()))
+        .expect("metric can be registered");
     registry
         .register(Box::new(ALERTS_STATES.clone()))
         .expect("metric can be registered");
+    registry
+        .register(Box::new(ALERT_EVAL_DURATION.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(ALERT_EVAL_ERRORS.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(ALERT_EVALS_QUEUED.clone()))
+        .expect("metric can be registered");
+    registry
+        .register(Box::new(ALERT_EVALS_DELAYED.clone()))
+        .expect("metric can be registered");
     // Register billing metrics
     registry
         .register(Box::new(TOTAL_EVENTS_INGESTED_BY_DATE.clone()))
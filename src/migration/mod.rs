@@ -355,6 +355,10 @@ async fn setup_logstream_metadata(
     arrow_schema: &mut Schema,
     stream_metadata_value: Value,
 ) -> anyhow::Result<LogStreamMetadata> {
+    let stream_json: ObjectStoreFormat =
+        serde_json::from_value(stream_metadata_value).unwrap_or_default();
+    let mut settings = LogStreamMetadata::default();
+    settings.set_stream_settings(&stream_json);
     let ObjectStoreFormat {
         schema_version,
         created_at,
@@ -364,6 +368,7 @@ async fn setup_logstream_metadata(
         stats,
         time_partition,
         time_partition_limit,
+        time_partition_format,
         custom_partition,
         static_schema_flag,
         hot_tier_enabled,
@@ -372,7 +377,7 @@ async fn setup_logstream_metadata(
         log_source,
         telemetry_type,
         ..
-    } = serde_json::from_value(stream_metadata_value).unwrap_or_default();
+    } = stream_json;
 
     update_data_type_time_partition(arrow_schema, time_partition.as_ref()).await?;
     PARSEABLE
@@ -400,6 +405,7 @@ async fn setup_logstream_metadata(
         first_event_at,
         time_partition,
         time_partition_limit: time_partition_limit.and_then(|limit| limit.parse().ok()),
+        time_partition_format,
         custom_partition,
         static_schema_flag,
         hot_tier_enabled,
@@ -407,6 +413,7 @@ async fn setup_logstream_metadata(
         stream_type,
         log_source,
         telemetry_type,
+        ..settings
     };
 
     Ok(metadata)
@@ -57,6 +57,35 @@ impl Mode {
     }
 }
 
+/// Format of the per request access log lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccessLogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// How changes to streams are forwarded to ingestors that can't be reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IngestorSyncPolicy {
+    /// fail the request on the first ingestor the change can't be sent to,
+    /// changes are still retried on the ingestors that are down once they return
+    #[default]
+    FailFast,
+    /// apply the change on the reachable ingestors, retrying the others once they return
+    BestEffort,
+}
+
+/// What happens to the alerts of a stream when the stream is deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeletedStreamAlertPolicy {
+    /// stop evaluating the alerts and mark them as stream-missing
+    #[default]
+    Mark,
+    /// delete the alerts
+    Delete,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Compression {
@@ -88,15 +117,18 @@ impl From<Compression> for parquet::basic::Compression {
 
 pub mod validation {
     use std::{
-        env, io,
+        collections::HashMap,
+        env, fs, io,
         net::ToSocketAddrs,
         path::{Path, PathBuf},
     };
 
     use crate::cli::DATASET_FIELD_COUNT_LIMIT;
+    use crate::handlers::http::compression::RESPONSE_ENCODINGS;
+    use crate::utils::time::TimeRange;
     use path_clean::PathClean;
 
-    use super::{Compression, Mode};
+    use super::{AccessLogFormat, Compression, DeletedStreamAlertPolicy, IngestorSyncPolicy, Mode};
 
     pub fn file_path(s: &str) -> Result<PathBuf, String> {
         if s.is_empty() {
@@ -129,6 +161,19 @@ pub mod validation {
         Ok(absolute_path(path).unwrap())
     }
 
+    pub fn writable_dir(s: &str) -> Result<PathBuf, String> {
+        let path = canonicalize_path(s)?;
+        fs::create_dir_all(&path)
+            .map_err(|err| format!("could not create directory {}: {err}", path.display()))?;
+
+        let probe = path.join(".parseable_write_check");
+        fs::write(&probe, b"")
+            .and_then(|_| fs::remove_file(&probe))
+            .map_err(|err| format!("directory {} is not writable: {err}", path.display()))?;
+
+        Ok(path)
+    }
+
     pub fn socket_addr(s: &str) -> Result<String, String> {
         s.to_socket_addrs()
             .is_ok()
@@ -140,6 +185,50 @@ pub mod validation {
         url::Url::parse(s).map_err(|_| "Invalid URL provided".to_string())
     }
 
+    /// Validates a CORS origin, either `*` or `<scheme>://<host>[:<port>]`
+    pub fn cors_origin(s: &str) -> Result<String, String> {
+        let s = s.trim();
+        if s == "*" {
+            return Ok(s.to_owned());
+        }
+
+        let invalid = || {
+            format!(
+                "Invalid CORS origin \"{s}\", it should be given as <scheme>://<host>[:<port>] or *"
+            )
+        };
+        let origin = url::Url::parse(s).map_err(|_| invalid())?;
+        if !matches!(origin.scheme(), "http" | "https")
+            || origin.host().is_none()
+            || !origin.username().is_empty()
+            || origin.path() != "/"
+            || origin.query().is_some()
+            || origin.fragment().is_some()
+        {
+            return Err(invalid());
+        }
+
+        Ok(origin.origin().ascii_serialization())
+    }
+
+    pub fn response_encoding(s: &str) -> Result<String, String> {
+        let encoding = s.trim().to_lowercase();
+        if RESPONSE_ENCODINGS.contains(&encoding.as_str()) {
+            Ok(encoding)
+        } else {
+            Err(format!(
+                "Invalid response encoding \"{s}\", it should be one of {}",
+                RESPONSE_ENCODINGS.join(", ")
+            ))
+        }
+    }
+
+    pub fn stream_name_pattern(s: &str) -> Result<regex::Regex, String> {
+        // the pattern is anchored so that it applies to the whole name
+        regex::Regex::new(&format!("^(?:{s})$"))
+            .map_err(|err| format!("Invalid stream name pattern \"{s}\": {err}"))
+    }
+
     pub fn mode(s: &str) -> Result<Mode, String> {
         match s {
             "query" => Ok(Mode::Query),
@@ -165,6 +254,42 @@ pub mod validation {
         }
     }
 
+    pub fn internal_stream_name(s: &str) -> Result<String, String> {
+        crate::validator::stream_name(s, crate::storage::StreamType::UserDefined)
+            .map(|_| s.to_owned())
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn access_log_format(s: &str) -> Result<AccessLogFormat, String> {
+        match s {
+            "text" => Ok(AccessLogFormat::Text),
+            "json" => Ok(AccessLogFormat::Json),
+            _ => Err("Invalid ACCESS LOG FORMAT provided, it should be text or json".to_string()),
+        }
+    }
+
+    pub fn ingestor_sync_policy(s: &str) -> Result<IngestorSyncPolicy, String> {
+        match s {
+            "fail_fast" => Ok(IngestorSyncPolicy::FailFast),
+            "best_effort" => Ok(IngestorSyncPolicy::BestEffort),
+            _ => Err(
+                "Invalid INGESTOR SYNC POLICY provided, it should be fail_fast or best_effort"
+                    .to_string(),
+            ),
+        }
+    }
+
+    pub fn deleted_stream_alert_policy(s: &str) -> Result<DeletedStreamAlertPolicy, String> {
+        match s {
+            "mark" => Ok(DeletedStreamAlertPolicy::Mark),
+            "delete" => Ok(DeletedStreamAlertPolicy::Delete),
+            _ => Err(
+                "Invalid DELETED STREAM ALERT POLICY provided, it should be mark or delete"
+                    .to_string(),
+            ),
+        }
+    }
+
     pub fn validate_disk_usage(max_disk_usage: &str) -> Result<f64, String> {
         if let Ok(max_disk_usage) = max_disk_usage.parse::<f64>() {
             if (0.0..=100.0).contains(&max_disk_usage) {
@@ -189,6 +314,13 @@ pub mod validation {
         }
     }
 
+    /// A human time range ending now, e.g. `15m`
+    pub fn query_range(s: &str) -> Result<String, String> {
+        TimeRange::parse_human_time(s, "now")
+            .map(|_| s.to_owned())
+            .map_err(|err| format!("Invalid query range {s}: {err}"))
+    }
+
     pub fn validate_seconds(s: &str) -> Result<u64, String> {
         if let Ok(seconds) = s.parse::<u64>() {
             Ok(seconds)
@@ -209,4 +341,82 @@ pub mod validation {
             Err("Invalid value for P_DATASET_FIELD_COUNT_LIMIT. It should be given as integer value".to_string())
         }
     }
+
+    /// Parses comma separated `<common name>:<username>` pairs
+    /// A file with at least one PEM certificate
+    pub fn pem_certs(s: &str) -> Result<PathBuf, String> {
+        let path = file_path(s)?;
+        let file = fs::File::open(&path).map_err(|err| format!("could not open {s}: {err}"))?;
+        let certs = rustls_pemfile::certs(&mut io::BufReader::new(file))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("could not parse the certificates in {s}: {err}"))?;
+        if certs.is_empty() {
+            return Err(format!("no PEM certificate found in {s}"));
+        }
+
+        Ok(path)
+    }
+
+    /// A PEM bundle of CA certificates, all of which are accepted by the outbound clients
+    pub fn ca_bundle(s: &str) -> Result<PathBuf, String> {
+        let path = pem_certs(s)?;
+        let pem = fs::read(&path).map_err(|err| format!("could not read {s}: {err}"))?;
+        reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|err| format!("invalid CA bundle {s}: {err}"))?;
+
+        Ok(path)
+    }
+
+    /// A file with a PEM private key
+    pub fn pem_private_key(s: &str) -> Result<PathBuf, String> {
+        let path = file_path(s)?;
+        let file = fs::File::open(&path).map_err(|err| format!("could not open {s}: {err}"))?;
+        match rustls_pemfile::private_key(&mut io::BufReader::new(file)) {
+            Ok(Some(_)) => Ok(path),
+            Ok(None) => Err(format!("no PEM private key found in {s}")),
+            Err(err) => Err(format!("could not parse the private key in {s}: {err}")),
+        }
+    }
+
+    pub fn client_cert_user_map(s: &str) -> Result<HashMap<String, String>, String> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(|rule| match rule.split_once(':') {
+                Some((common_name, username))
+                    if !common_name.trim().is_empty() && !username.trim().is_empty() =>
+                {
+                    Ok((common_name.trim().to_owned(), username.trim().to_owned()))
+                }
+                _ => Err(format!(
+                    "Invalid client certificate mapping \"{rule}\". It should be given as <common name>:<username>"
+                )),
+            })
+            .collect()
+    }
+
+    /// Parses comma separated `<severity>:<target id>[|<target id>...]` pairs, severities are lowercased
+    pub fn severity_targets(s: &str) -> Result<HashMap<String, Vec<ulid::Ulid>>, String> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(|rule| {
+                let invalid = || {
+                    format!(
+                        "Invalid severity routing \"{rule}\". It should be given as <severity>:<target id>[|<target id>...] with severity one of critical, high, medium or low"
+                    )
+                };
+                let (severity, targets) = rule.split_once(':').ok_or_else(invalid)?;
+                let severity = severity.trim().to_lowercase();
+                if !matches!(severity.as_str(), "critical" | "high" | "medium" | "low") {
+                    return Err(invalid());
+                }
+                let targets = targets
+                    .split('|')
+                    .map(|id| ulid::Ulid::from_string(id.trim()).map_err(|_| invalid()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((severity, targets))
+            })
+            .collect()
+    }
 }
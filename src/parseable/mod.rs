@@ -28,7 +28,10 @@ use std::{
 use actix_web::http::header::HeaderMap;
 use arrow_schema::{Field, Schema};
 use bytes::Bytes;
-use chrono::Utc;
+use chrono::{
+    Utc,
+    format::{Item, StrftimeItems},
+};
 use clap::{Parser, error::ErrorKind};
 use http::{HeaderName, HeaderValue, StatusCode, header::CONTENT_TYPE};
 use once_cell::sync::Lazy;
@@ -49,9 +52,7 @@ use crate::{
     handlers::{
         STREAM_TYPE_KEY, TelemetryType,
         http::{
-            cluster::{
-                BILLING_METRICS_STREAM_NAME, PMETA_STREAM_NAME, sync_streams_with_ingestors,
-            },
+            cluster::{BILLING_METRICS_STREAM_NAME, sync_streams_with_ingestors},
             ingest::PostError,
             logstream::error::{CreateStreamError, StreamError},
             modal::{ingest_server::INGESTOR_META, utils::logstream_utils::PutStreamHeaders},
@@ -65,7 +66,7 @@ use crate::{
     static_schema::{StaticSchema, convert_static_schema_to_arrow_schema},
     storage::{
         ObjectStorageError, ObjectStorageProvider, ObjectStoreFormat, Owner, Permisssion,
-        StreamType,
+        StreamType, retention::Retention,
     },
     validator,
 };
@@ -239,6 +240,7 @@ impl Parseable {
     // if the proper data directory is provided, or s3 bucket is provided etc
     pub async fn validate_storage(&self) -> Result<Option<Bytes>, ObjectStorageError> {
         let obj_store = self.storage.get_object_store();
+        obj_store.check_stream_buckets().await?;
         let mut has_parseable_json = false;
         let parseable_json_result = self
             .metastore
@@ -352,35 +354,30 @@ impl Parseable {
             .map(|field| (field.name().to_string(), field.clone()))
             .collect();
 
-        let created_at = stream_metadata.created_at;
-        let time_partition = stream_metadata.time_partition.unwrap_or_default();
-        let time_partition_limit = stream_metadata
-            .time_partition_limit
-            .and_then(|limit| limit.parse().ok());
-        let custom_partition = stream_metadata.custom_partition;
-        let static_schema_flag = stream_metadata.static_schema_flag;
-        let hot_tier_enabled = stream_metadata.hot_tier_enabled;
         let hot_tier = stream_metadata.hot_tier.clone();
-        let stream_type = stream_metadata.stream_type;
-        let schema_version = stream_metadata.schema_version;
-        let log_source = stream_metadata.log_source;
-        let telemetry_type = stream_metadata.telemetry_type;
         let mut metadata = LogStreamMetadata::new(
-            created_at,
-            time_partition,
-            time_partition_limit,
-            custom_partition,
-            static_schema_flag,
+            stream_metadata.created_at.clone(),
+            stream_metadata.time_partition.clone().unwrap_or_default(),
+            stream_metadata
+                .time_partition_limit
+                .as_ref()
+                .and_then(|limit| limit.parse().ok()),
+            stream_metadata.custom_partition.clone(),
+            stream_metadata.static_schema_flag,
             static_schema,
-            stream_type,
-            schema_version,
-            log_source,
-            telemetry_type,
+            stream_metadata.stream_type,
+            stream_metadata.schema_version,
+            stream_metadata.log_source.clone(),
+            stream_metadata.telemetry_type,
         );
 
         // Set hot tier fields from the stored metadata
-        metadata.hot_tier_enabled = hot_tier_enabled;
+        metadata.hot_tier_enabled = stream_metadata.hot_tier_enabled;
         metadata.hot_tier.clone_from(&hot_tier);
+        metadata
+            .time_partition_format
+            .clone_from(&stream_metadata.time_partition_format);
+        metadata.set_stream_settings(&stream_metadata);
 
         let ingestor_id = INGESTOR_META
             .get()
@@ -406,10 +403,11 @@ impl Parseable {
     }
 
     pub async fn create_internal_stream_if_not_exists(&self) -> Result<(), StreamError> {
+        let internal_stream = &self.options.internal_stream_name;
         let log_source_entry = LogSourceEntry::new(LogSource::Pmeta, HashSet::new());
         let internal_stream_result = self
             .create_stream_if_not_exists(
-                PMETA_STREAM_NAME,
+                internal_stream,
                 StreamType::Internal,
                 None,
                 vec![log_source_entry],
@@ -430,7 +428,12 @@ impl Parseable {
 
         // Check if either stream creation failed
         if let Err(e) = &internal_stream_result {
-            tracing::error!("Failed to create pmeta stream: {:?}", e);
+            tracing::error!("Failed to create {internal_stream} stream: {:?}", e);
+        } else if let Err(e) = self.apply_internal_stream_retention().await {
+            tracing::error!(
+                "Failed to set retention for {internal_stream} stream: {:?}",
+                e
+            );
         }
         if let Err(e) = &billing_stream_result {
             tracing::error!("Failed to create billing stream: {:?}", e);
@@ -451,10 +454,12 @@ impl Parseable {
         // Sync only the streams that were created successfully
         if matches!(internal_stream_result, Ok(false))
             && let Err(e) =
-                sync_streams_with_ingestors(header_map.clone(), Bytes::new(), PMETA_STREAM_NAME)
-                    .await
+                sync_streams_with_ingestors(header_map.clone(), Bytes::new(), internal_stream).await
         {
-            tracing::error!("Failed to sync pmeta stream with ingestors: {:?}", e);
+            tracing::error!(
+                "Failed to sync {internal_stream} stream with ingestors: {:?}",
+                e
+            );
         }
 
         if matches!(billing_stream_result, Ok(false))
@@ -468,6 +473,27 @@ impl Parseable {
         Ok(())
     }
 
+    /// Applies the configured retention to the internal stream, if it differs from the current one
+    async fn apply_internal_stream_retention(&self) -> Result<(), StreamError> {
+        let Some(days) = self.options.internal_stream_retention_days else {
+            return Ok(());
+        };
+        let internal_stream = &self.options.internal_stream_name;
+        let stream = self.get_stream(internal_stream)?;
+        let retention = Retention::delete_after(days);
+        if stream.get_retention().as_ref() == Some(&retention) {
+            return Ok(());
+        }
+
+        self.storage
+            .get_object_store()
+            .put_retention(internal_stream, &retention)
+            .await?;
+        stream.set_retention(retention);
+
+        Ok(())
+    }
+
     // Check if the stream exists and create a new stream if doesn't exist
     pub async fn create_stream_if_not_exists(
         &self,
@@ -573,9 +599,11 @@ impl Parseable {
         let PutStreamHeaders {
             time_partition,
             time_partition_limit,
+            time_partition_format,
             custom_partition,
             static_schema_flag,
             update_stream_flag,
+            if_not_exists_flag,
             stream_type,
             log_source,
             telemetry_type,
@@ -592,6 +620,19 @@ impl Parseable {
                 .create_stream_and_schema_from_storage(stream_name)
                 .await?;
         if stream_in_memory_dont_update || stream_in_storage_only_for_query_node {
+            if if_not_exists_flag {
+                let conflicts = self.conflicting_stream_settings(headers, body, stream_name)?;
+                if conflicts.is_empty() {
+                    return Ok(headers.clone());
+                }
+                return Err(StreamError::Custom {
+                    msg: format!(
+                        "Logstream {stream_name} already exists with different settings: {}",
+                        conflicts.join(", ")
+                    ),
+                    status: StatusCode::CONFLICT,
+                });
+            }
             return Err(StreamError::Custom {
                 msg: format!(
                     "Logstream {stream_name} already exists, please create a new log stream with unique name"
@@ -601,6 +642,13 @@ impl Parseable {
         }
 
         if update_stream_flag {
+            if time_partition_format.is_some() {
+                return Err(StreamError::Custom {
+                    msg: "Altering the time partition of an existing stream is restricted."
+                        .to_string(),
+                    status: StatusCode::BAD_REQUEST,
+                });
+            }
             return self
                 .update_stream(
                     headers,
@@ -623,6 +671,22 @@ impl Parseable {
             validate_custom_partition(custom_partition)?;
         }
 
+        if let Some(time_partition_format) = &time_partition_format {
+            if time_partition.is_empty() {
+                return Err(StreamError::Custom {
+                    msg: "Time partition format requires a time partition".to_string(),
+                    status: StatusCode::BAD_REQUEST,
+                });
+            }
+            validate_time_partition_format(time_partition_format)?;
+        }
+
+        if !time_partition.is_empty() {
+            validate_time_partition_path(&time_partition)?;
+        }
+        // nested fields are addressed with a dotted path, events are flattened with `_`
+        let time_partition = time_partition.replace('.', "_");
+
         if !time_partition.is_empty() && custom_partition.is_some() {
             return Err(StreamError::Custom {
                 msg: "Cannot set both time partition and custom partition".to_string(),
@@ -651,9 +715,88 @@ impl Parseable {
         )
         .await?;
 
+        if let Some(time_partition_format) = time_partition_format {
+            self.update_time_partition_format_in_stream(
+                stream_name.to_string(),
+                time_partition_format,
+            )
+            .await?;
+        }
+
         Ok(headers.clone())
     }
 
+    /// The settings of the stream definition in `headers` and `body` which differ from those
+    /// of the existing stream
+    fn conflicting_stream_settings(
+        &self,
+        headers: &HeaderMap,
+        body: &Bytes,
+        stream_name: &str,
+    ) -> Result<Vec<&'static str>, StreamError> {
+        let PutStreamHeaders {
+            time_partition,
+            time_partition_limit,
+            time_partition_format,
+            custom_partition,
+            static_schema_flag,
+            stream_type,
+            telemetry_type,
+            ..
+        } = headers.into();
+        let stream = self.get_stream(stream_name)?;
+
+        let time_partition = time_partition.replace('.', "_");
+        let time_partition_limit = if time_partition_limit.is_empty() {
+            None
+        } else {
+            Some(validate_time_partition_limit(&time_partition_limit)?)
+        };
+
+        let mut conflicts = vec![];
+        if stream.get_time_partition().unwrap_or_default() != time_partition {
+            conflicts.push("time partition");
+        }
+        if stream.get_time_partition_limit() != time_partition_limit {
+            conflicts.push("time partition limit");
+        }
+        if stream.get_time_partition_format() != time_partition_format {
+            conflicts.push("time partition format");
+        }
+        if stream.get_custom_partition() != custom_partition {
+            conflicts.push("custom partition");
+        }
+        if stream.get_stream_type() != stream_type {
+            conflicts.push("stream type");
+        }
+        if stream.get_telemetry_type() != telemetry_type {
+            conflicts.push("telemetry type");
+        }
+        if stream.get_static_schema_flag() != static_schema_flag {
+            conflicts.push("static schema flag");
+        } else if static_schema_flag {
+            let schema = validate_static_schema(
+                body,
+                stream_name,
+                &time_partition,
+                custom_partition.as_ref(),
+                static_schema_flag,
+            )?;
+            let fields = |schema: &Schema| {
+                schema
+                    .fields()
+                    .iter()
+                    .map(|field| (field.name().clone(), field.data_type().clone()))
+                    .collect::<HashSet<_>>()
+            };
+            if fields(&schema) != fields(&stream.get_schema()) {
+                conflicts.push("schema");
+            }
+        }
+
+        Ok(conflicts)
+    }
+
     async fn update_stream(
         &self,
         headers: &HeaderMap,
@@ -709,6 +852,11 @@ impl Parseable {
         // fail to proceed if invalid stream name
         if stream_type != StreamType::Internal {
             validator::stream_name(&stream_name, stream_type)?;
+            validator::stream_name_convention(
+                &stream_name,
+                self.options.stream_name_pattern.as_ref(),
+                &self.options.stream_name_reserved,
+            )?;
         }
         // Proceed to create log stream if it doesn't exist
         let storage = self.storage.get_object_store();
@@ -824,6 +972,31 @@ impl Parseable {
         Ok(())
     }
 
+    pub async fn update_time_partition_format_in_stream(
+        &self,
+        stream_name: String,
+        time_partition_format: String,
+    ) -> Result<(), CreateStreamError> {
+        let storage = self.storage.get_object_store();
+        if let Err(err) = storage
+            .update_time_partition_format_in_stream(&stream_name, &time_partition_format)
+            .await
+        {
+            return Err(CreateStreamError::Storage { stream_name, err });
+        }
+
+        if let Ok(stream) = self.get_stream(&stream_name) {
+            stream.set_time_partition_format(time_partition_format)
+        } else {
+            return Err(CreateStreamError::Custom {
+                msg: "failed to update time partition format in metadata".to_string(),
+                status: StatusCode::EXPECTATION_FAILED,
+            });
+        }
+
+        Ok(())
+    }
+
     pub async fn update_custom_partition_in_stream(
         &self,
         stream_name: String,
@@ -1007,6 +1180,35 @@ pub fn validate_time_partition_limit(
     Ok(days)
 }
 
+pub fn validate_time_partition_format(
+    time_partition_format: &str,
+) -> Result<(), CreateStreamError> {
+    let has_error =
+        StrftimeItems::new(time_partition_format).any(|item| matches!(item, Item::Error));
+    if time_partition_format.trim().is_empty() || has_error {
+        return Err(CreateStreamError::Custom {
+            msg: format!("Invalid time partition format: {time_partition_format}"),
+            status: StatusCode::BAD_REQUEST,
+        });
+    }
+
+    Ok(())
+}
+
+pub fn validate_time_partition_path(time_partition: &str) -> Result<(), CreateStreamError> {
+    if time_partition
+        .split('.')
+        .any(|key| key.is_empty() || key.trim() != key)
+    {
+        return Err(CreateStreamError::Custom {
+            msg: format!("Invalid time partition field: {time_partition}"),
+            status: StatusCode::BAD_REQUEST,
+        });
+    }
+
+    Ok(())
+}
+
 pub fn validate_custom_partition(custom_partition: &str) -> Result<(), CreateStreamError> {
     let custom_partition_list = custom_partition.split(',').collect::<Vec<&str>>();
     if custom_partition_list.len() > 1 {
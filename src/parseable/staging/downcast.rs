@@ -0,0 +1,242 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ *
+ */
+//! Downcasting of the wide numeric columns of a stream when its staging files are converted to
+//! parquet. Numbers are inferred as Int64 or Float64 from JSON while most of them fit in 32 bits,
+//! each parquet file gets the narrowest type holding all the values of a column in that file.
+//! The stream schema keeps the wide types, queries cast the narrow columns back to them.
+//!
+//! Whether the values fit is recorded while the staging files are written, so that they are
+//! read once on conversion. Files staged before the server started have no record and are
+//! converted with the wide types.
+//!
+//! Integers aren't narrowed below Int32, parquet stores the narrower ones on 32 bits anyway.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use arrow::compute::cast;
+use arrow_array::{Array, Float64Array, Int64Array, RecordBatch};
+use arrow_schema::{ArrowError, DataType, Schema};
+
+/// Narrow type a column of type `data_type` can be downcast to
+fn narrow_type(data_type: &DataType) -> Option<DataType> {
+    match data_type {
+        DataType::Int64 => Some(DataType::Int32),
+        DataType::Float64 => Some(DataType::Float32),
+        _ => None,
+    }
+}
+
+/// Whether all the values of the column are held by its narrow type, floats have to be exact
+/// in single precision
+fn fits_narrow_type(column: &dyn Array) -> bool {
+    if let Some(values) = column.as_any().downcast_ref::<Int64Array>() {
+        values
+            .iter()
+            .flatten()
+            .all(|value| i32::try_from(value).is_ok())
+    } else if let Some(values) = column.as_any().downcast_ref::<Float64Array>() {
+        values
+            .iter()
+            .flatten()
+            .all(|value| value.is_nan() || value as f32 as f64 == value)
+    } else {
+        false
+    }
+}
+
+/// Wide numeric columns of a staging file, with the type they were written with and whether
+/// all their values fit its narrow type
+#[derive(Debug, Default)]
+pub struct ColumnFits(HashMap<String, (DataType, bool)>);
+
+impl ColumnFits {
+    /// Accounts for the values of a batch written to the staging file
+    pub fn update(&mut self, batch: &RecordBatch) {
+        for (field, column) in batch.schema().fields().iter().zip(batch.columns()) {
+            if narrow_type(field.data_type()).is_none() {
+                continue;
+            }
+            let fits = fits_narrow_type(column.as_ref());
+            self.0
+                .entry(field.name().clone())
+                .and_modify(|(_, fit)| *fit &= fits)
+                .or_insert_with(|| (field.data_type().clone(), fits));
+        }
+    }
+}
+
+/// Column fits of the finished staging files of a stream, by path
+pub type StagedColumnFits = Arc<Mutex<HashMap<PathBuf, ColumnFits>>>;
+
+/// `schema` with the wide numeric columns whose values all fit a narrower type in the staging
+/// `files` downcast to it
+pub fn narrowed_schema<'a>(
+    schema: &Schema,
+    files: impl IntoIterator<Item = &'a ColumnFits>,
+) -> Schema {
+    let mut narrowable = schema
+        .fields()
+        .iter()
+        .filter(|field| narrow_type(field.data_type()).is_some())
+        .map(|field| (field.name().as_str(), field.data_type()))
+        .collect::<HashMap<_, _>>();
+    for ColumnFits(columns) in files {
+        if narrowable.is_empty() {
+            break;
+        }
+        // the staging files of a parquet file can miss some of its columns, a column written
+        // with another type is widened on conversion and may not fit anymore
+        narrowable.retain(|name, data_type| {
+            columns
+                .get(*name)
+                .is_none_or(|(written, fits)| *fits && written == *data_type)
+        });
+    }
+
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|field| match narrow_type(field.data_type()) {
+            Some(data_type) if narrowable.contains_key(field.name().as_str()) => {
+                Arc::new(field.as_ref().clone().with_data_type(data_type))
+            }
+            _ => field.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    Schema::new_with_metadata(fields, schema.metadata().clone())
+}
+
+/// Casts the columns of `batch` to the types of the narrowed `schema`
+pub fn downcast_batch(
+    batch: &RecordBatch,
+    schema: &Arc<Schema>,
+) -> Result<RecordBatch, ArrowError> {
+    let columns = batch
+        .columns()
+        .iter()
+        .zip(schema.fields())
+        .map(|(column, field)| {
+            if column.data_type() == field.data_type() {
+                Ok(column.clone())
+            } else {
+                cast(column, field.data_type())
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    RecordBatch::try_new(schema.clone(), columns)
+}
+
+/// Columns of a stream downcast in the last parquet file they were written to
+#[derive(Debug, Default)]
+pub struct NarrowedColumns(HashSet<String>);
+
+impl NarrowedColumns {
+    /// Records the types the columns of a parquet file were written with, returns the columns
+    /// which were narrowed before and had to be widened back for a value to fit
+    pub fn record(&mut self, schema: &Schema, narrowed: &Schema) -> Vec<String> {
+        let mut widened = vec![];
+        for (field, written) in schema.fields().iter().zip(narrowed.fields()) {
+            if narrow_type(field.data_type()).is_none() {
+                continue;
+            }
+            if written.data_type() != field.data_type() {
+                self.0.insert(field.name().clone());
+            } else if self.0.remove(field.name()) {
+                widened.push(field.name().clone());
+            }
+        }
+
+        widened
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_schema::Field;
+
+    use super::*;
+
+    fn batch(count: Vec<Option<i64>>, ratio: Vec<f64>) -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("count", DataType::Int64, true),
+            Field::new("ratio", DataType::Float64, true),
+        ]);
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int64Array::from(count)),
+                Arc::new(Float64Array::from(ratio)),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn fits(batches: &[RecordBatch]) -> ColumnFits {
+        let mut fits = ColumnFits::default();
+        batches.iter().for_each(|batch| fits.update(batch));
+        fits
+    }
+
+    #[test]
+    fn columns_are_narrowed_while_all_their_values_fit() {
+        let fitting = batch(vec![Some(1), None, Some(-70_000)], vec![0.5, 0.25]);
+        let schema = fitting.schema();
+
+        let narrowed = Arc::new(narrowed_schema(&schema, [&fits(&[fitting.clone()])]));
+        assert_eq!(narrowed.field(0).data_type(), &DataType::Int32);
+        assert_eq!(narrowed.field(1).data_type(), &DataType::Float32);
+        let downcast = downcast_batch(&fitting, &narrowed).unwrap();
+        assert_eq!(downcast.schema(), narrowed);
+
+        let overflowing = batch(vec![Some(i64::from(i32::MAX) + 1)], vec![0.1]);
+        let narrowed = narrowed_schema(&schema, [&fits(&[fitting.clone()]), &fits(&[overflowing])]);
+        assert_eq!(narrowed.field(0).data_type(), &DataType::Int64);
+        assert_eq!(narrowed.field(1).data_type(), &DataType::Float64);
+
+        // an Int64 column of a file is widened to the Float64 of the merged schema
+        let floats = Schema::new(vec![
+            Field::new("count", DataType::Float64, true),
+            Field::new("ratio", DataType::Float64, true),
+        ]);
+        let narrowed = narrowed_schema(&floats, [&fits(&[fitting])]);
+        assert_eq!(narrowed.field(0).data_type(), &DataType::Float64);
+        assert_eq!(narrowed.field(1).data_type(), &DataType::Float32);
+    }
+
+    #[test]
+    fn columns_widened_back_are_reported() {
+        let mut columns = NarrowedColumns::default();
+        let schema = batch(vec![], vec![]).schema();
+        let narrowed = narrowed_schema(&schema, [&fits(&[batch(vec![Some(1)], vec![1.5])])]);
+
+        assert!(columns.record(&schema, &narrowed).is_empty());
+        assert_eq!(columns.record(&schema, &schema), ["count", "ratio"]);
+        assert!(columns.record(&schema, &schema).is_empty());
+    }
+}
@@ -17,6 +17,7 @@
  *
  */
 
+pub mod downcast;
 pub mod reader;
 pub mod writer;
 
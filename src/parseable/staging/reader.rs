@@ -27,13 +27,14 @@ use std::{
 
 use arrow_array::{RecordBatch, TimestampMillisecondArray};
 use arrow_ipc::{MessageHeader, reader::StreamReader, root_as_message_unchecked};
-use arrow_schema::Schema;
+use arrow_schema::{ArrowError, Schema};
 use byteorder::{LittleEndian, ReadBytesExt};
 use itertools::kmerge_by;
 use tracing::error;
 
 use crate::{
     event::DEFAULT_TIMESTAMP_KEY,
+    storage::type_widening::merge_schemas,
     utils::arrow::{adapt_batch, reverse},
 };
 
@@ -75,7 +76,7 @@ impl MergedRecordReader {
     }
 
     pub fn merged_schema(&self) -> Schema {
-        Schema::try_merge(
+        merge_schemas(
             self.readers
                 .iter()
                 .map(|reader| reader.schema().as_ref().clone()),
@@ -118,7 +119,7 @@ impl MergedReverseRecordReader {
         self,
         schema: Arc<Schema>,
         time_partition: Option<String>,
-    ) -> impl Iterator<Item = RecordBatch> {
+    ) -> impl Iterator<Item = Result<RecordBatch, ArrowError>> {
         let adapted_readers = self.readers.into_iter().map(|reader| reader.flatten());
         kmerge_by(adapted_readers, move |a: &RecordBatch, b: &RecordBatch| {
             // Capture time_partition by value
@@ -131,7 +132,7 @@ impl MergedReverseRecordReader {
     }
 
     pub fn merged_schema(&self) -> Schema {
-        Schema::try_merge(
+        merge_schemas(
             self.readers
                 .iter()
                 .map(|reader| reader.schema().as_ref().clone()),
@@ -508,7 +509,7 @@ mod tests {
     ) -> io::Result<()> {
         let range = TimeRange::granularity_range(Utc::now(), OBJECT_STORE_DATA_GRANULARITY);
         let mut writer =
-            DiskWriter::try_new(path, schema, range).expect("Failed to create StreamWriter");
+            DiskWriter::try_new(path, schema, range, None).expect("Failed to create StreamWriter");
 
         for batch in batches {
             writer.write(batch).expect("Failed to write batch");
@@ -568,7 +569,10 @@ mod tests {
         // But first message should be schema, so we'll still read them in order
 
         // Read batch 3
-        let batch = reader.next().expect("Failed to read batch");
+        let batch = reader
+            .next()
+            .expect("Failed to read batch")
+            .expect("Failed to adapt batch");
         assert_eq!(batch.num_rows(), 2);
         let id_array = batch
             .column(0)
@@ -579,7 +583,10 @@ mod tests {
         assert_eq!(id_array.value(1), 30);
 
         // Read batch 2
-        let batch = reader.next().expect("Failed to read batch");
+        let batch = reader
+            .next()
+            .expect("Failed to read batch")
+            .expect("Failed to adapt batch");
         assert_eq!(batch.num_rows(), 2);
         let id_array = batch
             .column(0)
@@ -590,7 +597,10 @@ mod tests {
         assert_eq!(id_array.value(1), 20);
 
         // Read batch 1
-        let batch = reader.next().expect("Failed to read batch");
+        let batch = reader
+            .next()
+            .expect("Failed to read batch")
+            .expect("Failed to adapt batch");
         assert_eq!(batch.num_rows(), 2);
         let id_array = batch
             .column(0)
@@ -666,7 +676,10 @@ mod tests {
         let mut reader = MergedReverseRecordReader::try_new(&[file_path]).merged_iter(schema, None);
 
         // Should get the batch
-        let result_batch = reader.next().expect("Failed to read batch");
+        let result_batch = reader
+            .next()
+            .expect("Failed to read batch")
+            .expect("Failed to adapt batch");
         let id_array = result_batch
             .column(0)
             .as_any()
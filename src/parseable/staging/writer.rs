@@ -27,19 +27,23 @@ use std::{
 
 use arrow_array::RecordBatch;
 use arrow_ipc::writer::StreamWriter;
-use arrow_schema::Schema;
+use arrow_schema::{ArrowError, Schema};
 use arrow_select::concat::concat_batches;
 use chrono::Utc;
-use itertools::Itertools;
 use rand::distributions::{Alphanumeric, DistString};
 use tracing::error;
 
 use crate::{
+    LOCK_EXPECT,
     parseable::{ARROW_FILE_EXTENSION, PART_FILE_EXTENSION},
+    storage::type_widening::merge_schemas,
     utils::{arrow::adapt_batch, time::TimeRange},
 };
 
-use super::StagingError;
+use super::{
+    StagingError,
+    downcast::{ColumnFits, StagedColumnFits},
+};
 
 #[derive(Default)]
 pub struct Writer {
@@ -51,14 +55,18 @@ pub struct DiskWriter {
     inner: StreamWriter<BufWriter<File>>,
     path: PathBuf,
     range: TimeRange,
+    /// fits of the wide numeric columns written, recorded once the file is finished
+    column_fits: Option<(ColumnFits, StagedColumnFits)>,
 }
 
 impl DiskWriter {
-    /// Try to create a file to stream arrows into
+    /// Try to create a file to stream arrows into, the fits of its numeric columns are recorded
+    /// into `staged_fits` if given
     pub fn try_new(
         path: impl Into<PathBuf>,
         schema: &Schema,
         range: TimeRange,
+        staged_fits: Option<StagedColumnFits>,
     ) -> Result<Self, StagingError> {
         let mut path = path.into();
         path.set_extension(PART_FILE_EXTENSION);
@@ -69,7 +77,12 @@ impl DiskWriter {
             .open(&path)?;
         let inner = StreamWriter::try_new_buffered(file, schema)?;
 
-        Ok(Self { inner, path, range })
+        Ok(Self {
+            inner,
+            path,
+            range,
+            column_fits: staged_fits.map(|staged| (ColumnFits::default(), staged)),
+        })
     }
 
     pub fn is_current(&self) -> bool {
@@ -78,7 +91,12 @@ impl DiskWriter {
 
     /// Write a single recordbatch into file
     pub fn write(&mut self, rb: &RecordBatch) -> Result<(), StagingError> {
-        self.inner.write(rb).map_err(StagingError::Arrow)
+        self.inner.write(rb).map_err(StagingError::Arrow)?;
+        if let Some((fits, _)) = &mut self.column_fits {
+            fits.update(rb);
+        }
+
+        Ok(())
     }
 }
 
@@ -106,6 +124,8 @@ impl Drop for DiskWriter {
 
         if let Err(err) = std::fs::rename(&self.path, &arrow_path) {
             error!("Couldn't rename file {:?}, error = {err}", self.path);
+        } else if let Some((fits, staged)) = self.column_fits.take() {
+            staged.lock().expect(LOCK_EXPECT).insert(arrow_path, fits);
         }
     }
 }
@@ -135,16 +155,18 @@ impl<const N: usize> Default for MemWriter<N> {
 }
 
 impl<const N: usize> MemWriter<N> {
-    pub fn push(&mut self, schema_key: &str, rb: &RecordBatch) {
+    pub fn push(&mut self, schema_key: &str, rb: &RecordBatch) -> Result<(), ArrowError> {
         if !self.schema_map.contains(schema_key) {
             self.schema_map.insert(schema_key.to_owned());
-            self.schema = Schema::try_merge([self.schema.clone(), (*rb.schema()).clone()]).unwrap();
+            self.schema = merge_schemas([self.schema.clone(), (*rb.schema()).clone()]).unwrap();
         }
 
         if let Some(record) = self.mutable_buffer.push(rb) {
-            let record = concat_records(&Arc::new(self.schema.clone()), &record);
+            let record = concat_records(&Arc::new(self.schema.clone()), &record)?;
             self.read_buffer.push(record);
         }
+
+        Ok(())
     }
 
     pub fn clear(&mut self) {
@@ -154,10 +176,10 @@ impl<const N: usize> MemWriter<N> {
         self.mutable_buffer.inner.clear();
     }
 
-    pub fn recordbatch_cloned(&self, schema: &Arc<Schema>) -> Vec<RecordBatch> {
+    pub fn recordbatch_cloned(&self, schema: &Arc<Schema>) -> Result<Vec<RecordBatch>, ArrowError> {
         let mut read_buffer = self.read_buffer.clone();
         if !self.mutable_buffer.inner.is_empty() {
-            let rb = concat_records(schema, &self.mutable_buffer.inner);
+            let rb = concat_records(schema, &self.mutable_buffer.inner)?;
             read_buffer.push(rb)
         }
 
@@ -168,9 +190,12 @@ impl<const N: usize> MemWriter<N> {
     }
 }
 
-fn concat_records(schema: &Arc<Schema>, record: &[RecordBatch]) -> RecordBatch {
-    let records = record.iter().map(|x| adapt_batch(schema, x)).collect_vec();
-    concat_batches(schema, records.iter()).unwrap()
+fn concat_records(schema: &Arc<Schema>, record: &[RecordBatch]) -> Result<RecordBatch, ArrowError> {
+    let records = record
+        .iter()
+        .map(|x| adapt_batch(schema, x))
+        .collect::<Result<Vec<_>, _>>()?;
+    concat_batches(schema, records.iter())
 }
 
 #[derive(Debug, Default)]
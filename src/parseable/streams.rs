@@ -27,7 +27,7 @@ use std::{
 };
 
 use arrow_array::RecordBatch;
-use arrow_schema::{Field, Fields, Schema};
+use arrow_schema::{ArrowError, DataType, Field, Fields, Schema};
 use chrono::{NaiveDateTime, Timelike, Utc};
 use derive_more::derive::{Deref, DerefMut};
 use itertools::Itertools;
@@ -35,7 +35,10 @@ use parquet::{
     arrow::ArrowWriter,
     basic::Encoding,
     file::{
-        FOOTER_SIZE, metadata::SortingColumn, properties::WriterProperties, reader::FileReader,
+        FOOTER_SIZE,
+        metadata::SortingColumn,
+        properties::{EnabledStatistics, WriterProperties},
+        reader::FileReader,
         serialized_reader::SerializedFileReader,
     },
     schema::types::ColumnPath,
@@ -52,11 +55,26 @@ use crate::{
         DEFAULT_TIMESTAMP_KEY,
         format::{LogSource, LogSourceEntry},
     },
+    handlers::TelemetryType,
     hottier::StreamHotTier,
     metadata::{LogStreamMetadata, SchemaVersion},
     metrics,
     option::Mode,
-    storage::{StreamType, object_storage::to_bytes, retention::Retention},
+    storage::{
+        StreamType,
+        late_events::LateEventWindow,
+        masking::MaskingConfig,
+        object_storage::to_bytes,
+        partition_expression::PartitionExpression,
+        reserved_fields::ReservedFieldPolicy,
+        retention::Retention,
+        sampling::SamplingRate,
+        timestamp_policy::TimestampPolicy,
+        transforms::TransformRule,
+        type_coercion::TypeCoercionPolicy,
+        type_widening::{TypeWideningPolicy, merge_schemas},
+        upload_retry::QUARANTINE_DIR,
+    },
     utils::time::{Minute, TimeRange},
 };
 
@@ -64,6 +82,7 @@ use super::{
     ARROW_FILE_EXTENSION, LogStream,
     staging::{
         StagingError,
+        downcast::{NarrowedColumns, StagedColumnFits, downcast_batch, narrowed_schema},
         reader::{MergedRecordReader, MergedReverseRecordReader},
         writer::{DiskWriter, Writer},
     },
@@ -71,6 +90,9 @@ use super::{
 
 const INPROCESS_DIR_PREFIX: &str = "processing_";
 
+/// Custom partition value of the events of streams past `P_MAX_CUSTOM_PARTITIONS`
+pub const OVERFLOW_PARTITION: &str = "__overflow__";
+
 /// Returns the filename for parquet if provided arrows file path is valid as per our expectation
 fn arrow_path_to_parquet(
     stream_staging_path: &Path,
@@ -111,6 +133,13 @@ pub struct Stream {
     pub options: Arc<Options>,
     pub writer: Mutex<Writer>,
     pub ingestor_id: Option<String>,
+    /// distinct custom partition values ingested since the server started
+    custom_partitions: Mutex<HashSet<String>>,
+    /// numeric columns downcast in the last parquet file written
+    narrowed_columns: Mutex<NarrowedColumns>,
+    /// whether the numeric values of the arrow files staged since the server started fit
+    /// narrower types
+    staged_column_fits: StagedColumnFits,
 }
 
 impl Stream {
@@ -130,9 +159,52 @@ impl Stream {
             options,
             writer: Mutex::new(Writer::default()),
             ingestor_id,
+            custom_partitions: Mutex::new(HashSet::new()),
+            narrowed_columns: Mutex::new(NarrowedColumns::default()),
+            staged_column_fits: StagedColumnFits::default(),
         })
     }
 
+    /// Keeps track of the custom partition values of the stream, once there are as many as
+    /// `P_MAX_CUSTOM_PARTITIONS` events with new values are routed to the overflow partition
+    pub fn route_custom_partition(&self, custom_partition_values: &mut HashMap<String, String>) {
+        if custom_partition_values.is_empty() {
+            return;
+        }
+        let partition = custom_partition_values
+            .iter()
+            .sorted_by_key(|(key, _)| *key)
+            .map(|(key, value)| format!("{key}={value}"))
+            .join("&");
+
+        let mut custom_partitions = self.custom_partitions.lock().expect(LOCK_EXPECT);
+        if custom_partitions.contains(&partition) {
+            return;
+        }
+        match self.options.max_custom_partitions {
+            Some(max) if custom_partitions.len() as u64 >= max => {
+                let overflow =
+                    metrics::CUSTOM_PARTITION_OVERFLOW.with_label_values(&[&self.stream_name]);
+                if overflow.get() == 0 {
+                    warn!(
+                        "Stream {} reached {max} custom partitions, events of new partitions are stored in the {OVERFLOW_PARTITION} partition",
+                        self.stream_name
+                    );
+                }
+                overflow.inc();
+                custom_partition_values
+                    .values_mut()
+                    .for_each(|value| *value = OVERFLOW_PARTITION.to_owned());
+            }
+            _ => {
+                custom_partitions.insert(partition);
+                metrics::CUSTOM_PARTITIONS
+                    .with_label_values(&[&self.stream_name])
+                    .set(custom_partitions.len() as i64);
+            }
+        }
+    }
+
     // Concatenates record batches and puts them in memory store for each event.
     pub fn push(
         &self,
@@ -168,8 +240,12 @@ impl Stream {
                         OBJECT_STORE_DATA_GRANULARITY,
                     );
                     let file_path = self.data_path.join(&filename);
-                    let mut writer = DiskWriter::try_new(file_path, &record.schema(), range)
-                        .expect("File and RecordBatch both are checked");
+                    let staged_fits = self
+                        .get_numeric_downcast()
+                        .then(|| self.staged_column_fits.clone());
+                    let mut writer =
+                        DiskWriter::try_new(file_path, &record.schema(), range, staged_fits)
+                            .expect("File and RecordBatch both are checked");
 
                     writer.write(record)?;
                     guard.disk.insert(filename, writer);
@@ -177,7 +253,7 @@ impl Stream {
             };
         }
 
-        guard.mem.push(schema_key, record);
+        guard.mem.push(schema_key, record)?;
 
         Ok(())
     }
@@ -387,6 +463,11 @@ impl Stream {
                             arrow_file_path.display(),
                             new_path.display()
                         );
+                    } else {
+                        let mut staged = self.staged_column_fits.lock().expect(LOCK_EXPECT);
+                        if let Some(fits) = staged.remove(&arrow_file_path) {
+                            staged.insert(new_path, fits);
+                        }
                     }
                 }
                 Err(e) => {
@@ -417,6 +498,18 @@ impl Stream {
             .collect()
     }
 
+    /// Parquet files moved out of staging after repeated upload failures
+    pub fn quarantined_files(&self) -> Vec<PathBuf> {
+        let Ok(dir) = self.data_path.join(QUARANTINE_DIR).read_dir() else {
+            return vec![];
+        };
+
+        dir.flatten()
+            .map(|file| file.path())
+            .filter(|file| file.extension().is_some_and(|ext| ext.eq("parquet")))
+            .collect()
+    }
+
     pub fn schema_files(&self) -> Vec<PathBuf> {
         let Ok(dir) = self.data_path.read_dir() else {
             return vec![];
@@ -493,7 +586,7 @@ impl Stream {
                 let staging_schemas = self.get_schemas_if_present();
                 if let Some(mut staging_schemas) = staging_schemas {
                     staging_schemas.push(schema);
-                    schema = Schema::try_merge(staging_schemas)?;
+                    schema = merge_schemas(staging_schemas)?;
                 }
 
                 // save the merged schema on staging disk
@@ -506,7 +599,10 @@ impl Stream {
         Ok(())
     }
 
-    pub fn recordbatches_cloned(&self, schema: &Arc<Schema>) -> Vec<RecordBatch> {
+    pub fn recordbatches_cloned(
+        &self,
+        schema: &Arc<Schema>,
+    ) -> Result<Vec<RecordBatch>, ArrowError> {
         self.writer.lock().unwrap().mem.recordbatch_cloned(schema)
     }
 
@@ -544,7 +640,10 @@ impl Stream {
         let time_partition_idx = merged_schema.index_of(time_partition_field).unwrap_or(0);
 
         let mut props = WriterProperties::builder()
-            .set_max_row_group_size(self.options.row_group_size)
+            .set_max_row_group_size(
+                self.get_row_group_size()
+                    .unwrap_or(self.options.row_group_size),
+            )
             .set_compression(self.options.parquet_compression.into())
             .set_column_encoding(
                 ColumnPath::new(vec![time_partition_field.to_string()]),
@@ -574,6 +673,17 @@ impl Stream {
             }
         }
 
+        // Indexed columns get page level statistics and bloom filters,
+        // so that row groups and pages can be pruned when filtering on them
+        for column in self.get_indexed_columns() {
+            if merged_schema.index_of(&column).is_ok() {
+                let column_path = ColumnPath::new(vec![column]);
+                props = props
+                    .set_column_statistics_enabled(column_path.clone(), EnabledStatistics::Page)
+                    .set_column_bloom_filter_enabled(column_path, true);
+            }
+        }
+
         // Set sorting columns
         props.set_sorting_columns(Some(sorting_column_vec)).build()
     }
@@ -640,17 +750,27 @@ impl Stream {
             let props = self.parquet_writer_props(&merged_schema, time_partition, custom_partition);
             schemas.push(merged_schema.clone());
             let schema = Arc::new(merged_schema);
+            // the stream schema keeps the wide types, only the parquet file is narrowed
+            let file_schema = if self.get_numeric_downcast() {
+                Arc::new(self.narrowed_schema(&schema, &arrow_files))
+            } else {
+                schema.clone()
+            };
 
             let part_path = parquet_path.with_extension("part");
             if !self.write_parquet_part_file(
                 &part_path,
                 record_reader,
                 &schema,
+                &file_schema,
                 &props,
                 time_partition,
             )? {
                 continue;
             }
+            if self.get_numeric_downcast() {
+                self.record_narrowed_columns(&schema, &file_schema);
+            }
 
             if let Err(e) = std::fs::rename(&part_path, &parquet_path) {
                 error!("Couldn't rename part file: {part_path:?} -> {parquet_path:?}, error = {e}");
@@ -663,7 +783,21 @@ impl Stream {
             return Ok(None);
         }
 
-        Ok(Some(Schema::try_merge(schemas)?))
+        Ok(Some(merge_schemas(schemas)?))
+    }
+
+    /// `schema` narrowed as per the fits recorded while the arrow files were written, the
+    /// parquet file is kept wide if any of them was staged without recording them
+    fn narrowed_schema(&self, schema: &Schema, arrow_files: &[PathBuf]) -> Schema {
+        let staged = self.staged_column_fits.lock().expect(LOCK_EXPECT);
+        match arrow_files
+            .iter()
+            .map(|file| staged.get(file))
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(fits) => narrowed_schema(schema, fits),
+            None => schema.clone(),
+        }
     }
 
     fn write_parquet_part_file(
@@ -671,6 +805,7 @@ impl Stream {
         part_path: &Path,
         record_reader: MergedReverseRecordReader,
         schema: &Arc<Schema>,
+        file_schema: &Arc<Schema>,
         props: &WriterProperties,
         time_partition: Option<&String>,
     ) -> Result<bool, StagingError> {
@@ -679,9 +814,16 @@ impl Stream {
             .append(true)
             .open(part_path)
             .map_err(|_| StagingError::Create)?;
-        let mut writer = ArrowWriter::try_new(&mut part_file, schema.clone(), Some(props.clone()))?;
-        for ref record in record_reader.merged_iter(schema.clone(), time_partition.cloned()) {
-            writer.write(record)?;
+        let mut writer =
+            ArrowWriter::try_new(&mut part_file, file_schema.clone(), Some(props.clone()))?;
+        let downcast = file_schema != schema;
+        for record in record_reader.merged_iter(schema.clone(), time_partition.cloned()) {
+            let record = record?;
+            if downcast {
+                writer.write(&downcast_batch(&record, file_schema)?)?;
+            } else {
+                writer.write(&record)?;
+            }
         }
         writer.close()?;
 
@@ -697,6 +839,29 @@ impl Stream {
         Ok(true)
     }
 
+    /// Keeps track of the numeric columns written narrowed, a column written with its wide type
+    /// again after being narrowed is counted as widened
+    fn record_narrowed_columns(&self, schema: &Schema, file_schema: &Schema) {
+        let widened = self
+            .narrowed_columns
+            .lock()
+            .expect(LOCK_EXPECT)
+            .record(schema, file_schema);
+        for column in widened {
+            info!(
+                "Column {column} of stream {} widened back to {}, a value didn't fit the downcast type",
+                self.stream_name,
+                schema
+                    .field_with_name(&column)
+                    .map(|field| field.data_type().to_string())
+                    .unwrap_or_default()
+            );
+            metrics::NUMERIC_COLUMNS_WIDENED
+                .with_label_values(&[&self.stream_name])
+                .inc();
+        }
+    }
+
     /// function to validate parquet files
     fn is_valid_parquet_file(path: &Path, stream_name: &str) -> bool {
         // First check file size as a quick validation
@@ -742,6 +907,10 @@ impl Stream {
 
     fn cleanup_arrow_files_and_dir(&self, arrow_files: &[PathBuf]) {
         for (i, file) in arrow_files.iter().enumerate() {
+            self.staged_column_fits
+                .lock()
+                .expect(LOCK_EXPECT)
+                .remove(file);
             match file.metadata() {
                 Ok(meta) => {
                     let file_size = meta.len();
@@ -800,7 +969,7 @@ impl Stream {
 
         let schema = record_reader.merged_schema();
 
-        Schema::try_merge(vec![schema, current_schema]).unwrap()
+        merge_schemas(vec![schema, current_schema]).unwrap()
     }
 
     /// Stores the provided stream metadata in memory mapping
@@ -831,6 +1000,14 @@ impl Stream {
             .time_partition_limit
     }
 
+    pub fn get_time_partition_format(&self) -> Option<String> {
+        self.metadata
+            .read()
+            .expect(LOCK_EXPECT)
+            .time_partition_format
+            .clone()
+    }
+
     pub fn get_custom_partition(&self) -> Option<String> {
         self.metadata
             .read()
@@ -843,10 +1020,118 @@ impl Stream {
         self.metadata.read().expect(LOCK_EXPECT).static_schema_flag
     }
 
+    pub fn get_telemetry_type(&self) -> TelemetryType {
+        self.metadata.read().expect(LOCK_EXPECT).telemetry_type
+    }
+
     pub fn get_retention(&self) -> Option<Retention> {
         self.metadata.read().expect(LOCK_EXPECT).retention.clone()
     }
 
+    pub fn get_masking(&self) -> Option<MaskingConfig> {
+        self.metadata.read().expect(LOCK_EXPECT).masking.clone()
+    }
+
+    pub fn get_sampling_rate(&self) -> Option<SamplingRate> {
+        self.metadata.read().expect(LOCK_EXPECT).sampling_rate
+    }
+
+    pub fn get_indexed_columns(&self) -> Vec<String> {
+        self.metadata
+            .read()
+            .expect(LOCK_EXPECT)
+            .indexed_columns
+            .clone()
+    }
+
+    pub fn get_reserved_field_policy(&self) -> ReservedFieldPolicy {
+        self.metadata
+            .read()
+            .expect(LOCK_EXPECT)
+            .reserved_field_policy
+    }
+
+    pub fn get_type_coercion_policy(&self) -> TypeCoercionPolicy {
+        self.metadata
+            .read()
+            .expect(LOCK_EXPECT)
+            .type_coercion_policy
+    }
+
+    pub fn get_type_widening_policy(&self) -> TypeWideningPolicy {
+        self.metadata
+            .read()
+            .expect(LOCK_EXPECT)
+            .type_widening_policy
+    }
+
+    pub fn get_timestamp_policy(&self) -> TimestampPolicy {
+        self.metadata
+            .read()
+            .expect(LOCK_EXPECT)
+            .timestamp_policy
+            .clone()
+    }
+
+    pub fn get_row_group_size(&self) -> Option<usize> {
+        self.metadata.read().expect(LOCK_EXPECT).row_group_size
+    }
+
+    pub fn get_numeric_downcast(&self) -> bool {
+        self.metadata.read().expect(LOCK_EXPECT).numeric_downcast
+    }
+
+    pub fn get_late_event_window(&self) -> Option<LateEventWindow> {
+        self.metadata
+            .read()
+            .expect(LOCK_EXPECT)
+            .late_event_window
+            .clone()
+    }
+
+    pub fn get_schema_frozen(&self) -> bool {
+        self.metadata.read().expect(LOCK_EXPECT).schema_frozen
+    }
+
+    pub fn get_read_only(&self) -> bool {
+        self.metadata.read().expect(LOCK_EXPECT).read_only
+    }
+
+    pub fn get_timestamp_column(&self) -> Option<String> {
+        self.metadata
+            .read()
+            .expect(LOCK_EXPECT)
+            .timestamp_column
+            .clone()
+    }
+
+    /// Column holding the event time used by default time range filters,
+    /// the time partition if any, then the configured timestamp column
+    pub fn get_time_column(&self) -> Option<String> {
+        self.get_time_partition()
+            .or_else(|| self.get_timestamp_column())
+    }
+
+    pub fn get_column_aliases(&self) -> HashMap<String, String> {
+        self.metadata
+            .read()
+            .expect(LOCK_EXPECT)
+            .column_aliases
+            .clone()
+    }
+
+    pub fn get_partition_expressions(&self) -> HashMap<String, PartitionExpression> {
+        self.metadata
+            .read()
+            .expect(LOCK_EXPECT)
+            .partition_expressions
+            .clone()
+    }
+
+    pub fn get_transforms(&self) -> Vec<TransformRule> {
+        self.metadata.read().expect(LOCK_EXPECT).transforms.clone()
+    }
+
     pub fn get_schema_version(&self) -> SchemaVersion {
         self.metadata.read().expect(LOCK_EXPECT).schema_version
     }
@@ -870,10 +1155,124 @@ impl Stream {
         self.metadata.read().expect(LOCK_EXPECT).schema.clone()
     }
 
+    /// Widens the integer `columns` of the schema to Float64. The staged arrow files are
+    /// finished first so that each of them has a single type per column.
+    pub fn widen_columns(&self, columns: &[String]) {
+        let mut writer = self.writer.lock().expect(LOCK_EXPECT);
+        writer.mem.clear();
+        // dropping the disk writers finishes their files
+        writer.disk.clear();
+
+        let mut metadata = self.metadata.write().expect(LOCK_EXPECT);
+        for column in columns {
+            let Some(field) = metadata.schema.get_mut(column) else {
+                continue;
+            };
+            if !field.data_type().is_integer() {
+                continue;
+            }
+            info!(
+                "Column {column} of stream {} widened from {} to Float64",
+                self.stream_name,
+                field.data_type()
+            );
+            *field = Arc::new(field.as_ref().clone().with_data_type(DataType::Float64));
+            metrics::SCHEMA_COLUMNS_WIDENED
+                .with_label_values(&[&self.stream_name])
+                .inc();
+        }
+    }
+
     pub fn set_retention(&self, retention: Retention) {
         self.metadata.write().expect(LOCK_EXPECT).retention = Some(retention);
     }
 
+    pub fn set_masking(&self, masking: MaskingConfig) {
+        self.metadata.write().expect(LOCK_EXPECT).masking = Some(masking);
+    }
+
+    pub fn set_sampling_rate(&self, sampling_rate: Option<SamplingRate>) {
+        self.metadata.write().expect(LOCK_EXPECT).sampling_rate = sampling_rate;
+    }
+
+    pub fn set_indexed_columns(&self, indexed_columns: Vec<String>) {
+        self.metadata.write().expect(LOCK_EXPECT).indexed_columns = indexed_columns;
+    }
+
+    pub fn set_reserved_field_policy(&self, reserved_field_policy: ReservedFieldPolicy) {
+        self.metadata
+            .write()
+            .expect(LOCK_EXPECT)
+            .reserved_field_policy = reserved_field_policy;
+    }
+
+    pub fn set_type_coercion_policy(&self, type_coercion_policy: TypeCoercionPolicy) {
+        self.metadata
+            .write()
+            .expect(LOCK_EXPECT)
+            .type_coercion_policy = type_coercion_policy;
+    }
+
+    pub fn set_type_widening_policy(&self, type_widening_policy: TypeWideningPolicy) {
+        self.metadata
+            .write()
+            .expect(LOCK_EXPECT)
+            .type_widening_policy = type_widening_policy;
+    }
+
+    pub fn set_timestamp_policy(&self, timestamp_policy: TimestampPolicy) {
+        self.metadata.write().expect(LOCK_EXPECT).timestamp_policy = timestamp_policy;
+    }
+
+    pub fn set_row_group_size(&self, row_group_size: Option<usize>) {
+        self.metadata.write().expect(LOCK_EXPECT).row_group_size = row_group_size;
+    }
+
+    pub fn set_numeric_downcast(&self, numeric_downcast: bool) {
+        self.metadata.write().expect(LOCK_EXPECT).numeric_downcast = numeric_downcast;
+        if !numeric_downcast {
+            self.narrowed_columns.lock().expect(LOCK_EXPECT).clear();
+        }
+    }
+
+    pub fn set_late_event_window(&self, late_event_window: Option<LateEventWindow>) {
+        self.metadata.write().expect(LOCK_EXPECT).late_event_window = late_event_window;
+    }
+
+    pub fn set_schema_frozen(&self, schema_frozen: bool) {
+        self.metadata.write().expect(LOCK_EXPECT).schema_frozen = schema_frozen;
+    }
+
+    pub fn set_read_only(&self, read_only: bool) {
+        self.metadata.write().expect(LOCK_EXPECT).read_only = read_only;
+    }
+
+    pub fn set_timestamp_column(&self, timestamp_column: Option<String>) {
+        self.metadata.write().expect(LOCK_EXPECT).timestamp_column = timestamp_column;
+    }
+
+    pub fn add_column_alias(&self, alias: String, column: String) {
+        self.metadata
+            .write()
+            .expect(LOCK_EXPECT)
+            .column_aliases
+            .insert(alias, column);
+    }
+
+    pub fn set_partition_expressions(
+        &self,
+        partition_expressions: HashMap<String, PartitionExpression>,
+    ) {
+        self.metadata
+            .write()
+            .expect(LOCK_EXPECT)
+            .partition_expressions = partition_expressions;
+    }
+
+    pub fn set_transforms(&self, transforms: Vec<TransformRule>) {
+        self.metadata.write().expect(LOCK_EXPECT).transforms = transforms;
+    }
+
     pub fn set_first_event_at(&self, first_event_at: &str) {
         self.metadata.write().expect(LOCK_EXPECT).first_event_at = Some(first_event_at.to_owned());
     }
@@ -917,6 +1316,13 @@ impl Stream {
             .time_partition_limit = Some(time_partition_limit);
     }
 
+    pub fn set_time_partition_format(&self, time_partition_format: String) {
+        self.metadata
+            .write()
+            .expect(LOCK_EXPECT)
+            .time_partition_format = Some(time_partition_format);
+    }
+
     pub fn set_custom_partition(&self, custom_partition: Option<&String>) {
         self.metadata.write().expect(LOCK_EXPECT).custom_partition = custom_partition.cloned();
     }
@@ -1217,6 +1623,31 @@ mod tests {
         assert!(files.is_empty());
     }
 
+    #[test]
+    fn new_custom_partitions_overflow_past_limit() {
+        let options = Options {
+            max_custom_partitions: Some(2),
+            ..Default::default()
+        };
+        let stream = Stream::new(
+            Arc::new(options),
+            "test_stream_custom_partitions",
+            LogStreamMetadata::default(),
+            None,
+        );
+        let route = |tenant: &str| {
+            let mut values = HashMap::from([("tenant".to_owned(), tenant.to_owned())]);
+            stream.route_custom_partition(&mut values);
+            values["tenant"].clone()
+        };
+
+        assert_eq!(route("a"), "a");
+        assert_eq!(route("b"), "b");
+        assert_eq!(route("c"), OVERFLOW_PARTITION);
+        // partitions seen before the limit was reached are kept
+        assert_eq!(route("a"), "a");
+    }
+
     #[test]
     fn generate_correct_path_with_current_time_and_no_custom_partitioning() {
         let stream_name = "test_stream";
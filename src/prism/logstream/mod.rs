@@ -186,10 +186,26 @@ pub async fn get_stream_info_helper(stream_name: &str) -> Result<StreamInfo, Str
         time_partition_limit: stream_meta
             .time_partition_limit
             .map(|limit| limit.to_string()),
+        time_partition_format: stream_meta.time_partition_format.clone(),
         custom_partition: stream_meta.custom_partition.clone(),
         static_schema_flag: stream_meta.static_schema_flag,
         log_source: stream_meta.log_source.clone(),
         telemetry_type: stream_meta.telemetry_type,
+        sampling_rate: stream_meta.sampling_rate,
+        indexed_columns: stream_meta.indexed_columns.clone(),
+        reserved_field_policy: stream_meta.reserved_field_policy,
+        type_coercion_policy: stream_meta.type_coercion_policy,
+        type_widening_policy: stream_meta.type_widening_policy,
+        timestamp_policy: stream_meta.timestamp_policy.clone(),
+        row_group_size: stream_meta.row_group_size,
+        schema_frozen: stream_meta.schema_frozen,
+        numeric_downcast: stream_meta.numeric_downcast,
+        late_event_window: stream_meta.late_event_window.clone(),
+        read_only: stream_meta.read_only,
+        timestamp_column: stream_meta.timestamp_column.clone(),
+        column_aliases: stream_meta.column_aliases.clone(),
+        partition_expressions: stream_meta.partition_expressions.clone(),
+        transforms: stream_meta.transforms.clone(),
     };
 
     Ok(stream_info)
@@ -0,0 +1,151 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Column aliases of streams, added when a column is renamed. Queries can reference a column
+//! by its alias, references are rewritten to the stored column name before planning.
+
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+
+use datafusion::sql::sqlparser::ast::{
+    Expr, Ident, SelectItem, SetExpr, Statement, visit_expressions_mut,
+};
+use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
+use datafusion::sql::sqlparser::parser::{Parser, ParserError};
+
+use crate::parseable::PARSEABLE;
+
+use super::resolve_stream_names;
+
+/// Rewrites the references to column aliases of the queried streams, the SQL is returned
+/// untouched when none of the streams has aliases
+pub fn resolve_column_aliases(sql: &str) -> Result<String, anyhow::Error> {
+    let mut aliases = HashMap::new();
+    for stream in resolve_stream_names(sql)? {
+        if let Ok(stream) = PARSEABLE.get_stream(&stream) {
+            for (alias, column) in stream.get_column_aliases() {
+                aliases.entry(alias).or_insert(column);
+            }
+        }
+    }
+    if aliases.is_empty() {
+        return Ok(sql.to_owned());
+    }
+
+    Ok(rewrite_column_aliases(sql, &aliases)?)
+}
+
+/// Replaces the identifiers matching an alias in `aliases` (alias -> column) with the column,
+/// selected aliases keep their name in the result
+pub fn rewrite_column_aliases(
+    sql: &str,
+    aliases: &HashMap<String, String>,
+) -> Result<String, ParserError> {
+    let normalized_sql = sql.replace('`', "\"");
+    let mut statements = Parser::parse_sql(&PostgreSqlDialect {}, &normalized_sql)?;
+
+    for statement in statements.iter_mut() {
+        if let Statement::Query(query) = statement {
+            keep_selected_alias_names(&mut query.body, aliases);
+        }
+        let _ = visit_expressions_mut(statement, |expr| {
+            match expr {
+                Expr::Identifier(ident) => rename(ident, aliases),
+                Expr::CompoundIdentifier(idents) => {
+                    if let Some(ident) = idents.last_mut() {
+                        rename(ident, aliases);
+                    }
+                }
+                _ => {}
+            }
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    Ok(statements
+        .iter()
+        .map(Statement::to_string)
+        .collect::<Vec<_>>()
+        .join("; "))
+}
+
+fn keep_selected_alias_names(body: &mut SetExpr, aliases: &HashMap<String, String>) {
+    match body {
+        SetExpr::Select(select) => {
+            for item in select.projection.iter_mut() {
+                let SelectItem::UnnamedExpr(expr) = item else {
+                    continue;
+                };
+                let alias = match expr {
+                    Expr::Identifier(ident) => Some(ident),
+                    Expr::CompoundIdentifier(idents) => idents.last(),
+                    _ => None,
+                }
+                .filter(|ident| aliases.contains_key(&ident.value))
+                .cloned();
+                if let Some(alias) = alias {
+                    *item = SelectItem::ExprWithAlias {
+                        expr: expr.clone(),
+                        alias,
+                    };
+                }
+            }
+        }
+        SetExpr::SetOperation { left, right, .. } => {
+            keep_selected_alias_names(left, aliases);
+            keep_selected_alias_names(right, aliases);
+        }
+        SetExpr::Query(query) => keep_selected_alias_names(&mut query.body, aliases),
+        _ => {}
+    }
+}
+
+fn rename(ident: &mut Ident, aliases: &HashMap<String, String>) {
+    if let Some(column) = aliases.get(&ident.value) {
+        *ident = Ident::with_quote('"', column);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aliases() -> HashMap<String, String> {
+        HashMap::from([("status".to_owned(), "status_code".to_owned())])
+    }
+
+    #[test]
+    fn rewrites_alias_references() {
+        let sql = rewrite_column_aliases(
+            "SELECT status, count(*) FROM app WHERE app.status >= 500 GROUP BY status",
+            &aliases(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            sql,
+            r#"SELECT "status_code" AS status, count(*) FROM app WHERE app."status_code" >= 500 GROUP BY "status_code""#
+        );
+    }
+
+    #[test]
+    fn leaves_other_columns_untouched() {
+        let sql = "SELECT status_code, host FROM app WHERE host = 'status'";
+        assert_eq!(rewrite_column_aliases(sql, &aliases()).unwrap(), sql);
+    }
+}
@@ -0,0 +1,227 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::Value;
+use tracing::info;
+use ulid::Ulid;
+
+use crate::metrics::QUERY_JOB_COUNT;
+use crate::parseable::PARSEABLE;
+
+/// Queries submitted for asynchronous execution on this node, bounded by `--query-jobs-max`
+pub static QUERY_JOBS: Lazy<QueryJobs> = Lazy::new(|| {
+    QueryJobs::new(
+        PARSEABLE.options.query_jobs_max,
+        Duration::from_secs(PARSEABLE.options.query_jobs_ttl),
+    )
+});
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase", tag = "status")]
+pub enum JobStatus {
+    Running,
+    Completed { result: Value },
+    Failed { error: String },
+}
+
+impl JobStatus {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::Completed { .. } => "completed",
+            Self::Failed { .. } => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryJob {
+    pub id: Ulid,
+    #[serde(skip)]
+    owner: String,
+    pub submitted_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub status: JobStatus,
+    #[serde(skip)]
+    finished_at: Option<Instant>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Too many query jobs on this node, retry once running jobs complete")]
+pub struct JobsFull;
+
+pub struct QueryJobs {
+    max: usize,
+    // completed and failed jobs are dropped once this old
+    ttl: Duration,
+    jobs: Mutex<HashMap<Ulid, QueryJob>>,
+}
+
+impl QueryJobs {
+    fn new(max: usize, ttl: Duration) -> Self {
+        Self {
+            max,
+            ttl,
+            jobs: Mutex::default(),
+        }
+    }
+
+    /// Registers a running job of `owner`, rejected when the node already holds the maximum number of jobs
+    pub fn submit(&self, owner: &str) -> Result<Ulid, JobsFull> {
+        let mut jobs = self.jobs.lock().expect("lock poisoned");
+        if jobs.len() >= self.max {
+            self.evict_expired(&mut jobs, Instant::now());
+            if jobs.len() >= self.max {
+                return Err(JobsFull);
+            }
+        }
+
+        let id = Ulid::new();
+        jobs.insert(
+            id,
+            QueryJob {
+                id,
+                owner: owner.to_owned(),
+                submitted_at: Utc::now(),
+                status: JobStatus::Running,
+                finished_at: None,
+            },
+        );
+        update_metrics(&jobs);
+        Ok(id)
+    }
+
+    /// Records the outcome of a job
+    pub fn finish(&self, id: Ulid, result: Result<Value, String>) {
+        let mut jobs = self.jobs.lock().expect("lock poisoned");
+        if let Some(job) = jobs.get_mut(&id) {
+            job.status = match result {
+                Ok(result) => JobStatus::Completed { result },
+                Err(error) => JobStatus::Failed { error },
+            };
+            job.finished_at = Some(Instant::now());
+        }
+        update_metrics(&jobs);
+    }
+
+    /// A job, only visible to the user who submitted it
+    pub fn get(&self, id: Ulid, owner: &str) -> Option<QueryJob> {
+        self.jobs
+            .lock()
+            .expect("lock poisoned")
+            .get(&id)
+            .filter(|job| job.owner == owner)
+            .cloned()
+    }
+
+    /// Drops the completed and failed jobs older than the ttl, returns the number of jobs dropped
+    pub fn sweep(&self) -> usize {
+        let mut jobs = self.jobs.lock().expect("lock poisoned");
+        let evicted = self.evict_expired(&mut jobs, Instant::now());
+        update_metrics(&jobs);
+        evicted
+    }
+
+    fn evict_expired(&self, jobs: &mut HashMap<Ulid, QueryJob>, now: Instant) -> usize {
+        let before = jobs.len();
+        jobs.retain(|_, job| {
+            job.finished_at
+                .is_none_or(|finished_at| now.duration_since(finished_at) < self.ttl)
+        });
+        before - jobs.len()
+    }
+}
+
+fn update_metrics(jobs: &HashMap<Ulid, QueryJob>) {
+    for status in ["running", "completed", "failed"] {
+        let count = jobs
+            .values()
+            .filter(|job| job.status.label() == status)
+            .count();
+        QUERY_JOB_COUNT
+            .with_label_values(&[status])
+            .set(count as i64);
+    }
+}
+
+/// Periodically drops the expired query jobs
+pub fn init_query_jobs_sweeper() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let evicted = QUERY_JOBS.sweep();
+            if evicted > 0 {
+                info!("evicted {evicted} expired query jobs");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn rejects_submissions_when_full() {
+        let jobs = QueryJobs::new(2, Duration::from_secs(60));
+        let first = jobs.submit("alice").unwrap();
+        jobs.submit("alice").unwrap();
+        assert!(jobs.submit("bob").is_err());
+
+        // finished jobs are kept until they expire
+        jobs.finish(first, Ok(json!([])));
+        assert!(jobs.submit("bob").is_err());
+    }
+
+    #[test]
+    fn evicts_expired_jobs() {
+        let jobs = QueryJobs::new(2, Duration::ZERO);
+        let first = jobs.submit("alice").unwrap();
+        let second = jobs.submit("alice").unwrap();
+        jobs.finish(first, Err("failed".to_owned()));
+
+        assert!(jobs.submit("bob").is_ok());
+        assert!(jobs.get(first, "alice").is_none());
+        assert!(jobs.get(second, "alice").is_some());
+        assert_eq!(jobs.sweep(), 0);
+    }
+
+    #[test]
+    fn jobs_are_visible_to_their_owner_only() {
+        let jobs = QueryJobs::new(1, Duration::from_secs(60));
+        let id = jobs.submit("alice").unwrap();
+        jobs.finish(id, Ok(json!([{"count": 1}])));
+
+        assert!(jobs.get(id, "bob").is_none());
+        let job = jobs.get(id, "alice").unwrap();
+        assert!(matches!(job.status, JobStatus::Completed { .. }));
+    }
+}
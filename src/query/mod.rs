@@ -16,25 +16,34 @@
  *
  */
 
+pub mod column_aliases;
 mod filter_optimizer;
+pub mod jobs;
 mod listing_table_builder;
+pub mod result_cache;
+pub mod sampling;
+pub mod scatter_gather;
 pub mod stream_schema_provider;
 
 use actix_web::Either;
+use arrow_schema::Schema;
 use chrono::NaiveDateTime;
 use chrono::{DateTime, Duration, Utc};
 use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::common::tree_node::Transformed;
-use datafusion::execution::disk_manager::DiskManager;
+use datafusion::error::DataFusionError;
+use datafusion::execution::disk_manager::{DiskManager, DiskManagerMode};
 use datafusion::execution::{SendableRecordBatchStream, SessionState, SessionStateBuilder};
 use datafusion::logical_expr::expr::Alias;
 use datafusion::logical_expr::{
     Aggregate, Explain, Filter, LogicalPlan, PlanType, Projection, ToStringifiedPlan,
 };
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
 use datafusion::prelude::*;
 use datafusion::sql::parser::DFParser;
 use datafusion::sql::resolve::resolve_table_references;
 use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
+use futures::StreamExt;
 use itertools::Itertools;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
@@ -43,10 +52,13 @@ use std::ops::Bound;
 use std::sync::Arc;
 use sysinfo::System;
 use tokio::runtime::Runtime;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
 
 use self::error::ExecuteError;
-use self::stream_schema_provider::GlobalSchemaProvider;
+use self::sampling::{SampleFraction, scale_aggregates};
 pub use self::stream_schema_provider::PartialTimeFilter;
+use self::stream_schema_provider::{GlobalSchemaProvider, StagingSchemaProvider};
 use crate::alerts::alert_structs::Conditions;
 use crate::alerts::alerts_utils::get_filter_string;
 use crate::catalog::Snapshot as CatalogSnapshot;
@@ -54,15 +66,23 @@ use crate::catalog::column::{Int64Type, TypedStatistics};
 use crate::catalog::manifest::Manifest;
 use crate::catalog::snapshot::Snapshot;
 use crate::event::DEFAULT_TIMESTAMP_KEY;
+use crate::handlers::http::fetch_schema;
 use crate::handlers::http::query::QueryError;
+use crate::metrics::{
+    QUERIES_QUEUED, QUERIES_RUNNING, QUERY_CANCELLED_ON_DISCONNECT, QUERY_DEADLINE_EXCEEDED,
+};
 use crate::option::Mode;
-use crate::parseable::PARSEABLE;
+use crate::parseable::{PARSEABLE, StreamNotFound};
 use crate::storage::{ObjectStorageProvider, ObjectStoreFormat};
 use crate::utils::time::TimeRange;
 
 pub static QUERY_SESSION: Lazy<SessionContext> =
     Lazy::new(|| Query::create_session_context(PARSEABLE.storage()));
 
+/// Session restricted to the data in staging, see [`StagingSchemaProvider`]
+pub static STAGING_QUERY_SESSION: Lazy<SessionContext> =
+    Lazy::new(|| Query::create_staging_session_context(PARSEABLE.storage()));
+
 pub static QUERY_SESSION_STATE: Lazy<SessionState> =
     Lazy::new(|| Query::create_session_state(PARSEABLE.storage()));
 
@@ -70,6 +90,68 @@ pub static QUERY_SESSION_STATE: Lazy<SessionState> =
 pub static QUERY_RUNTIME: Lazy<Runtime> =
     Lazy::new(|| Runtime::new().expect("Runtime should be constructible"));
 
+/// Bounds the number of queries executing concurrently on this node, unbounded when `--query-max-concurrent` is not set
+static QUERY_SLOTS: Lazy<Option<Arc<Semaphore>>> = Lazy::new(|| {
+    PARSEABLE
+        .options
+        .query_max_concurrent
+        .map(|limit| Arc::new(Semaphore::new(limit as usize)))
+});
+
+/// An execution slot on this node, released when dropped
+pub struct QuerySlot {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for QuerySlot {
+    fn drop(&mut self) {
+        QUERIES_RUNNING.dec();
+    }
+}
+
+/// A query waiting for an execution slot, counted as queued until dropped, also when actix
+/// drops the handler future waiting for the slot because the client disconnected
+struct QueuedQuery;
+
+impl QueuedQuery {
+    fn new() -> Self {
+        QUERIES_QUEUED.inc();
+        Self
+    }
+}
+
+impl Drop for QueuedQuery {
+    fn drop(&mut self) {
+        QUERIES_QUEUED.dec();
+    }
+}
+
+/// Acquires an execution slot for a query, waiting at most `--query-queue-timeout` seconds
+/// for a running query to finish when the node is already at `--query-max-concurrent`.
+pub async fn acquire_query_slot() -> Result<QuerySlot, QueryError> {
+    let permit = match QUERY_SLOTS.as_ref() {
+        Some(slots) => {
+            let queue_timeout = PARSEABLE.options.query_queue_timeout;
+            let queued = QueuedQuery::new();
+            let permit = tokio::time::timeout(
+                std::time::Duration::from_secs(queue_timeout),
+                slots.clone().acquire_owned(),
+            )
+            .await;
+            drop(queued);
+
+            match permit {
+                Ok(Ok(permit)) => Some(permit),
+                _ => return Err(QueryError::TooManyQueries(queue_timeout.max(1))),
+            }
+        }
+        None => None,
+    };
+    QUERIES_RUNNING.inc();
+
+    Ok(QuerySlot { _permit: permit })
+}
+
 /// This function executes a query on the dedicated runtime, ensuring that the query is not isolated to a single thread/CPU
 /// at a time and has access to the entire thread pool, enabling better concurrent processing, and thus quicker results.
 pub async fn execute(
@@ -88,14 +170,113 @@ pub async fn execute(
         .expect("The Join should have been successful")
 }
 
+/// Same as [`execute`], but the query is aborted as soon as `cancel_token` is cancelled.
+/// HTTP handlers hold a drop guard for the token, so when actix drops the handler future
+/// because the client disconnected, the datafusion execution on [`QUERY_RUNTIME`] is dropped too
+/// instead of running to completion.
+///
+/// The query is also aborted once `--query-timeout` elapses, including while a result stream
+/// is being consumed. Dropping the execution drops the in-flight object store requests with it.
+pub async fn execute_with_cancellation(
+    query: Query,
+    is_streaming: bool,
+    stream_name: String,
+    cancel_token: CancellationToken,
+) -> Result<
+    (
+        Either<Vec<RecordBatch>, SendableRecordBatchStream>,
+        Vec<String>,
+    ),
+    ExecuteError,
+> {
+    let timeout = PARSEABLE.options.query_timeout;
+    let deadline =
+        timeout.map(|secs| tokio::time::Instant::now() + std::time::Duration::from_secs(secs));
+
+    QUERY_RUNTIME
+        .spawn(async move {
+            tokio::select! {
+                _ = cancel_token.cancelled() => {
+                    QUERY_CANCELLED_ON_DISCONNECT
+                        .with_label_values(&[&stream_name])
+                        .inc();
+                    Err(ExecuteError::Cancelled)
+                }
+                _ = wait_until(deadline) => {
+                    QUERY_DEADLINE_EXCEEDED
+                        .with_label_values(&[&stream_name])
+                        .inc();
+                    Err(ExecuteError::DeadlineExceeded(timeout.unwrap_or_default()))
+                }
+                result = query.execute(is_streaming) => result.map(|(records, fields)| {
+                    let records = match (records, deadline) {
+                        (Either::Right(stream), Some(deadline)) => Either::Right(
+                            with_deadline(stream, deadline, timeout.unwrap_or_default(), stream_name),
+                        ),
+                        (records, _) => records,
+                    };
+                    (records, fields)
+                }),
+            }
+        })
+        .await
+        .expect("The Join should have been successful")
+}
+
+// resolves at `deadline`, never when there is none
+async fn wait_until(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Ends the result stream with an error once `deadline` is reached, dropping the
+/// execution and its pending object store reads
+fn with_deadline(
+    stream: SendableRecordBatchStream,
+    deadline: tokio::time::Instant,
+    timeout: u64,
+    stream_name: String,
+) -> SendableRecordBatchStream {
+    let schema = stream.schema();
+    let batches = futures::stream::unfold(Some(stream), move |stream| {
+        let stream_name = stream_name.clone();
+        async move {
+            let mut stream = stream?;
+            match tokio::time::timeout_at(deadline, stream.next()).await {
+                Ok(batch) => batch.map(|batch| (batch, Some(stream))),
+                Err(_) => {
+                    QUERY_DEADLINE_EXCEEDED
+                        .with_label_values(&[&stream_name])
+                        .inc();
+                    let err = ExecuteError::DeadlineExceeded(timeout);
+                    Some((Err(DataFusionError::External(Box::new(err))), None))
+                }
+            }
+        }
+    });
+
+    Box::pin(RecordBatchStreamAdapter::new(schema, batches))
+}
+
 // A query request by client
 #[derive(Debug)]
 pub struct Query {
     pub raw_logical_plan: LogicalPlan,
     pub time_range: TimeRange,
     pub filter_tag: Option<Vec<String>>,
+    /// read the streams as they were at this time instead of their current state
+    pub as_of: Option<DateTime<Utc>>,
+    /// read this fraction of the data only, scaling counts and sums back up
+    pub sample: Option<f64>,
 }
 
+/// Session config extension of queries reading the streams as of a past time,
+/// see [`Snapshot::removed_after`]
+#[derive(Debug, Clone, Copy)]
+pub struct SnapshotAsOf(pub DateTime<Utc>);
+
 impl Query {
     // create session context for this query
     pub fn create_session_context(storage: Arc<dyn ObjectStorageProvider>) -> SessionContext {
@@ -117,10 +298,34 @@ impl Query {
         SessionContext::new_with_state(state)
     }
 
+    // create session context for queries over the staging of this node only
+    pub fn create_staging_session_context(
+        storage: Arc<dyn ObjectStorageProvider>,
+    ) -> SessionContext {
+        let state = Self::create_session_state(storage);
+        state
+            .catalog_list()
+            .catalog(&state.config_options().catalog.default_catalog)
+            .expect("default catalog is provided by datafusion")
+            .register_schema(
+                &state.config_options().catalog.default_schema,
+                Arc::new(StagingSchemaProvider),
+            )
+            .unwrap();
+
+        SessionContext::new_with_state(state)
+    }
+
     fn create_session_state(storage: Arc<dyn ObjectStorageProvider>) -> SessionState {
+        let disk_manager = match PARSEABLE.options.query_spill_path.as_ref() {
+            Some(path) => {
+                DiskManager::builder().with_mode(DiskManagerMode::Directories(vec![path.clone()]))
+            }
+            None => DiskManager::builder(),
+        };
         let runtime_config = storage
             .get_datafusion_runtime()
-            .with_disk_manager_builder(DiskManager::builder());
+            .with_disk_manager_builder(disk_manager);
         let (pool_size, fraction) = match PARSEABLE.options.query_memory_pool_size {
             Some(size) => (size, 1.),
             None => {
@@ -170,6 +375,16 @@ impl Query {
             .build()
     }
 
+    /// Executes the query over the data in the staging of this node only,
+    /// the plan must have been created with the state of [`STAGING_QUERY_SESSION`]
+    pub async fn execute_on_staging(&self) -> Result<Vec<RecordBatch>, ExecuteError> {
+        Ok(STAGING_QUERY_SESSION
+            .execute_logical_plan(self.final_logical_plan()?)
+            .await?
+            .collect()
+            .await?)
+    }
+
     /// this function returns the result of the query
     /// if streaming is true, it returns a stream
     /// if streaming is false, it returns a vector of record batches
@@ -183,8 +398,9 @@ impl Query {
         ),
         ExecuteError,
     > {
-        let df = QUERY_SESSION
-            .execute_logical_plan(self.final_logical_plan())
+        let df = self
+            .session()
+            .execute_logical_plan(self.final_logical_plan()?)
             .await?;
 
         let fields = df
@@ -209,21 +425,43 @@ impl Query {
     }
 
     pub async fn get_dataframe(&self) -> Result<DataFrame, ExecuteError> {
-        let df = QUERY_SESSION
-            .execute_logical_plan(self.final_logical_plan())
+        let df = self
+            .session()
+            .execute_logical_plan(self.final_logical_plan()?)
             .await?;
 
         Ok(df)
     }
 
+    // queries as of a past time or over a sample run in a copy of the session carrying
+    // them for the table scans
+    fn session(&self) -> SessionContext {
+        if self.as_of.is_none() && self.sample.is_none() {
+            return QUERY_SESSION.clone();
+        }
+
+        let mut state = QUERY_SESSION.state();
+        if let Some(as_of) = self.as_of {
+            state
+                .config_mut()
+                .set_extension(Arc::new(SnapshotAsOf(as_of)));
+        }
+        if let Some(fraction) = self.sample {
+            state
+                .config_mut()
+                .set_extension(Arc::new(SampleFraction(fraction)));
+        }
+        SessionContext::new_with_state(state)
+    }
+
     /// return logical plan with all time filters applied through
-    fn final_logical_plan(&self) -> LogicalPlan {
+    fn final_logical_plan(&self) -> Result<LogicalPlan, DataFusionError> {
         // see https://github.com/apache/arrow-datafusion/pull/8400
         // this can be eliminated in later version of datafusion but with slight caveat
         // transform cannot modify stringified plans by itself
         // we by knowing this plan is not in the optimization procees chose to overwrite the stringified plan
 
-        match self.raw_logical_plan.clone() {
+        Ok(match self.raw_logical_plan.clone() {
             LogicalPlan::Explain(plan) => {
                 let transformed = transform(
                     plan.plan.as_ref().clone(),
@@ -244,14 +482,18 @@ impl Query {
                 })
             }
             x => {
-                transform(
+                let plan = transform(
                     x,
                     self.time_range.start.naive_utc(),
                     self.time_range.end.naive_utc(),
                 )
-                .data
+                .data;
+                match self.sample {
+                    Some(fraction) => scale_aggregates(plan, fraction)?,
+                    None => plan,
+                }
             }
-        }
+        })
     }
 
     /// Evaluates to Some("count(*)") | Some("column_name") if the logical plan is a Projection: SELECT COUNT(*) | SELECT COUNT(*) as column_name
@@ -531,6 +773,50 @@ pub fn resolve_stream_names(sql: &str) -> Result<Vec<String>, anyhow::Error> {
     Ok(tables)
 }
 
+/// Fails with the first of the queried streams which doesn't exist, e.g. as it was deleted since
+/// the query was saved. Checked before planning, which would otherwise fail with a planning error.
+pub async fn ensure_streams_exist(tables: &[String]) -> Result<(), StreamNotFound> {
+    for table in tables {
+        if !PARSEABLE.check_or_load_stream(table).await {
+            return Err(StreamNotFound(table.clone()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Reason given along with the empty result of queries over streams without any event yet
+pub const NO_DATA_REASON: &str = "no data for range";
+
+/// Whether none of the streams has received an event yet. Their schema stays empty until then,
+/// so queries referencing any column can't be planned against them.
+pub async fn streams_without_data(tables: &[String]) -> bool {
+    let mut schemas = Vec::with_capacity(tables.len());
+    for table in tables {
+        if !PARSEABLE.check_or_load_stream(table).await {
+            return false;
+        }
+        let Ok(stream) = PARSEABLE.get_stream(table) else {
+            return false;
+        };
+        let mut schema = stream.get_schema();
+        // queriers only learn the fields of a stream from the schemas uploaded by the ingestors
+        if schema.fields().is_empty()
+            && matches!(PARSEABLE.options.mode, Mode::Query | Mode::Prism)
+            && let Ok(stored) = fetch_schema(table).await
+        {
+            schema = Arc::new(stored);
+        }
+        schemas.push(schema);
+    }
+
+    all_schemas_empty(&schemas)
+}
+
+fn all_schemas_empty(schemas: &[Arc<Schema>]) -> bool {
+    !schemas.is_empty() && schemas.iter().all(|schema| schema.fields().is_empty())
+}
+
 pub async fn get_manifest_list(
     stream_name: &str,
     time_range: &TimeRange,
@@ -605,11 +891,11 @@ fn transform(
     plan.transform_up_with_subqueries(&|plan| {
         match plan {
             LogicalPlan::TableScan(table) => {
-                // Get the specific time partition for this stream
+                // Get the specific time column for this stream
                 let time_partition = PARSEABLE
                     .get_stream(&table.table_name.to_string())
                     .ok()
-                    .and_then(|stream| stream.get_time_partition());
+                    .and_then(|stream| stream.get_time_column());
 
                 let mut new_filters = vec![];
                 if !table_contains_any_time_filters(&table, time_partition.as_ref()) {
@@ -738,15 +1024,33 @@ pub mod error {
         Datafusion(#[from] DataFusionError),
         #[error("{0}")]
         StreamNotFound(#[from] StreamNotFound),
+        #[error("Query Execution was cancelled as the client disconnected")]
+        Cancelled,
+        #[error("Query Execution was aborted as it exceeded the query timeout of {0} seconds")]
+        DeadlineExceeded(u64),
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use arrow_schema::{DataType, Field};
     use serde_json::json;
 
     use crate::query::flatten_objects_for_count;
 
+    use super::*;
+
+    #[test]
+    fn empty_streams_have_no_data() {
+        let empty = Arc::new(Schema::empty());
+        let populated = Arc::new(Schema::new(vec![Field::new("a", DataType::Utf8, true)]));
+
+        assert!(all_schemas_empty(&[empty.clone()]));
+        assert!(all_schemas_empty(&[empty.clone(), empty.clone()]));
+        assert!(!all_schemas_empty(&[empty, populated]));
+        assert!(!all_schemas_empty(&[]));
+    }
+
     #[test]
     fn test_flat_simple() {
         let val = vec![
@@ -0,0 +1,292 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use arrow_array::RecordBatch;
+use chrono::{DateTime, TimeDelta, Timelike, Utc};
+use once_cell::sync::Lazy;
+
+use crate::parseable::PARSEABLE;
+use crate::utils::time::TimeRange;
+
+use super::Query;
+
+/// Results of non-streaming queries, shared by repeated executions of the same query
+pub static QUERY_RESULT_CACHE: Lazy<QueryResultCache> = Lazy::new(|| {
+    QueryResultCache::new(
+        PARSEABLE.options.query_cache_ttl.map(Duration::from_secs),
+        PARSEABLE.options.query_cache_size as usize * 1024 * 1024,
+    )
+});
+
+/// Identifies a query by its normalized logical plan and the time range it was executed for
+#[derive(Debug, Clone)]
+pub struct CacheKey {
+    hash: u64,
+    streams: Vec<String>,
+    time_range: TimeRange,
+}
+
+/// Unmasked records and fields of a cached query result
+pub struct CachedResult {
+    pub records: Vec<RecordBatch>,
+    pub fields: Vec<String>,
+}
+
+struct CacheEntry {
+    result: Arc<CachedResult>,
+    streams: Vec<String>,
+    time_range: TimeRange,
+    size: usize,
+    inserted_at: Instant,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<u64, CacheEntry>,
+    size: usize,
+}
+
+impl CacheState {
+    fn remove(&mut self, hash: &u64) {
+        if let Some(entry) = self.entries.remove(hash) {
+            self.size -= entry.size;
+        }
+    }
+}
+
+pub struct QueryResultCache {
+    // caching is disabled when no ttl is configured
+    ttl: Option<Duration>,
+    max_size: usize,
+    state: Mutex<CacheState>,
+}
+
+impl QueryResultCache {
+    fn new(ttl: Option<Duration>, max_size: usize) -> Self {
+        Self {
+            ttl,
+            max_size,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Builds the cache key of a query over `streams`, returns `None` when caching is disabled
+    pub fn key(&self, query: &Query, streams: &[String]) -> Option<CacheKey> {
+        self.ttl?;
+
+        // the indented plan is independent of formatting and casing in the original SQL
+        let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+        hasher.update(
+            query
+                .raw_logical_plan
+                .display_indent()
+                .to_string()
+                .as_bytes(),
+        );
+        hasher.update(&query.time_range.start.timestamp_micros().to_le_bytes());
+        hasher.update(&query.time_range.end.timestamp_micros().to_le_bytes());
+        for tag in query.filter_tag.iter().flatten() {
+            hasher.update(tag.as_bytes());
+        }
+        if let Some(as_of) = query.as_of {
+            hasher.update(&as_of.timestamp_micros().to_le_bytes());
+        }
+        if let Some(fraction) = query.sample {
+            hasher.update(&fraction.to_le_bytes());
+        }
+
+        Some(CacheKey {
+            hash: hasher.digest(),
+            streams: streams.to_vec(),
+            time_range: query.time_range.clone(),
+        })
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<Arc<CachedResult>> {
+        let ttl = self.ttl?;
+        let mut state = self.state.lock().expect("lock poisoned");
+        let entry = state.entries.get(&key.hash)?;
+        if entry.inserted_at.elapsed() <= ttl {
+            return Some(entry.result.clone());
+        }
+
+        state.remove(&key.hash);
+        None
+    }
+
+    pub fn insert(&self, key: CacheKey, records: Vec<RecordBatch>, fields: Vec<String>) {
+        if self.ttl.is_none() {
+            return;
+        }
+
+        let size = records
+            .iter()
+            .map(RecordBatch::get_array_memory_size)
+            .sum::<usize>();
+        if size > self.max_size {
+            return;
+        }
+
+        let mut state = self.state.lock().expect("lock poisoned");
+        state.remove(&key.hash);
+        // evict the oldest entries until the new result fits
+        while state.size + size > self.max_size {
+            let Some(oldest) = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.inserted_at)
+                .map(|(hash, _)| *hash)
+            else {
+                break;
+            };
+            state.remove(&oldest);
+        }
+
+        state.size += size;
+        state.entries.insert(
+            key.hash,
+            CacheEntry {
+                result: Arc::new(CachedResult { records, fields }),
+                streams: key.streams,
+                time_range: key.time_range,
+                size,
+                inserted_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Drops the cached results of `stream` whose time range overlaps the minute of newly ingested data
+    pub fn invalidate(&self, stream: &str, timestamp: DateTime<Utc>) {
+        if self.ttl.is_none() {
+            return;
+        }
+
+        let start = timestamp
+            .with_second(0)
+            .and_then(|t| t.with_nanosecond(0))
+            .unwrap_or(timestamp);
+        let end = start + TimeDelta::minutes(1);
+
+        let mut state = self.state.lock().expect("lock poisoned");
+        let stale = state
+            .entries
+            .iter()
+            .filter(|(_, entry)| {
+                entry.streams.iter().any(|s| s == stream)
+                    && entry.time_range.start < end
+                    && entry.time_range.end > start
+            })
+            .map(|(hash, _)| *hash)
+            .collect::<Vec<_>>();
+        for hash in stale {
+            state.remove(&hash);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::Int64Array;
+    use arrow_schema::{DataType, Field, Schema};
+
+    use super::*;
+
+    fn records() -> Vec<RecordBatch> {
+        let schema = Schema::new(vec![Field::new("count", DataType::Int64, false)]);
+        vec![
+            RecordBatch::try_new(
+                Arc::new(schema),
+                vec![Arc::new(Int64Array::from(vec![1, 2, 3]))],
+            )
+            .unwrap(),
+        ]
+    }
+
+    fn key(hash: u64, start: &str, end: &str) -> CacheKey {
+        CacheKey {
+            hash,
+            streams: vec!["app".to_owned()],
+            time_range: TimeRange {
+                start: start.parse().unwrap(),
+                end: end.parse().unwrap(),
+            },
+        }
+    }
+
+    #[test]
+    fn serves_inserted_results() {
+        let cache = QueryResultCache::new(Some(Duration::from_secs(60)), 1024 * 1024);
+        let key = key(1, "2024-01-01T00:00:00Z", "2024-01-01T01:00:00Z");
+        assert!(cache.get(&key).is_none());
+
+        cache.insert(key.clone(), records(), vec!["count".to_owned()]);
+        let cached = cache.get(&key).unwrap();
+        assert_eq!(cached.records[0].num_rows(), 3);
+        assert_eq!(cached.fields, vec!["count".to_owned()]);
+    }
+
+    #[test]
+    fn disabled_without_ttl() {
+        let cache = QueryResultCache::new(None, 1024 * 1024);
+        let key = key(1, "2024-01-01T00:00:00Z", "2024-01-01T01:00:00Z");
+        cache.insert(key.clone(), records(), vec![]);
+
+        assert!(cache.get(&key).is_none());
+    }
+
+    #[test]
+    fn ingestion_invalidates_overlapping_entries() {
+        let cache = QueryResultCache::new(Some(Duration::from_secs(60)), 1024 * 1024);
+        let first_hour = key(1, "2024-01-01T00:00:00Z", "2024-01-01T01:00:00Z");
+        let second_hour = key(2, "2024-01-01T01:00:00Z", "2024-01-01T02:00:00Z");
+        cache.insert(first_hour.clone(), records(), vec![]);
+        cache.insert(second_hour.clone(), records(), vec![]);
+
+        cache.invalidate("other", "2024-01-01T01:30:00Z".parse().unwrap());
+        assert!(cache.get(&second_hour).is_some());
+
+        cache.invalidate("app", "2024-01-01T01:30:00Z".parse().unwrap());
+        assert!(cache.get(&first_hour).is_some());
+        assert!(cache.get(&second_hour).is_none());
+    }
+
+    #[test]
+    fn evicts_oldest_entries_beyond_max_size() {
+        let size = records()
+            .iter()
+            .map(RecordBatch::get_array_memory_size)
+            .sum::<usize>();
+        let cache = QueryResultCache::new(Some(Duration::from_secs(60)), size * 2);
+        let keys = (0..3)
+            .map(|hash| key(hash, "2024-01-01T00:00:00Z", "2024-01-01T01:00:00Z"))
+            .collect::<Vec<_>>();
+        for key in &keys {
+            cache.insert(key.clone(), records(), vec![]);
+        }
+
+        assert!(cache.get(&keys[0]).is_none());
+        assert!(cache.get(&keys[1]).is_some());
+        assert!(cache.get(&keys[2]).is_some());
+    }
+}
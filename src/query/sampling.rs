@@ -0,0 +1,169 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Approximate queries over a sample of the data, set with `?sample=<fraction>`. The scans
+//! only read the fraction of the parquet files whose path hashes below it, the same files for
+//! every query with that fraction. Counts and sums are scaled back up by the fraction.
+//!
+//! Data still in staging isn't read by sampled queries, only the uploaded files are sampled.
+
+use std::sync::Arc;
+
+use arrow_schema::DataType;
+use datafusion::common::Column;
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::error::DataFusionError;
+use datafusion::logical_expr::expr::{AggregateFunction, Alias};
+use datafusion::logical_expr::{Expr, LogicalPlan, Projection, cast, lit};
+
+/// Session config extension of sampled queries, the fraction of the files read by the scans
+#[derive(Debug, Clone, Copy)]
+pub struct SampleFraction(pub f64);
+
+/// Fractions of the data a query can be sampled over, a fraction of 1 reads all the data
+pub fn validate_sample_fraction(fraction: f64) -> Result<f64, String> {
+    if fraction.is_finite() && fraction > 0.0 && fraction <= 1.0 {
+        Ok(fraction)
+    } else {
+        Err(format!(
+            "sample should be a fraction greater than 0 and at most 1, got {fraction}"
+        ))
+    }
+}
+
+/// Whether the file at `file_path` is part of the sample
+pub fn is_sampled(file_path: &str, fraction: f64) -> bool {
+    let hash = xxhash_rust::xxh3::xxh3_64(file_path.as_bytes());
+    fraction >= 1.0 || (hash as f64) < fraction * u64::MAX as f64
+}
+
+/// Scales the counts and sums of the aggregations of the plan by the inverse of the fraction,
+/// distinct counts and other aggregates are left as is. Only the aggregations over the scans
+/// are scaled, the ones over other aggregations aggregate values already scaled.
+pub fn scale_aggregates(plan: LogicalPlan, fraction: f64) -> Result<LogicalPlan, DataFusionError> {
+    let scale = 1.0 / fraction;
+    plan.transform_up(|node| {
+        let LogicalPlan::Aggregate(aggregate) = &node else {
+            return Ok(Transformed::no(node));
+        };
+        if aggregate
+            .input
+            .exists(|input| Ok(matches!(input, LogicalPlan::Aggregate(_))))?
+        {
+            return Ok(Transformed::no(node));
+        }
+
+        // the aggregates are the last columns, after the group keys and any grouping id
+        let first_aggregate = aggregate.schema.fields().len() - aggregate.aggr_expr.len();
+        let mut scaled = false;
+        let exprs = aggregate
+            .schema
+            .iter()
+            .enumerate()
+            .map(|(i, (qualifier, field))| {
+                let column = Expr::Column(Column::from((qualifier, field)));
+                if i < first_aggregate || !is_additive(&aggregate.aggr_expr[i - first_aggregate]) {
+                    return column;
+                }
+                scaled = true;
+                cast(
+                    cast(column, DataType::Float64) * lit(scale),
+                    field.data_type().clone(),
+                )
+                .alias_qualified(qualifier.cloned(), field.name())
+            })
+            .collect::<Vec<_>>();
+        if !scaled {
+            return Ok(Transformed::no(node));
+        }
+
+        let projection = Projection::try_new(exprs, Arc::new(node))?;
+        Ok(Transformed::yes(LogicalPlan::Projection(projection)))
+    })
+    .map(|transformed| transformed.data)
+}
+
+/// Aggregates growing with the number of rows aggregated
+fn is_additive(expr: &Expr) -> bool {
+    match expr {
+        Expr::Alias(Alias { expr, .. }) => is_additive(expr),
+        Expr::AggregateFunction(AggregateFunction { func, params }) => {
+            !params.distinct && matches!(func.name(), "count" | "sum")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_schema::{Field, Schema};
+    use datafusion::functions_aggregate::expr_fn::{count, sum};
+    use datafusion::logical_expr::{col, table_scan};
+
+    use super::*;
+
+    #[test]
+    fn sample_fractions_are_validated() {
+        assert!(validate_sample_fraction(0.1).is_ok());
+        assert!(validate_sample_fraction(1.0).is_ok());
+        assert!(validate_sample_fraction(0.0).is_err());
+        assert!(validate_sample_fraction(1.5).is_err());
+        assert!(validate_sample_fraction(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn samples_read_about_the_fraction_of_files() {
+        let files = (0..10_000)
+            .map(|i| format!("date=2024-05-01/hour=00/minute={i}/data.parquet"))
+            .collect::<Vec<_>>();
+        let sampled = files.iter().filter(|file| is_sampled(file, 0.1)).count();
+
+        assert!((800..1200).contains(&sampled), "sampled {sampled} files");
+        assert!(files.iter().all(|file| is_sampled(file, 1.0)));
+    }
+
+    #[test]
+    fn nested_aggregates_are_scaled_once() {
+        let schema = Schema::new(vec![
+            Field::new("x", DataType::Utf8, true),
+            Field::new("y", DataType::Int64, true),
+        ]);
+        // SELECT sum(c) FROM (SELECT x, count(y) c FROM t GROUP BY x)
+        let plan = table_scan(Some("t"), &schema, None)
+            .unwrap()
+            .aggregate(vec![col("x")], vec![count(col("y")).alias("c")])
+            .unwrap()
+            .aggregate(Vec::<Expr>::new(), vec![sum(col("c"))])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let scaled = scale_aggregates(plan, 0.5).unwrap();
+
+        let LogicalPlan::Aggregate(outer) = &scaled else {
+            panic!("the outer sum was scaled:\n{scaled}");
+        };
+        let LogicalPlan::Projection(projection) = outer.input.as_ref() else {
+            panic!("the inner count wasn't scaled:\n{scaled}");
+        };
+        assert!(matches!(
+            projection.input.as_ref(),
+            LogicalPlan::Aggregate(_)
+        ));
+    }
+}
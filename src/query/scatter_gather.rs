@@ -0,0 +1,415 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Aggregations computed as partial aggregates on every node holding a part of the data,
+//! i.e. the object store on the querier and the staging of each ingestor,
+//! then merged on the querier.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use datafusion::functions_aggregate::expr_fn::{count, sum};
+use datafusion::logical_expr::{Aggregate, Expr, LogicalPlan, LogicalPlanBuilder};
+use serde_json::{Map, Value};
+
+/// How the partial results of an aggregate are merged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartialKind {
+    Count,
+    Sum,
+    Min,
+    Max,
+    /// computed from a partial sum and count
+    Avg,
+}
+
+impl PartialKind {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "count" => Some(Self::Count),
+            "sum" => Some(Self::Sum),
+            "min" => Some(Self::Min),
+            "max" => Some(Self::Max),
+            "avg" => Some(Self::Avg),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputSource {
+    Group(usize),
+    Aggregate(usize),
+}
+
+/// Describes how to merge the rows of a partial plan into the result of the original query
+#[derive(Debug, Clone)]
+pub struct MergePlan {
+    group_columns: Vec<String>,
+    aggregates: Vec<PartialKind>,
+    outputs: Vec<(String, OutputSource)>,
+}
+
+impl MergePlan {
+    /// Names of the result columns, in order
+    pub fn fields(&self) -> Vec<String> {
+        self.outputs.iter().map(|(name, _)| name.clone()).collect()
+    }
+}
+
+fn partial_column(index: usize) -> String {
+    format!("__partial_{index}")
+}
+
+fn partial_sum_column(index: usize) -> String {
+    format!("__partial_{index}_sum")
+}
+
+fn partial_count_column(index: usize) -> String {
+    format!("__partial_{index}_count")
+}
+
+/// Rewrites an aggregation into one computing the partial aggregates of every node,
+/// only `count`, `sum`, `min`, `max` and `avg` over an optional projection are supported
+pub fn partial_plan(plan: &LogicalPlan) -> Result<(LogicalPlan, MergePlan), String> {
+    let (aggregate, projection) = match plan {
+        LogicalPlan::Projection(projection) => match projection.input.as_ref() {
+            LogicalPlan::Aggregate(aggregate) => (aggregate, Some(projection)),
+            _ => return Err(unsupported()),
+        },
+        LogicalPlan::Aggregate(aggregate) => (aggregate, None),
+        _ => return Err(unsupported()),
+    };
+    if aggregate
+        .group_expr
+        .iter()
+        .any(|expr| matches!(expr, Expr::GroupingSet(_)))
+    {
+        return Err("grouping sets can't be merged across nodes".to_owned());
+    }
+
+    let group_len = aggregate.group_expr.len();
+    let group_columns = (0..group_len)
+        .map(|i| aggregate.schema.field(i).name().clone())
+        .collect();
+
+    let mut aggregates = Vec::with_capacity(aggregate.aggr_expr.len());
+    let mut partial_exprs = Vec::with_capacity(aggregate.aggr_expr.len());
+    for (index, expr) in aggregate.aggr_expr.iter().enumerate() {
+        let Expr::AggregateFunction(function) = expr.clone().unalias() else {
+            return Err(unsupported());
+        };
+        let kind = PartialKind::from_name(function.func.name()).ok_or_else(|| {
+            format!(
+                "aggregate {} can't be merged across nodes",
+                function.func.name()
+            )
+        })?;
+        if function.params.distinct || function.params.filter.is_some() {
+            return Err("DISTINCT and FILTER aggregates can't be merged across nodes".to_owned());
+        }
+
+        if kind == PartialKind::Avg {
+            let arg = function
+                .params
+                .args
+                .first()
+                .cloned()
+                .ok_or_else(unsupported)?;
+            partial_exprs.push(sum(arg.clone()).alias(partial_sum_column(index)));
+            partial_exprs.push(count(arg).alias(partial_count_column(index)));
+        } else {
+            partial_exprs.push(Expr::AggregateFunction(function).alias(partial_column(index)));
+        }
+        aggregates.push(kind);
+    }
+
+    let outputs = match projection {
+        Some(projection) => projection
+            .expr
+            .iter()
+            .enumerate()
+            .map(|(i, expr)| {
+                let Expr::Column(column) = expr.clone().unalias() else {
+                    return Err(unsupported());
+                };
+                let index = aggregate
+                    .schema
+                    .index_of_column(&column)
+                    .map_err(|err| err.to_string())?;
+                Ok((
+                    projection.schema.field(i).name().clone(),
+                    output_source(aggregate, index),
+                ))
+            })
+            .collect::<Result<Vec<_>, String>>()?,
+        None => (0..aggregate.schema.fields().len())
+            .map(|i| {
+                (
+                    aggregate.schema.field(i).name().clone(),
+                    output_source(aggregate, i),
+                )
+            })
+            .collect(),
+    };
+
+    let partial = LogicalPlanBuilder::from(aggregate.input.as_ref().clone())
+        .aggregate(aggregate.group_expr.clone(), partial_exprs)
+        .and_then(LogicalPlanBuilder::build)
+        .map_err(|err| err.to_string())?;
+
+    Ok((
+        partial,
+        MergePlan {
+            group_columns,
+            aggregates,
+            outputs,
+        },
+    ))
+}
+
+fn output_source(aggregate: &Aggregate, index: usize) -> OutputSource {
+    if index < aggregate.group_expr.len() {
+        OutputSource::Group(index)
+    } else {
+        OutputSource::Aggregate(index - aggregate.group_expr.len())
+    }
+}
+
+fn unsupported() -> String {
+    "only aggregations of count, sum, min, max and avg, without ORDER BY, LIMIT or HAVING, can be merged across nodes".to_owned()
+}
+
+/// Merges the partial rows returned by every node into the rows of the original query,
+/// fails when an integer count or sum overflows
+pub fn merge(
+    plan: &MergePlan,
+    partials: Vec<Map<String, Value>>,
+) -> Result<Vec<Map<String, Value>>, String> {
+    let mut groups: HashMap<String, (Vec<Value>, Vec<Accumulator>)> = HashMap::new();
+    // keep the groups in the order they were first seen
+    let mut order = Vec::new();
+
+    for row in partials {
+        let group = plan
+            .group_columns
+            .iter()
+            .map(|column| row.get(column).cloned().unwrap_or(Value::Null))
+            .collect::<Vec<_>>();
+        let key = Value::Array(group.clone()).to_string();
+        let (_, accumulators) = groups.entry(key.clone()).or_insert_with(|| {
+            order.push(key);
+            (
+                group,
+                plan.aggregates
+                    .iter()
+                    .map(|kind| Accumulator::new(*kind))
+                    .collect(),
+            )
+        });
+
+        for (index, accumulator) in accumulators.iter_mut().enumerate() {
+            match accumulator.kind {
+                PartialKind::Avg => {
+                    accumulator.update(row.get(&partial_sum_column(index)))?;
+                    accumulator.update_count(row.get(&partial_count_column(index)))?;
+                }
+                _ => accumulator.update(row.get(&partial_column(index)))?,
+            }
+        }
+    }
+
+    let rows = order
+        .into_iter()
+        .filter_map(|key| groups.remove(&key))
+        .map(|(group, accumulators)| {
+            plan.outputs
+                .iter()
+                .map(|(name, source)| {
+                    let value = match source {
+                        OutputSource::Group(i) => group[*i].clone(),
+                        OutputSource::Aggregate(i) => accumulators[*i].result(),
+                    };
+                    (name.clone(), value)
+                })
+                .collect()
+        })
+        .collect();
+
+    Ok(rows)
+}
+
+struct Accumulator {
+    kind: PartialKind,
+    value: Value,
+    count: i64,
+}
+
+impl Accumulator {
+    fn new(kind: PartialKind) -> Self {
+        Self {
+            kind,
+            value: Value::Null,
+            count: 0,
+        }
+    }
+
+    fn update(&mut self, partial: Option<&Value>) -> Result<(), String> {
+        let Some(partial) = partial.filter(|value| !value.is_null()) else {
+            return Ok(());
+        };
+        if self.value.is_null() {
+            self.value = partial.clone();
+            return Ok(());
+        }
+
+        self.value = match self.kind {
+            PartialKind::Count | PartialKind::Sum | PartialKind::Avg => add(&self.value, partial)?,
+            PartialKind::Min if compare(partial, &self.value) == Ordering::Less => partial.clone(),
+            PartialKind::Max if compare(partial, &self.value) == Ordering::Greater => {
+                partial.clone()
+            }
+            _ => return Ok(()),
+        };
+
+        Ok(())
+    }
+
+    fn update_count(&mut self, partial: Option<&Value>) -> Result<(), String> {
+        self.count = self
+            .count
+            .checked_add(partial.and_then(Value::as_i64).unwrap_or_default())
+            .ok_or_else(overflow)?;
+
+        Ok(())
+    }
+
+    fn result(&self) -> Value {
+        match self.kind {
+            PartialKind::Count if self.value.is_null() => Value::from(0),
+            PartialKind::Avg => match self.value.as_f64() {
+                Some(sum) if self.count > 0 => Value::from(sum / self.count as f64),
+                _ => Value::Null,
+            },
+            _ => self.value.clone(),
+        }
+    }
+}
+
+fn add(left: &Value, right: &Value) -> Result<Value, String> {
+    match (left.as_i64(), right.as_i64()) {
+        (Some(left), Some(right)) => left
+            .checked_add(right)
+            .map(Value::from)
+            .ok_or_else(overflow),
+        _ => Ok(Value::from(
+            left.as_f64().unwrap_or_default() + right.as_f64().unwrap_or_default(),
+        )),
+    }
+}
+
+fn overflow() -> String {
+    "the merged count or sum overflows a 64-bit integer".to_owned()
+}
+
+fn compare(left: &Value, right: &Value) -> Ordering {
+    match (left, right) {
+        (Value::Number(left), Value::Number(right)) => left
+            .as_f64()
+            .partial_cmp(&right.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(left), Value::String(right)) => left.cmp(right),
+        _ => Ordering::Equal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn rows(rows: Value) -> Vec<Map<String, Value>> {
+        serde_json::from_value(rows).unwrap()
+    }
+
+    #[test]
+    fn merges_partial_aggregates_per_group() {
+        let plan = MergePlan {
+            group_columns: vec!["status".to_owned()],
+            aggregates: vec![
+                PartialKind::Count,
+                PartialKind::Min,
+                PartialKind::Max,
+                PartialKind::Avg,
+            ],
+            outputs: vec![
+                ("status".to_owned(), OutputSource::Group(0)),
+                ("count".to_owned(), OutputSource::Aggregate(0)),
+                ("fastest".to_owned(), OutputSource::Aggregate(1)),
+                ("slowest".to_owned(), OutputSource::Aggregate(2)),
+                ("avg_latency".to_owned(), OutputSource::Aggregate(3)),
+            ],
+        };
+        let partials = rows(json!([
+            {"status": 200, "__partial_0": 3, "__partial_1": 10, "__partial_2": 30, "__partial_3_sum": 60, "__partial_3_count": 3},
+            {"status": 500, "__partial_0": 1, "__partial_1": 90, "__partial_2": 90, "__partial_3_sum": 90, "__partial_3_count": 1},
+            {"status": 200, "__partial_0": 1, "__partial_1": 5, "__partial_2": 20, "__partial_3_sum": 20, "__partial_3_count": 1},
+        ]));
+
+        assert_eq!(
+            merge(&plan, partials).unwrap(),
+            rows(json!([
+                {"status": 200, "count": 4, "fastest": 5, "slowest": 30, "avg_latency": 20.0},
+                {"status": 500, "count": 1, "fastest": 90, "slowest": 90, "avg_latency": 90.0},
+            ]))
+        );
+    }
+
+    #[test]
+    fn empty_partials_count_zero() {
+        let plan = MergePlan {
+            group_columns: vec![],
+            aggregates: vec![PartialKind::Count, PartialKind::Avg],
+            outputs: vec![
+                ("count".to_owned(), OutputSource::Aggregate(0)),
+                ("avg".to_owned(), OutputSource::Aggregate(1)),
+            ],
+        };
+        let partials = rows(json!([
+            {"__partial_0": null, "__partial_1_sum": null, "__partial_1_count": 0},
+        ]));
+
+        assert_eq!(
+            merge(&plan, partials).unwrap(),
+            rows(json!([{"count": 0, "avg": null}]))
+        );
+    }
+
+    #[test]
+    fn overflowing_sums_are_errors() {
+        let plan = MergePlan {
+            group_columns: vec![],
+            aggregates: vec![PartialKind::Sum],
+            outputs: vec![("total".to_owned(), OutputSource::Aggregate(0))],
+        };
+        let partials = rows(json!([{"__partial_0": i64::MAX}, {"__partial_0": 1}]));
+
+        assert!(merge(&plan, partials).is_err());
+    }
+}
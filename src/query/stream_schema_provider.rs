@@ -51,6 +51,7 @@ use crate::{
     catalog::{
         ManifestFile, Snapshot as CatalogSnapshot,
         column::{Column, TypedStatistics},
+        consolidation,
         manifest::File,
         snapshot::{ManifestItem, Snapshot},
     },
@@ -65,7 +66,9 @@ use crate::{
     storage::{ObjectStorage, ObjectStoreFormat},
 };
 
+use super::SnapshotAsOf;
 use super::listing_table_builder::ListingTableBuilder;
+use super::sampling::{SampleFraction, is_sampled};
 
 // schema provider for stream based on global data
 #[derive(Debug)]
@@ -91,6 +94,42 @@ impl SchemaProvider for GlobalSchemaProvider {
                     .expect(STREAM_EXISTS)
                     .get_schema(),
                 stream: name.to_owned(),
+                staging_only: false,
+            })))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn table_exist(&self, name: &str) -> bool {
+        PARSEABLE.streams.contains(name)
+    }
+}
+
+// schema provider for streams restricted to the data in the staging of this node,
+// used by ingestors to answer the partial queries of a scatter-gather query
+#[derive(Debug)]
+pub struct StagingSchemaProvider;
+
+#[async_trait::async_trait]
+impl SchemaProvider for StagingSchemaProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn table_names(&self) -> Vec<String> {
+        PARSEABLE.streams.list()
+    }
+
+    async fn table(&self, name: &str) -> DataFusionResult<Option<Arc<dyn TableProvider>>> {
+        if self.table_exist(name) {
+            Ok(Some(Arc::new(StandardTableProvider {
+                schema: PARSEABLE
+                    .get_stream(name)
+                    .expect(STREAM_EXISTS)
+                    .get_schema(),
+                stream: name.to_owned(),
+                staging_only: true,
             })))
         } else {
             Ok(None)
@@ -107,6 +146,8 @@ struct StandardTableProvider {
     schema: SchemaRef,
     // prefix under which to find snapshot
     stream: String,
+    // skip the data in object storage
+    staging_only: bool,
 }
 
 impl StandardTableProvider {
@@ -252,7 +293,7 @@ impl StandardTableProvider {
         };
 
         // Staging arrow exection plan
-        let records = staging.recordbatches_cloned(&self.schema);
+        let records = staging.recordbatches_cloned(&self.schema)?;
         let arrow_exec = reversed_mem_table(records, self.schema.clone())?
             .scan(state, projection, filters, limit)
             .await?;
@@ -519,6 +560,23 @@ impl TableProvider for StandardTableProvider {
         limit: Option<usize>,
     ) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
         let mut execution_plans = vec![];
+        if self.staging_only {
+            let time_partition = PARSEABLE
+                .get_stream(&self.stream)
+                .ok()
+                .and_then(|stream| stream.get_time_partition());
+            self.get_staging_execution_plan(
+                &mut execution_plans,
+                projection,
+                filters,
+                limit,
+                state,
+                time_partition.as_ref(),
+            )
+            .await?;
+            return self.final_plan(execution_plans, projection);
+        }
+
         let glob_storage = PARSEABLE.storage.get_object_store();
 
         let object_store_format: ObjectStoreFormat = serde_json::from_slice(
@@ -532,7 +590,17 @@ impl TableProvider for StandardTableProvider {
 
         let time_partition = object_store_format.time_partition;
         let mut time_filters = extract_primary_filter(filters, &time_partition);
-        if is_within_staging_window(&time_filters) {
+        // staging holds data uploaded after any past time, it is skipped when reading as of one,
+        // and isn't split in files a sample can be taken of
+        let as_of = state
+            .config()
+            .get_extension::<SnapshotAsOf>()
+            .map(|as_of| as_of.0);
+        let sample = state
+            .config()
+            .get_extension::<SampleFraction>()
+            .map(|sample| sample.0);
+        if as_of.is_none() && sample.is_none() && is_within_staging_window(&time_filters) {
             self.get_staging_execution_plan(
                 &mut execution_plans,
                 projection,
@@ -543,26 +611,21 @@ impl TableProvider for StandardTableProvider {
             )
             .await?;
         };
-        let mut merged_snapshot = Snapshot::default();
-        if PARSEABLE.options.mode == Mode::Query || PARSEABLE.options.mode == Mode::Prism {
-            let obs = PARSEABLE
-                .metastore
-                .get_all_stream_jsons(&self.stream, None)
-                .await;
-            if let Ok(obs) = obs {
-                for ob in obs {
-                    if let Ok(object_store_format) =
-                        serde_json::from_slice::<ObjectStoreFormat>(&ob)
-                    {
-                        let snapshot = object_store_format.snapshot;
-                        for manifest in snapshot.manifest_list {
-                            merged_snapshot.manifest_list.push(manifest);
-                        }
-                    }
-                }
-            }
-        } else {
-            merged_snapshot = object_store_format.snapshot;
+        let merged_snapshot =
+            if PARSEABLE.options.mode == Mode::Query || PARSEABLE.options.mode == Mode::Prism {
+                consolidation::merged_snapshot(&self.stream, time_partition.as_ref(), &time_filters)
+                    .await
+            } else {
+                object_store_format.snapshot
+            };
+
+        if let Some(as_of) = as_of
+            && let Some(removal) = merged_snapshot.removed_after(as_of, &time_filters)
+        {
+            return Err(DataFusionError::Plan(format!(
+                "Data of stream {} between {} and {} was removed at {}, after as_of",
+                self.stream, removal.time_lower_bound, removal.time_upper_bound, removal.removed_at
+            )));
         }
 
         // Is query timerange is overlapping with older data.
@@ -594,6 +657,16 @@ impl TableProvider for StandardTableProvider {
             &self.stream,
         )
         .await?;
+        // files uploaded before upload times were tracked are considered part of any past snapshot
+        if let Some(as_of) = as_of {
+            manifest_files.retain(|file| {
+                file.uploaded_at
+                    .is_none_or(|uploaded_at| uploaded_at <= as_of)
+            });
+        }
+        if let Some(fraction) = sample {
+            manifest_files.retain(|file| is_sampled(&file.file_path, fraction));
+        }
 
         if manifest_files.is_empty() {
             return self.final_plan(execution_plans, projection);
@@ -623,7 +696,7 @@ impl TableProvider for StandardTableProvider {
         let (partitioned_files, statistics) = self.partitioned_files(manifest_files);
         self.create_parquet_physical_plan(
             &mut execution_plans,
-            ObjectStoreUrl::parse(glob_storage.store_url()).unwrap(),
+            ObjectStoreUrl::parse(glob_storage.stream_store_url(&self.stream)).unwrap(),
             partitioned_files,
             statistics,
             projection,
@@ -155,6 +155,8 @@ pub fn init(metadata: &StorageMetadata) {
 pub enum SessionKey {
     BasicAuth { username: String, password: String },
     SessionId(ulid::Ulid),
+    // user mapped from a client certificate verified during the TLS handshake
+    ClientCert { username: String },
 }
 
 #[derive(Debug, Default)]
@@ -214,6 +216,12 @@ impl Sessions {
         }
     }
 
+    // remove the sessions of all the users, returns the number of sessions removed
+    pub fn remove_all(&mut self) -> usize {
+        self.user_sessions.clear();
+        self.active_sessions.drain().count()
+    }
+
     // remove sessions related to a user
     pub fn remove_user(&mut self, username: &str) {
         let sessions = self.user_sessions.remove(username);
@@ -170,6 +170,24 @@ impl Users {
             return res;
         }
 
+        // the certificate was already verified during the TLS handshake,
+        // only the user it maps to needs to exist
+        if let SessionKey::ClientCert { username } = &key {
+            let Some(user) = users().get(username) else {
+                return Response::UnAuthorized;
+            };
+            let mut sessions = mut_sessions();
+            sessions.track_new(
+                username.clone(),
+                key.clone(),
+                DateTime::<Utc>::MAX_UTC,
+                roles_to_permission(user.roles()),
+            );
+            return sessions
+                .check_auth(&key, action, context_stream, context_user)
+                .expect("entry for this key just added");
+        }
+
         // attempt reloading permissions into new session for basic auth user
         // id user will be reloaded only through login endpoint
         let SessionKey::BasicAuth { username, password } = &key else {
@@ -35,10 +35,46 @@ pub enum Action {
     DeleteStream,
     GetRetention,
     PutRetention,
+    GetMasking,
+    PutMasking,
+    GetSampling,
+    PutSampling,
+    GetIndexedColumns,
+    PutIndexedColumns,
+    GetReservedFieldPolicy,
+    PutReservedFieldPolicy,
+    GetTypeCoercionPolicy,
+    PutTypeCoercionPolicy,
+    GetTypeWideningPolicy,
+    PutTypeWideningPolicy,
+    GetTimestampPolicy,
+    PutTimestampPolicy,
+    GetPartitionExpressions,
+    PutPartitionExpressions,
+    GetTransforms,
+    PutTransforms,
+    GetRowGroupSize,
+    PutRowGroupSize,
+    GetSchemaFreeze,
+    PutSchemaFreeze,
+    GetNumericDowncast,
+    PutNumericDowncast,
+    GetLateEventWindow,
+    PutLateEventWindow,
+    GetReadOnly,
+    PutReadOnly,
+    GetTimestampColumn,
+    PutTimestampColumn,
+    RenameColumn,
+    RecomputeFirstEvent,
+    RebuildManifest,
+    ConsolidateSnapshot,
     PutHotTierEnabled,
     GetHotTierEnabled,
     DeleteHotTierEnabled,
+    CreateAlert,
     PutAlert,
+    PutAlertState,
     GetAlert,
     DeleteAlert,
     PutUser,
@@ -51,6 +87,8 @@ pub enum Action {
     DeleteRole,
     ListRole,
     GetAbout,
+    GetConfig,
+    RotateSessions,
     AddLLM,
     DeleteLLM,
     GetLLM,
@@ -120,6 +158,8 @@ impl RoleBuilder {
                 | Action::GetUserRoles
                 | Action::DeleteUser
                 | Action::GetAbout
+                | Action::GetConfig
+                | Action::RotateSessions
                 | Action::PutRole
                 | Action::GetRole
                 | Action::DeleteRole
@@ -145,7 +185,9 @@ impl RoleBuilder {
                 | Action::ListFilter
                 | Action::CreateFilter
                 | Action::DeleteFilter
+                | Action::CreateAlert
                 | Action::PutAlert
+                | Action::PutAlertState
                 | Action::GetAlert
                 | Action::DeleteAlert
                 | Action::CreateUserGroup
@@ -166,6 +208,40 @@ impl RoleBuilder {
                 | Action::GetStats
                 | Action::GetRetention
                 | Action::PutRetention
+                | Action::GetMasking
+                | Action::PutMasking
+                | Action::GetSampling
+                | Action::PutSampling
+                | Action::GetIndexedColumns
+                | Action::PutIndexedColumns
+                | Action::GetReservedFieldPolicy
+                | Action::PutReservedFieldPolicy
+                | Action::GetTypeCoercionPolicy
+                | Action::PutTypeCoercionPolicy
+                | Action::GetTypeWideningPolicy
+                | Action::PutTypeWideningPolicy
+                | Action::GetTimestampPolicy
+                | Action::PutTimestampPolicy
+                | Action::GetPartitionExpressions
+                | Action::PutPartitionExpressions
+                | Action::GetTransforms
+                | Action::PutTransforms
+                | Action::GetRowGroupSize
+                | Action::PutRowGroupSize
+                | Action::GetSchemaFreeze
+                | Action::PutSchemaFreeze
+                | Action::GetNumericDowncast
+                | Action::PutNumericDowncast
+                | Action::GetLateEventWindow
+                | Action::PutLateEventWindow
+                | Action::GetReadOnly
+                | Action::PutReadOnly
+                | Action::GetTimestampColumn
+                | Action::PutTimestampColumn
+                | Action::RenameColumn
+                | Action::RecomputeFirstEvent
+                | Action::RebuildManifest
+                | Action::ConsolidateSnapshot
                 | Action::All => Permission::Resource(action, self.resource_type.clone().unwrap()),
             };
             perms.push(perm);
@@ -239,10 +315,46 @@ pub mod model {
                 Action::GetStats,
                 Action::GetRetention,
                 Action::PutRetention,
+                Action::GetMasking,
+                Action::PutMasking,
+                Action::GetSampling,
+                Action::PutSampling,
+                Action::GetIndexedColumns,
+                Action::PutIndexedColumns,
+                Action::GetReservedFieldPolicy,
+                Action::PutReservedFieldPolicy,
+                Action::GetTypeCoercionPolicy,
+                Action::PutTypeCoercionPolicy,
+                Action::GetTypeWideningPolicy,
+                Action::PutTypeWideningPolicy,
+                Action::GetTimestampPolicy,
+                Action::PutTimestampPolicy,
+                Action::GetPartitionExpressions,
+                Action::PutPartitionExpressions,
+                Action::GetTransforms,
+                Action::PutTransforms,
+                Action::GetRowGroupSize,
+                Action::PutRowGroupSize,
+                Action::GetSchemaFreeze,
+                Action::PutSchemaFreeze,
+                Action::GetNumericDowncast,
+                Action::PutNumericDowncast,
+                Action::GetLateEventWindow,
+                Action::PutLateEventWindow,
+                Action::GetReadOnly,
+                Action::PutReadOnly,
+                Action::GetTimestampColumn,
+                Action::PutTimestampColumn,
+                Action::RenameColumn,
+                Action::RecomputeFirstEvent,
+                Action::RebuildManifest,
+                Action::ConsolidateSnapshot,
                 Action::PutHotTierEnabled,
                 Action::GetHotTierEnabled,
                 Action::DeleteHotTierEnabled,
+                Action::CreateAlert,
                 Action::PutAlert,
+                Action::PutAlertState,
                 Action::GetAlert,
                 Action::DeleteAlert,
                 Action::AddLLM,
@@ -274,10 +386,43 @@ pub mod model {
                 Action::GetSchema,
                 Action::GetStats,
                 Action::PutRetention,
+                Action::CreateAlert,
                 Action::PutAlert,
+                Action::PutAlertState,
                 Action::GetAlert,
                 Action::DeleteAlert,
                 Action::GetRetention,
+                Action::GetMasking,
+                Action::GetSampling,
+                Action::PutSampling,
+                Action::GetIndexedColumns,
+                Action::PutIndexedColumns,
+                Action::GetReservedFieldPolicy,
+                Action::PutReservedFieldPolicy,
+                Action::GetTypeCoercionPolicy,
+                Action::PutTypeCoercionPolicy,
+                Action::GetTypeWideningPolicy,
+                Action::PutTypeWideningPolicy,
+                Action::GetTimestampPolicy,
+                Action::PutTimestampPolicy,
+                Action::GetPartitionExpressions,
+                Action::PutPartitionExpressions,
+                Action::GetTransforms,
+                Action::PutTransforms,
+                Action::GetRowGroupSize,
+                Action::PutRowGroupSize,
+                Action::GetSchemaFreeze,
+                Action::PutSchemaFreeze,
+                Action::GetNumericDowncast,
+                Action::PutNumericDowncast,
+                Action::GetLateEventWindow,
+                Action::PutLateEventWindow,
+                Action::GetReadOnly,
+                Action::PutReadOnly,
+                Action::GetTimestampColumn,
+                Action::PutTimestampColumn,
+                Action::RenameColumn,
+                Action::RecomputeFirstEvent,
                 Action::PutHotTierEnabled,
                 Action::GetHotTierEnabled,
                 Action::DeleteHotTierEnabled,
@@ -329,6 +474,20 @@ pub mod model {
                 Action::CreateDashboard,
                 Action::DeleteDashboard,
                 Action::GetRetention,
+                Action::GetSampling,
+                Action::GetIndexedColumns,
+                Action::GetReservedFieldPolicy,
+                Action::GetTypeCoercionPolicy,
+                Action::GetTypeWideningPolicy,
+                Action::GetTimestampPolicy,
+                Action::GetPartitionExpressions,
+                Action::GetTransforms,
+                Action::GetRowGroupSize,
+                Action::GetSchemaFreeze,
+                Action::GetNumericDowncast,
+                Action::GetLateEventWindow,
+                Action::GetReadOnly,
+                Action::GetTimestampColumn,
                 Action::GetStreamInfo,
                 Action::GetUserRoles,
                 Action::GetAlert,
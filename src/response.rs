@@ -16,16 +16,81 @@
  *
  */
 
+use std::{fmt, str::FromStr};
+
 use crate::{handlers::http::query::QueryError, utils::arrow::record_batches_to_json};
 use datafusion::arrow::record_batch::RecordBatch;
 use itertools::Itertools;
-use serde_json::{Value, json};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
 use tracing::info;
 
+/// How the null values of query results are rendered, set with `?null_mode=`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NullMode {
+    /// the columns are left out of the records they are null in
+    Omit,
+    /// `null`
+    #[default]
+    Null,
+    /// `""`
+    Empty,
+}
+
+impl NullMode {
+    /// Renders the null values of a record with all the `columns`
+    pub fn apply(self, record: &mut Map<String, Value>, columns: &[String]) {
+        match self {
+            NullMode::Omit => record.retain(|_, value| !value.is_null()),
+            NullMode::Null | NullMode::Empty => {
+                let null = match self {
+                    NullMode::Empty => Value::String(String::new()),
+                    _ => Value::Null,
+                };
+                for column in columns {
+                    match record.get_mut(column) {
+                        Some(value) if !value.is_null() => {}
+                        Some(value) => *value = null.clone(),
+                        None => {
+                            record.insert(column.clone(), null.clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for NullMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "omit" => Ok(NullMode::Omit),
+            "null" => Ok(NullMode::Null),
+            "empty" => Ok(NullMode::Empty),
+            _ => Err(format!(
+                "invalid null_mode {s}, expected one of omit, null or empty"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for NullMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NullMode::Omit => "omit",
+            NullMode::Null => "null",
+            NullMode::Empty => "empty",
+        })
+    }
+}
+
 pub struct QueryResponse {
     pub records: Vec<RecordBatch>,
     pub fields: Vec<String>,
-    pub fill_null: bool,
+    pub null_mode: NullMode,
     pub with_fields: bool,
 }
 
@@ -34,14 +99,18 @@ impl QueryResponse {
         info!("{}", "Returning query results");
         let mut json_records = record_batches_to_json(&self.records)?;
 
-        if self.fill_null {
-            for map in &mut json_records {
-                for field in &self.fields {
-                    if !map.contains_key(field) {
-                        map.insert(field.clone(), Value::Null);
-                    }
-                }
-            }
+        // the JSON writer leaves out null values, the columns of the records are filled back in
+        let columns = match self.records.first() {
+            Some(batch) if self.fields.is_empty() => batch
+                .schema()
+                .fields()
+                .iter()
+                .map(|field| field.name().clone())
+                .collect(),
+            _ => self.fields.clone(),
+        };
+        for map in &mut json_records {
+            self.null_mode.apply(map, &columns);
         }
         let values = json_records.into_iter().map(Value::Object).collect_vec();
 
@@ -57,3 +126,38 @@ impl QueryResponse {
         Ok(response)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> Map<String, Value> {
+        json!({"host": "a", "status": null})
+            .as_object()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn null_modes_render_null_and_missing_columns() {
+        let columns = ["host", "status", "user"].map(String::from);
+
+        let mut omitted = record();
+        NullMode::Omit.apply(&mut omitted, &columns);
+        assert_eq!(Value::Object(omitted), json!({"host": "a"}));
+
+        let mut nulls = record();
+        NullMode::Null.apply(&mut nulls, &columns);
+        assert_eq!(
+            Value::Object(nulls),
+            json!({"host": "a", "status": null, "user": null})
+        );
+
+        let mut empty = record();
+        NullMode::Empty.apply(&mut empty, &columns);
+        assert_eq!(
+            Value::Object(empty),
+            json!({"host": "a", "status": "", "user": ""})
+        );
+    }
+}
@@ -61,7 +61,10 @@ use crate::{
 use super::{
     CONNECT_TIMEOUT_SECS, MIN_MULTIPART_UPLOAD_SIZE, ObjectStorage, ObjectStorageError,
     ObjectStorageProvider, PARSEABLE_ROOT_DIRECTORY, REQUEST_TIMEOUT_SECS,
-    STREAM_METADATA_FILE_NAME, metrics_layer::MetricLayer, object_storage::parseable_json_path,
+    STREAM_METADATA_FILE_NAME,
+    metrics_layer::{MetricLayer, observe_request_time},
+    multipart_slots::acquire_multipart_upload_slot,
+    object_storage::parseable_json_path,
     to_object_store_path,
 };
 
@@ -170,6 +173,18 @@ impl ObjectStorageProvider for AzureBlobConfig {
         "blob-store"
     }
 
+    fn redacted_config(&self) -> serde_json::Value {
+        serde_json::json!({
+            "endpoint": super::redact_url(&self.endpoint_url),
+            "account": self.account,
+            "container": self.container,
+            "accessKey": super::redact_secret(&self.access_key),
+            "clientId": self.client_id,
+            "clientSecret": super::redact_secret(&self.client_secret),
+            "tenantId": self.tenant_id,
+        })
+    }
+
     fn get_datafusion_runtime(&self) -> RuntimeEnvBuilder {
         let azure = self.get_default_builder().build().unwrap();
         // limit objectstore to a concurrent request limit
@@ -213,7 +228,9 @@ pub struct BlobStore {
 
 impl BlobStore {
     async fn _get_object(&self, path: &RelativePath) -> Result<Bytes, ObjectStorageError> {
+        let time = std::time::Instant::now();
         let resp = self.client.get(&to_object_store_path(path)).await;
+        observe_request_time("azure_blob", "GET", &resp, path.as_str(), time);
         increment_object_store_calls_by_date("GET", &Utc::now().date_naive().to_string());
 
         match resp {
@@ -240,7 +257,9 @@ impl BlobStore {
         path: &RelativePath,
         resource: PutPayload,
     ) -> Result<(), ObjectStorageError> {
+        let time = std::time::Instant::now();
         let resp = self.client.put(&to_object_store_path(path), resource).await;
+        observe_request_time("azure_blob", "PUT", &resp, path.as_str(), time);
         increment_object_store_calls_by_date("PUT", &Utc::now().date_naive().to_string());
         match resp {
             Ok(_) => {
@@ -355,6 +374,8 @@ impl BlobStore {
         key: &RelativePath,
         path: &Path,
     ) -> Result<(), ObjectStorageError> {
+        // held until the upload completes or fails, bounding the uploads in flight across streams
+        let _slot = acquire_multipart_upload_slot().await;
         let mut file = OpenOptions::new().read(true).open(path).await?;
         let location = &to_object_store_path(key);
 
@@ -59,7 +59,10 @@ use tracing::error;
 use super::{
     CONNECT_TIMEOUT_SECS, MIN_MULTIPART_UPLOAD_SIZE, ObjectStorage, ObjectStorageError,
     ObjectStorageProvider, PARSEABLE_ROOT_DIRECTORY, REQUEST_TIMEOUT_SECS,
-    STREAM_METADATA_FILE_NAME, metrics_layer::MetricLayer, object_storage::parseable_json_path,
+    STREAM_METADATA_FILE_NAME,
+    metrics_layer::{MetricLayer, observe_request_time},
+    multipart_slots::acquire_multipart_upload_slot,
+    object_storage::parseable_json_path,
     to_object_store_path,
 };
 
@@ -131,6 +134,14 @@ impl ObjectStorageProvider for GcsConfig {
         "gcs"
     }
 
+    fn redacted_config(&self) -> serde_json::Value {
+        serde_json::json!({
+            "endpoint": super::redact_url(&self.endpoint_url),
+            "bucket": self.bucket_name,
+            "tlsSkipVerify": self.skip_tls,
+        })
+    }
+
     fn get_datafusion_runtime(&self) -> RuntimeEnvBuilder {
         let gcs = self.get_default_builder().build().unwrap();
 
@@ -178,7 +189,9 @@ pub struct Gcs {
 
 impl Gcs {
     async fn _get_object(&self, path: &RelativePath) -> Result<Bytes, ObjectStorageError> {
+        let time = std::time::Instant::now();
         let resp = self.client.get(&to_object_store_path(path)).await;
+        observe_request_time("gcs", "GET", &resp, path.as_str(), time);
         increment_object_store_calls_by_date("GET", &Utc::now().date_naive().to_string());
         match resp {
             Ok(resp) => {
@@ -204,7 +217,9 @@ impl Gcs {
         path: &RelativePath,
         resource: PutPayload,
     ) -> Result<(), ObjectStorageError> {
+        let time = std::time::Instant::now();
         let resp = self.client.put(&to_object_store_path(path), resource).await;
+        observe_request_time("gcs", "PUT", &resp, path.as_str(), time);
         increment_object_store_calls_by_date("PUT", &Utc::now().date_naive().to_string());
         match resp {
             Ok(_) => {
@@ -319,6 +334,8 @@ impl Gcs {
         key: &RelativePath,
         path: &Path,
     ) -> Result<(), ObjectStorageError> {
+        // held until the upload completes or fails, bounding the uploads in flight across streams
+        let _slot = acquire_multipart_upload_slot().await;
         let mut file = OpenOptions::new().read(true).open(path).await?;
         let location = &to_object_store_path(key);
 
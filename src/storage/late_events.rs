@@ -0,0 +1,128 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{OBJECT_STORE_DATA_GRANULARITY, utils::time::TimeRange};
+
+/// How far behind the partition being written the events of a stream can be, events within the
+/// window are written to the partition of their event time, the ones beyond it are handled as per
+/// `beyond`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LateEventWindow {
+    /// e.g. "30m", "6h" or "2d"
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+    #[serde(default)]
+    pub beyond: LateEventAction,
+}
+
+/// What becomes of the events further behind than the late event window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LateEventAction {
+    /// written to the partition of the time they are ingested at, the late bucket
+    #[default]
+    Bucket,
+    Reject,
+}
+
+/// Where an event goes, as per the late event window of its stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LateEvent {
+    /// the event belongs to the partition being written
+    OnTime,
+    /// the event is written to the earlier partition of its event time
+    Routed,
+    /// the event is written to the partition being written
+    Bucketed,
+    Rejected,
+}
+
+impl LateEvent {
+    /// Label of the event in the late events metric
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::OnTime => "on_time",
+            Self::Routed => "routed",
+            Self::Bucketed => "bucketed",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+
+impl LateEventWindow {
+    /// Where an event with `event_time` ingested at `now` goes. Events are late once the
+    /// partition being written is past their own, its files having been sealed.
+    pub fn route(&self, event_time: NaiveDateTime, now: DateTime<Utc>) -> LateEvent {
+        let event_time = event_time.and_utc();
+        if event_time >= TimeRange::granularity_range(now, OBJECT_STORE_DATA_GRANULARITY).start {
+            return LateEvent::OnTime;
+        }
+        let window = TimeDelta::from_std(self.window).unwrap_or(TimeDelta::MAX);
+        if now.signed_duration_since(event_time) <= window {
+            return LateEvent::Routed;
+        }
+
+        match self.beyond {
+            LateEventAction::Bucket => LateEvent::Bucketed,
+            LateEventAction::Reject => LateEvent::Rejected,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn late_events_are_routed_within_the_window_only() {
+        let window: LateEventWindow =
+            serde_json::from_value(json!({"window": "1h", "beyond": "reject"})).unwrap();
+        let now = "2024-05-01T10:30:20Z".parse::<DateTime<Utc>>().unwrap();
+        let event_time = |time: &str| time.parse::<DateTime<Utc>>().unwrap().naive_utc();
+
+        assert_eq!(
+            window.route(event_time("2024-05-01T10:30:05Z"), now),
+            LateEvent::OnTime
+        );
+        assert_eq!(
+            window.route(event_time("2024-05-01T09:45:00Z"), now),
+            LateEvent::Routed
+        );
+        assert_eq!(
+            window.route(event_time("2024-05-01T08:00:00Z"), now),
+            LateEvent::Rejected
+        );
+
+        let window = LateEventWindow {
+            beyond: LateEventAction::Bucket,
+            ..window
+        };
+        assert_eq!(
+            window.route(event_time("2024-05-01T08:00:00Z"), now),
+            LateEvent::Bucketed
+        );
+    }
+}
@@ -75,6 +75,12 @@ impl ObjectStorageProvider for FSConfig {
         "drive"
     }
 
+    fn redacted_config(&self) -> serde_json::Value {
+        serde_json::json!({
+            "root": self.root.display().to_string(),
+        })
+    }
+
     fn get_datafusion_runtime(&self) -> RuntimeEnvBuilder {
         RuntimeEnvBuilder::new()
     }
@@ -0,0 +1,395 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow::compute::cast;
+use arrow_array::{Array, ArrayRef, RecordBatch, StringArray};
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use datafusion::common::tree_node::Transformed;
+use datafusion::error::DataFusionError;
+use datafusion::functions::expr_fn::{encode, sha256};
+use datafusion::logical_expr::{self, Expr, LogicalPlan, Projection, lit, when};
+use datafusion::prelude::Column;
+use serde::{Deserialize, Serialize};
+
+use crate::parseable::PARSEABLE;
+use crate::rbac::Users;
+use crate::rbac::map::{SessionKey, read_user_groups};
+use crate::utils::get_hash;
+
+pub const REDACTED_VALUE: &str = "***";
+
+/// How the values of a masked column are rewritten in query results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MaskingStrategy {
+    /// Replace the value with its sha256 hex digest, keeps values joinable/groupable
+    Hash,
+    /// Replace the value with `***`
+    Redact,
+}
+
+impl MaskingStrategy {
+    fn apply(&self, value: &str) -> String {
+        match self {
+            MaskingStrategy::Hash => get_hash(value),
+            MaskingStrategy::Redact => REDACTED_VALUE.to_owned(),
+        }
+    }
+
+    /// The expression masking `column` in a query, evaluates to the same values as [`Self::apply`]
+    fn expr(&self, column: Expr) -> Result<Expr, DataFusionError> {
+        let value = logical_expr::cast(column, DataType::Utf8);
+        match self {
+            MaskingStrategy::Hash => Ok(encode(sha256(value), lit("hex"))),
+            // a case without else is null for the null values
+            MaskingStrategy::Redact => when(value.is_not_null(), lit(REDACTED_VALUE)).end(),
+        }
+    }
+}
+
+/// Per stream masking configuration, maps a role name to the columns
+/// that are masked for the users holding that role
+///
+/// ```json
+/// { "support": { "email": "hash", "phone": "redact" } }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MaskingConfig(HashMap<String, HashMap<String, MaskingStrategy>>);
+
+impl MaskingConfig {
+    /// Ensures every masked column is part of the stream's schema
+    pub fn validate(&self, schema: &Schema) -> Result<(), String> {
+        for (role, columns) in &self.0 {
+            for column in columns.keys() {
+                if schema.field_with_name(column).is_err() {
+                    return Err(format!(
+                        "column \"{column}\" masked for role \"{role}\" does not exist in the stream's schema"
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Collects the masked columns that apply to any of the given roles,
+    /// when more than one role masks a column the stricter strategy wins
+    fn masked_columns<'a>(
+        &self,
+        roles: impl IntoIterator<Item = &'a String>,
+        masks: &mut HashMap<String, MaskingStrategy>,
+    ) {
+        for role in roles {
+            let Some(columns) = self.0.get(role) else {
+                continue;
+            };
+            for (column, strategy) in columns {
+                masks
+                    .entry(column.clone())
+                    .and_modify(|existing| *existing = (*existing).max(*strategy))
+                    .or_insert(*strategy);
+            }
+        }
+    }
+}
+
+/// Resolves the columns to be masked for the user behind `session` when querying `streams`,
+/// considering both the roles assigned directly to the user and the ones inherited from user groups
+pub fn masks_for_session(
+    session: &SessionKey,
+    streams: &[String],
+) -> HashMap<String, MaskingStrategy> {
+    let mut masks = HashMap::new();
+    let Some(userid) = Users.get_userid_from_session(session) else {
+        return masks;
+    };
+
+    let mut roles = Users.get_role(&userid);
+    for group in Users.get_user_groups(&userid) {
+        if let Some(group) = read_user_groups().get(&group) {
+            roles.extend(group.roles.iter().cloned());
+        }
+    }
+
+    for stream in streams {
+        if let Some(config) = PARSEABLE
+            .get_stream(stream)
+            .ok()
+            .and_then(|stream| stream.get_masking())
+        {
+            config.masked_columns(&roles, &mut masks);
+        }
+    }
+
+    masks
+}
+
+/// Masks the columns of every scan in `plan` right above the scan, so that no expression of the
+/// query, be it an alias, a function, a filter or a grouping, can reach the original values.
+/// Masked columns are always strings, under their original name and qualifier
+pub fn mask_logical_plan(
+    plan: LogicalPlan,
+    masks: &HashMap<String, MaskingStrategy>,
+) -> Result<LogicalPlan, DataFusionError> {
+    if masks.is_empty() {
+        return Ok(plan);
+    }
+
+    plan.transform_up_with_subqueries(|plan| {
+        let LogicalPlan::TableScan(scan) = &plan else {
+            // the types of the masked columns change, the schemas above the scans are derived again
+            return plan.recompute_schema().map(Transformed::yes);
+        };
+        let schema = scan.projected_schema.clone();
+        if !schema
+            .fields()
+            .iter()
+            .any(|field| masks.contains_key(field.name()))
+        {
+            return Ok(Transformed::no(plan));
+        }
+
+        let exprs = schema
+            .iter()
+            .map(|(qualifier, field)| {
+                let column = Expr::Column(Column::from((qualifier, field)));
+                match masks.get(field.name()) {
+                    Some(strategy) => Ok(strategy
+                        .expr(column)?
+                        .alias_qualified(qualifier.cloned(), field.name())),
+                    None => Ok(column),
+                }
+            })
+            .collect::<Result<Vec<_>, DataFusionError>>()?;
+        Projection::try_new(exprs, Arc::new(plan))
+            .map(|projection| Transformed::yes(LogicalPlan::Projection(projection)))
+    })
+    .map(|transformed| transformed.data)
+}
+
+/// Rewrites the masked columns of a record batch, masked columns are always returned as strings.
+/// Only for batches of raw events, query results are masked through [`mask_logical_plan`]
+pub fn mask_record_batch(
+    batch: RecordBatch,
+    masks: &HashMap<String, MaskingStrategy>,
+) -> Result<RecordBatch, ArrowError> {
+    if masks.is_empty() {
+        return Ok(batch);
+    }
+
+    let schema = batch.schema();
+    let mut fields = Vec::with_capacity(schema.fields().len());
+    let mut columns = Vec::with_capacity(batch.num_columns());
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        let Some(strategy) = masks.get(field.name()) else {
+            fields.push(field.clone());
+            columns.push(column.clone());
+            continue;
+        };
+
+        let values = cast(column, &DataType::Utf8)?;
+        let values = values
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .expect("column was cast to utf8");
+        let masked: StringArray = values
+            .iter()
+            .map(|value| value.map(|value| strategy.apply(value)))
+            .collect();
+
+        fields.push(Arc::new(Field::new(field.name(), DataType::Utf8, true)));
+        columns.push(Arc::new(masked) as ArrayRef);
+    }
+
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::Int64Array;
+    use datafusion::prelude::SessionContext;
+
+    use super::*;
+
+    fn batch() -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("email", DataType::Utf8, true),
+            Field::new("phone", DataType::Int64, true),
+            Field::new("level", DataType::Utf8, true),
+        ]);
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(StringArray::from(vec![Some("a@b.com"), None])),
+                Arc::new(Int64Array::from(vec![Some(12345), Some(6789)])),
+                Arc::new(StringArray::from(vec![Some("info"), Some("warn")])),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn masks_only_configured_columns() {
+        let masks = HashMap::from([
+            ("email".to_owned(), MaskingStrategy::Hash),
+            ("phone".to_owned(), MaskingStrategy::Redact),
+        ]);
+        let masked = mask_record_batch(batch(), &masks).unwrap();
+
+        let email = masked
+            .column_by_name("email")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(email.value(0), get_hash("a@b.com"));
+        assert!(email.is_null(1));
+
+        let phone = masked
+            .column_by_name("phone")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(phone.value(0), REDACTED_VALUE);
+        assert_eq!(phone.value(1), REDACTED_VALUE);
+
+        let level = masked
+            .column_by_name("level")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(level.value(0), "info");
+    }
+
+    // runs `sql` over `batch` with the email hashed and the phone redacted
+    async fn masked_query(sql: &str) -> Vec<RecordBatch> {
+        let ctx = SessionContext::new();
+        ctx.register_batch("logs", batch()).unwrap();
+        let masks = HashMap::from([
+            ("email".to_owned(), MaskingStrategy::Hash),
+            ("phone".to_owned(), MaskingStrategy::Redact),
+        ]);
+        let plan = ctx.state().create_logical_plan(sql).await.unwrap();
+        let plan = mask_logical_plan(plan, &masks).unwrap();
+        ctx.execute_logical_plan(plan)
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap()
+    }
+
+    fn strings(batches: &[RecordBatch], column: &str) -> Vec<Option<String>> {
+        batches
+            .iter()
+            .flat_map(|batch| {
+                let values = cast(batch.column_by_name(column).unwrap(), &DataType::Utf8).unwrap();
+                let values = values.as_any().downcast_ref::<StringArray>().unwrap();
+                values
+                    .iter()
+                    .map(|value| value.map(str::to_owned))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn aliased_columns_are_masked() {
+        let batches = masked_query("SELECT email AS contact, phone AS number FROM logs").await;
+
+        assert_eq!(
+            strings(&batches, "contact"),
+            vec![Some(get_hash("a@b.com")), None]
+        );
+        assert_eq!(
+            strings(&batches, "number"),
+            vec![Some(REDACTED_VALUE.to_owned()); 2]
+        );
+    }
+
+    #[tokio::test]
+    async fn functions_see_masked_values() {
+        let batches = masked_query(
+            "SELECT upper(email) AS email, substr(CAST(phone AS VARCHAR), 1, 2) AS prefix FROM logs",
+        )
+        .await;
+
+        assert_eq!(
+            strings(&batches, "email"),
+            vec![Some(get_hash("a@b.com").to_uppercase()), None]
+        );
+        assert_eq!(strings(&batches, "prefix"), vec![Some("**".to_owned()); 2]);
+    }
+
+    #[tokio::test]
+    async fn filters_and_groups_see_masked_values() {
+        let batches = masked_query("SELECT level FROM logs WHERE email = 'a@b.com'").await;
+        assert!(strings(&batches, "level").is_empty());
+
+        let batches = masked_query("SELECT level FROM logs WHERE phone LIKE '123%'").await;
+        assert!(strings(&batches, "level").is_empty());
+
+        // hashed values can still be matched on
+        let batches = masked_query(&format!(
+            "SELECT level FROM logs WHERE email = '{}'",
+            get_hash("a@b.com")
+        ))
+        .await;
+        assert_eq!(strings(&batches, "level"), vec![Some("info".to_owned())]);
+
+        let batches = masked_query(
+            "SELECT phone, count(*) AS events FROM logs GROUP BY phone ORDER BY phone",
+        )
+        .await;
+        assert_eq!(
+            strings(&batches, "phone"),
+            vec![Some(REDACTED_VALUE.to_owned())]
+        );
+        assert_eq!(strings(&batches, "events"), vec![Some("2".to_owned())]);
+    }
+
+    #[test]
+    fn stricter_strategy_wins_across_roles() {
+        let config: MaskingConfig = serde_json::from_value(serde_json::json!({
+            "support": { "email": "hash" },
+            "auditor": { "email": "redact" }
+        }))
+        .unwrap();
+        let roles = ["support".to_owned(), "auditor".to_owned()];
+        let mut masks = HashMap::new();
+        config.masked_columns(&roles, &mut masks);
+
+        assert_eq!(masks.get("email"), Some(&MaskingStrategy::Redact));
+    }
+
+    #[test]
+    fn validate_rejects_unknown_columns() {
+        let config: MaskingConfig = serde_json::from_value(serde_json::json!({
+            "support": { "ssn": "redact" }
+        }))
+        .unwrap();
+
+        assert!(config.validate(&batch().schema()).is_err());
+    }
+}
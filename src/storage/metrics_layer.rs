@@ -32,6 +32,23 @@ use object_store::{
 };
 
 use crate::metrics::STORAGE_REQUEST_RESPONSE_TIME;
+use crate::storage::{MANIFEST_FILE, SCHEMA_FILE_NAME};
+
+const METADATA_PURPOSE: &str = "metadata";
+
+/// Purpose of the object an operation is performed on, derived from its path.
+/// Lets operators tell bulk parquet reads apart from manifest/schema/metadata churn.
+pub fn operation_purpose(path: &str) -> &'static str {
+    if path.ends_with(".parquet") {
+        "parquet"
+    } else if path.ends_with(MANIFEST_FILE) {
+        "manifest"
+    } else if path.ends_with(SCHEMA_FILE_NAME) {
+        "schema"
+    } else {
+        METADATA_PURPOSE
+    }
+}
 
 // Public helper function to map object_store errors to HTTP status codes
 pub fn error_to_status_code(err: &object_store::Error) -> &'static str {
@@ -62,6 +79,24 @@ pub fn error_to_status_code(err: &object_store::Error) -> &'static str {
     }
 }
 
+/// Record the latency of a request made directly through an object store client
+/// which isn't wrapped in a [`MetricLayer`]
+pub fn observe_request_time<T>(
+    provider: &str,
+    method: &str,
+    result: &ObjectStoreResult<T>,
+    path: &str,
+    time: time::Instant,
+) {
+    let status = match result {
+        Ok(_) => "200",
+        Err(err) => error_to_status_code(err),
+    };
+    STORAGE_REQUEST_RESPONSE_TIME
+        .with_label_values(&[provider, method, status, operation_purpose(path)])
+        .observe(time.elapsed().as_secs_f64());
+}
+
 #[derive(Debug)]
 pub struct MetricLayer<T: ObjectStore> {
     inner: T,
@@ -92,6 +127,7 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         bytes: PutPayload, /* PutPayload */
     ) -> ObjectStoreResult<PutResult> {
         let time = time::Instant::now();
+        let purpose = operation_purpose(location.as_ref());
         let put_result = self.inner.put(location, bytes).await;
         let elapsed = time.elapsed().as_secs_f64();
 
@@ -101,7 +137,7 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         };
 
         STORAGE_REQUEST_RESPONSE_TIME
-            .with_label_values(&[&self.provider, "PUT", status])
+            .with_label_values(&[&self.provider, "PUT", status, purpose])
             .observe(elapsed);
         put_result
     }
@@ -113,6 +149,7 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         opts: PutOptions,
     ) -> ObjectStoreResult<PutResult> {
         let time = time::Instant::now();
+        let purpose = operation_purpose(location.as_ref());
         let put_result = self.inner.put_opts(location, payload, opts).await;
         let elapsed = time.elapsed().as_secs_f64();
 
@@ -122,7 +159,7 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         };
 
         STORAGE_REQUEST_RESPONSE_TIME
-            .with_label_values(&[&self.provider, "PUT_OPTS", status])
+            .with_label_values(&[&self.provider, "PUT_OPTS", status, purpose])
             .observe(elapsed);
         put_result
     }
@@ -149,6 +186,7 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         opts: PutMultipartOptions,
     ) -> ObjectStoreResult<Box<dyn MultipartUpload>> {
         let time = time::Instant::now();
+        let purpose = operation_purpose(location.as_ref());
         let result = self.inner.put_multipart_opts(location, opts).await;
         let elapsed = time.elapsed().as_secs_f64();
 
@@ -158,7 +196,7 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         };
 
         STORAGE_REQUEST_RESPONSE_TIME
-            .with_label_values(&[&self.provider, "PUT_MULTIPART_OPTS", status])
+            .with_label_values(&[&self.provider, "PUT_MULTIPART_OPTS", status, purpose])
             .observe(elapsed);
         result
     }
@@ -167,6 +205,7 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
     async fn put_multipart(&self, location: &Path) -> ObjectStoreResult<Box<dyn MultipartUpload>> /* ObjectStoreResult<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> */
     {
         let time = time::Instant::now();
+        let purpose = operation_purpose(location.as_ref());
         let result = self.inner.put_multipart(location).await;
         let elapsed = time.elapsed().as_secs_f64();
 
@@ -176,13 +215,14 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         };
 
         STORAGE_REQUEST_RESPONSE_TIME
-            .with_label_values(&[&self.provider, "PUT_MULTIPART", status])
+            .with_label_values(&[&self.provider, "PUT_MULTIPART", status, purpose])
             .observe(elapsed);
         result
     }
 
     async fn get(&self, location: &Path) -> ObjectStoreResult<GetResult> {
         let time = time::Instant::now();
+        let purpose = operation_purpose(location.as_ref());
         let get_result = self.inner.get(location).await;
         let elapsed = time.elapsed().as_secs_f64();
 
@@ -192,13 +232,14 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         };
 
         STORAGE_REQUEST_RESPONSE_TIME
-            .with_label_values(&[&self.provider, "GET", status])
+            .with_label_values(&[&self.provider, "GET", status, purpose])
             .observe(elapsed);
         get_result
     }
 
     async fn get_opts(&self, location: &Path, options: GetOptions) -> ObjectStoreResult<GetResult> {
         let time = time::Instant::now();
+        let purpose = operation_purpose(location.as_ref());
         let result = self.inner.get_opts(location, options).await;
         let elapsed = time.elapsed().as_secs_f64();
 
@@ -208,13 +249,14 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         };
 
         STORAGE_REQUEST_RESPONSE_TIME
-            .with_label_values(&[&self.provider, "GET_OPTS", status])
+            .with_label_values(&[&self.provider, "GET_OPTS", status, purpose])
             .observe(elapsed);
         result
     }
 
     async fn get_range(&self, location: &Path, range: Range<u64>) -> ObjectStoreResult<Bytes> {
         let time = time::Instant::now();
+        let purpose = operation_purpose(location.as_ref());
         let result = self.inner.get_range(location, range).await;
         let elapsed = time.elapsed().as_secs_f64();
 
@@ -224,7 +266,7 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         };
 
         STORAGE_REQUEST_RESPONSE_TIME
-            .with_label_values(&[&self.provider, "GET_RANGE", status])
+            .with_label_values(&[&self.provider, "GET_RANGE", status, purpose])
             .observe(elapsed);
         result
     }
@@ -235,6 +277,7 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         ranges: &[Range<u64>],
     ) -> ObjectStoreResult<Vec<Bytes>> {
         let time = time::Instant::now();
+        let purpose = operation_purpose(location.as_ref());
         let result = self.inner.get_ranges(location, ranges).await;
         let elapsed = time.elapsed().as_secs_f64();
 
@@ -244,13 +287,14 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         };
 
         STORAGE_REQUEST_RESPONSE_TIME
-            .with_label_values(&[&self.provider, "GET_RANGES", status])
+            .with_label_values(&[&self.provider, "GET_RANGES", status, purpose])
             .observe(elapsed);
         result
     }
 
     async fn head(&self, location: &Path) -> ObjectStoreResult<ObjectMeta> {
         let time = time::Instant::now();
+        let purpose = operation_purpose(location.as_ref());
         let result = self.inner.head(location).await;
         let elapsed = time.elapsed().as_secs_f64();
 
@@ -260,13 +304,14 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         };
 
         STORAGE_REQUEST_RESPONSE_TIME
-            .with_label_values(&[&self.provider, "HEAD", status])
+            .with_label_values(&[&self.provider, "HEAD", status, purpose])
             .observe(elapsed);
         result
     }
 
     async fn delete(&self, location: &Path) -> ObjectStoreResult<()> {
         let time = time::Instant::now();
+        let purpose = operation_purpose(location.as_ref());
         let result = self.inner.delete(location).await;
         let elapsed = time.elapsed().as_secs_f64();
 
@@ -276,7 +321,7 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         };
 
         STORAGE_REQUEST_RESPONSE_TIME
-            .with_label_values(&[&self.provider, "DELETE", status])
+            .with_label_values(&[&self.provider, "DELETE", status, purpose])
             .observe(elapsed);
         result
     }
@@ -290,10 +335,16 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
 
     fn list(&self, prefix: Option<&Path>) -> BoxStream<'static, ObjectStoreResult<ObjectMeta>> {
         let time = time::Instant::now();
+        let purpose = prefix.map_or(METADATA_PURPOSE, |p| operation_purpose(p.as_ref()));
         let inner = self.inner.list(prefix);
         let res = StreamMetricWrapper {
             time,
-            labels: ["LIST", "200"],
+            labels: [
+                self.provider.clone(),
+                "LIST".to_string(),
+                "200".to_string(),
+                purpose.to_string(),
+            ],
             inner,
         };
         Box::pin(res)
@@ -305,10 +356,16 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         offset: &Path,
     ) -> BoxStream<'static, ObjectStoreResult<ObjectMeta>> {
         let time = time::Instant::now();
+        let purpose = prefix.map_or(METADATA_PURPOSE, |p| operation_purpose(p.as_ref()));
         let inner = self.inner.list_with_offset(prefix, offset);
         let res = StreamMetricWrapper {
             time,
-            labels: ["LIST_OFFSET", "200"],
+            labels: [
+                self.provider.clone(),
+                "LIST_OFFSET".to_string(),
+                "200".to_string(),
+                purpose.to_string(),
+            ],
             inner,
         };
 
@@ -317,6 +374,7 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
 
     async fn list_with_delimiter(&self, prefix: Option<&Path>) -> ObjectStoreResult<ListResult> {
         let time = time::Instant::now();
+        let purpose = prefix.map_or(METADATA_PURPOSE, |p| operation_purpose(p.as_ref()));
         let result = self.inner.list_with_delimiter(prefix).await;
         let elapsed = time.elapsed().as_secs_f64();
 
@@ -326,13 +384,14 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         };
 
         STORAGE_REQUEST_RESPONSE_TIME
-            .with_label_values(&[&self.provider, "LIST_DELIM", status])
+            .with_label_values(&[&self.provider, "LIST_DELIM", status, purpose])
             .observe(elapsed);
         result
     }
 
     async fn copy(&self, from: &Path, to: &Path) -> ObjectStoreResult<()> {
         let time = time::Instant::now();
+        let purpose = operation_purpose(from.as_ref());
         let result = self.inner.copy(from, to).await;
         let elapsed = time.elapsed().as_secs_f64();
 
@@ -342,13 +401,14 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         };
 
         STORAGE_REQUEST_RESPONSE_TIME
-            .with_label_values(&[&self.provider, "COPY", status])
+            .with_label_values(&[&self.provider, "COPY", status, purpose])
             .observe(elapsed);
         result
     }
 
     async fn rename(&self, from: &Path, to: &Path) -> ObjectStoreResult<()> {
         let time = time::Instant::now();
+        let purpose = operation_purpose(from.as_ref());
         let result = self.inner.rename(from, to).await;
         let elapsed = time.elapsed().as_secs_f64();
 
@@ -358,13 +418,14 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         };
 
         STORAGE_REQUEST_RESPONSE_TIME
-            .with_label_values(&[&self.provider, "RENAME", status])
+            .with_label_values(&[&self.provider, "RENAME", status, purpose])
             .observe(elapsed);
         result
     }
 
     async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> ObjectStoreResult<()> {
         let time = time::Instant::now();
+        let purpose = operation_purpose(from.as_ref());
         let result = self.inner.copy_if_not_exists(from, to).await;
         let elapsed = time.elapsed().as_secs_f64();
 
@@ -374,13 +435,14 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         };
 
         STORAGE_REQUEST_RESPONSE_TIME
-            .with_label_values(&[&self.provider, "COPY_IF", status])
+            .with_label_values(&[&self.provider, "COPY_IF", status, purpose])
             .observe(elapsed);
         result
     }
 
     async fn rename_if_not_exists(&self, from: &Path, to: &Path) -> ObjectStoreResult<()> {
         let time = time::Instant::now();
+        let purpose = operation_purpose(from.as_ref());
         let result = self.inner.rename_if_not_exists(from, to).await;
         let elapsed = time.elapsed().as_secs_f64();
 
@@ -390,7 +452,7 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
         };
 
         STORAGE_REQUEST_RESPONSE_TIME
-            .with_label_values(&[&self.provider, "RENAME_IF", status])
+            .with_label_values(&[&self.provider, "RENAME_IF", status, purpose])
             .observe(elapsed);
         result
     }
@@ -398,7 +460,7 @@ impl<T: ObjectStore> ObjectStore for MetricLayer<T> {
 
 struct StreamMetricWrapper<'a, const N: usize, T> {
     time: time::Instant,
-    labels: [&'static str; N],
+    labels: [String; N],
     inner: BoxStream<'a, T>,
 }
 
@@ -412,7 +474,7 @@ impl<T, const N: usize> Stream for StreamMetricWrapper<'_, N, T> {
         match self.inner.poll_next_unpin(cx) {
             t @ Poll::Ready(None) => {
                 STORAGE_REQUEST_RESPONSE_TIME
-                    .with_label_values(&self.labels)
+                    .with_label_values(&self.labels.each_ref().map(|label| label.as_str()))
                     .observe(self.time.elapsed().as_secs_f64());
                 t
             }
@@ -0,0 +1,61 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::sync::Arc;
+
+use once_cell::sync::Lazy;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{metrics::MULTIPART_UPLOADS_IN_FLIGHT, parseable::PARSEABLE};
+
+/// Bounds the multipart uploads in flight across all the object store syncs of the process,
+/// unbounded when `--max-multipart-uploads` is not set
+static MULTIPART_UPLOAD_SLOTS: Lazy<Option<Arc<Semaphore>>> = Lazy::new(|| {
+    PARSEABLE
+        .options
+        .max_multipart_uploads
+        .map(|limit| Arc::new(Semaphore::new(limit as usize)))
+});
+
+/// A multipart upload in flight, released when dropped
+pub struct MultipartUploadSlot {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for MultipartUploadSlot {
+    fn drop(&mut self) {
+        MULTIPART_UPLOADS_IN_FLIGHT.dec();
+    }
+}
+
+/// Waits for a multipart upload to complete when `--max-multipart-uploads` are already in flight
+pub async fn acquire_multipart_upload_slot() -> MultipartUploadSlot {
+    let permit = match MULTIPART_UPLOAD_SLOTS.as_ref() {
+        Some(slots) => Some(
+            slots
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("multipart upload semaphore is never closed"),
+        ),
+        None => None,
+    };
+    MULTIPART_UPLOADS_IN_FLIGHT.inc();
+
+    MultipartUploadSlot { _permit: permit }
+}
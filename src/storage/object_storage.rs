@@ -51,7 +51,9 @@ use crate::handlers::http::modal::ingest_server::INGESTOR_META;
 use crate::handlers::http::users::{FILTER_DIR, USERS_ROOT_DIR};
 use crate::metrics::increment_parquets_stored_by_date;
 use crate::metrics::increment_parquets_stored_size_by_date;
-use crate::metrics::{EVENTS_STORAGE_SIZE_DATE, LIFETIME_EVENTS_STORAGE_SIZE, STORAGE_SIZE};
+use crate::metrics::{
+    EVENTS_STORAGE_SIZE_DATE, LIFETIME_EVENTS_STORAGE_SIZE, STAGING_FILES_QUARANTINED, STORAGE_SIZE,
+};
 use crate::option::Mode;
 use crate::parseable::{LogStream, PARSEABLE, Stream};
 use crate::stats::FullStats;
@@ -59,11 +61,13 @@ use crate::storage::SETTINGS_ROOT_DIRECTORY;
 use crate::storage::TARGETS_ROOT_DIRECTORY;
 use crate::storage::field_stats::DATASET_STATS_STREAM_NAME;
 use crate::storage::field_stats::calculate_field_stats;
+use crate::storage::upload_retry::{QUARANTINE_DIR, UPLOAD_FAILURES};
 
 use super::{
     ALERTS_ROOT_DIRECTORY, MANIFEST_FILE, ObjectStorageError, ObjectStoreFormat,
     PARSEABLE_METADATA_FILE_NAME, PARSEABLE_ROOT_DIRECTORY, SCHEMA_FILE_NAME,
     STREAM_METADATA_FILE_NAME, STREAM_ROOT_DIRECTORY, retention::Retention,
+    type_widening::merge_schemas,
 };
 
 /// Context for upload operations containing stream information
@@ -150,7 +154,8 @@ async fn upload_single_parquet_file(
         .absolute_url(RelativePath::from_path(&stream_relative_path).expect("valid relative path"))
         .to_string();
 
-    let manifest = catalog::create_from_parquet_file(absolute_path, &path)?;
+    let mut manifest = catalog::create_from_parquet_file(absolute_path, &path)?;
+    manifest.uploaded_at = Some(Utc::now());
 
     // Calculate field stats if enabled
     calculate_stats_if_enabled(&stream_name, &path, &schema).await;
@@ -258,6 +263,8 @@ pub trait ObjectStorageProvider: std::fmt::Debug + Send + Sync {
     }
     fn get_endpoint(&self) -> String;
     fn name(&self) -> &'static str;
+    /// The configuration of the provider, with the credentials redacted
+    fn redacted_config(&self) -> serde_json::Value;
 }
 
 #[async_trait]
@@ -286,6 +293,10 @@ pub trait ObjectStorage: Debug + Send + Sync + 'static {
     ) -> Result<(), ObjectStorageError>;
     async fn delete_prefix(&self, path: &RelativePath) -> Result<(), ObjectStorageError>;
     async fn check(&self) -> Result<(), ObjectStorageError>;
+    /// Verifies that the buckets holding the data of streams outside of the main bucket are reachable
+    async fn check_stream_buckets(&self) -> Result<(), ObjectStorageError> {
+        Ok(())
+    }
     async fn delete_stream(&self, stream_name: &str) -> Result<(), ObjectStorageError>;
     async fn list_streams(&self) -> Result<HashSet<LogStream>, ObjectStorageError>;
     async fn list_old_streams(&self) -> Result<HashSet<LogStream>, ObjectStorageError>;
@@ -338,6 +349,10 @@ pub trait ObjectStorage: Debug + Send + Sync + 'static {
     fn query_prefixes(&self, prefixes: Vec<String>) -> Vec<ListingTableUrl>;
     fn absolute_url(&self, prefix: &RelativePath) -> object_store::path::Path;
     fn store_url(&self) -> url::Url;
+    /// Url of the store holding the data files of `stream_name`
+    fn stream_store_url(&self, _stream_name: &str) -> url::Url {
+        self.store_url()
+    }
 
     async fn list_with_delimiter(
         &self,
@@ -388,6 +403,28 @@ pub trait ObjectStorage: Debug + Send + Sync + 'static {
         Ok(())
     }
 
+    async fn update_time_partition_format_in_stream(
+        &self,
+        stream_name: &str,
+        time_partition_format: &str,
+    ) -> Result<(), ObjectStorageError> {
+        let mut format: ObjectStoreFormat = serde_json::from_slice(
+            &PARSEABLE
+                .metastore
+                .get_stream_json(stream_name, false)
+                .await
+                .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?,
+        )?;
+        format.time_partition_format = Some(time_partition_format.to_owned());
+        PARSEABLE
+            .metastore
+            .put_stream_json(&format, stream_name)
+            .await
+            .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?;
+
+        Ok(())
+    }
+
     async fn update_custom_partition_in_stream(
         &self,
         stream_name: &str,
@@ -519,6 +556,29 @@ pub trait ObjectStorage: Debug + Send + Sync + 'static {
             .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?)
     }
 
+    /// Reads the stream json, applies `update` to it and writes it back, for the settings
+    /// of a stream updated on their own
+    async fn update_stream_json(
+        &self,
+        stream_name: &str,
+        update: Box<dyn FnOnce(&mut ObjectStoreFormat) + Send + '_>,
+    ) -> Result<(), ObjectStorageError> {
+        let mut stream_metadata: ObjectStoreFormat = serde_json::from_slice(
+            &PARSEABLE
+                .metastore
+                .get_stream_json(stream_name, false)
+                .await
+                .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?,
+        )?;
+        update(&mut stream_metadata);
+
+        Ok(PARSEABLE
+            .metastore
+            .put_stream_json(&stream_metadata, stream_name)
+            .await
+            .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?)
+    }
+
     async fn upsert_stream_metadata(
         &self,
         stream_name: &str,
@@ -902,8 +962,12 @@ async fn process_parquet_files(
     let mut join_set = JoinSet::new();
     let object_store = PARSEABLE.storage().get_object_store();
 
-    // Spawn upload tasks for each parquet file
+    // Spawn upload tasks for each parquet file, skipping the ones backing off after a failure
+    let now = Instant::now();
     for path in upload_context.stream.parquet_files() {
+        if !UPLOAD_FAILURES.is_due(&path, now) {
+            continue;
+        }
         spawn_parquet_upload_task(
             &mut join_set,
             semaphore.clone(),
@@ -916,7 +980,12 @@ async fn process_parquet_files(
     }
 
     // Collect results from all upload tasks
-    collect_upload_results(join_set).await
+    let manifest_files = collect_upload_results(join_set).await;
+    STAGING_FILES_QUARANTINED
+        .with_label_values(&[stream_name])
+        .set(upload_context.stream.quarantined_files().len() as i64);
+
+    manifest_files
 }
 
 /// Spawns an individual parquet file upload task
@@ -943,10 +1012,51 @@ async fn spawn_parquet_upload_task(
     join_set.spawn(async move {
         let _permit = semaphore.acquire().await.expect("semaphore is not closed");
 
-        upload_single_parquet_file(store, path, stream_relative_path, stream_name, schema).await
+        let result = upload_single_parquet_file(
+            store,
+            path.clone(),
+            stream_relative_path,
+            stream_name.clone(),
+            schema,
+        )
+        .await;
+        match &result {
+            Ok(UploadResult {
+                manifest_file: Some(_),
+                ..
+            }) => UPLOAD_FAILURES.clear(&path),
+            _ => handle_upload_failure(&path, &stream_name),
+        }
+
+        result
     });
 }
 
+/// Records a failed upload, the file is quarantined once it ran out of retries
+fn handle_upload_failure(path: &Path, stream_name: &str) {
+    let attempts = UPLOAD_FAILURES.record(path, Instant::now());
+    if attempts < PARSEABLE.options.upload_max_retries {
+        warn!("Upload of {path:?} failed {attempts} time(s), it will be retried with a backoff");
+        return;
+    }
+
+    UPLOAD_FAILURES.clear(path);
+    let (Some(dir), Some(filename)) = (path.parent(), path.file_name()) else {
+        return;
+    };
+    let quarantine_dir = dir.join(QUARANTINE_DIR);
+    let quarantined = std::fs::create_dir_all(&quarantine_dir)
+        .and_then(|_| std::fs::rename(path, quarantine_dir.join(filename)));
+    match quarantined {
+        Ok(()) => error!(
+            "Upload of {path:?} for stream {stream_name} failed {attempts} times, moved it to {quarantine_dir:?}. Its data is not queryable until it is moved back to staging"
+        ),
+        Err(e) => error!(
+            "Upload of {path:?} for stream {stream_name} failed {attempts} times and it couldn't be quarantined: {e}"
+        ),
+    }
+}
+
 /// Collects results from all upload tasks
 async fn collect_upload_results(
     mut join_set: JoinSet<Result<UploadResult, ObjectStorageError>>,
@@ -967,8 +1077,8 @@ async fn collect_upload_results(
                 }
             }
             Ok(Err(e)) => {
+                // the failure is recorded by the task, other files of the batch are still committed
                 error!("Error uploading parquet file: {e}");
-                return Err(e);
             }
             Err(e) => {
                 error!("Task panicked: {e}");
@@ -1055,6 +1165,19 @@ pub fn sync_all_streams(joinset: &mut JoinSet<Result<(), ObjectStorageError>>) {
     }
 }
 
+/// Size in bytes of the parquet files staged for upload across all the streams
+pub fn staging_backlog() -> u64 {
+    PARSEABLE
+        .streams
+        .list()
+        .iter()
+        .filter_map(|stream_name| PARSEABLE.get_stream(stream_name).ok())
+        .flat_map(|stream| stream.parquet_files())
+        .filter_map(|file| file.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
 pub async fn commit_schema_to_storage(
     stream_name: &str,
     schema: Schema,
@@ -1065,7 +1188,7 @@ pub async fn commit_schema_to_storage(
         .await
         .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?;
 
-    let new_schema = Schema::try_merge(vec![
+    let new_schema = merge_schemas(vec![
         schema,
         serde_json::from_slice::<Schema>(&stream_schema)?,
     ])
@@ -0,0 +1,408 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Partition keys derived from the fields of an event, e.g. `split_part(path, '/', 2)` or
+//! `hash(tenant_id, 16)`. The derived value is added to the event on ingestion, next to the
+//! fields it's computed from, so that the key can be used as a custom partition.
+//!
+//! Supported functions, all of them returning strings:
+//! - `lower(expr)`, `upper(expr)`
+//! - `split_part(expr, 'delimiter', n)`, the n-th part, starting at 1
+//! - `substr(expr, start, length)`, with `start` starting at 1
+//! - `hash(expr, buckets)`, a stable hash of the value modulo `buckets`
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum PartitionExpressionError {
+    #[error("Invalid partition expression `{0}`: {1}")]
+    Parse(String, String),
+    #[error("Field {0} referenced by partition key {1} is missing or null")]
+    MissingField(String, String),
+    #[error("Partition key {0} evaluated to an empty value")]
+    Empty(String),
+    #[error("Value of partition key {0} contains a period")]
+    ContainsPeriod(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Field(String),
+    Lower(Box<Expr>),
+    Upper(Box<Expr>),
+    SplitPart(Box<Expr>, String, usize),
+    Substr(Box<Expr>, usize, usize),
+    Hash(Box<Expr>, u64),
+}
+
+/// A parsed partition expression, stored as written
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PartitionExpression {
+    source: String,
+    expr: Expr,
+}
+
+impl TryFrom<String> for PartitionExpression {
+    type Error = PartitionExpressionError;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        let error = |msg: String| PartitionExpressionError::Parse(source.clone(), msg);
+        let mut tokens = tokenize(&source).map_err(error)?.into_iter().peekable();
+        let node = parse_node(&mut tokens).map_err(error)?;
+        if let Some(token) = tokens.next() {
+            return Err(error(format!("unexpected {token}")));
+        }
+        let expr = Expr::try_from(node).map_err(error)?;
+
+        Ok(Self { source, expr })
+    }
+}
+
+impl From<PartitionExpression> for String {
+    fn from(expression: PartitionExpression) -> Self {
+        expression.source
+    }
+}
+
+impl fmt::Display for PartitionExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl PartitionExpression {
+    /// Fields of the event the expression is computed from
+    pub fn fields(&self) -> HashSet<&str> {
+        let mut fields = HashSet::new();
+        let mut expr = &self.expr;
+        loop {
+            match expr {
+                Expr::Field(field) => {
+                    fields.insert(field.as_str());
+                    return fields;
+                }
+                Expr::Lower(inner)
+                | Expr::Upper(inner)
+                | Expr::SplitPart(inner, ..)
+                | Expr::Substr(inner, ..)
+                | Expr::Hash(inner, _) => expr = inner,
+            }
+        }
+    }
+
+    /// Computes the value of the expression for a flattened event, fails with the name of a missing field
+    fn evaluate(&self, event: &serde_json::Map<String, Value>) -> Result<String, String> {
+        evaluate(&self.expr, event)
+    }
+}
+
+/// Adds the value of every derived partition key to the flattened event(s), the values are
+/// validated like the ones of custom partitions as they end up in file names
+pub fn derive_partition_values(
+    json: &mut Value,
+    expressions: &HashMap<String, PartitionExpression>,
+) -> Result<(), PartitionExpressionError> {
+    match json {
+        Value::Array(events) => events
+            .iter_mut()
+            .try_for_each(|event| derive_partition_values(event, expressions)),
+        Value::Object(event) => {
+            for (key, expression) in expressions {
+                let value = expression
+                    .evaluate(event)
+                    .map_err(|field| PartitionExpressionError::MissingField(field, key.clone()))?;
+                if value.is_empty() {
+                    return Err(PartitionExpressionError::Empty(key.clone()));
+                }
+                if value.contains('.') {
+                    return Err(PartitionExpressionError::ContainsPeriod(key.clone()));
+                }
+                event.insert(key.clone(), Value::String(value));
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn evaluate(expr: &Expr, event: &serde_json::Map<String, Value>) -> Result<String, String> {
+    Ok(match expr {
+        Expr::Field(field) => match event.get(field) {
+            Some(Value::String(s)) => s.clone(),
+            Some(value @ (Value::Number(_) | Value::Bool(_))) => value.to_string(),
+            _ => return Err(field.clone()),
+        },
+        Expr::Lower(inner) => evaluate(inner, event)?.to_lowercase(),
+        Expr::Upper(inner) => evaluate(inner, event)?.to_uppercase(),
+        Expr::SplitPart(inner, delimiter, n) => evaluate(inner, event)?
+            .split(delimiter.as_str())
+            .nth(n - 1)
+            .unwrap_or_default()
+            .to_owned(),
+        Expr::Substr(inner, start, length) => evaluate(inner, event)?
+            .chars()
+            .skip(start - 1)
+            .take(*length)
+            .collect(),
+        Expr::Hash(inner, buckets) => {
+            (fnv1a(evaluate(inner, event)?.as_bytes()) % buckets).to_string()
+        }
+    })
+}
+
+/// FNV-1a, stable across releases unlike the std hasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Int(u64),
+    Open,
+    Close,
+    Comma,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "`{ident}`"),
+            Token::Str(s) => write!(f, "'{s}'"),
+            Token::Int(i) => write!(f, "{i}"),
+            Token::Open => f.write_str("`(`"),
+            Token::Close => f.write_str("`)`"),
+            Token::Comma => f.write_str("`,`"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            ',' => tokens.push(Token::Comma),
+            '\'' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        // a quote is escaped by doubling it
+                        Some('\'') if chars.peek() == Some(&'\'') => {
+                            chars.next();
+                            s.push('\'');
+                        }
+                        Some('\'') => break,
+                        Some(c) => s.push(c),
+                        None => return Err("unterminated string".to_owned()),
+                    }
+                }
+                tokens.push(Token::Str(s));
+            }
+            c if c.is_ascii_digit() => {
+                let digits = take_while(c, &mut chars, |c| c.is_ascii_digit());
+                let int = digits
+                    .parse()
+                    .map_err(|_| format!("{digits} is not a valid number"))?;
+                tokens.push(Token::Int(int));
+            }
+            c if c.is_alphabetic() || c == '_' || c == '@' => {
+                tokens.push(Token::Ident(take_while(c, &mut chars, |c| {
+                    c.is_alphanumeric() || c == '_' || c == '@'
+                })));
+            }
+            c => return Err(format!("unexpected character {c}")),
+        }
+    }
+    Ok(tokens)
+}
+
+fn take_while(first: char, chars: &mut Peekable<Chars>, f: impl Fn(char) -> bool) -> String {
+    let mut s = first.to_string();
+    while let Some(c) = chars.next_if(|c| f(*c)) {
+        s.push(c);
+    }
+    s
+}
+
+enum Node {
+    Ident(String),
+    Str(String),
+    Int(u64),
+    Call(String, Vec<Node>),
+}
+
+fn parse_node(tokens: &mut Peekable<impl Iterator<Item = Token>>) -> Result<Node, String> {
+    match tokens.next() {
+        Some(Token::Ident(ident)) if tokens.peek() == Some(&Token::Open) => {
+            tokens.next();
+            let mut args = vec![];
+            if tokens.next_if_eq(&Token::Close).is_some() {
+                return Ok(Node::Call(ident, args));
+            }
+            loop {
+                args.push(parse_node(tokens)?);
+                match tokens.next() {
+                    Some(Token::Comma) => {}
+                    Some(Token::Close) => return Ok(Node::Call(ident, args)),
+                    Some(token) => return Err(format!("unexpected {token}")),
+                    None => return Err("missing `)`".to_owned()),
+                }
+            }
+        }
+        Some(Token::Ident(ident)) => Ok(Node::Ident(ident)),
+        Some(Token::Str(s)) => Ok(Node::Str(s)),
+        Some(Token::Int(i)) => Ok(Node::Int(i)),
+        Some(token) => Err(format!("unexpected {token}")),
+        None => Err("unexpected end of expression".to_owned()),
+    }
+}
+
+impl TryFrom<Node> for Expr {
+    type Error = String;
+
+    fn try_from(node: Node) -> Result<Self, Self::Error> {
+        let (function, args) = match node {
+            Node::Ident(field) => return Ok(Expr::Field(field)),
+            Node::Call(function, args) => (function, args),
+            Node::Str(_) | Node::Int(_) => {
+                return Err("the expression should be a field or a function".to_owned());
+            }
+        };
+        let arity = match function.as_str() {
+            "lower" | "upper" => 1,
+            "hash" => 2,
+            "split_part" | "substr" => 3,
+            _ => return Err(format!("unknown function {function}")),
+        };
+        if args.len() != arity {
+            return Err(format!("{function} takes {arity} arguments"));
+        }
+
+        let mut args = args.into_iter();
+        let inner = Box::new(Expr::try_from(args.next().expect("arity is checked"))?);
+        let positive_int = |arg: Option<Node>, name: &str| match arg {
+            Some(Node::Int(i)) if i > 0 => Ok(i),
+            _ => Err(format!("{name} of {function} should be a positive integer")),
+        };
+        Ok(match function.as_str() {
+            "lower" => Expr::Lower(inner),
+            "upper" => Expr::Upper(inner),
+            "hash" => Expr::Hash(inner, positive_int(args.next(), "the number of buckets")?),
+            "substr" => Expr::Substr(
+                inner,
+                positive_int(args.next(), "the start")? as usize,
+                positive_int(args.next(), "the length")? as usize,
+            ),
+            _ => {
+                let Some(Node::Str(delimiter)) = args.next() else {
+                    return Err("the delimiter of split_part should be a string".to_owned());
+                };
+                if delimiter.is_empty() {
+                    return Err("the delimiter of split_part can't be empty".to_owned());
+                }
+                Expr::SplitPart(
+                    inner,
+                    delimiter,
+                    positive_int(args.next(), "the index")? as usize,
+                )
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn expression(source: &str) -> PartitionExpression {
+        PartitionExpression::try_from(source.to_owned()).unwrap()
+    }
+
+    #[test]
+    fn derives_partition_values() {
+        let expressions = HashMap::from([
+            (
+                "service".to_owned(),
+                expression("lower(split_part(path, '/', 2))"),
+            ),
+            (
+                "tenant_bucket".to_owned(),
+                expression("hash(tenant_id, 16)"),
+            ),
+        ]);
+        let mut events = json!([
+            {"path": "/Orders/42", "tenant_id": 7},
+            {"path": "/orders", "tenant_id": 7},
+        ]);
+
+        derive_partition_values(&mut events, &expressions).unwrap();
+        assert_eq!(events[0]["service"], "orders");
+        assert_eq!(events[1]["service"], "orders");
+        assert_eq!(events[0]["tenant_bucket"], events[1]["tenant_bucket"]);
+        assert_eq!(events[0]["path"], "/Orders/42");
+
+        let expressions = HashMap::from([("host".to_owned(), expression("lower(hostname)"))]);
+        assert_eq!(
+            derive_partition_values(&mut json!({"path": "/"}), &expressions),
+            Err(PartitionExpressionError::MissingField(
+                "hostname".to_owned(),
+                "host".to_owned()
+            ))
+        );
+        assert_eq!(
+            derive_partition_values(&mut json!({"hostname": "a.example"}), &expressions),
+            Err(PartitionExpressionError::ContainsPeriod("host".to_owned()))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert_eq!(
+            expression("substr(upper(host), 1, 3)").fields(),
+            HashSet::from(["host"])
+        );
+        for source in [
+            "",
+            "'literal'",
+            "sha256(host)",
+            "hash(host)",
+            "hash(host, 0)",
+            "split_part(path, '', 1)",
+            "lower(host",
+            "lower(host) host",
+        ] {
+            assert!(PartitionExpression::try_from(source.to_owned()).is_err());
+        }
+    }
+}
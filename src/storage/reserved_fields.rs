@@ -0,0 +1,119 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Suffix appended to the event fields renamed for colliding with a reserved field
+pub const RENAMED_FIELD_SUFFIX: &str = "_user";
+
+/// How a stream handles events with fields named like the columns added on ingestion,
+/// e.g. `p_timestamp`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ReservedFieldPolicy {
+    /// the event keeps its values for the custom fields added on ingestion, events carrying
+    /// a `p_timestamp` are still rejected when their record is built
+    #[default]
+    Allow,
+    /// the event is rejected
+    Reject,
+    /// the field is kept under its name suffixed with `_user`
+    Rename,
+}
+
+impl ReservedFieldPolicy {
+    /// Applies the policy to the fields of the flattened event(s) colliding with a `reserved` name
+    pub fn apply(&self, json: &mut Value, reserved: &[&str]) -> Result<(), String> {
+        match json {
+            _ if *self == Self::Allow => Ok(()),
+            Value::Array(events) => events
+                .iter_mut()
+                .try_for_each(|event| self.apply(event, reserved)),
+            Value::Object(event) => {
+                for &name in reserved {
+                    if !event.contains_key(name) {
+                        continue;
+                    }
+                    if *self == Self::Reject {
+                        return Err(format!(
+                            "field {name} collides with a reserved field, rename it or set the reserved field policy of the stream to rename"
+                        ));
+                    }
+
+                    let mut renamed = format!("{name}{RENAMED_FIELD_SUFFIX}");
+                    while event.contains_key(&renamed) {
+                        renamed.push_str(RENAMED_FIELD_SUFFIX);
+                    }
+                    let value = event.remove(name).expect("field is present");
+                    event.insert(renamed, value);
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const RESERVED: &[&str] = &["p_timestamp", "p_src_ip"];
+
+    #[test]
+    fn rejects_reserved_fields() {
+        let mut events = json!([{"a": 1}, {"a": 2, "p_timestamp": "2024-01-01"}]);
+
+        assert!(
+            ReservedFieldPolicy::Reject
+                .apply(&mut events, RESERVED)
+                .is_err()
+        );
+        assert!(
+            ReservedFieldPolicy::Reject
+                .apply(&mut json!({"a": 1}), RESERVED)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn allows_reserved_fields_by_default() {
+        let mut event = json!({"a": 1, "p_src_ip": "10.0.0.1"});
+
+        ReservedFieldPolicy::default()
+            .apply(&mut event, RESERVED)
+            .unwrap();
+        assert_eq!(event, json!({"a": 1, "p_src_ip": "10.0.0.1"}));
+    }
+
+    #[test]
+    fn renames_reserved_fields() {
+        let mut event = json!({"p_timestamp": 1, "p_timestamp_user": 2, "p_src_ip": "10.0.0.1"});
+
+        ReservedFieldPolicy::Rename
+            .apply(&mut event, RESERVED)
+            .unwrap();
+        assert_eq!(
+            event,
+            json!({"p_timestamp_user_user": 1, "p_timestamp_user": 2, "p_src_ip_user": "10.0.0.1"})
+        );
+    }
+}
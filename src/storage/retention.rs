@@ -21,6 +21,7 @@ use std::num::NonZeroU32;
 use std::sync::Mutex;
 use std::time::Duration;
 
+use chrono_tz::Tz;
 use clokwerk::AsyncScheduler;
 use clokwerk::Job;
 use clokwerk::TimeUnits;
@@ -30,6 +31,7 @@ use tokio::task::JoinHandle;
 use tracing::{info, warn};
 
 use crate::parseable::PARSEABLE;
+use crate::utils::time::parse_timezone;
 
 type SchedulerHandle = JoinHandle<()>;
 
@@ -49,12 +51,22 @@ pub fn init_scheduler() {
             match PARSEABLE.get_stream(&stream_name) {
                 Ok(stream) => {
                     if let Some(config) = stream.get_retention() {
-                        for Task { action, days, .. } in config.tasks.into_iter() {
+                        for Task {
+                            action,
+                            days,
+                            timezone,
+                            ..
+                        } in config.tasks.into_iter()
+                        {
+                            // validated when the retention was set
+                            let tz = timezone
+                                .and_then(|tz| parse_timezone(&tz).ok())
+                                .unwrap_or(Tz::UTC);
                             match action {
                                 Action::Delete => {
                                     let stream_name = stream_name.clone();
                                     tokio::spawn(async move {
-                                        action::delete(stream_name, u32::from(days)).await;
+                                        action::delete(stream_name, u32::from(days), tz).await;
                                     });
                                 }
                             };
@@ -93,11 +105,27 @@ pub struct Retention {
     tasks: Vec<Task>,
 }
 
+impl Retention {
+    /// Retention with a single task deleting data older than `days`
+    pub fn delete_after(days: NonZeroU32) -> Self {
+        Retention {
+            tasks: vec![Task {
+                description: format!("delete data older than {days} days"),
+                action: Action::Delete,
+                days,
+                timezone: None,
+            }],
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Task {
     description: String,
     action: Action,
     days: NonZeroU32,
+    /// IANA time zone whose days the retention is counted in, UTC if unset
+    timezone: Option<String>,
 }
 
 #[derive(
@@ -113,6 +141,8 @@ struct TaskView {
     description: String,
     action: Action,
     duration: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timezone: Option<String>,
 }
 
 impl TryFrom<Vec<TaskView>> for Retention {
@@ -130,6 +160,9 @@ impl TryFrom<Vec<TaskView>> for Retention {
             let Ok(days) = duration[0..duration.len() - 1].parse() else {
                 return Err("could not convert duration to an unsigned number".to_string());
             };
+            if let Some(timezone) = &task.timezone {
+                parse_timezone(timezone)?;
+            }
 
             if set.contains(&task.action) {
                 return Err(format!(
@@ -144,6 +177,7 @@ impl TryFrom<Vec<TaskView>> for Retention {
                 description: task.description,
                 action: task.action,
                 days,
+                timezone: task.timezone,
             })
         }
 
@@ -162,6 +196,7 @@ impl From<Retention> for Vec<TaskView> {
                     description: task.description,
                     action: task.action,
                     duration,
+                    timezone: task.timezone,
                 }
             })
             .collect()
@@ -171,17 +206,20 @@ impl From<Retention> for Vec<TaskView> {
 mod action {
     use crate::catalog::remove_manifest_from_snapshot;
     use crate::parseable::PARSEABLE;
-    use chrono::{Days, NaiveDate, Utc};
+    use crate::utils::time::start_of_day;
+    use chrono::{Days, NaiveDate, NaiveTime, Utc};
+    use chrono_tz::Tz;
     use futures::{StreamExt, stream::FuturesUnordered};
     use itertools::Itertools;
     use relative_path::RelativePathBuf;
     use tracing::{error, info};
 
-    pub(super) async fn delete(stream_name: String, days: u32) {
+    pub(super) async fn delete(stream_name: String, days: u32, tz: Tz) {
         info!("running retention task - delete for stream={stream_name}");
         let store = PARSEABLE.storage.get_object_store();
 
-        let retain_until = get_retain_until(Utc::now().date_naive(), days as u64);
+        let current_date = Utc::now().with_timezone(&tz).date_naive();
+        let retain_until = get_retain_until(current_date, days as u64);
 
         let Ok(mut dates) = store.list_dates(&stream_name).await else {
             return;
@@ -189,7 +227,7 @@ mod action {
         dates.retain(|date| date.starts_with("date"));
         let dates_to_delete = dates
             .into_iter()
-            .filter(|date| string_to_date(date) < retain_until)
+            .filter(|date| is_expired(string_to_date(date), retain_until, tz))
             .collect_vec();
         let dates = dates_to_delete.clone();
         if !dates.is_empty() {
@@ -230,6 +268,16 @@ mod action {
         current_date - Days::new(days)
     }
 
+    /// Data is kept in UTC dates, which expire once they end before `retain_until` starts in `tz`
+    fn is_expired(date: NaiveDate, retain_until: NaiveDate, tz: Tz) -> bool {
+        let date_end = date
+            .succ_opt()
+            .unwrap_or(date)
+            .and_time(NaiveTime::MIN)
+            .and_utc();
+        date_end <= start_of_day(retain_until, tz)
+    }
+
     fn string_to_date(date: &str) -> NaiveDate {
         let year = date[5..9].parse().unwrap();
         let month = date[10..12].parse().unwrap();
@@ -243,6 +291,7 @@ mod action {
         use chrono::{Datelike, NaiveDate};
 
         use super::get_retain_until;
+        use super::is_expired;
         use super::string_to_date;
 
         #[test]
@@ -257,5 +306,25 @@ mod action {
             let date = get_retain_until(current_date, 1);
             assert_eq!(date.day(), 1)
         }
+        #[test]
+        fn test_expiry_in_timezone() {
+            let retain_until = NaiveDate::from_ymd_opt(2000, 1, 10).unwrap();
+            let day_before = NaiveDate::from_ymd_opt(2000, 1, 9).unwrap();
+
+            assert!(is_expired(day_before, retain_until, chrono_tz::Tz::UTC));
+            assert!(!is_expired(retain_until, retain_until, chrono_tz::Tz::UTC));
+            // 2000-01-10 starts at 2000-01-09T18:30:00Z in Kolkata
+            assert!(!is_expired(
+                day_before,
+                retain_until,
+                chrono_tz::Asia::Kolkata
+            ));
+            // and at 2000-01-10T05:00:00Z in New York
+            assert!(is_expired(
+                day_before,
+                retain_until,
+                chrono_tz::America::New_York
+            ));
+        }
     }
 }
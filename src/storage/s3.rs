@@ -62,7 +62,10 @@ use crate::{
 use super::{
     CONNECT_TIMEOUT_SECS, MIN_MULTIPART_UPLOAD_SIZE, ObjectStorage, ObjectStorageError,
     ObjectStorageProvider, PARSEABLE_ROOT_DIRECTORY, REQUEST_TIMEOUT_SECS,
-    STREAM_METADATA_FILE_NAME, metrics_layer::MetricLayer, object_storage::parseable_json_path,
+    STREAM_METADATA_FILE_NAME,
+    metrics_layer::{MetricLayer, observe_request_time},
+    multipart_slots::acquire_multipart_upload_slot,
+    object_storage::parseable_json_path,
     to_object_store_path,
 };
 
@@ -154,6 +157,44 @@ pub struct S3Config {
         required = false
     )]
     pub metadata_endpoint: Option<String>,
+
+    /// Buckets holding the data of the streams whose name starts with a prefix,
+    /// like `archive-=cold-bucket,audit-=audit-bucket`. The buckets are reached with the
+    /// endpoint and credentials of the main bucket, which keeps the metadata of every stream.
+    /// The server doesn't start while a mapped stream still has data in the main bucket.
+    #[arg(
+        long,
+        env = "P_S3_STREAM_BUCKETS",
+        value_name = "prefix=bucket",
+        value_delimiter = ','
+    )]
+    pub stream_buckets: Vec<StreamBucket>,
+}
+
+/// Maps the streams whose name starts with `prefix` to `bucket`
+#[derive(Debug, Clone)]
+pub struct StreamBucket {
+    pub prefix: String,
+    pub bucket: String,
+}
+
+impl FromStr for StreamBucket {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((prefix, bucket)) = s.split_once('=') else {
+            return Err(format!("Expected <prefix>=<bucket>, got {s}"));
+        };
+        let (prefix, bucket) = (prefix.trim(), bucket.trim());
+        if prefix.is_empty() || bucket.is_empty() {
+            return Err(format!("Expected <prefix>=<bucket>, got {s}"));
+        }
+
+        Ok(Self {
+            prefix: prefix.to_owned(),
+            bucket: bucket.to_owned(),
+        })
+    }
 }
 
 /// This represents the server side encryption to be
@@ -295,6 +336,25 @@ impl S3Config {
 
         builder.with_client_options(client_options)
     }
+
+    /// Clients of the stream buckets, longest prefix first so that the most specific mapping wins
+    fn stream_bucket_clients(&self) -> Vec<StreamBucketClient> {
+        let mut clients = self
+            .stream_buckets
+            .iter()
+            .map(|stream_bucket| StreamBucketClient {
+                prefix: stream_bucket.prefix.clone(),
+                bucket: stream_bucket.bucket.clone(),
+                client: self
+                    .get_default_builder()
+                    .with_bucket_name(&stream_bucket.bucket)
+                    .build()
+                    .unwrap(),
+            })
+            .collect::<Vec<_>>();
+        clients.sort_by(|a, b| b.prefix.len().cmp(&a.prefix.len()));
+        clients
+    }
 }
 
 impl ObjectStorageProvider for S3Config {
@@ -302,6 +362,27 @@ impl ObjectStorageProvider for S3Config {
         "s3"
     }
 
+    fn redacted_config(&self) -> serde_json::Value {
+        serde_json::json!({
+            "endpoint": super::redact_url(&self.endpoint_url),
+            "region": self.region,
+            "bucket": self.bucket_name,
+            "accessKey": super::redact_secret(&self.access_key_id),
+            "secretKey": super::redact_secret(&self.secret_key),
+            "ssecEncryptionKey": super::redact_secret(&self.ssec_encryption_key),
+            "checksum": self.set_checksum,
+            "pathStyle": self.use_path_style,
+            "tlsSkipVerify": self.skip_tls,
+            "imdsv1Fallback": self.imdsv1_fallback,
+            "metadataEndpoint": self.metadata_endpoint.as_deref().map(super::redact_url),
+            "streamBuckets": self
+                .stream_buckets
+                .iter()
+                .map(|stream_bucket| format!("{}={}", stream_bucket.prefix, stream_bucket.bucket))
+                .collect::<Vec<_>>(),
+        })
+    }
+
     fn get_datafusion_runtime(&self) -> RuntimeEnvBuilder {
         let s3 = self.get_default_builder().build().unwrap();
 
@@ -313,6 +394,13 @@ impl ObjectStorageProvider for S3Config {
         let url = ObjectStoreUrl::parse(format!("s3://{}", &self.bucket_name)).unwrap();
         object_store_registry.register_store(url.as_ref(), Arc::new(s3));
 
+        for stream_bucket in self.stream_bucket_clients() {
+            let s3 = LimitStore::new(stream_bucket.client, super::MAX_OBJECT_STORE_REQUESTS);
+            let s3 = MetricLayer::new(s3, "s3");
+            let url = ObjectStoreUrl::parse(format!("s3://{}", &stream_bucket.bucket)).unwrap();
+            object_store_registry.register_store(url.as_ref(), Arc::new(s3));
+        }
+
         RuntimeEnvBuilder::new().with_object_store_registry(Arc::new(object_store_registry))
     }
 
@@ -323,6 +411,7 @@ impl ObjectStorageProvider for S3Config {
             client: s3,
             bucket: self.bucket_name.clone(),
             root: StorePath::from(""),
+            stream_buckets: self.stream_bucket_clients(),
         })
     }
 
@@ -331,16 +420,60 @@ impl ObjectStorageProvider for S3Config {
     }
 }
 
+#[derive(Debug)]
+struct StreamBucketClient {
+    prefix: String,
+    bucket: String,
+    client: AmazonS3,
+}
+
 #[derive(Debug)]
 pub struct S3 {
     client: AmazonS3,
     bucket: String,
     root: StorePath,
+    stream_buckets: Vec<StreamBucketClient>,
 }
 
 impl S3 {
+    fn stream_bucket(&self, stream: &str) -> Option<&StreamBucketClient> {
+        self.stream_buckets
+            .iter()
+            .find(|stream_bucket| stream.starts_with(&stream_bucket.prefix))
+    }
+
+    /// Bucket holding the object at `path`, the data of a stream (everything under its `date=`
+    /// directories) lives in the bucket mapped to the stream, all the rest in the main bucket
+    fn bucket_for(&self, path: &str) -> (&AmazonS3, &str) {
+        let mut parts = path.trim_start_matches('/').splitn(3, '/');
+        if let (Some(stream), Some(dir)) = (parts.next(), parts.next())
+            && dir.starts_with("date=")
+            && let Some(stream_bucket) = self.stream_bucket(stream)
+        {
+            return (&stream_bucket.client, &stream_bucket.bucket);
+        }
+
+        (&self.client, &self.bucket)
+    }
+
+    /// The bucket the stream is mapped to when its directory in the main bucket,
+    /// listed as `dirs`, still holds data
+    fn misplaced_data(&self, stream: &str, dirs: &[String]) -> Option<&StreamBucketClient> {
+        self.stream_bucket(stream)
+            .filter(|_| dirs.iter().any(|dir| dir.starts_with("date=")))
+    }
+
+    fn client_for(&self, path: &str) -> &AmazonS3 {
+        self.bucket_for(path).0
+    }
+
     async fn _get_object(&self, path: &RelativePath) -> Result<Bytes, ObjectStorageError> {
-        let resp = self.client.get(&to_object_store_path(path)).await;
+        let time = std::time::Instant::now();
+        let resp = self
+            .client_for(path.as_str())
+            .get(&to_object_store_path(path))
+            .await;
+        observe_request_time("s3", "GET", &resp, path.as_str(), time);
         increment_object_store_calls_by_date("GET", &Utc::now().date_naive().to_string());
 
         match resp {
@@ -367,7 +500,12 @@ impl S3 {
         path: &RelativePath,
         resource: PutPayload,
     ) -> Result<(), ObjectStorageError> {
-        let resp = self.client.put(&to_object_store_path(path), resource).await;
+        let time = std::time::Instant::now();
+        let resp = self
+            .client_for(path.as_str())
+            .put(&to_object_store_path(path), resource)
+            .await;
+        observe_request_time("s3", "PUT", &resp, path.as_str(), time);
         increment_object_store_calls_by_date("PUT", &Utc::now().date_naive().to_string());
         match resp {
             Ok(_) => {
@@ -383,10 +521,18 @@ impl S3 {
     }
 
     async fn _delete_prefix(&self, key: &str) -> Result<(), ObjectStorageError> {
+        self.delete_prefix_from(self.client_for(key), key).await
+    }
+
+    async fn delete_prefix_from(
+        &self,
+        client: &AmazonS3,
+        key: &str,
+    ) -> Result<(), ObjectStorageError> {
         let files_scanned = Arc::new(AtomicU64::new(0));
         let files_deleted = Arc::new(AtomicU64::new(0));
         // Track LIST operation
-        let object_stream = self.client.list(Some(&(key.into())));
+        let object_stream = client.list(Some(&(key.into())));
         increment_object_store_calls_by_date("LIST", &Utc::now().date_naive().to_string());
 
         object_stream
@@ -396,7 +542,7 @@ impl S3 {
                 match x {
                     Ok(obj) => {
                         files_deleted.fetch_add(1, Ordering::Relaxed);
-                        let delete_resp = self.client.delete(&obj.location).await;
+                        let delete_resp = client.delete(&obj.location).await;
                         increment_object_store_calls_by_date(
                             "DELETE",
                             &Utc::now().date_naive().to_string(),
@@ -429,10 +575,12 @@ impl S3 {
     }
 
     async fn _list_dates(&self, stream: &str) -> Result<Vec<String>, ObjectStorageError> {
-        let resp: Result<object_store::ListResult, object_store::Error> = self
-            .client
-            .list_with_delimiter(Some(&(stream.into())))
-            .await;
+        // the dates of a stream are only listed from the bucket holding its data
+        let client = self
+            .stream_bucket(stream)
+            .map_or(&self.client, |stream_bucket| &stream_bucket.client);
+        let resp: Result<object_store::ListResult, object_store::Error> =
+            client.list_with_delimiter(Some(&(stream.into()))).await;
         increment_object_store_calls_by_date("LIST", &Utc::now().date_naive().to_string());
 
         let resp = match resp {
@@ -463,7 +611,7 @@ impl S3 {
     async fn _upload_file(&self, key: &str, path: &Path) -> Result<(), ObjectStorageError> {
         let bytes = tokio::fs::read(path).await?;
 
-        let result = self.client.put(&key.into(), bytes.into()).await;
+        let result = self.client_for(key).put(&key.into(), bytes.into()).await;
         increment_object_store_calls_by_date("PUT", &Utc::now().date_naive().to_string());
         match result {
             Ok(_) => {
@@ -483,10 +631,13 @@ impl S3 {
         key: &RelativePath,
         path: &Path,
     ) -> Result<(), ObjectStorageError> {
+        // held until the upload completes or fails, bounding the uploads in flight across streams
+        let _slot = acquire_multipart_upload_slot().await;
         let mut file = OpenOptions::new().read(true).open(path).await?;
         let location = &to_object_store_path(key);
+        let client = self.client_for(key.as_str());
 
-        let async_writer = self.client.put_multipart(location).await;
+        let async_writer = client.put_multipart(location).await;
         let mut async_writer = match async_writer {
             Ok(writer) => writer,
             Err(err) => {
@@ -501,7 +652,7 @@ impl S3 {
             file.read_to_end(&mut data).await?;
 
             // Track single PUT operation for small files
-            let result = self.client.put(location, data.into()).await;
+            let result = client.put(location, data.into()).await;
             increment_object_store_calls_by_date("PUT", &Utc::now().date_naive().to_string());
             match result {
                 Ok(_) => {
@@ -572,8 +723,9 @@ impl ObjectStorage for S3 {
         &self,
         path: &RelativePath,
     ) -> Result<BufReader, ObjectStorageError> {
+        let client = self.client_for(path.as_str());
         let path = &to_object_store_path(path);
-        let meta = self.client.head(path).await;
+        let meta = client.head(path).await;
         increment_object_store_calls_by_date("HEAD", &Utc::now().date_naive().to_string());
         let meta = match meta {
             Ok(meta) => {
@@ -589,7 +741,7 @@ impl ObjectStorage for S3 {
             }
         };
 
-        let store: Arc<dyn ObjectStore> = Arc::new(self.client.clone());
+        let store: Arc<dyn ObjectStore> = Arc::new(client.clone());
         let buf = object_store::buffered::BufReader::new(store, &meta);
         Ok(buf)
     }
@@ -603,7 +755,10 @@ impl ObjectStorage for S3 {
     }
 
     async fn head(&self, path: &RelativePath) -> Result<ObjectMeta, ObjectStorageError> {
-        let result = self.client.head(&to_object_store_path(path)).await;
+        let result = self
+            .client_for(path.as_str())
+            .head(&to_object_store_path(path))
+            .await;
         increment_object_store_calls_by_date("HEAD", &Utc::now().date_naive().to_string());
         if result.is_ok() {
             increment_files_scanned_in_object_store_calls_by_date(
@@ -631,7 +786,7 @@ impl ObjectStorage for S3 {
             self.root.clone()
         };
 
-        let mut list_stream = self.client.list(Some(&prefix));
+        let mut list_stream = self.client_for(prefix.as_ref()).list(Some(&prefix));
 
         let mut res = vec![];
         let mut files_scanned = 0;
@@ -722,7 +877,10 @@ impl ObjectStorage for S3 {
     }
 
     async fn delete_object(&self, path: &RelativePath) -> Result<(), ObjectStorageError> {
-        let result = self.client.delete(&to_object_store_path(path)).await;
+        let result = self
+            .client_for(path.as_str())
+            .delete(&to_object_store_path(path))
+            .await;
         increment_object_store_calls_by_date("DELETE", &Utc::now().date_naive().to_string());
         if result.is_ok() {
             increment_files_scanned_in_object_store_calls_by_date(
@@ -753,8 +911,57 @@ impl ObjectStorage for S3 {
         Ok(result.map(|_| ())?)
     }
 
+    async fn check_stream_buckets(&self) -> Result<(), ObjectStorageError> {
+        for stream_bucket in &self.stream_buckets {
+            stream_bucket
+                .client
+                .list_with_delimiter(None)
+                .await
+                .map_err(|err| {
+                    ObjectStorageError::Custom(format!(
+                        "Bucket {} of the streams prefixed with {} is not reachable: {err}",
+                        stream_bucket.bucket, stream_bucket.prefix
+                    ))
+                })?;
+            increment_object_store_calls_by_date("LIST", &Utc::now().date_naive().to_string());
+        }
+        if self.stream_buckets.is_empty() {
+            return Ok(());
+        }
+
+        // data already in the main bucket would no longer be read once the stream is mapped,
+        // it has to be moved to the mapped bucket first
+        for stream in self.list_dirs().await? {
+            if self.stream_bucket(&stream).is_none() {
+                continue;
+            }
+            let prefix = object_store::path::Path::from(stream.as_str());
+            let dirs = self
+                .client
+                .list_with_delimiter(Some(&prefix))
+                .await?
+                .common_prefixes
+                .iter()
+                .filter_map(|path| path.filename().map(str::to_owned))
+                .collect::<Vec<_>>();
+            increment_object_store_calls_by_date("LIST", &Utc::now().date_naive().to_string());
+            if let Some(stream_bucket) = self.misplaced_data(&stream, &dirs) {
+                return Err(ObjectStorageError::Custom(format!(
+                    "Stream {stream} has data in bucket {}, move its date= directories to bucket {} before mapping it with P_S3_STREAM_BUCKETS",
+                    self.bucket, stream_bucket.bucket
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
     async fn delete_stream(&self, stream_name: &str) -> Result<(), ObjectStorageError> {
-        self._delete_prefix(stream_name).await?;
+        self.delete_prefix_from(&self.client, stream_name).await?;
+        if let Some(stream_bucket) = self.stream_bucket(stream_name) {
+            self.delete_prefix_from(&stream_bucket.client, stream_name)
+                .await?;
+        }
 
         Ok(())
     }
@@ -834,7 +1041,10 @@ impl ObjectStorage for S3 {
         date: &str,
     ) -> Result<Vec<String>, ObjectStorageError> {
         let pre = object_store::path::Path::from(format!("{}/{}/", stream_name, date));
-        let resp = self.client.list_with_delimiter(Some(&pre)).await?;
+        let resp = self
+            .client_for(pre.as_ref())
+            .list_with_delimiter(Some(&pre))
+            .await?;
         increment_files_scanned_in_object_store_calls_by_date(
             "LIST",
             resp.common_prefixes.len() as u64,
@@ -869,7 +1079,10 @@ impl ObjectStorage for S3 {
         hour: &str,
     ) -> Result<Vec<String>, ObjectStorageError> {
         let pre = object_store::path::Path::from(format!("{}/{}/{}/", stream_name, date, hour));
-        let resp = self.client.list_with_delimiter(Some(&pre)).await?;
+        let resp = self
+            .client_for(pre.as_ref())
+            .list_with_delimiter(Some(&pre))
+            .await?;
         increment_files_scanned_in_object_store_calls_by_date(
             "LIST",
             resp.common_prefixes.len() as u64,
@@ -909,7 +1122,7 @@ impl ObjectStorage for S3 {
         prefixes
             .into_iter()
             .map(|prefix| {
-                let path = format!("s3://{}/{}", &self.bucket, prefix);
+                let path = format!("s3://{}/{}", self.bucket_for(&prefix).1, prefix);
                 ListingTableUrl::parse(path).unwrap()
             })
             .collect()
@@ -919,6 +1132,15 @@ impl ObjectStorage for S3 {
         url::Url::parse(&format!("s3://{}", self.bucket)).unwrap()
     }
 
+    fn stream_store_url(&self, stream_name: &str) -> url::Url {
+        match self.stream_bucket(stream_name) {
+            Some(stream_bucket) => {
+                url::Url::parse(&format!("s3://{}", stream_bucket.bucket)).unwrap()
+            }
+            None => self.store_url(),
+        }
+    }
+
     async fn list_dirs(&self) -> Result<Vec<String>, ObjectStorageError> {
         let pre = object_store::path::Path::from("/");
         let resp = self.client.list_with_delimiter(Some(&pre)).await;
@@ -952,7 +1174,10 @@ impl ObjectStorage for S3 {
     ) -> Result<Vec<String>, ObjectStorageError> {
         let prefix = object_store::path::Path::from(relative_path.as_str());
 
-        let resp = self.client.list_with_delimiter(Some(&prefix)).await;
+        let resp = self
+            .client_for(prefix.as_ref())
+            .list_with_delimiter(Some(&prefix))
+            .await;
         increment_object_store_calls_by_date("LIST", &Utc::now().date_naive().to_string());
         let resp = match resp {
             Ok(resp) => {
@@ -985,7 +1210,10 @@ impl ObjectStorage for S3 {
         &self,
         prefix: Option<object_store::path::Path>,
     ) -> Result<ListResult, ObjectStorageError> {
-        Ok(self.client.list_with_delimiter(prefix.as_ref()).await?)
+        let client = prefix
+            .as_ref()
+            .map_or(&self.client, |prefix| self.client_for(prefix.as_ref()));
+        Ok(client.list_with_delimiter(prefix.as_ref()).await?)
     }
 }
 
@@ -1006,3 +1234,85 @@ impl From<serde_json::Error> for ObjectStorageError {
         ObjectStorageError::UnhandledError(Box::new(error))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(bucket: &str) -> AmazonS3 {
+        AmazonS3Builder::new()
+            .with_region("us-east-1")
+            .with_bucket_name(bucket)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn routes_stream_data_to_mapped_buckets() {
+        let s3 = S3 {
+            client: client("main"),
+            bucket: "main".to_owned(),
+            root: StorePath::from(""),
+            stream_buckets: ["archive-audit-", "archive-"]
+                .into_iter()
+                .map(|prefix| StreamBucketClient {
+                    prefix: prefix.to_owned(),
+                    bucket: format!("{prefix}bucket"),
+                    client: client(&format!("{prefix}bucket")),
+                })
+                .collect(),
+        };
+
+        assert_eq!(
+            s3.bucket_for("archive-app/date=2024-01-01/hour=00/minute=00/data.parquet")
+                .1,
+            "archive-bucket"
+        );
+        assert_eq!(
+            s3.bucket_for("archive-audit-app/date=2024-01-01/manifest.json")
+                .1,
+            "archive-audit-bucket"
+        );
+        // metadata stays in the main bucket
+        assert_eq!(s3.bucket_for("archive-app/.stream/.stream.json").1, "main");
+        assert_eq!(s3.bucket_for("app/date=2024-01-01/data.parquet").1, "main");
+    }
+
+    #[test]
+    fn data_left_in_the_main_bucket_is_misplaced() {
+        let s3 = S3 {
+            client: client("main"),
+            bucket: "main".to_owned(),
+            root: StorePath::from(""),
+            stream_buckets: vec![StreamBucketClient {
+                prefix: "archive-".to_owned(),
+                bucket: "cold".to_owned(),
+                client: client("cold"),
+            }],
+        };
+        let dirs = |dirs: &[&str]| dirs.iter().map(|dir| dir.to_string()).collect::<Vec<_>>();
+
+        assert!(
+            s3.misplaced_data("archive-app", &dirs(&[".stream", "date=2024-01-01"]))
+                .is_some()
+        );
+        assert!(
+            s3.misplaced_data("archive-app", &dirs(&[".stream"]))
+                .is_none()
+        );
+        assert!(
+            s3.misplaced_data("app", &dirs(&[".stream", "date=2024-01-01"]))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn parses_stream_buckets() {
+        let stream_bucket = StreamBucket::from_str("archive-=cold").unwrap();
+        assert_eq!(stream_bucket.prefix, "archive-");
+        assert_eq!(stream_bucket.bucket, "cold");
+
+        assert!(StreamBucket::from_str("archive-").is_err());
+        assert!(StreamBucket::from_str("=cold").is_err());
+    }
+}
@@ -0,0 +1,116 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Column added to every event ingested into a sampled stream, holding the rate it was sampled at.
+/// Queries can scale counts back up with `SUM(1 / CAST(p_sampling_rate AS DOUBLE))`
+pub const SAMPLING_RATE_KEY: &str = "p_sampling_rate";
+
+/// Fraction of events kept on ingestion for a stream, always within `(0.0, 1.0]`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "f64", into = "f64")]
+pub struct SamplingRate(f64);
+
+// NaN is rejected on construction, so equality is total
+impl Eq for SamplingRate {}
+
+impl TryFrom<f64> for SamplingRate {
+    type Error = String;
+
+    fn try_from(rate: f64) -> Result<Self, Self::Error> {
+        if rate > 0.0 && rate <= 1.0 {
+            Ok(SamplingRate(rate))
+        } else {
+            Err(format!(
+                "sampling rate should be greater than 0.0 and at most 1.0, got {rate}"
+            ))
+        }
+    }
+}
+
+impl From<SamplingRate> for f64 {
+    fn from(rate: SamplingRate) -> Self {
+        rate.0
+    }
+}
+
+impl Display for SamplingRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl SamplingRate {
+    /// Randomly drops events from the payload to keep approximately the configured fraction,
+    /// returns `None` when nothing is left to ingest
+    pub fn sample(&self, json: Value) -> Option<Value> {
+        if self.0 >= 1.0 {
+            return Some(json);
+        }
+
+        match json {
+            Value::Array(events) => {
+                let events: Vec<Value> = events.into_iter().filter(|_| self.keep()).collect();
+                (!events.is_empty()).then_some(Value::Array(events))
+            }
+            event => self.keep().then_some(event),
+        }
+    }
+
+    fn keep(&self) -> bool {
+        rand::random::<f64>() < self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn rejects_out_of_range_rates() {
+        assert!(serde_json::from_value::<SamplingRate>(json!(0.0)).is_err());
+        assert!(serde_json::from_value::<SamplingRate>(json!(1.5)).is_err());
+        assert!(serde_json::from_value::<SamplingRate>(json!(-0.1)).is_err());
+        assert!(serde_json::from_value::<SamplingRate>(json!(0.25)).is_ok());
+    }
+
+    #[test]
+    fn full_rate_keeps_everything() {
+        let rate = SamplingRate::try_from(1.0).unwrap();
+        let events = json!([{"a": 1}, {"a": 2}, {"a": 3}]);
+
+        assert_eq!(rate.sample(events.clone()), Some(events));
+    }
+
+    #[test]
+    fn keeps_approximately_the_configured_fraction() {
+        let rate = SamplingRate::try_from(0.5).unwrap();
+        let events = Value::Array((0..10_000).map(|i| json!({ "i": i })).collect());
+
+        let Some(Value::Array(kept)) = rate.sample(events) else {
+            panic!("expected sampled events");
+        };
+        assert!((4_000..6_000).contains(&kept.len()));
+    }
+}
@@ -23,6 +23,7 @@ use std::{
 };
 
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use once_cell::sync::OnceCell;
 use relative_path::RelativePathBuf;
 use std::io;
@@ -68,6 +69,9 @@ pub struct StorageMetadata {
     pub roles: HashMap<String, Vec<DefaultPrivilege>>,
     #[serde(default)]
     pub default_role: Option<String>,
+    /// when the sessions of all the users were last revoked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sessions_rotated_at: Option<DateTime<Utc>>,
 }
 
 impl Default for StorageMetadata {
@@ -84,6 +88,7 @@ impl Default for StorageMetadata {
             streams: Vec::new(),
             roles: HashMap::default(),
             default_role: None,
+            sessions_rotated_at: None,
         }
     }
 }
@@ -0,0 +1,142 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use arrow_schema::DataType;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// How a stream assigns the `p_timestamp` of its events
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "policy", rename_all = "camelCase")]
+pub enum TimestampPolicy {
+    /// the time the event is ingested at
+    #[default]
+    ServerTime,
+    /// the time in `field`, events without it are rejected
+    Reject { field: String },
+    /// the time in `field`, the time the event is ingested at for events without it
+    FieldOrServer { field: String },
+}
+
+impl TimestampPolicy {
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            Self::ServerTime => None,
+            Self::Reject { field } | Self::FieldOrServer { field } => Some(field),
+        }
+    }
+
+    /// Whether a column of type `data_type` can hold the timestamps of the policy
+    pub fn accepts_type(data_type: &DataType) -> bool {
+        matches!(
+            data_type,
+            DataType::Utf8 | DataType::Int64 | DataType::Timestamp(_, _)
+        )
+    }
+
+    /// Pairs the flattened events with their timestamp, batches are split into single events
+    /// unless the server time is used. Returns the number of events that fell back to `server_time`.
+    pub fn assign(
+        &self,
+        data: Vec<Value>,
+        server_time: DateTime<Utc>,
+    ) -> Result<(Vec<(Value, DateTime<Utc>)>, u64), String> {
+        let Some(field) = self.field() else {
+            return Ok((
+                data.into_iter().map(|json| (json, server_time)).collect(),
+                0,
+            ));
+        };
+
+        let mut fallbacks = 0;
+        let mut events = Vec::with_capacity(data.len());
+        for json in data.into_iter().flat_map(|json| match json {
+            Value::Array(events) => events,
+            event => vec![event],
+        }) {
+            let timestamp = match (parse_timestamp(json.get(field)), self) {
+                (Some(timestamp), _) => timestamp,
+                (None, Self::FieldOrServer { .. }) => {
+                    fallbacks += 1;
+                    server_time
+                }
+                (None, _) => {
+                    return Err(format!(
+                        "field {field} is missing or isn't a valid timestamp, it is required by the timestamp policy of the stream"
+                    ));
+                }
+            };
+            events.push((json, timestamp));
+        }
+
+        Ok((events, fallbacks))
+    }
+}
+
+/// Parses RFC 3339 timestamps, with or without a timezone, and milliseconds since the epoch
+fn parse_timestamp(value: Option<&Value>) -> Option<DateTime<Utc>> {
+    match value? {
+        Value::String(s) => DateTime::parse_from_rfc3339(s)
+            .map(|timestamp| timestamp.with_timezone(&Utc))
+            .or_else(|_| {
+                NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f").map(|t| t.and_utc())
+            })
+            .ok(),
+        Value::Number(n) => DateTime::from_timestamp_millis(n.as_i64()?),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn assigns_event_time_from_field() {
+        let server_time = Utc::now();
+        let data = vec![json!([
+            {"ts": "2024-05-01T10:00:00Z"},
+            {"ts": 1714557600000_i64},
+            {"msg": "no time"},
+        ])];
+        let policy = TimestampPolicy::FieldOrServer {
+            field: "ts".to_owned(),
+        };
+
+        let (events, fallbacks) = policy.assign(data.clone(), server_time).unwrap();
+        let expected = "2024-05-01T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(fallbacks, 1);
+        assert_eq!(
+            events.iter().map(|(_, t)| *t).collect::<Vec<_>>(),
+            vec![expected, expected, server_time]
+        );
+
+        let policy = TimestampPolicy::Reject {
+            field: "ts".to_owned(),
+        };
+        assert!(policy.assign(data.clone(), server_time).is_err());
+
+        let (events, _) = TimestampPolicy::ServerTime
+            .assign(data, server_time)
+            .unwrap();
+        assert_eq!(events.len(), 1);
+    }
+}
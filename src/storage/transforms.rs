@@ -0,0 +1,528 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Transforms of the events of a stream, applied in order on ingestion once the events are
+//! flattened and before their schema is inferred:
+//! - `rename` a field, events without it are left as is
+//! - `drop` a field
+//! - `derive` a field from an expression on the fields of the event, e.g. `floor(status / 100)`
+//!
+//! Derive expressions are made of fields, numbers, strings in single quotes, the operators
+//! `+ - * / %` and the functions `floor`, `ceil`, `round`, `abs`, `lower`, `upper` and `concat`.
+//! `floor`, `ceil` and `round` return integers, `/` always returns a float.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+
+/// A transform of the events of a stream
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TransformRule {
+    /// an existing field named `to` is replaced
+    Rename {
+        from: String,
+        to: String,
+    },
+    Drop {
+        field: String,
+    },
+    /// events for which the expression can't be computed don't get the field
+    Derive {
+        field: String,
+        expression: DeriveExpression,
+    },
+}
+
+impl TransformRule {
+    /// Fields the rule adds to the events
+    pub fn output_field(&self) -> Option<&str> {
+        match self {
+            Self::Rename { to, .. } => Some(to),
+            Self::Derive { field, .. } => Some(field),
+            Self::Drop { .. } => None,
+        }
+    }
+
+    /// Fields the rule removes from the events
+    pub fn removed_field(&self) -> Option<&str> {
+        match self {
+            Self::Rename { from, .. } => Some(from),
+            Self::Drop { field } => Some(field),
+            Self::Derive { .. } => None,
+        }
+    }
+}
+
+/// Applies the transforms to the flattened event(s), returns the number of derived fields
+/// that couldn't be computed and were left out of their event
+pub fn apply_transforms(json: &mut Value, rules: &[TransformRule]) -> u64 {
+    match json {
+        Value::Array(events) => events
+            .iter_mut()
+            .map(|event| apply_transforms(event, rules))
+            .sum(),
+        Value::Object(event) => {
+            let mut failures = 0;
+            for rule in rules {
+                match rule {
+                    TransformRule::Rename { from, to } => {
+                        if let Some(value) = event.remove(from) {
+                            event.insert(to.clone(), value);
+                        }
+                    }
+                    TransformRule::Drop { field } => {
+                        event.remove(field);
+                    }
+                    TransformRule::Derive { field, expression } => {
+                        match expression.evaluate(event) {
+                            Ok(value) => {
+                                event.insert(field.clone(), value);
+                            }
+                            Err(_) => failures += 1,
+                        }
+                    }
+                }
+            }
+            failures
+        }
+        _ => 0,
+    }
+}
+
+/// A parsed derive expression, stored as written
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DeriveExpression {
+    source: String,
+    expr: Expr,
+}
+
+impl TryFrom<String> for DeriveExpression {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        let error = |msg: String| format!("Invalid derive expression `{source}`: {msg}");
+        let mut tokens = tokenize(&source).map_err(error)?.into_iter().peekable();
+        let expr = parse_sum(&mut tokens).map_err(error)?;
+        if let Some(token) = tokens.next() {
+            return Err(error(format!("unexpected {token}")));
+        }
+
+        Ok(Self { source, expr })
+    }
+}
+
+impl From<DeriveExpression> for String {
+    fn from(expression: DeriveExpression) -> Self {
+        expression.source
+    }
+}
+
+impl fmt::Display for DeriveExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl DeriveExpression {
+    /// Computes the value of the expression for a flattened event
+    pub fn evaluate(&self, event: &Map<String, Value>) -> Result<Value, String> {
+        match evaluate(&self.expr, event)? {
+            Scalar::Int(i) => Ok(Value::from(i)),
+            Scalar::Float(f) => Number::from_f64(f)
+                .map(Value::Number)
+                .ok_or_else(|| format!("{f} is not a valid number")),
+            Scalar::Str(s) => Ok(Value::String(s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Field(String),
+    Literal(Scalar),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, Operator, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Floor,
+    Ceil,
+    Round,
+    Abs,
+    Lower,
+    Upper,
+    Concat,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Scalar {
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl Scalar {
+    fn as_f64(&self) -> Result<f64, String> {
+        match self {
+            Scalar::Int(i) => Ok(*i as f64),
+            Scalar::Float(f) => Ok(*f),
+            Scalar::Str(s) => Err(format!("'{s}' is not a number")),
+        }
+    }
+
+    fn into_string(self) -> String {
+        match self {
+            Scalar::Int(i) => i.to_string(),
+            Scalar::Float(f) => f.to_string(),
+            Scalar::Str(s) => s,
+        }
+    }
+}
+
+fn evaluate(expr: &Expr, event: &Map<String, Value>) -> Result<Scalar, String> {
+    let overflow = || "integer overflow".to_owned();
+    Ok(match expr {
+        Expr::Field(field) => match event.get(field) {
+            Some(Value::Number(n)) => match n.as_i64() {
+                Some(i) => Scalar::Int(i),
+                None => Scalar::Float(n.as_f64().ok_or_else(|| format!("{n} is out of range"))?),
+            },
+            Some(Value::String(s)) => Scalar::Str(s.clone()),
+            Some(Value::Bool(b)) => Scalar::Str(b.to_string()),
+            _ => return Err(format!("field {field} is missing or null")),
+        },
+        Expr::Literal(scalar) => scalar.clone(),
+        Expr::Neg(inner) => match evaluate(inner, event)? {
+            Scalar::Int(i) => Scalar::Int(i.checked_neg().ok_or_else(overflow)?),
+            scalar => Scalar::Float(-scalar.as_f64()?),
+        },
+        Expr::Binary(lhs, operator, rhs) => {
+            match (evaluate(lhs, event)?, *operator, evaluate(rhs, event)?) {
+                (_, Operator::Rem | Operator::Div, Scalar::Int(0)) => {
+                    return Err("division by zero".to_owned());
+                }
+                (Scalar::Int(a), Operator::Add, Scalar::Int(b)) => {
+                    Scalar::Int(a.checked_add(b).ok_or_else(overflow)?)
+                }
+                (Scalar::Int(a), Operator::Sub, Scalar::Int(b)) => {
+                    Scalar::Int(a.checked_sub(b).ok_or_else(overflow)?)
+                }
+                (Scalar::Int(a), Operator::Mul, Scalar::Int(b)) => {
+                    Scalar::Int(a.checked_mul(b).ok_or_else(overflow)?)
+                }
+                (Scalar::Int(a), Operator::Rem, Scalar::Int(b)) => {
+                    Scalar::Int(a.checked_rem(b).ok_or_else(overflow)?)
+                }
+                (a, operator, b) => {
+                    let (a, b) = (a.as_f64()?, b.as_f64()?);
+                    if b == 0.0 && matches!(operator, Operator::Div | Operator::Rem) {
+                        return Err("division by zero".to_owned());
+                    }
+                    Scalar::Float(match operator {
+                        Operator::Add => a + b,
+                        Operator::Sub => a - b,
+                        Operator::Mul => a * b,
+                        Operator::Div => a / b,
+                        Operator::Rem => a % b,
+                    })
+                }
+            }
+        }
+        Expr::Call(function, args) => {
+            let mut args = args
+                .iter()
+                .map(|arg| evaluate(arg, event))
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter();
+            let mut arg = || args.next().expect("arity is checked");
+            let to_int = |f: f64| {
+                (f.is_finite() && f >= i64::MIN as f64 && f <= i64::MAX as f64)
+                    .then_some(Scalar::Int(f as i64))
+                    .ok_or_else(|| format!("{f} is out of the range of integers"))
+            };
+            match function {
+                Function::Floor => to_int(arg().as_f64()?.floor())?,
+                Function::Ceil => to_int(arg().as_f64()?.ceil())?,
+                Function::Round => to_int(arg().as_f64()?.round())?,
+                Function::Abs => match arg() {
+                    Scalar::Int(i) => Scalar::Int(i.checked_abs().ok_or_else(overflow)?),
+                    scalar => Scalar::Float(scalar.as_f64()?.abs()),
+                },
+                Function::Lower => Scalar::Str(arg().into_string().to_lowercase()),
+                Function::Upper => Scalar::Str(arg().into_string().to_uppercase()),
+                Function::Concat => Scalar::Str(args.map(Scalar::into_string).collect()),
+            }
+        }
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Number(Scalar),
+    Operator(char),
+    Open,
+    Close,
+    Comma,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "`{ident}`"),
+            Token::Str(s) => write!(f, "'{s}'"),
+            Token::Number(number) => write!(f, "{}", number.clone().into_string()),
+            Token::Operator(c) => write!(f, "`{c}`"),
+            Token::Open => f.write_str("`(`"),
+            Token::Close => f.write_str("`)`"),
+            Token::Comma => f.write_str("`,`"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            ',' => tokens.push(Token::Comma),
+            '+' | '-' | '*' | '/' | '%' => tokens.push(Token::Operator(c)),
+            '\'' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        // a quote is escaped by doubling it
+                        Some('\'') if chars.peek() == Some(&'\'') => {
+                            chars.next();
+                            s.push('\'');
+                        }
+                        Some('\'') => break,
+                        Some(c) => s.push(c),
+                        None => return Err("unterminated string".to_owned()),
+                    }
+                }
+                tokens.push(Token::Str(s));
+            }
+            c if c.is_ascii_digit() => {
+                let digits = take_while(c, &mut chars, |c| c.is_ascii_digit() || c == '.');
+                let number = match digits.parse() {
+                    Ok(int) => Scalar::Int(int),
+                    Err(_) => Scalar::Float(
+                        digits
+                            .parse()
+                            .map_err(|_| format!("{digits} is not a valid number"))?,
+                    ),
+                };
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_alphabetic() || c == '_' || c == '@' => {
+                tokens.push(Token::Ident(take_while(c, &mut chars, |c| {
+                    c.is_alphanumeric() || c == '_' || c == '@'
+                })));
+            }
+            c => return Err(format!("unexpected character {c}")),
+        }
+    }
+    Ok(tokens)
+}
+
+fn take_while(first: char, chars: &mut Peekable<Chars>, f: impl Fn(char) -> bool) -> String {
+    let mut s = first.to_string();
+    while let Some(c) = chars.next_if(|c| f(*c)) {
+        s.push(c);
+    }
+    s
+}
+
+// sum := product (('+' | '-') product)*
+fn parse_sum(tokens: &mut Peekable<impl Iterator<Item = Token>>) -> Result<Expr, String> {
+    let mut expr = parse_product(tokens)?;
+    while let Some(Token::Operator(c @ ('+' | '-'))) = tokens.peek().cloned() {
+        tokens.next();
+        let operator = if c == '+' {
+            Operator::Add
+        } else {
+            Operator::Sub
+        };
+        expr = Expr::Binary(Box::new(expr), operator, Box::new(parse_product(tokens)?));
+    }
+    Ok(expr)
+}
+
+// product := unary (('*' | '/' | '%') unary)*
+fn parse_product(tokens: &mut Peekable<impl Iterator<Item = Token>>) -> Result<Expr, String> {
+    let mut expr = parse_unary(tokens)?;
+    while let Some(Token::Operator(c @ ('*' | '/' | '%'))) = tokens.peek().cloned() {
+        tokens.next();
+        let operator = match c {
+            '*' => Operator::Mul,
+            '/' => Operator::Div,
+            _ => Operator::Rem,
+        };
+        expr = Expr::Binary(Box::new(expr), operator, Box::new(parse_unary(tokens)?));
+    }
+    Ok(expr)
+}
+
+// unary := '-' unary | primary
+fn parse_unary(tokens: &mut Peekable<impl Iterator<Item = Token>>) -> Result<Expr, String> {
+    if tokens.next_if_eq(&Token::Operator('-')).is_some() {
+        return Ok(Expr::Neg(Box::new(parse_unary(tokens)?)));
+    }
+
+    match tokens.next() {
+        Some(Token::Ident(ident)) if tokens.peek() == Some(&Token::Open) => {
+            tokens.next();
+            let mut args = vec![];
+            if tokens.next_if_eq(&Token::Close).is_none() {
+                loop {
+                    args.push(parse_sum(tokens)?);
+                    match tokens.next() {
+                        Some(Token::Comma) => {}
+                        Some(Token::Close) => break,
+                        Some(token) => return Err(format!("unexpected {token}")),
+                        None => return Err("missing `)`".to_owned()),
+                    }
+                }
+            }
+            call(&ident, args)
+        }
+        Some(Token::Ident(ident)) => Ok(Expr::Field(ident)),
+        Some(Token::Str(s)) => Ok(Expr::Literal(Scalar::Str(s))),
+        Some(Token::Number(number)) => Ok(Expr::Literal(number)),
+        Some(Token::Open) => {
+            let expr = parse_sum(tokens)?;
+            match tokens.next() {
+                Some(Token::Close) => Ok(expr),
+                Some(token) => Err(format!("unexpected {token}")),
+                None => Err("missing `)`".to_owned()),
+            }
+        }
+        Some(token) => Err(format!("unexpected {token}")),
+        None => Err("unexpected end of expression".to_owned()),
+    }
+}
+
+fn call(function: &str, args: Vec<Expr>) -> Result<Expr, String> {
+    let function_of = match function {
+        "floor" => Function::Floor,
+        "ceil" => Function::Ceil,
+        "round" => Function::Round,
+        "abs" => Function::Abs,
+        "lower" => Function::Lower,
+        "upper" => Function::Upper,
+        "concat" => Function::Concat,
+        _ => return Err(format!("unknown function {function}")),
+    };
+    match function_of {
+        Function::Concat if args.is_empty() => {
+            return Err("concat takes at least 1 argument".to_owned());
+        }
+        Function::Concat => {}
+        _ if args.len() != 1 => return Err(format!("{function} takes 1 argument")),
+        _ => {}
+    }
+
+    Ok(Expr::Call(function_of, args))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn derive(field: &str, expression: &str) -> TransformRule {
+        TransformRule::Derive {
+            field: field.to_owned(),
+            expression: DeriveExpression::try_from(expression.to_owned()).unwrap(),
+        }
+    }
+
+    #[test]
+    fn transforms_events_in_order() {
+        let rules = vec![
+            TransformRule::Rename {
+                from: "code".to_owned(),
+                to: "status".to_owned(),
+            },
+            derive("status_class", "floor(status / 100)"),
+            derive("latency_ms", "-(latency * 1000) * -1"),
+            derive("route", "concat(lower(method), ' ', path)"),
+            TransformRule::Drop {
+                field: "debug".to_owned(),
+            },
+        ];
+        let mut events = json!([
+            {"code": 503, "latency": 0.25, "method": "GET", "path": "/", "debug": "x"},
+            {"code": "oops", "latency": 1, "method": "POST", "path": "/a"},
+        ]);
+
+        assert_eq!(apply_transforms(&mut events, &rules), 1);
+        assert_eq!(
+            events,
+            json!([
+                {"status": 503, "status_class": 5, "latency": 0.25, "latency_ms": 250.0, "method": "GET", "path": "/", "route": "get /"},
+                {"status": "oops", "latency": 1, "latency_ms": 1000, "method": "POST", "path": "/a", "route": "post /a"},
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for source in [
+            "",
+            "status /",
+            "floor(status",
+            "floor(status, 2)",
+            "sqrt(status)",
+            "concat()",
+            "status status",
+            "status == 5",
+        ] {
+            assert!(DeriveExpression::try_from(source.to_owned()).is_err());
+        }
+
+        let division = DeriveExpression::try_from("bytes % 0".to_owned()).unwrap();
+        assert!(
+            division
+                .evaluate(json!({"bytes": 7}).as_object().unwrap())
+                .is_err()
+        );
+    }
+}
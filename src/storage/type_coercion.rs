@@ -0,0 +1,130 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_schema::{DataType, Field};
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+
+/// How a stream handles event values whose type conflicts with the type of their existing column,
+/// e.g. a number sent for a string column
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TypeCoercionPolicy {
+    /// the event is rejected
+    #[default]
+    Reject,
+    /// numbers and booleans sent for string columns are stored as strings
+    CoerceToString,
+    /// numeric strings sent for float columns are stored as floats
+    CoerceToFloat,
+}
+
+impl TypeCoercionPolicy {
+    /// Converts the values of the flattened event(s) conflicting with the type of their column in `schema`,
+    /// returns the number of converted values. Values that can't be converted are left as is,
+    /// for the event to be rejected on the type mismatch.
+    pub fn apply(&self, json: &mut Value, schema: &HashMap<String, Arc<Field>>) -> u64 {
+        match json {
+            Value::Array(events) => events
+                .iter_mut()
+                .map(|event| self.apply(event, schema))
+                .sum(),
+            Value::Object(event) => {
+                let mut coerced = 0;
+                for (name, value) in event.iter_mut() {
+                    let Some(field) = schema.get(name) else {
+                        continue;
+                    };
+                    if let Some(converted) = self.coerce(field.data_type(), value) {
+                        *value = converted;
+                        coerced += 1;
+                    }
+                }
+                coerced
+            }
+            _ => 0,
+        }
+    }
+
+    fn coerce(&self, data_type: &DataType, value: &Value) -> Option<Value> {
+        match (self, data_type, value) {
+            (Self::CoerceToString, DataType::Utf8, Value::Number(_) | Value::Bool(_)) => {
+                Some(Value::String(value.to_string()))
+            }
+            (Self::CoerceToFloat, DataType::Float64, Value::String(s)) => s
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn schema() -> HashMap<String, Arc<Field>> {
+        [
+            Field::new("status", DataType::Utf8, true),
+            Field::new("latency", DataType::Float64, true),
+        ]
+        .into_iter()
+        .map(|field| (field.name().clone(), Arc::new(field)))
+        .collect()
+    }
+
+    #[test]
+    fn coerces_conflicting_values_only() {
+        let mut events = json!([
+            {"status": 200, "latency": "1.5"},
+            {"status": "ok", "latency": "slow", "other": 1},
+        ]);
+
+        assert_eq!(
+            TypeCoercionPolicy::CoerceToString.apply(&mut events.clone(), &schema()),
+            1
+        );
+        assert_eq!(
+            TypeCoercionPolicy::CoerceToFloat.apply(&mut events, &schema()),
+            1
+        );
+        assert_eq!(
+            events,
+            json!([
+                {"status": 200, "latency": 1.5},
+                {"status": "ok", "latency": "slow", "other": 1},
+            ])
+        );
+    }
+
+    #[test]
+    fn reject_leaves_events_untouched() {
+        let mut event = json!({"status": 200});
+
+        assert_eq!(TypeCoercionPolicy::Reject.apply(&mut event, &schema()), 0);
+        assert_eq!(event, json!({"status": 200}));
+    }
+}
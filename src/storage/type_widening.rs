@@ -0,0 +1,246 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use arrow::compute::cast;
+use arrow_array::ArrayRef;
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+
+/// How a dynamic schema stream handles fractional numbers sent for an integer column
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TypeWideningPolicy {
+    /// the event is rejected
+    #[default]
+    Reject,
+    /// the column is widened to Float64, the files already written are read as Float64
+    Widen,
+    /// the numbers are truncated to integers
+    Coerce,
+}
+
+impl TypeWideningPolicy {
+    /// Integer columns of `schema` the flattened event(s) send fractional numbers for, which are
+    /// to be widened. Fails when the policy rejects such events.
+    pub fn check(
+        &self,
+        json: &Value,
+        schema: &HashMap<String, Arc<Field>>,
+    ) -> Result<Vec<String>, String> {
+        let mut conflicts = vec![];
+        collect_conflicts(json, schema, &mut conflicts);
+        match self {
+            Self::Reject if !conflicts.is_empty() => Err(format!(
+                "field {} is a fractional number while its column is an integer, type widening is rejected by the stream",
+                conflicts[0]
+            )),
+            Self::Widen => Ok(conflicts),
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// Converts the numbers of the flattened event(s) to the type of their column, returns the
+    /// number of converted values. Integers are stored as floats in float columns, unless the
+    /// policy rejects, and fractional numbers are truncated in integer columns when coercing.
+    pub fn apply(&self, json: &mut Value, schema: &HashMap<String, Arc<Field>>) -> u64 {
+        match json {
+            Value::Array(events) => events
+                .iter_mut()
+                .map(|event| self.apply(event, schema))
+                .sum(),
+            Value::Object(event) => {
+                let mut converted = 0;
+                for (name, value) in event.iter_mut() {
+                    let (Some(field), Value::Number(number)) = (schema.get(name), &value) else {
+                        continue;
+                    };
+                    let number = match (self, field.data_type()) {
+                        (Self::Widen | Self::Coerce, DataType::Float64) if !number.is_f64() => {
+                            number.as_f64().and_then(Number::from_f64)
+                        }
+                        (Self::Coerce, data_type) if data_type.is_integer() && number.is_f64() => {
+                            number.as_f64().map(|n| Number::from(n.trunc() as i64))
+                        }
+                        _ => None,
+                    };
+                    if let Some(number) = number {
+                        *value = Value::Number(number);
+                        converted += 1;
+                    }
+                }
+                converted
+            }
+            _ => 0,
+        }
+    }
+}
+
+fn collect_conflicts(
+    json: &Value,
+    schema: &HashMap<String, Arc<Field>>,
+    conflicts: &mut Vec<String>,
+) {
+    match json {
+        Value::Array(events) => {
+            for event in events {
+                collect_conflicts(event, schema, conflicts);
+            }
+        }
+        Value::Object(event) => {
+            for (name, value) in event {
+                if value.is_f64()
+                    && schema
+                        .get(name)
+                        .is_some_and(|field| field.data_type().is_integer())
+                    && !conflicts.contains(name)
+                {
+                    conflicts.push(name.clone());
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Merges the schemas as `Schema::try_merge` does, integer columns which are Float64 in one of
+/// the schemas were widened and are merged as Float64
+pub fn merge_schemas(schemas: impl IntoIterator<Item = Schema>) -> Result<Schema, ArrowError> {
+    let schemas = schemas.into_iter().collect::<Vec<_>>();
+    let floats = schemas
+        .iter()
+        .flat_map(|schema| schema.fields().iter())
+        .filter(|field| field.data_type() == &DataType::Float64)
+        .map(|field| field.name().as_str())
+        .collect::<HashSet<_>>();
+    let widened = schemas
+        .iter()
+        .flat_map(|schema| schema.fields().iter())
+        .filter(|field| field.data_type().is_integer() && floats.contains(field.name().as_str()))
+        .map(|field| field.name().clone())
+        .collect::<HashSet<_>>();
+    if widened.is_empty() {
+        return Schema::try_merge(schemas);
+    }
+
+    Schema::try_merge(schemas.into_iter().map(|schema| {
+        let fields = schema
+            .fields()
+            .iter()
+            .map(|field| {
+                if widened.contains(field.name()) {
+                    Arc::new(field.as_ref().clone().with_data_type(DataType::Float64))
+                } else {
+                    field.clone()
+                }
+            })
+            .collect::<Vec<_>>();
+        Schema::new_with_metadata(fields, schema.metadata().clone())
+    }))
+}
+
+/// Casts a column written before it was widened to its type in the merged schema, only integer
+/// columns are widened to Float64, as in `merge_schemas`
+pub fn widen_column(column: &ArrayRef, data_type: &DataType) -> Result<ArrayRef, ArrowError> {
+    if column.data_type().is_integer() && data_type == &DataType::Float64 {
+        cast(column, data_type)
+    } else {
+        Err(ArrowError::SchemaError(format!(
+            "column of type {} can't be widened to {data_type}",
+            column.data_type()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn schema() -> HashMap<String, Arc<Field>> {
+        [
+            Field::new("status", DataType::Int64, true),
+            Field::new("latency", DataType::Float64, true),
+        ]
+        .into_iter()
+        .map(|field| (field.name().clone(), Arc::new(field)))
+        .collect()
+    }
+
+    #[test]
+    fn fractional_numbers_for_integer_columns_follow_the_policy() {
+        let events = json!([{"status": 200, "latency": 3}, {"status": 2.5}]);
+
+        assert!(
+            TypeWideningPolicy::Reject
+                .check(&events, &schema())
+                .is_err()
+        );
+        assert_eq!(
+            TypeWideningPolicy::Widen.check(&events, &schema()).unwrap(),
+            ["status"]
+        );
+
+        let mut coerced = events.clone();
+        assert!(
+            TypeWideningPolicy::Coerce
+                .check(&coerced, &schema())
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(TypeWideningPolicy::Coerce.apply(&mut coerced, &schema()), 2);
+        assert_eq!(
+            coerced,
+            json!([{"status": 200, "latency": 3.0}, {"status": 2}])
+        );
+    }
+
+    #[test]
+    fn widened_columns_merge_as_floats() {
+        let int = Schema::new(vec![Field::new("status", DataType::Int64, true)]);
+        let float = Schema::new(vec![
+            Field::new("status", DataType::Float64, true),
+            Field::new("host", DataType::Utf8, true),
+        ]);
+
+        let merged = merge_schemas([int, float]).unwrap();
+        assert_eq!(
+            merged.field_with_name("status").unwrap().data_type(),
+            &DataType::Float64
+        );
+        assert_eq!(merged.fields().len(), 2);
+
+        let conflicting = Schema::new(vec![Field::new("host", DataType::Int64, true)]);
+        assert!(merge_schemas([merged, conflicting]).is_err());
+    }
+
+    #[test]
+    fn only_integer_columns_are_widened() {
+        let status: ArrayRef = Arc::new(arrow_array::Int64Array::from(vec![200, 404]));
+        let widened = widen_column(&status, &DataType::Float64).unwrap();
+        assert_eq!(widened.data_type(), &DataType::Float64);
+
+        let host: ArrayRef = Arc::new(arrow_array::StringArray::from(vec!["a"]));
+        assert!(widen_column(&host, &DataType::Float64).is_err());
+        assert!(widen_column(&status, &DataType::Utf8).is_err());
+    }
+}
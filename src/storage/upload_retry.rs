@@ -0,0 +1,110 @@
+/*
+ * Parseable Server (C) 2022 - 2024 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+
+/// Staging directory of a stream that parquet files are moved to once their upload keeps failing
+pub const QUARANTINE_DIR: &str = "failed";
+
+const BASE_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_BACKOFF: Duration = Duration::from_secs(30 * 60);
+
+/// Upload failures of staged parquet files, shared by all object store syncs
+pub static UPLOAD_FAILURES: Lazy<UploadFailures> = Lazy::new(UploadFailures::default);
+
+struct Failure {
+    attempts: u32,
+    retry_at: Instant,
+}
+
+#[derive(Default)]
+pub struct UploadFailures {
+    files: Mutex<HashMap<PathBuf, Failure>>,
+}
+
+impl UploadFailures {
+    /// Whether a file is due for an upload, i.e. it never failed or its backoff has elapsed
+    pub fn is_due(&self, path: &Path, now: Instant) -> bool {
+        self.files
+            .lock()
+            .expect("lock poisoned")
+            .get(path)
+            .is_none_or(|failure| failure.retry_at <= now)
+    }
+
+    /// Records a failed upload of a file and returns the number of failed attempts so far
+    pub fn record(&self, path: &Path, now: Instant) -> u32 {
+        let mut files = self.files.lock().expect("lock poisoned");
+        let failure = files.entry(path.to_path_buf()).or_insert(Failure {
+            attempts: 0,
+            retry_at: now,
+        });
+        failure.attempts += 1;
+        failure.retry_at = now + backoff(failure.attempts);
+        failure.attempts
+    }
+
+    /// Forgets the failures of a file once it is uploaded or quarantined
+    pub fn clear(&self, path: &Path) {
+        self.files.lock().expect("lock poisoned").remove(path);
+    }
+}
+
+/// Exponential backoff after the given number of failed attempts
+fn backoff(attempts: u32) -> Duration {
+    BASE_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        assert_eq!(backoff(1), Duration::from_secs(30));
+        assert_eq!(backoff(2), Duration::from_secs(60));
+        assert_eq!(backoff(3), Duration::from_secs(120));
+        assert_eq!(backoff(10), MAX_BACKOFF);
+        assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn failed_files_wait_for_backoff() {
+        let failures = UploadFailures::default();
+        let path = Path::new("staging/app/file.parquet");
+        let now = Instant::now();
+        assert!(failures.is_due(path, now));
+
+        assert_eq!(failures.record(path, now), 1);
+        assert!(!failures.is_due(path, now));
+        assert!(failures.is_due(path, now + BASE_BACKOFF));
+
+        assert_eq!(failures.record(path, now + BASE_BACKOFF), 2);
+        assert!(!failures.is_due(path, now + BASE_BACKOFF * 2));
+
+        failures.clear(path);
+        assert!(failures.is_due(path, now));
+    }
+}
@@ -16,22 +16,31 @@
  *
  */
 
-use chrono::{TimeDelta, Timelike};
+use chrono::{DateTime, TimeDelta, Timelike, Utc};
 use futures::FutureExt;
 use std::collections::HashMap;
 use std::future::Future;
 use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::sync::{mpsc, oneshot};
 use tokio::task::JoinSet;
-use tokio::time::{Duration, Instant, interval_at, sleep};
+use tokio::time::{Duration, Instant, MissedTickBehavior, interval_at, sleep};
 use tokio::{select, task};
 use tracing::{error, info, trace, warn};
+use ulid::Ulid;
 
+use crate::LOCAL_SYNC_INTERVAL;
+use crate::alerts::AlertError;
 use crate::alerts::alert_enums::AlertTask;
+use crate::alerts::alert_structs::ScheduledAlertTask;
 use crate::alerts::alerts_utils;
+use crate::alerts::eval_slots::acquire_alert_eval_slot;
+use crate::metrics::{
+    ALERT_EVAL_DURATION, ALERT_EVAL_ERRORS, OBJECT_STORE_SYNCS_SKIPPED, STAGING_BACKLOG_BYTES,
+};
 use crate::parseable::PARSEABLE;
-use crate::storage::object_storage::sync_all_streams;
-use crate::{LOCAL_SYNC_INTERVAL, STORAGE_UPLOAD_INTERVAL};
+use crate::storage::object_storage::{staging_backlog, sync_all_streams};
 
 // Calculates the instant that is the start of the next minute
 fn next_minute() -> Instant {
@@ -77,7 +86,7 @@ where
 }
 
 /// Flushes arrows onto disk every `LOCAL_SYNC_INTERVAL` seconds, packs arrows into parquet every
-/// `STORAGE_CONVERSION_INTERVAL` secondsand uploads them every `STORAGE_UPLOAD_INTERVAL` seconds.
+/// `STORAGE_CONVERSION_INTERVAL` secondsand uploads them every `P_OBJECT_STORE_SYNC_INTERVAL` seconds.
 #[tokio::main(flavor = "multi_thread")]
 pub async fn handler(mut cancel_rx: oneshot::Receiver<()>) -> anyhow::Result<()> {
     let (localsync_handler, mut localsync_outbox, localsync_inbox) = local_sync();
@@ -122,12 +131,17 @@ pub fn object_store_sync() -> (
         let mut inbox_rx = inbox_rx;
 
         let result = tokio::spawn(async move {
-            let mut sync_interval = interval_at(next_minute(), STORAGE_UPLOAD_INTERVAL);
+            let upload_interval = Duration::from_secs(PARSEABLE.options.object_store_sync_interval);
+            let mut sync_interval = interval_at(next_minute(), upload_interval);
+            // a sync outlasting the interval is followed by the next scheduled one, not by a burst of syncs
+            sync_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
             loop {
                 select! {
                     _ = sync_interval.tick() => {
                         trace!("Syncing Parquets to Object Store... ");
+                        report_staging_backlog();
+                        let start = Instant::now();
 
                         // Monitor the duration of sync_all_streams execution
                         monitor_task_duration(
@@ -143,6 +157,12 @@ pub fn object_store_sync() -> (
                                 }
                             }
                         ).await;
+
+                        let skipped = start.elapsed().as_secs() / upload_interval.as_secs();
+                        if skipped > 0 {
+                            warn!("Object store sync took {:?}, skipping {skipped} sync(s)", start.elapsed());
+                            OBJECT_STORE_SYNCS_SKIPPED.inc_by(skipped);
+                        }
                     },
                     res = &mut inbox_rx => {
                         match res {
@@ -175,6 +195,20 @@ pub fn object_store_sync() -> (
     (handle, outbox_rx, inbox_tx)
 }
 
+/// Updates the size of the staged parquet files waiting for upload,
+/// warning when it is past `P_STAGING_BACKLOG_THRESHOLD`
+fn report_staging_backlog() {
+    let backlog = staging_backlog();
+    STAGING_BACKLOG_BYTES.set(backlog as i64);
+    if let Some(threshold) = PARSEABLE.options.staging_backlog_threshold
+        && backlog > threshold * 1024 * 1024
+    {
+        warn!(
+            "{backlog} bytes of staged parquet files are waiting for upload, past the threshold of {threshold} MiB, the object store sync is falling behind"
+        );
+    }
+}
+
 /// Flush arrows onto disk and convert them into parquet files
 pub fn local_sync() -> (
     task::JoinHandle<()>,
@@ -286,10 +320,48 @@ where
     }
 }
 
+// evaluation task of an alert, along with when it runs next
+struct AlertTaskHandle {
+    handle: task::JoinHandle<()>,
+    eval_frequency: u64,
+    next_run: Arc<Mutex<DateTime<Utc>>>,
+    consecutive_timeouts: Arc<AtomicU32>,
+    eval_stats: Arc<Mutex<AlertEvalStats>>,
+}
+
+/// Evaluations run by the task of an alert, also exported as the `alert_eval_*` metrics
+#[derive(Default)]
+struct AlertEvalStats {
+    evaluations: u64,
+    failures: u64,
+    last_duration: Option<Duration>,
+    total_duration: Duration,
+}
+
+impl AlertEvalStats {
+    fn record(&mut self, id: &Ulid, duration: Duration, failed: bool) {
+        let id = id.to_string();
+        ALERT_EVAL_DURATION
+            .with_label_values(&[&id])
+            .observe(duration.as_secs_f64());
+        if failed {
+            ALERT_EVAL_ERRORS.with_label_values(&[&id]).inc();
+            self.failures += 1;
+        }
+        self.evaluations += 1;
+        self.last_duration = Some(duration);
+        self.total_duration += duration;
+    }
+
+    fn avg_duration(&self) -> Option<Duration> {
+        (self.evaluations > 0).then(|| self.total_duration / self.evaluations as u32)
+    }
+}
+
 /// A separate runtime for running all alert tasks
 #[tokio::main(flavor = "multi_thread")]
 pub async fn alert_runtime(mut rx: mpsc::Receiver<AlertTask>) -> Result<(), anyhow::Error> {
-    let mut alert_tasks = HashMap::new();
+    let mut alert_tasks: HashMap<Ulid, AlertTaskHandle> = HashMap::new();
 
     // this is the select! loop which will keep waiting for the alert task to finish or get cancelled
     while let Some(task) = rx.recv().await {
@@ -303,13 +375,43 @@ pub async fn alert_runtime(mut rx: mpsc::Receiver<AlertTask>) -> Result<(), anyh
 
                 let alert = alert.clone_box();
                 let id = *alert.get_id();
+                let eval_frequency = alert.get_eval_frequency();
+                let next_run = Arc::new(Mutex::new(Utc::now()));
+                let task_next_run = next_run.clone();
+                let consecutive_timeouts = Arc::new(AtomicU32::new(0));
+                let task_timeouts = consecutive_timeouts.clone();
+                let eval_stats = Arc::new(Mutex::new(AlertEvalStats::default()));
+                let task_eval_stats = eval_stats.clone();
                 let handle = tokio::spawn(async move {
                     let mut retry_counter = 0;
                     let mut sleep_duration = alert.get_eval_frequency();
                     loop {
-                        match alerts_utils::evaluate_alert(&*alert).await {
+                        // the window is the one the evaluation was due for, however long it waits for a slot
+                        let scheduled_at = Utc::now();
+                        let slot = acquire_alert_eval_slot().await;
+                        let started = Instant::now();
+                        let result = alerts_utils::evaluate_alert(&*alert, scheduled_at).await;
+                        drop(slot);
+                        task_eval_stats.lock().expect("lock poisoned").record(
+                            &id,
+                            started.elapsed(),
+                            result.is_err(),
+                        );
+                        match result {
                             Ok(_) => {
                                 retry_counter = 0;
+                                task_timeouts.store(0, Ordering::Relaxed);
+                            }
+                            // retrying sooner would only add load, the next evaluation runs as scheduled
+                            Err(
+                                err @ (AlertError::EvalTimeout(_)
+                                | AlertError::ScanLimitExceeded { .. }),
+                            ) => {
+                                if matches!(err, AlertError::EvalTimeout(_)) {
+                                    task_timeouts.fetch_add(1, Ordering::Relaxed);
+                                }
+                                error!("Evaluation of alert with id {id} aborted- {err}");
+                                sleep_duration = alert.get_eval_frequency();
                             }
                             Err(err) => {
                                 warn!(
@@ -328,18 +430,32 @@ pub async fn alert_runtime(mut rx: mpsc::Receiver<AlertTask>) -> Result<(), anyh
                                 }
                             }
                         }
+                        *task_next_run.lock().expect("lock poisoned") =
+                            Utc::now() + TimeDelta::minutes(sleep_duration as i64);
                         tokio::time::sleep(Duration::from_secs(sleep_duration * 60)).await;
                     }
                 });
 
                 // store the handle in the map, since it is not awaited, it will keep on running
-                alert_tasks.insert(id, handle);
+                alert_tasks.insert(
+                    id,
+                    AlertTaskHandle {
+                        handle,
+                        eval_frequency,
+                        next_run,
+                        consecutive_timeouts,
+                        eval_stats,
+                    },
+                );
             }
             AlertTask::Delete(ulid) => {
                 // check if the alert exists
-                if let Some(handle) = alert_tasks.remove(&ulid) {
+                if let Some(task) = alert_tasks.remove(&ulid) {
                     // cancel the task
-                    handle.abort();
+                    task.handle.abort();
+                    let id = ulid.to_string();
+                    let _ = ALERT_EVAL_DURATION.remove_label_values(&[&id]);
+                    let _ = ALERT_EVAL_ERRORS.remove_label_values(&[&id]);
                     trace!("Alert with id {} deleted from evaluation tasks list", ulid);
                 } else {
                     error!(
@@ -348,6 +464,32 @@ pub async fn alert_runtime(mut rx: mpsc::Receiver<AlertTask>) -> Result<(), anyh
                     );
                 }
             }
+            AlertTask::List(tx) => {
+                let tasks = alert_tasks
+                    .iter()
+                    .map(|(id, task)| {
+                        let alive = !task.handle.is_finished();
+                        let eval_stats = task.eval_stats.lock().expect("lock poisoned");
+                        ScheduledAlertTask {
+                            id: *id,
+                            eval_frequency: task.eval_frequency,
+                            next_run: alive.then(|| *task.next_run.lock().expect("lock poisoned")),
+                            alive,
+                            consecutive_timeouts: task.consecutive_timeouts.load(Ordering::Relaxed),
+                            evaluations: eval_stats.evaluations,
+                            failed_evaluations: eval_stats.failures,
+                            last_eval_duration_ms: eval_stats
+                                .last_duration
+                                .map(|duration| duration.as_millis() as u64),
+                            avg_eval_duration_ms: eval_stats
+                                .avg_duration()
+                                .map(|duration| duration.as_millis() as u64),
+                        }
+                    })
+                    .collect();
+                // the requester may have gone away already
+                let _ = tx.send(tasks);
+            }
         }
     }
     Ok(())
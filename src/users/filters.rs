@@ -16,14 +16,18 @@
  *
  */
 
+use chrono::{DateTime, TimeDelta, Utc};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tokio::sync::RwLock;
+use tracing::{info, warn};
+use ulid::Ulid;
 
 use super::TimeFilter;
 use crate::{
     metastore::metastore_traits::MetastoreObject,
+    option::Mode,
     parseable::PARSEABLE,
     rbac::{Users, map::SessionKey},
     storage::object_storage::filter_path,
@@ -32,6 +36,8 @@ use crate::{
 
 pub static FILTERS: Lazy<Filters> = Lazy::new(Filters::default);
 pub const CURRENT_FILTER_VERSION: &str = "v2";
+// the last use of a filter is persisted at most this often
+const LAST_USED_PERSIST_INTERVAL: TimeDelta = TimeDelta::hours(1);
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct Filter {
     pub version: Option<String>,
@@ -41,11 +47,71 @@ pub struct Filter {
     pub filter_id: Option<String>,
     pub query: FilterQuery,
     pub time_filter: Option<TimeFilter>,
+    /// filters with a ttl are removed once they have not been used for that many days
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_days: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<DateTime<Utc>>,
     /// all other fields are variable and can be added as needed
     #[serde(flatten)]
     pub other_fields: Option<serde_json::Map<String, Value>>,
 }
 
+impl Filter {
+    /// Creation time of the filter, from its ulid
+    pub fn created_at(&self) -> Option<DateTime<Utc>> {
+        let id = Ulid::from_string(self.filter_id.as_deref()?).ok()?;
+        Some(id.datetime().into())
+    }
+
+    /// Whether the filter went unused for longer than its ttl, counting from its creation if never used
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        let Some(ttl_days) = self.ttl_days else {
+            return false;
+        };
+
+        self.last_used
+            .or_else(|| self.created_at())
+            .is_some_and(|last_active| last_active + TimeDelta::days(ttl_days as i64) < now)
+    }
+}
+
+/// Age and usage of a saved filter
+#[derive(Debug, Serialize)]
+pub struct FilterUsage {
+    pub filter_id: Option<String>,
+    pub filter_name: String,
+    pub stream_name: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub age_days: Option<i64>,
+    pub last_used: Option<DateTime<Utc>>,
+    pub ttl_days: Option<u32>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<&Filter> for FilterUsage {
+    fn from(filter: &Filter) -> Self {
+        let created_at = filter.created_at();
+        let expires_at = filter.ttl_days.and_then(|ttl_days| {
+            filter
+                .last_used
+                .or(created_at)
+                .map(|last_active| last_active + TimeDelta::days(ttl_days as i64))
+        });
+
+        Self {
+            filter_id: filter.filter_id.clone(),
+            filter_name: filter.filter_name.clone(),
+            stream_name: filter.stream_name.clone(),
+            created_at,
+            age_days: created_at.map(|created_at| (Utc::now() - created_at).num_days()),
+            last_used: filter.last_used,
+            ttl_days: filter.ttl_days,
+            expires_at,
+        }
+    }
+}
+
 impl MetastoreObject for Filter {
     fn get_object_path(&self) -> String {
         filter_path(
@@ -115,12 +181,52 @@ impl Filters {
     pub async fn load(&self) -> anyhow::Result<()> {
         let all_filters = PARSEABLE.metastore.get_filters().await.unwrap_or_default();
 
+        let now = Utc::now();
+        let (expired, all_filters): (Vec<_>, Vec<_>) = all_filters
+            .into_iter()
+            .partition(|filter| filter.is_expired(now));
+        // expired filters are removed from storage by the nodes serving them
+        if matches!(
+            PARSEABLE.options.mode,
+            Mode::All | Mode::Query | Mode::Prism
+        ) {
+            for filter in &expired {
+                match PARSEABLE.metastore.delete_filter(filter).await {
+                    Ok(_) => info!(
+                        "Removed expired filter {} of stream {}",
+                        filter.filter_name, filter.stream_name
+                    ),
+                    Err(err) => warn!(
+                        "Failed to remove expired filter {}: {}",
+                        filter.filter_name,
+                        err.to_detail()
+                    ),
+                }
+            }
+        }
+
         let mut s = self.0.write().await;
         s.extend(all_filters);
 
         Ok(())
     }
 
+    /// Records the use of a filter, returns the filter when its last use is due to be persisted
+    pub async fn mark_used(&self, filter_id: &str) -> Option<Filter> {
+        let mut s = self.0.write().await;
+        let filter = s
+            .iter_mut()
+            .find(|f| f.filter_id.as_deref() == Some(filter_id))?;
+
+        let now = Utc::now();
+        let persist = filter
+            .last_used
+            .is_none_or(|last_used| now - last_used >= LAST_USED_PERSIST_INTERVAL);
+        filter.last_used = Some(now);
+
+        persist.then(|| filter.clone())
+    }
+
     pub async fn update(&self, filter: &Filter) {
         let mut s = self.0.write().await;
         s.retain(|f| f.filter_id != filter.filter_id);
@@ -176,6 +282,15 @@ impl Filters {
         }
         filters
     }
+
+    /// Age and usage of the filters visible to the session
+    pub async fn list_usage(&self, key: &SessionKey) -> Vec<FilterUsage> {
+        self.list_filters(key)
+            .await
+            .iter()
+            .map(FilterUsage::from)
+            .collect()
+    }
 }
 
 pub fn migrate_v1_v2(mut filter_meta: Value) -> Value {
@@ -24,9 +24,17 @@ use actix_web::{
 };
 use actix_web_httpauth::extractors::basic::BasicAuth;
 
-use crate::rbac::map::SessionKey;
+use crate::{
+    handlers::http::modal::ssl_acceptor::ClientCertIdentity, parseable::PARSEABLE,
+    rbac::map::SessionKey,
+};
 
 pub fn extract_session_key(req: &mut ServiceRequest) -> Result<SessionKey, Error> {
+    let client_cert = req.conn_data::<ClientCertIdentity>();
+    if PARSEABLE.options.tls_require_client_cert && client_cert.is_none() {
+        return Err(ErrorUnauthorized("Client certificate required"));
+    }
+
     // Extract username and password from the request using basic auth extractor.
     let creds = req.extract::<BasicAuth>().into_inner();
     let basic = creds.map(|creds| {
@@ -43,12 +51,19 @@ pub fn extract_session_key(req: &mut ServiceRequest) -> Result<SessionKey, Error
         let ulid = ulid::Ulid::from_string(cookie.value())
             .map_err(|_| ErrorUnprocessableEntity("Cookie is tampered with or invalid"))?;
         Ok(SessionKey::SessionId(ulid))
+    } else if let Some(identity) = client_cert {
+        client_cert_session(identity)
     } else {
         Err(ErrorUnauthorized("No authentication method supplied"))
     }
 }
 
 pub fn extract_session_key_from_req(req: &HttpRequest) -> Result<SessionKey, Error> {
+    let client_cert = req.conn_data::<ClientCertIdentity>();
+    if PARSEABLE.options.tls_require_client_cert && client_cert.is_none() {
+        return Err(ErrorUnauthorized("Client certificate required"));
+    }
+
     // Extract username and password from the request using basic auth extractor.
     let creds = BasicAuth::extract(req).into_inner();
     let basic = creds.map(|creds| {
@@ -65,7 +80,17 @@ pub fn extract_session_key_from_req(req: &HttpRequest) -> Result<SessionKey, Err
         let ulid = ulid::Ulid::from_string(cookie.value())
             .map_err(|_| ErrorUnprocessableEntity("Cookie is tampered with or invalid"))?;
         Ok(SessionKey::SessionId(ulid))
+    } else if let Some(identity) = client_cert {
+        client_cert_session(identity)
     } else {
         Err(ErrorUnauthorized("No authentication method supplied"))
     }
 }
+
+fn client_cert_session(identity: &ClientCertIdentity) -> Result<SessionKey, Error> {
+    let username = PARSEABLE
+        .options
+        .client_cert_username(&identity.common_name)
+        .ok_or_else(|| ErrorUnauthorized("Client certificate is not mapped to a user"))?;
+    Ok(SessionKey::ClientCert { username })
+}
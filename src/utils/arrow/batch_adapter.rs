@@ -19,10 +19,13 @@
 use datafusion::arrow::array::ArrayRef;
 use datafusion::arrow::array::new_null_array;
 use datafusion::arrow::datatypes::Schema;
+use datafusion::arrow::error::ArrowError;
 use datafusion::arrow::record_batch::RecordBatch;
 
 use std::sync::Arc;
 
+use crate::storage::type_widening::widen_column;
+
 // This function takes a new event's record batch and the
 // current schema of the log stream. It returns a new record
 // with nulls added to the fields that don't exist
@@ -30,19 +33,29 @@ use std::sync::Arc;
 // log stream schema.
 // This is necessary because all the record batches in a log
 // stream need to have all the fields.
-pub fn adapt_batch(table_schema: &Schema, batch: &RecordBatch) -> RecordBatch {
+// Columns widened since the batch was written are cast to their
+// new type, any other type mismatch is an error.
+pub fn adapt_batch(table_schema: &Schema, batch: &RecordBatch) -> Result<RecordBatch, ArrowError> {
     let batch_schema = &*batch.schema();
     let batch_cols = batch.columns().to_vec();
 
     let mut cols: Vec<ArrayRef> = Vec::with_capacity(table_schema.fields().len());
     for table_field in table_schema.fields() {
-        if let Some((batch_idx, _)) = batch_schema.column_with_name(table_field.name().as_str()) {
-            cols.push(Arc::clone(&batch_cols[batch_idx]));
+        if let Some((batch_idx, batch_field)) =
+            batch_schema.column_with_name(table_field.name().as_str())
+        {
+            let column = &batch_cols[batch_idx];
+            // only integer columns widened to Float64 since the batch was written differ in type
+            if batch_field.data_type() != table_field.data_type() {
+                cols.push(widen_column(column, table_field.data_type())?);
+            } else {
+                cols.push(Arc::clone(column));
+            }
         } else {
             cols.push(new_null_array(table_field.data_type(), batch.num_rows()))
         }
     }
 
     let merged_schema = Arc::new(table_schema.clone());
-    RecordBatch::try_new(merged_schema, cols).unwrap()
+    RecordBatch::try_new(merged_schema, cols)
 }
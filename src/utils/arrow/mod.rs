@@ -159,6 +159,79 @@ pub fn reverse(rb: &RecordBatch) -> RecordBatch {
     RecordBatch::try_new(rb.schema(), arrays).unwrap()
 }
 
+/// Serializes a schema into the Arrow IPC stream format, without any record batches
+pub fn schema_to_ipc(schema: &Schema) -> Result<Vec<u8>, ArrowError> {
+    let mut writer = arrow_ipc::writer::StreamWriter::try_new(Vec::new(), schema)?;
+    writer.finish()?;
+    writer.into_inner()
+}
+
+/// Derives a `CREATE TABLE` statement for `table` from the fields of a schema
+pub fn schema_to_sql_ddl(table: &str, schema: &Schema) -> String {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let not_null = if field.is_nullable() { "" } else { " NOT NULL" };
+            format!(
+                "  {} {}{not_null}",
+                quote_identifier(field.name()),
+                sql_type(field.data_type())
+            )
+        })
+        .join(",\n");
+
+    format!("CREATE TABLE {} (\n{columns}\n);", quote_identifier(table))
+}
+
+/// Quotes an SQL identifier, escaping embedded double quotes
+pub fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn sql_type(data_type: &DataType) -> String {
+    match data_type {
+        DataType::Boolean => "BOOLEAN".to_owned(),
+        DataType::Int8 | DataType::UInt8 => "TINYINT".to_owned(),
+        DataType::Int16 | DataType::UInt16 => "SMALLINT".to_owned(),
+        DataType::Int32 | DataType::UInt32 => "INT".to_owned(),
+        DataType::Int64 | DataType::UInt64 => "BIGINT".to_owned(),
+        DataType::Float16 | DataType::Float32 => "REAL".to_owned(),
+        DataType::Float64 => "DOUBLE".to_owned(),
+        DataType::Decimal128(precision, scale) | DataType::Decimal256(precision, scale) => {
+            format!("DECIMAL({precision}, {scale})")
+        }
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => "VARCHAR".to_owned(),
+        DataType::Binary
+        | DataType::LargeBinary
+        | DataType::BinaryView
+        | DataType::FixedSizeBinary(_) => "VARBINARY".to_owned(),
+        DataType::Date32 | DataType::Date64 => "DATE".to_owned(),
+        DataType::Time32(_) | DataType::Time64(_) => "TIME".to_owned(),
+        DataType::Timestamp(_, None) => "TIMESTAMP".to_owned(),
+        DataType::Timestamp(_, Some(_)) => "TIMESTAMP WITH TIME ZONE".to_owned(),
+        DataType::Interval(_) | DataType::Duration(_) => "INTERVAL".to_owned(),
+        DataType::List(field)
+        | DataType::LargeList(field)
+        | DataType::ListView(field)
+        | DataType::LargeListView(field)
+        | DataType::FixedSizeList(field, _) => format!("{}[]", sql_type(field.data_type())),
+        DataType::Struct(fields) => format!(
+            "STRUCT({})",
+            fields
+                .iter()
+                .map(|field| format!(
+                    "{} {}",
+                    quote_identifier(field.name()),
+                    sql_type(field.data_type())
+                ))
+                .join(", ")
+        ),
+        DataType::Dictionary(_, value) => sql_type(value),
+        other => other.to_string().to_uppercase(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
@@ -196,4 +269,36 @@ mod tests {
         assert_eq!(array.len(), 0);
         assert!(array.is_empty());
     }
+
+    #[test]
+    fn schema_to_sql_ddl_maps_types() {
+        let schema = Schema::new(vec![
+            Field::new(
+                DEFAULT_TIMESTAMP_KEY,
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+            Field::new("level", DataType::Utf8, true),
+            Field::new(
+                "tags",
+                DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+                true,
+            ),
+            Field::new("latency", DataType::Float64, true),
+        ]);
+
+        assert_eq!(
+            schema_to_sql_ddl("app", &schema),
+            "CREATE TABLE \"app\" (\n  \"p_timestamp\" TIMESTAMP NOT NULL,\n  \"level\" VARCHAR,\n  \"tags\" VARCHAR[],\n  \"latency\" DOUBLE\n);"
+        );
+    }
+
+    #[test]
+    fn schema_to_ipc_round_trips() {
+        let schema = Schema::new(vec![Field::new("level", DataType::Utf8, true)]);
+        let bytes = schema_to_ipc(&schema).unwrap();
+
+        let reader = arrow_ipc::reader::StreamReader::try_new(bytes.as_slice(), None).unwrap();
+        assert_eq!(reader.schema().as_ref(), &schema);
+    }
 }
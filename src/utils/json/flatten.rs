@@ -20,7 +20,7 @@ use std::collections::BTreeMap;
 use std::num::NonZeroU32;
 use std::sync::Mutex;
 
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
 use serde_json::map::Map;
 use serde_json::value::Value;
 
@@ -163,7 +163,9 @@ pub fn validate_time_partition(
 
     let limit_days = time_partition_limit.map_or(30, |days| days.get() as i64);
 
-    let Some(timestamp_value) = value.get(partition_key) else {
+    let Some(timestamp_value) =
+        time_partition_path(value, partition_key).and_then(|path| get_path(value, &path))
+    else {
         return Err(JsonFlattenError::FieldNotPartOfLog(
             partition_key.to_owned(),
         ));
@@ -217,6 +219,92 @@ pub fn validate_time_partition(
     }
 }
 
+// Resolves the time partition column, either flattened or as a dotted path, to the path of keys
+// in the unflattened object, e.g. both `event_time` and `event.time` resolve to `["event", "time"]`
+// in `{"event": {"time": "..."}}`
+pub fn time_partition_path(value: &Map<String, Value>, column: &str) -> Option<Vec<String>> {
+    if value.contains_key(column) {
+        return Some(vec![column.to_owned()]);
+    }
+
+    value.iter().find_map(|(key, nested)| {
+        let rest = column
+            .strip_prefix(key.as_str())?
+            .strip_prefix(['_', '.'])?;
+        let mut path = time_partition_path(nested.as_object()?, rest)?;
+        path.insert(0, key.clone());
+        Some(path)
+    })
+}
+
+fn get_path<'a>(value: &'a Map<String, Value>, path: &[String]) -> Option<&'a Value> {
+    let (last, parents) = path.split_last()?;
+    let mut map = value;
+    for key in parents {
+        map = map.get(key)?.as_object()?;
+    }
+    map.get(last)
+}
+
+fn get_path_mut<'a>(value: &'a mut Map<String, Value>, path: &[String]) -> Option<&'a mut Value> {
+    let (last, parents) = path.split_last()?;
+    let mut map = value;
+    for key in parents {
+        map = map.get_mut(key)?.as_object_mut()?;
+    }
+    map.get_mut(last)
+}
+
+// Parses the time partition field of every event with the stream's `time_partition_format`
+// and rewrites it as an RFC3339 timestamp in UTC, so that it can be validated and partitioned on
+pub fn normalize_time_partition(
+    value: &mut Value,
+    time_partition: &str,
+    time_partition_format: &str,
+) -> Result<(), JsonFlattenError> {
+    match value {
+        Value::Object(map) => {
+            let path = time_partition_path(map, time_partition).unwrap_or_default();
+            let Some(timestamp) = get_path_mut(map, &path) else {
+                return Err(JsonFlattenError::FieldNotPartOfLog(
+                    time_partition.to_owned(),
+                ));
+            };
+            let raw = match timestamp {
+                Value::String(s) => s.clone(),
+                Value::Number(n) => n.to_string(),
+                _ => return Err(JsonFlattenError::FieldNotString(time_partition.to_owned())),
+            };
+            let parsed = parse_with_format(&raw, time_partition_format).ok_or_else(|| {
+                JsonFlattenError::InvalidDatetimeFormat(time_partition.to_owned())
+            })?;
+            *timestamp = Value::String(parsed.to_rfc3339_opts(SecondsFormat::Millis, true));
+        }
+        Value::Array(arr) => {
+            for value in arr {
+                normalize_time_partition(value, time_partition, time_partition_format)?;
+            }
+        }
+        _ => return Err(JsonFlattenError::CannotFlatten),
+    }
+
+    Ok(())
+}
+
+// Timestamps without an offset are taken to be in UTC, dates without a time to be at midnight
+fn parse_with_format(raw: &str, format: &str) -> Option<DateTime<Utc>> {
+    if let Ok(timestamp) = DateTime::parse_from_str(raw, format) {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+    if let Ok(timestamp) = NaiveDateTime::parse_from_str(raw, format) {
+        return Some(timestamp.and_utc());
+    }
+    NaiveDate::parse_from_str(raw, format)
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|timestamp| timestamp.and_utc())
+}
+
 // Flattens a nested JSON Object/Map into another target Map
 fn flatten_object(
     output_map: &mut Map<String, Value>,
@@ -395,7 +483,9 @@ pub fn convert_to_array(flattened: Vec<Value>) -> Result<Value, JsonFlattenError
 
 #[cfg(test)]
 mod tests {
-    use crate::utils::json::flatten::{flatten_array_objects, generic_flattening};
+    use crate::utils::json::flatten::{
+        flatten_array_objects, generic_flattening, normalize_time_partition, time_partition_path,
+    };
 
     use super::{JsonFlattenError, flatten};
     use serde_json::{Map, Value, json};
@@ -660,4 +750,72 @@ mod tests {
         let expected = vec![json!({"a":{"b":{"e":"a"}}}), json!({"a":{"b":{"e":"b"}}})];
         assert_eq!(generic_flattening(&value).unwrap(), expected);
     }
+
+    #[test]
+    fn normalize_nested_time_partition() {
+        let mut value = json!([
+            {"event": {"time": "15/05/2025 15:30:00", "id": 1}},
+            {"event": {"time": "2025-05-15 17:30:00 +0200", "id": 2}}
+        ]);
+        normalize_time_partition(&mut value, "event_time", "%d/%m/%Y %H:%M:%S").unwrap_err();
+
+        let mut value = json!([
+            {"event": {"time": "15/05/2025 15:30:00", "id": 1}},
+            {"event": {"time": "16/05/2025 09:00:00", "id": 2}}
+        ]);
+        normalize_time_partition(&mut value, "event_time", "%d/%m/%Y %H:%M:%S").unwrap();
+        assert_eq!(
+            value,
+            json!([
+                {"event": {"time": "2025-05-15T15:30:00.000Z", "id": 1}},
+                {"event": {"time": "2025-05-16T09:00:00.000Z", "id": 2}}
+            ])
+        );
+
+        let mut value = json!({"event": {"id": 1}});
+        assert!(matches!(
+            normalize_time_partition(&mut value, "event_time", "%s").unwrap_err(),
+            JsonFlattenError::FieldNotPartOfLog(_)
+        ));
+
+        let mut value = json!({"ts": 1747323000});
+        normalize_time_partition(&mut value, "ts", "%s").unwrap();
+        assert_eq!(value, json!({"ts": "2025-05-15T15:30:00.000Z"}));
+    }
+
+    #[test]
+    fn dotted_time_partition_path() {
+        let value = json!({"event": {"time": "2025-05-15T15:30:00.000Z", "id": 1}});
+        let map = value.as_object().unwrap();
+        assert_eq!(
+            time_partition_path(map, "event.time").unwrap(),
+            vec!["event".to_string(), "time".to_string()]
+        );
+        assert_eq!(
+            time_partition_path(map, "event_time").unwrap(),
+            vec!["event".to_string(), "time".to_string()]
+        );
+        assert!(time_partition_path(map, "event.id.time").is_none());
+
+        let mut value = json!({"event": {"time": "15/05/2025 15:30:00", "id": 1}});
+        normalize_time_partition(&mut value, "event.time", "%d/%m/%Y %H:%M:%S").unwrap();
+        assert_eq!(
+            value,
+            json!({"event": {"time": "2025-05-15T15:30:00.000Z", "id": 1}})
+        );
+
+        let mut value = json!({"event": {"id": 1}});
+        assert!(matches!(
+            flatten(
+                &mut value,
+                "_",
+                Some(&"event.time".to_string()),
+                None,
+                None,
+                true
+            )
+            .unwrap_err(),
+            JsonFlattenError::FieldNotPartOfLog(_)
+        ));
+    }
 }
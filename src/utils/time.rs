@@ -16,7 +16,10 @@
  *
  */
 
-use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Timelike, Utc};
+use chrono::{
+    DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Timelike, Utc,
+};
+use chrono_tz::Tz;
 
 #[derive(Debug, thiserror::Error)]
 pub enum TimeParseError {
@@ -290,6 +293,35 @@ impl TimeRange {
     pub fn contains(&self, time: DateTime<Utc>) -> bool {
         self.start <= time && self.end > time
     }
+
+    /// The range covered by `date` in the time zone `tz`
+    pub fn local_day(date: NaiveDate, tz: Tz) -> Self {
+        let end = date.succ_opt().unwrap_or(date);
+        Self {
+            start: start_of_day(date, tz),
+            end: start_of_day(end, tz),
+        }
+    }
+}
+
+/// Instant at which `date` starts in the time zone `tz`
+pub fn start_of_day(date: NaiveDate, tz: Tz) -> DateTime<Utc> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    // a DST transition can skip midnight, the day then starts at the end of the gap
+    (0..=2)
+        .find_map(|hours| {
+            tz.from_local_datetime(&(midnight + TimeDelta::hours(hours)))
+                .earliest()
+        })
+        .map(|start| start.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&midnight))
+}
+
+/// Parses an IANA time zone name, e.g. `Asia/Kolkata`
+pub fn parse_timezone(tz: &str) -> Result<Tz, String> {
+    tz.parse::<Tz>().map_err(|_| {
+        format!("Invalid time zone \"{tz}\", it should be an IANA name like Europe/Berlin")
+    })
 }
 
 pub fn truncate_to_minute(dt: DateTime<Utc>) -> DateTime<Utc> {
@@ -632,4 +664,27 @@ mod tests {
         assert_eq!(range.start.hour(), 12);
         assert_eq!(range.end.hour(), 13);
     }
+
+    #[test]
+    fn local_day_is_offset_from_utc() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+
+        let range = TimeRange::local_day(date, parse_timezone("Asia/Kolkata").unwrap());
+        assert_eq!(
+            range.start,
+            Utc.with_ymd_and_hms(2024, 5, 31, 18, 30, 0).unwrap()
+        );
+        assert_eq!(
+            range.end,
+            Utc.with_ymd_and_hms(2024, 6, 1, 18, 30, 0).unwrap()
+        );
+
+        let range = TimeRange::local_day(date, parse_timezone("UTC").unwrap());
+        assert_eq!(
+            range.start,
+            Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+        );
+
+        assert!(parse_timezone("Mars/Olympus").is_err());
+    }
 }
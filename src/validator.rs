@@ -20,6 +20,7 @@ use std::collections::HashSet;
 
 use error::HotTierValidationError;
 use once_cell::sync::Lazy;
+use regex::Regex;
 
 use self::error::{StreamNameValidationError, UsernameValidationError};
 use crate::hottier::MIN_STREAM_HOT_TIER_SIZE_BYTES;
@@ -70,6 +71,34 @@ pub fn stream_name(
     Ok(())
 }
 
+/// Checks a stream created by a user against the naming convention of the deployment,
+/// the names matching `pattern` and none of the `reserved` ones
+pub fn stream_name_convention(
+    stream_name: &str,
+    pattern: Option<&Regex>,
+    reserved: &[String],
+) -> Result<(), StreamNameValidationError> {
+    if reserved
+        .iter()
+        .any(|name| name.trim().eq_ignore_ascii_case(stream_name))
+    {
+        return Err(StreamNameValidationError::ReservedName(
+            stream_name.to_owned(),
+        ));
+    }
+
+    if let Some(pattern) = pattern
+        && !pattern.is_match(stream_name)
+    {
+        return Err(StreamNameValidationError::PatternMismatch {
+            name: stream_name.to_owned(),
+            pattern: pattern.as_str().to_owned(),
+        });
+    }
+
+    Ok(())
+}
+
 static RESERVED_NAMES: Lazy<HashSet<&'static str>> = Lazy::new(|| {
     [
         "admin",
@@ -178,6 +207,10 @@ pub mod error {
             "The stream {0} is reserved for internal use and cannot be used for user defined streams"
         )]
         InternalStream(String),
+        #[error("Stream name {0} is reserved")]
+        ReservedName(String),
+        #[error("Stream name {name} doesn't follow the naming convention {pattern}")]
+        PatternMismatch { name: String, pattern: String },
     }
 
     #[derive(Debug, thiserror::Error)]
@@ -220,3 +253,36 @@ pub mod error {
         NotFound(String),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::option::validation::stream_name_pattern;
+
+    use super::*;
+
+    #[test]
+    fn names_following_the_convention_are_accepted() {
+        let pattern = stream_name_pattern("[a-z]+-(dev|prod)-[a-z0-9-]+").unwrap();
+        let reserved = vec!["audit".to_owned()];
+
+        assert!(stream_name_convention("payments-prod-api", Some(&pattern), &reserved).is_ok());
+        assert!(stream_name_convention("anything_goes", None, &[]).is_ok());
+    }
+
+    #[test]
+    fn names_breaking_the_convention_are_rejected() {
+        let pattern = stream_name_pattern("[a-z]+-(dev|prod)-[a-z0-9-]+").unwrap();
+        let reserved = vec!["audit".to_owned()];
+
+        assert!(matches!(
+            stream_name_convention("payments-staging-api", Some(&pattern), &reserved),
+            Err(StreamNameValidationError::PatternMismatch { .. })
+        ));
+        // the pattern applies to the whole name
+        assert!(stream_name_convention("x-payments-prod-api!", Some(&pattern), &reserved).is_err());
+        assert!(matches!(
+            stream_name_convention("Audit", None, &reserved),
+            Err(StreamNameValidationError::ReservedName(_))
+        ));
+    }
+}